@@ -1,8 +1,10 @@
 use criterion::*;
 use image::RgbImage;
-use spectro_cam_rs::config::{Linearize, ReferenceConfig, SpectrometerConfig};
-use spectro_cam_rs::spectrum::{SpectrumCalculator, SpectrumContainer, SpectrumRgb};
-use spectro_cam_rs::tungsten_halogen::reference_from_filament_temp;
+use spectro_cam_rs::config::{
+    Binning, IntensityUnit, Linearize, ReferenceConfig, SmoothingMethod, SpectrometerConfig,
+};
+use spectro_cam_rs::spectrum::{SpectrumCalculator, SpectrumProcessor, SpectrumRgb};
+use spectro_cam_rs::tungsten_halogen::{reference_from_filament_temp, GlassEnvelope};
 
 fn spectrum_calculator_bench(c: &mut Criterion) {
     let window = RgbImage::new(1000, 20);
@@ -10,28 +12,36 @@ fn spectrum_calculator_bench(c: &mut Criterion) {
     c.bench_with_input(
         BenchmarkId::new("process_window", "window_1000_20"),
         &window,
-        |b, w| b.iter(|| SpectrumCalculator::process_window(w)),
+        |b, w| b.iter(|| SpectrumCalculator::process_window(w, &Binning::default())),
     );
 }
 
 fn spectrum_buffer_bench(c: &mut Criterion) {
-    let (_tx, rx) = flume::unbounded();
-    let mut sc = SpectrumContainer::new(rx);
+    let mut sp = SpectrumProcessor::new();
 
     c.bench_function("update_spectrum_default", |b| {
         let config = SpectrometerConfig::default();
         b.iter(|| {
             let s = SpectrumRgb::from_element(1000, 0.5);
-            sc.update_spectrum(black_box(s), &config);
+            sp.process(black_box(s), &config);
         });
     });
 
     c.bench_function("update_spectrum_filter", |b| {
         let mut config = SpectrometerConfig::default();
-        config.postprocessing_config.spectrum_filter_active = true;
+        config.postprocessing_config.smoothing_method = SmoothingMethod::LowPass;
         b.iter(|| {
             let s = SpectrumRgb::from_element(1000, 0.5);
-            sc.update_spectrum(black_box(s), &config);
+            sp.process(black_box(s), &config);
+        });
+    });
+
+    c.bench_function("update_spectrum_savitzky_golay", |b| {
+        let mut config = SpectrometerConfig::default();
+        config.postprocessing_config.smoothing_method = SmoothingMethod::SavitzkyGolay;
+        b.iter(|| {
+            let s = SpectrumRgb::from_element(1000, 0.5);
+            sp.process(black_box(s), &config);
         });
     });
 
@@ -40,35 +50,42 @@ fn spectrum_buffer_bench(c: &mut Criterion) {
         config.spectrum_calibration.linearize = Linearize::Rec601;
         b.iter(|| {
             let s = SpectrumRgb::from_element(1000, 0.5);
-            sc.update_spectrum(black_box(s), &config);
+            sp.process(black_box(s), &config);
         });
     });
 
-    sc.clear_buffer();
-    sc.update_spectrum(
-        SpectrumRgb::from_fn(1000, |_, j| (j % 20) as f32),
-        &SpectrometerConfig::default(),
-    );
+    sp.clear_buffer();
+    let frame = sp
+        .process(
+            SpectrumRgb::from_fn(1000, |_, j| (j % 20) as f32),
+            &SpectrometerConfig::default(),
+        )
+        .clone();
 
     c.bench_function("spectrum_to_peaks", |b| {
         let config = SpectrometerConfig::default();
         b.iter(|| {
-            sc.spectrum_to_peaks_and_dips(black_box(true), &config);
+            frame.spectrum_to_peaks_and_dips(black_box(true), &config);
         });
     });
 
     c.bench_function("spectrum_to_dips", |b| {
         let config = SpectrometerConfig::default();
         b.iter(|| {
-            sc.spectrum_to_peaks_and_dips(black_box(false), &config);
+            frame.spectrum_to_peaks_and_dips(black_box(false), &config);
         });
     });
 }
 
 fn config_bench(c: &mut Criterion) {
     let rc = ReferenceConfig {
-        reference: Some(reference_from_filament_temp(2500)),
+        reference: Some(reference_from_filament_temp(
+            2500,
+            true,
+            GlassEnvelope::None,
+        )),
         scale: 1.,
+        unit: IntensityUnit::Relative,
     };
 
     c.bench_function("get_value_at_wavelength", |b| {
@@ -0,0 +1,172 @@
+use std::fmt::{Display, Formatter};
+
+/// Where the frames of an acquisition come from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum AcquisitionSource {
+    Camera,
+    Synthetic,
+    StillImage,
+    Network,
+    /// Raw spectra of a recording, see [crate::recording].
+    Replay,
+}
+
+impl Display for AcquisitionSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AcquisitionSource::Camera => write!(f, "Camera"),
+            AcquisitionSource::Synthetic => write!(f, "Demo"),
+            AcquisitionSource::StillImage => write!(f, "Image"),
+            AcquisitionSource::Network => write!(f, "Network Camera"),
+            AcquisitionSource::Replay => write!(f, "Replay"),
+        }
+    }
+}
+
+/// Events changing the [AcquisitionState], requested by the GUI or reported by the camera
+/// thread.
+#[derive(Debug, PartialEq, Clone)]
+pub enum AcquisitionEvent {
+    /// The GUI requests frames from the given source.
+    Start(AcquisitionSource),
+    /// The camera thread opened the source and delivers frames.
+    Started,
+    /// The GUI requests the acquisition to end.
+    Stop,
+    /// The camera thread closed the source after a stop request.
+    Stopped,
+    /// The camera thread could not open the source or lost it.
+    Failed(String),
+    /// The GUI retries a lost source later instead of giving up.
+    Reconnect,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum AcquisitionState {
+    #[default]
+    Idle,
+    Starting(AcquisitionSource),
+    Streaming(AcquisitionSource),
+    /// Waiting to open the lost source again.
+    Reconnecting(AcquisitionSource),
+    Stopping,
+    Error(String),
+}
+
+impl Display for AcquisitionState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AcquisitionState::Idle => write!(f, "Idle"),
+            AcquisitionState::Starting(source) => write!(f, "Starting {}", source),
+            AcquisitionState::Streaming(source) => write!(f, "{} Running", source),
+            AcquisitionState::Reconnecting(source) => write!(f, "Reconnecting {}", source),
+            AcquisitionState::Stopping => write!(f, "Stopping"),
+            AcquisitionState::Error(_) => write!(f, "Error"),
+        }
+    }
+}
+
+impl AcquisitionState {
+    /// State after `event`, or an error if the event is not allowed in this state.
+    ///
+    /// Reports of the camera thread about a source which is being stopped anyway are
+    /// swallowed, the thread confirms the stop with [AcquisitionEvent::Stopped].
+    pub fn transition(&self, event: AcquisitionEvent) -> Result<AcquisitionState, String> {
+        use AcquisitionEvent as E;
+        use AcquisitionState as S;
+        match (self, event) {
+            (S::Idle | S::Error(_), E::Start(source)) => Ok(S::Starting(source)),
+            (S::Reconnecting(lost), E::Start(source)) if *lost == source => Ok(S::Starting(source)),
+            (S::Starting(source), E::Started) => Ok(S::Streaming(*source)),
+            (S::Starting(_) | S::Streaming(_) | S::Reconnecting(_), E::Stop) => Ok(S::Stopping),
+            (S::Starting(_) | S::Streaming(_) | S::Reconnecting(_), E::Failed(e)) => {
+                Ok(S::Error(e))
+            }
+            (S::Starting(source) | S::Streaming(source), E::Reconnect) => {
+                Ok(S::Reconnecting(*source))
+            }
+            (S::Stopping, E::Started | E::Failed(_)) => Ok(S::Stopping),
+            (S::Stopping, E::Stopped) => Ok(S::Idle),
+            (state, event) => Err(format!("{:?} is not allowed in state {:?}", event, state)),
+        }
+    }
+
+    /// Source of the current acquisition, while frames are requested.
+    pub fn source(&self) -> Option<AcquisitionSource> {
+        match self {
+            AcquisitionState::Starting(source)
+            | AcquisitionState::Streaming(source)
+            | AcquisitionState::Reconnecting(source) => Some(*source),
+            _ => None,
+        }
+    }
+
+    /// Whether frames are requested, which makes the windows available.
+    pub fn is_running(&self) -> bool {
+        self.source().is_some()
+    }
+
+    pub fn can_start(&self) -> bool {
+        matches!(self, AcquisitionState::Idle | AcquisitionState::Error(_))
+    }
+
+    pub fn can_stop(&self) -> bool {
+        self.is_running()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use AcquisitionEvent as E;
+    use AcquisitionSource::{Camera, StillImage, Synthetic};
+    use AcquisitionState as S;
+
+    #[test]
+    fn acquisition_transition() {
+        let error = || S::Error("lost".to_string());
+        let allowed = [
+            (S::Idle, E::Start(Camera), S::Starting(Camera)),
+            (error(), E::Start(Synthetic), S::Starting(Synthetic)),
+            (S::Starting(Camera), E::Started, S::Streaming(Camera)),
+            (S::Starting(Camera), E::Failed("lost".to_string()), error()),
+            (S::Streaming(StillImage), E::Stop, S::Stopping),
+            (S::Stopping, E::Started, S::Stopping),
+            (S::Stopping, E::Failed("lost".to_string()), S::Stopping),
+            (S::Stopping, E::Stopped, S::Idle),
+            (S::Streaming(Camera), E::Reconnect, S::Reconnecting(Camera)),
+            (
+                S::Reconnecting(Camera),
+                E::Start(Camera),
+                S::Starting(Camera),
+            ),
+            (S::Starting(Camera), E::Reconnect, S::Reconnecting(Camera)),
+            (
+                S::Reconnecting(Camera),
+                E::Failed("lost".to_string()),
+                error(),
+            ),
+            (S::Reconnecting(Camera), E::Stop, S::Stopping),
+        ];
+        for (state, event, expected) in allowed {
+            assert_eq!(state.transition(event), Ok(expected));
+        }
+
+        let forbidden = [
+            (S::Idle, E::Stop),
+            (S::Idle, E::Started),
+            (error(), E::Stopped),
+            (S::Streaming(Camera), E::Start(Synthetic)),
+            (S::Stopping, E::Start(Camera)),
+            (S::Reconnecting(Camera), E::Start(Synthetic)),
+            (error(), E::Reconnect),
+        ];
+        for (state, event) in forbidden {
+            assert!(state.transition(event).is_err());
+        }
+
+        assert!(S::Starting(Camera).can_stop());
+        assert!(!S::Stopping.can_start() && !S::Stopping.is_running());
+        assert_eq!(S::Streaming(StillImage).source(), Some(StillImage));
+    }
+}
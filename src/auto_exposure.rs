@@ -0,0 +1,76 @@
+use crate::frame_settling::SETTLE_FRAMES;
+use std::ops::RangeInclusive;
+
+/// Fill level of the brightest pixel in the window the exposure is adjusted to.
+pub const TARGET_FILL: f32 = 0.85;
+/// Deviation from the target fill level which is accepted without adjustment.
+const TOLERANCE: f32 = 0.05;
+/// Largest factor the exposure is increased by in one step.
+const MAX_STEP: f32 = 4.;
+
+/// Closed-loop exposure control keeping the brightest pixel of the window near [TARGET_FILL].
+#[derive(Debug, Default)]
+pub struct AutoExposure {
+    settling: usize,
+}
+
+impl AutoExposure {
+    /// New exposure for a frame whose window maximum is `peak`, if it has to change.
+    ///
+    /// Saturated frames halve the exposure, as the true peak is unknown. Otherwise the
+    /// exposure is scaled proportionally, assuming a linear sensor response.
+    pub fn update(&mut self, peak: u8, exposure: i32, range: RangeInclusive<i32>) -> Option<i32> {
+        if self.settling > 0 {
+            self.settling -= 1;
+            return None;
+        }
+
+        let fill = peak as f32 / u8::MAX as f32;
+        let factor = if peak == u8::MAX {
+            0.5
+        } else if (fill - TARGET_FILL).abs() > TOLERANCE {
+            (TARGET_FILL / fill.max(f32::EPSILON)).min(MAX_STEP)
+        } else {
+            return None;
+        };
+
+        let mut new_exposure = (exposure as f32 * factor).round() as i32;
+        // Small exposure values would never change by scaling alone
+        if new_exposure == exposure {
+            new_exposure += if factor > 1. { 1 } else { -1 };
+        }
+        let new_exposure = new_exposure.clamp(*range.start(), *range.end());
+        if new_exposure == exposure {
+            None
+        } else {
+            self.settling = SETTLE_FRAMES;
+            Some(new_exposure)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_exposure() {
+        let mut ae = AutoExposure::default();
+        assert_eq!(ae.update(255, 100, 1..=1000), Some(50));
+        // Waiting for the camera to apply the exposure
+        for _ in 0..SETTLE_FRAMES {
+            assert_eq!(ae.update(255, 50, 1..=1000), None);
+        }
+        assert_eq!(ae.update(108, 50, 1..=1000), Some(100));
+
+        let mut ae = AutoExposure::default();
+        assert_eq!(ae.update(217, 100, 1..=1000), None);
+        assert_eq!(ae.update(0, 100, 1..=1000), Some(400));
+
+        let mut ae = AutoExposure::default();
+        assert_eq!(ae.update(10, 900, 1..=1000), Some(1000));
+        let mut ae = AutoExposure::default();
+        assert_eq!(ae.update(10, 1000, 1..=1000), None);
+        assert_eq!(ae.update(120, 1, 1..=1000), Some(2));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::config::{SpectrumPoint, WavelengthBand};
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Integrals of the bands at one time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BandSample {
+    pub time: DateTime<Local>,
+    /// Seconds since the recording started.
+    pub seconds: f64,
+    /// Integral of each band, `None` if the spectrum does not cover it.
+    pub values: Vec<Option<f32>>,
+}
+
+/// Integrated intensity of wavelength bands over time, e.g. to follow kinetics or the
+/// stability of a light source.
+#[derive(Debug, Default)]
+pub struct BandMonitor {
+    started: Option<Instant>,
+    last_sample: Option<Instant>,
+    samples: VecDeque<BandSample>,
+}
+
+impl BandMonitor {
+    pub fn is_recording(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Start a new recording, discarding the previous one.
+    pub fn start(&mut self) {
+        self.clear();
+        self.started = Some(Instant::now());
+    }
+
+    pub fn stop(&mut self) {
+        self.started = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.last_sample = None;
+        if self.started.is_some() {
+            self.started = Some(Instant::now());
+        }
+    }
+
+    /// Whether a sample is to be recorded at `now`.
+    pub fn is_due(&self, interval: Duration, now: Instant) -> bool {
+        self.is_recording()
+            && self
+                .last_sample
+                .is_none_or(|last| now.saturating_duration_since(last) >= interval)
+    }
+
+    pub fn samples(&self) -> &VecDeque<BandSample> {
+        &self.samples
+    }
+
+    /// Record the band integrals of `spectrum` at `now` while recording, if `interval` passed
+    /// since the last sample. Samples older than `span` are dropped.
+    pub fn record(
+        &mut self,
+        spectrum: &[SpectrumPoint],
+        bands: &[WavelengthBand],
+        interval: Duration,
+        span: Duration,
+        now: Instant,
+    ) {
+        let started = match self.started {
+            Some(started) if self.is_due(interval, now) => started,
+            _ => return,
+        };
+        // Bands were added or removed
+        if self
+            .samples
+            .back()
+            .is_some_and(|s| s.values.len() != bands.len())
+        {
+            self.samples.clear();
+        }
+        self.last_sample = Some(now);
+        let seconds = now.saturating_duration_since(started).as_secs_f64();
+        self.samples.push_back(BandSample {
+            time: Local::now(),
+            seconds,
+            values: bands
+                .iter()
+                .map(|band| band.statistics(spectrum).map(|s| s.integral))
+                .collect(),
+        });
+        while self
+            .samples
+            .front()
+            .is_some_and(|s| seconds - s.seconds > span.as_secs_f64())
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Time in seconds and integral of the band with the index, leaving out samples which do
+    /// not cover it.
+    pub fn series(&self, band: usize) -> Vec<[f64; 2]> {
+        self.samples
+            .iter()
+            .filter_map(|s| Some([s.seconds, *s.values.get(band)?.as_ref()? as f64]))
+            .collect()
+    }
+
+    /// Write the samples as CSV with a column per band, empty where a band was not covered.
+    pub fn write_csv(&self, path: &Path, bands: &[WavelengthBand]) -> Result<(), String> {
+        let mut writer = csv::Writer::from_path(path).map_err(|e| e.to_string())?;
+        let mut header = vec!["time".to_string(), "seconds".to_string()];
+        header.extend(bands.iter().map(|band| band.to_string()));
+        writer.write_record(&header).map_err(|e| e.to_string())?;
+        for sample in &self.samples {
+            let mut record = vec![sample.time.to_rfc3339(), format!("{:.3}", sample.seconds)];
+            record.extend(
+                sample
+                    .values
+                    .iter()
+                    .map(|v| v.map_or(String::new(), |v| v.to_string())),
+            );
+            writer.write_record(&record).map_err(|e| e.to_string())?;
+        }
+        writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn band_monitor() {
+        let spectrum = |level: f32| {
+            (0..=100)
+                .map(|i| SpectrumPoint {
+                    wavelength: 500. + i as f32,
+                    value: level,
+                })
+                .collect::<Vec<_>>()
+        };
+        let bands = [
+            WavelengthBand {
+                start: 530.,
+                end: 560.,
+            },
+            WavelengthBand {
+                start: 650.,
+                end: 700.,
+            },
+        ];
+        let interval = Duration::from_secs(1);
+        let span = Duration::from_millis(3500);
+        let mut monitor = BandMonitor::default();
+        let now = Instant::now();
+        monitor.record(&spectrum(1.), &bands, interval, span, now);
+        assert!(monitor.samples().is_empty());
+
+        monitor.start();
+        let start = Instant::now();
+        for i in 0..10 {
+            let at = start + Duration::from_millis(500 * i);
+            monitor.record(&spectrum(i as f32), &bands, interval, span, at);
+        }
+        // Every other spectrum, the oldest ones beyond the span dropped
+        let series = monitor.series(0);
+        assert_eq!(series.len(), 4);
+        assert_relative_eq!(series[0][0], 1., max_relative = 1e-3);
+        assert_relative_eq!(series[3][1], 8. * 30., max_relative = 1e-3);
+        assert!(monitor.series(1).is_empty());
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("band-monitor.csv");
+        monitor.write_csv(&path, &bands).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(csv.lines().count(), 5);
+        assert!(csv.lines().last().unwrap().ends_with(",240,"));
+
+        monitor.stop();
+        assert!(!monitor.is_recording());
+        monitor.clear();
+        assert!(monitor.samples().is_empty());
+    }
+}
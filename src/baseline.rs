@@ -0,0 +1,203 @@
+use crate::config::{BaselineConfig, BaselineMethod};
+
+/// Reweighting iterations of the asymmetric least squares baseline.
+const ALS_ITERATIONS: usize = 10;
+
+/// Asymmetric least squares baseline after Eilers and Boelens.
+///
+/// A curve smoothed with the second difference penalty `lambda` is fitted, weighting points
+/// above it with `asymmetry` and points below with `1 - asymmetry`, so that for a small
+/// asymmetry it follows the background under the peaks.
+pub fn asymmetric_least_squares(signal: &[f32], lambda: f32, asymmetry: f32) -> Vec<f32> {
+    let n = signal.len();
+    if n < 3 {
+        return signal.to_vec();
+    }
+    // Bands of λ·DᵀD with D the second difference matrix
+    let mut penalty = [vec![0f64; n], vec![0f64; n - 1], vec![0f64; n - 2]];
+    let d = [1., -2., 1.];
+    for row in 0..n - 2 {
+        for a in 0..3 {
+            for b in a..3 {
+                penalty[b - a][row + a] += lambda as f64 * d[a] * d[b];
+            }
+        }
+    }
+
+    let y: Vec<f64> = signal.iter().map(|&v| v as f64).collect();
+    let mut weights = vec![1f64; n];
+    let mut baseline = y.clone();
+    for _ in 0..ALS_ITERATIONS {
+        let diagonal: Vec<f64> = penalty[0]
+            .iter()
+            .zip(&weights)
+            .map(|(p, w)| p + w)
+            .collect();
+        let rhs: Vec<f64> = y.iter().zip(&weights).map(|(y, w)| y * w).collect();
+        baseline = solve_pentadiagonal(&diagonal, &penalty[1], &penalty[2], &rhs);
+        for ((w, y), z) in weights.iter_mut().zip(&y).zip(&baseline) {
+            *w = if y > z {
+                asymmetry as f64
+            } else {
+                1. - asymmetry as f64
+            };
+        }
+    }
+    baseline.into_iter().map(|v| v as f32).collect()
+}
+
+/// Solve the symmetric positive definite system with the `diagonal` and the first and second
+/// off diagonals by an LDLᵀ decomposition.
+fn solve_pentadiagonal(diagonal: &[f64], first: &[f64], second: &[f64], rhs: &[f64]) -> Vec<f64> {
+    let n = diagonal.len();
+    let mut d = vec![0.; n];
+    let mut l1 = vec![0.; n];
+    let mut l2 = vec![0.; n];
+    for i in 0..n {
+        if i >= 2 {
+            l2[i] = second[i - 2] / d[i - 2];
+        }
+        if i >= 1 {
+            let coupled = if i >= 2 {
+                l2[i] * d[i - 2] * l1[i - 1]
+            } else {
+                0.
+            };
+            l1[i] = (first[i - 1] - coupled) / d[i - 1];
+        }
+        d[i] = diagonal[i];
+        if i >= 1 {
+            d[i] -= l1[i] * l1[i] * d[i - 1];
+        }
+        if i >= 2 {
+            d[i] -= l2[i] * l2[i] * d[i - 2];
+        }
+    }
+
+    let mut x = rhs.to_vec();
+    for i in 1..n {
+        x[i] -= l1[i] * x[i - 1];
+        if i >= 2 {
+            x[i] -= l2[i] * x[i - 2];
+        }
+    }
+    for i in 0..n {
+        x[i] /= d[i];
+    }
+    for i in (0..n - 1).rev() {
+        x[i] -= l1[i + 1] * x[i + 1];
+        if i + 2 < n {
+            x[i] -= l2[i + 2] * x[i + 2];
+        }
+    }
+    x
+}
+
+/// Rolling ball baseline: the opening of the signal with a window of `2 * radius + 1` points,
+/// which removes every peak narrower than that, smoothed with a moving mean of the same width
+/// and kept below the signal.
+pub fn rolling_ball(signal: &[f32], radius: usize) -> Vec<f32> {
+    let eroded = moving(signal, radius, f32::INFINITY, f32::min);
+    let opened = moving(&eroded, radius, f32::NEG_INFINITY, f32::max);
+    let last = signal.len() as isize - 1;
+    let radius_i = radius as isize;
+    (0..signal.len() as isize)
+        .map(|i| {
+            let mean = (i - radius_i..=i + radius_i)
+                .map(|j| opened[j.clamp(0, last) as usize])
+                .sum::<f32>()
+                / (2 * radius + 1) as f32;
+            mean.min(signal[i as usize])
+        })
+        .collect()
+}
+
+/// Fold of `f` over `2 * half_width + 1` points around each one, repeating the edges.
+fn moving(signal: &[f32], half_width: usize, init: f32, f: fn(f32, f32) -> f32) -> Vec<f32> {
+    let last = signal.len() as isize - 1;
+    let half_width = half_width as isize;
+    (0..signal.len() as isize)
+        .map(|i| {
+            (i - half_width..=i + half_width)
+                .map(|j| signal[j.clamp(0, last) as usize])
+                .fold(init, f)
+        })
+        .collect()
+}
+
+impl BaselineConfig {
+    /// Signal with the baseline of the configured method subtracted.
+    pub fn subtract(&self, signal: &[f32]) -> Vec<f32> {
+        let baseline = match self.method {
+            BaselineMethod::None => return signal.to_vec(),
+            BaselineMethod::AsymmetricLeastSquares => {
+                asymmetric_least_squares(signal, self.lambda, self.asymmetry)
+            }
+            BaselineMethod::RollingBall => rolling_ball(signal, self.radius),
+        };
+        signal.iter().zip(baseline).map(|(v, b)| v - b).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn baseline() {
+        // Sloped background with a narrow peak
+        let background = |x: usize| 0.1 + 0.001 * x as f32;
+        let signal: Vec<f32> = (0..200)
+            .map(|x| {
+                let peak = 0.5 * (-((x as f32 - 100.) / 3.).powi(2)).exp();
+                background(x) + peak
+            })
+            .collect();
+
+        let als = asymmetric_least_squares(&signal, 1e5, 0.001);
+        let rolling = rolling_ball(&signal, 20);
+        for x in [30, 50, 100, 150, 170] {
+            assert_relative_eq!(als[x], background(x), epsilon = 0.01);
+            assert_relative_eq!(rolling[x], background(x), epsilon = 0.01);
+        }
+        assert!(rolling.iter().zip(&signal).all(|(b, s)| b <= s));
+        let config = BaselineConfig {
+            method: BaselineMethod::RollingBall,
+            radius: 20,
+            ..Default::default()
+        };
+        let corrected = config.subtract(&signal);
+        assert_relative_eq!(corrected[100], 0.5, epsilon = 0.01);
+        assert_relative_eq!(corrected[50], 0., epsilon = 0.01);
+
+        // An exact solve of a system with a known solution
+        let diagonal = [6., 7., 8., 7., 6.];
+        let first = [-2., -3., -1., -2.];
+        let second = [1., 0.5, 1.];
+        let expected = [1., -1., 2., 0.5, 3.];
+        let rhs: Vec<f64> = (0..5)
+            .map(|i| {
+                let mut v = diagonal[i] * expected[i];
+                if i >= 1 {
+                    v += first[i - 1] * expected[i - 1];
+                }
+                if i + 1 < 5 {
+                    v += first[i] * expected[i + 1];
+                }
+                if i >= 2 {
+                    v += second[i - 2] * expected[i - 2];
+                }
+                if i + 2 < 5 {
+                    v += second[i] * expected[i + 2];
+                }
+                v
+            })
+            .collect();
+        let x = solve_pentadiagonal(&diagonal, &first, &second, &rhs);
+        for (x, e) in x.iter().zip(expected) {
+            assert_relative_eq!(*x, e, epsilon = 1e-9);
+        }
+        assert_eq!(asymmetric_least_squares(&[1., 2.], 1e5, 0.01), vec![1., 2.]);
+    }
+}
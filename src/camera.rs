@@ -1,17 +1,32 @@
-use crate::config::{CameraControl, ImageConfig};
-use crate::{ThreadId, ThreadResult};
+use crate::acquisition::AcquisitionEvent;
+use crate::config::{CameraBackend, CameraControl, ImageConfig, SyntheticCameraConfig};
+use crate::frame_integrity::{FrameIntegrityCheck, FrameStatistics};
+use crate::network_camera::{Credentials, NetworkCamera};
+use crate::pixel_format::PixelFormat;
+use crate::roi::{extract_deep_window, extract_window, window_inside, BeamWindows};
+use crate::synthetic_camera::SyntheticCamera;
 use flume::{Receiver, Sender};
-use image::{DynamicImage, GenericImageView, ImageBuffer, Rgb};
-use nokhwa::{CameraFormat, FrameFormat, Resolution, ThreadedCamera};
+use image::{DynamicImage, ImageBuffer, Rgb, RgbImage};
+use nokhwa::{query_devices, Camera, CameraFormat, FrameFormat, Resolution, ThreadedCamera};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 
 #[cfg(target_os = "linux")]
-use v4l::Control;
+use v4l::{
+    buffer::Type,
+    io::traits::CaptureStream,
+    prelude::MmapStream,
+    video::{capture::Parameters, Capture},
+    Control, Device, Format, FourCC,
+};
 
 #[derive(Debug, Clone)]
 pub struct CameraInfo {
     pub info: nokhwa::CameraInfo,
     pub formats: Vec<CameraFormat>,
+    /// Formats which can be read without conversion to 8-bit RGB.
+    pub pixel_formats: Vec<PixelFormat>,
 }
 
 impl CameraInfo {
@@ -21,17 +36,85 @@ impl CameraInfo {
             CameraFormat::new(Resolution::new(640, 480), FrameFormat::YUYV, 30),
         ]
     }
+
+    /// Sorted indices of the connected cameras, without opening them.
+    pub fn connected(backend: CameraBackend) -> Vec<usize> {
+        let mut indices = query_devices(backend.api())
+            .unwrap_or_default()
+            .iter()
+            .map(nokhwa::CameraInfo::index)
+            .collect::<Vec<_>>();
+        indices.sort_unstable();
+        indices
+    }
+
+    /// Open every connected camera to get its name and formats, which may take seconds.
+    pub fn query_all(backend: CameraBackend) -> HashMap<usize, CameraInfo> {
+        let default_camera_formats = Self::get_default_camera_formats();
+        let mut camera_info = HashMap::new();
+
+        for i in query_devices(backend.api())
+            .unwrap_or_default()
+            .iter()
+            .map(nokhwa::CameraInfo::index)
+        {
+            for format in &default_camera_formats {
+                if let Ok(mut cam) = Camera::with_backend(i, Some(*format), backend.api()) {
+                    let mut formats = cam.compatible_camera_formats().unwrap_or_default();
+                    formats.sort_by_key(nokhwa::CameraFormat::width);
+                    camera_info.insert(
+                        i,
+                        CameraInfo {
+                            info: cam.info().clone(),
+                            formats,
+                            pixel_formats: Self::pixel_formats(i),
+                        },
+                    );
+                    break;
+                }
+            }
+            if !camera_info.contains_key(&i) {
+                log::warn!("Could not query camera {}", i);
+            }
+        }
+        camera_info
+    }
+
+    #[cfg(target_os = "linux")]
+    fn pixel_formats(index: usize) -> Vec<PixelFormat> {
+        v4l::Device::new(index)
+            .and_then(|device| device.enum_formats())
+            .map(|formats| {
+                formats
+                    .iter()
+                    .filter_map(|f| PixelFormat::from_fourcc(&f.fourcc.repr))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    fn pixel_formats(_index: usize) -> Vec<PixelFormat> {
+        Vec::new()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum CameraEvent {
     StartStream {
+        backend: CameraBackend,
         id: usize,
         format: CameraFormat,
+        /// Read frames in this format instead of converting them to 8-bit RGB.
+        pixel_format: Option<PixelFormat>,
     },
+    /// Stream frames of a simulated spectrum instead of a camera.
+    StartSynthetic(SyntheticCameraConfig),
+    /// Stream frames of the network camera at the URL.
+    StartNetwork(String, Option<Credentials>),
     StopStream,
+    /// Stop the stream and analyze the image at the given path instead.
+    StillImage(String),
     Config(ImageConfig),
-    #[cfg(target_os = "linux")]
     Controls(Vec<CameraControl>),
 }
 
@@ -39,23 +122,26 @@ struct Exit {}
 
 pub struct CameraThread {
     frame_tx: Sender<ImageBuffer<Rgb<u8>, Vec<u8>>>,
-    window_tx: Sender<ImageBuffer<Rgb<u8>, Vec<u8>>>,
+    window_tx: Sender<BeamWindows>,
     config_rx: Receiver<CameraEvent>,
-    result_tx: Sender<ThreadResult>,
+    acquisition_tx: Sender<AcquisitionEvent>,
+    statistics: Arc<Mutex<FrameStatistics>>,
 }
 
 impl CameraThread {
     pub fn new(
         frame_tx: Sender<ImageBuffer<Rgb<u8>, Vec<u8>>>,
-        window_tx: Sender<ImageBuffer<Rgb<u8>, Vec<u8>>>,
+        window_tx: Sender<BeamWindows>,
         config_rx: Receiver<CameraEvent>,
-        result_tx: Sender<ThreadResult>,
+        acquisition_tx: Sender<AcquisitionEvent>,
+        statistics: Arc<Mutex<FrameStatistics>>,
     ) -> Self {
         Self {
             frame_tx,
             window_tx,
             config_rx,
-            result_tx,
+            acquisition_tx,
+            statistics,
         }
     }
 
@@ -64,125 +150,77 @@ impl CameraThread {
         let config: Arc<Mutex<Option<ImageConfig>>> = Arc::new(Mutex::new(None));
         let controls: Arc<Mutex<Option<Vec<CameraControl>>>> = Arc::new(Mutex::new(None));
         let mut join_handle = None;
+        let mut last_config: Option<ImageConfig> = None;
+        let mut still_image: Option<DynamicImage> = None;
         loop {
             if let Ok(event) = self.config_rx.recv() {
                 match event {
-                    CameraEvent::StartStream { id, format } => {
-                        let config = Arc::clone(&config);
-                        let controls = Arc::clone(&controls);
-
-                        let frame_tx = self.frame_tx.clone();
-                        let window_tx = self.window_tx.clone();
-                        let result_tx = self.result_tx.clone();
-                        let exit_rx = exit_rx.clone();
-                        let hdl = std::thread::spawn(move || {
-                            let mut camera = match ThreadedCamera::new(id, Some(format)) {
-                                Ok(camera) => camera,
-                                Err(e) => {
-                                    log::error!("{:?}", e);
-                                    result_tx
-                                        .send(ThreadResult {
-                                            id: ThreadId::Camera,
-                                            result: Err("Could not initialize camera".into()),
-                                        })
-                                        .unwrap();
-                                    return;
-                                }
-                            };
-
-                            match camera.open_stream(|_| {}) {
-                                Ok(_) => {}
-                                Err(e) => {
-                                    log::error!("{:?}", e);
-                                    result_tx
-                                        .send(ThreadResult {
-                                            id: ThreadId::Camera,
-                                            result: Err("Could not open stream".into()),
-                                        })
-                                        .unwrap();
-                                    return;
-                                }
-                            };
-
-                            result_tx
-                                .send(ThreadResult {
-                                    id: ThreadId::Camera,
-                                    result: Ok(()),
-                                })
-                                .unwrap();
-
-                            let mut inner_config = None;
-
-                            loop {
-                                // Check exit request
-                                if exit_rx.try_recv().is_ok() {
-                                    return;
-                                }
-                                // Check for new config
-                                if let Some(cfg) = config.lock().unwrap().take() {
-                                    inner_config = Some(cfg);
-                                }
-                                // Check for new controls
-                                if let Some(controls) = controls.lock().unwrap().take() {
-                                    for control in &controls {
-                                        Self::set_control(&mut camera, control);
-                                    }
-                                }
-                                // Get frame
-                                let frame = match camera.poll_frame() {
-                                    Ok(frame) => frame,
-                                    Err(e) => {
-                                        log::error!("{:?}", e);
-                                        result_tx
-                                            .send(ThreadResult {
-                                                id: ThreadId::Camera,
-                                                result: Err("Could not poll for frame".into()),
-                                            })
-                                            .unwrap();
-                                        return;
-                                    }
-                                };
-
-                                // TODO: Remove repacking after nokhwa uses image = "0.24"
-                                let (width, heigth) = frame.dimensions();
-                                let mut frame =
-                                    ImageBuffer::from_raw(width, heigth, frame.into_raw()).unwrap();
-
-                                if let Some(cfg) = &inner_config {
-                                    // Flip
-                                    if cfg.flip {
-                                        frame = DynamicImage::ImageRgb8(frame).fliph().into_rgb8();
-                                    }
-                                    // Extract window
-                                    let window = frame
-                                        .view(
-                                            cfg.window.offset.x as u32,
-                                            cfg.window.offset.y as u32,
-                                            cfg.window.size.x as u32,
-                                            cfg.window.size.y as u32,
-                                        )
-                                        .to_image();
-                                    if window_tx.send(window).is_err() {
-                                        return;
-                                    };
-                                }
-                                if frame_tx.send(frame).is_err() {
-                                    return;
-                                };
-                            }
-                        });
-                        join_handle = Some(hdl);
+                    CameraEvent::StartStream {
+                        backend,
+                        id,
+                        format,
+                        pixel_format,
+                    } => {
+                        still_image = None;
+                        self.stop_stream(&exit_tx, &mut join_handle);
+                        join_handle = Some(self.spawn_stream(
+                            move || FrameSource::open_camera(backend, id, format, pixel_format),
+                            &exit_rx,
+                            &config,
+                            &controls,
+                        ));
+                    }
+                    CameraEvent::StartSynthetic(synthetic_config) => {
+                        still_image = None;
+                        self.stop_stream(&exit_tx, &mut join_handle);
+                        join_handle = Some(self.spawn_stream(
+                            move || {
+                                Ok(FrameSource::Synthetic(SyntheticCamera::new(
+                                    synthetic_config,
+                                )))
+                            },
+                            &exit_rx,
+                            &config,
+                            &controls,
+                        ));
+                    }
+                    CameraEvent::StartNetwork(url, credentials) => {
+                        still_image = None;
+                        self.stop_stream(&exit_tx, &mut join_handle);
+                        join_handle = Some(self.spawn_stream(
+                            move || {
+                                NetworkCamera::open(&url, credentials.clone())
+                                    .map(FrameSource::Network)
+                            },
+                            &exit_rx,
+                            &config,
+                            &controls,
+                        ));
                     }
                     CameraEvent::StopStream => {
-                        if let Some(hdl) = join_handle.take() {
-                            exit_tx.send(Exit {}).ok();
-                            hdl.join().ok();
-                        }
+                        still_image = None;
+                        self.stop_stream(&exit_tx, &mut join_handle);
+                        self.acquisition_tx.send(AcquisitionEvent::Stopped).unwrap();
+                    }
+                    CameraEvent::StillImage(path) => {
+                        self.stop_stream(&exit_tx, &mut join_handle);
+                        // Keep the depth of 16-bit images for the window
+                        let result = image::open(&path)
+                            .map_err(|e| format!("Could not load {}: {}", path, e));
+                        still_image = result.as_ref().ok().cloned();
+                        self.acquisition_tx
+                            .send(match result {
+                                Ok(_) => AcquisitionEvent::Started,
+                                Err(e) => AcquisitionEvent::Failed(e),
+                            })
+                            .unwrap();
+                        self.send_still_image(still_image.as_ref(), last_config.as_ref());
                     }
                     CameraEvent::Config(cfg) => {
+                        last_config = Some(cfg.clone());
                         *config.lock().unwrap() = Some(cfg);
+                        self.send_still_image(still_image.as_ref(), last_config.as_ref());
                     }
-                    #[cfg(target_os = "linux")]
                     CameraEvent::Controls(ctrls) => {
                         *controls.lock().unwrap() = Some(ctrls);
                     }
@@ -191,13 +229,304 @@ impl CameraThread {
         }
     }
 
-    #[cfg(target_os = "linux")]
-    fn set_control(camera: &mut ThreadedCamera, control: &CameraControl) {
-        camera
-            .set_raw_camera_control(&control.id, &Control::Value(control.value))
-            .map_err(|e| log::warn!("Could not write camera control: {:?}", e))
+    fn stop_stream(&self, exit_tx: &Sender<Exit>, join_handle: &mut Option<JoinHandle<()>>) {
+        if let Some(hdl) = join_handle.take() {
+            exit_tx.send(Exit {}).ok();
+            hdl.join().ok();
+        }
+    }
+
+    /// Run a thread sending the frames of the source opened by `open` and their windows.
+    fn spawn_stream(
+        &self,
+        open: impl FnOnce() -> Result<FrameSource, String> + Send + 'static,
+        exit_rx: &Receiver<Exit>,
+        config: &Arc<Mutex<Option<ImageConfig>>>,
+        controls: &Arc<Mutex<Option<Vec<CameraControl>>>>,
+    ) -> JoinHandle<()> {
+        let config = Arc::clone(config);
+        let controls = Arc::clone(controls);
+        let statistics = Arc::clone(&self.statistics);
+        *statistics.lock().unwrap() = FrameStatistics::default();
+
+        let frame_tx = self.frame_tx.clone();
+        let window_tx = self.window_tx.clone();
+        let acquisition_tx = self.acquisition_tx.clone();
+        let exit_rx = exit_rx.clone();
+        std::thread::spawn(move || {
+            let mut source = match open() {
+                Ok(source) => source,
+                Err(e) => {
+                    acquisition_tx.send(AcquisitionEvent::Failed(e)).unwrap();
+                    return;
+                }
+            };
+
+            acquisition_tx.send(AcquisitionEvent::Started).unwrap();
+
+            let mut inner_config = None;
+            let mut integrity_check = FrameIntegrityCheck::default();
+
+            loop {
+                // Check exit request
+                if exit_rx.try_recv().is_ok() {
+                    return;
+                }
+                // Check for new config
+                if let Some(cfg) = config.lock().unwrap().take() {
+                    inner_config = Some(cfg);
+                    integrity_check.reset();
+                }
+                // Check for new controls
+                if let Some(controls) = controls.lock().unwrap().take() {
+                    for control in &controls {
+                        source.set_control(control);
+                    }
+                }
+                // Get frame
+                let mut frame = match source.poll_frame() {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        acquisition_tx.send(AcquisitionEvent::Failed(e)).unwrap();
+                        return;
+                    }
+                };
+                // Correct hot pixels, whose map is for the unflipped frame
+                if let Some(map) = inner_config
+                    .as_ref()
+                    .and_then(|cfg| cfg.hot_pixels.as_ref())
+                {
+                    map.correct_deep(&mut frame);
+                }
+                // Flip
+                if matches!(&inner_config, Some(cfg) if cfg.flip) {
+                    frame = frame.fliph();
+                }
+                let (frame, deep) = split_frame(frame);
+
+                if let Some(cfg) = &inner_config {
+                    // Keep torn frames out of the spectrum, but still preview them
+                    let plausible = !cfg.frame_integrity.active || {
+                        let result = integrity_check.check(&frame, &cfg.frame_integrity);
+                        if let Err(defect) = result {
+                            log::debug!("Discarded frame: {}", defect);
+                        }
+                        statistics.lock().unwrap().record(result);
+                        result.is_ok()
+                    };
+                    let reference_window = cfg.reference_window();
+                    // Skip windows beyond the frame, e.g. until the GUI learned the size of
+                    // a network camera
+                    let inside = cfg.rotation != 0.
+                        || [Some(cfg.window), reference_window]
+                            .iter()
+                            .flatten()
+                            .all(|window| window_inside(window, frame.width(), frame.height()));
+                    // Extract windows
+                    if plausible && inside {
+                        let extract = |window| match &deep {
+                            Some(deep) => extract_deep_window(deep, window, cfg.rotation),
+                            None => DynamicImage::ImageRgb8(extract_window(
+                                &frame,
+                                window,
+                                cfg.rotation,
+                            ))
+                            .into_rgb16(),
+                        };
+                        let windows = BeamWindows {
+                            sample: extract(&cfg.window),
+                            reference: reference_window.as_ref().map(extract),
+                            binning: cfg.binning,
+                        };
+                        if window_tx.send(windows).is_err() {
+                            return;
+                        };
+                    }
+                }
+                if frame_tx.send(frame).is_err() {
+                    return;
+                };
+            }
+        })
+    }
+
+    /// Send the still image and its windows once, like a single camera frame.
+    fn send_still_image(&self, image: Option<&DynamicImage>, config: Option<&ImageConfig>) {
+        let (image, config) = match (image, config) {
+            (Some(image), Some(config)) => (image, config),
+            _ => return,
+        };
+        let mut frame = image.clone();
+        if let Some(map) = &config.hot_pixels {
+            map.correct_deep(&mut frame);
+        }
+        if config.flip {
+            frame = frame.fliph();
+        }
+        let extract = |window| extract_deep_window(&frame, window, config.rotation);
+        self.window_tx
+            .send(BeamWindows {
+                sample: extract(&config.window),
+                reference: config.reference_window().as_ref().map(extract),
+                binning: config.binning,
+            })
             .ok();
+        self.frame_tx.send(frame.into_rgb8()).ok();
+    }
+}
+
+/// Split a frame into the 8-bit frame for the preview and the integrity check and, if the
+/// frame is deeper, the frame the window is cut from.
+fn split_frame(frame: DynamicImage) -> (RgbImage, Option<DynamicImage>) {
+    match frame {
+        DynamicImage::ImageRgb8(frame) => (frame, None),
+        deep => (deep.to_rgb8(), Some(deep)),
+    }
+}
+
+/// Where the frames of a running stream come from.
+enum FrameSource {
+    Camera(ThreadedCamera),
+    #[cfg(target_os = "linux")]
+    Raw(RawCamera),
+    Synthetic(SyntheticCamera),
+    Network(NetworkCamera),
+}
+
+impl FrameSource {
+    fn open_camera(
+        backend: CameraBackend,
+        id: usize,
+        format: CameraFormat,
+        pixel_format: Option<PixelFormat>,
+    ) -> Result<Self, String> {
+        if let Some(pixel_format) = pixel_format {
+            return Self::open_raw_camera(id, format, pixel_format);
+        }
+        let mut camera =
+            ThreadedCamera::with_backend(id, Some(format), backend.api()).map_err(|e| {
+                log::error!("{:?}", e);
+                "Could not initialize camera".to_string()
+            })?;
+        camera.open_stream(|_| {}).map_err(|e| {
+            log::error!("{:?}", e);
+            "Could not open stream".to_string()
+        })?;
+        Ok(Self::Camera(camera))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn open_raw_camera(
+        id: usize,
+        format: CameraFormat,
+        pixel_format: PixelFormat,
+    ) -> Result<Self, String> {
+        RawCamera::open(id, format, pixel_format).map(Self::Raw)
+    }
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    fn open_raw_camera(
+        _id: usize,
+        _format: CameraFormat,
+        pixel_format: PixelFormat,
+    ) -> Result<Self, String> {
+        Err(format!("{} is only supported on Linux", pixel_format))
+    }
+
+    fn poll_frame(&mut self) -> Result<DynamicImage, String> {
+        match self {
+            Self::Camera(camera) => {
+                let frame = camera.poll_frame().map_err(|e| {
+                    log::error!("{:?}", e);
+                    "Could not poll for frame".to_string()
+                })?;
+                // TODO: Remove repacking after nokhwa uses image = "0.24"
+                let (width, heigth) = frame.dimensions();
+                Ok(DynamicImage::ImageRgb8(
+                    ImageBuffer::from_raw(width, heigth, frame.into_raw()).unwrap(),
+                ))
+            }
+            #[cfg(target_os = "linux")]
+            Self::Raw(camera) => camera.poll_frame().map(DynamicImage::ImageRgb16),
+            Self::Synthetic(camera) => Ok(DynamicImage::ImageRgb8(camera.poll_frame())),
+            Self::Network(camera) => camera.poll_frame().map(DynamicImage::ImageRgb8),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_control(&mut self, control: &CameraControl) {
+        let result = match self {
+            Self::Camera(camera) => camera
+                .set_raw_camera_control(&control.id, &Control::Value(control.value))
+                .map_err(|e| format!("{:?}", e)),
+            Self::Raw(camera) => camera
+                .device
+                .set_control(control.id, Control::Value(control.value))
+                .map_err(|e| format!("{:?}", e)),
+            Self::Synthetic(_) | Self::Network(_) => Ok(()),
+        };
+        if let Err(e) = result {
+            log::warn!("Could not write camera control: {}", e);
+        }
     }
     #[cfg(any(target_os = "windows", target_os = "macos"))]
-    fn set_control(_camera: &mut ThreadedCamera, _control: &CameraControl) {}
+    fn set_control(&mut self, _control: &CameraControl) {}
+}
+
+/// Camera read directly through V4L2, for pixel formats the camera API only delivers
+/// converted to 8-bit RGB.
+#[cfg(target_os = "linux")]
+struct RawCamera {
+    stream: MmapStream<'static>,
+    device: Device,
+    pixel_format: PixelFormat,
+    width: u32,
+    height: u32,
+    stride: usize,
+}
+
+#[cfg(target_os = "linux")]
+impl RawCamera {
+    fn open(id: usize, format: CameraFormat, pixel_format: PixelFormat) -> Result<Self, String> {
+        let device = Device::new(id).map_err(|e| {
+            log::error!("{:?}", e);
+            "Could not initialize camera".to_string()
+        })?;
+        let requested = Format::new(
+            format.width(),
+            format.height(),
+            FourCC::new(pixel_format.fourcc()),
+        );
+        let actual = device.set_format(&requested).map_err(|e| {
+            log::error!("{:?}", e);
+            format!("Could not set pixel format {}", pixel_format)
+        })?;
+        if actual.fourcc != requested.fourcc {
+            return Err(format!("Camera does not support {}", pixel_format));
+        }
+        device
+            .set_params(&Parameters::with_fps(format.frame_rate()))
+            .map_err(|e| log::warn!("Could not set frame rate: {:?}", e))
+            .ok();
+        let stream = MmapStream::with_buffers(&device, Type::VideoCapture, 4).map_err(|e| {
+            log::error!("{:?}", e);
+            "Could not open stream".to_string()
+        })?;
+        Ok(Self {
+            stream,
+            device,
+            pixel_format,
+            width: actual.width,
+            height: actual.height,
+            stride: actual.stride as usize,
+        })
+    }
+
+    fn poll_frame(&mut self) -> Result<ImageBuffer<Rgb<u16>, Vec<u16>>, String> {
+        let (data, _) = self.stream.next().map_err(|e| {
+            log::error!("{:?}", e);
+            "Could not poll for frame".to_string()
+        })?;
+        self.pixel_format
+            .decode(data, self.width, self.height, self.stride)
+    }
 }
@@ -0,0 +1,179 @@
+use crate::acquisition::AcquisitionEvent;
+use crate::camera::{CameraEvent, CameraThread};
+use crate::config::{ChannelComparison, SpectrumPoint};
+use crate::engine::SpectroEngine;
+use crate::frame_integrity::FrameStatistics;
+use crate::spectrum::{
+    ProcessorEvent, RawSpectra, SpectrumCalculator, SpectrumFrame, SpectrumWorker,
+};
+use flume::{Receiver, Sender};
+use image::RgbImage;
+use std::sync::{Arc, Mutex};
+
+/// Camera thread, spectrum calculator and spectrum worker of one camera with the ends of their
+/// channels the GUI uses. Every camera streamed at the same time has its own.
+pub struct CameraStream {
+    pub camera_tx: Sender<CameraEvent>,
+    pub processor_tx: Sender<ProcessorEvent>,
+    /// Raw spectra to process besides those of the camera, e.g. of a replay.
+    pub spectrum_tx: Sender<RawSpectra>,
+    pub frame_rx: Receiver<RgbImage>,
+    pub spectrum_frame_rx: Receiver<SpectrumFrame>,
+    pub acquisition_rx: Receiver<AcquisitionEvent>,
+    pub statistics: Arc<Mutex<FrameStatistics>>,
+}
+
+impl CameraStream {
+    /// Start the threads, which idle until the camera thread is told to start a source. Every
+    /// processed spectrum is published to the subscribers of `engine`.
+    pub fn spawn(engine: SpectroEngine) -> Self {
+        let (frame_tx, frame_rx) = flume::unbounded();
+        let (window_tx, window_rx) = flume::unbounded();
+        let (spectrum_tx, spectrum_rx) = flume::unbounded();
+        let (spectrum_frame_tx, spectrum_frame_rx) = flume::unbounded();
+        let (processor_tx, processor_rx) = flume::unbounded();
+        let (camera_tx, camera_rx) = flume::unbounded();
+        let (acquisition_tx, acquisition_rx) = flume::unbounded();
+
+        let statistics = Arc::new(Mutex::new(FrameStatistics::default()));
+        let camera_statistics = Arc::clone(&statistics);
+        std::thread::spawn(move || {
+            CameraThread::new(
+                frame_tx,
+                window_tx,
+                camera_rx,
+                acquisition_tx,
+                camera_statistics,
+            )
+            .run()
+        });
+        let calculator_spectrum_tx = spectrum_tx.clone();
+        std::thread::spawn(move || {
+            SpectrumCalculator::new(window_rx, calculator_spectrum_tx).run()
+        });
+        std::thread::spawn(move || {
+            SpectrumWorker::new(spectrum_rx, processor_rx, spectrum_frame_tx, engine).run()
+        });
+
+        Self {
+            camera_tx,
+            processor_tx,
+            spectrum_tx,
+            frame_rx,
+            spectrum_frame_rx,
+            acquisition_rx,
+            statistics,
+        }
+    }
+
+    /// Channels without threads, whose other ends are returned to stand in for the threads,
+    /// e.g. to drive the GUI in tests.
+    pub fn detached() -> (Self, StreamEnds) {
+        let (frame_tx, frame_rx) = flume::unbounded();
+        let (spectrum_frame_tx, spectrum_frame_rx) = flume::unbounded();
+        let (processor_tx, processor_rx) = flume::unbounded();
+        let (spectrum_tx, spectrum_rx) = flume::unbounded();
+        let (camera_tx, camera_rx) = flume::unbounded();
+        let (acquisition_tx, acquisition_rx) = flume::unbounded();
+        let stream = Self {
+            camera_tx,
+            processor_tx,
+            spectrum_tx,
+            frame_rx,
+            spectrum_frame_rx,
+            acquisition_rx,
+            statistics: Default::default(),
+        };
+        let ends = StreamEnds {
+            camera_rx,
+            processor_rx,
+            spectrum_rx,
+            frame_tx,
+            spectrum_frame_tx,
+            acquisition_tx,
+        };
+        (stream, ends)
+    }
+}
+
+/// Thread ends of the channels of a [CameraStream::detached].
+pub struct StreamEnds {
+    pub camera_rx: Receiver<CameraEvent>,
+    pub processor_rx: Receiver<ProcessorEvent>,
+    pub spectrum_rx: Receiver<RawSpectra>,
+    pub frame_tx: Sender<RgbImage>,
+    pub spectrum_frame_tx: Sender<SpectrumFrame>,
+    pub acquisition_tx: Sender<AcquisitionEvent>,
+}
+
+/// Linear interpolation of `points`, sorted by wavelength, `None` outside of them.
+pub fn interpolate(points: &[SpectrumPoint], wavelength: f32) -> Option<f32> {
+    let i = points.partition_point(|p| p.wavelength < wavelength);
+    let upper = points.get(i)?;
+    if upper.wavelength == wavelength {
+        return Some(upper.value);
+    }
+    let lower = points.get(i.checked_sub(1)?)?;
+    let t = (wavelength - lower.wavelength) / (upper.wavelength - lower.wavelength);
+    Some(lower.value + t * (upper.value - lower.value))
+}
+
+/// Difference or ratio of the spectra of two cameras on the wavelengths of the `first`, with
+/// `second` interpolated as the cameras have their own calibrations. Wavelengths outside of
+/// `second`, and for a ratio without light in `second`, are left out.
+pub fn compare_channels(
+    first: &[SpectrumPoint],
+    second: &[SpectrumPoint],
+    comparison: ChannelComparison,
+) -> Vec<SpectrumPoint> {
+    let mut second = second.to_vec();
+    second.sort_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+    first
+        .iter()
+        .filter_map(|p| {
+            let other = interpolate(&second, p.wavelength)?;
+            let value = match comparison {
+                ChannelComparison::None => return None,
+                ChannelComparison::Difference => p.value - other,
+                ChannelComparison::Ratio if other.abs() > f32::EPSILON => p.value / other,
+                ChannelComparison::Ratio => return None,
+            };
+            Some(SpectrumPoint {
+                wavelength: p.wavelength,
+                value,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn compare_channels() {
+        let points = |wavelengths: &[f32], f: &dyn Fn(f32) -> f32| {
+            wavelengths
+                .iter()
+                .map(|&wavelength| SpectrumPoint {
+                    wavelength,
+                    value: f(wavelength),
+                })
+                .collect::<Vec<_>>()
+        };
+        let first = points(&[400., 450., 500., 550.], &|w| w / 100.);
+        // Coarser, descending calibration of the second camera ending at 520 nm
+        let second = points(&[520., 480., 440., 400.], &|w| w / 200.);
+
+        let difference = super::compare_channels(&first, &second, ChannelComparison::Difference);
+        assert_eq!(difference.len(), 3);
+        assert_relative_eq!(difference[1].value, 2.25);
+        let ratio = super::compare_channels(&first, &second, ChannelComparison::Ratio);
+        assert!(ratio.iter().all(|p| (p.value - 2.).abs() < 1e-5));
+        assert!(super::compare_channels(&first, &second, ChannelComparison::None).is_empty());
+
+        let dark = points(&[400., 600.], &|_| 0.);
+        assert!(super::compare_channels(&first, &dark, ChannelComparison::Ratio).is_empty());
+    }
+}
@@ -0,0 +1,374 @@
+use crate::colorimetry::{color_matching_functions, colorimetry, planck};
+use crate::config::SpectrumPoint;
+
+const START: f64 = 380.;
+const STEP: f64 = 5.;
+const POINTS: usize = 81;
+/// Below this CCT the reference is a black body, above it CIE daylight.
+const DAYLIGHT_CCT: f32 = 5000.;
+/// Largest distance from the Planckian locus for which the CRI is meaningful.
+pub const MAX_DUV: f32 = 0.0054;
+
+/// CIE daylight basis functions S0, S1 and S2 from 380 to 780 nm.
+const DAYLIGHT: [[f64; POINTS]; 3] = [
+    [
+        63.40, 64.60, 65.80, 80.30, 94.80, 99.80, 104.80, 105.35, 105.90, 101.35, 96.80, 105.35,
+        113.90, 119.75, 125.60, 125.55, 125.50, 123.40, 121.30, 121.30, 121.30, 117.40, 113.50,
+        113.30, 113.10, 111.95, 110.80, 108.65, 106.50, 107.65, 108.80, 107.05, 105.30, 104.85,
+        104.40, 102.20, 100.00, 98.00, 96.00, 95.55, 95.10, 92.10, 89.10, 89.80, 90.50, 90.40,
+        90.30, 89.35, 88.40, 86.20, 84.00, 84.55, 85.10, 83.50, 81.90, 82.25, 82.60, 83.75, 84.90,
+        83.10, 81.30, 76.60, 71.90, 73.10, 74.30, 75.35, 76.40, 69.85, 63.30, 67.50, 71.70, 74.35,
+        77.00, 71.10, 65.20, 56.45, 47.70, 58.15, 68.60, 66.80, 65.00,
+    ],
+    [
+        38.50, 36.75, 35.00, 39.20, 43.40, 44.85, 46.30, 45.10, 43.90, 40.50, 37.10, 36.90, 36.70,
+        36.30, 35.90, 34.25, 32.60, 30.25, 27.90, 26.10, 24.30, 22.20, 20.10, 18.15, 16.20, 14.70,
+        13.20, 10.90, 8.60, 7.35, 6.10, 5.15, 4.20, 3.05, 1.90, 0.95, 0.00, -0.80, -1.60, -2.55,
+        -3.50, -3.50, -3.50, -4.65, -5.80, -6.50, -7.20, -7.90, -8.60, -9.05, -9.50, -10.20,
+        -10.90, -10.80, -10.70, -11.35, -12.00, -13.00, -14.00, -13.80, -13.60, -12.80, -12.00,
+        -12.65, -13.30, -13.10, -12.90, -11.75, -10.60, -11.10, -11.60, -11.90, -12.20, -11.20,
+        -10.20, -9.00, -7.80, -9.50, -11.20, -10.80, -10.40,
+    ],
+    [
+        3.00, 2.10, 1.20, 0.05, -1.10, -0.80, -0.50, -0.60, -0.70, -0.95, -1.20, -1.90, -2.60,
+        -2.75, -2.90, -2.85, -2.80, -2.70, -2.60, -2.60, -2.60, -2.20, -1.80, -1.65, -1.50, -1.40,
+        -1.30, -1.25, -1.20, -1.10, -1.00, -0.75, -0.50, -0.40, -0.30, -0.15, 0.00, 0.10, 0.20,
+        0.35, 0.50, 1.30, 2.10, 2.65, 3.20, 3.65, 4.10, 4.40, 4.70, 4.90, 5.10, 5.90, 6.70, 7.00,
+        7.30, 7.95, 8.60, 9.20, 9.80, 10.00, 10.20, 9.25, 8.30, 8.95, 9.60, 9.05, 8.50, 7.75, 7.00,
+        7.30, 7.60, 7.80, 8.00, 7.35, 6.70, 5.95, 5.20, 6.30, 7.40, 7.10, 6.80,
+    ],
+];
+
+/// Spectral radiance factors of the CIE 13.3 test color samples TCS01 to TCS14 from 380 to
+/// 780 nm.
+#[allow(clippy::approx_constant)]
+const TEST_COLOR_SAMPLES: [[f64; POINTS]; 14] = [
+    [
+        0.219, 0.239, 0.252, 0.256, 0.256, 0.254, 0.252, 0.248, 0.244, 0.240, 0.237, 0.232, 0.230,
+        0.226, 0.225, 0.222, 0.220, 0.218, 0.216, 0.214, 0.214, 0.214, 0.216, 0.218, 0.223, 0.225,
+        0.226, 0.226, 0.225, 0.225, 0.227, 0.230, 0.236, 0.245, 0.253, 0.262, 0.272, 0.283, 0.298,
+        0.318, 0.341, 0.367, 0.390, 0.409, 0.424, 0.435, 0.442, 0.448, 0.450, 0.451, 0.451, 0.451,
+        0.451, 0.451, 0.450, 0.450, 0.451, 0.451, 0.453, 0.454, 0.455, 0.457, 0.458, 0.460, 0.462,
+        0.463, 0.464, 0.465, 0.466, 0.466, 0.466, 0.466, 0.467, 0.467, 0.467, 0.467, 0.467, 0.467,
+        0.467, 0.467, 0.467,
+    ],
+    [
+        0.070, 0.079, 0.089, 0.101, 0.111, 0.116, 0.118, 0.120, 0.121, 0.122, 0.122, 0.122, 0.123,
+        0.124, 0.127, 0.128, 0.131, 0.134, 0.138, 0.143, 0.150, 0.159, 0.174, 0.190, 0.207, 0.225,
+        0.242, 0.253, 0.260, 0.264, 0.267, 0.269, 0.272, 0.276, 0.282, 0.289, 0.299, 0.309, 0.322,
+        0.329, 0.335, 0.339, 0.341, 0.341, 0.342, 0.342, 0.342, 0.341, 0.341, 0.339, 0.339, 0.338,
+        0.338, 0.337, 0.336, 0.335, 0.334, 0.332, 0.332, 0.331, 0.331, 0.330, 0.329, 0.328, 0.328,
+        0.327, 0.326, 0.325, 0.324, 0.324, 0.324, 0.323, 0.322, 0.321, 0.320, 0.318, 0.316, 0.315,
+        0.315, 0.314, 0.314,
+    ],
+    [
+        0.065, 0.068, 0.070, 0.072, 0.073, 0.073, 0.074, 0.074, 0.074, 0.073, 0.073, 0.073, 0.073,
+        0.073, 0.074, 0.075, 0.077, 0.080, 0.085, 0.094, 0.109, 0.126, 0.148, 0.172, 0.198, 0.221,
+        0.241, 0.260, 0.278, 0.302, 0.339, 0.370, 0.392, 0.399, 0.400, 0.393, 0.380, 0.365, 0.349,
+        0.332, 0.315, 0.299, 0.285, 0.272, 0.264, 0.257, 0.252, 0.247, 0.241, 0.235, 0.229, 0.224,
+        0.220, 0.217, 0.216, 0.216, 0.219, 0.224, 0.230, 0.238, 0.251, 0.269, 0.288, 0.312, 0.340,
+        0.366, 0.390, 0.412, 0.431, 0.447, 0.460, 0.472, 0.481, 0.488, 0.493, 0.497, 0.500, 0.502,
+        0.505, 0.510, 0.516,
+    ],
+    [
+        0.074, 0.083, 0.093, 0.105, 0.116, 0.121, 0.124, 0.126, 0.128, 0.131, 0.135, 0.139, 0.144,
+        0.151, 0.161, 0.172, 0.186, 0.205, 0.229, 0.254, 0.281, 0.308, 0.332, 0.352, 0.370, 0.383,
+        0.390, 0.394, 0.395, 0.392, 0.385, 0.377, 0.367, 0.354, 0.341, 0.327, 0.312, 0.296, 0.280,
+        0.263, 0.247, 0.229, 0.214, 0.198, 0.185, 0.175, 0.169, 0.164, 0.160, 0.156, 0.154, 0.152,
+        0.151, 0.149, 0.148, 0.148, 0.148, 0.149, 0.151, 0.154, 0.158, 0.162, 0.165, 0.168, 0.170,
+        0.171, 0.170, 0.168, 0.166, 0.164, 0.164, 0.165, 0.168, 0.172, 0.177, 0.181, 0.185, 0.189,
+        0.192, 0.194, 0.197,
+    ],
+    [
+        0.295, 0.306, 0.310, 0.312, 0.313, 0.315, 0.319, 0.322, 0.326, 0.330, 0.334, 0.339, 0.346,
+        0.352, 0.360, 0.369, 0.381, 0.394, 0.403, 0.410, 0.415, 0.418, 0.419, 0.417, 0.413, 0.409,
+        0.403, 0.396, 0.389, 0.381, 0.372, 0.363, 0.353, 0.342, 0.331, 0.320, 0.308, 0.296, 0.284,
+        0.271, 0.260, 0.247, 0.232, 0.220, 0.210, 0.200, 0.194, 0.189, 0.185, 0.183, 0.180, 0.177,
+        0.176, 0.175, 0.175, 0.175, 0.175, 0.177, 0.180, 0.183, 0.186, 0.189, 0.192, 0.195, 0.199,
+        0.200, 0.199, 0.198, 0.196, 0.195, 0.195, 0.196, 0.197, 0.200, 0.203, 0.205, 0.208, 0.212,
+        0.215, 0.217, 0.219,
+    ],
+    [
+        0.151, 0.203, 0.265, 0.339, 0.410, 0.464, 0.492, 0.508, 0.517, 0.524, 0.531, 0.538, 0.544,
+        0.551, 0.556, 0.556, 0.554, 0.549, 0.541, 0.531, 0.519, 0.504, 0.488, 0.469, 0.450, 0.431,
+        0.414, 0.395, 0.377, 0.358, 0.341, 0.325, 0.309, 0.293, 0.279, 0.265, 0.253, 0.241, 0.234,
+        0.227, 0.225, 0.222, 0.221, 0.220, 0.220, 0.220, 0.220, 0.220, 0.223, 0.227, 0.233, 0.239,
+        0.244, 0.251, 0.258, 0.263, 0.268, 0.273, 0.278, 0.281, 0.283, 0.286, 0.291, 0.296, 0.302,
+        0.313, 0.325, 0.338, 0.351, 0.364, 0.376, 0.389, 0.401, 0.413, 0.425, 0.436, 0.447, 0.458,
+        0.469, 0.477, 0.485,
+    ],
+    [
+        0.378, 0.459, 0.524, 0.546, 0.551, 0.555, 0.559, 0.560, 0.561, 0.558, 0.556, 0.551, 0.544,
+        0.535, 0.522, 0.506, 0.488, 0.469, 0.448, 0.429, 0.408, 0.385, 0.363, 0.341, 0.324, 0.311,
+        0.301, 0.291, 0.283, 0.273, 0.265, 0.260, 0.257, 0.257, 0.259, 0.260, 0.260, 0.258, 0.256,
+        0.254, 0.254, 0.259, 0.270, 0.284, 0.302, 0.324, 0.344, 0.362, 0.377, 0.389, 0.400, 0.410,
+        0.420, 0.429, 0.438, 0.445, 0.452, 0.457, 0.462, 0.466, 0.468, 0.470, 0.473, 0.477, 0.483,
+        0.489, 0.496, 0.503, 0.511, 0.518, 0.525, 0.532, 0.539, 0.546, 0.553, 0.559, 0.565, 0.570,
+        0.575, 0.578, 0.581,
+    ],
+    [
+        0.104, 0.129, 0.170, 0.240, 0.319, 0.416, 0.462, 0.482, 0.490, 0.488, 0.482, 0.473, 0.462,
+        0.450, 0.439, 0.426, 0.413, 0.397, 0.382, 0.366, 0.352, 0.337, 0.325, 0.310, 0.299, 0.289,
+        0.283, 0.276, 0.270, 0.262, 0.256, 0.251, 0.250, 0.251, 0.254, 0.258, 0.264, 0.269, 0.272,
+        0.274, 0.278, 0.284, 0.295, 0.316, 0.348, 0.384, 0.434, 0.482, 0.528, 0.568, 0.604, 0.629,
+        0.648, 0.663, 0.676, 0.685, 0.693, 0.700, 0.705, 0.709, 0.712, 0.715, 0.717, 0.719, 0.721,
+        0.720, 0.719, 0.722, 0.725, 0.727, 0.729, 0.730, 0.730, 0.730, 0.730, 0.730, 0.730, 0.730,
+        0.730, 0.730, 0.730,
+    ],
+    [
+        0.066, 0.062, 0.058, 0.055, 0.052, 0.052, 0.051, 0.050, 0.050, 0.049, 0.048, 0.047, 0.046,
+        0.044, 0.042, 0.041, 0.038, 0.035, 0.033, 0.031, 0.030, 0.029, 0.028, 0.028, 0.028, 0.029,
+        0.030, 0.030, 0.031, 0.031, 0.032, 0.032, 0.033, 0.034, 0.035, 0.037, 0.041, 0.044, 0.048,
+        0.052, 0.060, 0.076, 0.102, 0.136, 0.190, 0.256, 0.336, 0.418, 0.505, 0.581, 0.641, 0.682,
+        0.717, 0.740, 0.758, 0.770, 0.781, 0.790, 0.797, 0.803, 0.809, 0.814, 0.819, 0.824, 0.828,
+        0.830, 0.831, 0.833, 0.835, 0.836, 0.836, 0.837, 0.838, 0.839, 0.839, 0.839, 0.839, 0.839,
+        0.839, 0.839, 0.839,
+    ],
+    [
+        0.050, 0.054, 0.059, 0.063, 0.066, 0.067, 0.068, 0.069, 0.069, 0.070, 0.072, 0.073, 0.076,
+        0.078, 0.083, 0.088, 0.095, 0.103, 0.113, 0.125, 0.142, 0.162, 0.189, 0.219, 0.262, 0.305,
+        0.365, 0.416, 0.465, 0.509, 0.546, 0.581, 0.610, 0.634, 0.653, 0.666, 0.678, 0.687, 0.693,
+        0.698, 0.701, 0.704, 0.705, 0.705, 0.706, 0.707, 0.707, 0.707, 0.708, 0.708, 0.710, 0.711,
+        0.712, 0.714, 0.716, 0.718, 0.720, 0.722, 0.725, 0.729, 0.731, 0.735, 0.739, 0.742, 0.746,
+        0.748, 0.749, 0.751, 0.753, 0.754, 0.755, 0.755, 0.755, 0.755, 0.756, 0.757, 0.758, 0.759,
+        0.759, 0.759, 0.759,
+    ],
+    [
+        0.111, 0.121, 0.127, 0.129, 0.127, 0.121, 0.116, 0.112, 0.108, 0.105, 0.104, 0.104, 0.105,
+        0.106, 0.110, 0.115, 0.123, 0.134, 0.148, 0.167, 0.192, 0.219, 0.252, 0.291, 0.325, 0.347,
+        0.356, 0.353, 0.346, 0.333, 0.314, 0.294, 0.271, 0.248, 0.227, 0.206, 0.188, 0.170, 0.153,
+        0.138, 0.125, 0.114, 0.106, 0.100, 0.096, 0.092, 0.090, 0.087, 0.085, 0.082, 0.080, 0.079,
+        0.078, 0.078, 0.078, 0.078, 0.081, 0.083, 0.088, 0.093, 0.102, 0.112, 0.125, 0.141, 0.161,
+        0.182, 0.203, 0.223, 0.242, 0.257, 0.270, 0.282, 0.292, 0.302, 0.310, 0.314, 0.317, 0.323,
+        0.330, 0.334, 0.338,
+    ],
+    [
+        0.120, 0.103, 0.090, 0.082, 0.076, 0.068, 0.064, 0.065, 0.075, 0.093, 0.123, 0.160, 0.207,
+        0.256, 0.300, 0.331, 0.346, 0.347, 0.341, 0.328, 0.307, 0.282, 0.257, 0.230, 0.204, 0.178,
+        0.154, 0.129, 0.109, 0.090, 0.075, 0.062, 0.051, 0.041, 0.035, 0.029, 0.025, 0.022, 0.019,
+        0.017, 0.017, 0.017, 0.016, 0.016, 0.016, 0.016, 0.016, 0.016, 0.016, 0.016, 0.018, 0.018,
+        0.018, 0.018, 0.019, 0.020, 0.023, 0.024, 0.026, 0.030, 0.035, 0.043, 0.056, 0.074, 0.097,
+        0.128, 0.166, 0.210, 0.257, 0.305, 0.354, 0.401, 0.446, 0.485, 0.520, 0.551, 0.577, 0.599,
+        0.618, 0.633, 0.645,
+    ],
+    [
+        0.104, 0.127, 0.161, 0.211, 0.264, 0.313, 0.341, 0.352, 0.359, 0.361, 0.364, 0.365, 0.367,
+        0.369, 0.372, 0.374, 0.376, 0.379, 0.384, 0.389, 0.397, 0.405, 0.416, 0.429, 0.443, 0.454,
+        0.461, 0.466, 0.469, 0.471, 0.474, 0.476, 0.483, 0.490, 0.506, 0.526, 0.553, 0.582, 0.618,
+        0.651, 0.680, 0.701, 0.717, 0.729, 0.736, 0.742, 0.745, 0.747, 0.748, 0.748, 0.748, 0.748,
+        0.748, 0.748, 0.748, 0.748, 0.747, 0.747, 0.747, 0.747, 0.747, 0.747, 0.747, 0.746, 0.746,
+        0.746, 0.745, 0.744, 0.743, 0.744, 0.745, 0.748, 0.750, 0.750, 0.749, 0.748, 0.748, 0.747,
+        0.747, 0.747, 0.747,
+    ],
+    [
+        0.036, 0.036, 0.037, 0.038, 0.039, 0.039, 0.040, 0.041, 0.042, 0.042, 0.043, 0.044, 0.044,
+        0.045, 0.045, 0.046, 0.047, 0.048, 0.050, 0.052, 0.055, 0.057, 0.062, 0.067, 0.075, 0.083,
+        0.092, 0.100, 0.108, 0.121, 0.133, 0.142, 0.150, 0.154, 0.155, 0.152, 0.147, 0.140, 0.133,
+        0.125, 0.118, 0.112, 0.106, 0.101, 0.098, 0.095, 0.093, 0.090, 0.089, 0.087, 0.086, 0.085,
+        0.084, 0.084, 0.084, 0.084, 0.085, 0.087, 0.092, 0.096, 0.102, 0.110, 0.123, 0.137, 0.152,
+        0.169, 0.188, 0.207, 0.226, 0.243, 0.260, 0.277, 0.294, 0.310, 0.325, 0.339, 0.353, 0.366,
+        0.379, 0.390, 0.399,
+    ],
+];
+
+/// CIE 13.3 color rendering indices.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct ColorRendering {
+    /// General color rendering index, the mean of R1 to R8.
+    pub ra: f32,
+    /// Special color rendering indices R1 to R14.
+    pub special: [f32; 14],
+}
+
+/// Color rendering of the light source with the spectral power distribution `spectrum`.
+///
+/// The spectrum has to be sorted by wavelength and calibrated in intensity, otherwise the
+/// result is meaningless. Returns `None` if the CCT is out of range.
+pub fn color_rendering(spectrum: &[SpectrumPoint]) -> Option<ColorRendering> {
+    let cct = colorimetry(spectrum)?.cct?;
+    let test = resample(spectrum);
+    let reference = reference_illuminant(cct);
+
+    let test_colors = sample_colors(&test)?;
+    let reference_colors = sample_colors(&reference)?;
+
+    let mut special = [0f32; 14];
+    for (i, r) in special.iter_mut().enumerate() {
+        let test = uvw(
+            test_colors.adapted(i, &reference_colors),
+            reference_colors.white,
+        );
+        let reference = uvw(reference_colors.samples[i], reference_colors.white);
+        let delta_e = (0..3)
+            .map(|c| (test[c] - reference[c]).powi(2))
+            .sum::<f64>()
+            .sqrt();
+        *r = (100. - 4.6 * delta_e) as f32;
+    }
+    Some(ColorRendering {
+        ra: special[..8].iter().sum::<f32>() / 8.,
+        special,
+    })
+}
+
+/// Spectrum linearly interpolated on the 5 nm grid, zero outside of the measured range.
+fn resample(spectrum: &[SpectrumPoint]) -> [f64; POINTS] {
+    let mut resampled = [0.; POINTS];
+    for (i, value) in resampled.iter_mut().enumerate() {
+        let wavelength = (START + i as f64 * STEP) as f32;
+        *value = spectrum
+            .windows(2)
+            .find(|p| wavelength >= p[0].wavelength && wavelength <= p[1].wavelength)
+            .map_or(0., |p| {
+                let width = p[1].wavelength - p[0].wavelength;
+                if width > 0. {
+                    (p[0].value
+                        + (wavelength - p[0].wavelength) / width * (p[1].value - p[0].value))
+                        as f64
+                } else {
+                    p[0].value as f64
+                }
+            });
+    }
+    resampled
+}
+
+/// Black body below 5000 K, CIE daylight above.
+fn reference_illuminant(cct: f32) -> [f64; POINTS] {
+    let t = cct as f64;
+    let mut spd = [0.; POINTS];
+    if cct < DAYLIGHT_CCT {
+        for (i, value) in spd.iter_mut().enumerate() {
+            let wavelength = START + i as f64 * STEP;
+            *value = planck(wavelength, t);
+        }
+    } else {
+        let x = if t <= 7000. {
+            -4.6070e9 / t.powi(3) + 2.9678e6 / t.powi(2) + 0.09911e3 / t + 0.244063
+        } else {
+            -2.0064e9 / t.powi(3) + 1.9018e6 / t.powi(2) + 0.24748e3 / t + 0.237040
+        };
+        let y = -3. * x * x + 2.87 * x - 0.275;
+        let m = 0.0241 + 0.2562 * x - 0.7341 * y;
+        let m1 = (-1.3515 - 1.7703 * x + 5.9114 * y) / m;
+        let m2 = (0.0300 - 31.4424 * x + 30.0717 * y) / m;
+        for (i, value) in spd.iter_mut().enumerate() {
+            *value = DAYLIGHT[0][i] + m1 * DAYLIGHT[1][i] + m2 * DAYLIGHT[2][i];
+        }
+    }
+    spd
+}
+
+/// CIE 1960 u, v and luminance factor Y of the illuminant and the test color samples.
+struct SampleColors {
+    white: (f64, f64, f64),
+    samples: [(f64, f64, f64); 14],
+}
+
+impl SampleColors {
+    /// Von Kries adapted color of sample `i` under the white point of `reference`.
+    fn adapted(&self, i: usize, reference: &SampleColors) -> (f64, f64, f64) {
+        let c = |(u, v, _): (f64, f64, f64)| (4. - u - 10. * v) / v;
+        let d = |(u, v, _): (f64, f64, f64)| (1.708 * v + 0.404 - 1.481 * u) / v;
+        let c_ratio = c(reference.white) / c(self.white);
+        let d_ratio = d(reference.white) / d(self.white);
+        let (ci, di) = (c(self.samples[i]), d(self.samples[i]));
+        let denominator = 16.518 + 1.481 * c_ratio * ci - d_ratio * di;
+        (
+            (10.872 + 0.404 * c_ratio * ci - 4. * d_ratio * di) / denominator,
+            5.520 / denominator,
+            self.samples[i].2,
+        )
+    }
+}
+
+fn sample_colors(spd: &[f64; POINTS]) -> Option<SampleColors> {
+    let cmf: Vec<[f32; 3]> = (0..POINTS)
+        .map(|i| color_matching_functions((START + i as f64 * STEP) as f32))
+        .collect();
+    let tristimulus = |reflectance: &dyn Fn(usize) -> f64| {
+        let mut xyz = [0f64; 3];
+        for i in 0..POINTS {
+            (0..3).for_each(|c| xyz[c] += spd[i] * reflectance(i) * cmf[i][c] as f64);
+        }
+        xyz
+    };
+    let white = tristimulus(&|_| 1.);
+    if white[1] <= 0. {
+        return None;
+    }
+    let uvy = |xyz: [f64; 3]| {
+        let denominator = xyz[0] + 15. * xyz[1] + 3. * xyz[2];
+        (
+            4. * xyz[0] / denominator,
+            6. * xyz[1] / denominator,
+            100. * xyz[1] / white[1],
+        )
+    };
+    Some(SampleColors {
+        white: uvy(white),
+        samples: TEST_COLOR_SAMPLES.map(|reflectance| uvy(tristimulus(&|i| reflectance[i]))),
+    })
+}
+
+/// CIE 1964 U*V*W* relative to the white point.
+fn uvw((u, v, y): (f64, f64, f64), white: (f64, f64, f64)) -> [f64; 3] {
+    let w = 25. * y.cbrt() - 17.;
+    [13. * w * (u - white.0), 13. * w * (v - white.1), w]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spectrum(spd: [f64; POINTS]) -> Vec<SpectrumPoint> {
+        spd.iter()
+            .enumerate()
+            .map(|(i, v)| SpectrumPoint {
+                wavelength: (START + i as f64 * STEP) as f32,
+                value: *v as f32,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reference_renders_perfectly() {
+        for cct in [2700., 4000., 6500.] {
+            let cri = color_rendering(&spectrum(reference_illuminant(cct))).unwrap();
+            assert!(cri.ra > 99.5, "{} K: {:?}", cct, cri);
+            assert!(cri.special.iter().all(|r| *r > 99.), "{} K: {:?}", cct, cri);
+        }
+    }
+
+    #[test]
+    fn narrow_band_renders_poorly() {
+        let mut spd = [0.; POINTS];
+        // Red, green and blue lines similar to an RGB LED
+        for i in [14, 15, 16, 30, 31, 32, 48, 49, 50] {
+            spd[i] = 1.;
+        }
+        let cri = color_rendering(&spectrum(spd)).unwrap();
+        assert!(cri.ra < 80., "{:?}", cri);
+        assert_eq!(color_rendering(&[]), None);
+    }
+
+    #[test]
+    fn cie_f2() {
+        // CIE standard illuminant F2, cool white fluorescent lamp
+        let f2 = [
+            1.18, 1.48, 1.84, 2.15, 3.44, 15.69, 3.85, 3.74, 4.19, 4.62, 5.06, 34.98, 11.81, 6.27,
+            6.63, 6.93, 7.19, 7.40, 7.54, 7.62, 7.65, 7.62, 7.62, 7.45, 7.28, 7.15, 7.05, 7.04,
+            7.16, 7.47, 8.04, 8.88, 10.01, 24.88, 16.64, 14.59, 16.16, 17.56, 18.62, 21.47, 22.79,
+            19.29, 18.66, 17.73, 16.54, 15.21, 13.80, 12.36, 10.95, 9.65, 8.40, 7.32, 6.31, 5.43,
+            4.68, 4.02, 3.45, 2.96, 2.55, 2.19, 1.89, 1.64, 1.53, 1.27, 1.10, 0.99, 0.88, 0.76,
+            0.68, 0.61, 0.56, 0.54, 0.51, 0.47, 0.47, 0.43, 0.46, 0.47, 0.40, 0.33, 0.27,
+        ];
+        let cri = color_rendering(&spectrum(f2)).unwrap();
+        // Published in CIE 15:2004
+        assert_eq!(cri.ra.round(), 64.);
+        assert_eq!(
+            cri.special.map(|r| r.round() as i32),
+            [56, 77, 90, 57, 59, 67, 74, 33, -84, 45, 46, 54, 60, 94]
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::config::SpectrumPoint;
+
+const CMF_START: f32 = 380.;
+const CMF_STEP: f32 = 5.;
+/// CIE 1931 2° standard observer (x̄, ȳ, z̄) from 380 to 780 nm.
+const CIE_1931: [[f64; 3]; 81] = [
+    [0.001368, 0.000039, 0.006450],
+    [0.002236, 0.000064, 0.010550],
+    [0.004243, 0.000120, 0.020050],
+    [0.007650, 0.000217, 0.036210],
+    [0.014310, 0.000396, 0.067850],
+    [0.023190, 0.000640, 0.110200],
+    [0.043510, 0.001210, 0.207400],
+    [0.077630, 0.002180, 0.371300],
+    [0.134380, 0.004000, 0.645600],
+    [0.214770, 0.007300, 1.039050],
+    [0.283900, 0.011600, 1.385600],
+    [0.328500, 0.016840, 1.622960],
+    [0.348280, 0.023000, 1.747060],
+    [0.348060, 0.029800, 1.782600],
+    [0.336200, 0.038000, 1.772110],
+    [0.318700, 0.048000, 1.744100],
+    [0.290800, 0.060000, 1.669200],
+    [0.251100, 0.073900, 1.528100],
+    [0.195360, 0.090980, 1.287640],
+    [0.142100, 0.112600, 1.041900],
+    [0.095640, 0.139020, 0.812950],
+    [0.057950, 0.169300, 0.616200],
+    [0.032010, 0.208020, 0.465180],
+    [0.014700, 0.258600, 0.353300],
+    [0.004900, 0.323000, 0.272000],
+    [0.002400, 0.407300, 0.212300],
+    [0.009300, 0.503000, 0.158200],
+    [0.029100, 0.608200, 0.111700],
+    [0.063270, 0.710000, 0.078250],
+    [0.109600, 0.793200, 0.057250],
+    [0.165500, 0.862000, 0.042160],
+    [0.225750, 0.914850, 0.029840],
+    [0.290400, 0.954000, 0.020300],
+    [0.359700, 0.980300, 0.013400],
+    [0.433450, 0.994950, 0.008750],
+    [0.512050, 1.000000, 0.005750],
+    [0.594500, 0.995000, 0.003900],
+    [0.678400, 0.978600, 0.002750],
+    [0.762100, 0.952000, 0.002100],
+    [0.842500, 0.915400, 0.001800],
+    [0.916300, 0.870000, 0.001650],
+    [0.978600, 0.816300, 0.001400],
+    [1.026300, 0.757000, 0.001100],
+    [1.056700, 0.694900, 0.001000],
+    [1.062200, 0.631000, 0.000800],
+    [1.045600, 0.566800, 0.000600],
+    [1.002600, 0.503000, 0.000340],
+    [0.938400, 0.441200, 0.000240],
+    [0.854450, 0.381000, 0.000190],
+    [0.751400, 0.321000, 0.000100],
+    [0.642400, 0.265000, 0.000050],
+    [0.541900, 0.217000, 0.000030],
+    [0.447900, 0.175000, 0.000020],
+    [0.360800, 0.138200, 0.000010],
+    [0.283500, 0.107000, 0.000000],
+    [0.218700, 0.081600, 0.000000],
+    [0.164900, 0.061000, 0.000000],
+    [0.121200, 0.044580, 0.000000],
+    [0.087400, 0.032000, 0.000000],
+    [0.063600, 0.023200, 0.000000],
+    [0.046770, 0.017000, 0.000000],
+    [0.032900, 0.011920, 0.000000],
+    [0.022700, 0.008210, 0.000000],
+    [0.015840, 0.005723, 0.000000],
+    [0.011359, 0.004102, 0.000000],
+    [0.008111, 0.002929, 0.000000],
+    [0.005790, 0.002091, 0.000000],
+    [0.004109, 0.001484, 0.000000],
+    [0.002899, 0.001047, 0.000000],
+    [0.002049, 0.000740, 0.000000],
+    [0.001440, 0.000520, 0.000000],
+    [0.001000, 0.000361, 0.000000],
+    [0.000690, 0.000249, 0.000000],
+    [0.000476, 0.000172, 0.000000],
+    [0.000332, 0.000120, 0.000000],
+    [0.000235, 0.000085, 0.000000],
+    [0.000166, 0.000060, 0.000000],
+    [0.000117, 0.000042, 0.000000],
+    [0.000083, 0.000030, 0.000000],
+    [0.000059, 0.000021, 0.000000],
+    [0.000042, 0.000015, 0.000000],
+];
+
+/// Second radiation constant in nm·K.
+pub const C2: f64 = 1.438777e7;
+const CCT_RANGE: (f64, f64) = (1000., 25000.);
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Colorimetry {
+    /// Tristimulus values, in units of the spectrum times nm.
+    pub xyz: [f32; 3],
+    pub x: f32,
+    pub y: f32,
+    /// CIE 1976 UCS coordinates.
+    pub u_prime: f32,
+    pub v_prime: f32,
+    /// Correlated color temperature in K, `None` outside of 1000 to 25000 K.
+    pub cct: Option<f32>,
+    /// Distance from the Planckian locus in CIE 1960 UCS, positive above the locus.
+    pub duv: Option<f32>,
+}
+
+/// Color matching functions linearly interpolated, zero outside of 380 to 780 nm.
+pub fn color_matching_functions(wavelength: f32) -> [f32; 3] {
+    let position = (wavelength - CMF_START) / CMF_STEP;
+    if !(0. ..=(CIE_1931.len() - 1) as f32).contains(&position) {
+        return [0.; 3];
+    }
+    let i = (position.floor() as usize).min(CIE_1931.len() - 2);
+    let t = position - i as f32;
+    let (a, b) = (CIE_1931[i], CIE_1931[i + 1]);
+    [0, 1, 2].map(|c| (a[c] + t as f64 * (b[c] - a[c])) as f32)
+}
+
+/// Chromaticity coordinates of the pure spectral colors, for drawing the horseshoe.
+pub fn spectral_locus() -> Vec<(f32, f32)> {
+    CIE_1931
+        .iter()
+        .filter_map(|[x, y, z]| {
+            let sum = x + y + z;
+            (sum > 0.).then(|| ((x / sum) as f32, (y / sum) as f32))
+        })
+        .collect()
+}
+
+/// Fold the spectrum, sorted by wavelength, with the CIE 1931 standard observer.
+///
+/// Returns `None` if the spectrum has no energy in the visible range.
+pub fn colorimetry(spectrum: &[SpectrumPoint]) -> Option<Colorimetry> {
+    let mut xyz = [0f32; 3];
+    for (i, sp) in spectrum.iter().enumerate() {
+        // Trapezoidal weights, which also work for an unevenly spaced axis
+        let previous = spectrum.get(i.wrapping_sub(1)).unwrap_or(sp).wavelength;
+        let next = spectrum.get(i + 1).unwrap_or(sp).wavelength;
+        let weight = (next - previous) / 2.;
+        let cmf = color_matching_functions(sp.wavelength);
+        (0..3).for_each(|c| xyz[c] += sp.value * cmf[c] * weight);
+    }
+
+    let sum: f32 = xyz.iter().sum();
+    if sum.is_nan() || sum <= 0. {
+        return None;
+    }
+    let (x, y) = (xyz[0] / sum, xyz[1] / sum);
+    let (u, v) = xy_to_uv(x as f64, y as f64);
+
+    let cct = correlated_color_temperature(u, v);
+    Some(Colorimetry {
+        xyz,
+        x,
+        y,
+        u_prime: u as f32,
+        v_prime: (1.5 * v) as f32,
+        cct: cct.map(|(cct, _)| cct as f32),
+        duv: cct.map(|(_, duv)| duv as f32),
+    })
+}
+
+/// Relative spectral radiance of a black body at `temperature` in K, with `wavelength` in nm.
+pub fn planck(wavelength: f64, temperature: f64) -> f64 {
+    wavelength.powi(-5) / (C2 / (wavelength * temperature)).exp_m1()
+}
+
+/// CIE 1960 UCS coordinates.
+fn xy_to_uv(x: f64, y: f64) -> (f64, f64) {
+    let denominator = -2. * x + 12. * y + 3.;
+    (4. * x / denominator, 6. * y / denominator)
+}
+
+/// Chromaticity of a black body in CIE 1960 UCS.
+fn planckian_uv(temperature: f64) -> (f64, f64) {
+    let mut xyz = [0f64; 3];
+    for (i, cmf) in CIE_1931.iter().enumerate() {
+        let wavelength = (CMF_START + i as f32 * CMF_STEP) as f64;
+        let radiance = planck(wavelength, temperature);
+        (0..3).for_each(|c| xyz[c] += radiance * cmf[c]);
+    }
+    let sum: f64 = xyz.iter().sum();
+    xy_to_uv(xyz[0] / sum, xyz[1] / sum)
+}
+
+/// Temperature of the closest point on the Planckian locus and the signed distance to it.
+///
+/// Coarse search on a logarithmic scale followed by a golden section search.
+fn correlated_color_temperature(u: f64, v: f64) -> Option<(f64, f64)> {
+    let distance = |t: f64| {
+        let (pu, pv) = planckian_uv(t);
+        (u - pu).hypot(v - pv)
+    };
+    let log_range = (CCT_RANGE.0.ln(), CCT_RANGE.1.ln());
+    let steps = 100;
+    let temperature =
+        |i: usize| (log_range.0 + (log_range.1 - log_range.0) * i as f64 / steps as f64).exp();
+    let best = (0..=steps)
+        .min_by(|a, b| distance(temperature(*a)).total_cmp(&distance(temperature(*b))))?;
+    if best == 0 || best == steps {
+        return None;
+    }
+
+    let ratio = (5f64.sqrt() - 1.) / 2.;
+    let (mut low, mut high) = (temperature(best - 1), temperature(best + 1));
+    for _ in 0..40 {
+        let a = high - ratio * (high - low);
+        let b = low + ratio * (high - low);
+        if distance(a) < distance(b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+    let cct = (low + high) / 2.;
+    let (_, pv) = planckian_uv(cct);
+    Some((cct, distance(cct).copysign(v - pv)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn black_body(temperature: f64) -> Vec<SpectrumPoint> {
+        (360..=800)
+            .map(|w| SpectrumPoint {
+                wavelength: w as f32,
+                value: (planck(w as f64, temperature) * 1e12) as f32,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chromaticity() {
+        let equal_energy: Vec<_> = (380..=780)
+            .map(|w| SpectrumPoint {
+                wavelength: w as f32,
+                value: 1.,
+            })
+            .collect();
+        let c = colorimetry(&equal_energy).unwrap();
+        assert_relative_eq!(c.x, 1. / 3., epsilon = 0.002);
+        assert_relative_eq!(c.y, 1. / 3., epsilon = 0.002);
+
+        // Illuminant A
+        let c = colorimetry(&black_body(2856.)).unwrap();
+        assert_relative_eq!(c.x, 0.4476, epsilon = 0.001);
+        assert_relative_eq!(c.y, 0.4074, epsilon = 0.001);
+        assert_relative_eq!(c.cct.unwrap(), 2856., max_relative = 0.005);
+        assert!(c.duv.unwrap().abs() < 1e-4);
+
+        assert_eq!(colorimetry(&[]), None);
+    }
+}
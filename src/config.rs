@@ -1,18 +1,37 @@
+use crate::camera_stream::interpolate;
+use crate::device_control::DeviceAction;
+use crate::expression::{Expression, ParsedExpression};
+use crate::flicker::synchronized_frames;
+use crate::pixel_format::PixelFormat;
+use crate::script::CompiledScript;
 use crate::serde::CameraFormatDef;
+use crate::spectrum::Spectrum;
 use egui::plot::{Line, Value, Values};
-use egui::Vec2;
+use egui::{Color32, Vec2};
 use glium::glutin::dpi::PhysicalSize;
-use nokhwa::CameraFormat;
-use serde::{Deserialize, Serialize};
+use nokhwa::{CameraFormat, CaptureAPIBackend};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_with::serde_as;
 use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr};
+use std::ops::RangeInclusive;
+use std::time::Duration;
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+/// Point of a custom linearization curve, both relative to full scale.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct LinearizationPoint {
+    pub value: f32,
+    pub linear: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum Linearize {
     Off,
     Rec601,
     Rec709,
     SRgb,
+    /// Curve sorted by value, interpolated linearly, see [crate::linearity].
+    Custom(Vec<LinearizationPoint>),
 }
 
 impl Display for Linearize {
@@ -22,6 +41,7 @@ impl Display for Linearize {
             Linearize::Rec601 => write!(f, "Rec. 601"),
             Linearize::Rec709 => write!(f, "Rec. 709"),
             Linearize::SRgb => write!(f, "sRGB"),
+            Linearize::Custom(_) => write!(f, "Custom"),
         }
     }
 }
@@ -44,19 +64,182 @@ impl Linearize {
                     ((value + 0.055) / 1.055).powf(2.4)
                 }
             }
+            Linearize::Custom(lut) => {
+                let i = lut.partition_point(|p| p.value <= value);
+                match (i.checked_sub(1).and_then(|i| lut.get(i)), lut.get(i)) {
+                    (Some(a), Some(b)) if b.value > a.value => {
+                        a.linear + (value - a.value) / (b.value - a.value) * (b.linear - a.linear)
+                    }
+                    (Some(a), _) => a.linear,
+                    (None, Some(b)) => b.linear,
+                    (None, None) => value,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum LowPowerMode {
+    Off,
+    On,
+    OnBattery,
+}
+
+impl Display for LowPowerMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LowPowerMode::Off => write!(f, "Off"),
+            LowPowerMode::On => write!(f, "On"),
+            LowPowerMode::OnBattery => write!(f, "On Battery"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    JcampDx,
+    Spc,
+    Hdf5,
+    SpectralWorkbench,
+    Theremino,
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Csv => write!(f, "CSV"),
+            ExportFormat::JcampDx => write!(f, "JCAMP-DX"),
+            ExportFormat::Spc => write!(f, "SPC"),
+            ExportFormat::Hdf5 => write!(f, "HDF5"),
+            ExportFormat::SpectralWorkbench => write!(f, "Spectral Workbench"),
+            ExportFormat::Theremino => write!(f, "Theremino"),
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::JcampDx => "jdx",
+            ExportFormat::Spc => "spc",
+            ExportFormat::Hdf5 => "h5",
+            ExportFormat::SpectralWorkbench => "json",
+            ExportFormat::Theremino => "txt",
         }
     }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
 pub struct ImportExportConfig {
     pub path: String,
+    pub post_export_command: String,
+    pub export_format: ExportFormat,
+    /// Name of exported files in the directory of `path`, see [crate::filename_template].
+    pub filename_template: String,
+    /// Value of the `{sample}` token.
+    pub sample: String,
+    /// Photo of a spectrum to analyze instead of a camera stream.
+    pub still_image_path: String,
+    pub export_traces: ExportTraces,
+    pub resampling: ResamplingConfig,
 }
 
 impl Default for ImportExportConfig {
     fn default() -> Self {
         Self {
             path: "spectrum.csv".to_string(),
+            post_export_command: String::new(),
+            export_format: ExportFormat::Csv,
+            filename_template: "{sample}_{date}_{seq}".to_string(),
+            sample: "spectrum".to_string(),
+            still_image_path: "spectrum.png".to_string(),
+            export_traces: ExportTraces::default(),
+            resampling: ResamplingConfig::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Resampling {
+    Off,
+    Linear,
+    /// Natural cubic spline.
+    Spline,
+}
+
+impl Display for Resampling {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Resampling::Off => write!(f, "Off"),
+            Resampling::Linear => write!(f, "Linear"),
+            Resampling::Spline => write!(f, "Cubic Spline"),
+        }
+    }
+}
+
+/// Uniform wavelength grid exported spectra are resampled onto, for tools requiring one.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct ResamplingConfig {
+    pub resampling: Resampling,
+    pub start: f32,
+    pub end: f32,
+    pub step: f32,
+}
+
+impl Default for ResamplingConfig {
+    fn default() -> Self {
+        Self {
+            resampling: Resampling::Off,
+            start: 380.,
+            end: 780.,
+            step: 1.,
+        }
+    }
+}
+
+impl ResamplingConfig {
+    /// Wavelengths of the grid in nm within `range`, rounded to avoid accumulated errors.
+    pub fn grid(&self, range: RangeInclusive<f32>) -> Vec<f32> {
+        if self.step <= 0. || self.end < self.start {
+            return vec![];
+        }
+        let n = ((self.end - self.start) / self.step + 1e-3).floor() as usize + 1;
+        (0..n)
+            .map(|i| ((self.start + i as f32 * self.step) * 1e4).round() / 1e4)
+            .filter(|w| range.contains(w))
+            .collect()
+    }
+}
+
+/// Columns of exported CSV files, to keep them small for tools needing only some.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct ExportTraces {
+    pub r: bool,
+    pub g: bool,
+    pub b: bool,
+    pub sum: bool,
+    pub detection_limit: bool,
+    /// One column per snapshot, named after it.
+    pub snapshots: bool,
+    pub reference: bool,
+}
+
+impl Default for ExportTraces {
+    fn default() -> Self {
+        Self {
+            r: true,
+            g: true,
+            b: true,
+            sum: true,
+            detection_limit: true,
+            snapshots: false,
+            reference: false,
         }
     }
 }
@@ -67,253 +250,2366 @@ pub struct SpectrumPoint {
     pub value: f32,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct WavelengthBand {
+    pub start: f32,
+    pub end: f32,
+}
+
+impl Display for WavelengthBand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{} nm", self.start, self.end)
+    }
+}
+
+impl WavelengthBand {
+    pub fn contains(&self, wavelength: f32) -> bool {
+        wavelength >= self.start && wavelength <= self.end
+    }
+
+    /// Mean value of all points inside the band or `None` if there are none.
+    pub fn mean_value(&self, spectrum: &[SpectrumPoint]) -> Option<f32> {
+        let (sum, count) = spectrum
+            .iter()
+            .filter(|sp| self.contains(sp.wavelength))
+            .fold((0., 0), |(sum, count), sp| (sum + sp.value, count + 1));
+        (count > 0).then(|| sum / count as f32)
+    }
+
+    /// Statistics of the points inside the band, `None` if there are less than two.
+    ///
+    /// `spectrum` has to be sorted by wavelength.
+    pub fn statistics(&self, spectrum: &[SpectrumPoint]) -> Option<BandStatistics> {
+        let points: Vec<_> = spectrum
+            .iter()
+            .filter(|sp| self.contains(sp.wavelength))
+            .collect();
+        if points.len() < 2 {
+            return None;
+        }
+        let (integral, moment) = points.windows(2).fold((0., 0.), |(area, moment), p| {
+            let width = p[1].wavelength - p[0].wavelength;
+            let value = (p[0].value + p[1].value) / 2.;
+            let center = (p[0].wavelength + p[1].wavelength) / 2.;
+            (area + width * value, moment + width * value * center)
+        });
+        Some(BandStatistics {
+            min: points
+                .iter()
+                .map(|sp| sp.value)
+                .fold(f32::INFINITY, f32::min),
+            max: points
+                .iter()
+                .map(|sp| sp.value)
+                .fold(f32::NEG_INFINITY, f32::max),
+            mean: points.iter().map(|sp| sp.value).sum::<f32>() / points.len() as f32,
+            integral,
+            centroid: moment / integral,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BandStatistics {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    /// Trapezoidal integral over wavelength in nm.
+    pub integral: f32,
+    /// Intensity weighted mean wavelength in nm, NaN for a zero integral.
+    pub centroid: f32,
+}
+
+/// Named expression over the spectrum, see [crate::expression::Expression].
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-pub struct ReferenceConfig {
-    pub reference: Option<Vec<SpectrumPoint>>,
-    pub scale: f32,
+pub struct DerivedValue {
+    pub name: String,
+    pub expression: String,
+    #[serde(skip)]
+    parsed: ParsedExpression,
 }
 
-impl Default for ReferenceConfig {
+impl DerivedValue {
+    pub fn new(name: String, expression: String) -> Self {
+        Self {
+            name,
+            expression,
+            parsed: ParsedExpression::default(),
+        }
+    }
+
+    pub fn eval(&self, spectrum: &[SpectrumPoint]) -> Result<f32, String> {
+        self.parsed.with(&self.expression, |e| e.eval(spectrum))
+    }
+}
+
+/// User script computing named values from the spectrum, see [crate::script].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct ScriptConfig {
+    /// Rhai source, empty for none.
+    pub source: String,
+    #[serde(skip)]
+    compiled: CompiledScript,
+}
+
+impl ScriptConfig {
+    /// Values of the script over `spectrum`, none without a script.
+    pub fn run(&self, spectrum: &[SpectrumPoint]) -> Result<Vec<(String, f32)>, String> {
+        if self.source.trim().is_empty() {
+            Ok(Vec::new())
+        } else {
+            self.compiled.run(&self.source, spectrum)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum LoggingMode {
+    FullSpectrum,
+    Bands,
+    DerivedValues,
+}
+
+impl Display for LoggingMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoggingMode::FullSpectrum => write!(f, "Full Spectrum"),
+            LoggingMode::Bands => write!(f, "Bands"),
+            LoggingMode::DerivedValues => write!(f, "Derived Values"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum LogFormat {
+    Csv,
+    Binary,
+    /// Binary stream compressed with zstd.
+    CompressedBinary,
+    /// Full spectrum with metadata in a SQLite database, see [crate::database].
+    Database,
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogFormat::Csv => write!(f, "CSV"),
+            LogFormat::Binary => write!(f, "Binary"),
+            LogFormat::CompressedBinary => write!(f, "Binary (zstd)"),
+            LogFormat::Database => write!(f, "SQLite"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum PeakShape {
+    #[default]
+    Gaussian,
+    Lorentzian,
+}
+
+impl Display for PeakShape {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeakShape::Gaussian => write!(f, "Gaussian"),
+            PeakShape::Lorentzian => write!(f, "Lorentzian"),
+        }
+    }
+}
+
+/// Photon energy in eV times wavelength in nm.
+const PHOTON_ENERGY_EV_NM: f64 = 1239.841984;
+/// Speed of light in nm·THz.
+const SPEED_OF_LIGHT_NM_THZ: f64 = 299792.458;
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum AxisUnit {
+    #[default]
+    Nanometer,
+    ElectronVolt,
+    Terahertz,
+    Wavenumber,
+    RamanShift,
+}
+
+impl Display for AxisUnit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AxisUnit::Nanometer => write!(f, "Wavelength"),
+            AxisUnit::ElectronVolt => write!(f, "Photon Energy"),
+            AxisUnit::Terahertz => write!(f, "Frequency"),
+            AxisUnit::Wavenumber => write!(f, "Wavenumber"),
+            AxisUnit::RamanShift => write!(f, "Raman Shift"),
+        }
+    }
+}
+
+impl AxisUnit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            AxisUnit::Nanometer => "nm",
+            AxisUnit::ElectronVolt => "eV",
+            AxisUnit::Terahertz => "THz",
+            AxisUnit::Wavenumber | AxisUnit::RamanShift => "cm⁻¹",
+        }
+    }
+
+    pub fn axis_label(&self) -> String {
+        format!("{} [{}]", self, self.symbol())
+    }
+
+    /// Decimal places for labels, giving a resolution of roughly 0.1 nm in the visible range.
+    pub fn precision(&self) -> usize {
+        match self {
+            AxisUnit::Nanometer | AxisUnit::Terahertz => 1,
+            AxisUnit::ElectronVolt => 4,
+            AxisUnit::Wavenumber | AxisUnit::RamanShift => 0,
+        }
+    }
+}
+
+/// Unit of the x axis of plots, labels and exports.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct AxisConfig {
+    pub unit: AxisUnit,
+    /// Excitation wavelength in nm for the Raman shift.
+    pub laser_wavelength: f32,
+    /// Leave out the Rayleigh scattered laser line around zero Raman shift from the plot,
+    /// peaks and exports.
+    pub mask_rayleigh: bool,
+    /// Half width in cm⁻¹ of the masked region around the laser line.
+    pub rayleigh_half_width: f32,
+    /// Convert intensities per nm to intensities per axis unit, instead of only relabeling the
+    /// axis.
+    pub jacobian: bool,
+}
+
+impl Default for AxisConfig {
     fn default() -> Self {
         Self {
-            reference: None,
-            scale: 1.0,
+            unit: AxisUnit::Nanometer,
+            laser_wavelength: 532.,
+            mask_rayleigh: false,
+            rayleigh_half_width: 150.,
+            jacobian: true,
         }
     }
 }
 
-impl ReferenceConfig {
-    pub fn to_line(&self) -> Option<Line> {
-        self.reference.as_ref().map(|reference| {
-            Line::new(Values::from_values_iter(
-                reference
-                    .iter()
-                    .map(|rp| Value::new(rp.wavelength, rp.value * self.scale)),
-            ))
-        })
+impl AxisConfig {
+    /// Convert a wavelength in nm to the axis unit.
+    pub fn convert(&self, wavelength: f32) -> f32 {
+        match self.unit {
+            AxisUnit::Nanometer => wavelength,
+            AxisUnit::ElectronVolt => (PHOTON_ENERGY_EV_NM / wavelength as f64) as f32,
+            AxisUnit::Terahertz => (SPEED_OF_LIGHT_NM_THZ / wavelength as f64) as f32,
+            AxisUnit::Wavenumber => 1e7 / wavelength,
+            AxisUnit::RamanShift => 1e7 / self.laser_wavelength - 1e7 / wavelength,
+        }
     }
 
-    pub fn get_value_at_wavelength(&self, wavelength: f32) -> Option<f32> {
-        self.reference.as_ref().map(|r| {
-            let mut sorted = r.clone();
-            sorted.sort_by(|a, b| a.wavelength.partial_cmp(&b.wavelength).unwrap());
-            let mut value = None;
-            for (rp1, rp2) in sorted.iter().zip(sorted[1..].iter()) {
-                if wavelength >= rp1.wavelength && wavelength <= rp2.wavelength {
-                    let a = (rp1.value - rp2.value) / (rp1.wavelength - rp2.wavelength);
-                    value = Some((a * wavelength + rp1.value - a * rp1.wavelength) * self.scale);
-                    break;
-                }
+    /// Convert a value in the axis unit back to a wavelength in nm.
+    pub fn to_wavelength(&self, value: f32) -> f32 {
+        match self.unit {
+            AxisUnit::Nanometer => value,
+            AxisUnit::ElectronVolt => (PHOTON_ENERGY_EV_NM / value as f64) as f32,
+            AxisUnit::Terahertz => (SPEED_OF_LIGHT_NM_THZ / value as f64) as f32,
+            AxisUnit::Wavenumber => 1e7 / value,
+            AxisUnit::RamanShift => 1e7 / (1e7 / self.laser_wavelength - value),
+        }
+    }
+
+    /// Whether `wavelength` is within the masked region of the Rayleigh line.
+    pub fn is_masked(&self, wavelength: f32) -> bool {
+        self.unit == AxisUnit::RamanShift
+            && self.mask_rayleigh
+            && self.convert(wavelength).abs() < self.rayleigh_half_width
+    }
+
+    /// Width in the axis unit of a band of `width` nm around `wavelength`.
+    pub fn convert_width(&self, wavelength: f32, width: f32) -> f32 {
+        (self.convert(wavelength + width / 2.) - self.convert(wavelength - width / 2.)).abs()
+    }
+
+    pub fn format(&self, wavelength: f32) -> String {
+        format!("{:.*}", self.unit.precision(), self.convert(wavelength))
+    }
+
+    /// Whether intensities are converted to intensities per axis unit.
+    pub fn converts_intensity(&self) -> bool {
+        self.jacobian && self.unit != AxisUnit::Nanometer
+    }
+
+    /// Factor |dλ/dx| from an intensity per nm to an intensity per axis unit at `wavelength`,
+    /// e.g. λ²/hc for the photon energy.
+    pub fn jacobian(&self, wavelength: f32) -> f32 {
+        if !self.jacobian {
+            return 1.;
+        }
+        let nm_times_unit = match self.unit {
+            AxisUnit::Nanometer => return 1.,
+            AxisUnit::ElectronVolt => PHOTON_ENERGY_EV_NM,
+            AxisUnit::Terahertz => SPEED_OF_LIGHT_NM_THZ,
+            AxisUnit::Wavenumber | AxisUnit::RamanShift => 1e7,
+        };
+        (wavelength as f64 * wavelength as f64 / nm_times_unit) as f32
+    }
+
+    /// Plot position of an intensity per nm at `wavelength`.
+    pub fn plot_value(&self, wavelength: f32, value: f32) -> Value {
+        Value::new(self.convert(wavelength), value * self.jacobian(wavelength))
+    }
+
+    /// Symbol of the unit the intensities are given per.
+    pub fn intensity_per(&self) -> &'static str {
+        if self.converts_intensity() {
+            self.unit.symbol()
+        } else {
+            AxisUnit::Nanometer.symbol()
+        }
+    }
+
+    /// Label of the intensity axis in `unit`, per axis unit if converted.
+    pub fn intensity_label(&self, unit: IntensityUnit) -> String {
+        match unit {
+            _ if !self.converts_intensity() => unit.axis_label(),
+            IntensityUnit::SpectralIrradiance => {
+                format!("Spectral Irradiance [W·m⁻²/{}]", self.unit.symbol())
             }
-            value.unwrap_or(0.)
+            _ => format!("{} per {}", unit.axis_label(), self.unit.symbol()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum SmoothingMethod {
+    #[default]
+    None,
+    LowPass,
+    SavitzkyGolay,
+    Median,
+    Gaussian,
+}
+
+impl Display for SmoothingMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SmoothingMethod::None => write!(f, "None"),
+            SmoothingMethod::LowPass => write!(f, "Low-Pass"),
+            SmoothingMethod::SavitzkyGolay => write!(f, "Savitzky-Golay"),
+            SmoothingMethod::Median => write!(f, "Median"),
+            SmoothingMethod::Gaussian => write!(f, "Gaussian"),
+        }
+    }
+}
+
+impl SmoothingMethod {
+    /// Deserialize a method or the former low-pass filter switch.
+    fn deserialize_or_filter_active<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum MethodOrSwitch {
+            Method(SmoothingMethod),
+            Switch(bool),
+        }
+
+        Ok(match MethodOrSwitch::deserialize(deserializer)? {
+            MethodOrSwitch::Method(method) => method,
+            MethodOrSwitch::Switch(true) => SmoothingMethod::LowPass,
+            MethodOrSwitch::Switch(false) => SmoothingMethod::None,
         })
     }
 }
 
+/// How the rows of the window are combined into the spectrum.
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
-pub struct SpectrumWindow {
-    pub offset: Vec2,
-    pub size: Vec2,
+pub enum BinningMode {
+    /// Sum of the rows. As the spectrum is relative to the full scale of the window, this is
+    /// the same as their mean.
+    #[default]
+    Sum,
+    /// Median of the rows, which rejects hot pixels and cosmic ray hits.
+    Median,
+    /// Sum weighted with a Gaussian around a center row, to favor the core of the spectrum.
+    Gaussian,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
-pub struct CameraControl {
-    pub id: u32,
-    pub name: String,
-    pub value: i32,
+impl Display for BinningMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinningMode::Sum => write!(f, "Sum (Mean)"),
+            BinningMode::Median => write!(f, "Median"),
+            BinningMode::Gaussian => write!(f, "Gaussian"),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
-pub struct ViewConfig {
-    pub window_size: PhysicalSize<u32>,
-    pub image_scale: f32,
-    pub draw_spectrum_r: bool,
-    pub draw_spectrum_g: bool,
-    pub draw_spectrum_b: bool,
-    pub draw_spectrum_combined: bool,
-    pub draw_peaks: bool,
-    pub draw_dips: bool,
-    pub peaks_dips_unique_window: f32,
-    pub peaks_dips_find_window: usize,
-    pub show_camera_window: bool,
-    pub show_calibration_window: bool,
-    pub show_postprocessing_window: bool,
-    pub show_camera_control_window: bool,
-    pub show_import_export_window: bool,
+#[serde(default)]
+pub struct Binning {
+    pub mode: BinningMode,
+    /// Center row of the Gaussian weights, counted from the top of the window.
+    pub center: f32,
+    /// Standard deviation of the Gaussian weights in rows.
+    pub sigma: f32,
+}
+
+impl Default for Binning {
+    fn default() -> Self {
+        Self {
+            mode: BinningMode::Sum,
+            center: 0.,
+            sigma: 2.,
+        }
+    }
+}
+
+impl Binning {
+    /// Weight of each of the `rows` of the window, adding up to 1. Empty for the median.
+    pub fn weights(&self, rows: usize) -> Vec<f32> {
+        match self.mode {
+            BinningMode::Sum => vec![1. / rows as f32; rows],
+            BinningMode::Median => vec![],
+            BinningMode::Gaussian => {
+                let sigma = self.sigma.max(0.1);
+                let weights: Vec<f32> = (0..rows)
+                    .map(|row| (-(row as f32 - self.center).powi(2) / (2. * sigma.powi(2))).exp())
+                    .collect();
+                let sum = weights.iter().sum::<f32>();
+                weights.into_iter().map(|w| w / sum).collect()
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct LoggingConfig {
+    pub path: String,
+    pub interval: f32,
+    pub mode: LoggingMode,
+    pub format: LogFormat,
+    pub bands: Vec<WavelengthBand>,
+    /// Stored with every measurement in the database.
+    pub notes: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            path: "spectrum_log.csv".to_string(),
+            interval: 1.,
+            mode: LoggingMode::FullSpectrum,
+            format: LogFormat::Csv,
+            bands: vec![WavelengthBand {
+                start: 530.,
+                end: 560.,
+            }],
+            notes: String::new(),
+        }
+    }
+}
+
+/// Bands whose integrated intensity is followed over time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BandMonitorConfig {
+    pub bands: Vec<WavelengthBand>,
+    /// Seconds between samples.
+    pub interval: f32,
+    /// Seconds of samples kept.
+    pub span: f32,
+}
+
+impl Default for BandMonitorConfig {
+    fn default() -> Self {
+        Self {
+            bands: vec![WavelengthBand {
+                start: 530.,
+                end: 560.,
+            }],
+            interval: 1.,
+            span: 3600.,
+        }
+    }
+}
+
+/// Standard of known concentration for the calibration curve of the quantitation. The spectrum
+/// is kept to get its absorbance at any wavelength.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct QuantitationStandard {
+    pub concentration: f32,
+    pub spectrum: Vec<SpectrumPoint>,
+}
+
+/// Concentration of a sample from its absorbance at one wavelength, following Beer–Lambert.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct QuantitationConfig {
+    pub wavelength: f32,
+    /// Width in nm the absorbance is averaged over.
+    pub bandwidth: f32,
+    pub concentration_unit: String,
+    /// Spectrum of the blank, e.g. the cuvette with the solvent only.
+    pub blank: Option<Vec<SpectrumPoint>>,
+    pub standards: Vec<QuantitationStandard>,
+}
+
+impl Default for QuantitationConfig {
+    fn default() -> Self {
+        Self {
+            wavelength: 550.,
+            bandwidth: 5.,
+            concentration_unit: "mg/L".to_string(),
+            blank: None,
+            standards: vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum IntensityUnit {
+    /// Uncalibrated camera values, normalized to full scale.
+    #[default]
+    Counts,
+    /// Calibrated against a reference of unknown absolute intensity.
+    Relative,
+    SpectralIrradiance,
+}
+
+impl Display for IntensityUnit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntensityUnit::Counts => write!(f, "Counts"),
+            IntensityUnit::Relative => write!(f, "Relative"),
+            IntensityUnit::SpectralIrradiance => write!(f, "Spectral Irradiance"),
+        }
+    }
+}
+
+impl IntensityUnit {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            IntensityUnit::Counts => "normalized counts",
+            IntensityUnit::Relative => "a.u.",
+            IntensityUnit::SpectralIrradiance => "W·m⁻²·nm⁻¹",
+        }
+    }
+
+    pub fn axis_label(&self) -> String {
+        match self {
+            IntensityUnit::Counts => format!("Intensity [{}]", self.symbol()),
+            IntensityUnit::Relative => format!("Relative Intensity [{}]", self.symbol()),
+            IntensityUnit::SpectralIrradiance => format!("Spectral Irradiance [{}]", self.symbol()),
+        }
+    }
+
+    /// Value of the JCAMP-DX `YUNITS` label.
+    pub fn jcamp_units(&self) -> &'static str {
+        match self {
+            IntensityUnit::Counts => "COUNTS",
+            IntensityUnit::Relative => "ARBITRARY UNITS",
+            IntensityUnit::SpectralIrradiance => "W/(M2 NM)",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct ReferenceConfig {
+    pub reference: Option<Vec<SpectrumPoint>>,
+    pub scale: f32,
+    pub unit: IntensityUnit,
+}
+
+impl Default for ReferenceConfig {
+    fn default() -> Self {
+        Self {
+            reference: None,
+            scale: 1.0,
+            unit: IntensityUnit::Relative,
+        }
+    }
+}
+
+impl ReferenceConfig {
+    pub fn to_line(&self, axis: &AxisConfig) -> Option<Line> {
+        self.reference.as_ref().map(|reference| {
+            Line::new(Values::from_values_iter(
+                reference
+                    .iter()
+                    .map(|rp| axis.plot_value(rp.wavelength, rp.value * self.scale)),
+            ))
+        })
+    }
+
+    pub fn get_value_at_wavelength(&self, wavelength: f32) -> Option<f32> {
+        self.reference.as_ref().map(|r| {
+            let mut sorted = r.clone();
+            sorted.sort_by(|a, b| a.wavelength.partial_cmp(&b.wavelength).unwrap());
+            let mut value = None;
+            for (rp1, rp2) in sorted.iter().zip(sorted[1..].iter()) {
+                if wavelength >= rp1.wavelength && wavelength <= rp2.wavelength {
+                    let a = (rp1.value - rp2.value) / (rp1.wavelength - rp2.wavelength);
+                    value = Some((a * wavelength + rp1.value - a * rp1.wavelength) * self.scale);
+                    break;
+                }
+            }
+            value.unwrap_or(0.)
+        })
+    }
+}
+
+/// Frozen spectrum drawn under the live data.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Snapshot {
+    pub name: String,
+    pub color: Color32,
+    pub visible: bool,
+    #[serde(default)]
+    pub unit: IntensityUnit,
+    pub spectrum: Vec<SpectrumPoint>,
+}
+
+impl Snapshot {
+    pub fn to_line(&self, axis: &AxisConfig) -> Line {
+        Line::new(Values::from_values_iter(
+            self.spectrum
+                .iter()
+                .map(|sp| axis.plot_value(sp.wavelength, sp.value)),
+        ))
+        .color(self.color)
+        .name(&self.name)
+    }
+}
+
+/// Lowest quantum efficiency which is corrected, to limit noise amplification at the edges.
+const MIN_QUANTUM_EFFICIENCY: f32 = 0.05;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+#[serde(default)]
+pub struct QePoint {
+    pub wavelength: f32,
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+/// Per channel quantum efficiency of a camera sensor.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct QeCurve {
+    pub name: String,
+    pub points: Vec<QePoint>,
+}
+
+impl QeCurve {
+    /// Linearly interpolated (r, g, b) quantum efficiency, constant outside of the curve.
+    pub fn get_qe_at_wavelength(&self, wavelength: f32) -> (f32, f32, f32) {
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return (1., 1., 1.),
+        };
+        let p = if wavelength <= first.wavelength {
+            *first
+        } else if wavelength >= last.wavelength {
+            *last
+        } else {
+            let i = self
+                .points
+                .iter()
+                .position(|p| p.wavelength > wavelength)
+                .unwrap_or(self.points.len() - 1);
+            let (p1, p2) = (self.points[i - 1], self.points[i]);
+            let t = (wavelength - p1.wavelength) / (p2.wavelength - p1.wavelength);
+            QePoint {
+                wavelength,
+                r: p1.r + t * (p2.r - p1.r),
+                g: p1.g + t * (p2.g - p1.g),
+                b: p1.b + t * (p2.b - p1.b),
+            }
+        };
+        (p.r, p.g, p.b)
+    }
+
+    /// Factors which flatten the sensor response relative to its most sensitive channel.
+    pub fn get_correction_at_wavelength(&self, wavelength: f32) -> (f32, f32, f32) {
+        let max = self
+            .points
+            .iter()
+            .map(|p| p.r.max(p.g).max(p.b))
+            .fold(MIN_QUANTUM_EFFICIENCY, f32::max);
+        let (r, g, b) = self.get_qe_at_wavelength(wavelength);
+        let correction = |qe: f32| max / qe.max(MIN_QUANTUM_EFFICIENCY);
+        (correction(r), correction(g), correction(b))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub struct SpectrumWindow {
+    pub offset: Vec2,
+    pub size: Vec2,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CameraControl {
+    pub id: u32,
+    pub name: String,
+    pub value: i32,
+}
+
+/// Width of the window around a point in which a peak or dip has to be the extreme.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum PeakWindow {
+    /// Points to either side.
+    Samples(usize),
+    /// Full width in nm, converted to points at every position of the spectrum.
+    Nanometer(f32),
+}
+
+impl PeakWindow {
+    /// Points covered to the left and right of `index`, at least one each, or `None` if the
+    /// window does not fit into `spectrum` at this position.
+    pub fn extent(&self, spectrum: &[SpectrumPoint], index: usize) -> Option<(usize, usize)> {
+        let last = spectrum.len().checked_sub(1)?;
+        match *self {
+            PeakWindow::Samples(half_width) => {
+                let half_width = half_width.max(1);
+                (index >= half_width && index + half_width <= last)
+                    .then_some((half_width, half_width))
+            }
+            PeakWindow::Nanometer(width) => {
+                let center = spectrum.get(index)?.wavelength;
+                let half_width = width / 2.;
+                let within = |p: &SpectrumPoint| (p.wavelength - center).abs() <= half_width;
+                if within(&spectrum[0]) || within(&spectrum[last]) {
+                    return None;
+                }
+                let left = spectrum[..index]
+                    .iter()
+                    .rev()
+                    .take_while(|p| within(p))
+                    .count();
+                let right = spectrum[index + 1..]
+                    .iter()
+                    .take_while(|p| within(p))
+                    .count();
+                Some((left.max(1), right.max(1)))
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct ViewConfig {
+    pub window_size: PhysicalSize<u32>,
+    pub image_scale: f32,
+    pub draw_spectrum_r: bool,
+    pub draw_spectrum_g: bool,
+    pub draw_spectrum_b: bool,
+    pub draw_spectrum_combined: bool,
+    pub draw_peaks: bool,
+    pub draw_dips: bool,
+    pub draw_detection_limit: bool,
+    pub fit_peaks: bool,
+    pub peak_shape: PeakShape,
+    pub axis: AxisConfig,
+    pub peaks_dips_unique_window: f32,
+    pub peaks_dips_find_window: usize,
+    /// Find window in nm, used instead of `peaks_dips_find_window` if
+    /// `peaks_dips_find_in_nm` is set.
+    pub peaks_dips_find_width: f32,
+    pub peaks_dips_find_in_nm: bool,
+    pub show_camera_window: bool,
+    pub show_calibration_window: bool,
+    pub show_postprocessing_window: bool,
+    pub show_camera_control_window: bool,
+    pub show_import_export_window: bool,
+    pub show_snapshot_window: bool,
+    pub show_peak_table_window: bool,
+    pub show_peak_diff_window: bool,
+    /// Largest wavelength difference in nm of peaks considered the same when comparing.
+    pub peak_match_tolerance: f32,
+    pub show_identification_window: bool,
+    /// Largest distance in nm of a peak from an emission line to count as match.
+    pub line_tolerance: f32,
+    pub show_dashboard_window: bool,
+    pub show_colorimetry_window: bool,
+    pub show_photobiology_window: bool,
+    pub show_buffer_window: bool,
+    pub show_filter_pair_window: bool,
+    pub show_second_camera_window: bool,
+    pub show_notifications_window: bool,
+    pub show_band_monitor_window: bool,
+    pub show_quantitation_window: bool,
+    pub show_lamp_check_window: bool,
+    pub show_linearity_window: bool,
+    pub show_lock_in_window: bool,
+    pub show_device_control_window: bool,
+    pub show_gpio_window: bool,
+    pub show_filter_wheel_window: bool,
+    pub show_database_window: bool,
+    /// Show acquisition parameters on the plot, also in exported plot images.
+    pub show_acquisition_overlay: bool,
+    /// Show window column indices along the top of the plot.
+    pub show_pixel_axis: bool,
+    /// Show the brightness of each row of the window in the camera window.
+    pub show_row_profile: bool,
+    pub low_power_mode: LowPowerMode,
+    pub low_power_refresh_rate: f32,
+    pub low_power_max_points: usize,
+    pub touch_mode: bool,
+}
+
+impl Default for ViewConfig {
+    fn default() -> Self {
+        Self {
+            window_size: PhysicalSize::new(800, 600),
+            image_scale: 0.25,
+            draw_spectrum_r: false,
+            draw_spectrum_g: false,
+            draw_spectrum_b: false,
+            draw_spectrum_combined: true,
+            draw_peaks: true,
+            draw_dips: true,
+            draw_detection_limit: false,
+            fit_peaks: false,
+            peak_shape: PeakShape::Gaussian,
+            axis: Default::default(),
+            peaks_dips_unique_window: 50.,
+            peaks_dips_find_window: 5,
+            peaks_dips_find_width: 5.,
+            peaks_dips_find_in_nm: false,
+            show_camera_window: true,
+            show_calibration_window: false,
+            show_postprocessing_window: false,
+            show_camera_control_window: false,
+            show_import_export_window: false,
+            show_snapshot_window: false,
+            show_peak_table_window: false,
+            show_peak_diff_window: false,
+            peak_match_tolerance: 5.,
+            show_identification_window: false,
+            line_tolerance: 1.5,
+            show_dashboard_window: false,
+            show_colorimetry_window: false,
+            show_photobiology_window: false,
+            show_buffer_window: false,
+            show_filter_pair_window: false,
+            show_second_camera_window: false,
+            show_notifications_window: false,
+            show_band_monitor_window: false,
+            show_quantitation_window: false,
+            show_lamp_check_window: false,
+            show_linearity_window: false,
+            show_lock_in_window: false,
+            show_device_control_window: false,
+            show_gpio_window: false,
+            show_filter_wheel_window: false,
+            show_database_window: false,
+            show_acquisition_overlay: false,
+            show_pixel_axis: false,
+            show_row_profile: false,
+            low_power_mode: LowPowerMode::Off,
+            low_power_refresh_rate: 5.,
+            low_power_max_points: 200,
+            touch_mode: false,
+        }
+    }
+}
+
+impl ViewConfig {
+    pub fn peak_find_window(&self) -> PeakWindow {
+        if self.peaks_dips_find_in_nm {
+            PeakWindow::Nanometer(self.peaks_dips_find_width)
+        } else {
+            PeakWindow::Samples(self.peaks_dips_find_window)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct WindowPreset {
+    pub name: String,
+    pub window: SpectrumWindow,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct FrameIntegrityConfig {
+    pub active: bool,
+    /// Largest change of the mean frame intensity relative to the last accepted frame.
+    pub max_intensity_jump: f32,
+    /// Largest step between adjacent rows of the change to the last accepted frame, in grey
+    /// levels.
+    pub max_row_step: f32,
+}
+
+impl Default for FrameIntegrityConfig {
+    fn default() -> Self {
+        Self {
+            active: false,
+            max_intensity_jump: 0.3,
+            max_row_step: 20.,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ImageConfig {
+    pub window: SpectrumWindow,
+    pub flip: bool,
+    /// Rotation of the window around its center in degrees, clockwise in the preview.
+    pub rotation: f32,
+    pub presets: Vec<WindowPreset>,
+    pub frame_integrity: FrameIntegrityConfig,
+    /// Vertical offset of the reference strip of the reference beam mode, which has the
+    /// horizontal position and the size of the window, `None` without.
+    pub reference_strip: Option<f32>,
+    pub binning: Binning,
+    /// Hot pixels replaced by their neighbors before the windows are cut out.
+    pub hot_pixels: Option<HotPixelMap>,
+    /// Brightness above the neighbors from which a pixel of a dark frame counts as hot,
+    /// relative to full scale.
+    pub hot_pixel_threshold: f32,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            window: SpectrumWindow {
+                offset: Vec2::new(100., 500.),
+                size: Vec2::new(1500., 1.),
+            },
+            flip: true,
+            rotation: 0.,
+            presets: vec![],
+            frame_integrity: Default::default(),
+            reference_strip: None,
+            binning: Default::default(),
+            hot_pixels: None,
+            hot_pixel_threshold: 0.1,
+        }
+    }
+}
+
+/// Hot pixels of the sensor, in coordinates of the unflipped frame, see
+/// [crate::hot_pixels].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct HotPixelMap {
+    pub width: u32,
+    pub height: u32,
+    /// Column and row of each hot pixel, sorted by row.
+    pub pixels: Vec<[u32; 2]>,
+}
+
+/// Spectral line of the synthetic camera, with a Gaussian profile.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct EmissionLine {
+    pub wavelength: f32,
+    /// Peak height relative to full scale.
+    pub intensity: f32,
+    /// Full width at half maximum in nm.
+    pub fwhm: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct SyntheticCameraConfig {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    /// Center row and height of the band the spectrum is drawn into.
+    pub band_row: u32,
+    pub band_height: u32,
+    /// Wavelength at the left edge of the flipped frame and nm per column.
+    pub wavelength_start: f32,
+    pub dispersion: f32,
+    /// Color temperature of the black body continuum in K.
+    pub temperature: f32,
+    /// Peak height of the continuum relative to full scale, 0 for none.
+    pub continuum: f32,
+    pub lines: Vec<EmissionLine>,
+    /// Standard deviation of the pixel noise relative to full scale.
+    pub noise: f32,
+}
+
+impl Default for SyntheticCameraConfig {
+    fn default() -> Self {
+        // Matches the default window and calibration, like a fluorescent lamp
+        Self {
+            width: 1920,
+            height: 1080,
+            fps: 30,
+            band_row: 500,
+            band_height: 40,
+            wavelength_start: 259.5,
+            dispersion: 0.489,
+            temperature: 3000.,
+            continuum: 0.25,
+            lines: vec![
+                EmissionLine {
+                    wavelength: 435.8,
+                    intensity: 0.5,
+                    fwhm: 2.,
+                },
+                EmissionLine {
+                    wavelength: 546.1,
+                    intensity: 0.7,
+                    fwhm: 2.,
+                },
+                EmissionLine {
+                    wavelength: 611.6,
+                    intensity: 0.4,
+                    fwhm: 3.,
+                },
+            ],
+            noise: 0.01,
+        }
+    }
+}
+
+/// Lines of the test pattern, placed exactly at their wavelengths by the current calibration.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct TestPatternConfig {
+    pub wavelengths: Vec<f32>,
+    /// Full width at half maximum in nm.
+    pub fwhm: f32,
+}
+
+impl Default for TestPatternConfig {
+    fn default() -> Self {
+        Self {
+            wavelengths: vec![450., 500., 550., 600., 650.],
+            fwhm: 3.,
+        }
+    }
+}
+
+/// Time of day, e.g. of a scheduled lamp check.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Copy)]
+pub struct TimeOfDay {
+    pub hour: u32,
+    pub minute: u32,
+}
+
+impl Display for TimeOfDay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+/// Scheduled measurement of a calibration lamp, which logs the drift of its lines and corrects
+/// the wavelength offset, see [crate::lamp_check].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct LampCheckConfig {
+    pub active: bool,
+    pub times: Vec<TimeOfDay>,
+    /// Wavelengths in nm of the lamp lines.
+    pub lines: Vec<f32>,
+    /// Distance in nm from its wavelength a line is searched within.
+    pub search_window: f32,
+    pub auto_correct: bool,
+    /// Largest drift in nm that is corrected, larger ones are only logged.
+    pub max_correction: f32,
+    /// Ask to switch on the lamp before measuring, off for a lamp switched on by a timer.
+    pub prompt: bool,
+    /// CSV file the results are appended to.
+    pub log_path: String,
+    /// Unix time of the last check.
+    pub last_check: Option<i64>,
+}
+
+impl Default for LampCheckConfig {
+    fn default() -> Self {
+        Self {
+            active: false,
+            times: vec![TimeOfDay { hour: 8, minute: 0 }],
+            // Mercury lines of fluorescent lamps
+            lines: vec![404.66, 435.83, 546.07],
+            search_window: 5.,
+            auto_correct: true,
+            max_correction: 2.,
+            prompt: true,
+            log_path: "lamp_check.csv".to_string(),
+            last_check: None,
+        }
+    }
+}
+
+/// Exposure brackets of the HDR mode, see [crate::hdr].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct HdrConfig {
+    /// Number of exposures, from 2 to 4.
+    pub brackets: usize,
+    /// Factor between the exposures of neighboring brackets.
+    pub step: f32,
+}
+
+impl Default for HdrConfig {
+    fn default() -> Self {
+        Self {
+            brackets: 3,
+            step: 4.,
+        }
+    }
+}
+
+/// Where the lock-in detection takes the modulation of the source from, see [crate::lock_in].
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum LockInReference {
+    /// The source is switched on and off by commands.
+    Toggled,
+    /// The source is modulated at a known frequency by itself, e.g. with a chopper.
+    Modulation,
+}
+
+impl Display for LockInReference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockInReference::Toggled => write!(f, "Toggled Source"),
+            LockInReference::Modulation => write!(f, "Known Modulation"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct LockInConfig {
+    pub reference: LockInReference,
+    /// Command switching the source on, e.g. a script writing to a serial port or a GPIO.
+    pub source_on_command: String,
+    pub source_off_command: String,
+    /// Camera frames captured with the source on and with it off in each cycle.
+    pub frames_per_state: usize,
+    /// Modulation frequency in Hz, below half the frame rate.
+    pub frequency: f32,
+    /// Periods of the modulation demodulated into one spectrum.
+    pub periods: usize,
+}
+
+impl Default for LockInConfig {
+    fn default() -> Self {
+        Self {
+            reference: LockInReference::Toggled,
+            source_on_command: String::new(),
+            source_off_command: String::new(),
+            frames_per_state: 5,
+            frequency: 5.,
+            periods: 10,
+        }
+    }
+}
+
+/// Serial port and command templates of a lamp and a shutter, see [crate::device_control].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct DeviceControlConfig {
+    pub port: String,
+    pub baud_rate: u32,
+    pub lamp_on: String,
+    pub lamp_off: String,
+    pub shutter_open: String,
+    pub shutter_close: String,
+    /// Seconds to wait after switching, e.g. for the lamp to warm up.
+    pub settle_time: f32,
+    /// Take darks and zero references with the light off.
+    pub switch_for_dark: bool,
+    /// Switch the light on for scheduled lamp checks only, for a calibration lamp.
+    pub switch_for_lamp_check: bool,
+    /// Switch the light on for each logged spectrum only.
+    pub switch_for_logging: bool,
+}
+
+impl Default for DeviceControlConfig {
+    fn default() -> Self {
+        Self {
+            port: String::new(),
+            baud_rate: 9600,
+            lamp_on: String::new(),
+            lamp_off: String::new(),
+            shutter_open: String::new(),
+            shutter_close: String::new(),
+            settle_time: 1.,
+            switch_for_dark: true,
+            switch_for_lamp_check: false,
+            switch_for_logging: false,
+        }
+    }
+}
+
+impl DeviceControlConfig {
+    pub fn command(&self, action: DeviceAction) -> &str {
+        match action {
+            DeviceAction::LampOn => &self.lamp_on,
+            DeviceAction::LampOff => &self.lamp_off,
+            DeviceAction::ShutterOpen => &self.shutter_open,
+            DeviceAction::ShutterClose => &self.shutter_close,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct FilterPosition {
+    pub label: String,
+    /// Command template moving the wheel to the position, see
+    /// [crate::device_control::parse_template].
+    pub command: String,
+}
+
+/// Positions of a filter wheel on the serial port of the device control, see
+/// [crate::filter_wheel].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct FilterWheelConfig {
+    pub positions: Vec<FilterPosition>,
+    /// Seconds to wait after moving the wheel.
+    pub settle_time: f32,
+}
+
+impl Default for FilterWheelConfig {
+    fn default() -> Self {
+        Self {
+            // Commands of the common "pos=n" protocol
+            positions: (1..=6)
+                .map(|n| FilterPosition {
+                    label: format!("Position {}", n),
+                    command: format!("pos={}\\r", n),
+                })
+                .collect(),
+            settle_time: 2.,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum TriggerEdge {
+    Rising,
+    Falling,
+}
+
+impl Display for TriggerEdge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TriggerEdge::Rising => write!(f, "Rising"),
+            TriggerEdge::Falling => write!(f, "Falling"),
+        }
+    }
+}
+
+/// Pins of the hardware trigger for single-shot acquisitions, see [crate::gpio].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct GpioConfig {
+    /// Character device of the GPIO chip.
+    pub chip: String,
+    /// Line offset of the trigger input on the chip, the BCM number on a Raspberry Pi.
+    pub trigger_pin: u32,
+    pub trigger_edge: TriggerEdge,
+    /// Drive the busy pin high from the trigger until the spectrum is acquired.
+    pub busy_output: bool,
+    pub busy_pin: u32,
+}
+
+impl Default for GpioConfig {
+    fn default() -> Self {
+        Self {
+            chip: "/dev/gpiochip0".to_string(),
+            trigger_pin: 17,
+            trigger_edge: TriggerEdge::Rising,
+            busy_output: false,
+            busy_pin: 27,
+        }
+    }
+}
+
+/// Trace drawn from the spectra of the first and the second camera.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum ChannelComparison {
+    #[default]
+    None,
+    /// First minus second.
+    Difference,
+    /// First divided by second, e.g. sample over reference channel.
+    Ratio,
+}
+
+impl Display for ChannelComparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelComparison::None => write!(f, "None"),
+            ChannelComparison::Difference => write!(f, "Difference"),
+            ChannelComparison::Ratio => write!(f, "Ratio"),
+        }
+    }
+}
+
+/// Camera streamed along with the first one, e.g. for the reference channel of a dual beam
+/// setup, with its own window and calibration. Averaging and smoothing are shared.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SecondCameraConfig {
+    pub camera_id: usize,
+    #[serde_as(as = "Option<CameraFormatDef>")]
+    pub camera_format: Option<CameraFormat>,
+    pub image_config: ImageConfig,
+    pub spectrum_calibration: SpectrumCalibration,
+    pub comparison: ChannelComparison,
+}
+
+/// Limit on the transmission of a filter in a band, as a fraction.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum TransmissionLimit {
+    /// The maximum in the band must not exceed the limit, e.g. to block UV.
+    AtMost(f32),
+    /// The mean in the band must reach the limit.
+    AtLeast(f32),
+}
+
+impl Display for TransmissionLimit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransmissionLimit::AtMost(limit) => write!(f, "max ≤ {:.1} %", limit * 100.),
+            TransmissionLimit::AtLeast(limit) => write!(f, "mean ≥ {:.1} %", limit * 100.),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct BandRequirement {
+    pub name: String,
+    pub band: WavelengthBand,
+    pub limit: TransmissionLimit,
+}
+
+/// Requirements the two samples of the filter pair comparison are checked against, see
+/// [crate::filter_pair].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct FilterPairConfig {
+    pub names: [String; 2],
+    pub requirements: Vec<BandRequirement>,
+    /// Largest relative difference of the mean visible transmissions of both samples.
+    pub max_mismatch: f32,
+}
+
+impl Default for FilterPairConfig {
+    fn default() -> Self {
+        Self {
+            names: ["Left".to_string(), "Right".to_string()],
+            requirements: vec![
+                BandRequirement {
+                    name: "UV400".to_string(),
+                    band: WavelengthBand {
+                        start: 280.,
+                        end: 400.,
+                    },
+                    limit: TransmissionLimit::AtMost(0.01),
+                },
+                BandRequirement {
+                    name: "Visible".to_string(),
+                    band: WavelengthBand {
+                        start: 400.,
+                        end: 700.,
+                    },
+                    limit: TransmissionLimit::AtLeast(0.03),
+                },
+            ],
+            max_mismatch: 0.15,
+        }
+    }
+}
+
+/// Reduction of the spectra sent to remote subscribers, see [crate::engine::SpectroEngine].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RemoteDecimation {
+    Full,
+    /// Every nth column.
+    EveryNth(usize),
+    /// Linearly resampled to the number of columns.
+    Resample(usize),
+}
+
+impl Display for RemoteDecimation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemoteDecimation::Full => write!(f, "Full Resolution"),
+            RemoteDecimation::EveryNth(_) => write!(f, "Every Nth Point"),
+            RemoteDecimation::Resample(_) => write!(f, "Resample"),
+        }
+    }
+}
+
+/// Resolution and rate of the spectra streamed to remote subscribers, independent of the local
+/// processing, to fit slow network links.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct RemoteStreamConfig {
+    pub decimation: RemoteDecimation,
+    /// Spectra per second sent at most, 0 for every spectrum.
+    pub max_rate: f32,
+}
+
+impl Default for RemoteStreamConfig {
+    fn default() -> Self {
+        Self {
+            decimation: RemoteDecimation::Full,
+            max_rate: 0.,
+        }
+    }
+}
+
+/// Server pushing the remote spectra as JSON over WebSocket, see [crate::websocket].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct WebSocketConfig {
+    /// Start the server with the application.
+    pub active: bool,
+    /// Interface to listen on, only this computer by default.
+    pub address: IpAddr,
+    pub port: u16,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            active: false,
+            address: Ipv4Addr::LOCALHOST.into(),
+            port: 8765,
+        }
+    }
+}
+
+/// HTTP API to read the spectrum and control the acquisition, see [crate::rest_api].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct RestApiConfig {
+    /// Start the server with the application.
+    pub active: bool,
+    /// Interface to listen on, only this computer by default.
+    pub address: IpAddr,
+    pub port: u16,
+    /// Bearer token the commands need, generated when the server starts without one.
+    pub token: String,
+}
+
+impl Default for RestApiConfig {
+    fn default() -> Self {
+        Self {
+            active: false,
+            address: Ipv4Addr::LOCALHOST.into(),
+            port: 8080,
+            token: String::new(),
+        }
+    }
+}
+
+/// Recording of the raw spectra and their replay, see [crate::recording].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct RecordingConfig {
+    pub path: String,
+    /// Replay speed relative to the recording.
+    pub speed: f32,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            path: "recording.spcs".to_string(),
+            speed: 1.,
+        }
+    }
+}
+
+/// Retrying the camera after it was lost during a stream.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    pub active: bool,
+    /// Attempts after an error before the stream is given up.
+    pub max_attempts: u32,
+    /// Delay before the first attempt in seconds, doubled for every following one.
+    pub initial_delay: f32,
+    /// Longest delay between attempts in seconds.
+    pub max_delay: f32,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            active: false,
+            max_attempts: 10,
+            initial_delay: 1.,
+            max_delay: 60.,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    /// Time to wait before attempt number `attempt`, counted from 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let doubled = self.initial_delay * 2f32.powi(attempt.saturating_sub(1).min(30) as i32);
+        Duration::from_secs_f32(doubled.min(self.max_delay).max(0.))
+    }
+}
+
+impl ImageConfig {
+    /// Window of the reference strip, see [ImageConfig::reference_strip].
+    pub fn reference_window(&self) -> Option<SpectrumWindow> {
+        self.reference_strip.map(|y| SpectrumWindow {
+            offset: Vec2::new(self.window.offset.x, y),
+            size: self.window.size,
+        })
+    }
+
+    /// Store the current window under `name`, replacing a preset with the same name.
+    pub fn store_preset(&mut self, name: &str) {
+        let preset = WindowPreset {
+            name: name.to_string(),
+            window: self.window,
+        };
+        match self.presets.iter_mut().find(|p| p.name == name) {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+    }
+
+    /// Make the preset `name` the current window.
+    ///
+    /// Returns `false` if there is no such preset.
+    pub fn load_preset(&mut self, name: &str) -> bool {
+        match self.presets.iter().find(|p| p.name == name) {
+            Some(preset) => {
+                self.window = preset.window;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clamp(&mut self, width: f32, height: f32) {
+        self.window.offset = self.window.offset.min(Vec2::new(width, height));
+        self.window.size = self
+            .window
+            .size
+            .min(Vec2::new(width, height) - self.window.offset);
+        if let Some(y) = &mut self.reference_strip {
+            *y = y.clamp(0., (height - self.window.size.y).max(0.));
+        }
+        self.binning.center = self
+            .binning
+            .center
+            .clamp(0., (self.window.size.y - 1.).max(0.));
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Copy, Clone)]
+pub struct SpectrumCalibrationPoint {
+    pub wavelength: u32,
+    pub index: usize,
+}
+
+/// Spectral resolution of the instrument from the fit of an isolated line.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Copy, Clone)]
+pub struct SpectralResolution {
+    /// Fitted center of the line in nm.
+    pub wavelength: f32,
+    /// FWHM of the line in nm.
+    pub fwhm: f32,
+    /// Unix time of the measurement.
+    pub measured: i64,
+}
+
+impl SpectralResolution {
+    /// Resolving power λ/Δλ.
+    pub fn resolving_power(&self) -> f32 {
+        self.wavelength / self.fwhm
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum GainPresets {
+    Unity,
+    Rec601,
+    Rec709,
+    SRgb,
+}
+
+impl GainPresets {
+    pub fn get_gain(&self) -> (f32, f32, f32) {
+        match self {
+            GainPresets::Unity => (1., 1., 1.),
+            GainPresets::Rec601 => (0.299, 0.587, 0.114),
+            GainPresets::Rec709 | GainPresets::SRgb => (0.2126, 0.7152, 0.0722),
+        }
+    }
+}
+
+impl Display for GainPresets {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GainPresets::Unity => write!(f, "Unity"),
+            GainPresets::Rec601 => write!(f, "Rec. 601"),
+            GainPresets::Rec709 => write!(f, "Rec. 709"),
+            GainPresets::SRgb => write!(f, "sRGB"),
+        }
+    }
+}
+
+/// Plausible range of the dispersion in nm per pixel.
+const MIN_DISPERSION: f32 = 0.02;
+const MAX_DISPERSION: f32 = 5.;
+/// Plausible range of the wavelength axis in nm.
+const MIN_WAVELENGTH: f32 = 100.;
+const MAX_WAVELENGTH: f32 = 3000.;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct SpectrumCalibration {
+    pub low: SpectrumCalibrationPoint,
+    pub high: SpectrumCalibrationPoint,
+    pub linearize: Linearize,
+    pub gain_r: f32,
+    pub gain_g: f32,
+    pub gain_b: f32,
+    pub scaling: Option<Vec<f32>>,
+    /// Unit of the reference the scaling was derived from.
+    pub scaling_unit: IntensityUnit,
+    /// Sensor quantum efficiency to correct before the scaling.
+    pub qe_curve: Option<QeCurve>,
+    /// Unix time of the last change, to show the age of the calibration.
+    pub modified: Option<i64>,
+    /// Setup the scaling was derived with, it does not apply to others.
+    pub scaling_setup: Option<StreamSetup>,
+    /// Shift in nm of every wavelength, to correct a drift found by a lamp check.
+    pub offset: f32,
+    pub resolution: Option<SpectralResolution>,
+}
+
+impl SpectrumCalibration {
+    fn get_wavelength_delta(&self) -> f32 {
+        (self.high.wavelength - self.low.wavelength) as f32
+            / (self.high.index - self.low.index) as f32
+    }
+
+    /// Wavelength difference of adjacent window columns in nm.
+    pub fn dispersion(&self) -> f32 {
+        self.get_wavelength_delta().abs()
+    }
+
+    pub fn get_wavelength_from_index(&self, index: usize) -> f32 {
+        self.low.wavelength as f32
+            + self.offset
+            + (index as f32 - self.low.index as f32) * self.get_wavelength_delta()
+    }
+
+    /// Fractional window column of `wavelength`, the inverse of [Self::get_wavelength_from_index].
+    pub fn get_index_from_wavelength(&self, wavelength: f32) -> f32 {
+        self.low.index as f32
+            + (wavelength - self.offset - self.low.wavelength as f32) / self.get_wavelength_delta()
+    }
+
+    /// Unit of the calibrated spectrum.
+    pub fn intensity_unit(&self) -> IntensityUnit {
+        if self.scaling.is_some() {
+            self.scaling_unit
+        } else {
+            IntensityUnit::Counts
+        }
+    }
+
+    pub fn get_scaling_factor_from_index(&self, index: usize) -> f32 {
+        if let Some(scaling) = self.scaling.as_ref() {
+            *scaling.get(index).unwrap_or(&1.)
+        } else {
+            1.
+        }
+    }
+
+    pub fn set_gain_preset(&mut self, preset: GainPresets) {
+        let factors = preset.get_gain();
+        self.gain_r = factors.0;
+        self.gain_g = factors.1;
+        self.gain_b = factors.2;
+    }
+
+    /// Check that the wavelength axis is plausible for a window `window_width` pixels wide.
+    pub fn check(&self, window_width: usize) -> Result<(), String> {
+        if self.high.index <= self.low.index || self.high.wavelength <= self.low.wavelength {
+            return Err("Inverted wavelength axis".to_string());
+        }
+        let delta = self.get_wavelength_delta();
+        if !(MIN_DISPERSION..=MAX_DISPERSION).contains(&delta) {
+            return Err(format!("Implausible dispersion of {:.3} nm/px", delta));
+        }
+        let first = self.get_wavelength_from_index(0);
+        let last = self.get_wavelength_from_index(window_width.saturating_sub(1));
+        if first < MIN_WAVELENGTH || last > MAX_WAVELENGTH {
+            return Err(format!(
+                "Wavelength range {:.0} to {:.0} nm out of bounds",
+                first, last
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for SpectrumCalibration {
+    fn default() -> Self {
+        Self {
+            low: SpectrumCalibrationPoint {
+                wavelength: 436,
+                index: 261,
+            },
+            high: SpectrumCalibrationPoint {
+                wavelength: 546,
+                index: 486,
+            },
+            linearize: Linearize::Off,
+            gain_r: 1.0,
+            gain_g: 1.0,
+            gain_b: 1.0,
+            scaling: None,
+            scaling_unit: IntensityUnit::Relative,
+            qe_curve: None,
+            modified: None,
+            scaling_setup: None,
+            offset: 0.,
+            resolution: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct DeconvolutionConfig {
+    pub active: bool,
+    /// Normalized instrument line shape, centered on the middle element.
+    pub line_shape: Option<Vec<f32>>,
+    pub line_shape_half_width: usize,
+    /// Instrument line width in nm for a Gaussian line shape.
+    pub gaussian_fwhm: f32,
+    pub iterations: usize,
+    pub regularization: f32,
+}
+
+impl Default for DeconvolutionConfig {
+    fn default() -> Self {
+        Self {
+            active: false,
+            line_shape: None,
+            line_shape_half_width: 10,
+            gaussian_fwhm: 2.,
+            iterations: 20,
+            regularization: 0.002,
+        }
+    }
+}
+
+impl DeconvolutionConfig {
+    /// Line shape to deconvolve with if deconvolution is active.
+    pub fn active_line_shape(&self) -> Option<&[f32]> {
+        self.line_shape
+            .as_deref()
+            .filter(|_| self.active && self.iterations > 0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum BaselineMethod {
+    #[default]
+    None,
+    AsymmetricLeastSquares,
+    RollingBall,
+}
+
+impl Display for BaselineMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BaselineMethod::None => write!(f, "None"),
+            BaselineMethod::AsymmetricLeastSquares => write!(f, "Asymmetric Least Squares"),
+            BaselineMethod::RollingBall => write!(f, "Rolling Ball"),
+        }
+    }
+}
+
+/// Slowly varying background like stray light and sensor offsets, subtracted before peaks
+/// are detected, see [crate::baseline].
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct BaselineConfig {
+    pub method: BaselineMethod,
+    /// Stiffness of the asymmetric least squares baseline.
+    pub lambda: f32,
+    /// Weight of the points above the asymmetric least squares baseline.
+    pub asymmetry: f32,
+    /// Half width in columns of the rolling ball, wider than the peaks.
+    pub radius: usize,
+}
+
+impl Default for BaselineConfig {
+    fn default() -> Self {
+        Self {
+            method: BaselineMethod::None,
+            lambda: 1e5,
+            asymmetry: 0.01,
+            radius: 50,
+        }
+    }
+}
+
+/// Mains frequency whose lamp flicker is averaged out, see [crate::flicker].
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Flicker {
+    Off,
+    Mains50Hz,
+    Mains60Hz,
+}
+
+impl Display for Flicker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Flicker::Off => write!(f, "Off"),
+            Flicker::Mains50Hz => write!(f, "50 Hz Mains"),
+            Flicker::Mains60Hz => write!(f, "60 Hz Mains"),
+        }
+    }
+}
+
+impl Flicker {
+    /// Frequency of the flicker in Hz, twice the mains frequency.
+    pub fn frequency(&self) -> Option<f32> {
+        match self {
+            Flicker::Off => None,
+            Flicker::Mains50Hz => Some(100.),
+            Flicker::Mains60Hz => Some(120.),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct PostprocessingConfig {
+    pub spectrum_buffer_size: usize,
+    /// Configurations from before the smoothing methods only had the low-pass filter
+    /// switch `spectrum_filter_active`.
+    #[serde(
+        alias = "spectrum_filter_active",
+        deserialize_with = "SmoothingMethod::deserialize_or_filter_active"
+    )]
+    pub smoothing_method: SmoothingMethod,
+    pub spectrum_filter_cutoff: f32,
+    pub savitzky_golay_half_width: usize,
+    pub savitzky_golay_order: usize,
+    pub median_half_width: usize,
+    pub gaussian_sigma: f32,
+    /// Keep the averaging buffer when the stream is restarted with the same camera, format
+    /// and window.
+    pub retain_buffer_on_restart: bool,
+    /// Number of columns the noise is averaged over for the detection limit.
+    pub noise_band_width: usize,
+    pub deconvolution: DeconvolutionConfig,
+    pub baseline: BaselineConfig,
+    /// Leave frames deviating strongly from the others out of the average.
+    pub outlier_rejection: bool,
+    /// Standard deviations from which a frame is an outlier.
+    pub outlier_sigma: f32,
+    /// Expression giving the new intensity of each point from its wavelength `x` and
+    /// intensity `y`, applied to the color channels. Empty for none, see [Expression].
+    pub transform: String,
+    #[serde(skip)]
+    pub(crate) parsed_transform: ParsedExpression,
+    /// Show every frame as is, without averaging, smoothing and deconvolution.
+    pub low_latency: bool,
+    /// How the window is related to the reference strip, if there is one.
+    pub reference_beam: ReferenceBeam,
+    pub spectrum_math: SpectrumMath,
+    pub flicker: Flicker,
+}
+
+impl PostprocessingConfig {
+    pub fn effective_buffer_size(&self) -> usize {
+        if self.low_latency {
+            1
+        } else {
+            self.spectrum_buffer_size
+        }
+    }
+
+    /// Averaging buffer size spanning whole periods of the flicker beat, if the flicker is
+    /// compensated and the frame rate known.
+    pub fn synchronized_buffer_size(&self, frame_rate: Option<f32>) -> usize {
+        let frames = self.effective_buffer_size();
+        match (self.flicker.frequency(), frame_rate) {
+            (Some(flicker), Some(frame_rate)) if !self.low_latency => {
+                synchronized_frames(frames, frame_rate, flicker)
+            }
+            _ => frames,
+        }
+    }
+
+    pub fn effective_smoothing_method(&self) -> SmoothingMethod {
+        if self.low_latency {
+            SmoothingMethod::None
+        } else {
+            self.smoothing_method
+        }
+    }
+
+    /// New intensities of `spectrum` by the transform, `None` if there is none or it is
+    /// invalid.
+    pub fn transform(&self, spectrum: &[SpectrumPoint]) -> Option<Vec<f32>> {
+        self.transform_result(|e| e.transform(spectrum))?.ok()
+    }
+
+    /// Whether the transform is applied.
+    pub fn is_transforming(&self) -> bool {
+        matches!(self.transform_result(|_| ()), Some(Ok(())))
+    }
+
+    /// Why the transform is not applied, `None` if there is none or it is valid.
+    pub fn transform_error(&self) -> Option<String> {
+        self.transform_result(|_| ())?.err()
+    }
+
+    fn transform_result<T>(&self, f: impl FnOnce(&Expression) -> T) -> Option<Result<T, String>> {
+        (!self.transform.trim().is_empty()).then(|| self.parsed_transform.with(&self.transform, f))
+    }
+
+    pub fn effective_line_shape(&self) -> Option<&[f32]> {
+        self.deconvolution
+            .active_line_shape()
+            .filter(|_| !self.low_latency)
+    }
+}
+
+impl Default for PostprocessingConfig {
+    fn default() -> Self {
+        Self {
+            spectrum_buffer_size: 10,
+            smoothing_method: SmoothingMethod::None,
+            spectrum_filter_cutoff: 0.5,
+            savitzky_golay_half_width: 5,
+            savitzky_golay_order: 2,
+            median_half_width: 2,
+            gaussian_sigma: 2.,
+            retain_buffer_on_restart: false,
+            noise_band_width: 16,
+            deconvolution: DeconvolutionConfig::default(),
+            baseline: BaselineConfig::default(),
+            outlier_rejection: false,
+            outlier_sigma: 3.,
+            transform: String::new(),
+            parsed_transform: ParsedExpression::default(),
+            low_latency: false,
+            reference_beam: ReferenceBeam::Ratio,
+            spectrum_math: SpectrumMath::default(),
+            flicker: Flicker::Off,
+        }
+    }
+}
+
+/// Smallest sample to reference ratio used for the absorbance, which limits it to 4.
+const MIN_BEAM_RATIO: f32 = 1e-4;
+
+/// Relation of the sample window to the reference strip in the reference beam mode, which
+/// compensates drifts of the lamp.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ReferenceBeam {
+    /// Sample divided by reference.
+    Ratio,
+    /// Decadic absorbance of the sample relative to the reference.
+    Absorbance,
+}
+
+impl Display for ReferenceBeam {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReferenceBeam::Ratio => write!(f, "Ratio"),
+            ReferenceBeam::Absorbance => write!(f, "Absorbance"),
+        }
+    }
+}
+
+impl ReferenceBeam {
+    /// Value of a column of the sample relative to the same column of the reference, 0 where
+    /// the reference has no light.
+    pub fn apply(&self, sample: f32, reference: f32) -> f32 {
+        if reference <= 0. {
+            return 0.;
+        }
+        let ratio = sample / reference;
+        match self {
+            ReferenceBeam::Ratio => ratio,
+            ReferenceBeam::Absorbance => -ratio.max(MIN_BEAM_RATIO).log10(),
+        }
+    }
 }
 
-impl Default for ViewConfig {
-    fn default() -> Self {
-        Self {
-            window_size: PhysicalSize::new(800, 600),
-            image_scale: 0.25,
-            draw_spectrum_r: false,
-            draw_spectrum_g: false,
-            draw_spectrum_b: false,
-            draw_spectrum_combined: true,
-            draw_peaks: true,
-            draw_dips: true,
-            peaks_dips_unique_window: 50.,
-            peaks_dips_find_window: 5,
-            show_camera_window: true,
-            show_calibration_window: false,
-            show_postprocessing_window: false,
-            show_camera_control_window: false,
-            show_import_export_window: false,
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum MathOperation {
+    None,
+    Subtract,
+    Divide,
+}
+
+impl Display for MathOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MathOperation::None => write!(f, "None"),
+            MathOperation::Subtract => write!(f, "Subtract"),
+            MathOperation::Divide => write!(f, "Divide"),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct ImageConfig {
-    pub window: SpectrumWindow,
-    pub flip: bool,
+/// Spectrum the sum channel is combined with after the zero reference, e.g. to subtract a
+/// solvent. Loaded spectra have no color channels, so r, g and b are left as they are.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct SpectrumMath {
+    pub operation: MathOperation,
+    /// Snapshot or file the operand was taken from.
+    pub name: String,
+    /// Sorted by wavelength.
+    pub operand: Vec<SpectrumPoint>,
 }
 
-impl Default for ImageConfig {
+impl Default for SpectrumMath {
     fn default() -> Self {
         Self {
-            window: SpectrumWindow {
-                offset: Vec2::new(100., 500.),
-                size: Vec2::new(1500., 1.),
-            },
-            flip: true,
+            operation: MathOperation::None,
+            name: String::new(),
+            operand: vec![],
         }
     }
 }
 
-impl ImageConfig {
-    pub fn clamp(&mut self, width: f32, height: f32) {
-        self.window.offset = self.window.offset.min(Vec2::new(width, height));
-        self.window.size = self
-            .window
-            .size
-            .min(Vec2::new(width, height) - self.window.offset);
+impl SpectrumMath {
+    pub fn is_active(&self) -> bool {
+        self.operation != MathOperation::None && !self.operand.is_empty()
     }
-}
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
-pub struct SpectrumCalibrationPoint {
-    pub wavelength: u32,
-    pub index: usize,
+    /// Combine `value` with the operand linearly interpolated at `wavelength`. Outside of the
+    /// operand, and for a ratio without light in it, the result is 0.
+    pub fn apply(&self, wavelength: f32, value: f32) -> f32 {
+        let operand = match self.operation {
+            MathOperation::None => return value,
+            _ => match interpolate(&self.operand, wavelength) {
+                Some(operand) => operand,
+                None => return 0.,
+            },
+        };
+        match self.operation {
+            MathOperation::Divide if operand <= 0. => 0.,
+            MathOperation::Divide => value / operand,
+            _ => value - operand,
+        }
+    }
+
+    /// Use `spectrum` as operand.
+    pub fn set_operand(&mut self, name: &str, spectrum: &[SpectrumPoint]) {
+        self.name = name.to_string();
+        self.operand = spectrum.to_vec();
+        self.operand
+            .sort_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
-pub enum GainPresets {
-    Unity,
-    Rec601,
-    Rec709,
-    SRgb,
+/// Capture API the cameras are enumerated and opened with, as some cameras only work with one
+/// of them.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum CameraBackend {
+    #[default]
+    Auto,
+    Video4Linux,
+    GStreamer,
+    OpenCv,
+    MediaFoundation,
+    AVFoundation,
 }
 
-impl GainPresets {
-    pub fn get_gain(&self) -> (f32, f32, f32) {
+impl Display for CameraBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            GainPresets::Unity => (1., 1., 1.),
-            GainPresets::Rec601 => (0.299, 0.587, 0.114),
-            GainPresets::Rec709 | GainPresets::SRgb => (0.2126, 0.7152, 0.0722),
+            CameraBackend::Auto => write!(f, "Auto"),
+            CameraBackend::Video4Linux => write!(f, "V4L2"),
+            CameraBackend::GStreamer => write!(f, "GStreamer"),
+            CameraBackend::OpenCv => write!(f, "OpenCV"),
+            CameraBackend::MediaFoundation => write!(f, "Media Foundation"),
+            CameraBackend::AVFoundation => write!(f, "AVFoundation"),
         }
     }
 }
 
-impl Display for GainPresets {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+impl CameraBackend {
+    /// Backends of this platform and the enabled `gstreamer` and `opencv` features.
+    pub fn available() -> Vec<CameraBackend> {
+        let mut backends = vec![CameraBackend::Auto];
+        if cfg!(target_os = "linux") {
+            backends.push(CameraBackend::Video4Linux);
+        }
+        if cfg!(target_os = "windows") {
+            backends.push(CameraBackend::MediaFoundation);
+        }
+        if cfg!(target_os = "macos") {
+            backends.push(CameraBackend::AVFoundation);
+        }
+        if cfg!(feature = "gstreamer") {
+            backends.push(CameraBackend::GStreamer);
+        }
+        if cfg!(feature = "opencv") {
+            backends.push(CameraBackend::OpenCv);
+        }
+        backends
+    }
+
+    pub fn api(&self) -> CaptureAPIBackend {
         match self {
-            GainPresets::Unity => write!(f, "Unity"),
-            GainPresets::Rec601 => write!(f, "Rec. 601"),
-            GainPresets::Rec709 => write!(f, "Rec. 709"),
-            GainPresets::SRgb => write!(f, "sRGB"),
+            CameraBackend::Auto => CaptureAPIBackend::Auto,
+            CameraBackend::Video4Linux => CaptureAPIBackend::Video4Linux,
+            CameraBackend::GStreamer => CaptureAPIBackend::GStreamer,
+            CameraBackend::OpenCv => CaptureAPIBackend::OpenCv,
+            CameraBackend::MediaFoundation => CaptureAPIBackend::MediaFoundation,
+            CameraBackend::AVFoundation => CaptureAPIBackend::AVFoundation,
         }
     }
 }
 
+#[serde_as]
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct SpectrumCalibration {
-    pub low: SpectrumCalibrationPoint,
-    pub high: SpectrumCalibrationPoint,
-    pub linearize: Linearize,
-    pub gain_r: f32,
-    pub gain_g: f32,
-    pub gain_b: f32,
-    pub scaling: Option<Vec<f32>>,
+pub struct CalibrationProfile {
+    pub name: String,
+    pub camera_id: usize,
+    #[serde_as(as = "CameraFormatDef")]
+    pub camera_format: CameraFormat,
+    pub image_config: ImageConfig,
+    pub spectrum_calibration: SpectrumCalibration,
 }
 
-impl SpectrumCalibration {
-    fn get_wavelength_delta(&self) -> f32 {
-        (self.high.wavelength - self.low.wavelength) as f32
-            / (self.high.index - self.low.index) as f32
+impl CalibrationProfile {
+    pub fn matches(&self, camera_id: usize, camera_format: CameraFormat) -> bool {
+        self.camera_id == camera_id && self.camera_format == camera_format
     }
+}
 
-    pub fn get_wavelength_from_index(&self, index: usize) -> f32 {
-        self.low.wavelength as f32
-            + (index as f32 - self.low.index as f32) * self.get_wavelength_delta()
-    }
+/// Camera, format and window spectra are acquired with.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub struct StreamSetup {
+    pub camera_id: usize,
+    #[serde_as(as = "CameraFormatDef")]
+    pub camera_format: CameraFormat,
+    pub window: SpectrumWindow,
+}
 
-    pub fn get_scaling_factor_from_index(&self, index: usize) -> f32 {
-        if let Some(scaling) = self.scaling.as_ref() {
-            *scaling.get(index).unwrap_or(&1.)
-        } else {
-            1.
+/// Zero reference with the setup it was taken with, to restore it after a restart.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ZeroReference {
+    pub setup: StreamSetup,
+    /// Channels r, g, b and sum per window column.
+    pub columns: Vec<[f32; 4]>,
+}
+
+impl ZeroReference {
+    pub fn new(setup: StreamSetup, spectrum: &Spectrum) -> Self {
+        Self {
+            setup,
+            columns: spectrum
+                .column_iter()
+                .map(|c| [c[0], c[1], c[2], c[3]])
+                .collect(),
         }
     }
 
-    pub fn set_gain_preset(&mut self, preset: GainPresets) {
-        let factors = preset.get_gain();
-        self.gain_r = factors.0;
-        self.gain_g = factors.1;
-        self.gain_b = factors.2;
+    pub fn to_spectrum(&self) -> Spectrum {
+        Spectrum::from_iterator(self.columns.len(), self.columns.iter().flatten().copied())
     }
 }
 
-impl Default for SpectrumCalibration {
-    fn default() -> Self {
-        Self {
-            low: SpectrumCalibrationPoint {
-                wavelength: 436,
-                index: 261,
-            },
-            high: SpectrumCalibrationPoint {
-                wavelength: 546,
-                index: 486,
-            },
-            linearize: Linearize::Off,
-            gain_r: 1.0,
-            gain_g: 1.0,
-            gain_b: 1.0,
-            scaling: None,
-        }
-    }
+/// Averaged raw spectrum taken with the lens covered.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct DarkFrame {
+    /// Value of the exposure control, 0 for cameras without one.
+    pub exposure: i32,
+    /// Channels r, g, b per window column.
+    pub columns: Vec<[f32; 3]>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct PostprocessingConfig {
-    pub spectrum_buffer_size: usize,
-    pub spectrum_filter_active: bool,
-    pub spectrum_filter_cutoff: f32,
+/// Darks taken at several exposures with the setup they were taken with, see
+/// [crate::dark_library].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct DarkLibrary {
+    pub setup: StreamSetup,
+    pub darks: Vec<DarkFrame>,
 }
 
-impl Default for PostprocessingConfig {
-    fn default() -> Self {
-        Self {
-            spectrum_buffer_size: 10,
-            spectrum_filter_active: false,
-            spectrum_filter_cutoff: 0.5,
-        }
+impl DarkLibrary {
+    /// Add the dark, replacing one taken at the same exposure.
+    pub fn add(&mut self, dark: DarkFrame) {
+        self.darks.retain(|d| d.exposure != dark.exposure);
+        self.darks.push(dark);
+        self.darks.sort_by_key(|d| d.exposure);
     }
 }
 
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
 pub struct SpectrometerConfig {
+    pub camera_backend: CameraBackend,
     pub camera_id: usize,
     #[serde_as(as = "Option<CameraFormatDef>")]
     pub camera_format: Option<CameraFormat>,
+    /// Format read directly from the camera, `None` for the 8-bit RGB of the camera API.
+    pub pixel_format: Option<PixelFormat>,
+    /// MJPEG stream or JPEG snapshot URL of a network camera, without credentials.
+    pub network_camera_url: String,
+    /// User for basic authentication at the network camera, whose password is not stored.
+    pub network_camera_user: String,
     pub image_config: ImageConfig,
     pub spectrum_calibration: SpectrumCalibration,
     pub postprocessing_config: PostprocessingConfig,
     pub view_config: ViewConfig,
     pub reference_config: ReferenceConfig,
     pub import_export_config: ImportExportConfig,
+    pub logging_config: LoggingConfig,
+    pub band_monitor: BandMonitorConfig,
+    pub quantitation: QuantitationConfig,
+    pub calibration_profiles: Vec<CalibrationProfile>,
+    pub snapshots: Vec<Snapshot>,
+    /// Spectrum at the last exit, shown until live data arrives.
+    pub last_spectrum: Option<Snapshot>,
+    pub derived_values: Vec<DerivedValue>,
+    pub script: ScriptConfig,
+    pub synthetic_camera: SyntheticCameraConfig,
+    pub test_pattern: TestPatternConfig,
+    pub lamp_check: LampCheckConfig,
+    pub reconnect: ReconnectConfig,
+    pub hdr: HdrConfig,
+    pub lock_in: LockInConfig,
+    pub device_control: DeviceControlConfig,
+    pub gpio: GpioConfig,
+    pub filter_wheel: FilterWheelConfig,
+    pub filter_pair: FilterPairConfig,
+    pub second_camera: SecondCameraConfig,
+    pub zero_reference: Option<ZeroReference>,
+    pub dark_library: Option<DarkLibrary>,
+    pub remote_stream: RemoteStreamConfig,
+    pub websocket: WebSocketConfig,
+    pub rest_api: RestApiConfig,
+    pub recording: RecordingConfig,
+}
+
+impl SpectrometerConfig {
+    /// Derived values and the values of the script over `spectrum`, as exported. Invalid
+    /// expressions give NaN, the values of a failing script are left out.
+    pub fn derived_values(&self, spectrum: &[SpectrumPoint]) -> Vec<(String, f32)> {
+        self.derived_values
+            .iter()
+            .map(|d| (d.name.clone(), d.eval(spectrum).unwrap_or(f32::NAN)))
+            .chain(self.script.run(spectrum).unwrap_or_default())
+            .collect()
+    }
+
+    /// Current camera, format and window, `None` without a camera format.
+    pub fn stream_setup(&self) -> Option<StreamSetup> {
+        Some(StreamSetup {
+            camera_id: self.camera_id,
+            camera_format: self.camera_format?,
+            window: self.image_config.window,
+        })
+    }
+
+    /// Discard zero reference and intensity scaling taken with a different setup, returning
+    /// what was discarded.
+    pub fn validate_references(&mut self) -> Vec<&'static str> {
+        let setup = self.stream_setup();
+        let width = self.image_config.window.size.x as usize;
+        let mut discarded = vec![];
+        if let Some(zero_reference) = &self.zero_reference {
+            if Some(zero_reference.setup) != setup || zero_reference.columns.len() != width {
+                self.zero_reference = None;
+                discarded.push("zero reference");
+            }
+        }
+        if let Some(dark_library) = &self.dark_library {
+            if Some(dark_library.setup) != setup
+                || dark_library.darks.iter().any(|d| d.columns.len() != width)
+            {
+                self.dark_library = None;
+                discarded.push("dark library");
+            }
+        }
+        let calibration = &mut self.spectrum_calibration;
+        if let Some(scaling) = &calibration.scaling {
+            // Scalings from before the setup was stored only have to fit the window
+            let setup_matches =
+                calibration.scaling_setup.is_none() || calibration.scaling_setup == setup;
+            if !setup_matches || scaling.len() != width {
+                calibration.scaling = None;
+                calibration.scaling_setup = None;
+                discarded.push("intensity calibration");
+            }
+        }
+        discarded
+    }
+
+    /// Store window and calibration as the profile of the given camera and format.
+    pub fn store_calibration_profile(&mut self, camera_id: usize, camera_format: CameraFormat) {
+        let image_config = self.image_config.clone();
+        let spectrum_calibration = self.spectrum_calibration.clone();
+        match self
+            .calibration_profiles
+            .iter_mut()
+            .find(|p| p.matches(camera_id, camera_format))
+        {
+            Some(profile) => {
+                profile.image_config = image_config;
+                profile.spectrum_calibration = spectrum_calibration;
+            }
+            None => self.calibration_profiles.push(CalibrationProfile {
+                name: format!("{}: {}", camera_id, camera_format),
+                camera_id,
+                camera_format,
+                image_config,
+                spectrum_calibration,
+            }),
+        }
+    }
+
+    /// Load window and calibration from the profile of the given camera and format.
+    ///
+    /// Returns `false` if there is no such profile.
+    pub fn restore_calibration_profile(
+        &mut self,
+        camera_id: usize,
+        camera_format: CameraFormat,
+    ) -> bool {
+        match self
+            .calibration_profiles
+            .iter()
+            .find(|p| p.matches(camera_id, camera_format))
+        {
+            Some(profile) => {
+                self.image_config = profile.image_config.clone();
+                self.spectrum_calibration = profile.spectrum_calibration.clone();
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -321,6 +2617,82 @@ mod tests {
     use super::*;
     use approx::assert_relative_eq;
 
+    /// Default configuration as stored by the first release, with the low-pass filter on.
+    const FIRST_RELEASE_CONFIG: &str = r#"---
+camera_id: 0
+camera_format: ~
+image_config:
+  window:
+    offset:
+      x: 100.0
+      y: 500.0
+    size:
+      x: 1500.0
+      y: 1.0
+  flip: true
+spectrum_calibration:
+  low:
+    wavelength: 436
+    index: 261
+  high:
+    wavelength: 546
+    index: 486
+  linearize: "Off"
+  gain_r: 1.0
+  gain_g: 1.0
+  gain_b: 1.0
+  scaling: ~
+postprocessing_config:
+  spectrum_buffer_size: 10
+  spectrum_filter_active: true
+  spectrum_filter_cutoff: 0.5
+view_config:
+  window_size:
+    width: 800
+    height: 600
+  image_scale: 0.25
+  draw_spectrum_r: false
+  draw_spectrum_g: false
+  draw_spectrum_b: false
+  draw_spectrum_combined: true
+  draw_peaks: true
+  draw_dips: true
+  peaks_dips_unique_window: 50.0
+  peaks_dips_find_window: 5
+  show_camera_window: true
+  show_calibration_window: false
+  show_postprocessing_window: false
+  show_camera_control_window: false
+  show_import_export_window: false
+reference_config:
+  reference: ~
+  scale: 1.0
+import_export_config:
+  path: spectrum.csv
+"#;
+
+    #[test]
+    fn load_first_release_config() {
+        let config: SpectrometerConfig = serde_yaml::from_str(FIRST_RELEASE_CONFIG).unwrap();
+        let default = SpectrometerConfig::default();
+
+        let postprocessing = &config.postprocessing_config;
+        assert_eq!(postprocessing.smoothing_method, SmoothingMethod::LowPass);
+        assert_eq!(postprocessing.spectrum_buffer_size, 10);
+        assert_eq!(
+            postprocessing.baseline,
+            default.postprocessing_config.baseline
+        );
+        assert_eq!(config.image_config.window, default.image_config.window);
+        assert_eq!(config.spectrum_calibration, default.spectrum_calibration);
+        assert_eq!(config.view_config, default.view_config);
+        assert_eq!(config.import_export_config, default.import_export_config);
+
+        let stored = serde_yaml::to_string(&config).unwrap();
+        let reloaded: SpectrometerConfig = serde_yaml::from_str(&stored).unwrap();
+        assert_eq!(reloaded.postprocessing_config, config.postprocessing_config);
+    }
+
     #[test]
     fn spectrum_calibration() {
         let low = SpectrumCalibrationPoint {
@@ -339,15 +2711,124 @@ mod tests {
             gain_g: 0.0,
             gain_b: 0.0,
             scaling: None,
+            scaling_unit: IntensityUnit::Relative,
+            qe_curve: None,
+            modified: None,
+            scaling_setup: None,
+            offset: 0.,
+            resolution: None,
         };
 
         assert_relative_eq!(s.get_wavelength_delta(), 2.2);
+        assert_relative_eq!(s.dispersion(), 2.2);
 
         assert_relative_eq!(s.get_wavelength_from_index(49), 433.8);
         assert_relative_eq!(s.get_wavelength_from_index(50), 436.);
         assert_relative_eq!(s.get_wavelength_from_index(51), 438.2);
         assert_relative_eq!(s.get_wavelength_from_index(100), 546.);
         assert_relative_eq!(s.get_wavelength_from_index(101), 548.2);
+        assert_relative_eq!(s.get_index_from_wavelength(438.2), 51., epsilon = 1e-4);
+
+        let shifted = SpectrumCalibration { offset: -0.5, ..s };
+        assert_relative_eq!(shifted.get_wavelength_from_index(50), 435.5);
+        assert_relative_eq!(
+            shifted.get_index_from_wavelength(435.5),
+            50.,
+            epsilon = 1e-4
+        );
+    }
+
+    #[test]
+    fn calibration_check() {
+        let mut calibration = SpectrumCalibration::default();
+        assert!(calibration.check(1500).is_ok());
+
+        calibration.high.index = calibration.low.index;
+        assert!(calibration.check(1500).is_err());
+
+        // 110 nm over 2 pixels
+        calibration.high.index = calibration.low.index + 2;
+        assert!(calibration.check(1500).is_err());
+
+        // Plausible dispersion, but the window extends far into the infrared
+        calibration.high.index = 486;
+        assert!(calibration.check(100_000).is_err());
+    }
+
+    #[test]
+    fn band_statistics() {
+        let band = WavelengthBand {
+            start: 500.,
+            end: 504.,
+        };
+        let spectrum: Vec<_> = [(499., 9.), (500., 0.), (502., 2.), (504., 0.), (505., 9.)]
+            .map(|(wavelength, value)| SpectrumPoint { wavelength, value })
+            .to_vec();
+        let statistics = band.statistics(&spectrum).unwrap();
+        assert_relative_eq!(statistics.min, 0.);
+        assert_relative_eq!(statistics.max, 2.);
+        assert_relative_eq!(statistics.mean, 2. / 3.);
+        assert_relative_eq!(statistics.integral, 4.);
+        assert_relative_eq!(statistics.centroid, 502.);
+        assert!(band.statistics(&spectrum[..2]).is_none());
+    }
+
+    #[test]
+    fn validate_references() {
+        let mut config = SpectrometerConfig {
+            camera_format: Some(CameraFormat::default()),
+            ..Default::default()
+        };
+        let width = config.image_config.window.size.x as usize;
+        let setup = config.stream_setup().unwrap();
+        config.zero_reference = Some(ZeroReference::new(
+            setup,
+            &Spectrum::from_element(width, 0.1),
+        ));
+        config.spectrum_calibration.scaling = Some(vec![1.; width]);
+        let mut dark_library = DarkLibrary {
+            setup,
+            darks: vec![],
+        };
+        for exposure in [300, 100, 300] {
+            dark_library.add(DarkFrame {
+                exposure,
+                columns: vec![[0.01; 3]; width],
+            });
+        }
+        assert_eq!(dark_library.darks.len(), 2);
+        assert_eq!(dark_library.darks[0].exposure, 100);
+        config.dark_library = Some(dark_library);
+        assert!(config.validate_references().is_empty());
+        assert_eq!(
+            config.zero_reference.as_ref().unwrap().to_spectrum(),
+            Spectrum::from_element(width, 0.1)
+        );
+
+        config.spectrum_calibration.scaling_setup = Some(setup);
+        config.image_config.window.offset.y += 10.;
+        assert_eq!(
+            config.validate_references(),
+            vec!["zero reference", "dark library", "intensity calibration"]
+        );
+        assert!(config.zero_reference.is_none());
+        assert!(config.dark_library.is_none());
+        assert!(config.spectrum_calibration.scaling.is_none());
+    }
+
+    #[test]
+    fn intensity_unit() {
+        let mut calibration = SpectrumCalibration::default();
+        assert_eq!(calibration.intensity_unit(), IntensityUnit::Counts);
+
+        calibration.scaling = Some(vec![1.; 10]);
+        assert_eq!(calibration.intensity_unit(), IntensityUnit::Relative);
+
+        calibration.scaling_unit = IntensityUnit::SpectralIrradiance;
+        assert_eq!(
+            calibration.intensity_unit().axis_label(),
+            "Spectral Irradiance [W·m⁻²·nm⁻¹]"
+        );
     }
 
     #[test]
@@ -366,6 +2847,25 @@ mod tests {
             }
             assert_eq!(l.linearize(1.), 1.);
         }
+
+        let custom = Linearize::Custom(vec![
+            LinearizationPoint {
+                value: 0.,
+                linear: 0.,
+            },
+            LinearizationPoint {
+                value: 0.5,
+                linear: 0.2,
+            },
+            LinearizationPoint {
+                value: 1.,
+                linear: 1.,
+            },
+        ]);
+        assert_relative_eq!(custom.linearize(0.25), 0.1);
+        assert_relative_eq!(custom.linearize(0.75), 0.6);
+        assert_eq!(custom.linearize(1.), 1.);
+        assert_eq!(Linearize::Custom(vec![]).linearize(0.3), 0.3);
     }
 
     #[test]
@@ -382,6 +2882,7 @@ mod tests {
                 },
             ]),
             scale: 1.0,
+            unit: IntensityUnit::Relative,
         };
 
         assert_eq!(rc.get_value_at_wavelength(100.), Some(1.0));
@@ -389,6 +2890,143 @@ mod tests {
         assert_eq!(rc.get_value_at_wavelength(200.), Some(2.0));
     }
 
+    #[test]
+    fn qe_curve() {
+        let curve = QeCurve {
+            name: "test".to_string(),
+            points: vec![
+                QePoint {
+                    wavelength: 400.,
+                    r: 0.1,
+                    g: 0.2,
+                    b: 0.8,
+                },
+                QePoint {
+                    wavelength: 500.,
+                    r: 0.3,
+                    g: 0.4,
+                    b: 0.01,
+                },
+            ],
+        };
+
+        assert_eq!(curve.get_qe_at_wavelength(300.), (0.1, 0.2, 0.8));
+        assert_eq!(curve.get_qe_at_wavelength(600.), (0.3, 0.4, 0.01));
+        let (r, g, b) = curve.get_qe_at_wavelength(450.);
+        assert_relative_eq!(r, 0.2);
+        assert_relative_eq!(g, 0.3);
+        assert_relative_eq!(b, 0.405);
+
+        let (r, g, b) = curve.get_correction_at_wavelength(400.);
+        assert_relative_eq!(r, 8.);
+        assert_relative_eq!(g, 4.);
+        assert_relative_eq!(b, 1.);
+        assert_relative_eq!(curve.get_correction_at_wavelength(500.).2, 16.);
+    }
+
+    #[test]
+    fn wavelength_band() {
+        let band = WavelengthBand {
+            start: 500.,
+            end: 510.,
+        };
+        let spectrum: Vec<_> = (490..520)
+            .map(|w| SpectrumPoint {
+                wavelength: w as f32,
+                value: w as f32,
+            })
+            .collect();
+
+        assert!(band.contains(500.));
+        assert!(band.contains(510.));
+        assert!(!band.contains(511.));
+        assert_eq!(band.mean_value(&spectrum), Some(505.));
+        assert_eq!(band.mean_value(&spectrum[..5]), None);
+    }
+
+    #[test]
+    fn calibration_profiles() {
+        let format_a = CameraFormat::default();
+        let format_b = CameraFormat::new(
+            nokhwa::Resolution::new(1280, 720),
+            nokhwa::FrameFormat::MJPEG,
+            30,
+        );
+
+        let mut config = SpectrometerConfig::default();
+        assert!(!config.restore_calibration_profile(0, format_a));
+
+        config.spectrum_calibration.low.wavelength = 400;
+        config.store_calibration_profile(0, format_a);
+        config.spectrum_calibration.low.wavelength = 450;
+        config.store_calibration_profile(0, format_b);
+        config.store_calibration_profile(0, format_b);
+        assert_eq!(config.calibration_profiles.len(), 2);
+
+        assert!(!config.restore_calibration_profile(1, format_a));
+        assert!(config.restore_calibration_profile(0, format_a));
+        assert_eq!(config.spectrum_calibration.low.wavelength, 400);
+        assert!(config.restore_calibration_profile(0, format_b));
+        assert_eq!(config.spectrum_calibration.low.wavelength, 450);
+    }
+
+    #[test]
+    fn axis_config() {
+        let mut axis = AxisConfig::default();
+        assert_eq!(axis.convert(500.), 500.);
+        axis.unit = AxisUnit::ElectronVolt;
+        assert_relative_eq!(axis.convert(500.), 2.4797, max_relative = 1e-4);
+        assert_eq!(axis.format(500.), "2.4797");
+        axis.unit = AxisUnit::Terahertz;
+        assert_relative_eq!(axis.convert(500.), 599.585, max_relative = 1e-5);
+        axis.unit = AxisUnit::Wavenumber;
+        assert_eq!(axis.convert(500.), 20000.);
+        assert_relative_eq!(axis.convert_width(500., 1.), 40., max_relative = 1e-3);
+        axis.unit = AxisUnit::RamanShift;
+        axis.laser_wavelength = 500.;
+        assert_eq!(axis.convert(500.), 0.);
+        assert_relative_eq!(axis.convert(520.), 769.23, max_relative = 1e-4);
+        assert_relative_eq!(axis.to_wavelength(769.23), 520., max_relative = 1e-5);
+        assert!(!axis.is_masked(500.));
+        axis.mask_rayleigh = true;
+        assert!(axis.is_masked(500.) && axis.is_masked(497.));
+        assert!(!axis.is_masked(520.));
+        axis.unit = AxisUnit::Wavenumber;
+        assert!(!axis.is_masked(500.));
+    }
+
+    #[test]
+    fn axis_jacobian() {
+        let mut axis = AxisConfig::default();
+        assert_eq!(axis.jacobian(500.), 1.);
+        assert_eq!(axis.intensity_per(), "nm");
+        axis.unit = AxisUnit::ElectronVolt;
+        // λ²/hc in nm/eV
+        assert_relative_eq!(axis.jacobian(500.), 201.64, max_relative = 1e-4);
+        assert_eq!(axis.intensity_per(), "eV");
+        assert_eq!(
+            axis.intensity_label(IntensityUnit::SpectralIrradiance),
+            "Spectral Irradiance [W·m⁻²/eV]"
+        );
+
+        // A flat spectrum keeps its integral over the converted axis
+        let step = 0.1;
+        let integral = (4000..7000)
+            .map(|i| {
+                let wavelength = i as f32 * step;
+                axis.jacobian(wavelength) * axis.convert_width(wavelength, step)
+            })
+            .sum::<f32>();
+        assert_relative_eq!(integral, 300., max_relative = 1e-3);
+
+        axis.jacobian = false;
+        assert_eq!(axis.jacobian(500.), 1.);
+        assert_eq!(
+            axis.intensity_label(IntensityUnit::Counts),
+            IntensityUnit::Counts.axis_label()
+        );
+    }
+
     #[test]
     fn image_config() {
         let mut ic = ImageConfig {
@@ -397,11 +3035,113 @@ mod tests {
                 size: Vec2::new(1000., 500.),
             },
             flip: false,
+            rotation: 0.,
+            presets: vec![],
+            frame_integrity: Default::default(),
+            reference_strip: Some(380.),
+            binning: Binning {
+                center: 400.,
+                ..Default::default()
+            },
+            hot_pixels: None,
+            hot_pixel_threshold: 0.1,
         };
 
         ic.clamp(500., 400.);
 
         assert_eq!(ic.window.offset, Vec2::new(100., 50.));
         assert_eq!(ic.window.size, Vec2::new(400., 350.));
+        let reference = ic.reference_window().unwrap();
+        assert_eq!(reference.offset, Vec2::new(100., 50.));
+        assert_eq!(reference.size, ic.window.size);
+        assert_eq!(ic.binning.center, 349.);
+    }
+
+    #[test]
+    fn window_presets() {
+        let mut ic = ImageConfig::default();
+        let wide = ic.window;
+        ic.store_preset("wide slit");
+
+        ic.window.size.y = 10.;
+        ic.store_preset("narrow slit");
+        ic.window.size.y = 20.;
+        ic.store_preset("narrow slit");
+        assert_eq!(ic.presets.len(), 2);
+
+        assert!(ic.load_preset("wide slit"));
+        assert_eq!(ic.window, wide);
+        assert!(ic.load_preset("narrow slit"));
+        assert_eq!(ic.window.size.y, 20.);
+        assert!(!ic.load_preset("2nd order"));
+        assert_eq!(ic.window.size.y, 20.);
+    }
+
+    #[test]
+    fn reconnect_delay() {
+        let config = ReconnectConfig::default();
+        assert_eq!(config.delay(1), Duration::from_secs(1));
+        assert_eq!(config.delay(3), Duration::from_secs(4));
+        assert_eq!(config.delay(7), Duration::from_secs(60));
+        assert_eq!(config.delay(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn derived_values() {
+        let spectrum = [
+            SpectrumPoint {
+                wavelength: 500.,
+                value: 1.,
+            },
+            SpectrumPoint {
+                wavelength: 510.,
+                value: 3.,
+            },
+        ];
+        let mut config = SpectrometerConfig {
+            derived_values: vec![
+                DerivedValue::new("Mean".to_string(), "mean(500, 510)".to_string()),
+                DerivedValue::new("Invalid".to_string(), "2 *".to_string()),
+            ],
+            ..Default::default()
+        };
+        config.script.source = "#{ last: intensity[1], count: 2 }".to_string();
+        let values = config.derived_values(&spectrum);
+        assert_eq!(values[0], ("Mean".to_string(), 2.));
+        assert!(values[1].1.is_nan());
+        assert_eq!(
+            values[2..],
+            [("count".to_string(), 2.), ("last".to_string(), 3.)]
+        );
+
+        config.script.source = "#{ last: ".to_string();
+        assert_eq!(config.derived_values(&spectrum).len(), 2);
+    }
+
+    #[test]
+    fn spectrum_math() {
+        let mut math = SpectrumMath::default();
+        assert_eq!(math.apply(500., 2.), 2.);
+        math.operation = MathOperation::Subtract;
+        assert!(!math.is_active());
+        math.set_operand(
+            "Solvent",
+            &[
+                SpectrumPoint {
+                    wavelength: 510.,
+                    value: 0.,
+                },
+                SpectrumPoint {
+                    wavelength: 500.,
+                    value: 1.,
+                },
+            ],
+        );
+        assert!(math.is_active());
+        assert_eq!(math.apply(505., 2.), 1.5);
+        assert_eq!(math.apply(520., 2.), 0.);
+        math.operation = MathOperation::Divide;
+        assert_eq!(math.apply(500., 2.), 2.);
+        assert_eq!(math.apply(510., 2.), 0.);
     }
 }
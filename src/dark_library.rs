@@ -0,0 +1,151 @@
+use crate::config::DarkFrame;
+use crate::spectrum::SpectrumRgb;
+
+/// Raw spectra averaged for one dark.
+pub const DARK_FRAMES: usize = 20;
+
+/// Averages raw spectra taken with the lens covered into a [DarkFrame].
+#[derive(Debug)]
+pub struct DarkCapture {
+    exposure: i32,
+    sum: Option<SpectrumRgb>,
+    frames: usize,
+}
+
+impl DarkCapture {
+    pub fn new(exposure: i32) -> Self {
+        Self {
+            exposure,
+            sum: None,
+            frames: 0,
+        }
+    }
+
+    /// Number of spectra added so far.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Add a raw spectrum and return the dark once [DARK_FRAMES] were added. A change of the
+    /// window width starts over.
+    pub fn add(&mut self, spectrum: &SpectrumRgb) -> Option<DarkFrame> {
+        match &mut self.sum {
+            Some(sum) if sum.ncols() == spectrum.ncols() => *sum += spectrum,
+            _ => {
+                self.sum = Some(spectrum.clone());
+                self.frames = 0;
+            }
+        }
+        self.frames += 1;
+        if self.frames < DARK_FRAMES {
+            return None;
+        }
+        let mean = self.sum.as_ref()? / self.frames as f32;
+        Some(DarkFrame {
+            exposure: self.exposure,
+            columns: mean.column_iter().map(|c| [c[0], c[1], c[2]]).collect(),
+        })
+    }
+}
+
+/// Per column dark signal `offset + rate · exposure`, fitted to darks of a library.
+///
+/// The offset is the bias of the sensor and the rate its dark current and hot pixels, so a dark
+/// for any exposure can be subtracted without taking a matching one first.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DarkModel {
+    pub offset: SpectrumRgb,
+    pub rate: SpectrumRgb,
+}
+
+impl DarkModel {
+    /// Least squares fit to the darks, which must have the same width. A single dark, or darks
+    /// all taken at the same exposure, give a constant dark. `None` without darks.
+    pub fn fit(darks: &[DarkFrame]) -> Option<Self> {
+        let ncols = darks.first()?.columns.len();
+        if darks.iter().any(|d| d.columns.len() != ncols) {
+            return None;
+        }
+        let n = darks.len() as f32;
+        let mean_exposure = darks.iter().map(|d| d.exposure as f32).sum::<f32>() / n;
+        let variance = darks
+            .iter()
+            .map(|d| (d.exposure as f32 - mean_exposure).powi(2))
+            .sum::<f32>();
+        let mut offset = SpectrumRgb::zeros(ncols);
+        let mut rate = SpectrumRgb::zeros(ncols);
+        for column in 0..ncols {
+            for channel in 0..3 {
+                let mean = darks
+                    .iter()
+                    .map(|d| d.columns[column][channel])
+                    .sum::<f32>()
+                    / n;
+                let slope = if variance > 0. {
+                    darks
+                        .iter()
+                        .map(|d| {
+                            (d.exposure as f32 - mean_exposure)
+                                * (d.columns[column][channel] - mean)
+                        })
+                        .sum::<f32>()
+                        / variance
+                } else {
+                    0.
+                };
+                rate[(channel, column)] = slope;
+                offset[(channel, column)] = mean - slope * mean_exposure;
+            }
+        }
+        Some(Self { offset, rate })
+    }
+
+    pub fn ncols(&self) -> usize {
+        self.offset.ncols()
+    }
+
+    /// Dark raw spectrum at the exposure.
+    pub fn dark(&self, exposure: i32) -> SpectrumRgb {
+        &self.offset + &self.rate * exposure as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn dark_model() {
+        // Bias of 0.01 and a hot pixel in the second column
+        let dark = |exposure: i32| {
+            let spectrum = SpectrumRgb::from_fn(3, |_, column| {
+                0.01 + if column == 1 { 1e-4 } else { 1e-6 } * exposure as f32
+            });
+            let mut capture = DarkCapture::new(exposure);
+            for _ in 1..DARK_FRAMES {
+                assert_eq!(capture.add(&spectrum), None);
+            }
+            capture.add(&spectrum).unwrap()
+        };
+        let darks = [dark(100), dark(300), dark(500)];
+        assert_eq!(darks[1].exposure, 300);
+
+        let model = DarkModel::fit(&darks).unwrap();
+        assert_eq!(model.ncols(), 3);
+        assert_relative_eq!(model.offset[(0, 1)], 0.01, max_relative = 1e-3);
+        assert_relative_eq!(model.rate[(2, 1)], 1e-4, max_relative = 1e-3);
+        assert_relative_eq!(model.dark(1000)[(1, 1)], 0.11, max_relative = 1e-3);
+        assert_relative_eq!(model.dark(1000)[(1, 0)], 0.011, max_relative = 1e-3);
+
+        let constant = DarkModel::fit(&darks[..1]).unwrap();
+        assert_eq!(constant.dark(1000), constant.dark(0));
+        assert_eq!(DarkModel::fit(&[]), None);
+
+        // A wider spectrum restarts the capture
+        let mut capture = DarkCapture::new(0);
+        capture.add(&SpectrumRgb::zeros(3));
+        capture.add(&SpectrumRgb::zeros(4));
+        assert_eq!(capture.frames(), 1);
+    }
+}
@@ -0,0 +1,269 @@
+//! SQLite database of logged measurements, one row per averaged spectrum with its metadata,
+//! as alternative to log files for long-term monitoring.
+
+use crate::config::{IntensityUnit, SpectrumPoint};
+use crate::fingerprint::spectrum_fingerprint;
+use chrono::{DateTime, Local, Utc};
+use rusqlite::{params, Connection, OpenFlags, Row};
+use std::path::Path;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS measurements (
+    id INTEGER PRIMARY KEY,
+    timestamp_us INTEGER NOT NULL,
+    exposure INTEGER,
+    calibration_id TEXT NOT NULL,
+    unit TEXT NOT NULL,
+    averaged_frames INTEGER NOT NULL,
+    notes TEXT NOT NULL,
+    wavelengths BLOB NOT NULL,
+    intensities BLOB NOT NULL,
+    fingerprint TEXT UNIQUE
+);
+CREATE INDEX IF NOT EXISTS measurements_timestamp ON measurements (timestamp_us);";
+
+const UNITS: [IntensityUnit; 3] = [
+    IntensityUnit::Counts,
+    IntensityUnit::Relative,
+    IntensityUnit::SpectralIrradiance,
+];
+
+/// Conditions a spectrum was measured under.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MeasurementMetadata {
+    /// Value of the exposure control, `None` for sources without one.
+    pub exposure: Option<i32>,
+    /// Fingerprint of the calibration, see [crate::config::SpectrumCalibration::fingerprint].
+    pub calibration_id: String,
+    pub unit: IntensityUnit,
+    pub averaged_frames: usize,
+    pub notes: String,
+}
+
+/// Row of the measurement list, without the spectrum.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MeasurementSummary {
+    pub id: i64,
+    /// Measurement ID, see [crate::fingerprint::spectrum_fingerprint].
+    pub fingerprint: String,
+    pub timestamp: DateTime<Local>,
+    pub metadata: MeasurementMetadata,
+    pub points: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Measurement {
+    pub timestamp: DateTime<Local>,
+    pub metadata: MeasurementMetadata,
+    pub spectrum: Vec<SpectrumPoint>,
+}
+
+fn to_blob(values: impl Iterator<Item = f32>) -> Vec<u8> {
+    values.flat_map(f32::to_le_bytes).collect()
+}
+
+fn from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect()
+}
+
+fn to_local_time(timestamp_us: i64) -> DateTime<Local> {
+    DateTime::<Utc>::from_timestamp_micros(timestamp_us)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+}
+
+/// Metadata in the columns following the timestamp.
+fn metadata(row: &Row) -> rusqlite::Result<MeasurementMetadata> {
+    let unit: String = row.get(4)?;
+    Ok(MeasurementMetadata {
+        exposure: row.get(2)?,
+        calibration_id: row.get(3)?,
+        unit: UNITS
+            .into_iter()
+            .find(|u| u.to_string() == unit)
+            .unwrap_or_default(),
+        averaged_frames: row.get::<_, i64>(5)? as usize,
+        notes: row.get(6)?,
+    })
+}
+
+pub struct MeasurementDatabase {
+    connection: Connection,
+}
+
+impl MeasurementDatabase {
+    /// Open the database at `path`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let connection = Connection::open(path).map_err(|e| e.to_string())?;
+        connection
+            .execute_batch(SCHEMA)
+            .map_err(|e| e.to_string())?;
+        Ok(Self { connection })
+    }
+
+    /// Open the existing database at `path` to browse it, leaving its schema as is.
+    pub fn open_existing<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+            .map_err(|e| e.to_string())?;
+        Ok(Self { connection })
+    }
+
+    /// Store the measurement, returning its id, or `None` if the same spectrum is stored
+    /// already.
+    pub fn insert(&self, measurement: &Measurement) -> Result<Option<i64>, String> {
+        let metadata = &measurement.metadata;
+        let inserted = self
+            .connection
+            .execute(
+                "INSERT INTO measurements (timestamp_us, exposure, calibration_id, unit, \
+                 averaged_frames, notes, wavelengths, intensities, fingerprint) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9) \
+                 ON CONFLICT (fingerprint) DO NOTHING",
+                params![
+                    measurement.timestamp.timestamp_micros(),
+                    metadata.exposure,
+                    metadata.calibration_id,
+                    metadata.unit.to_string(),
+                    metadata.averaged_frames as i64,
+                    metadata.notes,
+                    to_blob(measurement.spectrum.iter().map(|p| p.wavelength)),
+                    to_blob(measurement.spectrum.iter().map(|p| p.value)),
+                    spectrum_fingerprint(&measurement.spectrum, metadata.unit).to_string(),
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok((inserted > 0).then(|| self.connection.last_insert_rowid()))
+    }
+
+    /// Every measurement, the newest first.
+    pub fn list(&self) -> Result<Vec<MeasurementSummary>, String> {
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT id, timestamp_us, exposure, calibration_id, unit, averaged_frames, \
+                 notes, length(wavelengths) / 4, fingerprint FROM measurements \
+                 ORDER BY timestamp_us DESC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok(MeasurementSummary {
+                    id: row.get(0)?,
+                    fingerprint: row.get(8)?,
+                    timestamp: to_local_time(row.get(1)?),
+                    metadata: metadata(row)?,
+                    points: row.get::<_, i64>(7)? as usize,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<rusqlite::Result<_>>()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn load(&self, id: i64) -> Result<Measurement, String> {
+        self.connection
+            .query_row(
+                "SELECT id, timestamp_us, exposure, calibration_id, unit, averaged_frames, \
+                 notes, wavelengths, intensities FROM measurements WHERE id = ?1",
+                [id],
+                |row| {
+                    let wavelengths: Vec<u8> = row.get(7)?;
+                    let intensities: Vec<u8> = row.get(8)?;
+                    Ok(Measurement {
+                        timestamp: to_local_time(row.get(1)?),
+                        metadata: metadata(row)?,
+                        spectrum: from_blob(&wavelengths)
+                            .into_iter()
+                            .zip(from_blob(&intensities))
+                            .map(|(wavelength, value)| SpectrumPoint { wavelength, value })
+                            .collect(),
+                    })
+                },
+            )
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn delete(&self, id: i64) -> Result<(), String> {
+        self.connection
+            .execute("DELETE FROM measurements WHERE id = ?1", [id])
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn database() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("measurements.db");
+        let database = MeasurementDatabase::open(&path).unwrap();
+        let measurement = |seconds: i64, exposure: Option<i32>| Measurement {
+            timestamp: Local.timestamp_opt(seconds, 0).unwrap(),
+            metadata: MeasurementMetadata {
+                exposure,
+                calibration_id: "d228cb696f1a8caf78912b704e4a8964".to_string(),
+                unit: IntensityUnit::Relative,
+                averaged_frames: 10,
+                notes: "Lamp warm-up".to_string(),
+            },
+            spectrum: vec![
+                SpectrumPoint {
+                    wavelength: 500.,
+                    value: 0.25,
+                },
+                SpectrumPoint {
+                    wavelength: 501.,
+                    value: -1.5,
+                },
+            ],
+        };
+        let first = database
+            .insert(&measurement(1_650_000_000, Some(156)))
+            .unwrap()
+            .unwrap();
+        let mut changed = measurement(1_650_000_060, None);
+        changed.spectrum[1].value = -1.25;
+        let second = database.insert(&changed).unwrap().unwrap();
+        // The same spectrum is stored once, whatever it was measured under
+        assert_eq!(
+            database.insert(&measurement(1_650_000_120, None)).unwrap(),
+            None
+        );
+
+        let list = database.list().unwrap();
+        assert_eq!(
+            list.iter().map(|m| m.id).collect::<Vec<_>>(),
+            vec![second, first]
+        );
+        assert_eq!(list[1].metadata, measurement(0, Some(156)).metadata);
+        assert_eq!(list[1].points, 2);
+        assert_eq!(
+            list[1].fingerprint,
+            spectrum_fingerprint(&measurement(0, None).spectrum, IntensityUnit::Relative)
+                .to_string()
+        );
+        assert_eq!(
+            database.load(first).unwrap(),
+            measurement(1_650_000_000, Some(156))
+        );
+
+        database.delete(first).unwrap();
+        assert_eq!(database.list().unwrap().len(), 1);
+        assert!(database.load(first).is_err());
+
+        // Measurements are kept when the database is opened again
+        drop(database);
+        let database = MeasurementDatabase::open_existing(&path).unwrap();
+        assert_eq!(database.load(second).unwrap(), changed);
+
+        // Browsing does not create databases
+        let missing = directory.path().join("missing.db");
+        assert!(MeasurementDatabase::open_existing(&missing).is_err());
+        assert!(!missing.exists());
+    }
+}
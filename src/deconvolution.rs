@@ -0,0 +1,142 @@
+/// Smallest value used in divisions, as Richardson-Lucy needs positive data.
+const EPSILON: f32 = 1e-6;
+
+/// Cut the instrument line shape around the maximum of a narrow line spectrum.
+///
+/// The background is removed and the result is normalized to a sum of one.
+pub fn extract_line_shape(spectrum: &[f32], half_width: usize) -> Option<Vec<f32>> {
+    let (peak, _) = spectrum
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+    if peak < half_width || peak + half_width >= spectrum.len() {
+        return None;
+    }
+
+    let window = &spectrum[peak - half_width..=peak + half_width];
+    let background = window.iter().copied().fold(f32::INFINITY, f32::min);
+    let line_shape: Vec<f32> = window.iter().map(|v| v - background).collect();
+    let sum: f32 = line_shape.iter().sum();
+    (sum > EPSILON).then(|| line_shape.into_iter().map(|v| v / sum).collect())
+}
+
+/// Normalized Gaussian line shape with a FWHM of `fwhm` pixels, cut at three standard
+/// deviations, for a known instrument line width instead of a measured line shape.
+pub fn gaussian_line_shape(fwhm: f32) -> Vec<f32> {
+    let sigma = (fwhm / (2. * (2. * std::f32::consts::LN_2).sqrt())).max(EPSILON);
+    let half_width = (3. * sigma).ceil().max(1.) as isize;
+    let line_shape: Vec<f32> = (-half_width..=half_width)
+        .map(|i| (-(i as f32).powi(2) / (2. * sigma.powi(2))).exp())
+        .collect();
+    let sum: f32 = line_shape.iter().sum();
+    line_shape.into_iter().map(|v| v / sum).collect()
+}
+
+/// Richardson-Lucy deconvolution with total variation regularization.
+///
+/// `line_shape` must have an odd length and be centered. A `regularization` of zero gives
+/// the plain Richardson-Lucy algorithm, larger values suppress noise amplification.
+pub fn richardson_lucy(
+    observed: &[f32],
+    line_shape: &[f32],
+    iterations: usize,
+    regularization: f32,
+) -> Vec<f32> {
+    let observed: Vec<f32> = observed.iter().map(|v| v.max(EPSILON)).collect();
+    let mirrored: Vec<f32> = line_shape.iter().rev().copied().collect();
+    let mut estimate = observed.clone();
+
+    for _ in 0..iterations {
+        let blurred = convolve(&estimate, line_shape);
+        let ratio: Vec<f32> = observed
+            .iter()
+            .zip(&blurred)
+            .map(|(o, b)| o / b.max(EPSILON))
+            .collect();
+        let correction = convolve(&ratio, &mirrored);
+
+        let total_variation = total_variation_divergence(&estimate);
+        estimate
+            .iter_mut()
+            .zip(correction)
+            .zip(total_variation)
+            .for_each(|((e, c), tv)| {
+                *e = (*e * c / (1. - regularization * tv).max(EPSILON)).max(EPSILON);
+            });
+    }
+    estimate
+}
+
+/// Convolution with the same length as `signal`, repeating the edge values.
+pub(crate) fn convolve(signal: &[f32], kernel: &[f32]) -> Vec<f32> {
+    let half_width = (kernel.len() / 2) as isize;
+    let last = signal.len() as isize - 1;
+    (0..signal.len() as isize)
+        .map(|i| {
+            kernel
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * signal[(i + half_width - k as isize).clamp(0, last) as usize])
+                .sum()
+        })
+        .collect()
+}
+
+/// Divergence of the normalized gradient in one dimension.
+fn total_variation_divergence(signal: &[f32]) -> Vec<f32> {
+    let gradient_sign = |i: usize| {
+        signal
+            .get(i + 1)
+            .map_or(0., |next| (next - signal[i]).signum())
+    };
+    (0..signal.len())
+        .map(|i| gradient_sign(i) - if i > 0 { gradient_sign(i - 1) } else { 0. })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn line_shape() {
+        let spectrum = [1., 1., 2., 5., 2., 1., 1.];
+        let line_shape = extract_line_shape(&spectrum, 2).unwrap();
+        assert_relative_eq!(
+            line_shape.as_slice(),
+            [0., 1. / 6., 4. / 6., 1. / 6., 0.].as_slice()
+        );
+
+        assert_eq!(extract_line_shape(&spectrum, 4), None);
+        assert_eq!(extract_line_shape(&[1.; 7], 1), None);
+
+        let gaussian = gaussian_line_shape(4.);
+        assert_eq!(gaussian.len(), 13);
+        assert_relative_eq!(gaussian.iter().sum::<f32>(), 1., max_relative = 1e-5);
+        // Half of the maximum two pixels from the center
+        assert_relative_eq!(gaussian[4], gaussian[6] / 2., max_relative = 1e-4);
+        assert_eq!(gaussian_line_shape(0.), vec![0., 1., 0.]);
+    }
+
+    #[test]
+    fn deconvolve() {
+        let line_shape = [0.25, 0.5, 0.25];
+        let mut sharp = vec![0.01; 21];
+        sharp[8] = 1.;
+        sharp[11] = 1.;
+        let blurred = convolve(&sharp, &line_shape);
+
+        let deconvolved = richardson_lucy(&blurred, &line_shape, 50, 0.);
+        assert!(deconvolved[8] > blurred[8]);
+        assert!(deconvolved[11] > blurred[11]);
+        assert!(deconvolved[9] < blurred[9]);
+        assert_relative_eq!(
+            deconvolved.iter().sum::<f32>(),
+            blurred.iter().sum::<f32>(),
+            max_relative = 0.01
+        );
+
+        assert_eq!(richardson_lucy(&blurred, &line_shape, 0, 0.), blurred);
+    }
+}
@@ -0,0 +1,413 @@
+//! Control of a lamp and a shutter over a serial port, to switch them automatically around
+//! measurements which need the light off, or on only while measuring.
+
+use crate::config::DeviceControlConfig;
+use flume::{Receiver, Sender};
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Timeout for writing a command to the port.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum DeviceAction {
+    LampOn,
+    LampOff,
+    ShutterOpen,
+    ShutterClose,
+}
+
+impl Display for DeviceAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceAction::LampOn => write!(f, "Lamp On"),
+            DeviceAction::LampOff => write!(f, "Lamp Off"),
+            DeviceAction::ShutterOpen => write!(f, "Open Shutter"),
+            DeviceAction::ShutterClose => write!(f, "Close Shutter"),
+        }
+    }
+}
+
+/// Bytes of a command template, with the escapes `\n`, `\r`, `\t`, `\\` and `\xNN` for any
+/// byte in hex.
+pub fn parse_template(template: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('t') => bytes.push(b'\t'),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 2)
+                    .ok_or_else(|| format!("Invalid escape \\x{} in \"{}\"", hex, template))?;
+                bytes.push(byte);
+            }
+            other => {
+                return Err(format!(
+                    "Invalid escape \\{} in \"{}\"",
+                    other.map(String::from).unwrap_or_default(),
+                    template
+                ))
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// Names of the serial ports of the system.
+pub fn available_ports() -> Vec<String> {
+    serialport::available_ports()
+        .map(|ports| ports.into_iter().map(|p| p.port_name).collect())
+        .unwrap_or_default()
+}
+
+/// Bytes to write and what they are for, to report a failure.
+struct SerialCommand {
+    bytes: Vec<u8>,
+    description: String,
+}
+
+/// Serial port with the lamp and shutter on it, opened and written on a thread of its own so
+/// that a stuck adapter does not block the GUI. Failures are reported by [Self::errors].
+pub struct SerialDevice {
+    command_tx: Sender<SerialCommand>,
+    error_rx: Receiver<String>,
+    handle: JoinHandle<()>,
+}
+
+impl SerialDevice {
+    pub fn open(config: &DeviceControlConfig) -> Self {
+        let (command_tx, command_rx) = flume::unbounded::<SerialCommand>();
+        let (error_tx, error_rx) = flume::unbounded();
+        let (port_name, baud_rate) = (config.port.clone(), config.baud_rate);
+        let handle = std::thread::spawn(move || {
+            let mut port = match serialport::new(&port_name, baud_rate)
+                .timeout(WRITE_TIMEOUT)
+                .open()
+            {
+                Ok(port) => port,
+                Err(e) => {
+                    let _ = error_tx.send(format!("Could not open {}: {}", port_name, e));
+                    return;
+                }
+            };
+            // Until the device is dropped
+            for command in command_rx.iter() {
+                if let Err(e) = port.write_all(&command.bytes).and_then(|_| port.flush()) {
+                    let _ = error_tx.send(format!("Could not send {}: {}", command.description, e));
+                }
+            }
+        });
+        Self {
+            command_tx,
+            error_rx,
+            handle,
+        }
+    }
+
+    /// Whether the port is open, `false` once opening it failed.
+    pub fn is_connected(&self) -> bool {
+        !self.handle.is_finished()
+    }
+
+    /// Failures since the last call.
+    pub fn errors(&self) -> Vec<String> {
+        self.error_rx.drain().collect()
+    }
+
+    /// Send the command of `action`, nothing if its template is empty.
+    pub fn send(&self, action: DeviceAction, config: &DeviceControlConfig) -> Result<(), String> {
+        self.send_template(config.command(action), &format!("the {} command", action))
+    }
+
+    /// Queue the bytes of a command template, see [parse_template], with `description` of
+    /// the command for the error if writing fails.
+    pub fn send_template(&self, template: &str, description: &str) -> Result<(), String> {
+        let bytes = parse_template(template)?;
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.command_tx
+            .send(SerialCommand {
+                bytes,
+                description: description.to_string(),
+            })
+            .map_err(|_| "The device control is disconnected".to_string())
+    }
+
+    /// Switch the lamp on and open the shutter, or close the shutter and switch the lamp off.
+    pub fn switch_light(&self, on: bool, config: &DeviceControlConfig) -> Result<(), String> {
+        let actions = match on {
+            true => [DeviceAction::LampOn, DeviceAction::ShutterOpen],
+            false => [DeviceAction::ShutterClose, DeviceAction::LampOff],
+        };
+        actions
+            .into_iter()
+            .try_for_each(|action| self.send(action, config))
+    }
+}
+
+/// Measurement the device control switches the light for.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum SwitchedMeasurement {
+    Dark,
+    ZeroReference,
+    LampCheck,
+    /// Spectrum of a logging interval.
+    Log,
+}
+
+impl Display for SwitchedMeasurement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SwitchedMeasurement::Dark => write!(f, "Dark"),
+            SwitchedMeasurement::ZeroReference => write!(f, "Zero Reference"),
+            SwitchedMeasurement::LampCheck => write!(f, "Lamp Check"),
+            SwitchedMeasurement::Log => write!(f, "Logged Spectrum"),
+        }
+    }
+}
+
+impl SwitchedMeasurement {
+    /// Whether the light is on during the measurement, and off before and after it.
+    pub fn needs_light(&self) -> bool {
+        matches!(
+            self,
+            SwitchedMeasurement::LampCheck | SwitchedMeasurement::Log
+        )
+    }
+}
+
+/// What the GUI has to do next for a switched measurement.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum SequenceStep {
+    /// Switch the light on or off, see [SerialDevice::switch_light].
+    Switch(bool),
+    /// The light settled, discard the averaging buffer.
+    ClearBuffer,
+    /// Take the measurement and call [DeviceSequence::measured] once it is done.
+    Measure,
+}
+
+/// What to do next while waiting for the light or a filter wheel after a change.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum SettleStep {
+    /// Settled, discard the averaging buffer.
+    ClearBuffer,
+    /// The averaging buffer holds spectra from after the change only.
+    Ready,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SettleStage {
+    Settling(Instant),
+    /// Waiting for a full averaging buffer with spectra from after the time.
+    Filling(Instant),
+}
+
+/// Waits for the settle time after switching the light or moving a filter wheel, then for
+/// the averaging buffer to refill with spectra from after it.
+#[derive(Debug, Clone, Copy)]
+pub struct Settling {
+    settle_time: Duration,
+    refill: bool,
+    stage: SettleStage,
+}
+
+impl Settling {
+    /// Changed at `now`. Without `refill`, it is ready once settled.
+    pub fn new(now: Instant, settle_time: Duration, refill: bool) -> Self {
+        Self {
+            settle_time,
+            refill,
+            stage: SettleStage::Settling(now),
+        }
+    }
+
+    /// Advance at `now`, with `buffer_since` the acquisition time of the oldest spectrum of
+    /// the averaging buffer, if it is full.
+    pub fn next_step(&mut self, now: Instant, buffer_since: Option<Instant>) -> Option<SettleStep> {
+        match self.stage {
+            SettleStage::Settling(changed)
+                if now.saturating_duration_since(changed) >= self.settle_time =>
+            {
+                if !self.refill {
+                    return Some(SettleStep::Ready);
+                }
+                self.stage = SettleStage::Filling(now);
+                Some(SettleStep::ClearBuffer)
+            }
+            SettleStage::Filling(cleared) if buffer_since.is_some_and(|since| since >= cleared) => {
+                Some(SettleStep::Ready)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    Start,
+    Settling(Settling),
+    Measuring,
+    Restore,
+    Done,
+}
+
+/// Switches the light for a measurement, waits for it to settle and the averaging buffer to
+/// refill, and switches it back afterwards.
+#[derive(Debug)]
+pub struct DeviceSequence {
+    measurement: SwitchedMeasurement,
+    settle_time: Duration,
+    stage: Stage,
+}
+
+impl DeviceSequence {
+    pub fn new(measurement: SwitchedMeasurement, settle_time: Duration) -> Self {
+        Self {
+            measurement,
+            settle_time,
+            stage: Stage::Start,
+        }
+    }
+
+    pub fn measurement(&self) -> SwitchedMeasurement {
+        self.measurement
+    }
+
+    pub fn is_measuring(&self) -> bool {
+        matches!(self.stage, Stage::Measuring)
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.stage, Stage::Done)
+    }
+
+    /// The measurement is taken, switch the light back.
+    pub fn measured(&mut self) {
+        if self.is_measuring() {
+            self.stage = Stage::Restore;
+        }
+    }
+
+    /// Advance at `now`, with `buffer_since` the acquisition time of the oldest spectrum of
+    /// the averaging buffer, if it is full.
+    pub fn next_step(
+        &mut self,
+        now: Instant,
+        buffer_since: Option<Instant>,
+    ) -> Option<SequenceStep> {
+        match &mut self.stage {
+            Stage::Start => {
+                // A dark capture averages the spectra following its start by itself
+                let refill = self.measurement != SwitchedMeasurement::Dark;
+                self.stage = Stage::Settling(Settling::new(now, self.settle_time, refill));
+                Some(SequenceStep::Switch(self.measurement.needs_light()))
+            }
+            Stage::Settling(settling) => match settling.next_step(now, buffer_since)? {
+                SettleStep::ClearBuffer => Some(SequenceStep::ClearBuffer),
+                SettleStep::Ready => {
+                    self.stage = Stage::Measuring;
+                    Some(SequenceStep::Measure)
+                }
+            },
+            Stage::Restore => {
+                self.stage = Stage::Done;
+                Some(SequenceStep::Switch(!self.measurement.needs_light()))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_control() {
+        assert_eq!(parse_template("L1\\r\\n").unwrap(), b"L1\r\n");
+        assert_eq!(parse_template("\\x02S\\x030\\\\").unwrap(), b"\x02S\x030\\");
+        assert_eq!(parse_template("").unwrap(), b"");
+        assert!(parse_template("\\x2").is_err());
+        assert!(parse_template("\\q").is_err());
+        assert!(parse_template("L1\\").is_err());
+
+        let start = Instant::now();
+        let settle_time = Duration::from_secs(1);
+        let mut sequence = DeviceSequence::new(SwitchedMeasurement::Log, settle_time);
+        assert_eq!(
+            sequence.next_step(start, None),
+            Some(SequenceStep::Switch(true))
+        );
+        assert_eq!(sequence.next_step(start, Some(start)), None);
+        let settled = start + settle_time;
+        assert_eq!(
+            sequence.next_step(settled, None),
+            Some(SequenceStep::ClearBuffer)
+        );
+        // Spectra from before the buffer was cleared do not count
+        assert_eq!(sequence.next_step(settled, Some(start)), None);
+        let filled = settled + settle_time;
+        assert_eq!(
+            sequence.next_step(filled, Some(settled)),
+            Some(SequenceStep::Measure)
+        );
+        assert_eq!(sequence.next_step(filled, Some(settled)), None);
+        sequence.measured();
+        assert_eq!(
+            sequence.next_step(filled, None),
+            Some(SequenceStep::Switch(false))
+        );
+        assert!(sequence.is_done());
+
+        let mut sequence = DeviceSequence::new(SwitchedMeasurement::Dark, settle_time);
+        assert_eq!(
+            sequence.next_step(start, None),
+            Some(SequenceStep::Switch(false))
+        );
+        assert_eq!(
+            sequence.next_step(settled, None),
+            Some(SequenceStep::Measure)
+        );
+        assert!(sequence.is_measuring());
+    }
+
+    #[test]
+    fn missing_port() {
+        let directory = tempfile::tempdir().unwrap();
+        let config = DeviceControlConfig {
+            port: directory
+                .path()
+                .join("missing")
+                .to_string_lossy()
+                .to_string(),
+            lamp_on: "L1".to_string(),
+            ..Default::default()
+        };
+        let device = SerialDevice::open(&config);
+        let started = Instant::now();
+        while device.is_connected() {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(device.errors()[0].starts_with("Could not open"));
+        assert!(device.send(DeviceAction::LampOn, &config).is_err());
+        // Nothing to send
+        assert!(device.send(DeviceAction::LampOff, &config).is_ok());
+        assert!(device.send_template("\\q", "a test").is_err());
+    }
+}
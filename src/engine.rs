@@ -0,0 +1,269 @@
+use crate::config::{RemoteDecimation, RemoteStreamConfig};
+use crate::spectrum::Spectrum;
+use flume::{Receiver, Sender, TrySendError};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Spectra queued for a subscriber which does not keep up, further ones are dropped.
+pub const SUBSCRIBER_QUEUE: usize = 16;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize)]
+pub struct SpectrumFlags {
+    pub linearized: bool,
+    pub intensity_calibrated: bool,
+    pub filtered: bool,
+    pub zero_reference_applied: bool,
+    pub dark_subtracted: bool,
+    pub baseline_corrected: bool,
+    pub deconvolved: bool,
+    /// Changed by the user's transform expression.
+    pub transformed: bool,
+}
+
+/// Calibrated spectrum with its wavelength axis and acquisition metadata.
+#[derive(Debug, Clone)]
+pub struct ProcessedSpectrum {
+    pub timestamp: SystemTime,
+    /// Wavelength in nm of each column of `channels`.
+    pub wavelengths: Vec<f32>,
+    /// Rows are r, g, b and the combined sum.
+    pub channels: Spectrum,
+    /// Number of frames in the averaging buffer.
+    pub averaged_frames: usize,
+    pub flags: SpectrumFlags,
+}
+
+impl ProcessedSpectrum {
+    /// Spectrum with the columns reduced as configured for remote subscribers.
+    pub fn decimated(&self, decimation: RemoteDecimation) -> Self {
+        let ncols = self.wavelengths.len();
+        let columns: Vec<f32> = match decimation {
+            RemoteDecimation::EveryNth(n) if n > 1 => {
+                (0..ncols).step_by(n).map(|i| i as f32).collect()
+            }
+            RemoteDecimation::Resample(points) if points > 1 && ncols > 1 => (0..points)
+                .map(|i| i as f32 * (ncols - 1) as f32 / (points - 1) as f32)
+                .collect(),
+            _ => return self.clone(),
+        };
+        let interpolate = |values: &dyn Fn(usize) -> f32, column: f32| {
+            let lower = (column.floor() as usize).min(ncols - 1);
+            let upper = (lower + 1).min(ncols - 1);
+            let t = column - lower as f32;
+            values(lower) + t * (values(upper) - values(lower))
+        };
+        Self {
+            timestamp: self.timestamp,
+            wavelengths: columns
+                .iter()
+                .map(|&c| interpolate(&|i| self.wavelengths[i], c))
+                .collect(),
+            channels: Spectrum::from_fn(columns.len(), |row, i| {
+                interpolate(&|j| self.channels[(row, j)], columns[i])
+            }),
+            averaged_frames: self.averaged_frames,
+            flags: self.flags,
+        }
+    }
+
+    /// JSON object with the timestamp in ms since the UNIX epoch, the wavelengths, one array
+    /// per channel and the metadata. Values which are not finite are `null`.
+    pub fn to_json(&self) -> String {
+        let channel = |row: usize| self.channels.row(row).iter().copied().collect();
+        serde_json::to_string(&JsonSpectrum {
+            timestamp_ms: self
+                .timestamp
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+            wavelengths: &self.wavelengths,
+            r: channel(0),
+            g: channel(1),
+            b: channel(2),
+            sum: channel(3),
+            averaged_frames: self.averaged_frames,
+            flags: self.flags,
+        })
+        .unwrap()
+    }
+}
+
+/// Layout of [ProcessedSpectrum::to_json].
+#[derive(Serialize)]
+struct JsonSpectrum<'a> {
+    timestamp_ms: u64,
+    wavelengths: &'a [f32],
+    r: Vec<f32>,
+    g: Vec<f32>,
+    b: Vec<f32>,
+    sum: Vec<f32>,
+    averaged_frames: usize,
+    flags: SpectrumFlags,
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    tx: Sender<ProcessedSpectrum>,
+    /// Whether the subscriber gets the spectra reduced to the remote stream settings.
+    remote: bool,
+    last_sent: Option<Instant>,
+}
+
+/// Handle to distribute processed spectra to in-process subscribers, like the servers
+/// streaming them to remote clients.
+///
+/// Every spectrum is published by the spectrum worker of the camera stream, see
+/// [crate::camera_stream::CameraStream::spawn], independent of whether and how often the GUI
+/// shows it, so the library can be used without the GUI. Each subscriber has a queue of [SUBSCRIBER_QUEUE] spectra. When it is full, new spectra are
+/// dropped for that subscriber and counted in [SpectroEngine::dropped_spectra]. Remote
+/// subscribers also skip spectra to keep the maximum rate of the remote stream, which is
+/// not counted.
+///
+/// Clones share the same subscribers.
+#[derive(Debug, Clone, Default)]
+pub struct SpectroEngine {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    remote_stream: Arc<Mutex<RemoteStreamConfig>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl SpectroEngine {
+    /// Subscribe to every spectrum in full resolution.
+    pub fn subscribe(&self) -> Receiver<ProcessedSpectrum> {
+        self.add_subscriber(false)
+    }
+
+    /// Subscribe to spectra decimated and limited in rate by the remote stream settings.
+    pub fn subscribe_remote(&self) -> Receiver<ProcessedSpectrum> {
+        self.add_subscriber(true)
+    }
+
+    fn add_subscriber(&self, remote: bool) -> Receiver<ProcessedSpectrum> {
+        let (tx, rx) = flume::bounded(SUBSCRIBER_QUEUE);
+        self.subscribers.lock().unwrap().push(Subscriber {
+            tx,
+            remote,
+            last_sent: None,
+        });
+        rx
+    }
+
+    pub fn set_remote_stream(&self, config: RemoteStreamConfig) {
+        *self.remote_stream.lock().unwrap() = config;
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        !self.subscribers.lock().unwrap().is_empty()
+    }
+
+    /// Spectra dropped because the queue of a subscriber was full.
+    pub fn dropped_spectra(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Send the spectrum to all subscribers and forget the ones whose receiver was dropped.
+    /// Remote subscribers which got a spectrum less than the minimum interval ago are skipped.
+    pub fn publish(&self, spectrum: ProcessedSpectrum) {
+        let send = |tx: &Sender<ProcessedSpectrum>, spectrum: ProcessedSpectrum| match tx
+            .try_send(spectrum)
+        {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        };
+        let remote_stream = *self.remote_stream.lock().unwrap();
+        let interval = (remote_stream.max_rate > 0.)
+            .then(|| Duration::from_secs_f32(1. / remote_stream.max_rate));
+        let now = Instant::now();
+        let mut decimated = None;
+        self.subscribers.lock().unwrap().retain_mut(|subscriber| {
+            if !subscriber.remote {
+                return send(&subscriber.tx, spectrum.clone());
+            }
+            if let (Some(interval), Some(last_sent)) = (interval, subscriber.last_sent) {
+                if now.duration_since(last_sent) < interval {
+                    return !subscriber.tx.is_disconnected();
+                }
+            }
+            subscriber.last_sent = Some(now);
+            let decimated =
+                decimated.get_or_insert_with(|| spectrum.decimated(remote_stream.decimation));
+            send(&subscriber.tx, decimated.clone())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn processed_spectrum() -> ProcessedSpectrum {
+        ProcessedSpectrum {
+            timestamp: SystemTime::now(),
+            wavelengths: vec![400., 500.],
+            channels: Spectrum::from_element(2, 0.5),
+            averaged_frames: 1,
+            flags: SpectrumFlags::default(),
+        }
+    }
+
+    #[test]
+    fn subscribe() {
+        let engine = SpectroEngine::default();
+        assert!(!engine.has_subscribers());
+
+        let rx1 = engine.subscribe();
+        let rx2 = engine.clone().subscribe();
+        engine.publish(processed_spectrum());
+
+        assert_eq!(rx1.try_recv().unwrap().wavelengths, vec![400., 500.]);
+        assert_eq!(rx2.try_recv().unwrap().channels.ncols(), 2);
+
+        drop(rx1);
+        engine.publish(processed_spectrum());
+        assert_eq!(engine.subscribers.lock().unwrap().len(), 1);
+        assert!(rx2.try_recv().is_ok());
+
+        // A subscriber which does not keep up
+        for _ in 0..SUBSCRIBER_QUEUE + 2 {
+            engine.publish(processed_spectrum());
+        }
+        assert_eq!(rx2.drain().count(), SUBSCRIBER_QUEUE);
+        assert_eq!(engine.dropped_spectra(), 2);
+    }
+
+    #[test]
+    fn remote_stream() {
+        let spectrum = ProcessedSpectrum {
+            wavelengths: (0..10).map(|i| 400. + 10. * i as f32).collect(),
+            channels: Spectrum::from_fn(10, |row, i| (row * 100 + i) as f32),
+            ..processed_spectrum()
+        };
+        let every_third = spectrum.decimated(RemoteDecimation::EveryNth(3));
+        assert_eq!(every_third.wavelengths, vec![400., 430., 460., 490.]);
+        assert_eq!(every_third.channels[(3, 1)], 303.);
+        let resampled = spectrum.decimated(RemoteDecimation::Resample(4));
+        assert_eq!(resampled.wavelengths, vec![400., 430., 460., 490.]);
+        let resampled = spectrum.decimated(RemoteDecimation::Resample(7));
+        assert_eq!(resampled.wavelengths[1], 415.);
+        assert_eq!(resampled.channels[(1, 1)], 101.5);
+
+        let engine = SpectroEngine::default();
+        engine.set_remote_stream(RemoteStreamConfig {
+            decimation: RemoteDecimation::Resample(5),
+            max_rate: 0.001,
+        });
+        let local = engine.subscribe();
+        let remote = engine.subscribe_remote();
+        engine.publish(spectrum.clone());
+        engine.publish(spectrum);
+        assert_eq!(local.drain().count(), 2);
+        let sent: Vec<_> = remote.drain().collect();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].wavelengths.len(), 5);
+    }
+}
@@ -0,0 +1,498 @@
+use crate::colorimetry::colorimetry;
+use crate::config::{AxisConfig, AxisUnit, IntensityUnit, SpectrumCalibration, SpectrumPoint};
+use crate::spectrum::SpectrumExportPoint;
+use chrono::{Datelike, Local, Timelike};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+
+const JCAMP_Y_FACTOR: f64 = 1e-6;
+/// Largest deviation of an X step from the mean step, relative to it, for which the X values
+/// count as equally spaced.
+const JCAMP_SPACING_TOLERANCE: f32 = 1e-3;
+
+// Galactic SPC layout, shared with the reader in crate::import
+pub(crate) const SPC_HEADER_SIZE: usize = 512;
+pub(crate) const SPC_SUBHEADER_SIZE: usize = 32;
+pub(crate) const SPC_LOG_HEADER_SIZE: usize = 64;
+/// Position of the log block offset in the header.
+pub(crate) const SPC_LOG_OFFSET: usize = 248;
+/// New format, little endian.
+pub(crate) const SPC_VERSION: u8 = 0x4b;
+/// Y values are 16 bit integers.
+pub(crate) const SPC_FLAG_16_BIT: u8 = 0x01;
+/// Every subfile has its own X values.
+pub(crate) const SPC_FLAG_XYXY: u8 = 0x40;
+/// Explicit X values follow the header.
+pub(crate) const SPC_FLAG_XVALUES: u8 = 0x80;
+/// Exponent marking IEEE floats as Y values.
+pub(crate) const SPC_FLOAT_EXPONENT: u8 = 0x80;
+/// Labels of the log block entries read back by [crate::import::read_spc].
+pub(crate) const SPC_INTENSITY_PER_LABEL: &str = "INTENSITY PER=";
+pub(crate) const SPC_LASER_LABEL: &str = "LASER WAVELENGTH=";
+
+/// Spectrum as served by the PublicLab Spectral Workbench API.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SpectralWorkbenchSpectrum {
+    pub title: Option<String>,
+    pub notes: Option<String>,
+    pub data: SpectralWorkbenchData,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SpectralWorkbenchData {
+    pub name: Option<String>,
+    pub lines: Vec<SpectralWorkbenchLine>,
+}
+
+/// Intensities of a column of the camera image, `wavelength` is missing for uncalibrated
+/// spectra.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct SpectralWorkbenchLine {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub average: f32,
+    pub wavelength: Option<f32>,
+}
+
+/// Value of the JCAMP-DX `XUNITS` label.
+fn jcamp_x_units(unit: AxisUnit) -> &'static str {
+    match unit {
+        AxisUnit::Nanometer => "NANOMETERS",
+        AxisUnit::ElectronVolt => "EV",
+        AxisUnit::Terahertz => "THZ",
+        AxisUnit::Wavenumber | AxisUnit::RamanShift => "1/CM",
+    }
+}
+
+/// Value of the JCAMP-DX `YUNITS` label, spectral irradiance per axis unit if converted.
+fn jcamp_y_units(calibration: &SpectrumCalibration, axis: &AxisConfig) -> String {
+    match calibration.intensity_unit() {
+        IntensityUnit::SpectralIrradiance if axis.converts_intensity() => {
+            format!("W/(M2 {})", jcamp_x_units(axis.unit))
+        }
+        unit => unit.jcamp_units().to_string(),
+    }
+}
+
+/// SPC X unit code and the factor from the axis unit to it, THz are stored as GHz.
+fn spc_x_type(unit: AxisUnit) -> (u8, f32) {
+    match unit {
+        AxisUnit::Nanometer => (3, 1.),
+        AxisUnit::ElectronVolt => (14, 1.),
+        AxisUnit::Terahertz => (26, 1000.),
+        AxisUnit::Wavenumber => (1, 1.),
+        AxisUnit::RamanShift => (13, 1.),
+    }
+}
+
+/// Write the sum channel as JCAMP-DX 4.24 with the calibration as user defined labels.
+///
+/// The intensities of `points` are expected per unit of `axis`, see
+/// [AxisConfig::jacobian].
+pub fn write_jcamp_dx<W: Write>(
+    writer: &mut W,
+    title: &str,
+    points: &[SpectrumExportPoint],
+    calibration: &SpectrumCalibration,
+    axis: &AxisConfig,
+) -> std::io::Result<()> {
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Spectrum is empty",
+            ))
+        }
+    };
+    let min_y = points.iter().map(|p| p.sum).fold(f32::INFINITY, f32::min);
+    let max_y = points
+        .iter()
+        .map(|p| p.sum)
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    writeln!(writer, "##TITLE={}", title)?;
+    writeln!(writer, "##JCAMP-DX=4.24")?;
+    writeln!(writer, "##DATA TYPE=UV/VISIBLE SPECTRUM")?;
+    writeln!(writer, "##ORIGIN=spectro-cam-rs")?;
+    writeln!(writer, "##OWNER=")?;
+    writeln!(
+        writer,
+        "##LONG DATE={}",
+        Local::now().format("%Y/%m/%d %H:%M:%S")
+    )?;
+    writeln!(writer, "##SPECTROMETER/DATA SYSTEM=spectro-cam-rs")?;
+    writeln!(
+        writer,
+        "##$CALIBRATION LOW={} nm at index {}",
+        calibration.low.wavelength, calibration.low.index
+    )?;
+    writeln!(
+        writer,
+        "##$CALIBRATION HIGH={} nm at index {}",
+        calibration.high.wavelength, calibration.high.index
+    )?;
+    writeln!(writer, "##$LINEARIZE={}", calibration.linearize)?;
+    writeln!(
+        writer,
+        "##$GAIN RGB={}, {}, {}",
+        calibration.gain_r, calibration.gain_g, calibration.gain_b
+    )?;
+    writeln!(
+        writer,
+        "##$INTENSITY CALIBRATED={}",
+        if calibration.scaling.is_some() {
+            "YES"
+        } else {
+            "NO"
+        }
+    )?;
+    let limits: Vec<f32> = points.iter().filter_map(|p| p.detection_limit).collect();
+    if !limits.is_empty() {
+        writeln!(
+            writer,
+            "##$MEAN DETECTION LIMIT={}",
+            limits.iter().sum::<f32>() / limits.len() as f32
+        )?;
+    }
+    // Colorimetry needs the spectrum per nm
+    let spectrum: Vec<_> = points
+        .iter()
+        .map(|p| SpectrumPoint {
+            wavelength: p.wavelength,
+            value: p.sum / axis.jacobian(p.wavelength),
+        })
+        .collect();
+    if let Some(c) = colorimetry(&spectrum) {
+        writeln!(writer, "##$CIE 1931 XY={:.4}, {:.4}", c.x, c.y)?;
+        writeln!(
+            writer,
+            "##$CIE 1976 U'V'={:.4}, {:.4}",
+            c.u_prime, c.v_prime
+        )?;
+        if let (Some(cct), Some(duv)) = (c.cct, c.duv) {
+            writeln!(writer, "##$CCT={:.0} K", cct)?;
+            writeln!(writer, "##$DUV={:.4}", duv)?;
+        }
+    }
+    if axis.unit == AxisUnit::RamanShift {
+        writeln!(writer, "##$LASER WAVELENGTH={} nm", axis.laser_wavelength)?;
+    }
+    writeln!(writer, "##$INTENSITY PER={}", axis.intensity_per())?;
+    writeln!(writer, "##XUNITS={}", jcamp_x_units(axis.unit))?;
+    writeln!(writer, "##YUNITS={}", jcamp_y_units(calibration, axis))?;
+    writeln!(writer, "##XFACTOR=1")?;
+    writeln!(writer, "##YFACTOR={:e}", JCAMP_Y_FACTOR)?;
+    writeln!(writer, "##FIRSTX={}", axis.convert(first.wavelength))?;
+    writeln!(writer, "##LASTX={}", axis.convert(last.wavelength))?;
+    writeln!(writer, "##NPOINTS={}", points.len())?;
+    writeln!(writer, "##FIRSTY={}", first.sum)?;
+    writeln!(writer, "##MINY={}", min_y)?;
+    writeln!(writer, "##MAXY={}", max_y)?;
+    let x: Vec<f32> = points.iter().map(|p| axis.convert(p.wavelength)).collect();
+    // X++ implies equally spaced X values, which a nonlinear calibration or axis unit breaks
+    let separator = if equally_spaced(&x) {
+        writeln!(writer, "##XYDATA=(X++(Y..Y))")?;
+        " "
+    } else {
+        writeln!(writer, "##XYPOINTS=(XY..XY)")?;
+        ", "
+    };
+    for (x, p) in x.iter().zip(points) {
+        writeln!(
+            writer,
+            "{}{}{}",
+            x,
+            separator,
+            (p.sum as f64 / JCAMP_Y_FACTOR).round() as i64
+        )?;
+    }
+    writeln!(writer, "##END=")?;
+    Ok(())
+}
+
+fn equally_spaced(x: &[f32]) -> bool {
+    if x.len() < 3 {
+        return true;
+    }
+    let step = (x[x.len() - 1] - x[0]) / (x.len() - 1) as f32;
+    x.windows(2)
+        .all(|w| (w[1] - w[0] - step).abs() <= JCAMP_SPACING_TOLERANCE * step.abs())
+}
+
+/// Write the sum channel as single Galactic SPC file with explicit wavelengths.
+///
+/// The calibration is stored as `KEY=VALUE` lines in the log block, together with the unit
+/// the intensities of `points` are per.
+pub fn write_spc<W: Write>(
+    writer: &mut W,
+    title: &str,
+    points: &[SpectrumExportPoint],
+    calibration: &SpectrumCalibration,
+    axis: &AxisConfig,
+) -> std::io::Result<()> {
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Spectrum is empty",
+            ))
+        }
+    };
+    let n = points.len();
+    let log_offset = SPC_HEADER_SIZE + 4 * n + SPC_SUBHEADER_SIZE + 4 * n;
+    let now = Local::now();
+    let (x_type, x_factor) = spc_x_type(axis.unit);
+    let x = |p: &SpectrumExportPoint| axis.convert(p.wavelength) * x_factor;
+
+    let mut header = vec![0u8; SPC_HEADER_SIZE];
+    header[0] = SPC_FLAG_XVALUES;
+    header[1] = SPC_VERSION;
+    header[3] = SPC_FLOAT_EXPONENT;
+    header[4..8].copy_from_slice(&(n as u32).to_le_bytes());
+    header[8..16].copy_from_slice(&(x(first) as f64).to_le_bytes());
+    header[16..24].copy_from_slice(&(x(last) as f64).to_le_bytes());
+    header[24..28].copy_from_slice(&1u32.to_le_bytes());
+    header[28] = x_type;
+    header[29] = match calibration.intensity_unit() {
+        IntensityUnit::Counts => 4,
+        IntensityUnit::Relative => 13,
+        IntensityUnit::SpectralIrradiance => 12,
+    };
+    let date = (now.year() as u32) << 20
+        | now.month() << 16
+        | now.day() << 11
+        | now.hour() << 6
+        | now.minute();
+    header[32..36].copy_from_slice(&date.to_le_bytes());
+    copy_str(&mut header[45..54], "webcam");
+    copy_str(&mut header[88..218], title);
+    header[SPC_LOG_OFFSET..SPC_LOG_OFFSET + 4].copy_from_slice(&(log_offset as u32).to_le_bytes());
+    writer.write_all(&header)?;
+
+    for p in points {
+        writer.write_all(&x(p).to_le_bytes())?;
+    }
+
+    let mut subheader = [0u8; SPC_SUBHEADER_SIZE];
+    subheader[1] = SPC_FLOAT_EXPONENT;
+    writer.write_all(&subheader)?;
+    for p in points {
+        writer.write_all(&p.sum.to_le_bytes())?;
+    }
+
+    let mut log = format!(
+        "CALIBRATION LOW={} nm at index {}\r\n\
+         CALIBRATION HIGH={} nm at index {}\r\n\
+         LINEARIZE={}\r\n\
+         GAIN RGB={}, {}, {}\r\n\
+         INTENSITY UNIT={}\r\n\
+         {}{}\r\n\
+         DATE={}\r\n",
+        calibration.low.wavelength,
+        calibration.low.index,
+        calibration.high.wavelength,
+        calibration.high.index,
+        calibration.linearize,
+        calibration.gain_r,
+        calibration.gain_g,
+        calibration.gain_b,
+        calibration.intensity_unit().symbol(),
+        SPC_INTENSITY_PER_LABEL,
+        axis.intensity_per(),
+        now.format("%Y/%m/%d %H:%M:%S")
+    )
+    .into_bytes();
+    if axis.unit == AxisUnit::RamanShift {
+        log.extend(format!("{}{} nm\r\n", SPC_LASER_LABEL, axis.laser_wavelength).bytes());
+    }
+    log.push(0);
+    let mut log_header = [0u8; SPC_LOG_HEADER_SIZE];
+    let log_size = (SPC_LOG_HEADER_SIZE + log.len()) as u32;
+    log_header[0..4].copy_from_slice(&log_size.to_le_bytes());
+    log_header[4..8].copy_from_slice(&log_size.to_le_bytes());
+    log_header[8..12].copy_from_slice(&(SPC_LOG_HEADER_SIZE as u32).to_le_bytes());
+    writer.write_all(&log_header)?;
+    writer.write_all(&log)
+}
+
+/// Write the channels as Spectral Workbench JSON, with the sum channel as `average`.
+pub fn write_spectral_workbench<W: Write>(
+    writer: &mut W,
+    title: &str,
+    points: &[SpectrumExportPoint],
+) -> std::io::Result<()> {
+    let spectrum = SpectralWorkbenchSpectrum {
+        title: Some(title.to_string()),
+        notes: Some(format!("spectro-cam-rs {}", env!("CARGO_PKG_VERSION"))),
+        data: SpectralWorkbenchData {
+            name: Some(title.to_string()),
+            lines: points
+                .iter()
+                .map(|p| SpectralWorkbenchLine {
+                    r: p.r,
+                    g: p.g,
+                    b: p.b,
+                    average: p.sum,
+                    wavelength: Some(p.wavelength),
+                })
+                .collect(),
+        },
+    };
+    serde_json::to_writer(writer, &spectrum).map_err(std::io::Error::from)
+}
+
+/// Write the sum channel as Theremino Spectrometer text file, tab separated in 1 nm steps.
+pub fn write_theremino<W: Write>(
+    writer: &mut W,
+    points: &[SpectrumExportPoint],
+) -> std::io::Result<()> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(first), Some(last)) if last.wavelength > first.wavelength => (first, last),
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Spectrum is empty",
+            ))
+        }
+    };
+    writeln!(writer, "Wavelength (nm)\tIntensity")?;
+    let mut i = 0;
+    for wavelength in first.wavelength.ceil() as i32..=last.wavelength.floor() as i32 {
+        let wavelength = wavelength as f32;
+        while points[i + 1].wavelength < wavelength {
+            i += 1;
+        }
+        let (a, b) = (&points[i], &points[i + 1]);
+        let value = if b.wavelength > a.wavelength {
+            a.sum + (wavelength - a.wavelength) / (b.wavelength - a.wavelength) * (b.sum - a.sum)
+        } else {
+            a.sum
+        };
+        writeln!(writer, "{}\t{}", wavelength, value)?;
+    }
+    Ok(())
+}
+
+/// Copy as much of `s` as fits into the null terminated field `field`.
+fn copy_str(field: &mut [u8], s: &str) {
+    let len = s.len().min(field.len() - 1);
+    field[..len].copy_from_slice(&s.as_bytes()[..len]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jcamp_dx() {
+        let points: Vec<_> = (0..10)
+            .map(|i| SpectrumExportPoint {
+                wavelength: 400. + i as f32,
+                sum: i as f32 / 10.,
+                ..Default::default()
+            })
+            .collect();
+        let mut buffer = Vec::new();
+
+        write_jcamp_dx(
+            &mut buffer,
+            "test",
+            &points,
+            &SpectrumCalibration::default(),
+            &AxisConfig::default(),
+        )
+        .unwrap();
+        let jcamp = String::from_utf8(buffer).unwrap();
+        let lines: Vec<_> = jcamp.lines().collect();
+
+        assert_eq!(lines[0], "##TITLE=test");
+        assert!(lines.contains(&"##NPOINTS=10"));
+        assert!(lines.contains(&"##FIRSTX=400"));
+        assert!(lines.contains(&"##LASTX=409"));
+        assert!(lines.contains(&"##$CALIBRATION LOW=436 nm at index 261"));
+        assert!(lines.contains(&"##XYDATA=(X++(Y..Y))"));
+        assert!(lines.contains(&"409 900000"));
+        assert!(lines.contains(&"##YUNITS=COUNTS"));
+        assert!(lines.contains(&"##$INTENSITY PER=nm"));
+        assert!(lines.iter().any(|l| l.starts_with("##$CIE 1931 XY=")));
+        assert_eq!(lines.last(), Some(&"##END="));
+
+        let mut buffer = Vec::new();
+        write_jcamp_dx(
+            &mut buffer,
+            "test",
+            &points,
+            &SpectrumCalibration::default(),
+            &AxisConfig {
+                unit: AxisUnit::Wavenumber,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let jcamp = String::from_utf8(buffer).unwrap();
+        let lines: Vec<_> = jcamp.lines().collect();
+        assert!(!lines.contains(&"##XYDATA=(X++(Y..Y))"));
+        assert!(lines.contains(&"##XYPOINTS=(XY..XY)"));
+        assert!(lines.iter().any(|l| l.ends_with(", 900000")));
+
+        assert!(write_jcamp_dx(
+            &mut Vec::new(),
+            "test",
+            &[],
+            &SpectrumCalibration::default(),
+            &AxisConfig::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn spc() {
+        let points: Vec<_> = (0..10)
+            .map(|i| SpectrumExportPoint {
+                wavelength: 400. + i as f32,
+                sum: i as f32 / 10.,
+                ..Default::default()
+            })
+            .collect();
+        let mut buffer = Vec::new();
+
+        write_spc(
+            &mut buffer,
+            "test",
+            &points,
+            &SpectrumCalibration::default(),
+            &AxisConfig::default(),
+        )
+        .unwrap();
+
+        let u32_at = |i: usize| u32::from_le_bytes(buffer[i..i + 4].try_into().unwrap());
+        let f32_at = |i: usize| f32::from_le_bytes(buffer[i..i + 4].try_into().unwrap());
+        assert_eq!(buffer[1], SPC_VERSION);
+        assert_eq!(u32_at(4), 10);
+        assert_eq!(f64::from_le_bytes(buffer[16..24].try_into().unwrap()), 409.);
+        assert_eq!(&buffer[88..93], b"test\0");
+        assert_eq!(f32_at(SPC_HEADER_SIZE + 4 * 9), 409.);
+        let y = SPC_HEADER_SIZE + 4 * 10 + SPC_SUBHEADER_SIZE;
+        assert_eq!(f32_at(y + 4 * 9), 0.9);
+
+        let log_offset = u32_at(SPC_LOG_OFFSET) as usize;
+        assert_eq!(log_offset, y + 4 * 10);
+        assert_eq!(u32_at(log_offset) as usize, buffer.len() - log_offset);
+        let log = String::from_utf8_lossy(&buffer[log_offset + SPC_LOG_HEADER_SIZE..]);
+        assert!(log.starts_with("CALIBRATION LOW=436 nm at index 261\r\n"));
+
+        assert!(write_spc(
+            &mut Vec::new(),
+            "test",
+            &[],
+            &SpectrumCalibration::default(),
+            &AxisConfig::default()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,501 @@
+use crate::config::{SpectrumPoint, WavelengthBand};
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::{Mutex, PoisonError};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Function {
+    /// Linearly interpolated intensity at a wavelength.
+    Intensity,
+    /// Trapezoidal integral between two wavelengths.
+    Integral,
+    Mean,
+    Max,
+    Min,
+    /// Wavelength of the maximum between two wavelengths.
+    Peak,
+    Sqrt,
+    Ln,
+    Log10,
+    Abs,
+    Exp,
+}
+
+impl Function {
+    const ALL: [Function; 11] = [
+        Function::Intensity,
+        Function::Integral,
+        Function::Mean,
+        Function::Max,
+        Function::Min,
+        Function::Peak,
+        Function::Sqrt,
+        Function::Ln,
+        Function::Log10,
+        Function::Abs,
+        Function::Exp,
+    ];
+
+    fn arity(&self) -> usize {
+        match self {
+            Function::Integral
+            | Function::Mean
+            | Function::Max
+            | Function::Min
+            | Function::Peak => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Function::Intensity => write!(f, "I"),
+            Function::Integral => write!(f, "integral"),
+            Function::Mean => write!(f, "mean"),
+            Function::Max => write!(f, "max"),
+            Function::Min => write!(f, "min"),
+            Function::Peak => write!(f, "peak"),
+            Function::Sqrt => write!(f, "sqrt"),
+            Function::Ln => write!(f, "ln"),
+            Function::Log10 => write!(f, "log10"),
+            Function::Abs => write!(f, "abs"),
+            Function::Exp => write!(f, "exp"),
+        }
+    }
+}
+
+/// Value of the point an expression is evaluated at, see [Expression::transform].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Variable {
+    Wavelength,
+    Intensity,
+}
+
+impl Display for Variable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Variable::Wavelength => write!(f, "x"),
+            Variable::Intensity => write!(f, "y"),
+        }
+    }
+}
+
+/// Arithmetic expression over a spectrum with a calibrated wavelength axis.
+///
+/// ```
+/// use spectro_cam_rs::config::SpectrumPoint;
+/// use spectro_cam_rs::expression::Expression;
+///
+/// let spectrum: Vec<_> = (400..=700)
+///     .map(|w| SpectrumPoint { wavelength: w as f32, value: 1. })
+///     .collect();
+/// let expression = Expression::parse("I(650) / I(450) + integral(500, 600)").unwrap();
+/// assert_eq!(expression.eval(&spectrum), 101.);
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expression {
+    Number(f32),
+    Neg(Box<Expression>),
+    Binary(Box<Expression>, Operator, Box<Expression>),
+    Call(Function, Vec<Expression>),
+    Variable(Variable),
+}
+
+impl Expression {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: input.chars().peekable(),
+        };
+        let expression = parser.expression()?;
+        match parser.next_token() {
+            None => Ok(expression),
+            Some(c) => Err(format!("Unexpected '{}'", c)),
+        }
+    }
+
+    /// Evaluate over `spectrum`, which has to be sorted by wavelength.
+    ///
+    /// Wavelengths outside of the spectrum and the variables give NaN.
+    pub fn eval(&self, spectrum: &[SpectrumPoint]) -> f32 {
+        self.eval_at(spectrum, None)
+    }
+
+    /// New intensity of every point of `spectrum`, with `x` its wavelength and `y` its
+    /// intensity.
+    ///
+    /// ```
+    /// use spectro_cam_rs::config::SpectrumPoint;
+    /// use spectro_cam_rs::expression::Expression;
+    ///
+    /// let spectrum: Vec<_> = (400..=700)
+    ///     .map(|w| SpectrumPoint { wavelength: w as f32, value: 2. })
+    ///     .collect();
+    /// let expression = Expression::parse("y / max(400, 700) * x").unwrap();
+    /// assert_eq!(expression.transform(&spectrum)[100], 500.);
+    /// ```
+    pub fn transform(&self, spectrum: &[SpectrumPoint]) -> Vec<f32> {
+        // Parts independent of the point are the same for all
+        let bound = self.bind(spectrum);
+        spectrum
+            .iter()
+            .map(|&point| bound.eval_at(spectrum, Some(point)))
+            .collect()
+    }
+
+    fn uses_variables(&self) -> bool {
+        match self {
+            Expression::Number(_) => false,
+            Expression::Neg(e) => e.uses_variables(),
+            Expression::Binary(a, _, b) => a.uses_variables() || b.uses_variables(),
+            Expression::Call(_, args) => args.iter().any(|a| a.uses_variables()),
+            Expression::Variable(_) => true,
+        }
+    }
+
+    /// Expression with every part not using the variables evaluated over `spectrum`.
+    fn bind(&self, spectrum: &[SpectrumPoint]) -> Expression {
+        if !self.uses_variables() {
+            return Expression::Number(self.eval(spectrum));
+        }
+        match self {
+            Expression::Neg(e) => Expression::Neg(Box::new(e.bind(spectrum))),
+            Expression::Binary(a, op, b) => {
+                Expression::Binary(Box::new(a.bind(spectrum)), *op, Box::new(b.bind(spectrum)))
+            }
+            Expression::Call(function, args) => {
+                Expression::Call(*function, args.iter().map(|a| a.bind(spectrum)).collect())
+            }
+            e => e.clone(),
+        }
+    }
+
+    fn eval_at(&self, spectrum: &[SpectrumPoint], point: Option<SpectrumPoint>) -> f32 {
+        match self {
+            Expression::Number(v) => *v,
+            Expression::Variable(variable) => point.map_or(f32::NAN, |p| match variable {
+                Variable::Wavelength => p.wavelength,
+                Variable::Intensity => p.value,
+            }),
+            Expression::Neg(e) => -e.eval_at(spectrum, point),
+            Expression::Binary(a, op, b) => {
+                let (a, b) = (a.eval_at(spectrum, point), b.eval_at(spectrum, point));
+                match op {
+                    Operator::Add => a + b,
+                    Operator::Sub => a - b,
+                    Operator::Mul => a * b,
+                    Operator::Div => a / b,
+                    Operator::Pow => a.powf(b),
+                }
+            }
+            Expression::Call(function, args) => {
+                let args: Vec<f32> = args.iter().map(|a| a.eval_at(spectrum, point)).collect();
+                let band = || WavelengthBand {
+                    start: args[0].min(args[1]),
+                    end: args[0].max(args[1]),
+                };
+                let in_band = || {
+                    let band = band();
+                    spectrum
+                        .iter()
+                        .filter(move |sp| band.contains(sp.wavelength))
+                };
+                match function {
+                    Function::Intensity => intensity_at(spectrum, args[0]),
+                    Function::Integral => in_band()
+                        .collect::<Vec<_>>()
+                        .windows(2)
+                        .map(|p| {
+                            (p[1].wavelength - p[0].wavelength) * (p[0].value + p[1].value) / 2.
+                        })
+                        .sum(),
+                    Function::Mean => band().mean_value(spectrum).unwrap_or(f32::NAN),
+                    Function::Max => in_band()
+                        .map(|sp| sp.value)
+                        .reduce(f32::max)
+                        .unwrap_or(f32::NAN),
+                    Function::Min => in_band()
+                        .map(|sp| sp.value)
+                        .reduce(f32::min)
+                        .unwrap_or(f32::NAN),
+                    Function::Peak => in_band()
+                        .max_by(|a, b| a.value.total_cmp(&b.value))
+                        .map_or(f32::NAN, |sp| sp.wavelength),
+                    Function::Sqrt => args[0].sqrt(),
+                    Function::Ln => args[0].ln(),
+                    Function::Log10 => args[0].log10(),
+                    Function::Abs => args[0].abs(),
+                    Function::Exp => args[0].exp(),
+                }
+            }
+        }
+    }
+}
+
+fn intensity_at(spectrum: &[SpectrumPoint], wavelength: f32) -> f32 {
+    spectrum
+        .windows(2)
+        .find(|p| wavelength >= p[0].wavelength && wavelength <= p[1].wavelength)
+        .map_or(f32::NAN, |p| {
+            let t = (wavelength - p[0].wavelength) / (p[1].wavelength - p[0].wavelength);
+            if t.is_finite() {
+                p[0].value + t * (p[1].value - p[0].value)
+            } else {
+                p[0].value
+            }
+        })
+}
+
+/// An expression text parsed once and kept until the text changes, for expressions which
+/// are edited in the configuration but evaluated every frame.
+#[derive(Debug, Default)]
+pub struct ParsedExpression {
+    parsed: Mutex<Option<(String, Result<Expression, String>)>>,
+}
+
+impl ParsedExpression {
+    /// Apply `f` to the parsed `text`, parsing it only if it differs from the last call.
+    pub fn with<T>(&self, text: &str, f: impl FnOnce(&Expression) -> T) -> Result<T, String> {
+        let mut parsed = self.parsed.lock().unwrap_or_else(PoisonError::into_inner);
+        if !matches!(&*parsed, Some((parsed_text, _)) if parsed_text == text) {
+            *parsed = Some((text.to_string(), Expression::parse(text)));
+        }
+        match &parsed.as_ref().expect("parsed above").1 {
+            Ok(expression) => Ok(f(expression)),
+            Err(e) => Err(e.clone()),
+        }
+    }
+}
+
+/// The copy parses the text again when it is first used.
+impl Clone for ParsedExpression {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+/// Only a cache, so it never makes a difference.
+impl PartialEq for ParsedExpression {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// Recursive descent parser, `^` binds strongest and is right associative.
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn next_token(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.next()
+    }
+
+    fn peek_token(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next_token() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("Expected '{}' but found '{}'", expected, c)),
+            None => Err(format!("Expected '{}'", expected)),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let mut left = self.term()?;
+        while let Some(op) = match self.peek_token() {
+            Some('+') => Some(Operator::Add),
+            Some('-') => Some(Operator::Sub),
+            _ => None,
+        } {
+            self.next_token();
+            left = Expression::Binary(Box::new(left), op, Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> Result<Expression, String> {
+        let mut left = self.unary()?;
+        while let Some(op) = match self.peek_token() {
+            Some('*') => Some(Operator::Mul),
+            Some('/') => Some(Operator::Div),
+            _ => None,
+        } {
+            self.next_token();
+            left = Expression::Binary(Box::new(left), op, Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        if self.peek_token() == Some('-') {
+            self.next_token();
+            return Ok(Expression::Neg(Box::new(self.unary()?)));
+        }
+        let base = self.primary()?;
+        if self.peek_token() == Some('^') {
+            self.next_token();
+            return Ok(Expression::Binary(
+                Box::new(base),
+                Operator::Pow,
+                Box::new(self.unary()?),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        match self.peek_token() {
+            Some('(') => {
+                self.next_token();
+                let expression = self.expression()?;
+                self.expect(')')?;
+                Ok(expression)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+                number
+                    .parse()
+                    .map(Expression::Number)
+                    .map_err(|_| format!("Invalid number '{}'", number))
+            }
+            Some(c) if c.is_alphabetic() => {
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric()) {
+                    name.push(c);
+                }
+                if let Some(variable) = [Variable::Wavelength, Variable::Intensity]
+                    .into_iter()
+                    .find(|v| v.to_string() == name)
+                {
+                    return Ok(Expression::Variable(variable));
+                }
+                let function = Function::ALL
+                    .into_iter()
+                    .find(|f| f.to_string() == name)
+                    .ok_or_else(|| format!("Unknown function '{}'", name))?;
+
+                self.expect('(')?;
+                let mut args = vec![self.expression()?];
+                while self.peek_token() == Some(',') {
+                    self.next_token();
+                    args.push(self.expression()?);
+                }
+                self.expect(')')?;
+                if args.len() != function.arity() {
+                    return Err(format!(
+                        "{} takes {} argument(s) but got {}",
+                        function,
+                        function.arity(),
+                        args.len()
+                    ));
+                }
+                Ok(Expression::Call(function, args))
+            }
+            Some(c) => Err(format!("Unexpected '{}'", c)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn evaluate() {
+        let spectrum: Vec<_> = (400..=700)
+            .map(|w| SpectrumPoint {
+                wavelength: w as f32,
+                value: w as f32 / 100.,
+            })
+            .collect();
+        let eval = |input: &str| Expression::parse(input).unwrap().eval(&spectrum);
+
+        assert_eq!(eval("1 + 2 * 3"), 7.);
+        assert_eq!(eval("(1 + 2) * 3"), 9.);
+        assert_eq!(eval("-2 ^ 2"), -4.);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.);
+        assert_eq!(eval("8 / 4 / 2"), 1.);
+        assert_relative_eq!(eval("I(450.5)"), 4.505);
+        assert_relative_eq!(eval("I(650) / I(450)"), 650. / 450.);
+        assert_relative_eq!(eval("integral(500, 600)"), 550.);
+        assert_relative_eq!(eval("mean(600, 500)"), 5.5);
+        assert_eq!(eval("peak(500, 600)"), 600.);
+        assert_eq!(eval("max(500, 600) - min(500, 600)"), 1.);
+        assert!(eval("I(800)").is_nan());
+
+        assert!(Expression::parse("1 +").is_err());
+        assert!(Expression::parse("I(500, 600)").is_err());
+        assert!(Expression::parse("foo(1)").is_err());
+        assert!(Expression::parse("(1").is_err());
+        assert!(Expression::parse("1 2").is_err());
+        assert!(eval("x + y").is_nan());
+    }
+
+    #[test]
+    fn transform() {
+        let spectrum: Vec<_> = (400..=700)
+            .map(|w| SpectrumPoint {
+                wavelength: w as f32,
+                value: w as f32 / 100.,
+            })
+            .collect();
+        let transform = |input: &str| Expression::parse(input).unwrap().transform(&spectrum);
+
+        let normalized = transform("y / I(550)");
+        assert_relative_eq!(normalized[150], 1.);
+        assert_relative_eq!(normalized[0], 400. / 550.);
+        let shifted = transform("I(x + 10) - y");
+        assert_relative_eq!(shifted[0], 0.1, max_relative = 1e-4);
+        assert!(shifted[300].is_nan());
+        assert_eq!(transform("2")[42], 2.);
+        assert_eq!(
+            Expression::parse("x * mean(500, 600)")
+                .unwrap()
+                .bind(&spectrum),
+            Expression::Binary(
+                Box::new(Expression::Variable(Variable::Wavelength)),
+                Operator::Mul,
+                Box::new(Expression::Number(5.5))
+            )
+        );
+    }
+
+    #[test]
+    fn parsed_expression() {
+        let spectrum: Vec<_> = (400..=600)
+            .map(|w| SpectrumPoint {
+                wavelength: w as f32,
+                value: 2.,
+            })
+            .collect();
+        let parsed = ParsedExpression::default();
+        let eval = |text: &str| parsed.with(text, |e| e.eval(&spectrum));
+
+        assert_eq!(eval("I(500) * 2"), Ok(4.));
+        assert_eq!(eval("I(500) * 2"), Ok(4.));
+        assert_eq!(eval("I(500) + 1"), Ok(3.));
+        assert!(eval("I(500) +").is_err());
+        assert_eq!(eval("I(500)"), Ok(2.));
+    }
+}
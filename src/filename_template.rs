@@ -0,0 +1,103 @@
+use chrono::{DateTime, Local};
+use std::path::{Path, PathBuf};
+
+/// Values of the tokens in a filename template.
+pub struct TemplateValues<'a> {
+    pub sample: &'a str,
+    pub camera: &'a str,
+    pub time: DateTime<Local>,
+}
+
+/// Replace characters which are not allowed in file names on some platform.
+fn sanitize(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// File name for `template` with `{sample}`, `{camera}`, `{date}`, `{time}` and `{seq}` replaced.
+pub fn expand(template: &str, values: &TemplateValues, seq: usize) -> String {
+    template
+        .replace("{sample}", &sanitize(values.sample))
+        .replace("{camera}", &sanitize(values.camera))
+        .replace("{date}", &values.time.format("%Y-%m-%d").to_string())
+        .replace("{time}", &values.time.format("%H-%M-%S").to_string())
+        .replace("{seq}", &format!("{:03}", seq))
+}
+
+/// Path of a file which does not exist yet, in the directory of `path` and named after
+/// `template` with `extension`.
+///
+/// An empty template keeps the file name of `path`. If the name is taken, `{seq}` counts up
+/// from 1, templates without it get a `_2`, `_3`, ... suffix.
+pub fn export_path(
+    path: &str,
+    template: &str,
+    values: &TemplateValues,
+    extension: &str,
+) -> PathBuf {
+    let path = Path::new(path);
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let template = if template.is_empty() {
+        path.file_stem()
+            .map_or("spectrum".to_string(), |s| s.to_string_lossy().to_string())
+    } else {
+        template.to_string()
+    };
+    let numbered = template.contains("{seq}");
+    (1..)
+        .map(|seq| {
+            let mut name = expand(&template, values, seq);
+            if !numbered && seq > 1 {
+                name = format!("{}_{}", name, seq);
+            }
+            directory.join(format!("{}.{}", name, extension))
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn filename_template() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("spectrum.csv");
+        let path = path.to_str().unwrap();
+        let values = TemplateValues {
+            sample: "LED 3/4",
+            camera: "USB: Cam",
+            time: Local.with_ymd_and_hms(2022, 6, 1, 12, 30, 5).unwrap(),
+        };
+
+        assert_eq!(
+            expand("{sample}_{camera}_{date}_{time}_{seq}", &values, 7),
+            "LED 3_4_USB_ Cam_2022-06-01_12-30-05_007"
+        );
+
+        let template = "{sample}_{seq}";
+        let first = export_path(path, template, &values, "csv");
+        assert_eq!(first, directory.path().join("LED 3_4_001.csv"));
+        std::fs::write(&first, "").unwrap();
+        assert_eq!(
+            export_path(path, template, &values, "csv"),
+            directory.path().join("LED 3_4_002.csv")
+        );
+
+        let fixed = export_path(path, "", &values, "spc");
+        assert_eq!(fixed, directory.path().join("spectrum.spc"));
+        std::fs::write(&fixed, "").unwrap();
+        assert_eq!(
+            export_path(path, "", &values, "spc"),
+            directory.path().join("spectrum_2.spc")
+        );
+    }
+}
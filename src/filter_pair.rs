@@ -0,0 +1,211 @@
+use crate::config::{
+    BandRequirement, FilterPairConfig, SpectrumPoint, TransmissionLimit, WavelengthBand,
+};
+use std::fmt::Write;
+
+/// Fraction of the open beam maximum below which the transmission is too noisy to be used.
+const MIN_OPEN_BEAM: f32 = 0.02;
+
+/// Band in which the mean transmissions of both samples are compared.
+pub const MATCH_BAND: WavelengthBand = WavelengthBand {
+    start: 400.,
+    end: 700.,
+};
+
+/// Spectra of the light source and of the two samples in front of it, taken one after another.
+#[derive(Debug, Default)]
+pub struct FilterPair {
+    pub open_beam: Option<Vec<SpectrumPoint>>,
+    pub samples: [Option<Vec<SpectrumPoint>>; 2],
+}
+
+impl FilterPair {
+    /// Transmission of the sample with the index, `None` until it and the open beam were taken.
+    pub fn transmission(&self, sample: usize) -> Option<Vec<SpectrumPoint>> {
+        Some(transmission(
+            self.samples[sample].as_ref()?,
+            self.open_beam.as_ref()?,
+        ))
+    }
+}
+
+/// Ratio of `sample` to `open_beam`, leaving out wavelengths with too little light.
+pub fn transmission(sample: &[SpectrumPoint], open_beam: &[SpectrumPoint]) -> Vec<SpectrumPoint> {
+    let max = open_beam.iter().map(|p| p.value).fold(0., f32::max);
+    sample
+        .iter()
+        .zip(open_beam)
+        .filter(|(_, open)| open.value > MIN_OPEN_BEAM * max)
+        .map(|(sample, open)| SpectrumPoint {
+            wavelength: sample.wavelength,
+            value: (sample.value / open.value).max(0.),
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RequirementCheck {
+    /// Maximum of the band for an upper limit, mean for a lower one.
+    pub value: f32,
+    /// Whether the transmission was measured over the whole band.
+    pub complete: bool,
+    pub passed: bool,
+}
+
+/// Check `transmission` against `requirement`, `None` if the band was not measured. Bands
+/// which are only partly measured do not pass.
+pub fn check_requirement(
+    requirement: &BandRequirement,
+    transmission: &[SpectrumPoint],
+) -> Option<RequirementCheck> {
+    let statistics = requirement.band.statistics(transmission)?;
+    let complete = transmission
+        .first()
+        .zip(transmission.last())
+        .is_some_and(|(first, last)| {
+            first.wavelength <= requirement.band.start && last.wavelength >= requirement.band.end
+        });
+    let (value, within) = match requirement.limit {
+        TransmissionLimit::AtMost(limit) => (statistics.max, statistics.max <= limit),
+        TransmissionLimit::AtLeast(limit) => (statistics.mean, statistics.mean >= limit),
+    };
+    Some(RequirementCheck {
+        value,
+        complete,
+        passed: complete && within,
+    })
+}
+
+/// Result of the comparison of the two samples of a [FilterPair].
+#[derive(Debug, PartialEq, Clone)]
+pub struct FilterPairReport {
+    /// Check of every requirement for both samples.
+    pub checks: Vec<[Option<RequirementCheck>; 2]>,
+    /// Mean transmission of both samples in [MATCH_BAND].
+    pub means: [Option<f32>; 2],
+    pub passed: bool,
+}
+
+impl FilterPairReport {
+    pub fn new(transmissions: [&[SpectrumPoint]; 2], config: &FilterPairConfig) -> Self {
+        let checks: Vec<_> = config
+            .requirements
+            .iter()
+            .map(|requirement| transmissions.map(|t| check_requirement(requirement, t)))
+            .collect();
+        let means = transmissions.map(|t| MATCH_BAND.mean_value(t));
+        let mut report = Self {
+            checks,
+            means,
+            passed: false,
+        };
+        report.passed = report
+            .checks
+            .iter()
+            .flatten()
+            .all(|check| check.is_some_and(|c| c.passed))
+            && report
+                .mismatch()
+                .is_some_and(|mismatch| mismatch <= config.max_mismatch);
+        report
+    }
+
+    /// Difference of the mean transmissions relative to the larger one.
+    pub fn mismatch(&self) -> Option<f32> {
+        let [a, b] = self.means;
+        let (a, b) = (a?, b?);
+        (a.max(b) > 0.).then(|| (a - b).abs() / a.max(b))
+    }
+
+    /// Plain text summary with one line per requirement.
+    pub fn to_text(&self, config: &FilterPairConfig) -> String {
+        let verdict = |passed| if passed { "Pass" } else { "Fail" };
+        let check = |check: &Option<RequirementCheck>| match check {
+            None => "not measured".to_string(),
+            Some(c) if !c.complete => format!("{:.1} % partly measured", c.value * 100.),
+            Some(c) => format!("{:.1} % {}", c.value * 100., verdict(c.passed)),
+        };
+        let mut text = format!(
+            "Filter pair report\nSamples: {}, {}\n",
+            config.names[0], config.names[1]
+        );
+        for (requirement, checks) in config.requirements.iter().zip(&self.checks) {
+            writeln!(
+                text,
+                "{} ({}, {}): {}, {}",
+                requirement.name,
+                requirement.band,
+                requirement.limit,
+                check(&checks[0]),
+                check(&checks[1])
+            )
+            .unwrap();
+        }
+        match self.mismatch() {
+            Some(mismatch) => writeln!(
+                text,
+                "Mismatch ({}, ≤ {:.1} %): {:.1} % {}",
+                MATCH_BAND,
+                config.max_mismatch * 100.,
+                mismatch * 100.,
+                verdict(mismatch <= config.max_mismatch)
+            ),
+            None => writeln!(text, "Mismatch ({}): not measured", MATCH_BAND),
+        }
+        .unwrap();
+        writeln!(text, "Result: {}", verdict(self.passed)).unwrap();
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn filter_pair_report() {
+        let spectrum = |f: &dyn Fn(f32) -> f32| {
+            (0..=80)
+                .map(|i| {
+                    let wavelength = 350. + i as f32 * 5.;
+                    SpectrumPoint {
+                        wavelength,
+                        value: f(wavelength),
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut pair = FilterPair {
+            open_beam: Some(spectrum(&|w| if w < 360. { 0. } else { 2. })),
+            samples: [None, None],
+        };
+        // UV blocking lenses with a slightly different tint
+        pair.samples[0] = Some(spectrum(&|w| if w < 400. { 0. } else { 0.2 }));
+        assert_eq!(pair.transmission(1), None);
+        pair.samples[1] = Some(spectrum(&|w| if w < 400. { 0.01 } else { 0.22 }));
+
+        let left = pair.transmission(0).unwrap();
+        assert_eq!(left[0].wavelength, 360.);
+        assert_relative_eq!(left.last().unwrap().value, 0.1);
+        let right = pair.transmission(1).unwrap();
+
+        let mut config = FilterPairConfig::default();
+        config.requirements[0].band = WavelengthBand {
+            start: 360.,
+            end: 395.,
+        };
+        let report = FilterPairReport::new([&left, &right], &config);
+        assert!(report.passed);
+        assert_relative_eq!(report.mismatch().unwrap(), 1. / 11., max_relative = 1e-4);
+        assert!(report.to_text(&config).ends_with("Result: Pass\n"));
+
+        // The default UV400 band starts below the measured range
+        let report = FilterPairReport::new([&left, &right], &FilterPairConfig::default());
+        assert!(!report.passed);
+        assert!(!report.checks[0][0].unwrap().complete);
+
+        config.max_mismatch = 0.05;
+        assert!(!FilterPairReport::new([&left, &right], &config).passed);
+    }
+}
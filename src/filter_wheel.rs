@@ -0,0 +1,163 @@
+//! Stepping a filter wheel on the serial port of the device control through its positions,
+//! with an averaged spectrum per position, e.g. for stray light and order sorting filter
+//! measurements.
+
+use crate::config::SpectrumPoint;
+use crate::device_control::{SettleStep, Settling};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+/// What the GUI has to do next for the sequence.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum WheelStep {
+    /// Send the command moving the wheel to the position.
+    Move(usize),
+    /// The wheel settled, discard the averaging buffer.
+    ClearBuffer,
+    /// Store the averaged spectrum for the position.
+    Capture(usize),
+    /// Every position is captured.
+    Done,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    Move,
+    Settling(Settling),
+}
+
+/// Moves the wheel through the positions one after another.
+#[derive(Debug)]
+pub struct FilterWheelSequence {
+    positions: usize,
+    settle_time: Duration,
+    position: usize,
+    stage: Stage,
+}
+
+impl FilterWheelSequence {
+    pub fn new(positions: usize, settle_time: Duration) -> Self {
+        Self {
+            positions,
+            settle_time,
+            position: 0,
+            stage: Stage::Move,
+        }
+    }
+
+    /// Index of the current position and the number of positions.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.position, self.positions)
+    }
+
+    /// Advance at `now`, with `buffer_since` the acquisition time of the oldest spectrum of
+    /// the averaging buffer, if it is full.
+    pub fn next_step(&mut self, now: Instant, buffer_since: Option<Instant>) -> Option<WheelStep> {
+        if self.position >= self.positions {
+            return Some(WheelStep::Done);
+        }
+        match &mut self.stage {
+            Stage::Move => {
+                self.stage = Stage::Settling(Settling::new(now, self.settle_time, true));
+                Some(WheelStep::Move(self.position))
+            }
+            Stage::Settling(settling) => match settling.next_step(now, buffer_since)? {
+                SettleStep::ClearBuffer => Some(WheelStep::ClearBuffer),
+                SettleStep::Ready => {
+                    let captured = self.position;
+                    self.position += 1;
+                    self.stage = Stage::Move;
+                    Some(WheelStep::Capture(captured))
+                }
+            },
+        }
+    }
+}
+
+/// Write the spectra of the positions as CSV with a column per label, all spectra taken with
+/// the same calibration.
+pub fn write_filter_set<W: Write>(
+    writer: W,
+    labels: &[String],
+    spectra: &[Vec<SpectrumPoint>],
+    unit: &str,
+) -> Result<(), String> {
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut header = vec!["wavelength".to_string()];
+    header.extend(labels.iter().cloned());
+    header.push("unit".to_string());
+    writer.write_record(&header).map_err(|e| e.to_string())?;
+    let rows = spectra.iter().map(Vec::len).max().unwrap_or(0);
+    for i in 0..rows {
+        let wavelength = spectra
+            .iter()
+            .find_map(|spectrum| spectrum.get(i))
+            .map_or(0., |p| p.wavelength);
+        let mut record = vec![wavelength.to_string()];
+        record.extend(spectra.iter().map(|spectrum| {
+            spectrum
+                .get(i)
+                .map_or(String::new(), |p| p.value.to_string())
+        }));
+        record.push(unit.to_string());
+        writer.write_record(&record).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_wheel() {
+        let start = Instant::now();
+        let settle_time = Duration::from_secs(2);
+        let mut sequence = FilterWheelSequence::new(2, settle_time);
+        let mut steps = vec![];
+        for t in 0..20 {
+            let now = start + Duration::from_secs(t);
+            // The buffer fills within a second after clearing
+            let buffer_since = Some(now - Duration::from_secs(1));
+            match sequence.next_step(now, buffer_since) {
+                Some(WheelStep::Done) => break,
+                Some(step) => steps.push((t, step)),
+                None => {}
+            }
+        }
+        assert_eq!(
+            steps,
+            vec![
+                (0, WheelStep::Move(0)),
+                (2, WheelStep::ClearBuffer),
+                (3, WheelStep::Capture(0)),
+                (4, WheelStep::Move(1)),
+                (6, WheelStep::ClearBuffer),
+                (7, WheelStep::Capture(1)),
+            ]
+        );
+        assert_eq!(sequence.progress(), (2, 2));
+
+        let spectrum = |value: f32| {
+            (0..3)
+                .map(|i| SpectrumPoint {
+                    wavelength: 400. + i as f32,
+                    value,
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut buffer = vec![];
+        write_filter_set(
+            &mut buffer,
+            &["Open".to_string(), "OG550".to_string()],
+            &[spectrum(1.), spectrum(0.5)],
+            "counts",
+        )
+        .unwrap();
+        let csv = String::from_utf8(buffer).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "wavelength,Open,OG550,unit");
+        assert_eq!(lines[2], "401,1,0.5,counts");
+    }
+}
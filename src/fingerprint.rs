@@ -0,0 +1,172 @@
+use crate::config::{IntensityUnit, Snapshot, SpectrumCalibration, SpectrumPoint};
+use std::fmt::{Display, Formatter};
+
+const FNV_OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// Content hash of a measurement, which identifies it independent of its name and stays the
+/// same across runs and platforms, unlike the hashers of the standard library.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Fingerprint(pub u128);
+
+impl Fingerprint {
+    /// First hex digits, enough to tell measurements apart at a glance.
+    pub fn short(&self) -> String {
+        self.to_string()[..12].to_string()
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+/// 128 bit FNV-1a hash.
+struct FingerprintHasher(u128);
+
+impl FingerprintHasher {
+    fn new() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u128;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_f32(&mut self, value: f32) {
+        // Negative zero is the same measurement
+        let value = if value == 0. { 0. } else { value };
+        self.write(&value.to_le_bytes());
+    }
+
+    fn finish(&self) -> Fingerprint {
+        Fingerprint(self.0)
+    }
+}
+
+/// Fingerprint of the wavelengths, values and unit of a spectrum.
+pub fn spectrum_fingerprint(spectrum: &[SpectrumPoint], unit: IntensityUnit) -> Fingerprint {
+    let mut hasher = FingerprintHasher::new();
+    hasher.write(unit.to_string().as_bytes());
+    hasher.write(&(spectrum.len() as u64).to_le_bytes());
+    for point in spectrum {
+        hasher.write_f32(point.wavelength);
+        hasher.write_f32(point.value);
+    }
+    hasher.finish()
+}
+
+impl Snapshot {
+    /// Fingerprint of the spectrum, leaving out name, color and visibility.
+    pub fn fingerprint(&self) -> Fingerprint {
+        spectrum_fingerprint(&self.spectrum, self.unit)
+    }
+}
+
+impl SpectrumCalibration {
+    /// Fingerprint of everything which changes the calibrated spectrum, to tell measurements
+    /// taken with different calibrations apart.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut hasher = FingerprintHasher::new();
+        for point in [self.low, self.high] {
+            hasher.write(&point.wavelength.to_le_bytes());
+            hasher.write(&(point.index as u64).to_le_bytes());
+        }
+        // Serialized like in the configuration, which stays readable across versions
+        hasher.write(&serde_json::to_vec(&self.linearize).expect("serializable"));
+        for value in [self.gain_r, self.gain_g, self.gain_b, self.offset] {
+            hasher.write_f32(value);
+        }
+        hasher.write(self.intensity_unit().to_string().as_bytes());
+        for value in self.scaling.iter().flatten() {
+            hasher.write_f32(*value);
+        }
+        if let Some(qe_curve) = &self.qe_curve {
+            hasher.write(qe_curve.name.as_bytes());
+        }
+        hasher.finish()
+    }
+}
+
+/// Add the snapshot unless one of the same spectrum is stored already, whose name is the
+/// error then.
+pub fn add_snapshot(snapshots: &mut Vec<Snapshot>, snapshot: Snapshot) -> Result<(), String> {
+    let fingerprint = snapshot.fingerprint();
+    match snapshots.iter().find(|s| s.fingerprint() == fingerprint) {
+        Some(existing) => Err(existing.name.clone()),
+        None => {
+            snapshots.push(snapshot);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Linearize;
+    use egui::Color32;
+
+    #[test]
+    fn fingerprint() {
+        let mut hasher = FingerprintHasher::new();
+        hasher.write(b"a");
+        // Published test vector of FNV-1a 128
+        assert_eq!(
+            hasher.finish().to_string(),
+            "d228cb696f1a8caf78912b704e4a8964"
+        );
+
+        let snapshot = Snapshot {
+            name: "Sample".to_string(),
+            color: Color32::RED,
+            visible: true,
+            unit: IntensityUnit::Relative,
+            spectrum: vec![
+                SpectrumPoint {
+                    wavelength: 500.,
+                    value: 0.,
+                },
+                SpectrumPoint {
+                    wavelength: 501.,
+                    value: 0.25,
+                },
+            ],
+        };
+        let fingerprint = snapshot.fingerprint();
+        assert_eq!(fingerprint.short().len(), 12);
+        let mut snapshots = vec![];
+        assert_eq!(add_snapshot(&mut snapshots, snapshot.clone()), Ok(()));
+
+        let mut renamed = snapshot.clone();
+        renamed.name = "Imported".to_string();
+        renamed.spectrum[0].value = -0.;
+        assert_eq!(renamed.fingerprint(), fingerprint);
+        assert_eq!(
+            add_snapshot(&mut snapshots, renamed),
+            Err("Sample".to_string())
+        );
+        assert_eq!(snapshots.len(), 1);
+
+        let mut changed = snapshot.clone();
+        changed.spectrum[1].value = 0.26;
+        assert_ne!(changed.fingerprint(), fingerprint);
+        changed.spectrum[1].value = 0.25;
+        changed.unit = IntensityUnit::SpectralIrradiance;
+        assert_ne!(changed.fingerprint(), fingerprint);
+
+        let mut calibration = SpectrumCalibration::default();
+        let fingerprint = calibration.fingerprint();
+        calibration.modified = Some(1_650_000_000);
+        assert_eq!(calibration.fingerprint(), fingerprint);
+        calibration.offset = 0.5;
+        assert_ne!(calibration.fingerprint(), fingerprint);
+        let fingerprint = calibration.fingerprint();
+        calibration.linearize = Linearize::SRgb;
+        assert_ne!(calibration.fingerprint(), fingerprint);
+    }
+}
@@ -0,0 +1,72 @@
+//! Averaging out the flicker of lamps on mains power, which shows up in the frames as a slow
+//! beat of the flicker with the frame rate.
+
+use crate::spectrum::BufferedFrame;
+
+/// Beat frequency relative to the frame rate below which the flicker counts as standing
+/// still, as every frame catches the same phase.
+const MIN_BEAT: f32 = 0.01;
+
+/// Frequency the flicker at `flicker` Hz appears with in frames taken at `frame_rate`.
+pub fn beat_frequency(flicker: f32, frame_rate: f32) -> f32 {
+    (flicker - (flicker / frame_rate).round() * frame_rate).abs()
+}
+
+/// Number of frames closest to `frames` which spans whole periods of the beat, so that the
+/// flicker averages out, or `frames` if there is no beat.
+pub fn synchronized_frames(frames: usize, frame_rate: f32, flicker: f32) -> usize {
+    if frame_rate <= 0. {
+        return frames;
+    }
+    let beat = beat_frequency(flicker, frame_rate);
+    if beat < MIN_BEAT * frame_rate {
+        return frames;
+    }
+    let period = frame_rate / beat;
+    let periods = (frames as f32 / period).round().max(1.);
+    ((periods * period).round() as usize).max(1)
+}
+
+/// Frame rate from the acquisition times of frames sorted newest first.
+pub fn frame_rate<'a>(frames: impl IntoIterator<Item = &'a BufferedFrame>) -> Option<f32> {
+    let mut frames = frames.into_iter();
+    let newest = frames.next()?;
+    let (count, oldest) = frames.fold((1, newest), |(count, _), frame| (count + 1, frame));
+    let duration = newest
+        .acquired
+        .saturating_duration_since(oldest.acquired)
+        .as_secs_f32();
+    (count > 1 && duration > 0.).then(|| (count - 1) as f32 / duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn flicker() {
+        // 100 Hz flicker of 50 Hz mains at 30 fps beats with 10 Hz, every third frame
+        assert_relative_eq!(beat_frequency(100., 30.), 10., epsilon = 1e-4);
+        assert_eq!(synchronized_frames(10, 30., 100.), 9);
+        assert_eq!(synchronized_frames(1, 30., 100.), 3);
+        // 120 Hz flicker of 60 Hz mains is sampled at the same phase at 30 fps
+        assert_eq!(synchronized_frames(10, 30., 120.), 10);
+        // 5 Hz beat at 25 fps
+        assert_eq!(synchronized_frames(12, 25., 120.), 10);
+
+        let start = Instant::now();
+        let frames: Vec<_> = (0..4)
+            .rev()
+            .map(|i| BufferedFrame {
+                id: i,
+                acquired: start + Duration::from_millis(40 * i),
+                max: 0.5,
+                rejected: false,
+            })
+            .collect();
+        assert_relative_eq!(frame_rate(&frames).unwrap(), 25., max_relative = 1e-4);
+        assert_eq!(frame_rate(&frames[..1]), None);
+    }
+}
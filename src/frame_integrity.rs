@@ -0,0 +1,155 @@
+use crate::config::FrameIntegrityConfig;
+use image::{ImageBuffer, Rgb};
+use std::fmt::{Display, Formatter};
+
+/// Number of consecutive suspect frames after which a frame is accepted as the new normal,
+/// e.g. after the light source was switched on.
+const MAX_CONSECUTIVE_DISCARDS: usize = 3;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FrameDefect {
+    IntensityJump,
+    RowDiscontinuity,
+}
+
+impl Display for FrameDefect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameDefect::IntensityJump => write!(f, "Intensity Jump"),
+            FrameDefect::RowDiscontinuity => write!(f, "Row Discontinuity"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct FrameStatistics {
+    pub accepted: u64,
+    pub intensity_jumps: u64,
+    pub row_discontinuities: u64,
+}
+
+impl FrameStatistics {
+    pub fn discarded(&self) -> u64 {
+        self.intensity_jumps + self.row_discontinuities
+    }
+
+    pub fn record(&mut self, result: Result<(), FrameDefect>) {
+        match result {
+            Ok(()) => self.accepted += 1,
+            Err(FrameDefect::IntensityJump) => self.intensity_jumps += 1,
+            Err(FrameDefect::RowDiscontinuity) => self.row_discontinuities += 1,
+        }
+    }
+}
+
+/// Plausibility check against torn or corrupted frames of cheap USB cameras.
+///
+/// Frames are compared to the last accepted frame: a sudden change of the mean intensity or a
+/// step in the per-row change, where the lower part of the frame is stale or garbage, marks the
+/// frame as suspect.
+#[derive(Debug, Default)]
+pub struct FrameIntegrityCheck {
+    reference: Option<Vec<f32>>,
+    consecutive_discards: usize,
+}
+
+impl FrameIntegrityCheck {
+    pub fn reset(&mut self) {
+        *self = Default::default();
+    }
+
+    pub fn check(
+        &mut self,
+        frame: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+        config: &FrameIntegrityConfig,
+    ) -> Result<(), FrameDefect> {
+        let rows = row_means(frame);
+        let result = match &self.reference {
+            Some(reference) if reference.len() == rows.len() => {
+                Self::compare(reference, &rows, config)
+            }
+            _ => Ok(()),
+        };
+        if result.is_ok() || self.consecutive_discards >= MAX_CONSECUTIVE_DISCARDS {
+            self.reference = Some(rows);
+            self.consecutive_discards = 0;
+            Ok(())
+        } else {
+            self.consecutive_discards += 1;
+            result
+        }
+    }
+
+    fn compare(
+        reference: &[f32],
+        rows: &[f32],
+        config: &FrameIntegrityConfig,
+    ) -> Result<(), FrameDefect> {
+        let mean = |rows: &[f32]| rows.iter().sum::<f32>() / rows.len().max(1) as f32;
+        let (reference_mean, current_mean) = (mean(reference), mean(rows));
+        // One grey level keeps the relative change of dark frames finite
+        if (current_mean - reference_mean).abs() / reference_mean.max(1.)
+            > config.max_intensity_jump
+        {
+            return Err(FrameDefect::IntensityJump);
+        }
+
+        let change: Vec<f32> = rows.iter().zip(reference).map(|(c, r)| c - r).collect();
+        if change
+            .windows(2)
+            .any(|c| (c[1] - c[0]).abs() > config.max_row_step)
+        {
+            return Err(FrameDefect::RowDiscontinuity);
+        }
+        Ok(())
+    }
+}
+
+/// Mean grey level of each row.
+fn row_means(frame: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<f32> {
+    let values_per_row = (frame.width() * 3).max(1) as f32;
+    frame
+        .rows()
+        .map(|r| r.flat_map(|p| p.0).map(|v| v as u32).sum::<u32>() as f32 / values_per_row)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_integrity() {
+        let config = FrameIntegrityConfig {
+            active: true,
+            ..Default::default()
+        };
+        let frame = ImageBuffer::from_fn(8, 8, |_, y| Rgb([y as u8 * 10 + 50; 3]));
+        let mut torn = frame.clone();
+        for y in 6..8 {
+            for x in 0..8 {
+                torn.put_pixel(x, y, Rgb([0, 135, 0]));
+            }
+        }
+        let bright = ImageBuffer::from_fn(8, 8, |_, y| Rgb([y as u8 * 10 + 150; 3]));
+
+        let mut check = FrameIntegrityCheck::default();
+        let mut statistics = FrameStatistics::default();
+        for f in [&frame, &frame, &torn, &frame, &bright] {
+            statistics.record(check.check(f, &config));
+        }
+        assert_eq!(statistics.accepted, 3);
+        assert_eq!(statistics.row_discontinuities, 1);
+        assert_eq!(statistics.intensity_jumps, 1);
+
+        // A lasting change is accepted after a few discarded frames
+        for _ in 1..MAX_CONSECUTIVE_DISCARDS {
+            assert_eq!(
+                check.check(&bright, &config),
+                Err(FrameDefect::IntensityJump)
+            );
+        }
+        assert_eq!(check.check(&bright, &config), Ok(()));
+        assert_eq!(check.check(&bright, &config), Ok(()));
+    }
+}
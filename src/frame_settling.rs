@@ -0,0 +1,78 @@
+//! Counting of camera frames after a change of the exposure or the light, for the
+//! acquisitions which step through several states.
+
+/// Frames to wait after a change until the camera delivers frames with it.
+pub const SETTLE_FRAMES: usize = 3;
+
+/// What the caller has to do after the current camera frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FrameStep {
+    /// The change shows in the following frames, capture them.
+    Capture,
+    /// Enough frames are captured, make the next change.
+    Change,
+}
+
+/// Waits [SETTLE_FRAMES] after each change, then captures a fixed number of frames before the
+/// next one.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameSettling {
+    capture_frames: usize,
+    settling: usize,
+    captured: usize,
+}
+
+impl FrameSettling {
+    /// Settling after the first change, which the caller makes.
+    pub fn new(capture_frames: usize) -> Self {
+        Self {
+            capture_frames: capture_frames.max(1),
+            settling: SETTLE_FRAMES,
+            captured: 0,
+        }
+    }
+
+    /// Whether the current frame may still be from before the last change.
+    pub fn is_settling(&self) -> bool {
+        self.settling > 0
+    }
+
+    /// Advance by one camera frame.
+    pub fn next_frame(&mut self) -> Option<FrameStep> {
+        if self.settling > 0 {
+            self.settling -= 1;
+            return (self.settling == 0).then_some(FrameStep::Capture);
+        }
+        self.captured += 1;
+        if self.captured < self.capture_frames {
+            return None;
+        }
+        self.captured = 0;
+        self.settling = SETTLE_FRAMES;
+        Some(FrameStep::Change)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_settling() {
+        let mut settling = FrameSettling::new(2);
+        let steps: Vec<_> = (0..2 * (SETTLE_FRAMES + 2))
+            .map(|_| {
+                let settled = !settling.is_settling();
+                (settled, settling.next_frame())
+            })
+            .collect();
+        let cycle = [
+            (false, None),
+            (false, None),
+            (false, Some(FrameStep::Capture)),
+            (true, None),
+            (true, Some(FrameStep::Change)),
+        ];
+        assert_eq!(steps, [cycle, cycle].concat());
+    }
+}
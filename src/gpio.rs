@@ -0,0 +1,194 @@
+//! Trigger input and busy output on GPIO lines through the character device of Linux boards
+//! like the Raspberry Pi, to synchronize single-shot acquisitions with flash lamps and pulsed
+//! sources.
+
+use crate::config::TriggerEdge;
+use crate::Waker;
+use flume::Receiver;
+use gpio_cdev::{Chip, EventRequestFlags, Line, LineEventHandle, LineHandle, LineRequestFlags};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Name the lines are requested with, shown by `gpioinfo`.
+const CONSUMER: &str = "spectro-cam-rs";
+
+/// How long the trigger thread waits for an edge before checking whether it is stopped.
+const STOP_INTERVAL: Duration = Duration::from_millis(50);
+
+fn line(chip: &str, offset: u32) -> Result<Line, String> {
+    Chip::new(chip)
+        .and_then(|mut chip| chip.get_line(offset))
+        .map_err(|e| format!("Could not open GPIO {} of {}: {}", offset, chip, e))
+}
+
+/// Output line, low until set.
+#[derive(Debug)]
+pub struct BusyOutput {
+    offset: u32,
+    handle: LineHandle,
+}
+
+impl BusyOutput {
+    pub fn new(chip: &str, offset: u32) -> Result<Self, String> {
+        let handle = line(chip, offset)?
+            .request(LineRequestFlags::OUTPUT, 0, CONSUMER)
+            .map_err(|e| format!("Could not request GPIO {} as output: {}", offset, e))?;
+        Ok(Self { offset, handle })
+    }
+
+    pub fn set(&self, high: bool) -> Result<(), String> {
+        self.handle
+            .set_value(high.into())
+            .map_err(|e| format!("Could not write GPIO {}: {}", self.offset, e))
+    }
+}
+
+/// Input line watched for the edges of a trigger signal on a thread of its own, so that
+/// pulses shorter than a frame of the GUI are not missed and it can stay idle in between.
+#[derive(Debug)]
+pub struct TriggerInput {
+    edges: Receiver<Result<(), String>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TriggerInput {
+    /// Watch line `offset` of `chip` for `edge`, calling `waker` on each.
+    pub fn new(
+        chip: &str,
+        offset: u32,
+        edge: TriggerEdge,
+        waker: Option<Waker>,
+    ) -> Result<Self, String> {
+        let flags = match edge {
+            TriggerEdge::Rising => EventRequestFlags::RISING_EDGE,
+            TriggerEdge::Falling => EventRequestFlags::FALLING_EDGE,
+        };
+        let mut events = line(chip, offset)?
+            .events(LineRequestFlags::INPUT, flags, CONSUMER)
+            .map_err(|e| format!("Could not request the edges of GPIO {}: {}", offset, e))?;
+        Ok(Self::spawn(
+            move |timeout| {
+                wait_for_event(&mut events, timeout)
+                    .map_err(|e| format!("Could not read GPIO {}: {}", offset, e))
+            },
+            waker,
+        ))
+    }
+
+    /// Forward the edges `wait` returns within its timeout until dropped or `wait` fails.
+    fn spawn(
+        mut wait: impl FnMut(Duration) -> Result<bool, String> + Send + 'static,
+        waker: Option<Waker>,
+    ) -> Self {
+        let (edges_tx, edges) = flume::unbounded();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let handle = std::thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                let result = match wait(STOP_INTERVAL) {
+                    Ok(false) => continue,
+                    result => result.map(|_| ()),
+                };
+                let failed = result.is_err();
+                if edges_tx.send(result).is_err() {
+                    break;
+                }
+                if let Some(waker) = &waker {
+                    waker();
+                }
+                if failed {
+                    break;
+                }
+            }
+        });
+        Self {
+            edges,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// Whether an edge came since the last call.
+    pub fn triggered(&self) -> Result<bool, String> {
+        let mut triggered = false;
+        for edge in self.edges.try_iter() {
+            edge?;
+            triggered = true;
+        }
+        Ok(triggered)
+    }
+}
+
+/// Release the line once the thread is done with it, so it can be requested again.
+impl Drop for TriggerInput {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Whether an edge came within `timeout`.
+fn wait_for_event(events: &mut LineEventHandle, timeout: Duration) -> Result<bool, String> {
+    let mut fds = [PollFd::new(&*events, PollFlags::POLLIN)];
+    match poll(&mut fds, timeout.as_millis() as i32) {
+        Ok(0) | Err(Errno::EINTR) => Ok(false),
+        Ok(_) => events.get_event().map(|_| true).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Instant;
+
+    #[test]
+    fn trigger() {
+        // Pulses fed in place of the line, as the character device only exists on the boards
+        let (pulse_tx, pulse_rx) = flume::unbounded::<Result<(), String>>();
+        let wakes = Arc::new(AtomicUsize::new(0));
+        let waker_wakes = wakes.clone();
+        let trigger = TriggerInput::spawn(
+            move |timeout| match pulse_rx.recv_timeout(timeout) {
+                Ok(pulse) => pulse.map(|_| true),
+                Err(_) => Ok(false),
+            },
+            Some(Arc::new(move || {
+                waker_wakes.fetch_add(1, Ordering::Relaxed);
+            })),
+        );
+        let wait_for_wakes = |count: usize| {
+            let started = Instant::now();
+            while wakes.load(Ordering::Relaxed) < count {
+                assert!(started.elapsed() < Duration::from_secs(5));
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        };
+
+        assert_eq!(trigger.triggered(), Ok(false));
+        // Both pulses between two frames count once
+        pulse_tx.send(Ok(())).unwrap();
+        pulse_tx.send(Ok(())).unwrap();
+        wait_for_wakes(2);
+        assert_eq!(trigger.triggered(), Ok(true));
+        assert_eq!(trigger.triggered(), Ok(false));
+
+        pulse_tx.send(Err("Gone".to_string())).unwrap();
+        wait_for_wakes(3);
+        assert_eq!(trigger.triggered(), Err("Gone".to_string()));
+
+        // Stops without edges
+        let trigger = TriggerInput::spawn(|_| Ok(false), None);
+        let started = Instant::now();
+        drop(trigger);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -1,26 +1,130 @@
+use crate::acquisition::{AcquisitionEvent, AcquisitionSource, AcquisitionState};
+use crate::auto_exposure::AutoExposure;
+use crate::band_monitor::BandMonitor;
 use crate::camera::{CameraEvent, CameraInfo};
-use crate::config::{CameraControl, GainPresets, Linearize, SpectrometerConfig, SpectrumPoint};
-use crate::spectrum::{SpectrumContainer, SpectrumRgb};
-use crate::tungsten_halogen::reference_from_filament_temp;
-use crate::{ThreadId, ThreadResult};
+use crate::camera_stream::{compare_channels, CameraStream};
+use crate::color_rendering::{color_rendering, MAX_DUV};
+use crate::colorimetry::{colorimetry, spectral_locus};
+use crate::config::{
+    AxisConfig, AxisUnit, BandRequirement, BaselineMethod, BinningMode, CameraBackend,
+    CameraControl, ChannelComparison, DarkFrame, DarkLibrary, DerivedValue, ExportFormat,
+    FilterPosition, Flicker, GainPresets, ImportExportConfig, IntensityUnit, Linearize,
+    LockInReference, LogFormat, LoggingMode, LowPowerMode, MathOperation, PeakShape,
+    PostprocessingConfig, QeCurve, QePoint, QuantitationStandard, ReferenceBeam, RemoteDecimation,
+    Resampling, SmoothingMethod, Snapshot, SpectralResolution, SpectrometerConfig,
+    SpectrumCalibration, SpectrumPoint, SpectrumWindow, StreamSetup, TestPatternConfig,
+    TransmissionLimit, WavelengthBand, ZeroReference,
+};
+use crate::dark_library::{DarkModel, DARK_FRAMES};
+use crate::database::{MeasurementDatabase, MeasurementMetadata, MeasurementSummary};
+use crate::deconvolution::gaussian_line_shape;
+use crate::device_control::{
+    available_ports, DeviceAction, DeviceSequence, SequenceStep, SerialDevice, SwitchedMeasurement,
+};
+use crate::engine::{SpectroEngine, SUBSCRIBER_QUEUE};
+use crate::filename_template::{export_path, TemplateValues};
+use crate::filter_pair::{FilterPair, FilterPairReport, RequirementCheck, MATCH_BAND};
+use crate::filter_wheel::{write_filter_set, FilterWheelSequence, WheelStep};
+use crate::fingerprint::add_snapshot;
+use crate::flicker::{beat_frequency, frame_rate};
+use crate::frame_integrity::{FrameDefect, FrameStatistics};
+use crate::hdf5::Hdf5Value;
+use crate::hdr::{HdrBracketing, HdrStep};
+use crate::hook::{run_command, run_post_export_hook};
+use crate::hot_pixels::{HotPixelCapture, HOT_PIXEL_FRAMES};
+use crate::import::{read_linearization, read_spectrum_file};
+use crate::lamp_check::{append_to_log, format_times, parse_times, LampCheck};
+use crate::line_identification::identify;
+use crate::linearity::{fit_response, LinearitySweep, ResponseFit, SweepStep};
+use crate::lock_in::{LockInDemodulator, SourceToggling, ToggleStep};
+use crate::network_camera::{split_credentials, Credentials};
+use crate::notifications::{NotificationLevel, Notifications};
+use crate::peak_diff::diff_peaks;
+use crate::peak_fit::{closest_fit, PeakFit};
+use crate::photobiology::{photobiology, PhotonBand};
+use crate::power::on_battery;
+use crate::qe_curves::bundled_qe_curves;
+use crate::quantitation::CalibrationCurve;
+use crate::recording::Replay;
+use crate::reference_library::library_references;
+use crate::report::{write_html, Report};
+use crate::rest_api::{generate_token, RemoteCommand, RestServer};
+use crate::roi::{extract_window, rotation_handle, rotation_towards, row_profile, window_corners};
+use crate::session::Session;
+use crate::spectrum::{decimate, find_peaks_dips, ProcessorEvent, RawSpectra, SpectrumFrame};
+use crate::spectrum_log::{repair_log, SpectrumLogger};
+use crate::spectrum_stream::ConversionJob;
+use crate::test_pattern::{format_wavelengths, max_deviation, parse_wavelengths, test_pattern};
+use crate::tungsten_halogen::{reference_from_filament_temp, GlassEnvelope};
+use crate::websocket::WebSocketServer;
+use crate::widgets::{numpad, touch_style, TouchSlider};
+use crate::window_detection::detect_spectrum_window;
+use crate::{ThreadId, ThreadResult, Waker};
+use chrono::{DateTime, Local, Utc};
 use egui::plot::{Legend, Line, MarkerShape, Plot, Points, Polygon, Text, VLine, Value, Values};
 use egui::{
-    Button, Color32, ComboBox, Context, Rect, RichText, Rounding, Sense, Slider, Stroke, TextureId,
-    Vec2,
+    Align2, Button, Color32, ComboBox, Context, DragValue, FontId, Key, Painter, Pos2, ProgressBar,
+    Rect, RichText, Rounding, Sense, Shape, Stroke, Style, TextEdit, TextureId, Vec2,
 };
-use flume::{Receiver, Sender};
+use flume::{Receiver, Sender, TryRecvError};
 use glium::glutin::dpi::PhysicalSize;
-use nokhwa::{query, Camera};
+use image::{imageops, ImageBuffer, Rgb, RgbaImage};
+use nokhwa::{Camera, CameraFormat};
 use std::any::Any;
-use std::borrow::BorrowMut;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use std::net::{IpAddr, Ipv4Addr};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
+#[cfg(target_os = "linux")]
+use crate::auto_exposure::TARGET_FILL;
+#[cfg(target_os = "linux")]
+use crate::config::HdrConfig;
+#[cfg(target_os = "linux")]
+use crate::config::TriggerEdge;
+#[cfg(target_os = "linux")]
+use crate::gpio::{BusyOutput, TriggerInput};
 #[cfg(target_os = "linux")]
 use v4l::{
     control::{Description, Flags},
     Control,
 };
 
+/// Value of the V4L2 auto exposure menu for manual exposure.
+#[cfg(target_os = "linux")]
+const V4L2_EXPOSURE_MANUAL: i32 = 1;
+/// Color temperature in K of the fixed white balance of the spectroscopy defaults.
+#[cfg(target_os = "linux")]
+const NEUTRAL_WHITE_BALANCE: i32 = 6500;
+
+/// Number of good calibrations kept to revert to.
+const MAX_CALIBRATION_UNDO: usize = 10;
+/// Exposures of a linearity sweep.
+const LINEARITY_STEPS: usize = 16;
+/// Time between two autosaves of the session.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time between two checks for plugged or unplugged cameras.
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Indices of the connected cameras and what could be queried from them.
+type CameraQueryResult = (Vec<usize>, HashMap<usize, CameraInfo>);
+
+const SNAPSHOT_COLORS: [Color32; 6] = [
+    Color32::from_rgb(0, 170, 255),
+    Color32::from_rgb(255, 120, 0),
+    Color32::from_rgb(200, 0, 200),
+    Color32::from_rgb(0, 200, 120),
+    Color32::from_rgb(255, 220, 0),
+    Color32::from_rgb(255, 80, 120),
+];
+
 pub fn wavelength_to_color(wavelength: f64) -> Color32 {
     let gamma = 0.8;
     let intensity_max = 255.0;
@@ -114,76 +218,629 @@ pub fn adjust_color(color: f64, factor: f64, gamma: f64, intensity_max: f64) ->
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NumpadTarget {
+    LowWavelength,
+    HighWavelength,
+}
+
+impl Display for NumpadTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumpadTarget::LowWavelength => write!(f, "Low Wavelength"),
+            NumpadTarget::HighWavelength => write!(f, "High Wavelength"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PeakTableColumn {
+    Center,
+    Fwhm,
+    Amplitude,
+}
+
+impl Display for PeakTableColumn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PeakTableColumn::Center => write!(f, "Center"),
+            PeakTableColumn::Fwhm => write!(f, "FWHM"),
+            PeakTableColumn::Amplitude => write!(f, "Amplitude"),
+        }
+    }
+}
+
+/// Paint `text` in a translucent box with its `anchor` corner at `pos`.
+fn paint_text_box(painter: &Painter, text: String, pos: Pos2, anchor: Align2) {
+    let galley = painter.layout_no_wrap(text, FontId::monospace(12.), Color32::WHITE);
+    let margin = Vec2::splat(6.);
+    let rect = anchor.anchor_rect(Rect::from_min_size(pos, galley.size() + 2. * margin));
+    painter.rect_filled(rect, Rounding::same(3.), Color32::from_black_alpha(180));
+    painter.galley(rect.min + margin, galley);
+}
+
+/// Pixel indices at round steps, giving about eight ticks from `start` to `end`.
+fn pixel_ticks(start: f32, end: f32) -> Vec<usize> {
+    let span = (end - start).max(1.);
+    let magnitude = 10f32.powf((span / 8.).log10().floor());
+    let step = [1., 2., 5., 10.]
+        .map(|factor| factor * magnitude)
+        .into_iter()
+        .find(|step| span / step <= 10.)
+        .unwrap_or(10. * magnitude)
+        .max(1.) as usize;
+    let first = (start / step as f32).ceil() as usize * step;
+    (first..=end as usize).step_by(step).collect()
+}
+
+/// Checkbox to listen on all interfaces instead of only this computer, `hover_text` tells
+/// who may connect then.
+fn remote_access_checkbox(ui: &mut egui::Ui, address: &mut IpAddr, hover_text: &str) {
+    let mut remote = !address.is_loopback();
+    if ui
+        .checkbox(&mut remote, "Remote Access")
+        .on_hover_text(hover_text)
+        .changed()
+    {
+        *address = if remote {
+            Ipv4Addr::UNSPECIFIED.into()
+        } else {
+            Ipv4Addr::LOCALHOST.into()
+        };
+    }
+}
+
+/// Whether the camera control `name` sets `quantity` directly, not its automatic mode.
+fn is_manual_control(name: &str, quantity: &str) -> bool {
+    let name = name.to_lowercase();
+    name.contains(quantity) && !name.contains("auto")
+}
 
+fn notification_color(level: NotificationLevel) -> Color32 {
+    match level {
+        NotificationLevel::Info => Color32::LIGHT_GREEN,
+        NotificationLevel::Warning => Color32::YELLOW,
+        NotificationLevel::Error => Color32::RED,
+    }
+}
 
+/// Spectrum whose peaks are compared in the peak diff window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PeakSource {
+    Live,
+    Snapshot(usize),
+}
 
+impl PeakSource {
+    fn label(&self, snapshots: &[Snapshot]) -> String {
+        match self {
+            PeakSource::Live => "Live".to_string(),
+            PeakSource::Snapshot(i) => snapshots
+                .get(*i)
+                .map_or("-".to_string(), |s| s.name.clone()),
+        }
+    }
+}
 
 pub struct SpectrometerGui {
     config: SpectrometerConfig,
-    running: bool,
+    acquisition: AcquisitionState,
     camera_info: HashMap<usize, CameraInfo>,
+    /// Pending enumeration of the connected cameras.
+    camera_query: Option<Receiver<CameraQueryResult>>,
+    waker: Option<Waker>,
+    /// Cameras connected at the last enumeration, to notice plugged or unplugged ones.
+    connected_cameras: Option<Vec<usize>>,
+    last_device_check: Instant,
+    /// Number and time of the next attempt to open the lost camera again.
+    reconnect: Option<(u32, Instant)>,
+    /// Password of the network camera, only kept while running.
+    network_camera_password: String,
     camera_raw_controls: Vec<Box<dyn Any>>,
     camera_controls: Vec<CameraControl>,
     webcam_texture_id: TextureId,
-    spectrum_container: SpectrumContainer,
+    spectrum_frame: SpectrumFrame,
+    spectrum_frame_rx: Receiver<SpectrumFrame>,
+    processor_tx: Sender<ProcessorEvent>,
+    /// Feeds raw spectra into the processing besides the camera, see [Replay].
+    spectrum_tx: Sender<RawSpectra>,
+    processing_config: Option<(SpectrumCalibration, PostprocessingConfig)>,
     tungsten_filament_temp: u16,
+    /// Apply the emissivity of tungsten instead of a black body.
+    tungsten_emissivity: bool,
+    glass_envelope: GlassEnvelope,
+    /// Name of the reference selected in the library.
+    library_reference: String,
+    /// Wavelength in nm of the isolated line to measure the resolution with.
+    resolution_line: f32,
     camera_config_tx: Sender<CameraEvent>,
     camera_config_change_pending: bool,
-    result_rx: Receiver<ThreadResult>,
+    acquisition_rx: Receiver<AcquisitionEvent>,
+    frame_statistics: Arc<Mutex<FrameStatistics>>,
     last_error: Option<ThreadResult>,
+    /// Last result an error toast was posted for.
+    notified_result: Option<ThreadResult>,
+    notifications: Notifications,
+    band_monitor: BandMonitor,
+    /// Concentration of the next quantitation standard.
+    standard_concentration: f32,
+    on_battery: bool,
+    last_power_check: Instant,
+    touch_style_active: bool,
+    numpad_target: Option<NumpadTarget>,
+    numpad_input: String,
+    logger: Option<SpectrumLogger>,
+    engine: SpectroEngine,
+    websocket_server: Option<WebSocketServer>,
+    rest_server: Option<RestServer>,
+    /// Measurements of the database at the log path, as listed at the last refresh.
+    measurements: Vec<MeasurementSummary>,
+    measurement_filter: String,
+    /// Raw spectra recorded so far, while recording.
+    recording: Option<u64>,
+    replay: Option<Replay>,
+    window_detection_pending: bool,
+    window_proposal: Option<SpectrumWindow>,
+    window_preset_name: String,
+    snapshot_name: String,
+    /// Camera, format and window the buffered spectra were acquired with.
+    last_stream_setup: Option<StreamSetup>,
+    peak_table_sort: (PeakTableColumn, bool),
+    peak_diff_sources: (PeakSource, PeakSource),
+    /// Plot area in points and the scale to physical pixels, for cropping plot images.
+    plot_rect: (Rect, f32),
+    plot_image_requested: bool,
+    auto_exposure: Option<AutoExposure>,
+    /// Exposure bracketing of the HDR mode, if active.
+    hdr: Option<HdrBracketing>,
+    lock_in_active: bool,
+    /// Switching of the source of the lock-in detection with the toggled reference.
+    source_toggling: Option<SourceToggling>,
+    /// Serial port of the lamp and shutter, if connected.
+    device: Option<SerialDevice>,
+    /// Measurement the light is switched for.
+    device_sequence: Option<DeviceSequence>,
+    serial_ports: Vec<String>,
+    /// Filter wheel stepping through its positions, if running.
+    filter_wheel: Option<FilterWheelSequence>,
+    /// Spectra captured at the positions of the filter wheel so far.
+    filter_set: Vec<Vec<SpectrumPoint>>,
+    /// Path of the filter set still being written, see [Self::export_filter_set].
+    filter_set_export: Option<(String, JoinHandle<Result<(), String>>)>,
+    /// Hardware trigger starting single-shot acquisitions, if armed.
+    #[cfg(target_os = "linux")]
+    trigger: Option<TriggerInput>,
+    #[cfg(target_os = "linux")]
+    busy_pin: Option<BusyOutput>,
+    /// Time of the trigger of the running single-shot acquisition.
+    #[cfg(target_os = "linux")]
+    single_shot_since: Option<Instant>,
+    filter_pair: FilterPair,
+    /// Last good calibrations before a change made them implausible.
+    calibration_undo: Vec<SpectrumCalibration>,
+    /// Calibration as of the last frame, to notice changes.
+    tracked_calibration: SpectrumCalibration,
+    conversion: Option<ConversionJob>,
+    hot_pixel_capture: Option<HotPixelCapture>,
+    linearity_sweep: Option<LinearitySweep>,
+    /// Response fitted to the last linearity sweep.
+    linearity_fit: Option<ResponseFit>,
+    /// Mean of each row of the window in the last frame, while the row profile is shown.
+    row_profile: Vec<f32>,
+    /// Size of the still image or the network camera frames, which have no camera format.
+    source_frame_size: Option<Vec2>,
+    /// Plot position where the selection of the region started, in axis units.
+    region_drag_start: Option<f64>,
+    /// Region of the plot selected for statistics.
+    region: Option<WavelengthBand>,
+    /// Spectrum frames skipped since the stream started because the GUI fell behind.
+    dropped_frames: u64,
+    /// The test pattern is shown instead of the camera spectrum.
+    test_pattern: bool,
+    test_pattern_input: String,
+    /// Calibration, window width and lines of the test pattern the worker has.
+    test_pattern_sent: Option<(SpectrumCalibration, usize, TestPatternConfig)>,
+    lamp_check_times_input: String,
+    lamp_check_lines_input: String,
+    /// A scheduled lamp check waits for the lamp to be switched on.
+    lamp_check_pending: bool,
+    /// Lamp checks of this session.
+    lamp_checks: Vec<LampCheck>,
+    /// Start of the session, checks scheduled before are not due without a previous one.
+    session_start: DateTime<Local>,
+    /// Threads of the second camera, spawned when it is started the first time.
+    second_camera: Option<CameraStream>,
+    second_acquisition: AcquisitionState,
+    second_spectrum_frame: SpectrumFrame,
+    /// Calibration and postprocessing the worker of the second camera has.
+    second_processing_sent: Option<(SpectrumCalibration, PostprocessingConfig)>,
+    /// Dark library the worker has the model of and exposure it subtracts the dark for.
+    dark_library_sent: (Option<DarkLibrary>, i32),
+    /// Whether cameras are looked for and the session is saved, see [SpectrometerGui::headless].
+    attached: bool,
+    /// Session of a run which did not exit cleanly, until it is restored or discarded.
+    recovered_session: Option<Session>,
+    last_autosave: Instant,
+    /// Autosave still writing the session.
+    autosave_job: Option<JoinHandle<()>>,
 }
 
 impl SpectrometerGui {
+    /// GUI for the threads of `stream`, which enumerates the cameras and offers to restore the
+    /// session of a run which did not exit cleanly. `waker` repaints once an enumeration
+    /// finished.
     pub fn new(
         webcam_texture_id: TextureId,
-        camera_config_tx: Sender<CameraEvent>,
-        spectrum_rx: Receiver<SpectrumRgb>,
+        stream: CameraStream,
+        config: SpectrometerConfig,
+        engine: SpectroEngine,
+        waker: Waker,
+    ) -> Self {
+        let mut gui = Self::headless(webcam_texture_id, stream, config, engine);
+        gui.attached = true;
+        gui.waker = Some(waker);
+        gui.recovered_session = Session::load_unfinished();
+        gui.query_cameras();
+        if gui.config.websocket.active {
+            gui.set_websocket_server(true);
+        }
+        if gui.config.rest_api.active {
+            gui.set_rest_server(true);
+        }
+        gui
+    }
+
+    /// GUI which neither looks for cameras nor reads or writes session files, so that it can be
+    /// updated with a headless egui context, e.g. in tests.
+    pub fn headless(
+        webcam_texture_id: TextureId,
+        stream: CameraStream,
         config: SpectrometerConfig,
-        result_rx: Receiver<ThreadResult>,
+        engine: SpectroEngine,
     ) -> Self {
+        let tracked_calibration = config.spectrum_calibration.clone();
+        let CameraStream {
+            camera_tx: camera_config_tx,
+            processor_tx,
+            spectrum_tx,
+            spectrum_frame_rx,
+            acquisition_rx,
+            statistics: frame_statistics,
+            ..
+        } = stream;
+        let test_pattern_input = format_wavelengths(&config.test_pattern.wavelengths);
+        let lamp_check_times_input = format_times(&config.lamp_check.times);
+        let lamp_check_lines_input = format_wavelengths(&config.lamp_check.lines);
         let mut gui = Self {
             config,
-            running: false,
+            acquisition: AcquisitionState::Idle,
             camera_info: Default::default(),
+            camera_query: None,
+            waker: None,
+            connected_cameras: None,
+            last_device_check: Instant::now(),
+            reconnect: None,
+            network_camera_password: String::new(),
             camera_raw_controls: Default::default(),
             camera_controls: Default::default(),
             webcam_texture_id,
-            spectrum_container: SpectrumContainer::new(spectrum_rx),
+            spectrum_frame: SpectrumFrame::default(),
+            spectrum_frame_rx,
+            processor_tx,
+            spectrum_tx,
+            processing_config: None,
             tungsten_filament_temp: 2800,
+            tungsten_emissivity: true,
+            glass_envelope: GlassEnvelope::None,
+            library_reference: "CIE D65".to_string(),
+            resolution_line: 546.07,
             camera_config_tx,
             camera_config_change_pending: false,
-            result_rx,
+            acquisition_rx,
+            frame_statistics,
             last_error: None,
+            notified_result: None,
+            notifications: Notifications::default(),
+            band_monitor: BandMonitor::default(),
+            standard_concentration: 1.,
+            on_battery: on_battery(),
+            last_power_check: Instant::now(),
+            touch_style_active: false,
+            numpad_target: None,
+            numpad_input: String::new(),
+            logger: None,
+            engine,
+            websocket_server: None,
+            rest_server: None,
+            measurements: vec![],
+            measurement_filter: String::new(),
+            recording: None,
+            replay: None,
+            window_detection_pending: false,
+            window_proposal: None,
+            window_preset_name: String::new(),
+            snapshot_name: String::new(),
+            last_stream_setup: None,
+            peak_table_sort: (PeakTableColumn::Center, true),
+            peak_diff_sources: (PeakSource::Live, PeakSource::Snapshot(0)),
+            plot_rect: (Rect::NOTHING, 1.),
+            plot_image_requested: false,
+            auto_exposure: None,
+            hdr: None,
+            lock_in_active: false,
+            source_toggling: None,
+            device: None,
+            device_sequence: None,
+            serial_ports: available_ports(),
+            filter_wheel: None,
+            filter_set: vec![],
+            filter_set_export: None,
+            #[cfg(target_os = "linux")]
+            trigger: None,
+            #[cfg(target_os = "linux")]
+            busy_pin: None,
+            #[cfg(target_os = "linux")]
+            single_shot_since: None,
+            filter_pair: FilterPair::default(),
+            calibration_undo: vec![],
+            tracked_calibration,
+            conversion: None,
+            hot_pixel_capture: None,
+            linearity_sweep: None,
+            linearity_fit: None,
+            row_profile: vec![],
+            source_frame_size: None,
+            region_drag_start: None,
+            region: None,
+            dropped_frames: 0,
+            test_pattern: false,
+            test_pattern_input,
+            test_pattern_sent: None,
+            lamp_check_times_input,
+            lamp_check_lines_input,
+            lamp_check_pending: false,
+            lamp_checks: vec![],
+            session_start: Local::now(),
+            dark_library_sent: (None, 0),
+            second_camera: None,
+            second_acquisition: AcquisitionState::Idle,
+            second_spectrum_frame: SpectrumFrame::default(),
+            second_processing_sent: None,
+            attached: false,
+            recovered_session: None,
+            last_autosave: Instant::now(),
+            autosave_job: None,
         };
-        gui.query_cameras();
+        gui.validate_references();
         gui
     }
 
+    /// Start or stop the WebSocket server, deactivating it if it cannot listen on the port.
+    fn set_websocket_server(&mut self, active: bool) {
+        self.websocket_server = None;
+        if active {
+            match WebSocketServer::start(
+                self.config.websocket.address,
+                self.config.websocket.port,
+                &self.engine,
+            ) {
+                Ok(server) => self.websocket_server = Some(server),
+                Err(e) => {
+                    self.config.websocket.active = false;
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result: Err(format!("WebSocket server: {}", e)),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Start or stop the REST API, deactivating it if it cannot listen on the port.
+    fn set_rest_server(&mut self, active: bool) {
+        self.rest_server = None;
+        if active {
+            let rest_api = &mut self.config.rest_api;
+            if rest_api.token.is_empty() {
+                rest_api.token = generate_token();
+            }
+            match RestServer::start(
+                rest_api.address,
+                rest_api.port,
+                rest_api.token.clone(),
+                &self.engine,
+                self.waker.clone(),
+            ) {
+                Ok(server) => self.rest_server = Some(server),
+                Err(e) => {
+                    self.config.rest_api.active = false;
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result: Err(format!("REST API: {}", e)),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Carry out the commands received by the REST API and answer the clients.
+    fn poll_remote_commands(&mut self) {
+        let requests = match &self.rest_server {
+            Some(server) => server.requests(),
+            None => return,
+        };
+        for request in requests {
+            log::info!("Remote command {:?}", request.command);
+            let result = self.run_remote_command(request.command);
+            if let Err(e) = &result {
+                log::warn!("Remote command {:?} failed: {}", request.command, e);
+            }
+            request.reply(result);
+        }
+    }
+
+    fn run_remote_command(&mut self, command: RemoteCommand) -> Result<(), String> {
+        match command {
+            RemoteCommand::SetZeroReference => {
+                if !self.acquisition.is_running() {
+                    return Err("No acquisition is running".to_string());
+                }
+                if self.config.camera_format.is_none() {
+                    return Err("No camera format is chosen".to_string());
+                }
+                if self.device_busy() {
+                    return Err("A switched measurement or the filter wheel is running".to_string());
+                }
+                self.set_zero_reference();
+            }
+            RemoteCommand::Start => {
+                if !self.acquisition.can_start() {
+                    return Err("The acquisition is already running".to_string());
+                }
+                if self.config.camera_format.is_none() {
+                    return Err("No camera format is chosen".to_string());
+                }
+                self.start_camera();
+            }
+            RemoteCommand::Stop => self.stop_acquisition(),
+        }
+        Ok(())
+    }
+
+    /// Start or stop recording the raw spectra into the recording file.
+    fn set_recording(&mut self, active: bool) {
+        if !active {
+            self.recording = None;
+            self.processor_tx
+                .send(ProcessorEvent::Record(None))
+                .unwrap();
+            return;
+        }
+        // The worker only reports failures to the log, catch the common ones here
+        let path = self.config.recording.path.clone();
+        match File::create(&path) {
+            Ok(_) => {
+                self.recording = Some(0);
+                self.processor_tx
+                    .send(ProcessorEvent::Record(Some(path)))
+                    .unwrap();
+            }
+            Err(e) => {
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err(format!("Recording to {}: {}", path, e)),
+                });
+            }
+        }
+    }
+
+    /// Process the raw spectra of the recording file instead of a camera.
+    fn start_replay(&mut self) {
+        if !self.acquisition.can_start() {
+            return;
+        }
+        let recording = &self.config.recording;
+        match Replay::spawn(&recording.path, recording.speed, self.spectrum_tx.clone()) {
+            Ok(replay) => {
+                self.replay = Some(replay);
+                self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+                self.send_config();
+                // There is no camera thread to report the start
+                self.transition(AcquisitionEvent::Start(AcquisitionSource::Replay));
+                self.transition(AcquisitionEvent::Started);
+            }
+            Err(e) => {
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err(format!("Replay of {}: {}", recording.path, e)),
+                });
+            }
+        }
+    }
+
+    /// Follow the progress of the recorder, and stop the replay at the end of the recording.
+    fn poll_recording(&mut self) {
+        match (self.recording, self.spectrum_frame.recorded_frames()) {
+            (Some(_), Some(frames)) => self.recording = Some(frames),
+            // Frames from before the recording started do not report it either
+            (Some(frames), None) if frames > 0 => {
+                self.recording = None;
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err("Recording failed, see the log".to_string()),
+                });
+            }
+            _ => {}
+        }
+        if self.replay.as_ref().is_some_and(Replay::is_finished) {
+            self.notifications.info("Replay finished");
+            self.stop_acquisition();
+        }
+    }
+
+    /// Enumerate the cameras in the background, as opening them blocks.
     fn query_cameras(&mut self) {
-        let default_camera_formats = CameraInfo::get_default_camera_formats();
+        self.spawn_camera_query(None);
+    }
 
-        for i in query()
-            .unwrap_or_default()
-            .iter()
-            .map(nokhwa::CameraInfo::index)
+    /// Enumerate the cameras in the background if other ones are connected than at the last
+    /// enumeration.
+    fn spawn_camera_query(&mut self, known: Option<Vec<usize>>) {
+        let (tx, rx) = flume::bounded(1);
+        let backend = self.config.camera_backend;
+        let waker = self.waker.clone();
+        std::thread::spawn(move || {
+            let connected = CameraInfo::connected(backend);
+            if known.as_ref() != Some(&connected) {
+                tx.send((connected, CameraInfo::query_all(backend))).ok();
+            }
+            drop(tx);
+            if let Some(waker) = waker {
+                waker();
+            }
+        });
+        self.camera_query = Some(rx);
+    }
+
+    /// Look for plugged or unplugged cameras from time to time, while no camera is open.
+    fn check_devices(&mut self) {
+        if self.attached
+            && self.acquisition.can_start()
+            && self.camera_query.is_none()
+            && self.last_device_check.elapsed() > DEVICE_CHECK_INTERVAL
         {
-            for format in &default_camera_formats {
-                if let Ok(cam) = Camera::new(i, Some(*format)).borrow_mut() {
-                    let mut formats = cam.compatible_camera_formats().unwrap_or_default();
-                    formats.sort_by_key(nokhwa::CameraFormat::width);
-                    self.camera_info.insert(
-                        i,
-                        CameraInfo {
-                            info: cam.info().clone(),
-                            formats,
-                        },
-                    );
-                    break;
-                }
+            self.last_device_check = Instant::now();
+            self.spawn_camera_query(self.connected_cameras.clone());
+        }
+    }
+
+    /// Open the lost camera again once the backoff delay passed, continuing the measurement
+    /// with the same format and controls.
+    fn retry_reconnect(&mut self) {
+        if matches!(self.reconnect, Some((_, due)) if Instant::now() >= due)
+            && matches!(self.acquisition, AcquisitionState::Reconnecting(_))
+        {
+            match self.acquisition.source() {
+                Some(AcquisitionSource::Network) => self.start_network(),
+                _ => self.start_stream(),
             }
-            if !self.camera_info.contains_key(&i) {
-                log::warn!("Could not query camera {}", i);
+        }
+    }
+
+    /// Take over the cameras once the enumeration finished.
+    fn poll_camera_query(&mut self) {
+        let result = match &self.camera_query {
+            None => return,
+            Some(rx) => rx.try_recv(),
+        };
+        match result {
+            Ok((connected, camera_info)) => {
+                self.camera_info = camera_info;
+                self.connected_cameras = Some(connected);
+                self.camera_query = None;
             }
+            Err(TryRecvError::Disconnected) => self.camera_query = None,
+            Err(TryRecvError::Empty) => {}
         }
     }
 
@@ -193,10 +850,53 @@ impl SpectrometerGui {
             .unwrap();
     }
 
+    /// Start the stream of the selected camera, with the window clamped to its format.
+    fn start_camera(&mut self) {
+        match self.config.camera_format {
+            Some(camera_format) => {
+                self.config
+                    .image_config
+                    .clamp(camera_format.width() as f32, camera_format.height() as f32);
+                self.start_stream();
+            }
+            None => {
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err("Choose a camera format!".to_string()),
+                });
+            }
+        }
+    }
+
     fn start_stream(&mut self) {
+        let reconnecting = matches!(self.acquisition, AcquisitionState::Reconnecting(_));
+        if !self.transition(AcquisitionEvent::Start(AcquisitionSource::Camera)) {
+            return;
+        }
+        if !reconnecting {
+            self.prepare_stream();
+        }
+        self.send_config();
+        self.camera_config_tx
+            .send(CameraEvent::StartStream {
+                backend: self.config.camera_backend,
+                id: self.config.camera_id,
+                format: self.config.camera_format.unwrap(),
+                pixel_format: self.config.pixel_format,
+            })
+            .unwrap();
+    }
+
+    /// Read the controls of the camera and reset what belongs to another setup, before a
+    /// stream is started by the user.
+    fn prepare_stream(&mut self) {
         let default_camera_formats = CameraInfo::get_default_camera_formats();
         for format in default_camera_formats {
-            if let Ok(cam) = Camera::new(self.config.camera_id, Some(format)) {
+            if let Ok(cam) = Camera::with_backend(
+                self.config.camera_id,
+                Some(format),
+                self.config.camera_backend.api(),
+            ) {
                 let raw_controls = Self::get_raw_controls(&cam);
 
                 self.camera_controls = Self::get_controls_from_raw_controls(&cam, &raw_controls);
@@ -204,14 +904,17 @@ impl SpectrometerGui {
                 break;
             }
         }
-        self.spectrum_container.clear_buffer();
-        self.send_config();
-        self.camera_config_tx
-            .send(CameraEvent::StartStream {
-                id: self.config.camera_id,
-                format: self.config.camera_format.unwrap(),
-            })
-            .unwrap();
+        if self.last_stream_setup != self.config.stream_setup() {
+            self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+            self.processor_tx
+                .send(ProcessorEvent::ClearZeroReference)
+                .unwrap();
+            self.validate_references();
+            self.restore_zero_reference();
+        } else if !self.config.postprocessing_config.retain_buffer_on_restart {
+            self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+        }
+        self.dropped_frames = 0;
     }
 
     #[cfg(target_os = "linux")]
@@ -275,136 +978,827 @@ impl SpectrometerGui {
         Vec::new()
     }
 
-    fn stop_stream(&mut self) {
-        self.camera_config_tx.send(CameraEvent::StopStream).unwrap();
+    /// Analyze the still image at the configured path instead of a camera stream.
+    fn open_still_image(&mut self) {
+        let path = self.config.import_export_config.still_image_path.clone();
+        let (width, height) = match image::image_dimensions(&path) {
+            Ok(dimensions) => dimensions,
+            Err(e) => {
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err(format!("Could not load {}: {}", path, e)),
+                });
+                return;
+            }
+        };
+        if !self.transition(AcquisitionEvent::Start(AcquisitionSource::StillImage)) {
+            return;
+        }
+        self.config.image_config.clamp(width as f32, height as f32);
+        self.source_frame_size = Some(Vec2::new(width as f32, height as f32));
+        self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+        self.processor_tx
+            .send(ProcessorEvent::ClearZeroReference)
+            .unwrap();
+        self.processor_tx
+            .send(ProcessorEvent::SingleShot(true))
+            .unwrap();
+        self.send_config();
+        self.camera_config_tx
+            .send(CameraEvent::StillImage(path))
+            .unwrap();
     }
 
-    fn draw_spectrum(&mut self, ctx: &Context) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            Plot::new("Spectrum")
-                .legend(Legend::default())
-                .show(ui, |plot_ui| {
-                    if self.config.view_config.draw_spectrum_r {
-                        plot_ui.line(self.get_spectrum_line(0).color(Color32::RED).name("r"));
-                    }
-                    if self.config.view_config.draw_spectrum_g {
-                        plot_ui.line(self.get_spectrum_line(1).color(Color32::GREEN).name("g"));
-                    }
-                    if self.config.view_config.draw_spectrum_b {
-                        plot_ui.line(self.get_spectrum_line(2).color(Color32::BLUE).name("b"));
-                    }
-
-                    if self.config.view_config.draw_spectrum_combined {
-                        plot_ui.line(
-                            self.get_spectrum_line(3)
-                                .color(Color32::LIGHT_GRAY)
-                                .name("sum"),
-                        );
-                    }
+    fn start_synthetic(&mut self) {
+        if !self.transition(AcquisitionEvent::Start(AcquisitionSource::Synthetic)) {
+            return;
+        }
+        let synthetic = &self.config.synthetic_camera;
+        self.config
+            .image_config
+            .clamp(synthetic.width as f32, synthetic.height as f32);
+        self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+        self.processor_tx
+            .send(ProcessorEvent::ClearZeroReference)
+            .unwrap();
+        self.dropped_frames = 0;
+        self.send_config();
+        self.camera_config_tx
+            .send(CameraEvent::StartSynthetic(
+                self.config.synthetic_camera.clone(),
+            ))
+            .unwrap();
+    }
 
-                let spectrum_data: Vec<egui::plot::Value> = self.spectrum_container
-                    .get_spectrum_channel(3, &self.config)
-                    .into_iter()
-                    .map(|sp| egui::plot::Value::new(sp.wavelength as f64, sp.value as f64))
-                    .collect();
+    /// Stream the network camera, whose frame size is only known from its first frame.
+    fn start_network(&mut self) {
+        let reconnecting = matches!(self.acquisition, AcquisitionState::Reconnecting(_));
+        if !self.transition(AcquisitionEvent::Start(AcquisitionSource::Network)) {
+            return;
+        }
+        if !reconnecting {
+            self.source_frame_size = None;
+            self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+            self.processor_tx
+                .send(ProcessorEvent::ClearZeroReference)
+                .unwrap();
+            self.dropped_frames = 0;
+        }
+        // Credentials typed into the URL are moved out of it, to not store the password
+        if let (url, Some(credentials)) = split_credentials(&self.config.network_camera_url) {
+            self.config.network_camera_url = url;
+            self.config.network_camera_user = credentials.user;
+            self.network_camera_password = credentials.password;
+        }
+        let credentials = (!self.config.network_camera_user.is_empty()).then(|| Credentials {
+            user: self.config.network_camera_user.clone(),
+            password: self.network_camera_password.clone(),
+        });
+        self.send_config();
+        self.camera_config_tx
+            .send(CameraEvent::StartNetwork(
+                self.config.network_camera_url.clone(),
+                credentials,
+            ))
+            .unwrap();
+    }
 
-                    if !spectrum_data.is_empty() {
-                        // Plot the gray sum line
-                        plot_ui.line(
-                            Line::new(Values::from_values(spectrum_data.clone()))
-                                .color(Color32::LIGHT_GRAY)
-                                .name("sum"),
-                        );
+    /// Value of the `{camera}` token of the filename template.
+    fn camera_name(&self) -> String {
+        match self.acquisition.source() {
+            Some(AcquisitionSource::Synthetic) => "synthetic".to_string(),
+            Some(AcquisitionSource::StillImage) => "image".to_string(),
+            Some(AcquisitionSource::Network) => "network".to_string(),
+            _ => self.camera_info.get(&self.config.camera_id).map_or_else(
+                || self.config.camera_id.to_string(),
+                |ci| ci.info.human_name(),
+            ),
+        }
+    }
 
-                        // Draw vertical colored lines under the sum line
-                        for point in &spectrum_data {
-                            let color = wavelength_to_color(point.x);
+    /// Unused path for an exported file, named after the filename template.
+    fn export_path(config: &ImportExportConfig, camera: &str, extension: &str) -> PathBuf {
+        let values = TemplateValues {
+            sample: &config.sample,
+            camera,
+            time: Local::now(),
+        };
+        export_path(&config.path, &config.filename_template, &values, extension)
+    }
 
-                            // Create a line from (x, 0) to (x, y)
-                            let vertical_line = Line::new(Values::from_values(vec![
-                                Value::new(point.x, 0.0),
-                                Value::new(point.x, point.y),
-                            ]))
-                            .color(color);
+    /// Size of the camera frames, the still image or the synthetic frames.
+    fn frame_size(&self) -> Vec2 {
+        if self.acquisition.source() == Some(AcquisitionSource::Synthetic) {
+            let synthetic = &self.config.synthetic_camera;
+            return Vec2::new(synthetic.width as f32, synthetic.height as f32);
+        }
+        self.source_frame_size.unwrap_or_else(|| {
+            self.config.camera_format.map_or(Vec2::ZERO, |format| {
+                Vec2::new(format.width() as f32, format.height() as f32)
+            })
+        })
+    }
 
-                            plot_ui.line(vertical_line);
-                        }                        
-                    }
-                    
-                    
-                    if self.config.view_config.draw_peaks || self.config.view_config.draw_dips {
-                        let max_spectrum_value = self
-                            .spectrum_container
-                            .get_spectrum_max_value()
-                            .unwrap_or_default();
+    /// Apply `event` to the acquisition state, `false` if it is not allowed in the current
+    /// state.
+    fn transition(&mut self, event: AcquisitionEvent) -> bool {
+        match self.acquisition.transition(event) {
+            Ok(state) => {
+                self.acquisition = state;
+                true
+            }
+            Err(e) => {
+                log::warn!("{}", e);
+                false
+            }
+        }
+    }
 
-                        if self.config.view_config.draw_peaks {
-                            let filtered_peaks = self
-                                .spectrum_container
-                                .spectrum_to_peaks_and_dips(true, &self.config);
+    /// Stop the acquisition of any source.
+    fn stop_acquisition(&mut self) {
+        let source = self.acquisition.source();
+        if !self.transition(AcquisitionEvent::Stop) {
+            return;
+        }
+        if let Some(source) = source {
+            self.release_source(source);
+        }
+        self.camera_config_tx.send(CameraEvent::StopStream).unwrap();
+    }
 
-                            let (peaks, peak_labels) =
-                                Self::peaks_dips_to_plot(&filtered_peaks, true, max_spectrum_value);
+    /// Reset what was set up for `source` when its acquisition ends.
+    fn release_source(&mut self, source: AcquisitionSource) {
+        match source {
+            AcquisitionSource::Camera => {
+                self.auto_exposure = None;
+                if self.hdr.take().is_some() {
+                    self.processor_tx.send(ProcessorEvent::Hdr(None)).unwrap();
+                }
+                self.set_lock_in(false);
+                self.reconnect = None;
+                self.last_stream_setup = self.config.stream_setup();
+            }
+            AcquisitionSource::StillImage => {
+                self.source_frame_size = None;
+                self.processor_tx
+                    .send(ProcessorEvent::SingleShot(false))
+                    .unwrap();
+                self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+            }
+            AcquisitionSource::Synthetic => {}
+            AcquisitionSource::Network => {
+                self.source_frame_size = None;
+                self.reconnect = None;
+            }
+            AcquisitionSource::Replay => self.replay = None,
+        }
+    }
 
-                            plot_ui.points(peaks);
-                            for peak_label in peak_labels {
-                                plot_ui.text(peak_label);
-                            }
-                        }
-                        if self.config.view_config.draw_dips {
-                            let filtered_dips = self
-                                .spectrum_container
-                                .spectrum_to_peaks_and_dips(false, &self.config);
+    /// Apply an event reported by the camera thread and show its outcome.
+    fn handle_acquisition_event(&mut self, event: AcquisitionEvent) {
+        let source = self.acquisition.source();
+        if let AcquisitionEvent::Failed(e) = &event {
+            if self.schedule_reconnect(e) {
+                return;
+            }
+        }
+        let result = match &event {
+            AcquisitionEvent::Started => Some(Ok(())),
+            AcquisitionEvent::Failed(e) => Some(Err(e.clone())),
+            _ => None,
+        };
+        if !self.transition(event) {
+            return;
+        }
+        match (&self.acquisition, source) {
+            // Reports about a source which is being stopped anyway
+            (AcquisitionState::Stopping, _) => return,
+            (AcquisitionState::Error(_), Some(source)) => self.release_source(source),
+            (AcquisitionState::Streaming(source), _) if self.reconnect.is_some() => {
+                self.reconnect = None;
+                if *source == AcquisitionSource::Camera {
+                    // The camera may have come back with its default controls
+                    self.camera_config_tx
+                        .send(CameraEvent::Controls(self.camera_controls.clone()))
+                        .unwrap();
+                }
+                self.notifications.info(format!("{} reconnected", source));
+            }
+            _ => {}
+        }
+        if let Some(result) = result {
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Camera,
+                result,
+            });
+        }
+    }
 
-                            let (dips, dip_labels) =
-                                Self::peaks_dips_to_plot(&filtered_dips, false, max_spectrum_value);
+    /// Retry a lost camera later if the reconnect policy allows another attempt, returning
+    /// whether an attempt was scheduled.
+    ///
+    /// Only a camera which already streamed is retried, failing to open it at the start is
+    /// reported right away.
+    fn schedule_reconnect(&mut self, error: &str) -> bool {
+        let reconnect = self.config.reconnect.clone();
+        let attempt = self.reconnect.map_or(1, |(attempt, _)| attempt + 1);
+        let was_streaming = self.reconnect.is_some()
+            || matches!(
+                self.acquisition,
+                AcquisitionState::Streaming(AcquisitionSource::Camera | AcquisitionSource::Network)
+            );
+        if !reconnect.active
+            || !was_streaming
+            || attempt > reconnect.max_attempts
+            || !self.transition(AcquisitionEvent::Reconnect)
+        {
+            return false;
+        }
+        let delay = reconnect.delay(attempt);
+        log::warn!(
+            "Camera lost: {}, reconnect attempt {} of {} in {:.1} s",
+            error,
+            attempt,
+            reconnect.max_attempts,
+            delay.as_secs_f32()
+        );
+        self.reconnect = Some((attempt, Instant::now() + delay));
+        self.last_error = Some(ThreadResult {
+            id: ThreadId::Camera,
+            result: Err(format!("{}, reconnecting...", error)),
+        });
+        true
+    }
 
-                            plot_ui.points(dips);
+    /// Drop persisted references which do not fit the current setup.
+    fn validate_references(&mut self) {
+        let discarded = self.config.validate_references();
+        if !discarded.is_empty() {
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result: Err(format!(
+                    "Discarded {} taken with another camera, format or window",
+                    discarded.join(" and ")
+                )),
+            });
+        }
+    }
+
+    /// Hand the zero reference of the last session to the worker.
+    fn restore_zero_reference(&self) {
+        if let Some(zero_reference) = &self.config.zero_reference {
+            self.processor_tx
+                .send(ProcessorEvent::LoadZeroReference(Box::new(
+                    zero_reference.to_spectrum(),
+                )))
+                .unwrap();
+        }
+    }
+
+    fn draw_spectrum(&mut self, ctx: &Context) {
+        let deconvolved = self
+            .config
+            .postprocessing_config
+            .effective_line_shape()
+            .is_some();
+        let name = |channel: &str| {
+            if deconvolved {
+                format!("{} (deconvolved)", channel)
+            } else {
+                channel.to_string()
+            }
+        };
+        let unit = self.config.spectrum_calibration.intensity_unit();
+        // Visible traces which do not share the unit of the live spectrum
+        let mut mixed_units: Vec<String> = self
+            .config
+            .snapshots
+            .iter()
+            .filter(|s| s.visible && s.unit != unit)
+            .map(|s| format!("{} ({})", s.name, s.unit.symbol()))
+            .collect();
+        if self.config.reference_config.reference.is_some()
+            && self.config.reference_config.unit != unit
+        {
+            mixed_units.push(format!(
+                "reference ({})",
+                self.config.reference_config.unit.symbol()
+            ));
+        }
+        let waiting_for_data = self.spectrum_frame.spectrum().ncols() == 0;
+        let reference_beam = self.spectrum_frame.reference_beam();
+        let axis = self.config.view_config.axis;
+        let calibration_check = self
+            .config
+            .spectrum_calibration
+            .check(self.config.image_config.window.size.x as usize);
+        let pixel_calibration = self
+            .config
+            .view_config
+            .show_pixel_axis
+            .then(|| self.config.spectrum_calibration.clone());
+        let selecting = ctx.input().modifiers.shift;
+        if ctx.input().key_pressed(Key::Escape) {
+            self.region = None;
+        }
+        let second_spectrum = if self.second_acquisition.is_running() {
+            self.second_spectrum()
+        } else {
+            vec![]
+        };
+        let comparison = self.config.second_camera.comparison;
+        let comparison_trace = if second_spectrum.is_empty() {
+            vec![]
+        } else {
+            compare_channels(
+                &self.spectrum_frame.get_spectrum_channel(3, &self.config),
+                &second_spectrum,
+                comparison,
+            )
+        };
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(format!(
+                    "{} over {}",
+                    axis.intensity_label(unit),
+                    axis.unit.axis_label()
+                ));
+                if waiting_for_data && self.config.last_spectrum.is_some() {
+                    ui.label(
+                        RichText::new("Showing the spectrum of the last session")
+                            .color(Color32::GRAY),
+                    );
+                }
+                if deconvolved {
+                    ui.label(
+                        RichText::new("Showing deconvolved spectrum").color(Color32::LIGHT_YELLOW),
+                    );
+                }
+                if let Some(reference_beam) = reference_beam {
+                    ui.label(
+                        RichText::new(format!("Showing {} to the reference strip", reference_beam))
+                            .color(Color32::LIGHT_YELLOW),
+                    );
+                }
+                if let Err(e) = &calibration_check {
+                    ui.label(
+                        RichText::new(format!("⚠ Implausible calibration: {}", e))
+                            .color(Color32::RED),
+                    );
+                }
+                if !mixed_units.is_empty() {
+                    ui.label(
+                        RichText::new(format!(
+                            "⚠ Different units on one axis: {}",
+                            mixed_units.join(", ")
+                        ))
+                        .color(Color32::YELLOW),
+                    );
+                }
+            });
+            let plot_response = Plot::new("Spectrum")
+                .legend(Legend::default())
+                .allow_drag(!selecting)
+                .label_formatter(move |name, value| {
+                    let pixel = pixel_calibration.as_ref().map_or(String::new(), |c| {
+                        let index = c.get_index_from_wavelength(axis.to_wavelength(value.x as f32));
+                        format!(" (px {:.0})", index)
+                    });
+                    format!(
+                        "{}\n{:.*} {}{}\n{:.4} {}",
+                        name,
+                        axis.unit.precision(),
+                        value.x,
+                        axis.unit.symbol(),
+                        pixel,
+                        value.y,
+                        unit.symbol()
+                    )
+                })
+                .show(ui, |plot_ui| {
+                    for snapshot in self.config.snapshots.iter().filter(|s| s.visible) {
+                        plot_ui.line(snapshot.to_line(&axis));
+                    }
+
+                    if let Some(last_spectrum) = self.config.last_spectrum.as_ref() {
+                        if waiting_for_data {
+                            plot_ui.line(last_spectrum.to_line(&axis));
+                        }
+                    }
+
+                    if self.config.view_config.draw_spectrum_r {
+                        plot_ui.line(
+                            self.get_spectrum_line(0)
+                                .color(Color32::RED)
+                                .name(name("r")),
+                        );
+                    }
+                    if self.config.view_config.draw_spectrum_g {
+                        plot_ui.line(
+                            self.get_spectrum_line(1)
+                                .color(Color32::GREEN)
+                                .name(name("g")),
+                        );
+                    }
+                    if self.config.view_config.draw_spectrum_b {
+                        plot_ui.line(
+                            self.get_spectrum_line(2)
+                                .color(Color32::BLUE)
+                                .name(name("b")),
+                        );
+                    }
+
+                    if self.config.view_config.draw_spectrum_combined {
+                        plot_ui.line(
+                            self.get_spectrum_line(3)
+                                .color(Color32::LIGHT_GRAY)
+                                .name(name("sum")),
+                        );
+                    }
+
+                    for (points, color, trace_name) in [
+                        (
+                            second_spectrum,
+                            Color32::LIGHT_BLUE,
+                            name("sum (second camera)"),
+                        ),
+                        (
+                            comparison_trace,
+                            Color32::YELLOW,
+                            comparison.to_string().to_lowercase(),
+                        ),
+                    ] {
+                        if !points.is_empty() {
+                            plot_ui.line(
+                                Line::new(Values::from_values_iter(
+                                    self.decimate_for_display(points)
+                                        .iter()
+                                        .map(|sp| axis.plot_value(sp.wavelength, sp.value)),
+                                ))
+                                .color(color)
+                                .name(trace_name),
+                            );
+                        }
+                    }
+
+                    let spectrum_data = self.decimate_for_display(
+                        self.spectrum_frame.get_spectrum_channel(3, &self.config),
+                    );
+
+                    if !spectrum_data.is_empty() {
+                        // Plot the gray sum line
+                        plot_ui.line(
+                            Line::new(Values::from_values_iter(
+                                spectrum_data
+                                    .iter()
+                                    .map(|sp| axis.plot_value(sp.wavelength, sp.value)),
+                            ))
+                            .color(Color32::LIGHT_GRAY)
+                            .name(name("sum")),
+                        );
+
+                        // Draw vertical colored lines under the sum line
+                        for point in &spectrum_data {
+                            let color = wavelength_to_color(point.wavelength as f64);
+                            let top = axis.plot_value(point.wavelength, point.value);
+
+                            // Create a line from (x, 0) to (x, y)
+                            let vertical_line =
+                                Line::new(Values::from_values(vec![Value::new(top.x, 0.0), top]))
+                                    .color(color);
+
+                            plot_ui.line(vertical_line);
+                        }
+                    }
+
+                    if self.config.view_config.draw_peaks || self.config.view_config.draw_dips {
+                        let max_spectrum_value = self
+                            .spectrum_frame
+                            .get_spectrum_max_value()
+                            .unwrap_or_default();
+
+                        if self.config.view_config.draw_peaks {
+                            let fit_peaks = self.config.view_config.fit_peaks;
+                            let filtered_peaks = if fit_peaks {
+                                self.spectrum_frame
+                                    .fit_peaks(&self.config)
+                                    .into_iter()
+                                    .map(|fit| SpectrumPoint {
+                                        wavelength: fit.center,
+                                        value: fit.baseline + fit.amplitude,
+                                    })
+                                    .collect()
+                            } else {
+                                self.spectrum_frame
+                                    .spectrum_to_peaks_and_dips(true, &self.config)
+                            };
+
+                            let (peaks, peak_labels) = Self::peaks_dips_to_plot(
+                                &filtered_peaks,
+                                true,
+                                fit_peaks,
+                                max_spectrum_value,
+                                &axis,
+                            );
+
+                            plot_ui.points(peaks);
+                            for peak_label in peak_labels {
+                                plot_ui.text(peak_label);
+                            }
+                        }
+                        if self.config.view_config.draw_dips {
+                            let filtered_dips = self
+                                .spectrum_frame
+                                .spectrum_to_peaks_and_dips(false, &self.config);
+
+                            let (dips, dip_labels) = Self::peaks_dips_to_plot(
+                                &filtered_dips,
+                                false,
+                                false,
+                                max_spectrum_value,
+                                &axis,
+                            );
+
+                            plot_ui.points(dips);
                             for dip_label in dip_labels {
                                 plot_ui.text(dip_label);
                             }
                         }
                     }
 
-                    let line = self.config.reference_config.to_line();
+                    if self.config.view_config.draw_detection_limit {
+                        let limit = self.decimate_for_display(
+                            self.spectrum_frame.get_detection_limit(&self.config),
+                        );
+                        plot_ui.line(
+                            Line::new(Values::from_values_iter(
+                                limit
+                                    .into_iter()
+                                    .map(|sp| axis.plot_value(sp.wavelength, sp.value)),
+                            ))
+                            .color(Color32::from_rgba_unmultiplied(200, 200, 200, 60))
+                            .name("detection limit"),
+                        );
+                    }
+
+                    let line = self.config.reference_config.to_line(&axis);
 
                     if let Some(reference) = line {
                         plot_ui.line(reference.color(Color32::KHAKI).name("reference"));
                     }
 
                     if self.config.view_config.show_calibration_window {
-                        plot_ui.vline(VLine::new(self.config.spectrum_calibration.low.wavelength));
-                        plot_ui.vline(VLine::new(self.config.spectrum_calibration.high.wavelength));
+                        let calibration = &self.config.spectrum_calibration;
+                        plot_ui.vline(VLine::new(axis.convert(calibration.low.wavelength as f32)));
+                        plot_ui.vline(VLine::new(axis.convert(calibration.high.wavelength as f32)));
+                    }
+
+                    // Shift-drag selects a region for statistics
+                    let dragging = plot_ui.ctx().input().pointer.primary_down();
+                    match plot_ui.pointer_coordinate() {
+                        Some(pointer) if selecting && dragging && plot_ui.plot_hovered() => {
+                            let start = *self.region_drag_start.get_or_insert(pointer.x);
+                            let [a, b] = [start, pointer.x].map(|x| axis.to_wavelength(x as f32));
+                            self.region = Some(WavelengthBand {
+                                start: a.min(b),
+                                end: a.max(b),
+                            });
+                        }
+                        _ => self.region_drag_start = None,
+                    }
+                    if self.test_pattern {
+                        for &wavelength in &self.config.test_pattern.wavelengths {
+                            plot_ui
+                                .vline(VLine::new(axis.convert(wavelength)).color(Color32::GOLD));
+                        }
                     }
+
+                    if let Some(region) = self.region {
+                        for wavelength in [region.start, region.end] {
+                            plot_ui.vline(
+                                VLine::new(axis.convert(wavelength)).color(Color32::LIGHT_BLUE),
+                            );
+                        }
+                    }
+
+                    // Screen positions of pixel ticks, egui plots have no secondary axis
+                    if !self.config.view_config.show_pixel_axis {
+                        return vec![];
+                    }
+                    let calibration = &self.config.spectrum_calibration;
+                    let bounds = plot_ui.plot_bounds();
+                    let [start, end] = [bounds.min()[0], bounds.max()[0]].map(|x| {
+                        let index =
+                            calibration.get_index_from_wavelength(axis.to_wavelength(x as f32));
+                        index.clamp(0., self.config.image_config.window.size.x)
+                    });
+                    pixel_ticks(start.min(end), start.max(end))
+                        .into_iter()
+                        .map(|index| {
+                            let x = axis.convert(calibration.get_wavelength_from_index(index));
+                            (plot_ui.screen_from_plot(Value::new(x, 0.)).x, index)
+                        })
+                        .collect()
                 });
+            let plot_rect = plot_response.response.rect;
+            self.plot_rect = (plot_rect, ctx.pixels_per_point());
+
+            let mut overlay_top = plot_rect.top();
+            if self.config.view_config.show_pixel_axis {
+                let painter = ui.painter_at(plot_rect);
+                for (x, index) in plot_response.inner {
+                    let top = Pos2::new(x, plot_rect.top());
+                    painter.line_segment(
+                        [top, top + Vec2::new(0., 6.)],
+                        Stroke::new(1., Color32::GRAY),
+                    );
+                    painter.text(
+                        top + Vec2::new(0., 8.),
+                        Align2::CENTER_TOP,
+                        index.to_string(),
+                        FontId::proportional(11.),
+                        Color32::GRAY,
+                    );
+                }
+                overlay_top += 20.;
+            }
+
+            if self.config.view_config.show_acquisition_overlay {
+                paint_text_box(
+                    &ui.painter_at(plot_rect),
+                    self.acquisition_overlay_text(),
+                    Pos2::new(plot_rect.left(), overlay_top) + Vec2::splat(10.),
+                    Align2::LEFT_TOP,
+                );
+            }
+
+            if let Some(region) = self.region {
+                paint_text_box(
+                    &ui.painter_at(plot_rect),
+                    self.region_statistics_text(region),
+                    Pos2::new(plot_rect.right(), overlay_top) + Vec2::new(-10., 10.),
+                    Align2::RIGHT_TOP,
+                );
+            }
+        });
+    }
+
+    /// Statistics of the sum channel in the selected region.
+    fn region_statistics_text(&self, region: WavelengthBand) -> String {
+        let axis = self.config.view_config.axis;
+        let unit = self.config.spectrum_calibration.intensity_unit().symbol();
+        let precision = axis.unit.precision();
+        let [start, end] = [region.start, region.end].map(|w| axis.convert(w));
+        let mut text = format!(
+            "Region {:.*} to {:.*} {}",
+            precision,
+            start.min(end),
+            precision,
+            start.max(end),
+            axis.unit.symbol()
+        );
+        let spectrum = self.spectrum_frame.get_spectrum_channel(3, &self.config);
+        match region.statistics(&spectrum) {
+            Some(statistics) => {
+                text += &format!(
+                    "\nMin      {:.4} {}\nMax      {:.4} {}\nMean     {:.4} {}\n\
+                    Integral {:.4} {}·nm\nCentroid {:.*} {}",
+                    statistics.min,
+                    unit,
+                    statistics.max,
+                    unit,
+                    statistics.mean,
+                    unit,
+                    statistics.integral,
+                    unit,
+                    precision,
+                    axis.convert(statistics.centroid),
+                    axis.unit.symbol()
+                );
+            }
+            None => text += "\nToo few points",
+        }
+        text + "\nEsc to clear"
+    }
+
+    /// Acquisition parameters shown on top of the plot.
+    fn acquisition_overlay_text(&self) -> String {
+        let control = |quantity: &str| {
+            self.camera_controls
+                .iter()
+                .find(|c| is_manual_control(&c.name, quantity))
+                .map_or("n/a".to_string(), |c| c.value.to_string())
+        };
+        let calibration_age = match self.config.spectrum_calibration.modified {
+            None => "unknown".to_string(),
+            Some(modified) => {
+                let age = Local::now().timestamp() - modified;
+                match age {
+                    a if a < 3600 => format!("{} min", a / 60),
+                    a if a < 86400 => format!("{} h", a / 3600),
+                    a => format!("{} d", a / 86400),
+                }
+            }
+        };
+        format!(
+            "Exposure: {}\nGain: {}\nAveraging: {}\nCalibration Age: {}\nDropped Frames: {}\n{}",
+            control("exposure"),
+            control("gain"),
+            self.config.postprocessing_config.effective_buffer_size(),
+            calibration_age,
+            self.dropped_frames,
+            Local::now().format("%Y-%m-%d %H:%M:%S")
+        )
+    }
+
+    /// Whether a plot image has to be saved from the next rendered frame.
+    pub fn plot_image_requested(&self) -> bool {
+        self.plot_image_requested
+    }
+
+    /// Give up the requested plot image as the window contents could not be read.
+    pub fn plot_image_failed(&mut self, error: String) {
+        self.plot_image_requested = false;
+        self.last_error = Some(ThreadResult {
+            id: ThreadId::Main,
+            result: Err(format!("Could not read the window contents: {}", error)),
+        });
+    }
+
+    /// Crop the plot out of a screenshot of the whole window and save it as PNG next to the
+    /// export path.
+    pub fn save_plot_image(&mut self, screenshot: RgbaImage) {
+        self.plot_image_requested = false;
+        let (rect, pixels_per_point) = self.plot_rect;
+        // OpenGL rows start at the bottom
+        let screenshot = imageops::flip_vertical(&screenshot);
+        let x = (rect.min.x * pixels_per_point).max(0.) as u32;
+        let y = (rect.min.y * pixels_per_point).max(0.) as u32;
+        let width =
+            ((rect.width() * pixels_per_point) as u32).min(screenshot.width().saturating_sub(x));
+        let height =
+            ((rect.height() * pixels_per_point) as u32).min(screenshot.height().saturating_sub(y));
+        let path = Self::export_path(
+            &self.config.import_export_config,
+            &self.camera_name(),
+            "png",
+        );
+        let result = imageops::crop_imm(&screenshot, x, y, width, height)
+            .to_image()
+            .save(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|()| {
+                run_post_export_hook(
+                    &self.config.import_export_config.post_export_command,
+                    &path.to_string_lossy(),
+                )
+            });
+        if result.is_ok() {
+            self.notifications
+                .info(format!("Exported {}", path.display()));
+        }
+        self.last_error = Some(ThreadResult {
+            id: ThreadId::Main,
+            result,
         });
     }
 
     fn get_spectrum_line(&self, index: usize) -> Line {
+        let axis = self.config.view_config.axis;
         Line::new({
             Values::from_values_iter(
-                self.spectrum_container
-                    .get_spectrum_channel(index, &self.config)
-                    .into_iter()
-                    .map(|sp| Value {
-                        x: sp.wavelength as f64,
-                        y: sp.value as f64,
-                    }),
+                self.decimate_for_display(
+                    self.spectrum_frame
+                        .get_spectrum_channel(index, &self.config),
+                )
+                .into_iter()
+                .filter(|sp| !axis.is_masked(sp.wavelength))
+                .map(|sp| axis.plot_value(sp.wavelength, sp.value)),
             )
         })
     }
 
+    fn decimate_for_display<T>(&self, points: Vec<T>) -> Vec<T> {
+        if self.low_power_active() {
+            decimate(points, self.config.view_config.low_power_max_points)
+        } else {
+            points
+        }
+    }
+
     fn peaks_dips_to_plot(
         filtered_peaks_dips: &Vec<SpectrumPoint>,
         peaks: bool,
+        fitted: bool,
         max_spectrum_value: f32,
+        axis: &AxisConfig,
     ) -> (Points, Vec<Text>) {
         let mut peak_dip_labels = Vec::new();
 
         for peak_dip in filtered_peaks_dips {
             peak_dip_labels.push(
                 Text::new(
-                    Value::new(
+                    axis.plot_value(
                         peak_dip.wavelength,
                         if peaks {
                             peak_dip.value + (max_spectrum_value * 0.01)
@@ -412,7 +1806,11 @@ impl SpectrometerGui {
                             peak_dip.value - (max_spectrum_value * 0.01)
                         },
                     ),
-                    format!("{}", peak_dip.wavelength as u32),
+                    if fitted || axis.unit != AxisUnit::Nanometer {
+                        axis.format(peak_dip.wavelength)
+                    } else {
+                        format!("{}", peak_dip.wavelength as u32)
+                    },
                 )
                 .color(if peaks {
                     Color32::LIGHT_RED
@@ -426,7 +1824,7 @@ impl SpectrometerGui {
             Points::new(Values::from_values_iter(
                 filtered_peaks_dips
                     .iter()
-                    .map(|sp| Value::new(sp.wavelength, sp.value)),
+                    .map(|sp| axis.plot_value(sp.wavelength, sp.value)),
             ))
             .name("Peaks")
             .shape(if peaks {
@@ -447,40 +1845,117 @@ impl SpectrometerGui {
     }
 
     fn draw_camera_window(&mut self, ctx: &Context) {
+        let frame_size = self.frame_size();
+        let touch = self.config.view_config.touch_mode;
+        let low_power = self.low_power_active();
+        let mut load_preset = None;
         egui::Window::new("Camera")
             .open(&mut self.config.view_config.show_camera_window)
             .show(ctx, |ui| {
                 ui.add(
-                    Slider::new(&mut self.config.view_config.image_scale, 0.1..=2.)
+                    TouchSlider::new(&mut self.config.view_config.image_scale, 0.1..=2., touch)
                         .text("Preview Scaling Factor"),
                 );
 
                 ui.separator();
 
-                let image_size = egui::Vec2::new(
-                    self.config.camera_format.unwrap().width() as f32,
-                    self.config.camera_format.unwrap().height() as f32,
-                ) * self.config.view_config.image_scale;
-                let image_response = ui.image(self.webcam_texture_id, image_size);
-
-                // Paint window rect
-                ui.with_layer_id(image_response.layer_id, |ui| {
-                    let painter = ui.painter();
-                    let image_rect = image_response.rect;
-                    let image_origin = image_rect.min;
-                    let scale = Vec2::new(
-                        image_rect.width() / self.config.camera_format.unwrap().width() as f32,
-                        image_rect.height() / self.config.camera_format.unwrap().height() as f32,
-                    );
-                    let window_rect = Rect::from_min_size(
-                        image_origin + self.config.image_config.window.offset * scale,
-                        self.config.image_config.window.size * scale,
-                    );
-                    painter.rect_stroke(
-                        window_rect,
-                        Rounding::none(),
-                        Stroke::new(2., Color32::GOLD),
-                    );
+                if low_power {
+                    ui.label("Preview disabled in low power mode");
+                } else {
+                    let image_size = frame_size * self.config.view_config.image_scale;
+                    let image_response = ui.image(self.webcam_texture_id, image_size);
+
+                    // Paint window rect
+                    ui.with_layer_id(image_response.layer_id, |ui| {
+                        let painter = ui.painter();
+                        let image_rect = image_response.rect;
+                        let image_origin = image_rect.min;
+                        let scale = image_rect.size() / frame_size;
+                        let window = &self.config.image_config.window;
+                        let rotation = self.config.image_config.rotation;
+                        let to_screen = |v: Vec2| image_origin + v * scale;
+                        painter.add(Shape::closed_line(
+                            window_corners(window, rotation)
+                                .into_iter()
+                                .map(to_screen)
+                                .collect(),
+                            Stroke::new(2., Color32::GOLD),
+                        ));
+                        if let Some(reference) = self.config.image_config.reference_window() {
+                            painter.add(Shape::closed_line(
+                                window_corners(&reference, rotation)
+                                    .into_iter()
+                                    .map(to_screen)
+                                    .collect(),
+                                Stroke::new(2., Color32::LIGHT_GREEN),
+                            ));
+                        }
+
+                        // Drag the handle to rotate the window
+                        let handle = to_screen(rotation_handle(window, rotation));
+                        let handle_response = ui.interact(
+                            Rect::from_center_size(handle, Vec2::splat(12.)),
+                            ui.id().with("rotation_handle"),
+                            Sense::drag(),
+                        );
+                        painter.circle_filled(handle, 5., Color32::GOLD);
+                        if handle_response.dragged() {
+                            if let Some(pointer) = handle_response.interact_pointer_pos() {
+                                self.config.image_config.rotation =
+                                    rotation_towards(window, (pointer - image_origin) / scale)
+                                        .clamp(-45., 45.);
+                                self.camera_config_change_pending = true;
+                            }
+                        }
+                        if let Some(proposal) = self.window_proposal {
+                            painter.rect_stroke(
+                                Rect::from_min_size(
+                                    image_origin + proposal.offset * scale,
+                                    proposal.size * scale,
+                                ),
+                                Rounding::none(),
+                                Stroke::new(2., Color32::LIGHT_BLUE),
+                            );
+                        }
+                    });
+                }
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            self.acquisition.is_running() && !self.window_detection_pending,
+                            Button::new("Detect Window"),
+                        )
+                        .on_hover_text("Search the brightest horizontal band in the next frame")
+                        .clicked()
+                    {
+                        self.window_detection_pending = true;
+                    }
+                    if let Some(proposal) = self.window_proposal {
+                        ui.label(
+                            RichText::new(format!(
+                                "Proposed: {}x{} at {}, {}",
+                                proposal.size.x,
+                                proposal.size.y,
+                                proposal.offset.x,
+                                proposal.offset.y
+                            ))
+                            .color(Color32::LIGHT_BLUE),
+                        );
+                        if ui.button("Apply").clicked() {
+                            self.config.image_config.window = proposal;
+                            self.window_proposal = None;
+                            self.camera_config_change_pending = false;
+                            // Cannot use self.send_config due to mutable borrow in open
+                            self.camera_config_tx
+                                .send(CameraEvent::Config(self.config.image_config.clone()))
+                                .unwrap();
+                        }
+                        if ui.button("Discard").clicked() {
+                            self.window_proposal = None;
+                        }
+                    }
                 });
                 ui.separator();
 
@@ -490,9 +1965,10 @@ impl SpectrometerGui {
                 ui.columns(2, |cols| {
                     changed |= cols[0]
                         .add(
-                            Slider::new(
+                            TouchSlider::new(
                                 &mut self.config.image_config.window.offset.x,
-                                1.0..=(self.config.camera_format.unwrap().width() as f32 - 1.),
+                                1.0..=(frame_size.x - 1.),
+                                touch,
                             )
                             .step_by(1.)
                             .text("Offset X"),
@@ -500,9 +1976,10 @@ impl SpectrometerGui {
                         .changed();
                     changed |= cols[0]
                         .add(
-                            Slider::new(
+                            TouchSlider::new(
                                 &mut self.config.image_config.window.offset.y,
-                                1.0..=(self.config.camera_format.unwrap().height() as f32 - 1.),
+                                1.0..=(frame_size.y - 1.),
+                                touch,
                             )
                             .step_by(1.)
                             .text("Offset Y"),
@@ -511,11 +1988,12 @@ impl SpectrometerGui {
 
                     changed |= cols[1]
                         .add(
-                            Slider::new(
+                            TouchSlider::new(
                                 &mut self.config.image_config.window.size.x,
-                                1.0..=(self.config.camera_format.unwrap().width() as f32
+                                1.0..=(frame_size.x
                                     - self.config.image_config.window.offset.x
                                     - 1.),
+                                touch,
                             )
                             .step_by(1.)
                             .text("Size X"),
@@ -523,11 +2001,12 @@ impl SpectrometerGui {
                         .changed();
                     changed |= cols[1]
                         .add(
-                            Slider::new(
+                            TouchSlider::new(
                                 &mut self.config.image_config.window.size.y,
-                                1.0..=(self.config.camera_format.unwrap().height() as f32
+                                1.0..=(frame_size.y
                                     - self.config.image_config.window.offset.y
                                     - 1.),
+                                touch,
                             )
                             .step_by(1.)
                             .text("Size Y"),
@@ -535,14 +2014,273 @@ impl SpectrometerGui {
                         .changed();
                 });
                 ui.separator();
+                changed |= ui
+                    .add(
+                        TouchSlider::new(
+                            &mut self.config.image_config.rotation,
+                            -45.0..=45.,
+                            touch,
+                        )
+                        .step_by(0.1)
+                        .text("Rotation [°]"),
+                    )
+                    .on_hover_text("Straighten tilted spectra, or drag the handle in the preview")
+                    .changed();
                 changed |= ui
                     .checkbox(&mut self.config.image_config.flip, "Flip")
                     .changed();
+                let window = self.config.image_config.window;
+                let mut reference_strip = self.config.image_config.reference_strip.is_some();
+                if ui
+                    .checkbox(&mut reference_strip, "Reference Strip")
+                    .on_hover_text(
+                        "Relate the window to a strip of the unfiltered beam in the same frame, \
+                        which compensates drifts of the lamp",
+                    )
+                    .changed()
+                {
+                    // Directly below the window, or above if it does not fit
+                    let below = window.offset.y + window.size.y;
+                    self.config.image_config.reference_strip = reference_strip.then(|| {
+                        if below + window.size.y < frame_size.y {
+                            below
+                        } else {
+                            (window.offset.y - window.size.y).max(0.)
+                        }
+                    });
+                    changed = true;
+                }
+                if let Some(y) = &mut self.config.image_config.reference_strip {
+                    // Keep the strip in the frame when the window grows
+                    *y = y.min((frame_size.y - window.size.y).max(0.));
+                    changed |= ui
+                        .add(
+                            TouchSlider::new(
+                                y,
+                                0.0..=(frame_size.y - window.size.y).max(0.),
+                                touch,
+                            )
+                            .step_by(1.)
+                            .text("Reference Offset Y"),
+                        )
+                        .changed();
+                    let reference_beam = &mut self.config.postprocessing_config.reference_beam;
+                    ComboBox::from_label("Reference Beam")
+                        .selected_text(reference_beam.to_string())
+                        .show_ui(ui, |ui| {
+                            for mode in [ReferenceBeam::Ratio, ReferenceBeam::Absorbance] {
+                                ui.selectable_value(reference_beam, mode, mode.to_string());
+                            }
+                        });
+                }
+                let binning = &mut self.config.image_config.binning;
+                ComboBox::from_label("Binning")
+                    .selected_text(binning.mode.to_string())
+                    .show_ui(ui, |ui| {
+                        for mode in [BinningMode::Sum, BinningMode::Median, BinningMode::Gaussian] {
+                            changed |= ui
+                                .selectable_value(&mut binning.mode, mode, mode.to_string())
+                                .changed();
+                        }
+                    });
+                if binning.mode == BinningMode::Gaussian {
+                    let rows = self.config.image_config.window.size.y;
+                    changed |= ui
+                        .add(
+                            TouchSlider::new(&mut binning.center, 0.0..=(rows - 1.).max(0.), touch)
+                                .step_by(1.)
+                                .text("Center Row"),
+                        )
+                        .changed();
+                    changed |= ui
+                        .add(
+                            TouchSlider::new(&mut binning.sigma, 0.5..=rows.max(1.), touch)
+                                .text("Sigma [rows]"),
+                        )
+                        .changed();
+                }
+                ui.checkbox(&mut self.config.view_config.show_row_profile, "Row Profile")
+                    .on_hover_text(
+                        "Brightness of each row of the window, to see where the spectrum falls",
+                    );
+                if self.config.view_config.show_row_profile {
+                    let profile_max = self.row_profile.iter().copied().fold(0., f32::max);
+                    let weights = binning.weights(self.row_profile.len());
+                    let weight_max = weights.iter().copied().fold(0., f32::max);
+                    Plot::new("row_profile")
+                        .height(100.)
+                        .include_y(0.)
+                        .show(ui, |plot_ui| {
+                            plot_ui.line(
+                                Line::new(Values::from_values_iter(
+                                    self.row_profile
+                                        .iter()
+                                        .enumerate()
+                                        .map(|(row, &v)| Value::new(row as f64, v as f64)),
+                                ))
+                                .name("Row Profile"),
+                            );
+                            if binning.mode == BinningMode::Gaussian && weight_max > 0. {
+                                // Weights scaled to the profile
+                                plot_ui.line(
+                                    Line::new(Values::from_values_iter(
+                                        weights.iter().enumerate().map(|(row, &w)| {
+                                            Value::new(
+                                                row as f64,
+                                                (w / weight_max * profile_max) as f64,
+                                            )
+                                        }),
+                                    ))
+                                    .color(Color32::YELLOW)
+                                    .name("Weights"),
+                                );
+                            }
+                        });
+                }
+                let integrity = &mut self.config.image_config.frame_integrity;
+                changed |= ui
+                    .checkbox(&mut integrity.active, "Frame Integrity Check")
+                    .on_hover_text("Discard torn or corrupted frames")
+                    .changed();
+                if integrity.active {
+                    changed |= ui
+                        .add(
+                            TouchSlider::new(&mut integrity.max_intensity_jump, 0.05..=1., touch)
+                                .text("Max Intensity Jump"),
+                        )
+                        .changed();
+                    changed |= ui
+                        .add(
+                            TouchSlider::new(&mut integrity.max_row_step, 1.0..=100., touch)
+                                .text("Max Row Step"),
+                        )
+                        .changed();
+                    let statistics = *self.frame_statistics.lock().unwrap();
+                    ui.label(format!(
+                        "Discarded {} of {} frames ({}: {}, {}: {})",
+                        statistics.discarded(),
+                        statistics.accepted + statistics.discarded(),
+                        FrameDefect::IntensityJump,
+                        statistics.intensity_jumps,
+                        FrameDefect::RowDiscontinuity,
+                        statistics.row_discontinuities
+                    ));
+                }
 
                 if changed {
                     self.camera_config_change_pending = true;
                 }
 
+                let reconnect = &mut self.config.reconnect;
+                ui.checkbox(&mut reconnect.active, "Reconnect After Errors")
+                    .on_hover_text("Open the camera again after it was lost, e.g. by a USB glitch");
+                if reconnect.active {
+                    ui.add(
+                        TouchSlider::new(&mut reconnect.max_attempts, 1..=50, touch)
+                            .text("Max Attempts"),
+                    );
+                    ui.add(
+                        TouchSlider::new(&mut reconnect.max_delay, 1.0..=600., touch)
+                            .logarithmic(true)
+                            .text("Max Delay [s]"),
+                    );
+                }
+
+                ui.separator();
+                ui.collapsing("Window Presets", |ui| {
+                    let mut remove_preset = None;
+                    for (i, preset) in self.config.image_config.presets.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            let active = preset.window == self.config.image_config.window;
+                            if ui.selectable_label(active, &preset.name).clicked() {
+                                load_preset = Some(preset.name.clone());
+                            }
+                            if ui.button("Delete").clicked() {
+                                remove_preset = Some(i);
+                            }
+                        });
+                    }
+                    if let Some(i) = remove_preset {
+                        self.config.image_config.presets.remove(i);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.window_preset_name);
+                        if ui
+                            .add_enabled(
+                                !self.window_preset_name.is_empty(),
+                                Button::new("Save Current Window"),
+                            )
+                            .clicked()
+                        {
+                            self.config
+                                .image_config
+                                .store_preset(&self.window_preset_name);
+                        }
+                    });
+                });
+
+                ui.collapsing("Hot Pixels", |ui| {
+                    ui.label(match &self.config.image_config.hot_pixels {
+                        Some(map) => format!(
+                            "{} hot pixels corrected in {}x{} frames",
+                            map.pixels.len(),
+                            map.width,
+                            map.height
+                        ),
+                        None => "No hot pixel map".to_string(),
+                    });
+                    ui.add(
+                        TouchSlider::new(
+                            &mut self.config.image_config.hot_pixel_threshold,
+                            0.01..=0.5,
+                            touch,
+                        )
+                        .logarithmic(true)
+                        .text("Threshold"),
+                    )
+                    .on_hover_text("Brightness above the neighbors, relative to full scale");
+                    ui.horizontal(|ui| {
+                        match &self.hot_pixel_capture {
+                            Some(capture) => {
+                                ui.label(format!(
+                                    "Capturing {}/{}",
+                                    capture.frames(),
+                                    HOT_PIXEL_FRAMES
+                                ));
+                            }
+                            None => {
+                                if ui
+                                    .button("Capture Hot Pixels")
+                                    .on_hover_text(format!(
+                                        "Cover the lens, the next {} frames are averaged and \
+                                        the pixels found are added to the map",
+                                        HOT_PIXEL_FRAMES
+                                    ))
+                                    .clicked()
+                                {
+                                    self.hot_pixel_capture = Some(HotPixelCapture::new(
+                                        self.config.image_config.hot_pixel_threshold,
+                                        self.config.image_config.flip,
+                                    ));
+                                }
+                            }
+                        }
+                        if ui
+                            .add_enabled(
+                                self.config.image_config.hot_pixels.is_some(),
+                                Button::new("Clear"),
+                            )
+                            .clicked()
+                        {
+                            self.config.image_config.hot_pixels = None;
+                            self.camera_config_change_pending = false;
+                            self.camera_config_tx
+                                .send(CameraEvent::Config(self.config.image_config.clone()))
+                                .unwrap();
+                        }
+                    });
+                });
+
                 ui.separator();
                 let update_config_button = ui.add(Button::new("Update Config").sense(
                     if self.camera_config_change_pending {
@@ -559,42 +2297,774 @@ impl SpectrometerGui {
                         .unwrap();
                 }
             });
+        if let Some(name) = load_preset {
+            self.load_window_preset(&name);
+        }
     }
 
-    fn draw_calibration_window(&mut self, ctx: &Context) {
+    /// Switch to the window preset with the name, if there is one.
+    fn load_window_preset(&mut self, name: &str) {
+        if !self.config.image_config.load_preset(name) {
+            return;
+        }
+        let frame_size = self.frame_size();
+        self.config.image_config.clamp(frame_size.x, frame_size.y);
+        // The buffered spectra and zero reference belong to the previous window
+        self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+        self.processor_tx
+            .send(ProcessorEvent::ClearZeroReference)
+            .unwrap();
+        self.camera_config_change_pending = false;
+        self.send_config();
+    }
+
+    /// Inspect a full camera frame, e.g. for a pending window detection.
+    pub fn inspect_frame(&mut self, frame: &ImageBuffer<Rgb<u8>, Vec<u8>>) {
+        if self.acquisition.source() == Some(AcquisitionSource::Network) {
+            let size = Vec2::new(frame.width() as f32, frame.height() as f32);
+            if self.source_frame_size != Some(size) {
+                self.source_frame_size = Some(size);
+                self.config.image_config.clamp(size.x, size.y);
+                self.send_config();
+            }
+        }
+        if self.auto_exposure.is_some() {
+            self.adjust_exposure(frame);
+        }
+        if let Some(map) = self
+            .hot_pixel_capture
+            .as_mut()
+            .and_then(|capture| capture.add(frame))
+        {
+            self.hot_pixel_capture = None;
+            self.notifications
+                .info(format!("Found {} hot pixels", map.pixels.len()));
+            match &mut self.config.image_config.hot_pixels {
+                Some(hot_pixels) => hot_pixels.merge(map),
+                hot_pixels => *hot_pixels = Some(map),
+            }
+            self.send_config();
+        }
+        if self.config.view_config.show_camera_window && self.config.view_config.show_row_profile {
+            self.row_profile = row_profile(&extract_window(
+                frame,
+                &self.config.image_config.window,
+                self.config.image_config.rotation,
+            ));
+        }
+        if self.hdr.is_some() {
+            self.step_hdr();
+        }
+        if self.source_toggling.is_some() {
+            self.step_source_toggling();
+        }
+        if self.linearity_sweep.is_some() {
+            self.step_linearity_sweep(frame);
+        }
+        if !self.window_detection_pending {
+            return;
+        }
+        self.window_detection_pending = false;
+        match detect_spectrum_window(frame) {
+            Some(window) => self.window_proposal = Some(window),
+            None => {
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err("No spectrum line found".to_string()),
+                })
+            }
+        }
+    }
+
+    /// Adjust the exposure control to the maximum of the spectrum window in `frame`.
+    fn adjust_exposure(&mut self, frame: &ImageBuffer<Rgb<u8>, Vec<u8>>) {
+        let (index, range) = match self.exposure_control() {
+            None => return,
+            Some(control) => control,
+        };
+        let window = extract_window(
+            frame,
+            &self.config.image_config.window,
+            self.config.image_config.rotation,
+        );
+        let peak = window.pixels().flat_map(|p| p.0).max().unwrap_or(0);
+        let control = &mut self.camera_controls[index];
+        if let Some(exposure) = self
+            .auto_exposure
+            .as_mut()
+            .and_then(|ae| ae.update(peak, control.value, range))
+        {
+            control.value = exposure;
+            self.camera_config_tx
+                .send(CameraEvent::Controls(vec![control.clone()]))
+                .unwrap();
+            self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+        }
+    }
+
+    /// Move the HDR bracketing on by one camera frame.
+    fn step_hdr(&mut self) {
+        let step = match self.hdr.as_mut().and_then(|hdr| hdr.next_frame()) {
+            None => return,
+            Some(step) => step,
+        };
+        match step {
+            HdrStep::Expose(exposure) => {
+                self.processor_tx
+                    .send(ProcessorEvent::HdrBracket(None))
+                    .unwrap();
+                if let Some((index, _)) = self.exposure_control() {
+                    let control = &mut self.camera_controls[index];
+                    control.value = exposure;
+                    self.camera_config_tx
+                        .send(CameraEvent::Controls(vec![control.clone()]))
+                        .unwrap();
+                }
+            }
+            HdrStep::Capture(bracket) => self
+                .processor_tx
+                .send(ProcessorEvent::HdrBracket(Some(bracket)))
+                .unwrap(),
+        }
+    }
+
+    /// Whether the device control switches the light for `measurement`.
+    fn switches_light_for(&self, measurement: SwitchedMeasurement) -> bool {
+        let device_control = &self.config.device_control;
+        self.device.is_some()
+            && match measurement {
+                SwitchedMeasurement::Dark | SwitchedMeasurement::ZeroReference => {
+                    device_control.switch_for_dark
+                }
+                SwitchedMeasurement::LampCheck => device_control.switch_for_lamp_check,
+                SwitchedMeasurement::Log => device_control.switch_for_logging,
+            }
+    }
+
+    /// Whether a switched measurement or the filter wheel is using the device control and the
+    /// averaging buffer, only one of which may run at a time.
+    fn device_busy(&self) -> bool {
+        self.device_sequence.is_some() || self.filter_wheel.is_some()
+    }
+
+    /// Take `measurement`, switching the light for it first if configured, see
+    /// [DeviceSequence].
+    fn switched_measurement(&mut self, measurement: SwitchedMeasurement) {
+        if !self.switches_light_for(measurement) {
+            self.measure(measurement);
+        } else if self.filter_wheel.is_some() {
+            self.notifications.push(
+                NotificationLevel::Warning,
+                format!("{} skipped while the filter wheel is running", measurement),
+            );
+        } else if self.device_sequence.is_none() {
+            self.device_sequence = Some(DeviceSequence::new(
+                measurement,
+                Duration::from_secs_f32(self.config.device_control.settle_time.max(0.)),
+            ));
+        }
+    }
+
+    fn measure(&mut self, measurement: SwitchedMeasurement) {
+        match measurement {
+            SwitchedMeasurement::Dark => self
+                .processor_tx
+                .send(ProcessorEvent::CaptureDark(self.current_exposure()))
+                .unwrap(),
+            SwitchedMeasurement::ZeroReference => self.take_zero_reference(),
+            SwitchedMeasurement::LampCheck => self.run_lamp_check(),
+            SwitchedMeasurement::Log => self.write_log_entry(),
+        }
+    }
+
+    /// Acquisition time of the oldest spectrum of the averaging buffer, if it is full.
+    fn buffer_filled_since(&self) -> Option<Instant> {
+        let frames = self.spectrum_frame.buffered_frames();
+        let buffer_size = self
+            .config
+            .postprocessing_config
+            .synchronized_buffer_size(frame_rate(frames));
+        // Newest first
+        frames
+            .last()
+            .filter(|_| frames.len() >= buffer_size)
+            .map(|frame| frame.acquired)
+    }
+
+    /// Report the failures of the serial port and stop the sequences relying on it, dropping
+    /// the device once its port could not be opened.
+    fn poll_device(&mut self) {
+        let device = match &self.device {
+            None => return,
+            Some(device) => device,
+        };
+        let connected = device.is_connected();
+        if let Some(e) = device.errors().pop() {
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result: Err(e),
+            });
+        } else if connected {
+            return;
+        }
+        self.device_sequence = None;
+        self.filter_wheel = None;
+        self.filter_set.clear();
+        if !connected {
+            self.device = None;
+        }
+    }
+
+    /// Move the measurement the light is switched for on.
+    fn step_device_sequence(&mut self) {
+        let buffer_since = self.buffer_filled_since();
+        let sequence = match &mut self.device_sequence {
+            None => return,
+            Some(sequence) => sequence,
+        };
+        let measurement = sequence.measurement();
+        match sequence.next_step(Instant::now(), buffer_since) {
+            Some(SequenceStep::Switch(on)) => {
+                let result = match &self.device {
+                    Some(device) => device.switch_light(on, &self.config.device_control),
+                    None => Err("The device control is disconnected".to_string()),
+                };
+                if let Err(e) = result {
+                    self.device_sequence = None;
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result: Err(e),
+                    });
+                }
+            }
+            Some(SequenceStep::ClearBuffer) => {
+                self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap()
+            }
+            Some(SequenceStep::Measure) => {
+                self.measure(measurement);
+                // A dark is taken once the worker returns it
+                if measurement != SwitchedMeasurement::Dark {
+                    if let Some(sequence) = &mut self.device_sequence {
+                        sequence.measured();
+                    }
+                }
+            }
+            None => {}
+        }
+        if self
+            .device_sequence
+            .as_ref()
+            .is_some_and(DeviceSequence::is_done)
+        {
+            self.device_sequence = None;
+        }
+    }
+
+    /// Move the filter wheel through its positions and capture a spectrum at each, see
+    /// [FilterWheelSequence].
+    fn step_filter_wheel(&mut self) {
+        let buffer_since = self.buffer_filled_since();
+        let step = match &mut self.filter_wheel {
+            None => return,
+            Some(sequence) => sequence.next_step(Instant::now(), buffer_since),
+        };
+        let result = match step {
+            Some(WheelStep::Move(position)) => {
+                let position = &self.config.filter_wheel.positions[position];
+                match &self.device {
+                    Some(device) => device
+                        .send_template(
+                            &position.command,
+                            &format!("the filter wheel to {}", position.label),
+                        )
+                        .map_err(|e| {
+                            format!(
+                                "Could not move the filter wheel to {}: {}",
+                                position.label, e
+                            )
+                        }),
+                    None => Err("The device control is disconnected".to_string()),
+                }
+            }
+            Some(WheelStep::ClearBuffer) => {
+                self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+                Ok(())
+            }
+            Some(WheelStep::Capture(_)) => {
+                let spectrum = self.spectrum_frame.get_spectrum_channel(3, &self.config);
+                self.filter_set.push(spectrum);
+                Ok(())
+            }
+            Some(WheelStep::Done) => {
+                self.filter_wheel = None;
+                self.export_filter_set();
+                Ok(())
+            }
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            self.filter_wheel = None;
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result: Err(e),
+            });
+        }
+    }
+
+    /// Write the spectra of the filter wheel positions in the background, named like the other
+    /// exports, and keep them as snapshots.
+    fn export_filter_set(&mut self) {
+        let spectra = std::mem::take(&mut self.filter_set);
+        let labels: Vec<String> = self
+            .config
+            .filter_wheel
+            .positions
+            .iter()
+            .map(|p| p.label.clone())
+            .collect();
+        let unit = self.config.spectrum_calibration.intensity_unit();
+        let path = Self::export_path(
+            &self.config.import_export_config,
+            &self.camera_name(),
+            "csv",
+        );
+        let path_string = path.to_string_lossy().to_string();
+        let (thread_labels, thread_spectra) = (labels.clone(), spectra.clone());
+        let waker = self.waker.clone();
+        let job = std::thread::spawn(move || {
+            let result = File::create(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|file| {
+                    write_filter_set(file, &thread_labels, &thread_spectra, unit.symbol())
+                });
+            if let Some(waker) = waker {
+                waker();
+            }
+            result
+        });
+        self.filter_set_export = Some((path_string, job));
+        for (label, spectrum) in labels.into_iter().zip(spectra) {
+            let index = self.config.snapshots.len();
+            let snapshot = Snapshot {
+                name: label,
+                color: SNAPSHOT_COLORS[index % SNAPSHOT_COLORS.len()],
+                visible: true,
+                unit,
+                spectrum,
+            };
+            if let Err(name) = add_snapshot(&mut self.config.snapshots, snapshot) {
+                self.notifications.push(
+                    NotificationLevel::Warning,
+                    format!("Same spectrum as snapshot {}", name),
+                );
+            }
+        }
+    }
+
+    /// Report the filter set once it is written.
+    fn poll_filter_set_export(&mut self) {
+        if !self
+            .filter_set_export
+            .as_ref()
+            .is_some_and(|(_, job)| job.is_finished())
+        {
+            return;
+        }
+        let (path, job) = self.filter_set_export.take().unwrap();
+        let result = job
+            .join()
+            .unwrap_or_else(|_| Err("Export thread panicked".to_string()))
+            .map_err(|e| format!("Could not write {}: {}", path, e))
+            .and_then(|_| {
+                self.notifications.info(format!("Exported {}", path));
+                run_post_export_hook(&self.config.import_export_config.post_export_command, &path)
+            });
+        if let Err(e) = result {
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result: Err(e),
+            });
+        }
+    }
+
+    /// Arm the hardware trigger with the busy output, or release their pins.
+    #[cfg(target_os = "linux")]
+    fn set_trigger(&mut self, armed: bool) {
+        if !armed {
+            self.set_busy(false);
+            self.trigger = None;
+            self.busy_pin = None;
+            return;
+        }
+        let gpio = &self.config.gpio;
+        let result = TriggerInput::new(
+            &gpio.chip,
+            gpio.trigger_pin,
+            gpio.trigger_edge,
+            self.waker.clone(),
+        )
+        .and_then(|trigger| {
+            let busy_pin = match gpio.busy_output {
+                true => Some(BusyOutput::new(&gpio.chip, gpio.busy_pin)?),
+                false => None,
+            };
+            Ok((trigger, busy_pin))
+        });
+        match result {
+            Ok((trigger, busy_pin)) => {
+                self.trigger = Some(trigger);
+                self.busy_pin = busy_pin;
+            }
+            Err(e) => {
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err(e),
+                })
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_busy(&mut self, busy: bool) {
+        if let Some(Err(e)) = self.busy_pin.as_ref().map(|pin| pin.set(busy)) {
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result: Err(e),
+            });
+        }
+    }
+
+    /// Start the camera if needed and acquire one averaged spectrum from the following
+    /// frames, then stop. Triggers during the acquisition are ignored.
+    #[cfg(target_os = "linux")]
+    fn start_single_shot(&mut self) {
+        if self.single_shot_since.is_some() {
+            return;
+        }
+        self.set_busy(true);
+        if self.acquisition.can_start() {
+            self.start_camera();
+            if self.acquisition.can_start() {
+                self.set_busy(false);
+                return;
+            }
+        }
+        self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+        self.single_shot_since = Some(Instant::now());
+    }
+
+    /// Start a single-shot acquisition on an edge of the hardware trigger and complete it once
+    /// the averaging buffer is filled with spectra from after the trigger.
+    #[cfg(target_os = "linux")]
+    fn poll_trigger(&mut self) {
+        if let Some(trigger) = &self.trigger {
+            match trigger.triggered() {
+                Ok(true) => self.start_single_shot(),
+                Ok(false) => {}
+                Err(e) => {
+                    self.set_trigger(false);
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result: Err(e),
+                    });
+                }
+            }
+        }
+        let since = match self.single_shot_since {
+            None => return,
+            Some(since) => since,
+        };
+        if !self.acquisition.is_running() {
+            // Stopped or failed in between
+            self.single_shot_since = None;
+            self.set_busy(false);
+        } else if self.buffer_filled_since().is_some_and(|s| s >= since) {
+            self.single_shot_since = None;
+            self.stop_acquisition();
+            self.set_busy(false);
+            self.notifications.info("Single shot acquired");
+        }
+    }
+
+    /// Move the switching of the lock-in source on by one camera frame.
+    fn step_source_toggling(&mut self) {
+        match self
+            .source_toggling
+            .as_mut()
+            .and_then(|toggling| toggling.next_frame())
+        {
+            Some(ToggleStep::Switch(on)) => {
+                self.processor_tx
+                    .send(ProcessorEvent::LockInState(None))
+                    .unwrap();
+                self.switch_source(on);
+            }
+            Some(ToggleStep::Capture(on)) => self
+                .processor_tx
+                .send(ProcessorEvent::LockInState(Some(on)))
+                .unwrap(),
+            None => {}
+        }
+    }
+
+    /// Run the command switching the source of the lock-in detection on or off, or switch
+    /// the lamp of the device control without commands.
+    fn switch_source(&mut self, on: bool) {
+        let lock_in = &self.config.lock_in;
+        let command = match on {
+            true => &lock_in.source_on_command,
+            false => &lock_in.source_off_command,
+        };
+        let result = match &self.device {
+            Some(device) if command.trim().is_empty() => device.send(
+                match on {
+                    true => DeviceAction::LampOn,
+                    false => DeviceAction::LampOff,
+                },
+                &self.config.device_control,
+            ),
+            _ => run_command(command, "Source switching"),
+        };
+        if let Err(e) = result {
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result: Err(e),
+            });
+        }
+    }
+
+    /// Start the lock-in detection, switching the source with the toggled reference, or stop
+    /// it and leave the source off.
+    fn set_lock_in(&mut self, active: bool) {
+        if active {
+            #[cfg(target_os = "linux")]
+            self.set_hdr(false);
+            if self.config.lock_in.reference == LockInReference::Toggled {
+                self.switch_source(true);
+                self.source_toggling =
+                    Some(SourceToggling::new(self.config.lock_in.frames_per_state));
+            }
+            self.processor_tx
+                .send(ProcessorEvent::LockIn(Some(Box::new(
+                    LockInDemodulator::new(&self.config.lock_in),
+                ))))
+                .unwrap();
+            self.lock_in_active = true;
+        } else if self.lock_in_active {
+            self.processor_tx
+                .send(ProcessorEvent::LockIn(None))
+                .unwrap();
+            if self.source_toggling.take().is_some() {
+                self.switch_source(false);
+            }
+            self.lock_in_active = false;
+        }
+    }
+
+    /// Move the linearity sweep on by one camera frame.
+    fn step_linearity_sweep(&mut self, frame: &ImageBuffer<Rgb<u8>, Vec<u8>>) {
+        let window = extract_window(
+            frame,
+            &self.config.image_config.window,
+            self.config.image_config.rotation,
+        );
+        match self
+            .linearity_sweep
+            .as_mut()
+            .and_then(|sweep| sweep.next_frame(&window))
+        {
+            Some(SweepStep::Expose(exposure)) => self.set_exposure(exposure),
+            Some(SweepStep::Done) => self.finish_linearity_sweep(true),
+            None => {}
+        }
+    }
+
+    /// Sweep the exposure from the shortest to the longest, see [LinearitySweep].
+    fn start_linearity_sweep(&mut self) {
+        let (index, range) = match self.exposure_control() {
+            None => return,
+            Some(control) => control,
+        };
+        #[cfg(target_os = "linux")]
+        {
+            self.set_hdr(false);
+            self.set_manual_exposure();
+        }
+        self.auto_exposure = None;
+        self.linearity_fit = None;
+        self.linearity_sweep = Some(LinearitySweep::new(
+            self.camera_controls[index].value,
+            range,
+            LINEARITY_STEPS,
+        ));
+    }
+
+    /// Go back to the exposure before the sweep, fitting the response if it is complete.
+    fn finish_linearity_sweep(&mut self, complete: bool) {
+        let sweep = match self.linearity_sweep.take() {
+            None => return,
+            Some(sweep) => sweep,
+        };
+        self.set_exposure(sweep.initial_exposure());
+        self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+        if !complete {
+            return;
+        }
+        match fit_response(sweep.points()) {
+            Ok(fit) => {
+                self.notifications.info(format!(
+                    "Linearity sweep finished, fitted gamma {:.2}",
+                    fit.gamma
+                ));
+                self.linearity_fit = Some(fit);
+            }
+            Err(e) => {
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err(e),
+                })
+            }
+        }
+    }
+
+    /// Set the exposure control, if the camera has one.
+    fn set_exposure(&mut self, exposure: i32) {
+        if let Some((index, _)) = self.exposure_control() {
+            let control = &mut self.camera_controls[index];
+            control.value = exposure;
+            self.camera_config_tx
+                .send(CameraEvent::Controls(vec![control.clone()]))
+                .unwrap();
+        }
+    }
+
+    /// Index and range of the absolute exposure control.
+    #[cfg(target_os = "linux")]
+    fn exposure_control(&self) -> Option<(usize, RangeInclusive<i32>)> {
+        self.camera_raw_controls
+            .iter()
+            .filter_map(|ctrl| ctrl.downcast_ref::<Description>())
+            .filter(|ctrl| {
+                ctrl.typ == v4l::control::Type::Integer && is_manual_control(&ctrl.name, "exposure")
+            })
+            .find_map(|ctrl| {
+                let index = self.camera_controls.iter().position(|c| c.id == ctrl.id)?;
+                Some((index, ctrl.minimum..=ctrl.maximum))
+            })
+    }
+
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    fn exposure_control(&self) -> Option<(usize, RangeInclusive<i32>)> {
+        None
+    }
+
+    /// Value of the exposure control, 0 for cameras without one.
+    fn current_exposure(&self) -> i32 {
+        self.exposure_control()
+            .map_or(0, |(index, _)| self.camera_controls[index].value)
+    }
+
+    fn draw_calibration_window(&mut self, ctx: &Context) {
+        let touch = self.config.view_config.touch_mode;
+        let mut store_profile = false;
+        let mut set_zero = false;
+        let mut capture_dark = false;
+        let stream_setup = self.config.stream_setup();
+        let test_pattern_deviation = self.test_pattern_deviation();
+        let exposure = self.current_exposure();
+        // Darks need a camera at a fixed exposure
+        let dark_capture_possible = stream_setup.is_some()
+            && matches!(self.acquisition.source(), Some(AcquisitionSource::Camera))
+            && self.hdr.is_none()
+            && self.spectrum_frame.dark_capture_frames().is_none()
+            && !self.device_busy();
+        let resolution_fit = if self.config.view_config.show_calibration_window {
+            closest_fit(
+                &self.spectrum_frame.fit_peaks(&self.config),
+                self.resolution_line,
+                self.config.view_config.peak_match_tolerance,
+            )
+        } else {
+            None
+        };
         egui::Window::new("Calibration")
             .open(&mut self.config.view_config.show_calibration_window)
             .show(ctx, |ui| {
-                ui.add(
-                    Slider::new(
-                        &mut self.config.spectrum_calibration.low.wavelength,
-                        200..=self.config.spectrum_calibration.high.wavelength - 1,
-                    )
-                    .text("Low Wavelength"),
-                );
-                ui.add(
-                    Slider::new(
-                        &mut self.config.spectrum_calibration.low.index,
-                        0..=self.config.spectrum_calibration.high.index - 1,
-                    )
-                    .text("Low Index"),
-                );
+                let calibration_check = self
+                    .config
+                    .spectrum_calibration
+                    .check(self.config.image_config.window.size.x as usize);
+                ui.scope(|ui| {
+                    if calibration_check.is_err() {
+                        let visuals = ui.visuals_mut();
+                        visuals.override_text_color = Some(Color32::RED);
+                        visuals.widgets.inactive.bg_fill = Color32::DARK_RED;
+                        visuals.widgets.hovered.bg_fill = Color32::DARK_RED;
+                        visuals.widgets.active.bg_fill = Color32::RED;
+                    }
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            TouchSlider::new(
+                                &mut self.config.spectrum_calibration.low.wavelength,
+                                200..=self.config.spectrum_calibration.high.wavelength - 1,
+                                touch,
+                            )
+                            .text("Low Wavelength"),
+                        );
+                        if touch && ui.button("⌨").clicked() {
+                            self.numpad_target = Some(NumpadTarget::LowWavelength);
+                            self.numpad_input.clear();
+                        }
+                    });
+                    ui.add(
+                        TouchSlider::new(
+                            &mut self.config.spectrum_calibration.low.index,
+                            0..=self.config.spectrum_calibration.high.index - 1,
+                            touch,
+                        )
+                        .text("Low Index"),
+                    );
 
-                ui.add(
-                    Slider::new(
-                        &mut self.config.spectrum_calibration.high.wavelength,
-                        (self.config.spectrum_calibration.low.wavelength + 1)..=2000,
-                    )
-                    .text("High Wavelength"),
-                );
-                ui.add(
-                    Slider::new(
-                        &mut self.config.spectrum_calibration.high.index,
-                        (self.config.spectrum_calibration.low.index + 1)
-                            ..=self.config.image_config.window.size.x as usize,
-                    )
-                    .text("High Index"),
-                );
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            TouchSlider::new(
+                                &mut self.config.spectrum_calibration.high.wavelength,
+                                (self.config.spectrum_calibration.low.wavelength + 1)..=2000,
+                                touch,
+                            )
+                            .text("High Wavelength"),
+                        );
+                        if touch && ui.button("⌨").clicked() {
+                            self.numpad_target = Some(NumpadTarget::HighWavelength);
+                            self.numpad_input.clear();
+                        }
+                    });
+                    ui.add(
+                        TouchSlider::new(
+                            &mut self.config.spectrum_calibration.high.index,
+                            (self.config.spectrum_calibration.low.index + 1)
+                                ..=self.config.image_config.window.size.x as usize,
+                            touch,
+                        )
+                        .text("High Index"),
+                    );
+                });
+                if let Err(e) = calibration_check {
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(format!("⚠ {}", e)).color(Color32::RED));
+                        if ui
+                            .add_enabled(
+                                !self.calibration_undo.is_empty(),
+                                Button::new("Revert to Last Good Calibration"),
+                            )
+                            .clicked()
+                        {
+                            self.config.spectrum_calibration = self.calibration_undo.pop().unwrap();
+                            self.notifications
+                                .info("Reverted to the last good calibration");
+                        }
+                    });
+                }
                 ui.separator();
                 ComboBox::from_label("Linearize")
                     .selected_text(self.config.spectrum_calibration.linearize.to_string())
@@ -628,23 +3098,62 @@ impl SpectrometerGui {
                                 Linearize::SRgb.to_string(),
                             )
                             .changed();
+                        if matches!(
+                            self.config.spectrum_calibration.linearize,
+                            Linearize::Custom(_)
+                        ) {
+                            ui.selectable_label(true, "Custom")
+                                .on_hover_text("Measured in the Linearity window or imported");
+                        }
 
                         // Clear buffer if value changed
                         if changed {
-                            self.spectrum_container.clear_buffer()
+                            self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap()
                         };
                     });
+                if ui
+                    .button("Import Linearization CSV")
+                    .on_hover_text(
+                        "Columns of value and linearized value, relative to full scale or as \
+                         8 bit codes, from the path of the Import/Export window",
+                    )
+                    .clicked()
+                {
+                    let result = File::open(&self.config.import_export_config.path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|file| read_linearization(BufReader::new(file)))
+                        .map(|lut| {
+                            self.config.spectrum_calibration.linearize = Linearize::Custom(lut);
+                            self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+                        });
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result,
+                    });
+                }
                 ui.add(
-                    Slider::new(&mut self.config.spectrum_calibration.gain_r, 0.0..=10.)
-                        .text("Gain R"),
+                    TouchSlider::new(
+                        &mut self.config.spectrum_calibration.gain_r,
+                        0.0..=10.,
+                        touch,
+                    )
+                    .text("Gain R"),
                 );
                 ui.add(
-                    Slider::new(&mut self.config.spectrum_calibration.gain_g, 0.0..=10.)
-                        .text("Gain G"),
+                    TouchSlider::new(
+                        &mut self.config.spectrum_calibration.gain_g,
+                        0.0..=10.,
+                        touch,
+                    )
+                    .text("Gain G"),
                 );
                 ui.add(
-                    Slider::new(&mut self.config.spectrum_calibration.gain_b, 0.0..=10.)
-                        .text("Gain B"),
+                    TouchSlider::new(
+                        &mut self.config.spectrum_calibration.gain_b,
+                        0.0..=10.,
+                        touch,
+                    )
+                    .text("Gain B"),
                 );
 
                 ui.horizontal(|ui| {
@@ -674,6 +3183,57 @@ impl SpectrometerGui {
                     }
                 });
 
+                ComboBox::from_label("QE Correction")
+                    .selected_text(
+                        self.config
+                            .spectrum_calibration
+                            .qe_curve
+                            .as_ref()
+                            .map_or("Off", |c| c.name.as_str()),
+                    )
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut self.config.spectrum_calibration.qe_curve,
+                            None,
+                            "Off",
+                        );
+                        for curve in bundled_qe_curves() {
+                            let name = curve.name.clone();
+                            ui.selectable_value(
+                                &mut self.config.spectrum_calibration.qe_curve,
+                                Some(curve),
+                                name,
+                            );
+                        }
+                    });
+                let import_qe_button = ui.button("Import QE Curve CSV");
+                if import_qe_button.clicked() {
+                    let path = &self.config.import_export_config.path;
+                    match csv::Reader::from_path(path)
+                        .and_then(|mut r| r.deserialize().collect::<Result<Vec<QePoint>, _>>())
+                    {
+                        Ok(mut points) => {
+                            points.sort_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+                            self.config.spectrum_calibration.qe_curve = Some(QeCurve {
+                                name: Path::new(path)
+                                    .file_stem()
+                                    .map_or(path.clone(), |s| s.to_string_lossy().to_string()),
+                                points,
+                            });
+                            self.last_error = Some(ThreadResult {
+                                id: ThreadId::Main,
+                                result: Ok(()),
+                            });
+                        }
+                        Err(e) => {
+                            self.last_error = Some(ThreadResult {
+                                id: ThreadId::Main,
+                                result: Err(e.to_string()),
+                            });
+                        }
+                    };
+                }
+
                 ui.separator();
                 let set_calibration_button = ui.add_enabled(
                     self.config.reference_config.reference.is_some()
@@ -681,10 +3241,11 @@ impl SpectrometerGui {
                     Button::new("Set Reference as Calibration"),
                 );
                 if set_calibration_button.clicked() {
-                    self.spectrum_container.set_calibration(
+                    self.spectrum_frame.set_calibration(
                         &mut self.config.spectrum_calibration,
                         &self.config.reference_config,
                     );
+                    self.config.spectrum_calibration.scaling_setup = stream_setup;
                 };
                 let delete_calibration_button = ui.add_enabled(
                     self.config.reference_config.reference.is_some()
@@ -693,57 +3254,554 @@ impl SpectrometerGui {
                 );
                 if delete_calibration_button.clicked() {
                     self.config.spectrum_calibration.scaling = None;
+                    self.config.spectrum_calibration.scaling_setup = None;
                 };
 
                 ui.separator();
                 let set_zero_button = ui.add_enabled(
-                    !self.spectrum_container.has_zero_reference(),
+                    !self.spectrum_frame.has_zero_reference(),
                     Button::new("Set Current As Zero Reference"),
                 );
-                if set_zero_button.clicked() {
-                    self.spectrum_container.set_zero_reference();
-                }
+                set_zero = set_zero_button.clicked();
                 let clear_zero_button = ui.add_enabled(
-                    self.spectrum_container.has_zero_reference(),
+                    self.spectrum_frame.has_zero_reference(),
                     Button::new("Clear Zero Reference"),
                 );
                 if clear_zero_button.clicked() {
-                    self.spectrum_container.clear_zero_reference();
+                    self.config.zero_reference = None;
+                    self.processor_tx
+                        .send(ProcessorEvent::ClearZeroReference)
+                        .unwrap();
                 }
-            });
-    }
 
-    fn draw_postprocessing_window(&mut self, ctx: &Context) {
-        egui::Window::new("Postprocessing")
-            .open(&mut self.config.view_config.show_postprocessing_window)
-            .show(ctx, |ui| {
-                ui.add(
-                    Slider::new(
-                        &mut self.config.postprocessing_config.spectrum_buffer_size,
-                        1..=100,
-                    )
-                    .text("Averaging Buffer Size"),
+                ui.separator();
+                ui.collapsing("Dark Library", |ui| {
+                    let mut delete_dark = None;
+                    match &self.config.dark_library {
+                        Some(library) if !library.darks.is_empty() => {
+                            for (i, dark) in library.darks.iter().enumerate() {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("Exposure {}", dark.exposure));
+                                    if ui.button("Delete").clicked() {
+                                        delete_dark = Some(i);
+                                    }
+                                });
+                            }
+                        }
+                        _ => {
+                            ui.label(
+                                "No darks yet, capture one per exposure with the lens covered",
+                            );
+                        }
+                    }
+                    if let Some(i) = delete_dark {
+                        if let Some(library) = &mut self.config.dark_library {
+                            library.darks.remove(i);
+                        }
+                    }
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(dark_capture_possible, Button::new("Capture Dark"))
+                            .on_hover_text(format!(
+                                "Cover the lens, the next {} spectra are averaged",
+                                DARK_FRAMES
+                            ))
+                            .clicked()
+                        {
+                            capture_dark = true;
+                        }
+                        if ui
+                            .add_enabled(
+                                self.config.dark_library.is_some(),
+                                Button::new("Clear Library"),
+                            )
+                            .clicked()
+                        {
+                            self.config.dark_library = None;
+                        }
+                    });
+                    if let Some(frames) = self.spectrum_frame.dark_capture_frames() {
+                        ui.label(format!("Capturing dark: {}/{}", frames, DARK_FRAMES));
+                    } else if self.spectrum_frame.dark_subtracted() {
+                        ui.label(format!("Dark subtracted for exposure {}", exposure));
+                    }
+                });
+
+                ui.separator();
+                ui.collapsing("Resolution", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Isolated Line");
+                        ui.add(
+                            DragValue::new(&mut self.resolution_line)
+                                .clamp_range(200..=2000)
+                                .speed(0.1)
+                                .suffix(" nm"),
+                        )
+                        .on_hover_text("e.g. the 546.07 nm line of mercury");
+                    });
+                    let dispersion = self.config.spectrum_calibration.dispersion();
+                    match resolution_fit {
+                        Some(fit) => {
+                            let pixels = fit.fwhm / dispersion;
+                            ui.label(format!(
+                                "FWHM {:.2} nm ({:.1} px) at {:.2} nm, λ/Δλ {:.0}",
+                                fit.fwhm,
+                                pixels,
+                                fit.center,
+                                fit.center / fit.fwhm
+                            ));
+                            if pixels < 2.5 {
+                                ui.label(
+                                    RichText::new(
+                                        "⚠ Undersampled, the pixel pitch limits the resolution",
+                                    )
+                                    .color(Color32::YELLOW),
+                                );
+                            }
+                            if ui.button("Store with Calibration").clicked() {
+                                self.config.spectrum_calibration.resolution =
+                                    Some(SpectralResolution {
+                                        wavelength: fit.center,
+                                        fwhm: fit.fwhm,
+                                        measured: Local::now().timestamp(),
+                                    });
+                            }
+                        }
+                        None => {
+                            ui.label(format!(
+                                "No peak within {} nm of the line",
+                                self.config.view_config.peak_match_tolerance
+                            ));
+                        }
+                    }
+                    if let Some(resolution) = self.config.spectrum_calibration.resolution {
+                        ui.label(format!(
+                            "Stored: {:.2} nm FWHM at {:.1} nm, λ/Δλ {:.0}",
+                            resolution.fwhm,
+                            resolution.wavelength,
+                            resolution.resolving_power()
+                        ));
+                    }
+                });
+
+                ui.separator();
+                ui.collapsing("Profiles", |ui| {
+                    let store_profile_button = ui.add_enabled(
+                        self.config.camera_format.is_some(),
+                        Button::new("Store Current As Profile"),
+                    );
+                    // Cannot store the profile here due to mutable borrow in open
+                    store_profile = store_profile_button.clicked();
+                    let mut delete_profile = None;
+                    for (i, profile) in self.config.calibration_profiles.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut profile.name);
+                            ui.label(format!("{}: {}", profile.camera_id, profile.camera_format));
+                            if ui.button("Delete").clicked() {
+                                delete_profile = Some(i);
+                            }
+                        });
+                    }
+                    if let Some(i) = delete_profile {
+                        self.config.calibration_profiles.remove(i);
+                    }
+                });
+
+                ui.collapsing("Test Pattern", |ui| {
+                    ui.checkbox(&mut self.test_pattern, "Show Test Pattern")
+                        .on_hover_text(
+                            "Replace the camera spectrum by lines at exactly these wavelengths",
+                        );
+                    let input = ui
+                        .text_edit_singleline(&mut self.test_pattern_input)
+                        .on_hover_text("Wavelengths in nm, separated by commas");
+                    let wavelengths = parse_wavelengths(&self.test_pattern_input);
+                    match wavelengths {
+                        Some(wavelengths) if input.changed() => {
+                            self.config.test_pattern.wavelengths = wavelengths
+                        }
+                        Some(_) => {}
+                        None => {
+                            ui.colored_label(Color32::RED, "Invalid wavelengths");
+                        }
+                    }
+                    ui.add(
+                        TouchSlider::new(&mut self.config.test_pattern.fwhm, 0.5..=20., touch)
+                            .text("FWHM [nm]"),
+                    );
+                    if let Some(deviation) = test_pattern_deviation {
+                        ui.label(match deviation {
+                            Some(deviation) => {
+                                format!("Largest Peak Deviation: {:.3} nm", deviation)
+                            }
+                            None => "No peaks found".to_string(),
+                        })
+                        .on_hover_text("Fitted peak centers compared to the wavelengths");
+                    }
+                });
+            });
+
+        if set_zero {
+            self.set_zero_reference();
+        }
+        if capture_dark {
+            self.switched_measurement(SwitchedMeasurement::Dark);
+        }
+        if store_profile {
+            self.config.store_calibration_profile(
+                self.config.camera_id,
+                self.config.camera_format.unwrap(),
+            );
+        }
+    }
+
+    /// Subtract the current spectrum from the following ones, stored for the current setup.
+    fn set_zero_reference(&mut self) {
+        self.switched_measurement(SwitchedMeasurement::ZeroReference);
+    }
+
+    fn take_zero_reference(&mut self) {
+        let spectrum = self.spectrum_frame.spectrum();
+        self.config.zero_reference = self
+            .config
+            .stream_setup()
+            .map(|setup| ZeroReference::new(setup, spectrum));
+        self.processor_tx
+            .send(ProcessorEvent::LoadZeroReference(Box::new(
+                spectrum.clone(),
+            )))
+            .unwrap();
+    }
+
+    fn draw_postprocessing_window(&mut self, ctx: &Context) {
+        let touch = self.config.view_config.touch_mode;
+        let frame_rate = frame_rate(self.spectrum_frame.buffered_frames());
+        egui::Window::new("Postprocessing")
+            .open(&mut self.config.view_config.show_postprocessing_window)
+            .show(ctx, |ui| {
+                ui.checkbox(
+                    &mut self.config.postprocessing_config.low_latency,
+                    "Low Latency",
+                )
+                .on_hover_text("Show the freshest frame, e.g. for aligning optics");
+                if self.config.postprocessing_config.low_latency {
+                    ui.label(
+                        RichText::new("Averaging, smoothing and deconvolution are bypassed")
+                            .color(Color32::LIGHT_YELLOW),
+                    );
+                }
+                ui.add_enabled(
+                    !self.config.postprocessing_config.low_latency,
+                    TouchSlider::new(
+                        &mut self.config.postprocessing_config.spectrum_buffer_size,
+                        1..=100,
+                        touch,
+                    )
+                    .text("Averaging Buffer Size"),
                 );
+                ui.checkbox(
+                    &mut self.config.postprocessing_config.retain_buffer_on_restart,
+                    "Keep Buffer On Restart",
+                )
+                .on_hover_text("If camera, format and window are unchanged");
+                ui.horizontal(|ui| {
+                    let postprocessing = &mut self.config.postprocessing_config;
+                    ui.checkbox(&mut postprocessing.outlier_rejection, "Reject Outliers")
+                        .on_hover_text(
+                            "Leave frames deviating strongly from the others out of the average, \
+                            e.g. when someone walks past the lamp",
+                        );
+                    ui.add_enabled(
+                        postprocessing.outlier_rejection,
+                        DragValue::new(&mut postprocessing.outlier_sigma)
+                            .clamp_range(1.0..=10.)
+                            .speed(0.1)
+                            .suffix(" σ"),
+                    );
+                });
+                let postprocessing = &mut self.config.postprocessing_config;
+                ComboBox::from_label("Flicker Compensation")
+                    .selected_text(postprocessing.flicker.to_string())
+                    .show_ui(ui, |ui| {
+                        for flicker in [Flicker::Off, Flicker::Mains50Hz, Flicker::Mains60Hz] {
+                            ui.selectable_value(
+                                &mut postprocessing.flicker,
+                                flicker,
+                                flicker.to_string(),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "Average whole periods of the beat of the lamp flicker with the frame \
+                         rate",
+                    );
+                if let Some(flicker) = postprocessing.flicker.frequency() {
+                    match frame_rate {
+                        Some(frame_rate) => {
+                            ui.label(format!(
+                                "{:.1} fps, beat {:.1} Hz, averaging {} frames",
+                                frame_rate,
+                                beat_frequency(flicker, frame_rate),
+                                postprocessing.synchronized_buffer_size(Some(frame_rate))
+                            ));
+                        }
+                        None => {
+                            ui.label("Measuring the frame rate");
+                        }
+                    }
+                    ui.label(
+                        RichText::new(format!(
+                            "Exposures of whole multiples of {:.2} ms show no flicker",
+                            1000. / flicker
+                        ))
+                        .weak(),
+                    );
+                }
+                ui.separator();
+                ComboBox::from_label("Smoothing")
+                    .selected_text(postprocessing.smoothing_method.to_string())
+                    .show_ui(ui, |ui| {
+                        for method in [
+                            SmoothingMethod::None,
+                            SmoothingMethod::LowPass,
+                            SmoothingMethod::SavitzkyGolay,
+                            SmoothingMethod::Median,
+                            SmoothingMethod::Gaussian,
+                        ] {
+                            ui.selectable_value(
+                                &mut postprocessing.smoothing_method,
+                                method,
+                                method.to_string(),
+                            );
+                        }
+                    });
+                match postprocessing.smoothing_method {
+                    SmoothingMethod::None => {}
+                    SmoothingMethod::LowPass => {
+                        ui.add(
+                            TouchSlider::new(
+                                &mut postprocessing.spectrum_filter_cutoff,
+                                0.001..=1.,
+                                touch,
+                            )
+                            .logarithmic(true)
+                            .text("Cutoff"),
+                        );
+                    }
+                    SmoothingMethod::SavitzkyGolay => {
+                        ui.add(
+                            TouchSlider::new(
+                                &mut postprocessing.savitzky_golay_half_width,
+                                1..=25,
+                                touch,
+                            )
+                            .text("Half Width"),
+                        );
+                        ui.add(
+                            TouchSlider::new(
+                                &mut postprocessing.savitzky_golay_order,
+                                0..=6,
+                                touch,
+                            )
+                            .text("Polynomial Order"),
+                        );
+                    }
+                    SmoothingMethod::Median => {
+                        ui.add(
+                            TouchSlider::new(&mut postprocessing.median_half_width, 1..=25, touch)
+                                .text("Half Width"),
+                        );
+                    }
+                    SmoothingMethod::Gaussian => {
+                        ui.add(
+                            TouchSlider::new(&mut postprocessing.gaussian_sigma, 0.1..=20., touch)
+                                .text("Sigma"),
+                        );
+                    }
+                }
+                ui.separator();
+                let baseline = &mut postprocessing.baseline;
+                ComboBox::from_label("Baseline")
+                    .selected_text(baseline.method.to_string())
+                    .show_ui(ui, |ui| {
+                        for method in [
+                            BaselineMethod::None,
+                            BaselineMethod::AsymmetricLeastSquares,
+                            BaselineMethod::RollingBall,
+                        ] {
+                            ui.selectable_value(&mut baseline.method, method, method.to_string());
+                        }
+                    })
+                    .response
+                    .on_hover_text("Subtract a slowly varying background before peak detection");
+                match baseline.method {
+                    BaselineMethod::None => {}
+                    BaselineMethod::AsymmetricLeastSquares => {
+                        ui.add(
+                            TouchSlider::new(&mut baseline.lambda, 1e2..=1e9, touch)
+                                .logarithmic(true)
+                                .text("Stiffness"),
+                        );
+                        ui.add(
+                            TouchSlider::new(&mut baseline.asymmetry, 0.001..=0.1, touch)
+                                .logarithmic(true)
+                                .text("Asymmetry"),
+                        );
+                    }
+                    BaselineMethod::RollingBall => {
+                        ui.add(
+                            TouchSlider::new(&mut baseline.radius, 5..=500, touch).text("Radius"),
+                        );
+                    }
+                }
                 ui.separator();
+                let spectrum_math = &mut postprocessing.spectrum_math;
                 ui.horizontal(|ui| {
-                    ui.checkbox(
-                        &mut self.config.postprocessing_config.spectrum_filter_active,
-                        "Low-Pass Filter",
+                    ComboBox::from_label("Spectrum Math")
+                        .selected_text(spectrum_math.operation.to_string())
+                        .show_ui(ui, |ui| {
+                            for operation in [
+                                MathOperation::None,
+                                MathOperation::Subtract,
+                                MathOperation::Divide,
+                            ] {
+                                ui.selectable_value(
+                                    &mut spectrum_math.operation,
+                                    operation,
+                                    operation.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Subtract a spectrum from the sum channel or divide it by one, \
+                            interpolated to the wavelengths of the spectrum",
+                        );
+                });
+                ui.horizontal(|ui| {
+                    ComboBox::from_id_source("cb_math_operand")
+                        .selected_text(if spectrum_math.operand.is_empty() {
+                            "No Operand".to_string()
+                        } else {
+                            spectrum_math.name.clone()
+                        })
+                        .show_ui(ui, |ui| {
+                            for snapshot in &self.config.snapshots {
+                                if ui.selectable_label(false, &snapshot.name).clicked() {
+                                    spectrum_math.set_operand(&snapshot.name, &snapshot.spectrum);
+                                }
+                            }
+                        });
+                    if ui
+                        .button("Load File")
+                        .on_hover_text("From the path of the Import/Export window")
+                        .clicked()
+                    {
+                        let path = Path::new(&self.config.import_export_config.path);
+                        match read_spectrum_file(path) {
+                            Ok(spectrum) => spectrum_math.set_operand(
+                                &path
+                                    .file_name()
+                                    .map(|name| name.to_string_lossy().to_string())
+                                    .unwrap_or_default(),
+                                &spectrum,
+                            ),
+                            Err(e) => {
+                                self.last_error = Some(ThreadResult {
+                                    id: ThreadId::Main,
+                                    result: Err(e),
+                                })
+                            }
+                        }
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Transform");
+                    ui.text_edit_singleline(&mut postprocessing.transform)
+                        .on_hover_text(
+                            "New intensity of each point from its wavelength x and intensity y \
+                            with the dashboard functions, e.g. y / I(550) or y - mean(700, 750). \
+                            Empty for none.",
+                        );
+                });
+                if let Some(e) = postprocessing.transform_error() {
+                    ui.colored_label(Color32::RED, e);
+                }
+                ui.separator();
+                ui.collapsing("Deconvolution", |ui| {
+                    let deconvolution = &mut self.config.postprocessing_config.deconvolution;
+                    ui.add(
+                        TouchSlider::new(&mut deconvolution.line_shape_half_width, 1..=50, touch)
+                            .text("Line Shape Half Width"),
                     );
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("Capture Line Shape")
+                            .on_hover_text("Use the strongest line of the current spectrum")
+                            .clicked()
+                        {
+                            match self
+                                .spectrum_frame
+                                .get_line_shape(deconvolution.line_shape_half_width)
+                            {
+                                Some(line_shape) => deconvolution.line_shape = Some(line_shape),
+                                None => {
+                                    self.last_error = Some(ThreadResult {
+                                        id: ThreadId::Main,
+                                        result: Err("No line found for the line shape".to_string()),
+                                    })
+                                }
+                            }
+                        }
+                        if ui
+                            .add_enabled(
+                                deconvolution.line_shape.is_some(),
+                                Button::new("Clear Line Shape"),
+                            )
+                            .clicked()
+                        {
+                            deconvolution.line_shape = None;
+                            deconvolution.active = false;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            TouchSlider::new(&mut deconvolution.gaussian_fwhm, 0.1..=20., touch)
+                                .text("FWHM [nm]"),
+                        );
+                        if ui
+                            .button("Use Gaussian")
+                            .on_hover_text("Line shape of the given instrument line width")
+                            .clicked()
+                        {
+                            let dispersion = self.config.spectrum_calibration.dispersion();
+                            deconvolution.line_shape = Some(gaussian_line_shape(
+                                deconvolution.gaussian_fwhm / dispersion,
+                            ));
+                        }
+                    });
+                    if let Some(line_shape) = deconvolution.line_shape.as_ref() {
+                        ui.label(format!("Line shape of {} pixels", line_shape.len()));
+                    }
                     ui.add_enabled(
-                        self.config.postprocessing_config.spectrum_filter_active,
-                        Slider::new(
-                            &mut self.config.postprocessing_config.spectrum_filter_cutoff,
-                            0.001..=1.,
-                        )
-                        .logarithmic(true)
-                        .text("Cutoff"),
+                        deconvolution.line_shape.is_some(),
+                        egui::Checkbox::new(&mut deconvolution.active, "Deconvolve"),
+                    );
+                    ui.add(
+                        TouchSlider::new(&mut deconvolution.iterations, 1..=200, touch)
+                            .text("Iterations"),
+                    );
+                    ui.add(
+                        TouchSlider::new(&mut deconvolution.regularization, 0.0..=0.05, touch)
+                            .text("Regularization"),
                     );
                 });
                 ui.separator();
                 ui.add_enabled(
                     self.config.reference_config.reference.is_some(),
-                    Slider::new(&mut self.config.reference_config.scale, 0.001..=100.)
+                    TouchSlider::new(&mut self.config.reference_config.scale, 0.001..=100., touch)
                         .logarithmic(true)
                         .text("Reference Scale"),
                 );
@@ -752,204 +3810,3359 @@ impl SpectrometerGui {
                     ui.checkbox(&mut self.config.view_config.draw_peaks, "Show Peaks");
                     ui.checkbox(&mut self.config.view_config.draw_dips, "Show Dips");
                 });
-                ui.add(
-                    Slider::new(&mut self.config.view_config.peaks_dips_find_window, 1..=200)
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.config.view_config.fit_peaks, "Fit Peaks")
+                        .on_hover_text("Label peaks with the fitted center wavelength");
+                    ComboBox::from_label("Peak Shape")
+                        .selected_text(self.config.view_config.peak_shape.to_string())
+                        .show_ui(ui, |ui| {
+                            for shape in [PeakShape::Gaussian, PeakShape::Lorentzian] {
+                                ui.selectable_value(
+                                    &mut self.config.view_config.peak_shape,
+                                    shape,
+                                    shape.to_string(),
+                                );
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut self.config.view_config.draw_detection_limit,
+                        "Show Detection Limit",
+                    )
+                    .on_hover_text("3σ noise of the averaged spectrum, needs an averaging buffer");
+                    ui.add_enabled(
+                        self.config.view_config.draw_detection_limit,
+                        TouchSlider::new(
+                            &mut self.config.postprocessing_config.noise_band_width,
+                            1..=100,
+                            touch,
+                        )
+                        .text("Noise Band Width"),
+                    );
+                });
+                ui.checkbox(
+                    &mut self.config.view_config.peaks_dips_find_in_nm,
+                    "Find Window in nm",
+                )
+                .on_hover_text("Same window width at every wavelength instead of every pixel");
+                if self.config.view_config.peaks_dips_find_in_nm {
+                    ui.add(
+                        TouchSlider::new(
+                            &mut self.config.view_config.peaks_dips_find_width,
+                            0.5..=100.,
+                            touch,
+                        )
+                        .text("Peaks/Dips Find Window [nm]"),
+                    );
+                } else {
+                    ui.add(
+                        TouchSlider::new(
+                            &mut self.config.view_config.peaks_dips_find_window,
+                            1..=200,
+                            touch,
+                        )
                         .text("Peaks/Dips Find Window"),
-                );
+                    );
+                }
                 ui.add(
-                    Slider::new(
+                    TouchSlider::new(
                         &mut self.config.view_config.peaks_dips_unique_window,
                         1.0..=200.,
+                        touch,
                     )
-                    .text("Peaks/Dips Filter Window"),
+                    .text("Peaks/Dips Filter Window [nm]"),
+                );
+            });
+    }
+
+    #[cfg(target_os = "linux")]
+    fn draw_camera_control_window(&mut self, ctx: &Context) {
+        let touch = self.config.view_config.touch_mode;
+        let exposure_available = self.exposure_control().is_some();
+        let mut auto_exposure = self.auto_exposure.is_some();
+        let mut hdr = self.hdr.is_some();
+        let hdr_bracket = self
+            .hdr
+            .as_ref()
+            .map(|hdr| (hdr.bracket(), hdr.exposures().len()));
+        let mut spectroscopy_defaults = false;
+        let mut open = self.config.view_config.show_camera_control_window;
+        egui::Window::new("Camera Controls")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.add_enabled(
+                    exposure_available,
+                    egui::Checkbox::new(&mut auto_exposure, "Auto Exposure"),
+                )
+                .on_hover_text(format!(
+                    "Keep the window maximum at {:.0}% of full scale",
+                    TARGET_FILL * 100.
+                ));
+                Self::draw_hdr_controls(
+                    ui,
+                    &mut self.config.hdr,
+                    &mut hdr,
+                    hdr_bracket,
+                    exposure_available,
+                    touch,
                 );
+                spectroscopy_defaults = Self::draw_spectroscopy_defaults_button(ui);
+                ui.separator();
+                let changed_controls = self.draw_controls(ui, touch);
+                if ui.button("All default").clicked() {
+                    self.reset_controls();
+                    self.camera_config_tx
+                        .send(CameraEvent::Controls(self.camera_controls.clone()))
+                        .unwrap();
+                }
+                if !changed_controls.is_empty() {
+                    self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+                    self.camera_config_tx
+                        .send(CameraEvent::Controls(changed_controls))
+                        .unwrap();
+                }
             });
+        self.config.view_config.show_camera_control_window = open;
+        if auto_exposure != self.auto_exposure.is_some() {
+            self.set_auto_exposure(auto_exposure);
+        }
+        if hdr != self.hdr.is_some() {
+            self.set_hdr(hdr);
+        }
+        if spectroscopy_defaults {
+            self.apply_spectroscopy_defaults();
+        }
+    }
+
+    /// Widgets of the camera's own controls, returning the changed ones.
+    #[cfg(target_os = "linux")]
+    fn draw_controls(&mut self, ui: &mut egui::Ui, touch: bool) -> Vec<CameraControl> {
+        let mut changed_controls = vec![];
+        for ctrl in &self.camera_raw_controls {
+            let ctrl = match ctrl.downcast_ref::<Description>() {
+                None => continue,
+                Some(ctrl) => ctrl,
+            };
+            let own_ctrl = match self.camera_controls.iter_mut().find(|c| c.id == ctrl.id) {
+                None => continue,
+                Some(own_ctrl) => own_ctrl,
+            };
+            let value_changed = match ctrl.typ {
+                v4l::control::Type::Integer => ui
+                    .add(
+                        TouchSlider::new(
+                            &mut own_ctrl.value,
+                            (ctrl.minimum + 1)..=(ctrl.maximum - 1),
+                            touch,
+                        )
+                        .step_by(ctrl.step as f64)
+                        .text(&ctrl.name),
+                    )
+                    .changed(),
+                v4l::control::Type::Boolean => {
+                    let mut checked = own_ctrl.value == 1;
+                    let response = ui.checkbox(&mut checked, &ctrl.name);
+                    own_ctrl.value = checked as i32;
+                    response.changed()
+                }
+                v4l::control::Type::Menu => {
+                    let mut changed = false;
+                    let items = match ctrl.items.as_ref() {
+                        None => continue,
+                        Some(items) => items,
+                    };
+                    let selected_text = match items.iter().find(|&i| i.0 == own_ctrl.value as u32) {
+                        None => continue,
+                        Some(i) => i.1.to_string(),
+                    };
+                    ComboBox::from_label(&ctrl.name)
+                        .selected_text(selected_text)
+                        .show_ui(ui, |ui| {
+                            for item in items.iter() {
+                                changed |= ui
+                                    .selectable_value(
+                                        &mut own_ctrl.value,
+                                        item.0 as i32,
+                                        item.1.to_string(),
+                                    )
+                                    .changed();
+                            }
+                        });
+                    changed
+                }
+                _ => false,
+            };
+            if value_changed {
+                changed_controls.push(own_ctrl.clone());
+            };
+        }
+        changed_controls
+    }
+
+    /// Set every control to the camera's default.
+    #[cfg(target_os = "linux")]
+    fn reset_controls(&mut self) {
+        for ctrl in &self.camera_raw_controls {
+            let ctrl = match ctrl.downcast_ref::<Description>() {
+                None => continue,
+                Some(ctrl) => ctrl,
+            };
+            if let Some(own_ctrl) = self.camera_controls.iter_mut().find(|c| c.id == ctrl.id) {
+                own_ctrl.value = ctrl.default;
+            }
+        }
+    }
+
+    /// Switch the camera's own automatic exposure to manual.
+    #[cfg(target_os = "linux")]
+    fn set_manual_exposure(&mut self) {
+        let manual_controls: Vec<CameraControl> = self
+            .camera_controls
+            .iter_mut()
+            .filter(|c| {
+                let name = c.name.to_lowercase();
+                name.contains("exposure") && name.contains("auto")
+            })
+            .map(|c| {
+                c.value = V4L2_EXPOSURE_MANUAL;
+                c.clone()
+            })
+            .collect();
+        if !manual_controls.is_empty() {
+            self.camera_config_tx
+                .send(CameraEvent::Controls(manual_controls))
+                .unwrap();
+        }
+    }
+
+    /// HDR checkbox with the bracket settings, which can only be changed while it is off.
+    #[cfg(target_os = "linux")]
+    fn draw_hdr_controls(
+        ui: &mut egui::Ui,
+        config: &mut HdrConfig,
+        active: &mut bool,
+        bracket: Option<(usize, usize)>,
+        exposure_available: bool,
+        touch: bool,
+    ) {
+        ui.horizontal(|ui| {
+            ui.add_enabled(exposure_available, egui::Checkbox::new(active, "HDR"))
+                .on_hover_text(
+                    "Cycle the exposure through brackets and merge them into one spectrum of \
+                    high dynamic range",
+                );
+            if let Some((bracket, brackets)) = bracket {
+                ui.label(format!("Bracket {} of {}", bracket + 1, brackets));
+            }
+        });
+        ui.add_enabled_ui(bracket.is_none(), |ui| {
+            ui.add(TouchSlider::new(&mut config.brackets, 2..=4, touch).text("Brackets"));
+            ui.add(
+                TouchSlider::new(&mut config.step, 2.0..=16., touch)
+                    .logarithmic(true)
+                    .text("Exposure Step"),
+            )
+            .on_hover_text("Factor between the exposures of neighboring brackets");
+        });
+    }
+
+    /// Button applying [Self::spectroscopy_defaults], returning whether it was clicked.
+    #[cfg(target_os = "linux")]
+    fn draw_spectroscopy_defaults_button(ui: &mut egui::Ui) -> bool {
+        ui.button("Spectroscopy Defaults")
+            .on_hover_text(
+                "Switch off automatic exposure, gain and white balance and fix a neutral white \
+                balance",
+            )
+            .clicked()
+    }
+
+    /// Switch the closed-loop exposure control, and the camera's own automatic to manual.
+    #[cfg(target_os = "linux")]
+    fn set_auto_exposure(&mut self, active: bool) {
+        if active {
+            self.set_hdr(false);
+            self.set_manual_exposure();
+        }
+        self.auto_exposure = active.then(AutoExposure::default);
+    }
+
+    /// Start bracketing from the current exposure, or stop it and go back to that exposure.
+    #[cfg(target_os = "linux")]
+    fn set_hdr(&mut self, active: bool) {
+        let (index, range) = match self.exposure_control() {
+            None => return,
+            Some(control) => control,
+        };
+        if active {
+            self.auto_exposure = None;
+            self.set_lock_in(false);
+            self.set_manual_exposure();
+            let bracketing =
+                HdrBracketing::new(self.camera_controls[index].value, &self.config.hdr, range);
+            self.processor_tx
+                .send(ProcessorEvent::Hdr(Some(bracketing.relative_exposures())))
+                .unwrap();
+            self.hdr = Some(bracketing);
+        } else if let Some(bracketing) = self.hdr.take() {
+            let control = &mut self.camera_controls[index];
+            control.value = bracketing.exposures()[0];
+            self.camera_config_tx
+                .send(CameraEvent::Controls(vec![control.clone()]))
+                .unwrap();
+            self.processor_tx.send(ProcessorEvent::Hdr(None)).unwrap();
+        }
+    }
+
+    /// Set the controls for reproducible measurements, see [Self::spectroscopy_defaults].
+    #[cfg(target_os = "linux")]
+    fn apply_spectroscopy_defaults(&mut self) {
+        let controls = self.spectroscopy_defaults();
+        if controls.is_empty() {
+            return;
+        }
+        self.camera_config_tx
+            .send(CameraEvent::Controls(controls))
+            .unwrap();
+        self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+    }
+
+    /// Switch off automatic exposure, gain and white balance and fix the white balance at
+    /// [NEUTRAL_WHITE_BALANCE], returning the changed controls with the automatics first.
+    #[cfg(target_os = "linux")]
+    fn spectroscopy_defaults(&mut self) -> Vec<CameraControl> {
+        let mut changed = vec![];
+        for descr in self
+            .camera_raw_controls
+            .iter()
+            .filter_map(|ctrl| ctrl.downcast_ref::<Description>())
+        {
+            let own_ctrl = match self.camera_controls.iter_mut().find(|c| c.id == descr.id) {
+                None => continue,
+                Some(own_ctrl) => own_ctrl,
+            };
+            let name = descr.name.to_lowercase();
+            let automatic = name.contains("auto");
+            let value = match descr.typ {
+                v4l::control::Type::Menu if automatic && name.contains("exposure") => {
+                    V4L2_EXPOSURE_MANUAL
+                }
+                v4l::control::Type::Boolean
+                    if automatic && (name.contains("white balance") || name.contains("gain")) =>
+                {
+                    0
+                }
+                v4l::control::Type::Integer
+                    if is_manual_control(&name, "white balance temperature") =>
+                {
+                    NEUTRAL_WHITE_BALANCE.clamp(descr.minimum, descr.maximum)
+                }
+                _ => continue,
+            };
+            if own_ctrl.value != value {
+                own_ctrl.value = value;
+                changed.push(own_ctrl.clone());
+            }
+        }
+        // The camera only accepts manual values once the automatic is off
+        changed.sort_by_key(|c| !c.name.to_lowercase().contains("auto"));
+        changed
+    }
+
+    #[cfg(any(target_os = "windows", target_os = "macos"))]
+    fn draw_camera_control_window(&mut self, _ctx: &Context) {}
+
+    fn draw_import_export_window(&mut self, ctx: &Context) {
+        let touch = self.config.view_config.touch_mode;
+        let camera = self.camera_name();
+        let mut export_clicked = false;
+        let mut report_clicked = false;
+        let mut websocket_toggled = false;
+        let mut rest_api_toggled = false;
+        let mut recording_toggled = false;
+        let mut replay_clicked = false;
+        let mut export_log_hdf5 = None;
+        let recording = self.recording;
+        let replay_frames = self.replay.as_ref().map(Replay::frames);
+        let can_start = self.acquisition.can_start();
+        let websocket_clients = self.websocket_server.as_ref().map(|s| s.clients());
+        let dropped_spectra = self.engine.dropped_spectra();
+        egui::Window::new("Import/Export")
+            .open(&mut self.config.view_config.show_import_export_window)
+            .show(ctx, |ui| {
+                ui.text_edit_singleline(&mut self.config.import_export_config.path);
+                ui.horizontal(|ui| {
+                    ui.label("Filename Template");
+                    ui.text_edit_singleline(
+                        &mut self.config.import_export_config.filename_template,
+                    )
+                    .on_hover_text(
+                        "Name of exported files in the directory of the path above, with \
+                        {sample}, {camera}, {date}, {time} and {seq}. Empty to use the path's \
+                        file name. Existing files are never overwritten.",
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Sample");
+                    ui.text_edit_singleline(&mut self.config.import_export_config.sample);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Post-Export Command");
+                    ui.text_edit_singleline(
+                        &mut self.config.import_export_config.post_export_command,
+                    )
+                    .on_hover_text(
+                        "Run with the exported path as last argument, quote arguments with \
+                         spaces",
+                    );
+                });
+                ui.separator();
+                ComboBox::from_label("Reference Unit")
+                    .selected_text(self.config.reference_config.unit.to_string())
+                    .show_ui(ui, |ui| {
+                        for unit in [IntensityUnit::Relative, IntensityUnit::SpectralIrradiance] {
+                            ui.selectable_value(
+                                &mut self.config.reference_config.unit,
+                                unit,
+                                unit.to_string(),
+                            );
+                        }
+                    });
+                let import_reference_button = ui.button("Import Reference CSV");
+                if import_reference_button.clicked() {
+                    match csv::Reader::from_path(&self.config.import_export_config.path)
+                        .and_then(|mut r| r.deserialize().collect())
+                    {
+                        Ok(r) => {
+                            self.config.reference_config.reference = Some(r);
+                            self.last_error = Some(ThreadResult {
+                                id: ThreadId::Main,
+                                result: Ok(()),
+                            });
+                        }
+                        Err(e) => {
+                            self.last_error = Some(ThreadResult {
+                                id: ThreadId::Main,
+                                result: Err(e.to_string()),
+                            });
+                        }
+                    };
+                }
+                let export_reference_button = ui.add_enabled(
+                    self.config.reference_config.reference.is_some(),
+                    Button::new("Export Reference CSV"),
+                );
+                if export_reference_button.clicked() {
+                    let path = Self::export_path(&self.config.import_export_config, &camera, "csv");
+                    let writer = csv::Writer::from_path(&path);
+                    match writer {
+                        Ok(mut writer) => {
+                            for p in self.config.reference_config.reference.as_ref().unwrap() {
+                                writer.serialize(p).unwrap();
+                            }
+                            writer.flush().unwrap();
+                            let result = run_post_export_hook(
+                                &self.config.import_export_config.post_export_command,
+                                &path.to_string_lossy(),
+                            );
+                            if result.is_ok() {
+                                self.notifications
+                                    .info(format!("Exported {}", path.display()));
+                            }
+                            self.last_error = Some(ThreadResult {
+                                id: ThreadId::Main,
+                                result,
+                            });
+                        }
+                        Err(e) => {
+                            self.last_error = Some(ThreadResult {
+                                id: ThreadId::Main,
+                                result: Err(e.to_string()),
+                            })
+                        }
+                    }
+                }
+                let delete_button = ui.add_enabled(
+                    self.config.reference_config.reference.is_some(),
+                    Button::new("Delete Reference"),
+                );
+                if delete_button.clicked() {
+                    self.config.reference_config.reference = None;
+                }
+                ui.horizontal(|ui| {
+                    let as_reference = ui
+                        .button("Import Reference")
+                        .on_hover_text(
+                            "Delimited text (e.g. SpectraSuite, Spectragryph, Theremino), SPC or \
+                            Spectral Workbench JSON",
+                        );
+                    let as_overlay = ui.button("Import Overlay");
+                    if as_reference.clicked() || as_overlay.clicked() {
+                        let path = Path::new(&self.config.import_export_config.path);
+                        match read_spectrum_file(path) {
+                            Ok(spectrum) if as_reference.clicked() => {
+                                self.config.reference_config.reference = Some(spectrum);
+                                self.last_error = Some(ThreadResult {
+                                    id: ThreadId::Main,
+                                    result: Ok(()),
+                                });
+                            }
+                            Ok(spectrum) => {
+                                let index = self.config.snapshots.len();
+                                let snapshot = Snapshot {
+                                    name: path
+                                        .file_stem()
+                                        .map(|stem| stem.to_string_lossy().to_string())
+                                        .unwrap_or_default(),
+                                    color: SNAPSHOT_COLORS[index % SNAPSHOT_COLORS.len()],
+                                    visible: true,
+                                    unit: IntensityUnit::Relative,
+                                    spectrum,
+                                };
+                                if let Err(name) =
+                                    add_snapshot(&mut self.config.snapshots, snapshot)
+                                {
+                                    self.notifications.push(
+                                        NotificationLevel::Warning,
+                                        format!("Already imported as snapshot {}", name),
+                                    );
+                                }
+                            }
+                            Err(e) => {
+                                self.last_error = Some(ThreadResult {
+                                    id: ThreadId::Main,
+                                    result: Err(e),
+                                });
+                            }
+                        }
+                    }
+                });
+                ui.separator();
+                let generate_reference_button =
+                    ui.button("Generate Reference From Tungsten Temperature");
+                if generate_reference_button.clicked() {
+                    self.config.reference_config.reference =
+                        Some(reference_from_filament_temp(
+                            self.tungsten_filament_temp,
+                            self.tungsten_emissivity,
+                            self.glass_envelope,
+                        ));
+                    self.config.reference_config.unit = IntensityUnit::Relative;
+                }
+                ui.add(
+                    TouchSlider::new(&mut self.tungsten_filament_temp, 1000..=3500, touch)
+                        .text("Tungsten Temperature"),
+                );
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.tungsten_emissivity, "Tungsten Emissivity")
+                        .on_hover_text("Off for an ideal black body");
+                    ComboBox::from_label("Glass Envelope")
+                        .selected_text(self.glass_envelope.to_string())
+                        .show_ui(ui, |ui| {
+                            for envelope in [
+                                GlassEnvelope::None,
+                                GlassEnvelope::Quartz,
+                                GlassEnvelope::Borosilicate,
+                                GlassEnvelope::SodaLime,
+                            ] {
+                                ui.selectable_value(
+                                    &mut self.glass_envelope,
+                                    envelope,
+                                    envelope.to_string(),
+                                );
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    let references = library_references();
+                    ComboBox::from_id_source("cb_library_reference")
+                        .selected_text(self.library_reference.clone())
+                        .show_ui(ui, |ui| {
+                            for reference in &references {
+                                ui.selectable_value(
+                                    &mut self.library_reference,
+                                    reference.name.clone(),
+                                    &reference.name,
+                                );
+                            }
+                        });
+                    if ui
+                        .button("Use Library Reference")
+                        .on_hover_text("Standard illuminants, solar spectrum and LED models")
+                        .clicked()
+                    {
+                        if let Some(reference) = references
+                            .into_iter()
+                            .find(|r| r.name == self.library_reference)
+                        {
+                            self.config.reference_config.reference = Some(reference.spectrum);
+                            self.config.reference_config.unit = reference.unit;
+                        }
+                    }
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ComboBox::from_id_source("cb_export_format")
+                        .selected_text(self.config.import_export_config.export_format.to_string())
+                        .show_ui(ui, |ui| {
+                            for format in [
+                                ExportFormat::Csv,
+                                ExportFormat::JcampDx,
+                                ExportFormat::Spc,
+                                ExportFormat::Hdf5,
+                                ExportFormat::SpectralWorkbench,
+                                ExportFormat::Theremino,
+                            ] {
+                                ui.selectable_value(
+                                    &mut self.config.import_export_config.export_format,
+                                    format,
+                                    format.to_string(),
+                                );
+                            }
+                        });
+                    export_clicked = ui.add(Button::new("Export Spectrum")).clicked();
+                    if ui
+                        .button("Copy to Clipboard")
+                        .on_hover_text("Wavelength, R, G, B and sum as tab separated text")
+                        .clicked()
+                    {
+                        ui.output().copied_text = self.spectrum_frame.to_tab_separated(
+                            &self.config.spectrum_calibration,
+                            &self.config.view_config.axis,
+                        );
+                        self.notifications.info("Copied spectrum to clipboard");
+                    }
+                });
+                ui.collapsing("Resampling", |ui| {
+                    let resampling = &mut self.config.import_export_config.resampling;
+                    ComboBox::from_label("Interpolation")
+                        .selected_text(resampling.resampling.to_string())
+                        .show_ui(ui, |ui| {
+                            for method in [Resampling::Off, Resampling::Linear, Resampling::Spline]
+                            {
+                                ui.selectable_value(
+                                    &mut resampling.resampling,
+                                    method,
+                                    method.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Export on a uniform wavelength grid, e.g. for colorimetry software",
+                        );
+                    ui.add_enabled_ui(resampling.resampling != Resampling::Off, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                DragValue::new(&mut resampling.start)
+                                    .clamp_range(200.0..=2000.0)
+                                    .prefix("From: ")
+                                    .suffix(" nm"),
+                            );
+                            ui.add(
+                                DragValue::new(&mut resampling.end)
+                                    .clamp_range(200.0..=2000.0)
+                                    .prefix("To: ")
+                                    .suffix(" nm"),
+                            );
+                            ui.add(
+                                DragValue::new(&mut resampling.step)
+                                    .clamp_range(0.1..=50.0)
+                                    .speed(0.1)
+                                    .prefix("Step: ")
+                                    .suffix(" nm"),
+                            );
+                        });
+                    });
+                });
+                if self.config.import_export_config.export_format == ExportFormat::Csv {
+                    ui.collapsing("CSV Columns", |ui| {
+                        let traces = &mut self.config.import_export_config.export_traces;
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut traces.sum, "Sum");
+                            ui.checkbox(&mut traces.r, "R");
+                            ui.checkbox(&mut traces.g, "G");
+                            ui.checkbox(&mut traces.b, "B");
+                        });
+                        ui.checkbox(&mut traces.detection_limit, "Detection Limit");
+                        ui.checkbox(&mut traces.snapshots, "Snapshots")
+                            .on_hover_text("One column per snapshot, interpolated to the wavelengths of the spectrum");
+                        ui.checkbox(&mut traces.reference, "Reference");
+                    });
+                }
+                if ui
+                    .button("Export Plot Image")
+                    .on_hover_text(
+                        "PNG next to the export path, with the acquisition overlay if shown",
+                    )
+                    .clicked()
+                {
+                    self.plot_image_requested = true;
+                }
+                report_clicked = ui
+                    .button("Export Report")
+                    .on_hover_text(
+                        "HTML file with plot, peaks, calibration and camera settings",
+                    )
+                    .clicked();
+                ui.separator();
+                let logging = self.logger.is_some();
+                ui.add_enabled_ui(!logging, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Log File");
+                        ui.text_edit_singleline(&mut self.config.logging_config.path);
+                    });
+                    ui.add(
+                        TouchSlider::new(
+                            &mut self.config.logging_config.interval,
+                            0.1..=3600.,
+                            touch,
+                        )
+                        .logarithmic(true)
+                        .text("Log Interval [s]"),
+                    );
+                    ComboBox::from_label("Log Mode")
+                        .selected_text(self.config.logging_config.mode.to_string())
+                        .show_ui(ui, |ui| {
+                            for mode in [
+                                LoggingMode::FullSpectrum,
+                                LoggingMode::Bands,
+                                LoggingMode::DerivedValues,
+                            ] {
+                                ui.selectable_value(
+                                    &mut self.config.logging_config.mode,
+                                    mode,
+                                    mode.to_string(),
+                                );
+                            }
+                        });
+                    ComboBox::from_label("Log Format")
+                        .selected_text(self.config.logging_config.format.to_string())
+                        .show_ui(ui, |ui| {
+                            for format in [
+                                LogFormat::Csv,
+                                LogFormat::Binary,
+                                LogFormat::CompressedBinary,
+                                LogFormat::Database,
+                            ] {
+                                ui.selectable_value(
+                                    &mut self.config.logging_config.format,
+                                    format,
+                                    format.to_string(),
+                                );
+                            }
+                        });
+                    if self.config.logging_config.mode == LoggingMode::Bands {
+                        let mut remove_band = None;
+                        for (i, band) in self.config.logging_config.bands.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.add(DragValue::new(&mut band.start).suffix(" nm"));
+                                ui.label("-");
+                                ui.add(DragValue::new(&mut band.end).suffix(" nm"));
+                                if ui.button("Remove").clicked() {
+                                    remove_band = Some(i);
+                                }
+                            });
+                        }
+                        if let Some(i) = remove_band {
+                            self.config.logging_config.bands.remove(i);
+                        }
+                        if ui.button("Add Band").clicked() {
+                            self.config.logging_config.bands.push(WavelengthBand {
+                                start: 500.,
+                                end: 510.,
+                            });
+                        }
+                    }
+                    if self.config.logging_config.mode == LoggingMode::DerivedValues {
+                        ui.label("Logs the values defined in the dashboard");
+                    }
+                    if self.config.logging_config.format == LogFormat::Database {
+                        ui.label("The database stores the full spectrum in every mode");
+                        ui.horizontal(|ui| {
+                            ui.label("Notes");
+                            ui.text_edit_singleline(&mut self.config.logging_config.notes);
+                        });
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let logging_button = ui.button(if logging {
+                        "Stop Logging"
+                    } else {
+                        "Start Logging"
+                    });
+                    if logging_button.clicked() {
+                        if logging {
+                            self.logger = None;
+                        } else {
+                            match SpectrumLogger::new(&self.config.logging_config) {
+                                Ok(logger) => self.logger = Some(logger),
+                                Err(e) => {
+                                    self.last_error = Some(ThreadResult {
+                                        id: ThreadId::Main,
+                                        result: Err(e),
+                                    })
+                                }
+                            }
+                        }
+                    }
+                    if let Some(logger) = self.logger.as_ref() {
+                        ui.label(format!(
+                            "{} rows written to {}",
+                            logger.rows(),
+                            logger.path()
+                        ));
+                    }
+                });
+                match self.conversion.as_ref() {
+                    None => {
+                        if ui
+                            .add_enabled(
+                                !logging
+                                    && self.config.logging_config.format != LogFormat::Database,
+                                Button::new("Convert Log (CSV ↔ Binary)"),
+                            )
+                            .clicked()
+                        {
+                            self.conversion =
+                                Some(ConversionJob::spawn(&self.config.logging_config.path));
+                        }
+                        if ui
+                            .add_enabled(
+                                !logging
+                                    && self.config.logging_config.format != LogFormat::Database,
+                                Button::new("Export Log as HDF5"),
+                            )
+                            .clicked()
+                        {
+                            export_log_hdf5 = Some(self.config.logging_config.path.clone());
+                        }
+                    }
+                    Some(conversion) => {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                ProgressBar::new(conversion.progress())
+                                    .show_percentage()
+                                    .desired_width(150.),
+                            );
+                            if ui.button("Cancel").clicked() {
+                                conversion.cancel();
+                            }
+                        });
+                    }
+                }
+                ui.separator();
+                ui.collapsing("Recording", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("File");
+                        ui.add_enabled(
+                            recording.is_none() && replay_frames.is_none(),
+                            TextEdit::singleline(&mut self.config.recording.path),
+                        );
+                    });
+                    ui.horizontal(|ui| match recording {
+                        None => {
+                            recording_toggled = ui
+                                .button("Record")
+                                .on_hover_text(
+                                    "Writes every raw spectrum with its timestamp, replacing the file",
+                                )
+                                .clicked();
+                        }
+                        Some(frames) => {
+                            recording_toggled = ui.button("Stop Recording").clicked();
+                            ui.label(format!("{} spectra", frames));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            DragValue::new(&mut self.config.recording.speed)
+                                .clamp_range(0.1..=100.0)
+                                .speed(0.1)
+                                .prefix("Speed: ")
+                                .suffix("×"),
+                        );
+                        match replay_frames {
+                            None => {
+                                replay_clicked = ui
+                                    .add_enabled(can_start, Button::new("Replay"))
+                                    .on_hover_text(
+                                        "Processes the recorded spectra instead of the camera",
+                                    )
+                                    .clicked();
+                            }
+                            Some(frames) => {
+                                replay_clicked = ui.button("Stop Replay").clicked();
+                                ui.label(format!("{} spectra", frames));
+                            }
+                        }
+                    });
+                    if ui
+                        .add_enabled(
+                            recording.is_none() && self.conversion.is_none(),
+                            Button::new("Export as HDF5"),
+                        )
+                        .clicked()
+                    {
+                        export_log_hdf5 = Some(self.config.recording.path.clone());
+                    }
+                });
+                ui.collapsing("Remote Streaming", |ui| {
+                    let remote_stream = &mut self.config.remote_stream;
+                    ComboBox::from_label("Resolution")
+                        .selected_text(remote_stream.decimation.to_string())
+                        .show_ui(ui, |ui| {
+                            for decimation in [
+                                RemoteDecimation::Full,
+                                RemoteDecimation::EveryNth(4),
+                                RemoteDecimation::Resample(256),
+                            ] {
+                                let selected = std::mem::discriminant(&remote_stream.decimation)
+                                    == std::mem::discriminant(&decimation);
+                                if ui
+                                    .selectable_label(selected, decimation.to_string())
+                                    .clicked()
+                                    && !selected
+                                {
+                                    remote_stream.decimation = decimation;
+                                }
+                            }
+                        });
+                    match &mut remote_stream.decimation {
+                        RemoteDecimation::Full => {}
+                        RemoteDecimation::EveryNth(n) => {
+                            ui.add(DragValue::new(n).clamp_range(1..=100).prefix("N: "));
+                        }
+                        RemoteDecimation::Resample(points) => {
+                            ui.add(
+                                DragValue::new(points)
+                                    .clamp_range(2..=4096)
+                                    .suffix(" points"),
+                            );
+                        }
+                    }
+                    ui.add(
+                        DragValue::new(&mut remote_stream.max_rate)
+                            .clamp_range(0.0..=100.0)
+                            .speed(0.1)
+                            .prefix("Max. Rate: ")
+                            .suffix(" Hz"),
+                    )
+                    .on_hover_text("0 sends every spectrum");
+                    ui.label("Local processing and exports keep the full resolution");
+                    ui.horizontal(|ui| {
+                        let websocket = &mut self.config.websocket;
+                        websocket_toggled = ui
+                            .checkbox(&mut websocket.active, "WebSocket Server")
+                            .on_hover_text("Pushes every spectrum as JSON to connected clients")
+                            .changed();
+                        ui.add_enabled(
+                            !websocket.active,
+                            DragValue::new(&mut websocket.port).prefix("Port: "),
+                        );
+                        ui.add_enabled_ui(!websocket.active, |ui| {
+                            remote_access_checkbox(
+                                ui,
+                                &mut websocket.address,
+                                "Accept connections from other computers, without authentication",
+                            )
+                        });
+                        if let Some(clients) = websocket_clients {
+                            ui.label(format!("{} clients", clients));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let rest_api = &mut self.config.rest_api;
+                        rest_api_toggled = ui
+                            .checkbox(&mut rest_api.active, "REST API")
+                            .on_hover_text(
+                                "GET /spectrum, POST /zero, /start and /stop for lab automation",
+                            )
+                            .changed();
+                        ui.add_enabled(
+                            !rest_api.active,
+                            DragValue::new(&mut rest_api.port).prefix("Port: "),
+                        );
+                        ui.add_enabled_ui(!rest_api.active, |ui| {
+                            remote_access_checkbox(
+                                ui,
+                                &mut rest_api.address,
+                                "Accept connections from other computers, commands need the token",
+                            );
+                            ui.label("Token:");
+                            ui.add(TextEdit::singleline(&mut rest_api.token).desired_width(120.))
+                                .on_hover_text(
+                                    "Sent as 'Authorization: Bearer <token>' with POST requests, \
+                                     generated when empty",
+                                );
+                        });
+                    });
+                    if dropped_spectra > 0 {
+                        ui.label(format!(
+                            "{} spectra dropped for clients which did not keep up",
+                            dropped_spectra
+                        ))
+                        .on_hover_text(format!(
+                            "Every WebSocket client and the REST API queue up to {} spectra",
+                            SUBSCRIBER_QUEUE
+                        ));
+                    }
+                });
+            });
+        if export_clicked {
+            let format = self.config.import_export_config.export_format;
+            let path = Self::export_path(
+                &self.config.import_export_config,
+                &camera,
+                format.extension(),
+            );
+            let path_string = path.to_string_lossy().to_string();
+            let written = if format == ExportFormat::Hdf5 {
+                self.spectrum_frame.write_to_hdf5(
+                    &path_string,
+                    &self.config,
+                    &self.hdf5_camera_attributes(&camera),
+                )
+            } else {
+                self.spectrum_frame
+                    .write_to_file(&path_string, format, &self.config)
+            };
+            match written {
+                Ok(()) => {
+                    let result = run_post_export_hook(
+                        &self.config.import_export_config.post_export_command,
+                        &path.to_string_lossy(),
+                    );
+                    if result.is_ok() {
+                        self.notifications
+                            .info(format!("Exported {}", path.display()));
+                    }
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result,
+                    });
+                }
+                Err(e) => {
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result: Err(e),
+                    });
+                }
+            }
+        }
+        if websocket_toggled {
+            self.set_websocket_server(self.config.websocket.active);
+        }
+        if rest_api_toggled {
+            self.set_rest_server(self.config.rest_api.active);
+        }
+        if recording_toggled {
+            self.set_recording(recording.is_none());
+        }
+        if let Some(path) = export_log_hdf5 {
+            self.conversion = Some(ConversionJob::spawn_hdf5(&path));
+        }
+        if replay_clicked {
+            if replay_frames.is_some() {
+                self.stop_acquisition();
+            } else {
+                self.start_replay();
+            }
+        }
+        if report_clicked {
+            let path = Self::export_path(&self.config.import_export_config, &camera, "html");
+            let result = self.write_report(&path, &camera).and_then(|_| {
+                run_post_export_hook(
+                    &self.config.import_export_config.post_export_command,
+                    &path.to_string_lossy(),
+                )
+            });
+            if result.is_ok() {
+                self.notifications
+                    .info(format!("Exported {}", path.display()));
+            }
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result,
+            });
+        }
+    }
+
+    /// Camera settings stored with HDF5 exports.
+    fn hdf5_camera_attributes(&self, camera: &str) -> Vec<(&'static str, Hdf5Value)> {
+        let mut attributes = vec![("camera", Hdf5Value::from(camera))];
+        if let Some(format) = self.config.camera_format {
+            attributes.push(("camera_format", format.to_string().into()));
+        }
+        if self.exposure_control().is_some() {
+            attributes.push(("exposure", i64::from(self.current_exposure()).into()));
+        }
+        attributes
+    }
+
+    /// Write a measurement report of the current spectrum to `path`.
+    fn write_report(&self, path: &Path, camera: &str) -> Result<(), String> {
+        let spectrum = self.spectrum_frame.get_spectrum_channel(3, &self.config);
+        let peaks = self.spectrum_frame.fit_peaks(&self.config);
+        let derived_values: Vec<_> = self
+            .config
+            .derived_values(&spectrum)
+            .into_iter()
+            .filter(|(_, value)| !value.is_nan())
+            .collect();
+        let report = Report {
+            title: &self.config.import_export_config.sample,
+            time: Local::now(),
+            spectrum: &spectrum,
+            peaks: &peaks,
+            derived_values: &derived_values,
+            calibration: &self.config.spectrum_calibration,
+            camera,
+            camera_format: self.config.camera_format.map(|f| f.to_string()),
+            window: self.config.image_config.window,
+            camera_controls: &self.camera_controls,
+            averaged_frames: self.spectrum_frame.averaged_frames(),
+        };
+        let mut file = File::create(path).map_err(|e| e.to_string())?;
+        write_html(&mut file, &report).map_err(|e| e.to_string())
+    }
+
+    fn draw_snapshot_window(&mut self, ctx: &Context) {
+        let mut take_snapshot = false;
+        let camera = self.camera_name();
+        egui::Window::new("Snapshots")
+            .open(&mut self.config.view_config.show_snapshot_window)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.snapshot_name);
+                    take_snapshot = ui.button("Take Snapshot").clicked();
+                });
+                ui.separator();
+
+                let mut remove_snapshot = None;
+                let mut save_snapshot = None;
+                for (i, snapshot) in self.config.snapshots.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.color_edit_button_srgba(&mut snapshot.color);
+                        ui.checkbox(&mut snapshot.visible, &snapshot.name);
+                        let fingerprint = snapshot.fingerprint();
+                        ui.label(RichText::new(fingerprint.short()).monospace())
+                            .on_hover_text(format!("Measurement ID {}", fingerprint));
+                        if ui.button("Save CSV").clicked() {
+                            save_snapshot = Some(i);
+                        }
+                        if ui.button("Delete").clicked() {
+                            remove_snapshot = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = save_snapshot {
+                    let path = Self::export_path(&self.config.import_export_config, &camera, "csv");
+                    let result = csv::Writer::from_path(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|mut writer| {
+                            for p in &self.config.snapshots[i].spectrum {
+                                writer.serialize(p).map_err(|e| e.to_string())?;
+                            }
+                            writer.flush().map_err(|e| e.to_string())
+                        })
+                        .and_then(|_| {
+                            run_post_export_hook(
+                                &self.config.import_export_config.post_export_command,
+                                &path.to_string_lossy(),
+                            )
+                        });
+                    if result.is_ok() {
+                        self.notifications
+                            .info(format!("Exported {}", path.display()));
+                    }
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result,
+                    });
+                }
+                if let Some(i) = remove_snapshot {
+                    self.config.snapshots.remove(i);
+                }
+
+                ui.separator();
+                if ui
+                    .add_enabled(!self.config.snapshots.is_empty(), Button::new("Clear All"))
+                    .clicked()
+                {
+                    self.config.snapshots.clear();
+                }
+            });
+
+        if take_snapshot {
+            self.take_snapshot();
+        }
+    }
+
+    fn draw_peak_table_window(&mut self, ctx: &Context) {
+        let axis = self.config.view_config.axis;
+        let mut fits: Vec<PeakFit> = if self.config.view_config.show_peak_table_window {
+            self.spectrum_frame
+                .fit_peaks(&self.config)
+                .into_iter()
+                .map(|fit| PeakFit {
+                    center: axis.convert(fit.center),
+                    fwhm: axis.convert_width(fit.center, fit.fwhm),
+                    ..fit
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let (column, ascending) = self.peak_table_sort;
+        fits.sort_by(|a, b| {
+            let ordering = match column {
+                PeakTableColumn::Center => a.center.total_cmp(&b.center),
+                PeakTableColumn::Fwhm => a.fwhm.total_cmp(&b.fwhm),
+                PeakTableColumn::Amplitude => a.amplitude.total_cmp(&b.amplitude),
+            };
+            if ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+
+        let peak_shape = self.config.view_config.peak_shape;
+        let peak_table_sort = &mut self.peak_table_sort;
+        egui::Window::new("Peak Table")
+            .open(&mut self.config.view_config.show_peak_table_window)
+            .show(ctx, |ui| {
+                ui.label(format!("{} fit of the detected peaks", peak_shape));
+                egui::Grid::new("peak_table").striped(true).show(ui, |ui| {
+                    for header in [
+                        PeakTableColumn::Center,
+                        PeakTableColumn::Fwhm,
+                        PeakTableColumn::Amplitude,
+                    ] {
+                        let mut text = match header {
+                            PeakTableColumn::Amplitude => header.to_string(),
+                            _ => format!("{} [{}]", header, axis.unit.symbol()),
+                        };
+                        if header == peak_table_sort.0 {
+                            text.push_str(if peak_table_sort.1 { " ⏶" } else { " ⏷" });
+                        }
+                        if ui.button(text).clicked() {
+                            *peak_table_sort = if header == peak_table_sort.0 {
+                                (header, !peak_table_sort.1)
+                            } else {
+                                (header, true)
+                            };
+                        }
+                    }
+                    ui.end_row();
+                    for fit in &fits {
+                        ui.label(format!("{:.*}", axis.unit.precision() + 1, fit.center));
+                        ui.label(format!("{:.*}", axis.unit.precision() + 1, fit.fwhm));
+                        ui.label(format!("{:.4}", fit.amplitude));
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    fn draw_identification_window(&mut self, ctx: &Context) {
+        let candidates = if self.config.view_config.show_identification_window {
+            let spectrum = self.spectrum_frame.get_spectrum_channel(3, &self.config);
+            let peaks: Vec<f32> = self
+                .spectrum_frame
+                .fit_peaks(&self.config)
+                .iter()
+                .map(|fit| fit.center)
+                .collect();
+            match (spectrum.first(), spectrum.last()) {
+                (Some(first), Some(last)) => identify(
+                    &peaks,
+                    first.wavelength.min(last.wavelength)..=first.wavelength.max(last.wavelength),
+                    self.config.view_config.line_tolerance,
+                ),
+                _ => Vec::new(),
+            }
+        } else {
+            Vec::new()
+        };
+        let axis = self.config.view_config.axis;
+        let touch = self.config.view_config.touch_mode;
+        egui::Window::new("Line Identification")
+            .open(&mut self.config.view_config.show_identification_window)
+            .show(ctx, |ui| {
+                ui.add(
+                    TouchSlider::new(
+                        &mut self.config.view_config.line_tolerance,
+                        0.1..=10.,
+                        touch,
+                    )
+                    .text("Match Tolerance [nm]"),
+                )
+                .on_hover_text("Largest distance of a detected peak from an emission line");
+                ui.separator();
+                if candidates.is_empty() {
+                    ui.label("No emission lines match the detected peaks");
+                    return;
+                }
+                egui::Grid::new("line_identification")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Source");
+                        ui.label("Score");
+                        ui.label("Lines");
+                        ui.label(format!("Matched [{}]", axis.unit.symbol()));
+                        ui.end_row();
+                        for candidate in &candidates {
+                            ui.label(candidate.source);
+                            ui.label(format!("{:.0} %", candidate.score * 100.));
+                            ui.label(format!(
+                                "{} of {}",
+                                candidate.matches.len(),
+                                candidate.lines_in_range
+                            ));
+                            ui.label(
+                                candidate
+                                    .matches
+                                    .iter()
+                                    .map(|m| axis.format(m.line))
+                                    .collect::<Vec<_>>()
+                                    .join(", "),
+                            )
+                            .on_hover_text(
+                                candidate
+                                    .matches
+                                    .iter()
+                                    .map(|m| {
+                                        format!(
+                                            "{} at {}",
+                                            axis.format(m.line),
+                                            axis.format(m.peak)
+                                        )
+                                    })
+                                    .collect::<Vec<_>>()
+                                    .join("\n"),
+                            );
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    /// Peaks of the sum channel of the live spectrum or a snapshot.
+    fn peaks_of(&self, source: PeakSource) -> Option<Vec<SpectrumPoint>> {
+        let spectrum = match source {
+            PeakSource::Live => self.spectrum_frame.get_spectrum_channel(3, &self.config),
+            PeakSource::Snapshot(i) => self.config.snapshots.get(i)?.spectrum.clone(),
+        };
+        Some(
+            find_peaks_dips(
+                &spectrum,
+                true,
+                self.config.view_config.peak_find_window(),
+                self.config.view_config.peaks_dips_unique_window,
+            )
+            .into_iter()
+            .map(|i| spectrum[i])
+            .collect(),
+        )
+    }
+
+    fn draw_peak_diff_window(&mut self, ctx: &Context) {
+        let (a, b) = self.peak_diff_sources;
+        let rows = if self.config.view_config.show_peak_diff_window {
+            self.peaks_of(a)
+                .zip(self.peaks_of(b))
+                .map(|(a, b)| diff_peaks(&a, &b, self.config.view_config.peak_match_tolerance))
+        } else {
+            None
+        };
+        let axis = self.config.view_config.axis;
+        let touch = self.config.view_config.touch_mode;
+        let sources: Vec<PeakSource> = std::iter::once(PeakSource::Live)
+            .chain((0..self.config.snapshots.len()).map(PeakSource::Snapshot))
+            .collect();
+        let snapshots = &self.config.snapshots;
+        let peak_diff_sources = &mut self.peak_diff_sources;
+        let mut export_clicked = false;
+        egui::Window::new("Peak Diff")
+            .open(&mut self.config.view_config.show_peak_diff_window)
+            .show(ctx, |ui| {
+                for (label, source) in [
+                    ("A", &mut peak_diff_sources.0),
+                    ("B", &mut peak_diff_sources.1),
+                ] {
+                    ComboBox::from_label(label)
+                        .selected_text(source.label(snapshots))
+                        .show_ui(ui, |ui| {
+                            for s in &sources {
+                                ui.selectable_value(source, *s, s.label(snapshots));
+                            }
+                        });
+                }
+                ui.add(
+                    TouchSlider::new(
+                        &mut self.config.view_config.peak_match_tolerance,
+                        0.5..=50.,
+                        touch,
+                    )
+                    .text("Match Tolerance [nm]"),
+                );
+                ui.separator();
+                let rows = match &rows {
+                    Some(rows) => rows,
+                    None => {
+                        ui.label("Select two spectra");
+                        return;
+                    }
+                };
+                let format = |w: Option<f32>| w.map_or("-".to_string(), |w| axis.format(w));
+                egui::Grid::new("peak_diff").striped(true).show(ui, |ui| {
+                    ui.label(format!("A [{}]", axis.unit.symbol()));
+                    ui.label(format!("B [{}]", axis.unit.symbol()));
+                    ui.label(format!("Shift [{}]", axis.unit.symbol()));
+                    ui.label("Ratio B/A");
+                    ui.end_row();
+                    for row in rows {
+                        ui.label(format(row.wavelength_a));
+                        ui.label(format(row.wavelength_b));
+                        match (row.wavelength_a, row.wavelength_b, row.ratio) {
+                            (Some(a), Some(b), Some(ratio)) => {
+                                ui.label(format!(
+                                    "{:+.*}",
+                                    axis.unit.precision(),
+                                    axis.convert(b) - axis.convert(a)
+                                ));
+                                ui.label(format!("{:.3}", ratio));
+                            }
+                            _ => {
+                                let only = if row.wavelength_a.is_some() { "A" } else { "B" };
+                                ui.label(RichText::new(format!("only in {}", only)).weak());
+                                ui.label("");
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+                export_clicked = ui
+                    .add_enabled(!rows.is_empty(), Button::new("Export CSV"))
+                    .on_hover_text(
+                        "Write to the directory and filename template of the Import/Export window",
+                    )
+                    .clicked();
+            });
+
+        if export_clicked {
+            let path = Self::export_path(
+                &self.config.import_export_config,
+                &self.camera_name(),
+                "csv",
+            );
+            let result = csv::Writer::from_path(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|mut writer| {
+                    for row in rows.iter().flatten() {
+                        writer.serialize(row).map_err(|e| e.to_string())?;
+                    }
+                    writer.flush().map_err(|e| e.to_string())
+                })
+                .and_then(|_| {
+                    run_post_export_hook(
+                        &self.config.import_export_config.post_export_command,
+                        &path.to_string_lossy(),
+                    )
+                });
+            if result.is_ok() {
+                self.notifications
+                    .info(format!("Exported {}", path.display()));
+            }
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result,
+            });
+        }
+    }
+
+    fn draw_dashboard_window(&mut self, ctx: &Context) {
+        let spectrum = if self.config.view_config.show_dashboard_window {
+            self.spectrum_frame.get_spectrum_channel(3, &self.config)
+        } else {
+            Vec::new()
+        };
+        let derived_values = &mut self.config.derived_values;
+        let script = &mut self.config.script;
+        egui::Window::new("Dashboard")
+            .open(&mut self.config.view_config.show_dashboard_window)
+            .show(ctx, |ui| {
+                let mut remove_value = None;
+                for (i, derived) in derived_values.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(&derived.name).strong());
+                        match derived.eval(&spectrum) {
+                            Ok(value) => ui.label(RichText::new(format!("{:.4}", value)).heading()),
+                            Err(e) => ui.label(RichText::new(e).color(Color32::RED)),
+                        };
+                        if ui.button("Remove").clicked() {
+                            remove_value = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove_value {
+                    derived_values.remove(i);
+                }
+                match script.run(&spectrum) {
+                    Ok(values) => {
+                        for (name, value) in values {
+                            ui.horizontal(|ui| {
+                                ui.label(RichText::new(name).strong());
+                                ui.label(RichText::new(format!("{:.4}", value)).heading());
+                            });
+                        }
+                    }
+                    Err(e) => {
+                        ui.label(RichText::new(format!("Script: {}", e)).color(Color32::RED));
+                    }
+                }
+                ui.separator();
+                ui.collapsing("Edit", |ui| {
+                    for derived in derived_values.iter_mut() {
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut derived.name);
+                            ui.text_edit_singleline(&mut derived.expression);
+                        });
+                    }
+                    if ui.button("Add Value").clicked() {
+                        derived_values.push(DerivedValue::new(
+                            format!("Value {}", derived_values.len() + 1),
+                            "I(650) / I(450)".to_string(),
+                        ));
+                    }
+                    ui.label(
+                        "Functions: I(nm), integral(nm, nm), mean(nm, nm), max(nm, nm), \
+                         min(nm, nm), peak(nm, nm), sqrt, ln, log10, abs, exp",
+                    );
+                });
+                ui.collapsing("Script", |ui| {
+                    ui.add(
+                        TextEdit::multiline(&mut script.source)
+                            .code_editor()
+                            .desired_rows(6)
+                            .hint_text("#{ ratio: intensity[200] / intensity[100] }"),
+                    );
+                    ui.label(
+                        "Rhai script getting the arrays wavelength and intensity, returning a \
+                         map of the values to show and export next to the derived values",
+                    );
+                });
+            });
+    }
+
+    fn draw_colorimetry_window(&mut self, ctx: &Context) {
+        let (result, rendering) = if self.config.view_config.show_colorimetry_window {
+            let spectrum = self.spectrum_frame.get_spectrum_channel(3, &self.config);
+            (colorimetry(&spectrum), color_rendering(&spectrum))
+        } else {
+            (None, None)
+        };
+        let calibrated = self.config.spectrum_calibration.scaling.is_some();
+        egui::Window::new("Colorimetry")
+            .open(&mut self.config.view_config.show_colorimetry_window)
+            .show(ctx, |ui| {
+                if !calibrated {
+                    ui.label(
+                        RichText::new("⚠ Spectrum is not intensity calibrated")
+                            .color(Color32::YELLOW),
+                    );
+                }
+                let c = match result {
+                    Some(c) => c,
+                    None => {
+                        ui.label("No visible light");
+                        return;
+                    }
+                };
+                egui::Grid::new("colorimetry").show(ui, |ui| {
+                    ui.label("x, y");
+                    ui.label(format!("{:.4}, {:.4}", c.x, c.y));
+                    ui.end_row();
+                    ui.label("u', v'");
+                    ui.label(format!("{:.4}, {:.4}", c.u_prime, c.v_prime));
+                    ui.end_row();
+                    ui.label("CCT");
+                    ui.label(c.cct.map_or("-".to_string(), |cct| format!("{:.0} K", cct)));
+                    ui.end_row();
+                    ui.label("Duv");
+                    ui.label(c.duv.map_or("-".to_string(), |duv| format!("{:.4}", duv)));
+                    ui.end_row();
+                });
+                if let Some(rendering) = rendering {
+                    ui.separator();
+                    if c.duv.is_some_and(|duv| duv.abs() > MAX_DUV) {
+                        ui.label(
+                            RichText::new("⚠ Too far from the Planckian locus for CRI")
+                                .color(Color32::YELLOW),
+                        );
+                    }
+                    // Without intensity calibration the CRI reflects the camera, not the source
+                    ui.add_enabled_ui(calibrated, |ui| {
+                        egui::Grid::new("color_rendering").show(ui, |ui| {
+                            ui.label("Ra");
+                            ui.label(format!("{:.1}", rendering.ra));
+                            ui.end_row();
+                            for (i, r) in rendering.special.iter().enumerate() {
+                                ui.label(format!("R{}", i + 1));
+                                ui.label(format!("{:.1}", r));
+                                ui.end_row();
+                            }
+                        });
+                    });
+                }
+                Plot::new("chromaticity")
+                    .data_aspect(1.)
+                    .height(250.)
+                    .show(ui, |plot_ui| {
+                        let mut locus = spectral_locus();
+                        // Close the horseshoe with the line of purples
+                        locus.push(locus[0]);
+                        plot_ui.line(
+                            Line::new(Values::from_values_iter(
+                                locus
+                                    .into_iter()
+                                    .map(|(x, y)| Value::new(x as f64, y as f64)),
+                            ))
+                            .color(Color32::GRAY),
+                        );
+                        plot_ui.points(
+                            Points::new(Values::from_values(vec![Value::new(c.x, c.y)]))
+                                .radius(4.)
+                                .color(Color32::WHITE),
+                        );
+                    });
+            });
+    }
+
+    fn draw_photobiology_window(&mut self, ctx: &Context) {
+        let result = if self.config.view_config.show_photobiology_window {
+            photobiology(&self.spectrum_frame.get_spectrum_channel(3, &self.config))
+        } else {
+            None
+        };
+        let unit = self.config.spectrum_calibration.intensity_unit();
+        egui::Window::new("Photobiology")
+            .open(&mut self.config.view_config.show_photobiology_window)
+            .show(ctx, |ui| {
+                let p = match result {
+                    Some(p) => p,
+                    None => {
+                        ui.label("No light from 400 to 750 nm");
+                        return;
+                    }
+                };
+                if unit == IntensityUnit::SpectralIrradiance {
+                    ui.label(RichText::new(format!("PPFD: {:.1} µmol·m⁻²·s⁻¹", p.ppfd)).heading());
+                } else {
+                    ui.label(
+                        RichText::new("⚠ Spectrum is not calibrated in spectral irradiance")
+                            .color(Color32::YELLOW),
+                    );
+                    ui.label(format!("Photon flux 400-700 nm: {:.3e} a.u.", p.ppfd));
+                }
+                ui.separator();
+                egui::Grid::new("photon_bands").show(ui, |ui| {
+                    for (band, fraction) in PhotonBand::ALL.iter().zip(p.fractions) {
+                        ui.label(format!("{} ({})", band, band.band()));
+                        ui.add(
+                            ProgressBar::new(fraction)
+                                .text(format!("{:.1} %", fraction * 100.))
+                                .desired_width(150.),
+                        );
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+
+    fn draw_buffer_window(&mut self, ctx: &Context) {
+        let frames = self.spectrum_frame.buffered_frames();
+        let mut dropped = vec![];
+        egui::Window::new("Averaging Buffer")
+            .open(&mut self.config.view_config.show_buffer_window)
+            .show(ctx, |ui| {
+                if frames.is_empty() {
+                    ui.label("No frames in the buffer");
+                    return;
+                }
+                let saturated: Vec<u64> = frames
+                    .iter()
+                    .filter(|f| f.saturated())
+                    .map(|f| f.id)
+                    .collect();
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} frames", frames.len()));
+                    if ui
+                        .add_enabled(!saturated.is_empty(), Button::new("Drop Saturated"))
+                        .clicked()
+                    {
+                        dropped.extend(&saturated);
+                    }
+                });
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(300.)
+                    .show(ui, |ui| {
+                        egui::Grid::new("buffered_frames")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("Age [s]");
+                                ui.strong("Max [%]");
+                                ui.strong("Saturated");
+                                ui.strong("Rejected");
+                                ui.end_row();
+                                for frame in frames {
+                                    ui.label(format!(
+                                        "{:.1}",
+                                        frame.acquired.elapsed().as_secs_f32()
+                                    ));
+                                    ui.add(
+                                        ProgressBar::new(frame.max.clamp(0., 1.))
+                                            .text(format!("{:.1}", frame.max * 100.))
+                                            .desired_width(100.),
+                                    );
+                                    if frame.saturated() {
+                                        ui.label(RichText::new("⚠").color(Color32::RED));
+                                    } else {
+                                        ui.label("");
+                                    }
+                                    ui.label(if frame.rejected { "✖" } else { "" })
+                                        .on_hover_text("Outlier left out of the average");
+                                    if ui.button("Drop").clicked() {
+                                        dropped.push(frame.id);
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                    });
+            });
+        for id in dropped {
+            self.processor_tx
+                .send(ProcessorEvent::DropFrame(id))
+                .unwrap();
+        }
+    }
+
+    fn draw_linearity_window(&mut self, ctx: &Context) {
+        let exposure_available = self.exposure_control().is_some();
+        let progress = self.linearity_sweep.as_ref().map(|sweep| sweep.progress());
+        let mut start = false;
+        let mut cancel = false;
+        let mut apply = false;
+        egui::Window::new("Linearity")
+            .open(&mut self.config.view_config.show_linearity_window)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Sweeps the exposure over a steady broadband source until the brightest \
+                     column of the window saturates, and fits the sensor response to it.",
+                );
+                match progress {
+                    Some((index, count)) => {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                ProgressBar::new(index as f32 / count as f32)
+                                    .text(format!("Exposure {} of {}", index + 1, count))
+                                    .desired_width(150.),
+                            );
+                            cancel = ui.button("Cancel").clicked();
+                        });
+                    }
+                    None => {
+                        start = ui
+                            .add_enabled(exposure_available, Button::new("Start Sweep"))
+                            .on_disabled_hover_text("Needs a camera with an exposure control")
+                            .clicked();
+                    }
+                }
+                let fit = match &self.linearity_fit {
+                    None => return,
+                    Some(fit) => fit,
+                };
+                ui.separator();
+                ui.label(format!("Fitted gamma {:.2}", fit.gamma));
+                egui::Grid::new("linearity_lut")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label("Value");
+                        ui.label("Linear");
+                        ui.end_row();
+                        for point in &fit.lut {
+                            ui.label(format!("{:.3}", point.value));
+                            ui.label(format!("{:.3}", point.linear));
+                            ui.end_row();
+                        }
+                    });
+                apply = ui
+                    .button("Use as Linearization")
+                    .on_hover_text("Set the curve as custom linearization of the calibration")
+                    .clicked();
+            });
+
+        if start {
+            self.start_linearity_sweep();
+        }
+        if cancel {
+            self.finish_linearity_sweep(false);
+        }
+        if apply {
+            if let Some(fit) = &self.linearity_fit {
+                self.config.spectrum_calibration.linearize = Linearize::Custom(fit.lut.clone());
+                self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+            }
+        }
+    }
+
+    fn draw_device_control_window(&mut self, ctx: &Context) {
+        let touch = self.config.view_config.touch_mode;
+        let connected = self.device.is_some();
+        let sequence = self
+            .device_sequence
+            .as_ref()
+            .map(|s| (s.measurement(), s.is_measuring()));
+        let mut connect = None;
+        let mut refresh = false;
+        let mut actions = vec![];
+        egui::Window::new("Device Control")
+            .open(&mut self.config.view_config.show_device_control_window)
+            .show(ctx, |ui| {
+                let device_control = &mut self.config.device_control;
+                ui.add_enabled_ui(!connected, |ui| {
+                    ui.horizontal(|ui| {
+                        ComboBox::from_label("Serial Port")
+                            .selected_text(device_control.port.clone())
+                            .show_ui(ui, |ui| {
+                                for port in &self.serial_ports {
+                                    ui.selectable_value(
+                                        &mut device_control.port,
+                                        port.clone(),
+                                        port,
+                                    );
+                                }
+                            });
+                        refresh = ui.button("Refresh").clicked();
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            DragValue::new(&mut device_control.baud_rate)
+                                .clamp_range(300..=921600)
+                                .speed(100),
+                        );
+                        ui.label("Baud Rate");
+                    });
+                });
+                if connected {
+                    if ui.button("Disconnect").clicked() {
+                        connect = Some(false);
+                    }
+                } else if ui
+                    .add_enabled(!device_control.port.is_empty(), Button::new("Connect"))
+                    .clicked()
+                {
+                    connect = Some(true);
+                }
+
+                ui.separator();
+                egui::Grid::new("device_commands").show(ui, |ui| {
+                    for (action, command) in [
+                        (DeviceAction::LampOn, &mut device_control.lamp_on),
+                        (DeviceAction::LampOff, &mut device_control.lamp_off),
+                        (DeviceAction::ShutterOpen, &mut device_control.shutter_open),
+                        (
+                            DeviceAction::ShutterClose,
+                            &mut device_control.shutter_close,
+                        ),
+                    ] {
+                        ui.label(action.to_string());
+                        ui.text_edit_singleline(command).on_hover_text(
+                            "Sent as is, with the escapes \\n, \\r, \\t, \\\\ and \\xNN \
+                             for a byte in hex",
+                        );
+                        if ui.add_enabled(connected, Button::new("Send")).clicked() {
+                            actions.push(action);
+                        }
+                        ui.end_row();
+                    }
+                });
+
+                ui.separator();
+                ui.add(
+                    TouchSlider::new(&mut device_control.settle_time, 0.0..=60., touch)
+                        .text("Settle Time [s]"),
+                )
+                .on_hover_text("Wait after switching, e.g. for the lamp to warm up");
+                ui.checkbox(
+                    &mut device_control.switch_for_dark,
+                    "Light Off for Darks and Zero References",
+                );
+                ui.checkbox(
+                    &mut device_control.switch_for_lamp_check,
+                    "Light On for Scheduled Lamp Checks",
+                )
+                .on_hover_text("For a calibration lamp on the port, instead of asking for it");
+                ui.checkbox(
+                    &mut device_control.switch_for_logging,
+                    "Light On for Logged Spectra Only",
+                )
+                .on_hover_text("Switched on a settle time and a full averaging buffer before");
+                if let Some((measurement, measuring)) = sequence {
+                    ui.label(format!(
+                        "{}: {}",
+                        measurement,
+                        if measuring { "measuring" } else { "switching" }
+                    ));
+                }
+            });
+
+        if refresh {
+            self.serial_ports = available_ports();
+        }
+        match connect {
+            Some(true) => self.device = Some(SerialDevice::open(&self.config.device_control)),
+            Some(false) => {
+                self.device = None;
+                self.device_sequence = None;
+            }
+            None => {}
+        }
+        for action in actions {
+            if let Some(device) = &self.device {
+                if let Err(e) = device.send(action, &self.config.device_control) {
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result: Err(e),
+                    });
+                }
+            }
+        }
+    }
+
+    fn draw_filter_wheel_window(&mut self, ctx: &Context) {
+        let touch = self.config.view_config.touch_mode;
+        let progress = self
+            .filter_wheel
+            .as_ref()
+            .map(FilterWheelSequence::progress);
+        let can_start = self.device.is_some() && !self.device_busy();
+        let mut start = false;
+        let mut cancel = false;
+        egui::Window::new("Filter Wheel")
+            .open(&mut self.config.view_config.show_filter_wheel_window)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Moves a filter wheel on the serial port of the device control through its \
+                     positions and exports an averaged spectrum per position.",
+                );
+                let filter_wheel = &mut self.config.filter_wheel;
+                ui.add_enabled_ui(progress.is_none(), |ui| {
+                    let mut removed = None;
+                    egui::Grid::new("filter_wheel_positions").show(ui, |ui| {
+                        ui.label("Label");
+                        ui.label("Command");
+                        ui.end_row();
+                        for (i, position) in filter_wheel.positions.iter_mut().enumerate() {
+                            ui.text_edit_singleline(&mut position.label);
+                            ui.text_edit_singleline(&mut position.command)
+                                .on_hover_text(
+                                    "Sent as is, with the escapes \\n, \\r, \\t, \\\\ and \\xNN \
+                                     for a byte in hex",
+                                );
+                            if ui.button("Remove").clicked() {
+                                removed = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    if let Some(i) = removed {
+                        filter_wheel.positions.remove(i);
+                    }
+                    if ui.button("Add Position").clicked() {
+                        let n = filter_wheel.positions.len() + 1;
+                        filter_wheel.positions.push(FilterPosition {
+                            label: format!("Position {}", n),
+                            command: String::new(),
+                        });
+                    }
+                    ui.separator();
+                    ui.add(
+                        TouchSlider::new(&mut filter_wheel.settle_time, 0.0..=30., touch)
+                            .text("Settle Time [s]"),
+                    )
+                    .on_hover_text("Wait after moving, before the averaging buffer refills");
+                });
+                match progress {
+                    Some((position, positions)) => {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                ProgressBar::new(position as f32 / positions.max(1) as f32)
+                                    .text(format!("{} / {}", position, positions)),
+                            );
+                            cancel = ui.button("Cancel").clicked();
+                        });
+                    }
+                    None => {
+                        start = ui
+                            .add_enabled(
+                                can_start && !filter_wheel.positions.is_empty(),
+                                Button::new("Start"),
+                            )
+                            .on_hover_text(
+                                "Needs the device control connected and no switched measurement \
+                                 running, exports to the path of the export settings",
+                            )
+                            .clicked();
+                    }
+                }
+            });
+
+        if start && !self.device_busy() {
+            self.filter_set.clear();
+            self.filter_wheel = Some(FilterWheelSequence::new(
+                self.config.filter_wheel.positions.len(),
+                Duration::from_secs_f32(self.config.filter_wheel.settle_time.max(0.)),
+            ));
+        }
+        if cancel {
+            self.filter_wheel = None;
+            self.filter_set.clear();
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn draw_gpio_window(&mut self, ctx: &Context) {
+        let armed = self.trigger.is_some();
+        let acquiring = self.single_shot_since.is_some();
+        let mut arm = None;
+        let mut single_shot = false;
+        egui::Window::new("GPIO Trigger")
+            .open(&mut self.config.view_config.show_gpio_window)
+            .show(ctx, |ui| {
+                let gpio = &mut self.config.gpio;
+                ui.add_enabled_ui(!armed, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut gpio.chip);
+                        ui.label("Chip");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(DragValue::new(&mut gpio.trigger_pin).clamp_range(0..=1023))
+                            .on_hover_text(
+                                "Line offset on the chip, e.g. 17 for GPIO 17 on a Raspberry Pi",
+                            );
+                        ui.label("Trigger Pin");
+                    });
+                    ComboBox::from_label("Trigger Edge")
+                        .selected_text(gpio.trigger_edge.to_string())
+                        .show_ui(ui, |ui| {
+                            for edge in [TriggerEdge::Rising, TriggerEdge::Falling] {
+                                ui.selectable_value(&mut gpio.trigger_edge, edge, edge.to_string());
+                            }
+                        });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut gpio.busy_output, "Busy Output");
+                        ui.add_enabled(
+                            gpio.busy_output,
+                            DragValue::new(&mut gpio.busy_pin).clamp_range(0..=1023),
+                        )
+                        .on_hover_text("High from the trigger until the spectrum is acquired");
+                    });
+                });
+                let mut checked = armed;
+                if ui.checkbox(&mut checked, "Armed").changed() {
+                    arm = Some(checked);
+                }
+                ui.horizontal(|ui| {
+                    single_shot = ui
+                        .add_enabled(!acquiring, Button::new("Single Shot"))
+                        .on_hover_text("Acquire one averaged spectrum as on a trigger")
+                        .clicked();
+                    if acquiring {
+                        ui.label("Acquiring");
+                    }
+                });
+            });
+
+        if let Some(armed) = arm {
+            self.set_trigger(armed);
+        }
+        if single_shot {
+            self.start_single_shot();
+        }
+    }
+
+    fn draw_lock_in_window(&mut self, ctx: &Context) {
+        let touch = self.config.view_config.touch_mode;
+        let active = self.lock_in_active;
+        let source_on = self.source_toggling.as_ref().map(SourceToggling::is_on);
+        let mut set_active = None;
+        egui::Window::new("Lock-In")
+            .open(&mut self.config.view_config.show_lock_in_window)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Demodulates the spectra so that only the light of a modulated source \
+                     contributes and constant ambient light cancels out.",
+                );
+                ui.add_enabled_ui(!active, |ui| {
+                    let lock_in = &mut self.config.lock_in;
+                    ComboBox::from_label("Reference")
+                        .selected_text(lock_in.reference.to_string())
+                        .show_ui(ui, |ui| {
+                            for reference in [LockInReference::Toggled, LockInReference::Modulation]
+                            {
+                                ui.selectable_value(
+                                    &mut lock_in.reference,
+                                    reference,
+                                    reference.to_string(),
+                                );
+                            }
+                        });
+                    match lock_in.reference {
+                        LockInReference::Toggled => {
+                            ui.horizontal(|ui| {
+                                ui.text_edit_singleline(&mut lock_in.source_on_command)
+                                    .on_hover_text(
+                                        "e.g. a script writing to a serial port or a GPIO",
+                                    );
+                                ui.label("Source On Command");
+                            });
+                            ui.horizontal(|ui| {
+                                ui.text_edit_singleline(&mut lock_in.source_off_command);
+                                ui.label("Source Off Command");
+                            });
+                            ui.add(
+                                TouchSlider::new(&mut lock_in.frames_per_state, 1..=100, touch)
+                                    .text("Frames Per State"),
+                            );
+                        }
+                        LockInReference::Modulation => {
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    DragValue::new(&mut lock_in.frequency)
+                                        .clamp_range(0.1..=100.)
+                                        .speed(0.1)
+                                        .suffix(" Hz"),
+                                );
+                                ui.label("Frequency");
+                            });
+                            ui.add(
+                                TouchSlider::new(&mut lock_in.periods, 1..=1000, touch)
+                                    .logarithmic(true)
+                                    .text("Periods"),
+                            );
+                            ui.label(
+                                RichText::new(
+                                    "The frame rate has to be at least four times the \
+                                     frequency, with exposures short against its period",
+                                )
+                                .weak(),
+                            );
+                        }
+                    }
+                });
+                let mut checked = active;
+                if ui.checkbox(&mut checked, "Active").changed() {
+                    set_active = Some(checked);
+                }
+                if let Some(on) = source_on {
+                    ui.label(format!("Source {}", if on { "on" } else { "off" }));
+                }
+            });
+
+        if let Some(active) = set_active {
+            self.set_lock_in(active);
+        }
+    }
+
+    fn draw_lamp_check_window(&mut self, ctx: &Context) {
+        let mut measure = false;
+        egui::Window::new("Lamp Check")
+            .open(&mut self.config.view_config.show_lamp_check_window)
+            .show(ctx, |ui| {
+                let lamp_check = &mut self.config.lamp_check;
+                if self.lamp_check_pending {
+                    ui.label(
+                        RichText::new("⚠ Check due, switch on the calibration lamp")
+                            .color(Color32::YELLOW),
+                    );
+                    ui.horizontal(|ui| {
+                        measure = ui.button("Measure").clicked();
+                        if ui.button("Skip").clicked() {
+                            self.lamp_check_pending = false;
+                            lamp_check.last_check = Some(Local::now().timestamp());
+                        }
+                    });
+                    ui.separator();
+                }
+                ui.checkbox(&mut lamp_check.active, "Scheduled");
+                ui.horizontal(|ui| {
+                    let input = ui
+                        .text_edit_singleline(&mut self.lamp_check_times_input)
+                        .on_hover_text("Times of day like 08:00, separated by commas");
+                    ui.label("Times");
+                    match parse_times(&self.lamp_check_times_input) {
+                        Some(times) if input.changed() => lamp_check.times = times,
+                        Some(_) => {}
+                        None => {
+                            ui.colored_label(Color32::RED, "Invalid times");
+                        }
+                    }
+                });
+                ui.checkbox(&mut lamp_check.prompt, "Ask for the Lamp")
+                    .on_hover_text("Measure right away if the lamp is switched on by a timer");
+                ui.horizontal(|ui| {
+                    let input = ui
+                        .text_edit_singleline(&mut self.lamp_check_lines_input)
+                        .on_hover_text("Wavelengths in nm, separated by commas");
+                    ui.label("Lines");
+                    match parse_wavelengths(&self.lamp_check_lines_input) {
+                        Some(lines) if input.changed() => lamp_check.lines = lines,
+                        Some(_) => {}
+                        None => {
+                            ui.colored_label(Color32::RED, "Invalid wavelengths");
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.add(
+                        DragValue::new(&mut lamp_check.search_window)
+                            .clamp_range(0.5..=50.)
+                            .speed(0.1)
+                            .suffix(" nm"),
+                    );
+                    ui.label("Search Window");
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut lamp_check.auto_correct, "Correct Drift up to");
+                    ui.add_enabled(
+                        lamp_check.auto_correct,
+                        DragValue::new(&mut lamp_check.max_correction)
+                            .clamp_range(0.0..=20.)
+                            .speed(0.01)
+                            .suffix(" nm"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut lamp_check.log_path);
+                    ui.label("Log");
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Measure Now").clicked() {
+                        measure = true;
+                    }
+                    let calibration = &mut self.config.spectrum_calibration;
+                    ui.label(format!("Offset: {:.3} nm", calibration.offset));
+                    if ui
+                        .add_enabled(calibration.offset != 0., Button::new("Reset Offset"))
+                        .clicked()
+                    {
+                        calibration.offset = 0.;
+                    }
+                });
+                if self.lamp_checks.is_empty() {
+                    return;
+                }
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .max_height(200.)
+                    .show(ui, |ui| {
+                        egui::Grid::new("lamp_checks").striped(true).show(ui, |ui| {
+                            ui.strong("Time");
+                            ui.strong("Drift [nm]");
+                            ui.strong("Lines Found");
+                            ui.strong("Corrected");
+                            ui.end_row();
+                            for check in self.lamp_checks.iter().rev() {
+                                ui.label(check.time.format("%H:%M:%S").to_string());
+                                ui.label(
+                                    check
+                                        .drift
+                                        .map_or("-".to_string(), |d| format!("{:+.3}", d)),
+                                );
+                                ui.label(format!(
+                                    "{}/{}",
+                                    check.deviations.iter().flatten().count(),
+                                    check.deviations.len()
+                                ));
+                                ui.label(if check.corrected { "✔" } else { "" });
+                                ui.end_row();
+                            }
+                        });
+                    });
+            });
+        if measure {
+            self.run_lamp_check();
+        }
+    }
+
+    fn draw_quantitation_window(&mut self, ctx: &Context) {
+        let spectrum = if self.config.view_config.show_quantitation_window {
+            self.spectrum_frame.get_spectrum_channel(3, &self.config)
+        } else {
+            vec![]
+        };
+        egui::Window::new("Quantitation")
+            .open(&mut self.config.view_config.show_quantitation_window)
+            .show(ctx, |ui| {
+                let quantitation = &mut self.config.quantitation;
+                ui.horizontal(|ui| {
+                    ui.add(
+                        DragValue::new(&mut quantitation.wavelength)
+                            .clamp_range(200.0..=1200.)
+                            .suffix(" nm"),
+                    );
+                    ui.label("Wavelength");
+                    ui.add(
+                        DragValue::new(&mut quantitation.bandwidth)
+                            .clamp_range(0.5..=50.)
+                            .speed(0.1)
+                            .suffix(" nm"),
+                    );
+                    ui.label("Bandwidth")
+                        .on_hover_text("The absorbance is averaged over this width");
+                });
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut quantitation.concentration_unit)
+                            .desired_width(60.),
+                    );
+                    ui.label("Concentration Unit");
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Capture Blank")
+                        .on_hover_text(
+                            "Take the current spectrum, e.g. of the cuvette with the solvent only",
+                        )
+                        .clicked()
+                    {
+                        quantitation.blank = Some(spectrum.clone());
+                    }
+                    if ui
+                        .add_enabled(quantitation.blank.is_some(), Button::new("Clear Blank"))
+                        .clicked()
+                    {
+                        quantitation.blank = None;
+                    }
+                });
+                let absorbance = quantitation.absorbance(&spectrum);
+                if quantitation.blank.is_none() {
+                    ui.label("Capture a blank to measure absorbance");
+                    return;
+                }
+
+                ui.separator();
+                let unit = quantitation.concentration_unit.clone();
+                let mut remove_standard = None;
+                let absorbances: Vec<_> = quantitation
+                    .standards
+                    .iter()
+                    .map(|s| quantitation.absorbance(&s.spectrum))
+                    .collect();
+                egui::Grid::new("quantitation_standards")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong(format!("Concentration [{}]", unit));
+                        ui.strong("Absorbance");
+                        ui.end_row();
+                        for (i, (standard, absorbance)) in quantitation
+                            .standards
+                            .iter_mut()
+                            .zip(absorbances)
+                            .enumerate()
+                        {
+                            ui.add(DragValue::new(&mut standard.concentration).speed(0.01));
+                            match absorbance {
+                                Some(a) => ui.label(format!("{:.4}", a)),
+                                None => ui.label("-"),
+                            };
+                            if ui.button("Remove").clicked() {
+                                remove_standard = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(i) = remove_standard {
+                    quantitation.standards.remove(i);
+                }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        DragValue::new(&mut self.standard_concentration)
+                            .clamp_range(0.0..=f32::MAX)
+                            .speed(0.01)
+                            .suffix(format!(" {}", unit)),
+                    );
+                    if ui
+                        .add_enabled(absorbance.is_some(), Button::new("Add Standard"))
+                        .on_hover_text(
+                            "Take the current spectrum as standard of this concentration",
+                        )
+                        .clicked()
+                    {
+                        quantitation.standards.push(QuantitationStandard {
+                            concentration: self.standard_concentration,
+                            spectrum: spectrum.clone(),
+                        });
+                    }
+                });
+
+                ui.separator();
+                let points = quantitation.standard_points();
+                let curve = CalibrationCurve::fit(&points);
+                match curve {
+                    Some(curve) => {
+                        ui.label(format!(
+                            "A = {:.5} · c {:+.5}, R² = {:.5}",
+                            curve.slope, curve.intercept, curve.r_squared
+                        ));
+                    }
+                    None => {
+                        ui.label("At least two standards of different concentration needed");
+                    }
+                }
+                let concentration = absorbance.zip(curve).and_then(|(a, c)| c.concentration(a));
+                Plot::new("quantitation_curve")
+                    .height(200.)
+                    .include_x(0.)
+                    .include_y(0.)
+                    .show(ui, |plot_ui| {
+                        plot_ui.points(
+                            Points::new(Values::from_values_iter(
+                                points.iter().map(|p| Value::new(p[0], p[1])),
+                            ))
+                            .radius(4.)
+                            .name("Standards"),
+                        );
+                        if let Some(curve) = curve {
+                            let max = points
+                                .iter()
+                                .map(|p| p[0])
+                                .chain(concentration)
+                                .fold(0., f32::max);
+                            plot_ui.line(
+                                Line::new(Values::from_values(vec![
+                                    Value::new(0., curve.absorbance(0.)),
+                                    Value::new(max, curve.absorbance(max)),
+                                ]))
+                                .name("Calibration Curve"),
+                            );
+                        }
+                        if let Some((c, a)) = concentration.zip(absorbance) {
+                            plot_ui.points(
+                                Points::new(Values::from_values(vec![Value::new(c, a)]))
+                                    .radius(5.)
+                                    .shape(MarkerShape::Diamond)
+                                    .color(Color32::YELLOW)
+                                    .name("Sample"),
+                            );
+                        }
+                    });
+
+                ui.separator();
+                match absorbance {
+                    Some(a) => ui.label(format!("Absorbance: {:.4}", a)),
+                    None => ui.label("No absorbance at the wavelength"),
+                };
+                if let Some(c) = concentration {
+                    ui.label(RichText::new(format!("Concentration: {:.4} {}", c, unit)).heading());
+                }
+            });
+    }
+
+    fn draw_band_monitor_window(&mut self, ctx: &Context) {
+        let mut export_clicked = false;
+        egui::Window::new("Band Monitor")
+            .open(&mut self.config.view_config.show_band_monitor_window)
+            .show(ctx, |ui| {
+                let monitor = &mut self.config.band_monitor;
+                let mut bands_changed = false;
+                let mut remove_band = None;
+                for (i, band) in monitor.bands.iter_mut().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            RichText::new("■").color(SNAPSHOT_COLORS[i % SNAPSHOT_COLORS.len()]),
+                        );
+                        bands_changed |= ui
+                            .add(DragValue::new(&mut band.start).suffix(" nm"))
+                            .changed();
+                        ui.label("-");
+                        bands_changed |= ui
+                            .add(DragValue::new(&mut band.end).suffix(" nm"))
+                            .changed();
+                        if ui.button("Remove").clicked() {
+                            remove_band = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = remove_band {
+                    monitor.bands.remove(i);
+                    bands_changed = true;
+                }
+                if ui.button("Add Band").clicked() {
+                    monitor.bands.push(WavelengthBand {
+                        start: 500.,
+                        end: 510.,
+                    });
+                    bands_changed = true;
+                }
+                if bands_changed {
+                    // Earlier samples are of other bands
+                    self.band_monitor.clear();
+                }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        DragValue::new(&mut monitor.interval)
+                            .clamp_range(0.05..=3600.)
+                            .speed(0.1)
+                            .suffix(" s"),
+                    );
+                    ui.label("Interval");
+                    ui.add(
+                        DragValue::new(&mut monitor.span)
+                            .clamp_range(10.0..=86400.)
+                            .speed(10.)
+                            .suffix(" s"),
+                    );
+                    ui.label("Span")
+                        .on_hover_text("Older samples are discarded");
+                });
+                ui.horizontal(|ui| {
+                    let recording = self.band_monitor.is_recording();
+                    if ui.button(if recording { "Stop" } else { "Start" }).clicked() {
+                        if recording {
+                            self.band_monitor.stop();
+                        } else {
+                            self.band_monitor.start();
+                        }
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.band_monitor.clear();
+                    }
+                    export_clicked = ui
+                        .add_enabled(
+                            !self.band_monitor.samples().is_empty(),
+                            Button::new("Export CSV"),
+                        )
+                        .on_hover_text(
+                            "Write to the directory and filename template of the Import/Export window",
+                        )
+                        .clicked();
+                    ui.label(format!("{} samples", self.band_monitor.samples().len()));
+                });
+                Plot::new("band_monitor")
+                    .height(200.)
+                    .legend(Legend::default())
+                    .include_y(0.)
+                    .show(ui, |plot_ui| {
+                        for (i, band) in monitor.bands.iter().enumerate() {
+                            plot_ui.line(
+                                Line::new(Values::from_values_iter(
+                                    self.band_monitor
+                                        .series(i)
+                                        .into_iter()
+                                        .map(|[t, v]| Value::new(t, v)),
+                                ))
+                                .color(SNAPSHOT_COLORS[i % SNAPSHOT_COLORS.len()])
+                                .name(band.to_string()),
+                            );
+                        }
+                    });
+                ui.label("Integrated intensity over seconds since the start");
+            });
+
+        if export_clicked {
+            let path = Self::export_path(
+                &self.config.import_export_config,
+                &self.camera_name(),
+                "csv",
+            );
+            let result = self
+                .band_monitor
+                .write_csv(&path, &self.config.band_monitor.bands)
+                .and_then(|_| {
+                    run_post_export_hook(
+                        &self.config.import_export_config.post_export_command,
+                        &path.to_string_lossy(),
+                    )
+                });
+            if result.is_ok() {
+                self.notifications
+                    .info(format!("Exported {}", path.display()));
+            }
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result,
+            });
+        }
+    }
+
+    fn draw_second_camera_window(&mut self, ctx: &Context) {
+        let can_start = self.second_acquisition.can_start();
+        let can_swap = can_start && self.acquisition.can_start();
+        let mut start = false;
+        let mut stop = false;
+        let mut copy = false;
+        let mut swap = false;
+        egui::Window::new("Second Camera")
+            .open(&mut self.config.view_config.show_second_camera_window)
+            .show(ctx, |ui| {
+                let second = &mut self.config.second_camera;
+                let window = second.image_config.window;
+                ui.add_enabled_ui(can_start, |ui| {
+                    ui.horizontal(|ui| {
+                        ComboBox::from_id_source("cb_second_camera")
+                            .selected_text(format!(
+                                "{}: {}",
+                                second.camera_id,
+                                self.camera_info
+                                    .get(&second.camera_id)
+                                    .map(|ci| ci.info.human_name())
+                                    .unwrap_or_default()
+                            ))
+                            .show_ui(ui, |ui| {
+                                for (i, ci) in &self.camera_info {
+                                    ui.selectable_value(
+                                        &mut second.camera_id,
+                                        *i,
+                                        format!("{}: {}", i, ci.info.human_name()),
+                                    );
+                                }
+                            });
+                        ComboBox::from_id_source("cb_second_camera_format")
+                            .selected_text(
+                                second
+                                    .camera_format
+                                    .map_or(String::new(), |f| f.to_string()),
+                            )
+                            .show_ui(ui, |ui| {
+                                if let Some(ci) = self.camera_info.get(&second.camera_id) {
+                                    for cf in &ci.formats {
+                                        ui.selectable_value(
+                                            &mut second.camera_format,
+                                            Some(*cf),
+                                            cf.to_string(),
+                                        );
+                                    }
+                                }
+                            });
+                    });
+                });
+                ui.horizontal(|ui| {
+                    if can_start {
+                        start = ui
+                            .add_enabled(second.camera_format.is_some(), Button::new("Start"))
+                            .clicked();
+                    } else {
+                        stop = ui
+                            .add_enabled(self.second_acquisition.can_stop(), Button::new("Stop"))
+                            .clicked();
+                    }
+                    ui.label(self.second_acquisition.to_string());
+                });
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("Window");
+                    let window = &mut second.image_config.window;
+                    ui.add(DragValue::new(&mut window.offset.x).prefix("x: "));
+                    ui.add(DragValue::new(&mut window.offset.y).prefix("y: "));
+                    ui.add(
+                        DragValue::new(&mut window.size.x)
+                            .prefix("width: ")
+                            .clamp_range(1..=u16::MAX),
+                    );
+                    ui.add(
+                        DragValue::new(&mut window.size.y)
+                            .prefix("height: ")
+                            .clamp_range(1..=u16::MAX),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    copy = ui
+                        .button("Copy Window and Calibration")
+                        .on_hover_text("Take over window and calibration of the first camera")
+                        .clicked();
+                    swap = ui
+                        .add_enabled(can_swap, Button::new("Swap With First Camera"))
+                        .on_hover_text(
+                            "Exchange camera, window and calibration with the first camera, \
+                            e.g. to calibrate the second one",
+                        )
+                        .clicked();
+                });
+
+                ui.separator();
+                ComboBox::from_label("Comparison Trace")
+                    .selected_text(second.comparison.to_string())
+                    .show_ui(ui, |ui| {
+                        for comparison in [
+                            ChannelComparison::None,
+                            ChannelComparison::Difference,
+                            ChannelComparison::Ratio,
+                        ] {
+                            ui.selectable_value(
+                                &mut second.comparison,
+                                comparison,
+                                comparison.to_string(),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text("Trace of first and second camera sum, drawn in the plot");
+
+                if second.image_config.window != window && !can_start {
+                    if let Some(stream) = &self.second_camera {
+                        stream
+                            .processor_tx
+                            .send(ProcessorEvent::ClearBuffer)
+                            .unwrap();
+                        stream
+                            .camera_tx
+                            .send(CameraEvent::Config(second.image_config.clone()))
+                            .unwrap();
+                    }
+                }
+            });
+
+        if copy {
+            let second = &mut self.config.second_camera;
+            second.image_config.window = self.config.image_config.window;
+            second.image_config.rotation = self.config.image_config.rotation;
+            second.image_config.flip = self.config.image_config.flip;
+            second.spectrum_calibration = self.config.spectrum_calibration.clone();
+            if let Some(stream) = self.second_camera.as_ref().filter(|_| !can_start) {
+                stream
+                    .processor_tx
+                    .send(ProcessorEvent::ClearBuffer)
+                    .unwrap();
+                stream
+                    .camera_tx
+                    .send(CameraEvent::Config(second.image_config.clone()))
+                    .unwrap();
+            }
+        }
+        if swap {
+            let config = &mut self.config;
+            let second = &mut config.second_camera;
+            std::mem::swap(&mut config.camera_id, &mut second.camera_id);
+            std::mem::swap(&mut config.camera_format, &mut second.camera_format);
+            std::mem::swap(&mut config.image_config, &mut second.image_config);
+            std::mem::swap(
+                &mut config.spectrum_calibration,
+                &mut second.spectrum_calibration,
+            );
+        }
+        if start {
+            self.start_second_camera();
+        }
+        if stop {
+            self.stop_second_camera();
+        }
+    }
+
+    fn draw_filter_pair_window(&mut self, ctx: &Context) {
+        let transmissions = [0, 1].map(|i| self.filter_pair.transmission(i));
+        let report = match &transmissions {
+            [Some(a), Some(b)] => Some(FilterPairReport::new([a, b], &self.config.filter_pair)),
+            _ => None,
+        };
+        let zero_reference = self.spectrum_frame.has_zero_reference();
+        // `None` for the open beam, the index for a sample
+        let mut take = None;
+        let mut save_report = false;
+        egui::Window::new("Filter Pair")
+            .open(&mut self.config.view_config.show_filter_pair_window)
+            .show(ctx, |ui| {
+                if zero_reference {
+                    ui.label(
+                        RichText::new("⚠ Zero reference is subtracted from the spectra")
+                            .color(Color32::YELLOW),
+                    );
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Take Open Beam")
+                        .on_hover_text("Spectrum of the light source without a sample")
+                        .clicked()
+                    {
+                        take = Some(None);
+                    }
+                    if self.filter_pair.open_beam.is_some() {
+                        ui.label("✔");
+                    }
+                });
+                for i in 0..2 {
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.config.filter_pair.names[i])
+                                .desired_width(100.),
+                        );
+                        if ui.button("Take Sample").clicked() {
+                            take = Some(Some(i));
+                        }
+                        if self.filter_pair.samples[i].is_some() {
+                            ui.label("✔");
+                        }
+                    });
+                }
+                ui.separator();
+
+                Plot::new("filter_pair")
+                    .height(200.)
+                    .include_y(0.)
+                    .include_y(100.)
+                    .legend(Legend::default())
+                    .show(ui, |plot_ui| {
+                        for (transmission, name) in
+                            transmissions.iter().zip(&self.config.filter_pair.names)
+                        {
+                            if let Some(transmission) = transmission {
+                                plot_ui.line(
+                                    Line::new(Values::from_values_iter(
+                                        transmission
+                                            .iter()
+                                            .map(|p| Value::new(p.wavelength, p.value * 100.)),
+                                    ))
+                                    .name(name),
+                                );
+                            }
+                        }
+                    });
+
+                let check_label = |check: Option<RequirementCheck>| match check {
+                    None => RichText::new("-"),
+                    Some(c) if !c.complete => {
+                        RichText::new(format!("{:.1} %", c.value * 100.)).color(Color32::YELLOW)
+                    }
+                    Some(c) => {
+                        RichText::new(format!("{:.1} %", c.value * 100.)).color(if c.passed {
+                            Color32::GREEN
+                        } else {
+                            Color32::RED
+                        })
+                    }
+                };
+                let mut remove = None;
+                egui::Grid::new("filter_requirements").show(ui, |ui| {
+                    ui.strong("Requirement");
+                    ui.strong("Band");
+                    ui.strong("Limit");
+                    ui.strong(&self.config.filter_pair.names[0]);
+                    ui.strong(&self.config.filter_pair.names[1]);
+                    ui.end_row();
+                    for (i, requirement) in
+                        self.config.filter_pair.requirements.iter_mut().enumerate()
+                    {
+                        ui.add(
+                            egui::TextEdit::singleline(&mut requirement.name).desired_width(80.),
+                        );
+                        ui.horizontal(|ui| {
+                            ui.add(DragValue::new(&mut requirement.band.start).suffix(" nm"));
+                            ui.add(DragValue::new(&mut requirement.band.end).suffix(" nm"));
+                        });
+                        ui.horizontal(|ui| {
+                            let (mut at_most, limit) = match requirement.limit {
+                                TransmissionLimit::AtMost(limit) => (true, limit),
+                                TransmissionLimit::AtLeast(limit) => (false, limit),
+                            };
+                            ComboBox::from_id_source(("transmission_limit", i))
+                                .selected_text(if at_most { "Max ≤" } else { "Mean ≥" })
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut at_most, true, "Max ≤");
+                                    ui.selectable_value(&mut at_most, false, "Mean ≥");
+                                });
+                            let mut percent = limit * 100.;
+                            ui.add(
+                                DragValue::new(&mut percent)
+                                    .clamp_range(0. ..=100.)
+                                    .speed(0.1)
+                                    .suffix(" %"),
+                            );
+                            requirement.limit = if at_most {
+                                TransmissionLimit::AtMost(percent / 100.)
+                            } else {
+                                TransmissionLimit::AtLeast(percent / 100.)
+                            };
+                        });
+                        let checks = report.as_ref().and_then(|r| r.checks.get(i));
+                        ui.label(check_label(checks.and_then(|c| c[0])));
+                        ui.label(check_label(checks.and_then(|c| c[1])));
+                        if ui.button("Delete").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.end_row();
+                    }
+                    let mismatch = report.as_ref().and_then(FilterPairReport::mismatch);
+                    ui.label("Mismatch");
+                    ui.label(MATCH_BAND.to_string());
+                    let mut max_mismatch = self.config.filter_pair.max_mismatch * 100.;
+                    ui.horizontal(|ui| {
+                        ui.label("Mean diff. ≤");
+                        ui.add(
+                            DragValue::new(&mut max_mismatch)
+                                .clamp_range(0. ..=100.)
+                                .speed(0.1)
+                                .suffix(" %"),
+                        );
+                    });
+                    self.config.filter_pair.max_mismatch = max_mismatch / 100.;
+                    ui.label(mismatch.map_or(RichText::new("-"), |m| {
+                        RichText::new(format!("{:.1} %", m * 100.)).color(
+                            if m <= self.config.filter_pair.max_mismatch {
+                                Color32::GREEN
+                            } else {
+                                Color32::RED
+                            },
+                        )
+                    }));
+                    ui.end_row();
+                });
+                if let Some(i) = remove {
+                    self.config.filter_pair.requirements.remove(i);
+                }
+                if ui.button("Add Requirement").clicked() {
+                    self.config.filter_pair.requirements.push(BandRequirement {
+                        name: format!(
+                            "Requirement {}",
+                            self.config.filter_pair.requirements.len() + 1
+                        ),
+                        band: MATCH_BAND,
+                        limit: TransmissionLimit::AtLeast(0.5),
+                    });
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    match &report {
+                        None => ui.label("Take the open beam and both samples"),
+                        Some(report) if report.passed => {
+                            ui.label(RichText::new("Pass").heading().color(Color32::GREEN))
+                        }
+                        Some(_) => ui.label(RichText::new("Fail").heading().color(Color32::RED)),
+                    };
+                    save_report = ui
+                        .add_enabled(report.is_some(), Button::new("Save Report"))
+                        .on_hover_text(
+                            "Write to the directory and filename template of the Import/Export \
+                            window",
+                        )
+                        .clicked();
+                });
+            });
+
+        if let Some(target) = take {
+            let spectrum = self.spectrum_frame.get_spectrum_channel(3, &self.config);
+            if spectrum.is_empty() {
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err("No spectrum to take".to_string()),
+                });
+            } else {
+                match target {
+                    None => self.filter_pair.open_beam = Some(spectrum),
+                    Some(i) => self.filter_pair.samples[i] = Some(spectrum),
+                }
+            }
+        }
+        if let (true, Some(report)) = (save_report, report) {
+            let path = Self::export_path(
+                &self.config.import_export_config,
+                &self.camera_name(),
+                "txt",
+            );
+            let result = std::fs::write(&path, report.to_text(&self.config.filter_pair))
+                .map_err(|e| e.to_string())
+                .and_then(|_| {
+                    run_post_export_hook(
+                        &self.config.import_export_config.post_export_command,
+                        &path.to_string_lossy(),
+                    )
+                });
+            if result.is_ok() {
+                self.notifications
+                    .info(format!("Exported {}", path.display()));
+            }
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result,
+            });
+        }
+    }
+
+    fn take_snapshot(&mut self) {
+        let spectrum = self.spectrum_frame.get_spectrum_channel(3, &self.config);
+        if spectrum.is_empty() {
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result: Err("No spectrum to take a snapshot of".to_string()),
+            });
+            return;
+        }
+        let index = self.config.snapshots.len();
+        let name = if self.snapshot_name.is_empty() {
+            format!("Snapshot {}", index + 1)
+        } else {
+            std::mem::take(&mut self.snapshot_name)
+        };
+        let snapshot = Snapshot {
+            name,
+            color: SNAPSHOT_COLORS[index % SNAPSHOT_COLORS.len()],
+            visible: true,
+            unit: self.config.spectrum_calibration.intensity_unit(),
+            spectrum,
+        };
+        if let Err(name) = add_snapshot(&mut self.config.snapshots, snapshot) {
+            self.notifications.push(
+                NotificationLevel::Warning,
+                format!("Same spectrum as snapshot {}", name),
+            );
+        }
     }
 
-    #[cfg(target_os = "linux")]
-    fn draw_camera_control_window(&mut self, ctx: &Context) {
-        egui::Window::new("Camera Controls")
-            .open(&mut self.config.view_config.show_camera_control_window)
-            .show(ctx, |ui| {
-                let mut changed_controls = vec![];
-                for ctrl in &mut self.camera_raw_controls {
-                    let ctrl = match ctrl.downcast_ref::<Description>() {
-                        None => continue,
-                        Some(ctrl) => ctrl,
-                    };
-                    let own_ctrl = match self.camera_controls.iter_mut().find(|c| c.id == ctrl.id) {
-                        None => continue,
-                        Some(own_ctrl) => own_ctrl,
-                    };
-                    let value_changed = match ctrl.typ {
-                        v4l::control::Type::Integer => ui
-                            .add(
-                                Slider::new(
-                                    &mut own_ctrl.value,
-                                    (ctrl.minimum + 1)..=(ctrl.maximum - 1),
-                                )
-                                .step_by(ctrl.step as f64)
-                                .text(&ctrl.name),
-                            )
-                            .changed(),
-                        v4l::control::Type::Boolean => {
-                            let mut checked = own_ctrl.value == 1;
-                            let response = ui.checkbox(&mut checked, &ctrl.name);
-                            own_ctrl.value = checked as i32;
-                            response.changed()
-                        }
-                        v4l::control::Type::Menu => {
-                            let mut changed = false;
-                            let items = match ctrl.items.as_ref() {
-                                None => continue,
-                                Some(items) => items,
-                            };
-                            let selected_text =
-                                match items.iter().find(|&i| i.0 == own_ctrl.value as u32) {
-                                    None => continue,
-                                    Some(i) => i.1.to_string(),
-                                };
-                            ComboBox::from_label(&ctrl.name)
-                                .selected_text(selected_text)
-                                .show_ui(ui, |ui| {
-                                    for item in items.iter() {
-                                        changed |= ui
-                                            .selectable_value(
-                                                &mut own_ctrl.value,
-                                                item.0 as i32,
-                                                item.1.to_string(),
-                                            )
-                                            .changed();
-                                    }
-                                });
-                            changed
-                        }
-                        _ => false,
-                    };
-                    if value_changed {
-                        changed_controls.push(own_ctrl.clone());
-                        self.spectrum_container.clear_buffer();
+    fn log_spectrum(&mut self) {
+        let due = self.logger.as_ref().is_some_and(|logger| logger.is_due());
+        if due && self.switches_light_for(SwitchedMeasurement::Log) {
+            if !self.device_busy() {
+                self.switched_measurement(SwitchedMeasurement::Log);
+            }
+            return;
+        }
+        self.write_log_entry();
+    }
+
+    fn write_log_entry(&mut self) {
+        let exposure = self
+            .exposure_control()
+            .map(|(index, _)| self.camera_controls[index].value);
+        if let Some(logger) = self.logger.as_mut() {
+            if logger.is_due() {
+                let spectrum = self.spectrum_frame.get_spectrum_channel(3, &self.config);
+                let calibration = &self.config.spectrum_calibration;
+                let metadata = MeasurementMetadata {
+                    exposure,
+                    calibration_id: calibration.fingerprint().to_string(),
+                    unit: calibration.intensity_unit(),
+                    averaged_frames: self.spectrum_frame.averaged_frames(),
+                    notes: self.config.logging_config.notes.clone(),
+                };
+                let derived_values =
+                    if self.config.logging_config.mode == LoggingMode::DerivedValues {
+                        self.config.derived_values(&spectrum)
+                    } else {
+                        Vec::new()
                     };
+                if let Err(e) = logger.log(
+                    &spectrum,
+                    &self.config.logging_config,
+                    &derived_values,
+                    &metadata,
+                ) {
+                    self.logger = None;
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result: Err(e),
+                    });
                 }
-                let default_button = ui.button("All default");
-                if default_button.clicked() {
-                    for ctrl in &mut self.camera_raw_controls {
-                        let ctrl = match ctrl.downcast_ref::<Description>() {
-                            None => continue,
-                            Some(ctrl) => ctrl,
-                        };
-                        let own_ctrl =
-                            match self.camera_controls.iter_mut().find(|c| c.id == ctrl.id) {
-                                None => continue,
-                                Some(own_ctrl) => own_ctrl,
-                            };
+            }
+        }
+    }
 
-                        own_ctrl.value = ctrl.default;
-                    }
-                    // Cannot use self.send_config due to mutable borrow in open
-                    self.camera_config_tx
-                        .send(CameraEvent::Controls(self.camera_controls.clone()))
-                        .unwrap();
-                }
-                if !changed_controls.is_empty() {
-                    // Cannot use self.send_config due to mutable borrow in open
-                    self.camera_config_tx
-                        .send(CameraEvent::Controls(changed_controls))
-                        .unwrap();
-                }
-            });
+    /// Database at the log path to browse, if the log is written into one.
+    fn browsed_database(&self) -> Result<MeasurementDatabase, String> {
+        if self.config.logging_config.format != LogFormat::Database {
+            return Err("The log format is not Database".to_string());
+        }
+        MeasurementDatabase::open_existing(&self.config.logging_config.path)
     }
 
-    #[cfg(any(target_os = "windows", target_os = "macos"))]
-    fn draw_camera_control_window(&mut self, _ctx: &Context) {}
+    /// List the measurements of the database at the log path again.
+    fn refresh_measurements(&mut self) {
+        let result = self.browsed_database().and_then(|database| database.list());
+        match result {
+            Ok(measurements) => self.measurements = measurements,
+            Err(e) => {
+                self.measurements.clear();
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err(format!("{}: {}", self.config.logging_config.path, e)),
+                });
+            }
+        }
+    }
 
-    fn draw_import_export_window(&mut self, ctx: &Context) {
-        egui::Window::new("Import/Export")
-            .open(&mut self.config.view_config.show_import_export_window)
+    fn draw_database_window(&mut self, ctx: &Context) {
+        let mut refresh = false;
+        let mut load = None;
+        let mut delete = None;
+        let filter = self.measurement_filter.to_lowercase();
+        let measurements: Vec<&MeasurementSummary> = self
+            .measurements
+            .iter()
+            .filter(|m| {
+                m.metadata.notes.to_lowercase().contains(&filter)
+                    || m.metadata.calibration_id.starts_with(&filter)
+                    || m.fingerprint.starts_with(&filter)
+            })
+            .collect();
+        egui::Window::new("Measurement Database")
+            .open(&mut self.config.view_config.show_database_window)
             .show(ctx, |ui| {
-                ui.text_edit_singleline(&mut self.config.import_export_config.path);
+                let database = self.config.logging_config.format == LogFormat::Database;
+                ui.horizontal(|ui| {
+                    ui.label(&self.config.logging_config.path);
+                    refresh = ui.add_enabled(database, Button::new("Refresh")).clicked();
+                });
+                if !database {
+                    ui.label("Choose the Database log format to browse the logged measurements");
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Filter");
+                    ui.text_edit_singleline(&mut self.measurement_filter)
+                        .on_hover_text(
+                            "Part of the notes or start of the measurement or calibration ID",
+                        );
+                });
+                ui.label(format!("{} measurements", measurements.len()));
                 ui.separator();
-                let import_reference_button = ui.button("Import Reference CSV");
-                if import_reference_button.clicked() {
-                    match csv::Reader::from_path(&self.config.import_export_config.path)
-                        .and_then(|mut r| r.deserialize().collect())
-                    {
-                        Ok(r) => {
-                            self.config.reference_config.reference = Some(r);
-                            self.last_error = Some(ThreadResult {
-                                id: ThreadId::Main,
-                                result: Ok(()),
-                            });
-                        }
-                        Err(e) => {
-                            self.last_error = Some(ThreadResult {
-                                id: ThreadId::Main,
-                                result: Err(e.to_string()),
+                let row_height = ui.spacing().interact_size.y;
+                egui::ScrollArea::vertical().max_height(400.).show_rows(
+                    ui,
+                    row_height,
+                    measurements.len(),
+                    |ui, rows| {
+                        egui::Grid::new("measurements")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                ui.strong("Time");
+                                ui.strong("ID");
+                                ui.strong("Exposure");
+                                ui.strong("Calibration");
+                                ui.strong("Frames");
+                                ui.strong("Notes");
+                                ui.end_row();
+                                for measurement in &measurements[rows] {
+                                    let metadata = &measurement.metadata;
+                                    ui.label(
+                                        measurement
+                                            .timestamp
+                                            .format("%Y-%m-%d %H:%M:%S")
+                                            .to_string(),
+                                    );
+                                    ui.label(
+                                        RichText::new(
+                                            measurement
+                                                .fingerprint
+                                                .get(..12)
+                                                .unwrap_or(&measurement.fingerprint),
+                                        )
+                                        .monospace(),
+                                    )
+                                    .on_hover_text(&measurement.fingerprint);
+                                    ui.label(
+                                        metadata
+                                            .exposure
+                                            .map_or("-".to_string(), |e| e.to_string()),
+                                    );
+                                    ui.label(
+                                        metadata
+                                            .calibration_id
+                                            .get(..12)
+                                            .unwrap_or(&metadata.calibration_id),
+                                    )
+                                    .on_hover_text(&metadata.calibration_id);
+                                    ui.label(metadata.averaged_frames.to_string());
+                                    ui.label(&metadata.notes);
+                                    if ui.button("Load").clicked() {
+                                        load = Some(measurement.id);
+                                    }
+                                    if ui.button("Delete").clicked() {
+                                        delete = Some(measurement.id);
+                                    }
+                                    ui.end_row();
+                                }
                             });
-                        }
-                    };
-                }
-                let export_reference_button = ui.add_enabled(
-                    self.config.reference_config.reference.is_some(),
-                    Button::new("Export Reference CSV"),
+                    },
                 );
-                if export_reference_button.clicked() {
-                    let writer = csv::Writer::from_path(&self.config.import_export_config.path);
-                    match writer {
-                        Ok(mut writer) => {
-                            for p in self.config.reference_config.reference.as_ref().unwrap() {
-                                writer.serialize(p).unwrap();
-                            }
-                            writer.flush().unwrap();
-                        }
-                        Err(e) => {
-                            self.last_error = Some(ThreadResult {
-                                id: ThreadId::Main,
-                                result: Err(e.to_string()),
-                            })
-                        }
-                    }
+            });
+        if load.is_some() || delete.is_some() {
+            let result = self.browsed_database().and_then(|database| {
+                if let Some(id) = delete {
+                    database.delete(id)?;
                 }
-                let delete_button = ui.add_enabled(
-                    self.config.reference_config.reference.is_some(),
-                    Button::new("Delete Reference"),
-                );
-                if delete_button.clicked() {
-                    self.config.reference_config.reference = None;
+                load.map(|id| database.load(id)).transpose()
+            });
+            match result {
+                Ok(Some(measurement)) => {
+                    let index = self.config.snapshots.len();
+                    let mut name = measurement
+                        .timestamp
+                        .format("%Y-%m-%d %H:%M:%S")
+                        .to_string();
+                    if !measurement.metadata.notes.is_empty() {
+                        name = format!("{} {}", name, measurement.metadata.notes);
+                    }
+                    let snapshot = Snapshot {
+                        name,
+                        color: SNAPSHOT_COLORS[index % SNAPSHOT_COLORS.len()],
+                        visible: true,
+                        unit: measurement.metadata.unit,
+                        spectrum: measurement.spectrum,
+                    };
+                    if let Err(name) = add_snapshot(&mut self.config.snapshots, snapshot) {
+                        self.notifications.push(
+                            NotificationLevel::Warning,
+                            format!("Already loaded as snapshot {}", name),
+                        );
+                    }
                 }
-                ui.separator();
-                let generate_reference_button =
-                    ui.button("Generate Reference From Tungsten Temperature");
-                if generate_reference_button.clicked() {
-                    self.config.reference_config.reference =
-                        Some(reference_from_filament_temp(self.tungsten_filament_temp));
+                Ok(None) => refresh = true,
+                Err(e) => {
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result: Err(e),
+                    });
                 }
-                ui.add(
-                    Slider::new(&mut self.tungsten_filament_temp, 1000..=3500)
-                        .text("Tungsten Temperature"),
-                );
-                ui.separator();
-                let export_button = ui.add(Button::new("Export Spectrum"));
-                if export_button.clicked() {
-                    match self.spectrum_container.write_to_csv(
-                        &self.config.import_export_config.path.clone(),
-                        &self.config.spectrum_calibration,
-                    ) {
-                        Ok(()) => {
-                            self.last_error = Some(ThreadResult {
-                                id: ThreadId::Main,
-                                result: Ok(()),
-                            });
-                        }
-                        Err(e) => {
-                            self.last_error = Some(ThreadResult {
-                                id: ThreadId::Main,
-                                result: Err(e),
-                            });
-                        }
+            }
+        }
+        if refresh {
+            self.refresh_measurements();
+        }
+    }
+
+    fn monitor_bands(&mut self) {
+        let monitor = &self.config.band_monitor;
+        let interval = Duration::from_secs_f32(monitor.interval);
+        let now = Instant::now();
+        if self.acquisition.is_running() && self.band_monitor.is_due(interval, now) {
+            let spectrum = self.spectrum_frame.get_spectrum_channel(3, &self.config);
+            self.band_monitor.record(
+                &spectrum,
+                &monitor.bands,
+                interval,
+                Duration::from_secs_f32(monitor.span),
+                now,
+            );
+        }
+    }
+
+    /// Run a lamp check at the scheduled times, or ask to switch on the lamp first.
+    fn schedule_lamp_check(&mut self) {
+        let lamp_check = &self.config.lamp_check;
+        if !lamp_check.active || self.lamp_check_pending || !self.acquisition.is_running() {
+            return;
+        }
+        let since = lamp_check.last_check().unwrap_or(self.session_start);
+        if !lamp_check.is_due(since, Local::now()) {
+            return;
+        }
+        if self.switches_light_for(SwitchedMeasurement::LampCheck) {
+            if !self.device_busy() {
+                self.switched_measurement(SwitchedMeasurement::LampCheck);
+            }
+        } else if lamp_check.prompt {
+            self.lamp_check_pending = true;
+            self.config.view_config.show_lamp_check_window = true;
+            self.notifications.push(
+                NotificationLevel::Warning,
+                "Lamp check due, switch on the calibration lamp",
+            );
+        } else {
+            self.run_lamp_check();
+        }
+    }
+
+    /// Measure the lamp lines in the current spectrum, log the drift and correct it.
+    fn run_lamp_check(&mut self) {
+        self.lamp_check_pending = false;
+        let peaks: Vec<f32> = self
+            .spectrum_frame
+            .fit_peaks(&self.config)
+            .into_iter()
+            .map(|fit| fit.center)
+            .collect();
+        let lamp_check = &self.config.lamp_check;
+        let check = lamp_check.check(&peaks, &mut self.config.spectrum_calibration);
+        let result = append_to_log(Path::new(&lamp_check.log_path), &check, &lamp_check.lines);
+        match check.drift {
+            None => self.notifications.push(
+                NotificationLevel::Warning,
+                "Lamp check: no line of the lamp found",
+            ),
+            Some(drift) if check.corrected => {
+                self.config.spectrum_calibration.modified = Some(Local::now().timestamp());
+                self.notifications
+                    .info(format!("Lamp check: drift of {:.3} nm corrected", drift));
+            }
+            Some(drift) if lamp_check.auto_correct => self.notifications.push(
+                NotificationLevel::Warning,
+                format!(
+                    "Lamp check: drift of {:.3} nm exceeds the bounds, not corrected",
+                    drift
+                ),
+            ),
+            Some(drift) => self
+                .notifications
+                .info(format!("Lamp check: drift of {:.3} nm", drift)),
+        }
+        self.config.lamp_check.last_check = Some(check.time.timestamp());
+        self.lamp_checks.push(check);
+        if result.is_err() {
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result,
+            });
+        }
+    }
+
+    fn draw_numpad_window(&mut self, ctx: &Context) {
+        let target = match self.numpad_target {
+            None => return,
+            Some(target) => target,
+        };
+        let mut open = true;
+        let mut enter = false;
+        egui::Window::new(target.to_string())
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                enter = numpad(ui, &mut self.numpad_input);
+            });
+
+        if enter {
+            let calibration = &mut self.config.spectrum_calibration;
+            let valid_range = match target {
+                NumpadTarget::LowWavelength => 200..=calibration.high.wavelength - 1,
+                NumpadTarget::HighWavelength => calibration.low.wavelength + 1..=2000,
+            };
+            match self.numpad_input.trim().parse::<f32>() {
+                Ok(wavelength) if valid_range.contains(&(wavelength.round() as u32)) => {
+                    let wavelength = wavelength.round() as u32;
+                    match target {
+                        NumpadTarget::LowWavelength => calibration.low.wavelength = wavelength,
+                        NumpadTarget::HighWavelength => calibration.high.wavelength = wavelength,
                     }
                 }
-            });
+                _ => {
+                    self.last_error = Some(ThreadResult {
+                        id: ThreadId::Main,
+                        result: Err(format!(
+                            "Wavelength must be between {} and {}",
+                            valid_range.start(),
+                            valid_range.end()
+                        )),
+                    });
+                }
+            }
+        }
+        if enter || !open {
+            self.numpad_target = None;
+        }
     }
 
     fn draw_windows(&mut self, ctx: &Context) {
@@ -958,11 +7171,80 @@ impl SpectrometerGui {
         self.draw_postprocessing_window(ctx);
         self.draw_camera_control_window(ctx);
         self.draw_import_export_window(ctx);
+        self.draw_snapshot_window(ctx);
+        self.draw_peak_table_window(ctx);
+        self.draw_peak_diff_window(ctx);
+        self.draw_identification_window(ctx);
+        self.draw_dashboard_window(ctx);
+        self.draw_colorimetry_window(ctx);
+        self.draw_photobiology_window(ctx);
+        self.draw_buffer_window(ctx);
+        self.draw_filter_pair_window(ctx);
+        self.draw_second_camera_window(ctx);
+        self.draw_band_monitor_window(ctx);
+        self.draw_quantitation_window(ctx);
+        self.draw_lamp_check_window(ctx);
+        self.draw_linearity_window(ctx);
+        self.draw_lock_in_window(ctx);
+        self.draw_device_control_window(ctx);
+        self.draw_filter_wheel_window(ctx);
+        #[cfg(target_os = "linux")]
+        self.draw_gpio_window(ctx);
+        self.draw_database_window(ctx);
+        self.draw_notifications_window(ctx);
+        self.draw_numpad_window(ctx);
+    }
+
+    fn switch_calibration_profile(
+        &mut self,
+        previous_camera_id: usize,
+        previous_camera_format: Option<CameraFormat>,
+    ) {
+        if let Some(camera_format) = previous_camera_format {
+            self.config
+                .store_calibration_profile(previous_camera_id, camera_format);
+        }
+        if let Some(camera_format) = self.config.camera_format {
+            if self
+                .config
+                .restore_calibration_profile(self.config.camera_id, camera_format)
+            {
+                self.notifications.info(format!(
+                    "Restored calibration profile for {}: {}",
+                    self.config.camera_id, camera_format
+                ));
+                self.processor_tx.send(ProcessorEvent::ClearBuffer).unwrap();
+            }
+        }
     }
 
     fn draw_connection_panel(&mut self, ctx: &Context) {
+        let previous_camera_id = self.config.camera_id;
+        let previous_camera_format = self.config.camera_format;
+
         egui::TopBottomPanel::top("camera").show(ctx, |ui| {
             ui.horizontal(|ui| {
+                let previous_backend = self.config.camera_backend;
+                ComboBox::from_id_source("cb_camera_backend")
+                    .selected_text(self.config.camera_backend.to_string())
+                    .show_ui(ui, |ui| {
+                        if self.acquisition.can_start() {
+                            for backend in CameraBackend::available() {
+                                ui.selectable_value(
+                                    &mut self.config.camera_backend,
+                                    backend,
+                                    backend.to_string(),
+                                );
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Capture API the cameras are listed and opened with");
+                // The cameras and their indices depend on the backend, a running enumeration with
+                // the previous one is dropped
+                if self.config.camera_backend != previous_backend {
+                    self.query_cameras();
+                }
                 ComboBox::from_id_source("cb_camera")
                     .selected_text(format!(
                         "{}: {}",
@@ -973,7 +7255,7 @@ impl SpectrometerGui {
                             .unwrap_or_default()
                     ))
                     .show_ui(ui, |ui| {
-                        if !self.running {
+                        if self.acquisition.can_start() {
                             for (i, ci) in &self.camera_info {
                                 ui.selectable_value(
                                     &mut self.config.camera_id,
@@ -989,7 +7271,7 @@ impl SpectrometerGui {
                         Some(camera_format) => format!("{}", camera_format),
                     })
                     .show_ui(ui, |ui| {
-                        if !self.running {
+                        if self.acquisition.can_start() {
                             if let Some(ci) = self.camera_info.get(&self.config.camera_id) {
                                 for cf in &ci.formats {
                                     ui.selectable_value(
@@ -1001,51 +7283,358 @@ impl SpectrometerGui {
                             }
                         }
                     });
+                let pixel_formats = self
+                    .camera_info
+                    .get(&self.config.camera_id)
+                    .map(|ci| ci.pixel_formats.clone())
+                    .unwrap_or_default();
+                if !pixel_formats.is_empty() || self.config.pixel_format.is_some() {
+                    ComboBox::from_id_source("cb_pixel_format")
+                        .selected_text(match self.config.pixel_format {
+                            None => "8-bit RGB".to_string(),
+                            Some(pixel_format) => format!("{}", pixel_format),
+                        })
+                        .show_ui(ui, |ui| {
+                            if self.acquisition.can_start() {
+                                ui.selectable_value(
+                                    &mut self.config.pixel_format,
+                                    None,
+                                    "8-bit RGB",
+                                );
+                                for pf in pixel_formats {
+                                    ui.selectable_value(
+                                        &mut self.config.pixel_format,
+                                        Some(pf),
+                                        format!("{}", pf),
+                                    );
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Read raw frames with the full bit depth of the sensor instead of \
+                            8-bit RGB converted by the camera API",
+                        );
+                }
+                let querying = self.camera_query.is_some();
+                if ui
+                    .add_enabled(
+                        !querying && self.acquisition.can_start(),
+                        Button::new(if querying {
+                            "Searching..."
+                        } else {
+                            "Refresh Devices"
+                        }),
+                    )
+                    .on_hover_text("Search for cameras connected since the start")
+                    .clicked()
+                {
+                    self.query_cameras();
+                }
 
-                let connect_button = ui.button(if self.running { "Stop..." } else { "Start..." });
+                let source = self.acquisition.source();
+                let can_start = self.acquisition.can_start();
+                let camera_running = source == Some(AcquisitionSource::Camera);
+                let connect_button = ui.add_enabled(
+                    can_start || camera_running,
+                    Button::new(if camera_running {
+                        "Stop..."
+                    } else {
+                        "Start..."
+                    }),
+                );
                 if connect_button.clicked() {
-                    if self.config.camera_format.is_some() {
-                        // Clamp window values to camera-resolution
-                        let camera_format = self.config.camera_format.unwrap();
-                        self.config
-                            .image_config
-                            .clamp(camera_format.width() as f32, camera_format.height() as f32);
-
-                        self.running = !self.running;
-                        if self.running {
-                            self.start_stream();
-                        } else {
-                            self.stop_stream();
-                        };
+                    if camera_running {
+                        self.stop_acquisition();
                     } else {
-                        self.last_error = Some(ThreadResult {
-                            id: ThreadId::Main,
-                            result: Err("Choose a camera format!".to_string()),
-                        });
+                        self.start_camera();
                     }
                 };
+
+                ui.separator();
+                ui.add_enabled(
+                    can_start,
+                    egui::TextEdit::singleline(
+                        &mut self.config.import_export_config.still_image_path,
+                    )
+                    .desired_width(200.),
+                )
+                .on_hover_text("PNG, JPEG or TIFF photo of a spectrum");
+                if source == Some(AcquisitionSource::StillImage) {
+                    if ui.button("Close Image").clicked() {
+                        self.stop_acquisition();
+                    }
+                } else if ui
+                    .add_enabled(can_start, Button::new("Open Image"))
+                    .clicked()
+                {
+                    self.open_still_image();
+                }
+
+                ui.separator();
+                ui.add_enabled(
+                    can_start,
+                    egui::TextEdit::singleline(&mut self.config.network_camera_url)
+                        .hint_text("http://192.168.4.1:81/stream")
+                        .desired_width(200.),
+                )
+                .on_hover_text("MJPEG stream or JPEG snapshot URL of an IP camera or ESP32-CAM");
+                ui.add_enabled(
+                    can_start,
+                    egui::TextEdit::singleline(&mut self.config.network_camera_user)
+                        .hint_text("User")
+                        .desired_width(60.),
+                );
+                ui.add_enabled(
+                    can_start,
+                    egui::TextEdit::singleline(&mut self.network_camera_password)
+                        .hint_text("Password")
+                        .password(true)
+                        .desired_width(60.),
+                )
+                .on_hover_text("Not stored, has to be entered again after a restart");
+                if source == Some(AcquisitionSource::Network) {
+                    if ui.button("Disconnect").clicked() {
+                        self.stop_acquisition();
+                    }
+                } else if ui
+                    .add_enabled(
+                        can_start && !self.config.network_camera_url.trim().is_empty(),
+                        Button::new("Connect"),
+                    )
+                    .clicked()
+                {
+                    self.start_network();
+                }
+
+                ui.separator();
+                if source == Some(AcquisitionSource::Synthetic) {
+                    if ui.button("Stop Demo").clicked() {
+                        self.stop_acquisition();
+                    }
+                } else if ui
+                    .add_enabled(can_start, Button::new("Demo"))
+                    .on_hover_text("Simulated camera showing a fluorescent lamp spectrum")
+                    .clicked()
+                {
+                    self.start_synthetic();
+                }
+
+                ui.separator();
+                ui.label(self.acquisition.to_string());
+                if let Some((attempt, due)) = self.reconnect {
+                    ui.label(format!(
+                        "Attempt {} of {} in {:.0} s",
+                        attempt,
+                        self.config.reconnect.max_attempts,
+                        due.saturating_duration_since(Instant::now()).as_secs_f32()
+                    ));
+                }
+                if self.acquisition.is_running() {
+                    ui.label(format!("Dropped Frames: {}", self.dropped_frames))
+                        .on_hover_text("Spectra skipped because the display could not keep up");
+                }
             });
         });
+
+        if previous_camera_id != self.config.camera_id
+            || previous_camera_format != self.config.camera_format
+        {
+            self.switch_calibration_profile(previous_camera_id, previous_camera_format);
+        }
     }
 
     fn draw_window_selection_panel(&mut self, ctx: &Context) {
+        let touch = self.config.view_config.touch_mode;
         egui::SidePanel::left("window_selection").show(ctx, |ui| {
             ui.checkbox(&mut self.config.view_config.show_camera_window, "Camera");
             ui.checkbox(
-                &mut self.config.view_config.show_camera_control_window,
-                "Camera Controls",
+                &mut self.config.view_config.show_camera_control_window,
+                "Camera Controls",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_calibration_window,
+                "Calibration",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_postprocessing_window,
+                "Postprocessing",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_import_export_window,
+                "Import/Export",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_snapshot_window,
+                "Snapshots",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_peak_table_window,
+                "Peak Table",
             );
             ui.checkbox(
-                &mut self.config.view_config.show_calibration_window,
-                "Calibration",
+                &mut self.config.view_config.show_peak_diff_window,
+                "Peak Diff",
             );
             ui.checkbox(
-                &mut self.config.view_config.show_postprocessing_window,
-                "Postprocessing",
+                &mut self.config.view_config.show_identification_window,
+                "Line Identification",
             );
             ui.checkbox(
-                &mut self.config.view_config.show_import_export_window,
-                "Import/Export",
+                &mut self.config.view_config.show_dashboard_window,
+                "Dashboard",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_colorimetry_window,
+                "Colorimetry",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_photobiology_window,
+                "Photobiology",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_buffer_window,
+                "Averaging Buffer",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_filter_pair_window,
+                "Filter Pair",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_second_camera_window,
+                "Second Camera",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_band_monitor_window,
+                "Band Monitor",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_database_window,
+                "Measurement Database",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_quantitation_window,
+                "Quantitation",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_lamp_check_window,
+                "Lamp Check",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_linearity_window,
+                "Linearity",
+            );
+            ui.checkbox(&mut self.config.view_config.show_lock_in_window, "Lock-In");
+            ui.checkbox(
+                &mut self.config.view_config.show_device_control_window,
+                "Device Control",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_filter_wheel_window,
+                "Filter Wheel",
+            );
+            #[cfg(target_os = "linux")]
+            ui.checkbox(
+                &mut self.config.view_config.show_gpio_window,
+                "GPIO Trigger",
+            );
+            ui.checkbox(
+                &mut self.config.view_config.show_notifications_window,
+                "Notifications",
+            );
+            ui.separator();
+            ui.checkbox(
+                &mut self.config.view_config.show_acquisition_overlay,
+                "Acquisition Overlay",
+            );
+            ui.checkbox(&mut self.config.view_config.show_pixel_axis, "Pixel Axis")
+                .on_hover_text("Window column indices along the top of the plot");
+            ComboBox::from_label("X Axis")
+                .selected_text(self.config.view_config.axis.unit.to_string())
+                .show_ui(ui, |ui| {
+                    for unit in [
+                        AxisUnit::Nanometer,
+                        AxisUnit::ElectronVolt,
+                        AxisUnit::Terahertz,
+                        AxisUnit::Wavenumber,
+                        AxisUnit::RamanShift,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.config.view_config.axis.unit,
+                            unit,
+                            unit.axis_label(),
+                        );
+                    }
+                });
+            if self.config.view_config.axis.unit != AxisUnit::Nanometer {
+                ui.checkbox(
+                    &mut self.config.view_config.axis.jacobian,
+                    format!(
+                        "Intensity per {}",
+                        self.config.view_config.axis.unit.symbol()
+                    ),
+                )
+                .on_hover_text(
+                    "Convert intensities per nm with the Jacobian |dλ/dx| instead of only \
+                     relabeling the axis",
+                );
+            }
+            if self.config.view_config.axis.unit == AxisUnit::RamanShift {
+                ui.horizontal(|ui| {
+                    ui.label("Laser");
+                    ui.add(
+                        DragValue::new(&mut self.config.view_config.axis.laser_wavelength)
+                            .clamp_range(200..=1100)
+                            .suffix(" nm"),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(
+                        &mut self.config.view_config.axis.mask_rayleigh,
+                        "Mask Rayleigh Line",
+                    )
+                    .on_hover_text("Leave out the laser line from the plot, peaks and exports");
+                    ui.add_enabled(
+                        self.config.view_config.axis.mask_rayleigh,
+                        DragValue::new(&mut self.config.view_config.axis.rayleigh_half_width)
+                            .clamp_range(0..=2000)
+                            .prefix("±")
+                            .suffix(" cm⁻¹"),
+                    );
+                });
+            }
+            ui.separator();
+            ui.checkbox(&mut self.config.view_config.touch_mode, "Touch Mode");
+            ComboBox::from_label("Low Power")
+                .selected_text(self.config.view_config.low_power_mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in [LowPowerMode::Off, LowPowerMode::On, LowPowerMode::OnBattery] {
+                        ui.selectable_value(
+                            &mut self.config.view_config.low_power_mode,
+                            mode,
+                            mode.to_string(),
+                        );
+                    }
+                });
+            let low_power_configurable =
+                self.config.view_config.low_power_mode != LowPowerMode::Off;
+            ui.add_enabled(
+                low_power_configurable,
+                TouchSlider::new(
+                    &mut self.config.view_config.low_power_refresh_rate,
+                    1.0..=30.,
+                    touch,
+                )
+                .text("Refresh Rate"),
+            );
+            ui.add_enabled(
+                low_power_configurable,
+                TouchSlider::new(
+                    &mut self.config.view_config.low_power_max_points,
+                    50..=1000,
+                    touch,
+                )
+                .text("Plot Points"),
             );
         });
     }
@@ -1063,43 +7652,771 @@ impl SpectrometerGui {
         });
     }
 
-    fn handle_thread_result(&mut self, res: &ThreadResult) {
-        if let ThreadResult {
-            id: ThreadId::Camera,
-            result: Err(_),
-        } = res
+    /// Post a toast for a new error result.
+    fn notify_last_error(&mut self) {
+        if self.last_error == self.notified_result {
+            return;
+        }
+        if let Some(ThreadResult { result: Err(e), .. }) = &self.last_error {
+            self.notifications.push(NotificationLevel::Error, e.clone());
+        }
+        self.notified_result = self.last_error.clone();
+    }
+
+    fn draw_toasts(&mut self, ctx: &Context) {
+        let toasts = self.notifications.toasts(Instant::now());
+        if toasts.is_empty() {
+            return;
+        }
+        // Keep repainting to hide the toasts in time
+        ctx.request_repaint();
+        let mut dismissed = None;
+        egui::Area::new("toasts")
+            .anchor(Align2::RIGHT_BOTTOM, Vec2::new(-10., -40.))
+            .show(ctx, |ui| {
+                for (index, notification) in toasts {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                RichText::new(&notification.message)
+                                    .color(notification_color(notification.level)),
+                            );
+                            if ui.small_button("✖").clicked() {
+                                dismissed = Some(index);
+                            }
+                        });
+                    });
+                }
+            });
+        if let Some(index) = dismissed {
+            self.notifications.dismiss(index);
+        }
+    }
+
+    fn draw_notifications_window(&mut self, ctx: &Context) {
+        let mut clear = false;
+        egui::Window::new("Notifications")
+            .open(&mut self.config.view_config.show_notifications_window)
+            .show(ctx, |ui| {
+                if ui.button("Clear").clicked() {
+                    clear = true;
+                }
+                ui.separator();
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for notification in self.notifications.history().rev() {
+                        ui.label(
+                            RichText::new(format!(
+                                "{} {}",
+                                notification.time.format("%H:%M:%S"),
+                                notification.message
+                            ))
+                            .color(notification_color(notification.level)),
+                        );
+                    }
+                });
+            });
+        if clear {
+            self.notifications.clear();
+        }
+    }
+
+    fn update_power_state(&mut self) {
+        if self.config.view_config.low_power_mode == LowPowerMode::OnBattery
+            && self.last_power_check.elapsed() > Duration::from_secs(10)
         {
-            self.running = false;
+            self.on_battery = on_battery();
+            self.last_power_check = Instant::now();
+        }
+    }
+
+    pub fn low_power_active(&self) -> bool {
+        match self.config.view_config.low_power_mode {
+            LowPowerMode::Off => false,
+            LowPowerMode::On => true,
+            LowPowerMode::OnBattery => self.on_battery,
+        }
+    }
+
+    /// Time after which an idle GUI has to update anyway, to notice connected cameras. In low
+    /// power mode cameras are only looked for when the GUI updates for other reasons.
+    pub fn idle_wakeup_interval(&self) -> Option<Duration> {
+        (self.acquisition.can_start() && !self.low_power_active()).then_some(DEVICE_CHECK_INTERVAL)
+    }
+
+    /// Minimum time between two repaints, if the repaint rate should be limited.
+    pub fn repaint_interval(&self) -> Option<Duration> {
+        self.low_power_active()
+            .then(|| Duration::from_secs_f32(1. / self.config.view_config.low_power_refresh_rate))
+    }
+
+    fn update_touch_style(&mut self, ctx: &Context) {
+        if self.config.view_config.touch_mode != self.touch_style_active {
+            self.touch_style_active = self.config.view_config.touch_mode;
+            ctx.set_style(if self.touch_style_active {
+                touch_style(&Style::default())
+            } else {
+                Style::default()
+            });
         }
     }
 
     pub fn update(&mut self, ctx: &Context) {
-        if self.running {
+        // Keep polling while frames are expected or a transition is pending
+        if !self.acquisition.can_start() {
             ctx.request_repaint();
         }
 
-        self.spectrum_container.update(&self.config);
+        self.update_power_state();
+        self.update_touch_style(ctx);
+
+        // Only the latest frame is of interest if the GUI lags behind
+        let pending: Vec<_> = self.spectrum_frame_rx.drain().collect();
+        self.dropped_frames += pending.len().saturating_sub(1) as u64;
+        for dark in pending.iter().filter_map(|frame| frame.captured_dark()) {
+            self.add_dark(dark.clone());
+            if let Some(sequence) = self
+                .device_sequence
+                .as_mut()
+                .filter(|s| s.measurement() == SwitchedMeasurement::Dark)
+            {
+                sequence.measured();
+            }
+        }
+        if let Some(frame) = pending.into_iter().last() {
+            self.spectrum_frame = frame;
+        }
+        self.engine.set_remote_stream(self.config.remote_stream);
+        self.log_spectrum();
+        self.monitor_bands();
+        self.schedule_lamp_check();
+        self.poll_device();
+        self.step_device_sequence();
+        self.step_filter_wheel();
+        self.poll_filter_set_export();
+        #[cfg(target_os = "linux")]
+        self.poll_trigger();
+        self.poll_remote_commands();
+        self.poll_recording();
+        self.poll_conversion(ctx);
+        self.poll_camera_query();
+        self.poll_second_camera();
+        self.check_devices();
+        self.retry_reconnect();
 
-        if let Ok(error) = self.result_rx.try_recv() {
-            self.handle_thread_result(&error);
-            self.last_error = Some(error);
+        while let Ok(event) = self.acquisition_rx.try_recv() {
+            self.handle_acquisition_event(event);
         }
 
         self.draw_connection_panel(ctx);
 
-        if self.running {
+        if self.acquisition.is_running() {
             self.draw_window_selection_panel(ctx);
             self.draw_windows(ctx);
         }
+        self.track_calibration_change();
 
         self.draw_spectrum(ctx);
+        self.notify_last_error();
         self.draw_last_result(ctx);
+        self.draw_toasts(ctx);
+        self.draw_recovery_window(ctx);
+        self.autosave();
+
+        self.send_processing_config();
+        self.send_test_pattern();
+        self.send_dark_model();
+    }
+
+    /// Remember the last good calibration before a change made it implausible and date the
+    /// change.
+    fn track_calibration_change(&mut self) {
+        if self.config.spectrum_calibration == self.tracked_calibration {
+            return;
+        }
+        let previous = std::mem::replace(
+            &mut self.tracked_calibration,
+            self.config.spectrum_calibration.clone(),
+        );
+        let window_width = self.config.image_config.window.size.x as usize;
+        if previous.check(window_width).is_ok()
+            && self
+                .config
+                .spectrum_calibration
+                .check(window_width)
+                .is_err()
+        {
+            if self.calibration_undo.len() == MAX_CALIBRATION_UNDO {
+                self.calibration_undo.remove(0);
+            }
+            self.calibration_undo.push(previous.clone());
+        }
+        // Loaded profiles keep their own modification time
+        if self.config.spectrum_calibration.modified == previous.modified {
+            let modified = Some(Local::now().timestamp());
+            self.config.spectrum_calibration.modified = modified;
+            self.tracked_calibration.modified = modified;
+        }
+    }
+
+    /// Report the result of a finished log conversion.
+    fn poll_conversion(&mut self, ctx: &Context) {
+        match self.conversion.as_ref() {
+            None => return,
+            Some(conversion) if !conversion.is_finished() => {
+                ctx.request_repaint();
+                return;
+            }
+            Some(_) => {}
+        }
+        match self.conversion.take().unwrap().join() {
+            Ok(path) => self.notifications.info(format!("Converted to {}", path)),
+            Err(e) => {
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result: Err(e),
+                })
+            }
+        }
+    }
+
+    /// Send calibration and postprocessing settings to the worker if they changed.
+    fn send_processing_config(&mut self) {
+        let processing_config = (
+            self.config.spectrum_calibration.clone(),
+            self.config.postprocessing_config.clone(),
+        );
+        if self.processing_config.as_ref() != Some(&processing_config) {
+            self.processor_tx
+                .send(ProcessorEvent::Config {
+                    spectrum_calibration: Box::new(processing_config.0.clone()),
+                    postprocessing_config: Box::new(processing_config.1.clone()),
+                })
+                .unwrap();
+            self.processing_config = Some(processing_config);
+        }
+    }
+
+    /// Send the test pattern to the worker whenever it is switched or its inputs change.
+    fn send_test_pattern(&mut self) {
+        self.test_pattern &= self.acquisition.is_running();
+        let inputs = self.test_pattern.then(|| {
+            (
+                self.config.spectrum_calibration.clone(),
+                self.config.image_config.window.size.x as usize,
+                self.config.test_pattern.clone(),
+            )
+        });
+        if inputs != self.test_pattern_sent {
+            let pattern = inputs.as_ref().map(|(calibration, width, config)| {
+                Box::new(test_pattern(config, calibration, *width))
+            });
+            self.processor_tx
+                .send(ProcessorEvent::TestPattern(pattern))
+                .unwrap();
+            self.test_pattern_sent = inputs;
+        }
+    }
+
+    /// Send the model of the dark library whenever it changes or stops fitting the camera
+    /// stream, and the exposure to subtract the dark for.
+    fn send_dark_model(&mut self) {
+        let camera = matches!(self.acquisition.source(), Some(AcquisitionSource::Camera));
+        let setup = self.config.stream_setup();
+        let library = self
+            .config
+            .dark_library
+            .as_ref()
+            .filter(|library| camera && Some(library.setup) == setup);
+        if library != self.dark_library_sent.0.as_ref() {
+            let model = library
+                .and_then(|library| DarkModel::fit(&library.darks))
+                .map(Box::new);
+            self.processor_tx
+                .send(ProcessorEvent::DarkModel(model))
+                .unwrap();
+            self.dark_library_sent.0 = library.cloned();
+        }
+        let exposure = self.current_exposure();
+        if exposure != self.dark_library_sent.1 {
+            self.processor_tx
+                .send(ProcessorEvent::Exposure(exposure))
+                .unwrap();
+            self.dark_library_sent.1 = exposure;
+        }
+    }
+
+    /// Stream the second camera with its own window and calibration.
+    fn start_second_camera(&mut self) {
+        let second = &mut self.config.second_camera;
+        let format = match second.camera_format {
+            Some(format) => format,
+            None => return,
+        };
+        if second.camera_id == self.config.camera_id
+            && self.acquisition.source() == Some(AcquisitionSource::Camera)
+        {
+            self.last_error = Some(ThreadResult {
+                id: ThreadId::Main,
+                result: Err("The second camera is already streamed as the first one".to_string()),
+            });
+            return;
+        }
+        match self
+            .second_acquisition
+            .transition(AcquisitionEvent::Start(AcquisitionSource::Camera))
+        {
+            Ok(state) => self.second_acquisition = state,
+            Err(e) => {
+                log::warn!("{}", e);
+                return;
+            }
+        }
+        second
+            .image_config
+            .clamp(format.width() as f32, format.height() as f32);
+        // Only the spectra of the first camera are published
+        let stream = self
+            .second_camera
+            .get_or_insert_with(|| CameraStream::spawn(SpectroEngine::default()));
+        stream
+            .processor_tx
+            .send(ProcessorEvent::ClearBuffer)
+            .unwrap();
+        stream
+            .camera_tx
+            .send(CameraEvent::Config(second.image_config.clone()))
+            .unwrap();
+        stream
+            .camera_tx
+            .send(CameraEvent::StartStream {
+                backend: self.config.camera_backend,
+                id: second.camera_id,
+                format,
+                pixel_format: None,
+            })
+            .unwrap();
+        self.second_processing_sent = None;
+    }
+
+    fn stop_second_camera(&mut self) {
+        match self.second_acquisition.transition(AcquisitionEvent::Stop) {
+            Ok(state) => self.second_acquisition = state,
+            Err(e) => {
+                log::warn!("{}", e);
+                return;
+            }
+        }
+        if let Some(stream) = &self.second_camera {
+            stream.camera_tx.send(CameraEvent::StopStream).unwrap();
+        }
+        self.second_spectrum_frame = SpectrumFrame::default();
+    }
+
+    /// Take over spectra and state changes of the second camera and keep its worker
+    /// configured.
+    fn poll_second_camera(&mut self) {
+        let stream = match &self.second_camera {
+            Some(stream) => stream,
+            None => return,
+        };
+        // The second camera has no preview
+        stream.frame_rx.drain();
+        let frame = stream.spectrum_frame_rx.drain().last();
+        let events: Vec<_> = stream.acquisition_rx.drain().collect();
+        if let Some(frame) = frame {
+            self.second_spectrum_frame = frame;
+        }
+        for event in events {
+            let result = match &event {
+                AcquisitionEvent::Started => Some(Ok(())),
+                AcquisitionEvent::Failed(e) => Some(Err(format!("Second camera: {}", e))),
+                _ => None,
+            };
+            match self.second_acquisition.transition(event) {
+                Ok(state) => self.second_acquisition = state,
+                Err(e) => {
+                    log::warn!("{}", e);
+                    continue;
+                }
+            }
+            // Reports about a camera which is being stopped anyway
+            if self.second_acquisition == AcquisitionState::Stopping {
+                continue;
+            }
+            if let Some(result) = result {
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Camera,
+                    result,
+                });
+            }
+        }
+
+        let processing_config = (
+            self.config.second_camera.spectrum_calibration.clone(),
+            self.config.postprocessing_config.clone(),
+        );
+        if self.second_processing_sent.as_ref() != Some(&processing_config) {
+            if let Some(stream) = &self.second_camera {
+                stream
+                    .processor_tx
+                    .send(ProcessorEvent::Config {
+                        spectrum_calibration: Box::new(processing_config.0.clone()),
+                        postprocessing_config: Box::new(processing_config.1.clone()),
+                    })
+                    .unwrap();
+            }
+            self.second_processing_sent = Some(processing_config);
+        }
+    }
+
+    /// Sum channel of the second camera on the wavelengths of its own calibration.
+    fn second_spectrum(&self) -> Vec<SpectrumPoint> {
+        let calibration = &self.config.second_camera.spectrum_calibration;
+        self.second_spectrum_frame
+            .spectrum()
+            .row(3)
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| SpectrumPoint {
+                wavelength: calibration.get_wavelength_from_index(i),
+                value,
+            })
+            .collect()
+    }
+
+    /// Add a dark the worker finished to the library, starting a new one for another setup.
+    fn add_dark(&mut self, dark: DarkFrame) {
+        let setup = match self.config.stream_setup() {
+            Some(setup) => setup,
+            None => return,
+        };
+        self.notifications
+            .info(format!("Dark captured at exposure {}", dark.exposure));
+        match &mut self.config.dark_library {
+            Some(library) if library.setup == setup => library.add(dark),
+            library => {
+                *library = Some(DarkLibrary {
+                    setup,
+                    darks: vec![dark],
+                })
+            }
+        }
+    }
+
+    /// Largest deviation of the fitted peaks from the test pattern lines, `None` if the test
+    /// pattern is not shown.
+    fn test_pattern_deviation(&self) -> Option<Option<f32>> {
+        if !self.test_pattern {
+            return None;
+        }
+        let peaks = self
+            .spectrum_frame
+            .fit_peaks(&self.config)
+            .into_iter()
+            .map(|fit| SpectrumPoint {
+                wavelength: fit.center,
+                value: fit.amplitude,
+            })
+            .collect::<Vec<_>>();
+        Some(max_deviation(&self.config.test_pattern, &peaks))
+    }
+
+    /// Snapshot of the current spectrum to show at the next start, if there is one.
+    fn last_spectrum_snapshot(&self) -> Option<Snapshot> {
+        let spectrum = self.spectrum_frame.get_spectrum_channel(3, &self.config);
+        (!spectrum.is_empty()).then(|| Snapshot {
+            name: format!("last session ({})", Local::now().format("%Y-%m-%d %H:%M")),
+            color: Color32::DARK_GRAY,
+            visible: true,
+            unit: self.config.spectrum_calibration.intensity_unit(),
+            spectrum,
+        })
+    }
+
+    /// Save the session from time to time, to offer restoring it after a crash.
+    fn autosave(&mut self) {
+        // Keep the unfinished session until the user decided about it
+        if !self.attached
+            || self.recovered_session.is_some()
+            || self.last_autosave.elapsed() < AUTOSAVE_INTERVAL
+            || self
+                .autosave_job
+                .as_ref()
+                .is_some_and(|job| !job.is_finished())
+        {
+            return;
+        }
+        self.last_autosave = Instant::now();
+        let mut session = Session::new(
+            Local::now().timestamp(),
+            &self.config,
+            self.logger
+                .is_some()
+                .then(|| self.config.logging_config.clone()),
+        );
+        if let Some(snapshot) = self.last_spectrum_snapshot() {
+            session.last_spectrum = Some(snapshot);
+        }
+        self.autosave_job = Some(session.autosave());
+    }
+
+    /// Offer to restore the session of a run which did not exit cleanly.
+    fn draw_recovery_window(&mut self, ctx: &Context) {
+        let session = match &self.recovered_session {
+            None => return,
+            Some(session) => session,
+        };
+        let mut restore = None;
+        egui::Window::new("Restore Session")
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let saved = DateTime::<Utc>::from_timestamp(session.saved, 0)
+                    .map_or("unknown".to_string(), |t| {
+                        t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+                    });
+                ui.label(format!(
+                    "The last session did not end cleanly, it was saved at {}.",
+                    saved
+                ));
+                ui.label(
+                    "Restoring brings back its camera window, calibration, references and \
+                    snapshots.",
+                );
+                if let Some(logging) = &session.logging {
+                    ui.label(format!(
+                        "The incomplete last row of the log {} is removed and logging resumes.",
+                        logging.path
+                    ));
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(self.acquisition.can_start(), Button::new("Restore"))
+                        .clicked()
+                    {
+                        restore = Some(true);
+                    }
+                    if ui.button("Discard").clicked() {
+                        restore = Some(false);
+                    }
+                });
+            });
+
+        match restore {
+            Some(true) => {
+                let session = self.recovered_session.take().unwrap();
+                let logging = session.restore(&mut self.config);
+                self.validate_references();
+                let result = logging.map_or(Ok(()), |logging| {
+                    repair_log(&logging)?;
+                    self.config.logging_config = logging;
+                    self.logger = Some(SpectrumLogger::new(&self.config.logging_config)?);
+                    Ok(())
+                });
+                self.last_error = Some(ThreadResult {
+                    id: ThreadId::Main,
+                    result,
+                });
+            }
+            Some(false) => {
+                self.recovered_session = None;
+                Session::finish();
+            }
+            None => {}
+        }
     }
 
     pub fn persist_config(&mut self, window_size: PhysicalSize<u32>) {
         self.config.view_config.window_size = window_size;
+        if let Some(snapshot) = self.last_spectrum_snapshot() {
+            self.config.last_spectrum = Some(snapshot);
+        }
+        if let Some(camera_format) = self.config.camera_format {
+            self.config
+                .store_calibration_profile(self.config.camera_id, camera_format);
+        }
         if let Err(e) = confy::store("spectro-cam-rs", None, self.config.clone()) {
             log::error!("Could not persist config: {:?}", e);
         }
+        // A pending autosave would store the session again
+        if let Some(job) = self.autosave_job.take() {
+            job.join().ok();
+        }
+        if let Some((_, job)) = self.filter_set_export.take() {
+            job.join().ok();
+        }
+        Session::finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera_stream::StreamEnds;
+    use crate::spectrum::{SpectrumProcessor, SpectrumRgb};
+    use egui::RawInput;
+
+    /// GUI whose threads are stood in for by the test, updated with a headless egui context.
+    struct Harness {
+        gui: SpectrometerGui,
+        ends: StreamEnds,
+        ctx: Context,
+    }
+
+    impl Harness {
+        fn new(config: SpectrometerConfig) -> Self {
+            let (stream, ends) = CameraStream::detached();
+            Self {
+                gui: SpectrometerGui::headless(
+                    TextureId::Managed(0),
+                    stream,
+                    config,
+                    SpectroEngine::default(),
+                ),
+                ends,
+                ctx: Context::default(),
+            }
+        }
+
+        fn update(&mut self) {
+            // Nothing is painted headless
+            let _output = self
+                .ctx
+                .run(RawInput::default(), |ctx| self.gui.update(ctx));
+        }
+
+        /// Start the synthetic camera and report it started, as the camera thread would.
+        fn start(&mut self) {
+            self.gui.start_synthetic();
+            self.ends
+                .acquisition_tx
+                .send(AcquisitionEvent::Started)
+                .unwrap();
+            self.update();
+        }
+
+        /// Send spectrum frames as the spectrum worker would.
+        fn send_frames(&self, frames: usize) {
+            let mut processor = SpectrumProcessor::new();
+            for i in 0..frames {
+                let raw = SpectrumRgb::from_fn(
+                    self.gui.config.image_config.window.size.x as usize,
+                    |_, j| (i + j % 20) as f32 / 100.,
+                );
+                let frame = processor.process(raw, &self.gui.config).clone();
+                self.ends.spectrum_frame_tx.send(frame).unwrap();
+            }
+        }
+
+        fn processor_events(&self) -> Vec<ProcessorEvent> {
+            self.ends.processor_rx.drain().collect()
+        }
+    }
+
+    #[test]
+    fn headless_update() {
+        let mut harness = Harness::new(SpectrometerConfig::default());
+        harness.update();
+        assert!(!harness.gui.acquisition.is_running());
+
+        harness.start();
+        assert!(harness.gui.acquisition.is_running());
+        assert!(harness
+            .processor_events()
+            .iter()
+            .any(|e| matches!(e, ProcessorEvent::ClearBuffer)));
+        assert!(harness
+            .ends
+            .camera_rx
+            .drain()
+            .any(|e| matches!(e, CameraEvent::StartSynthetic(_))));
+
+        // Draw every window with a spectrum, only the newest of which is shown
+        let view = &mut harness.gui.config.view_config;
+        view.show_calibration_window = true;
+        view.show_postprocessing_window = true;
+        view.show_import_export_window = true;
+        view.show_snapshot_window = true;
+        view.show_peak_table_window = true;
+        view.show_colorimetry_window = true;
+        view.show_buffer_window = true;
+        view.show_notifications_window = true;
+        harness.send_frames(3);
+        harness.update();
+        harness.update();
+        assert_eq!(harness.gui.dropped_frames, 2);
+        assert!(!harness.gui.spectrum_frame.spectrum().is_empty());
+        assert!(harness
+            .gui
+            .last_error
+            .as_ref()
+            .is_none_or(|e| e.result.is_ok()));
+
+        harness.gui.take_snapshot();
+        harness.gui.take_snapshot();
+        assert_eq!(harness.gui.config.snapshots.len(), 1);
+    }
+
+    #[test]
+    fn window_preset_clears_buffer() {
+        let mut harness = Harness::new(SpectrometerConfig::default());
+        harness.start();
+        let image_config = &mut harness.gui.config.image_config;
+        let wide = image_config.window;
+        image_config.window.size.y = 10.;
+        image_config.store_preset("narrow slit");
+        image_config.window = wide;
+        harness.processor_events();
+        harness.ends.camera_rx.drain();
+
+        harness.gui.load_window_preset("narrow slit");
+        let events = harness.processor_events();
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, ProcessorEvent::ClearBuffer)));
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, ProcessorEvent::ClearZeroReference)));
+        assert!(harness
+            .ends
+            .camera_rx
+            .drain()
+            .any(|e| matches!(e, CameraEvent::Config(c) if c.window.size.y == 10.)));
+
+        harness.gui.load_window_preset("2nd order");
+        assert!(harness.processor_events().is_empty());
+    }
+
+    #[test]
+    fn calibration_undo() {
+        let mut harness = Harness::new(SpectrometerConfig::default());
+        let good = harness.gui.config.spectrum_calibration.clone();
+        harness.gui.config.spectrum_calibration.high.index = 100;
+        harness.gui.track_calibration_change();
+        assert_eq!(harness.gui.calibration_undo, vec![good.clone()]);
+        assert!(harness.gui.config.spectrum_calibration.modified.is_some());
+
+        // Changes of an implausible calibration keep the last good one
+        harness.gui.config.spectrum_calibration.high.index = 90;
+        harness.gui.track_calibration_change();
+        assert_eq!(harness.gui.calibration_undo.len(), 1);
+        harness.update();
+        assert_eq!(harness.gui.calibration_undo.len(), 1);
+    }
+
+    #[test]
+    fn idle_wakeup() {
+        let mut harness = Harness::new(SpectrometerConfig::default());
+        assert_eq!(
+            harness.gui.idle_wakeup_interval(),
+            Some(DEVICE_CHECK_INTERVAL)
+        );
+
+        // No polling for cameras in low power mode
+        harness.gui.config.view_config.low_power_mode = LowPowerMode::On;
+        assert_eq!(harness.gui.idle_wakeup_interval(), None);
+
+        harness.gui.config.view_config.low_power_mode = LowPowerMode::Off;
+        harness.start();
+        assert_eq!(harness.gui.idle_wakeup_interval(), None);
     }
 }
@@ -0,0 +1,551 @@
+//! Minimal HDF5 writer for exports, covering only what they need: a root group of contiguous
+//! datasets of numbers, with attributes on the file and on each dataset.
+//!
+//! Uses the file format of HDF5 1.8 and later, i.e. superblock version 2 and version 2 object
+//! headers with the links stored in the header of the root group. All numbers are little
+//! endian, addresses and lengths 8 bytes.
+
+use std::io::Write;
+
+const SIGNATURE: &[u8; 8] = b"\x89HDF\r\n\x1a\n";
+const SUPERBLOCK_SIZE: usize = 48;
+const UNDEFINED_ADDRESS: u64 = u64::MAX;
+/// Size of chunk #0 as 4 bytes, attribute phase change values stored.
+const OBJECT_HEADER_FLAGS: u8 = 0x02 | 0x10;
+/// Default of the library, more attributes are still read from the header.
+const MIN_DENSE_ATTRIBUTES: u16 = 6;
+
+const MESSAGE_DATASPACE: u8 = 0x01;
+const MESSAGE_LINK_INFO: u8 = 0x02;
+const MESSAGE_DATATYPE: u8 = 0x03;
+const MESSAGE_FILL_VALUE: u8 = 0x05;
+const MESSAGE_LINK: u8 = 0x06;
+const MESSAGE_LAYOUT: u8 = 0x08;
+const MESSAGE_GROUP_INFO: u8 = 0x0a;
+const MESSAGE_ATTRIBUTE: u8 = 0x0c;
+
+/// Value of an attribute, stored as scalar.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Hdf5Value {
+    Text(String),
+    Float(f64),
+    Integer(i64),
+}
+
+impl From<&str> for Hdf5Value {
+    fn from(text: &str) -> Self {
+        Hdf5Value::Text(text.to_string())
+    }
+}
+
+impl From<String> for Hdf5Value {
+    fn from(text: String) -> Self {
+        Hdf5Value::Text(text)
+    }
+}
+
+impl From<f64> for Hdf5Value {
+    fn from(value: f64) -> Self {
+        Hdf5Value::Float(value)
+    }
+}
+
+impl From<f32> for Hdf5Value {
+    fn from(value: f32) -> Self {
+        Hdf5Value::Float(value as f64)
+    }
+}
+
+impl From<i64> for Hdf5Value {
+    fn from(value: i64) -> Self {
+        Hdf5Value::Integer(value)
+    }
+}
+
+/// Elements of a dataset in row-major order.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Hdf5Data {
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+    I64(Vec<i64>),
+}
+
+impl Hdf5Data {
+    fn len(&self) -> usize {
+        match self {
+            Hdf5Data::F32(values) => values.len(),
+            Hdf5Data::F64(values) => values.len(),
+            Hdf5Data::I64(values) => values.len(),
+        }
+    }
+
+    fn datatype(&self) -> Vec<u8> {
+        match self {
+            Hdf5Data::F32(_) => float_datatype(4),
+            Hdf5Data::F64(_) => float_datatype(8),
+            Hdf5Data::I64(_) => integer_datatype(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Hdf5Data::F32(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Hdf5Data::F64(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            Hdf5Data::I64(values) => values.iter().flat_map(|v| v.to_le_bytes()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Hdf5Dataset {
+    name: String,
+    shape: Vec<usize>,
+    data: Hdf5Data,
+    attributes: Vec<(String, Hdf5Value)>,
+}
+
+impl Hdf5Dataset {
+    /// Dataset with the dimensions in `shape`, whose product is the number of elements.
+    pub fn new(name: &str, shape: &[usize], data: Hdf5Data) -> Self {
+        assert_eq!(shape.iter().product::<usize>(), data.len());
+        Self {
+            name: name.to_string(),
+            shape: shape.to_vec(),
+            data,
+            attributes: vec![],
+        }
+    }
+
+    pub fn attribute(mut self, name: &str, value: impl Into<Hdf5Value>) -> Self {
+        self.attributes.push((name.to_string(), value.into()));
+        self
+    }
+}
+
+/// Contents of an HDF5 file, written at once as the layout needs every size.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Hdf5File {
+    attributes: Vec<(String, Hdf5Value)>,
+    datasets: Vec<Hdf5Dataset>,
+}
+
+impl Hdf5File {
+    /// Attribute of the root group.
+    pub fn attribute(&mut self, name: &str, value: impl Into<Hdf5Value>) {
+        self.attributes.push((name.to_string(), value.into()));
+    }
+
+    /// Add the dataset to the root group, dataset names need less than 256 bytes.
+    pub fn dataset(&mut self, dataset: Hdf5Dataset) {
+        assert!(dataset.name.len() < 256);
+        self.datasets.push(dataset);
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        // Sizes do not depend on the addresses, which follow from them
+        let root_size = self.root_header(&[]).len();
+        let data: Vec<Vec<u8>> = self.datasets.iter().map(|d| d.data.to_bytes()).collect();
+        let header_sizes: Vec<usize> = self
+            .datasets
+            .iter()
+            .map(|d| dataset_header(d, 0, 0).len())
+            .collect();
+        let mut address = SUPERBLOCK_SIZE + root_size;
+        let mut header_addresses = vec![];
+        for size in &header_sizes {
+            header_addresses.push(address as u64);
+            address += size;
+        }
+        let mut data_addresses = vec![];
+        for bytes in &data {
+            data_addresses.push(if bytes.is_empty() {
+                UNDEFINED_ADDRESS
+            } else {
+                address as u64
+            });
+            address += bytes.len();
+        }
+
+        let mut superblock = Vec::with_capacity(SUPERBLOCK_SIZE);
+        superblock.extend_from_slice(SIGNATURE);
+        // Version, size of offsets and lengths, consistency flags
+        superblock.extend_from_slice(&[2, 8, 8, 0]);
+        superblock.extend_from_slice(&0u64.to_le_bytes());
+        superblock.extend_from_slice(&UNDEFINED_ADDRESS.to_le_bytes());
+        superblock.extend_from_slice(&(address as u64).to_le_bytes());
+        superblock.extend_from_slice(&(SUPERBLOCK_SIZE as u64).to_le_bytes());
+        superblock.extend_from_slice(&lookup3(&superblock).to_le_bytes());
+        writer.write_all(&superblock)?;
+        writer.write_all(&self.root_header(&header_addresses))?;
+        for ((dataset, &data_address), bytes) in
+            self.datasets.iter().zip(&data_addresses).zip(&data)
+        {
+            writer.write_all(&dataset_header(dataset, data_address, bytes.len()))?;
+        }
+        for bytes in &data {
+            writer.write_all(bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Object header of the root group with links to the datasets at `addresses`, or
+    /// placeholders if empty.
+    fn root_header(&self, addresses: &[u64]) -> Vec<u8> {
+        let mut link_info = vec![0, 0];
+        link_info.extend_from_slice(&UNDEFINED_ADDRESS.to_le_bytes());
+        link_info.extend_from_slice(&UNDEFINED_ADDRESS.to_le_bytes());
+        let mut messages = vec![
+            (MESSAGE_LINK_INFO, link_info),
+            (MESSAGE_GROUP_INFO, vec![0, 0]),
+        ];
+        for (i, dataset) in self.datasets.iter().enumerate() {
+            // Hard link with a one byte name length
+            let mut link = vec![1, 0, dataset.name.len() as u8];
+            link.extend_from_slice(dataset.name.as_bytes());
+            link.extend_from_slice(&addresses.get(i).copied().unwrap_or(0).to_le_bytes());
+            messages.push((MESSAGE_LINK, link));
+        }
+        messages.extend(attribute_messages(&self.attributes));
+        object_header(&messages, self.attributes.len())
+    }
+}
+
+fn dataset_header(dataset: &Hdf5Dataset, data_address: u64, data_size: usize) -> Vec<u8> {
+    // Contiguous layout
+    let mut layout = vec![3, 1];
+    layout.extend_from_slice(&data_address.to_le_bytes());
+    layout.extend_from_slice(&(data_size as u64).to_le_bytes());
+    let mut messages = vec![
+        (MESSAGE_DATASPACE, dataspace(&dataset.shape)),
+        (MESSAGE_DATATYPE, dataset.data.datatype()),
+        // Allocated late, written if set, no fill value defined
+        (MESSAGE_FILL_VALUE, vec![3, 0x0a]),
+        (MESSAGE_LAYOUT, layout),
+    ];
+    messages.extend(attribute_messages(&dataset.attributes));
+    object_header(&messages, dataset.attributes.len())
+}
+
+fn attribute_messages(attributes: &[(String, Hdf5Value)]) -> Vec<(u8, Vec<u8>)> {
+    attributes
+        .iter()
+        .map(|(name, value)| {
+            let (datatype, data) = match value {
+                Hdf5Value::Text(text) => {
+                    // Null padded, at least one byte
+                    let mut data = text.as_bytes().to_vec();
+                    if data.is_empty() {
+                        data.push(0);
+                    }
+                    (string_datatype(data.len()), data)
+                }
+                Hdf5Value::Float(value) => (float_datatype(8), value.to_le_bytes().to_vec()),
+                Hdf5Value::Integer(value) => (integer_datatype(), value.to_le_bytes().to_vec()),
+            };
+            let dataspace = dataspace(&[]);
+            // Version 3 with a UTF-8 name
+            let mut message = vec![3, 0];
+            message.extend_from_slice(&(name.len() as u16 + 1).to_le_bytes());
+            message.extend_from_slice(&(datatype.len() as u16).to_le_bytes());
+            message.extend_from_slice(&(dataspace.len() as u16).to_le_bytes());
+            message.push(1);
+            message.extend_from_slice(name.as_bytes());
+            message.push(0);
+            message.extend(datatype);
+            message.extend(dataspace);
+            message.extend(data);
+            (MESSAGE_ATTRIBUTE, message)
+        })
+        .collect()
+}
+
+/// Version 2 object header in a single chunk.
+fn object_header(messages: &[(u8, Vec<u8>)], attributes: usize) -> Vec<u8> {
+    let chunk_size: usize = messages.iter().map(|(_, data)| 4 + data.len()).sum();
+    let mut header = b"OHDR".to_vec();
+    header.extend_from_slice(&[2, OBJECT_HEADER_FLAGS]);
+    let max_compact = (attributes as u16).max(8);
+    header.extend_from_slice(&max_compact.to_le_bytes());
+    header.extend_from_slice(&MIN_DENSE_ATTRIBUTES.to_le_bytes());
+    header.extend_from_slice(&(chunk_size as u32).to_le_bytes());
+    for (message_type, data) in messages {
+        header.push(*message_type);
+        header.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        header.push(0);
+        header.extend_from_slice(data);
+    }
+    header.extend_from_slice(&lookup3(&header).to_le_bytes());
+    header
+}
+
+/// Simple dataspace, scalar without dimensions.
+fn dataspace(shape: &[usize]) -> Vec<u8> {
+    let space_type = if shape.is_empty() { 0 } else { 1 };
+    let mut message = vec![2, shape.len() as u8, 0, space_type];
+    for &size in shape {
+        message.extend_from_slice(&(size as u64).to_le_bytes());
+    }
+    message
+}
+
+/// IEEE 754 floating point of 4 or 8 bytes.
+fn float_datatype(size: u32) -> Vec<u8> {
+    let (exponent_location, exponent_size, mantissa_size, bias) = match size {
+        4 => (23u8, 8u8, 23u8, 127u32),
+        _ => (52, 11, 52, 1023),
+    };
+    // Version 1 floating point, implied mantissa MSB, sign bit at the top
+    let mut message = vec![0x11, 0x20, size as u8 * 8 - 1, 0];
+    message.extend_from_slice(&size.to_le_bytes());
+    message.extend_from_slice(&0u16.to_le_bytes());
+    message.extend_from_slice(&(size as u16 * 8).to_le_bytes());
+    message.extend_from_slice(&[exponent_location, exponent_size, 0, mantissa_size]);
+    message.extend_from_slice(&bias.to_le_bytes());
+    message
+}
+
+/// Signed 64 bit integer.
+fn integer_datatype() -> Vec<u8> {
+    let mut message = vec![0x10, 0x08, 0, 0];
+    message.extend_from_slice(&8u32.to_le_bytes());
+    message.extend_from_slice(&0u16.to_le_bytes());
+    message.extend_from_slice(&64u16.to_le_bytes());
+    message
+}
+
+/// Null padded UTF-8 string of fixed size.
+fn string_datatype(size: usize) -> Vec<u8> {
+    let mut message = vec![0x13, 0x11, 0, 0];
+    message.extend_from_slice(&(size as u32).to_le_bytes());
+    message
+}
+
+/// Bob Jenkins' lookup3 `hashlittle` with an initial value of 0, the checksum of HDF5.
+fn lookup3(data: &[u8]) -> u32 {
+    fn mix(a: &mut u32, b: &mut u32, c: &mut u32) {
+        *a = a.wrapping_sub(*c) ^ c.rotate_left(4);
+        *c = c.wrapping_add(*b);
+        *b = b.wrapping_sub(*a) ^ a.rotate_left(6);
+        *a = a.wrapping_add(*c);
+        *c = c.wrapping_sub(*b) ^ b.rotate_left(8);
+        *b = b.wrapping_add(*a);
+        *a = a.wrapping_sub(*c) ^ c.rotate_left(16);
+        *c = c.wrapping_add(*b);
+        *b = b.wrapping_sub(*a) ^ a.rotate_left(19);
+        *a = a.wrapping_add(*c);
+        *c = c.wrapping_sub(*b) ^ b.rotate_left(4);
+        *b = b.wrapping_add(*a);
+    }
+
+    fn word(bytes: &[u8]) -> u32 {
+        bytes
+            .iter()
+            .enumerate()
+            .fold(0, |word, (i, &byte)| word | (byte as u32) << (8 * i))
+    }
+
+    let mut a = 0xdeadbeefu32.wrapping_add(data.len() as u32);
+    let mut b = a;
+    let mut c = a;
+    let mut rest = data;
+    while rest.len() > 12 {
+        a = a.wrapping_add(word(&rest[0..4]));
+        b = b.wrapping_add(word(&rest[4..8]));
+        c = c.wrapping_add(word(&rest[8..12]));
+        mix(&mut a, &mut b, &mut c);
+        rest = &rest[12..];
+    }
+    if rest.is_empty() {
+        return c;
+    }
+    a = a.wrapping_add(word(&rest[..rest.len().min(4)]));
+    if rest.len() > 4 {
+        b = b.wrapping_add(word(&rest[4..rest.len().min(8)]));
+    }
+    if rest.len() > 8 {
+        c = c.wrapping_add(word(&rest[8..]));
+    }
+    c ^= b;
+    c = c.wrapping_sub(b.rotate_left(14));
+    a ^= c;
+    a = a.wrapping_sub(c.rotate_left(11));
+    b ^= a;
+    b = b.wrapping_sub(a.rotate_left(25));
+    c ^= b;
+    c = c.wrapping_sub(b.rotate_left(16));
+    a ^= c;
+    a = a.wrapping_sub(c.rotate_left(4));
+    b ^= a;
+    b = b.wrapping_sub(a.rotate_left(14));
+    c ^= b;
+    c.wrapping_sub(b.rotate_left(24))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    /// Messages of the object header at `address`, after checking its checksum.
+    fn messages(file: &[u8], address: usize) -> Vec<(u8, &[u8])> {
+        assert_eq!(&file[address..address + 6], b"OHDR\x02\x12");
+        let chunk_size =
+            u32::from_le_bytes(file[address + 10..address + 14].try_into().unwrap()) as usize;
+        let end = address + 14 + chunk_size;
+        let checksum = u32::from_le_bytes(file[end..end + 4].try_into().unwrap());
+        assert_eq!(lookup3(&file[address..end]), checksum);
+        let mut messages = vec![];
+        let mut offset = address + 14;
+        while offset < end {
+            let size = u16::from_le_bytes([file[offset + 1], file[offset + 2]]) as usize;
+            messages.push((file[offset], &file[offset + 4..offset + 4 + size]));
+            offset += 4 + size;
+        }
+        assert_eq!(offset, end);
+        messages
+    }
+
+    #[test]
+    fn hdf5() {
+        // Test vectors of lookup3.c
+        assert_eq!(lookup3(b""), 0xdeadbeef);
+        assert_eq!(lookup3(b"Four score and seven years ago"), 0x17770551);
+
+        let mut hdf5 = Hdf5File::default();
+        hdf5.attribute("camera", "webcam");
+        hdf5.attribute("exposure", 156i64);
+        hdf5.dataset(
+            Hdf5Dataset::new("wavelength", &[3], Hdf5Data::F32(vec![400., 500., 600.]))
+                .attribute("unit", "nm"),
+        );
+        hdf5.dataset(Hdf5Dataset::new(
+            "time",
+            &[2, 1],
+            Hdf5Data::I64(vec![-1, 1_650_000_000_000_000]),
+        ));
+        hdf5.dataset(Hdf5Dataset::new("empty", &[0], Hdf5Data::F64(vec![])));
+        let mut file = vec![];
+        hdf5.write(&mut file).unwrap();
+
+        assert_eq!(&file[..8], SIGNATURE);
+        assert_eq!(
+            u32::from_le_bytes(file[44..48].try_into().unwrap()),
+            lookup3(&file[..44])
+        );
+        assert_eq!(u64_at(&file, 28), file.len() as u64);
+        let root = messages(&file, u64_at(&file, 36) as usize);
+        assert_eq!(
+            root.iter().filter(|(t, _)| *t == MESSAGE_ATTRIBUTE).count(),
+            2
+        );
+        assert!(root.contains(&(
+            MESSAGE_ATTRIBUTE,
+            b"\x03\x00\x07\x00\x08\x00\x04\x00\x01camera\x00\x13\x11\x00\x00\x06\x00\x00\x00\
+              \x02\x00\x00\x00webcam"
+                .as_slice()
+        )));
+
+        let links: Vec<(&[u8], usize)> = root
+            .iter()
+            .filter(|(t, _)| *t == MESSAGE_LINK)
+            .map(|(_, link)| {
+                let name_end = 3 + link[2] as usize;
+                (&link[3..name_end], u64_at(link, name_end) as usize)
+            })
+            .collect();
+        assert_eq!(
+            links.iter().map(|(name, _)| *name).collect::<Vec<_>>(),
+            vec![b"wavelength".as_slice(), b"time", b"empty"]
+        );
+        let data: Vec<&[u8]> = links
+            .iter()
+            .map(|&(_, address)| {
+                let dataset = messages(&file, address);
+                let layout = dataset
+                    .iter()
+                    .find(|(t, _)| *t == MESSAGE_LAYOUT)
+                    .unwrap()
+                    .1;
+                let (address, size) = (u64_at(layout, 2), u64_at(layout, 10) as usize);
+                if size == 0 {
+                    assert_eq!(address, UNDEFINED_ADDRESS);
+                    return &[][..];
+                }
+                &file[address as usize..address as usize + size]
+            })
+            .collect();
+        assert_eq!(
+            data[0],
+            &Hdf5Data::F32(vec![400., 500., 600.]).to_bytes()[..]
+        );
+        assert_eq!(&data[1][8..], &1_650_000_000_000_000i64.to_le_bytes());
+        assert!(data[2].is_empty());
+        let time = messages(&file, links[1].1);
+        assert!(time.contains(&(
+            MESSAGE_DATASPACE,
+            b"\x02\x02\x00\x01\x02\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00\x00\x00\x00\x00"
+                .as_slice()
+        )));
+    }
+
+    /// Read back by the HDF5 library, run with `cargo test -- --ignored` where its `h5dump` is
+    /// installed.
+    #[test]
+    #[ignore = "needs h5dump of the HDF5 tools"]
+    fn h5dump() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("h5dump.h5");
+        let mut hdf5 = Hdf5File::default();
+        hdf5.attribute("camera", "webcam");
+        hdf5.attribute("exposure", 156i64);
+        hdf5.dataset(
+            Hdf5Dataset::new("wavelength", &[3], Hdf5Data::F32(vec![400., 500., 600.]))
+                .attribute("unit", "nm"),
+        );
+        hdf5.dataset(Hdf5Dataset::new(
+            "time",
+            &[2],
+            Hdf5Data::I64(vec![-1, 1_650_000_000_000_000]),
+        ));
+        hdf5.dataset(Hdf5Dataset::new(
+            "spectra",
+            &[2, 3],
+            Hdf5Data::F64(vec![0.5, 1., 1.5, 2., 2.5, 3.]),
+        ));
+        hdf5.write(&mut std::fs::File::create(&path).unwrap())
+            .unwrap();
+
+        let output = std::process::Command::new("h5dump")
+            .arg(&path)
+            .output()
+            .expect("h5dump is not installed");
+        let dump = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "{}{}",
+            dump,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        for expected in [
+            "ATTRIBUTE \"camera\"",
+            "\"webcam\"",
+            "ATTRIBUTE \"exposure\"",
+            "156",
+            "DATASET \"wavelength\"",
+            "H5T_IEEE_F32LE",
+            "400, 500, 600",
+            "\"nm\"",
+            "DATASET \"time\"",
+            "H5T_STD_I64LE",
+            "-1, 1650000000000000",
+            "DATASET \"spectra\"",
+            "( 2, 3 )",
+            "0.5, 1, 1.5",
+        ] {
+            assert!(dump.contains(expected), "{} missing in\n{}", expected, dump);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::config::HdrConfig;
+use crate::frame_settling::{FrameSettling, FrameStep};
+use crate::spectrum::{SpectrumRgb, CHANNEL_FULL_SCALE};
+use std::ops::RangeInclusive;
+
+/// Frames captured per exposure, so that the worker receives at least one of them.
+const CAPTURE_FRAMES: usize = 2;
+/// Fraction of full scale from which a value of a bracket is not merged.
+const SATURATION_LEVEL: f32 = 0.95;
+
+/// What the GUI has to do for the current camera frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HdrStep {
+    /// Set the exposure control to the value and discard spectra until it settled.
+    Expose(i32),
+    /// The following spectra belong to the bracket with the index.
+    Capture(usize),
+}
+
+/// Cycles the camera exposure through the brackets of an HDR acquisition.
+#[derive(Debug)]
+pub struct HdrBracketing {
+    exposures: Vec<i32>,
+    index: usize,
+    frames: FrameSettling,
+}
+
+impl HdrBracketing {
+    /// Brackets starting at the `base` exposure, each `config.step` times longer than the one
+    /// before and limited to `range`.
+    pub fn new(base: i32, config: &HdrConfig, range: RangeInclusive<i32>) -> Self {
+        let mut exposures: Vec<i32> = (0..config.brackets.max(1))
+            .map(|i| {
+                let exposure = (base.max(1) as f32 * config.step.powi(i as i32)).round();
+                (exposure.min(i32::MAX as f32) as i32).clamp(*range.start(), *range.end())
+            })
+            .collect();
+        // Brackets beyond the range would only repeat the longest exposure
+        exposures.dedup();
+        Self {
+            exposures,
+            index: 0,
+            frames: FrameSettling::new(CAPTURE_FRAMES),
+        }
+    }
+
+    pub fn exposures(&self) -> &[i32] {
+        &self.exposures
+    }
+
+    /// Exposures relative to the shortest one, which the merged spectrum is scaled to.
+    pub fn relative_exposures(&self) -> Vec<f32> {
+        self.exposures
+            .iter()
+            .map(|&e| e as f32 / self.exposures[0] as f32)
+            .collect()
+    }
+
+    /// Index of the bracket currently settling or captured.
+    pub fn bracket(&self) -> usize {
+        self.index
+    }
+
+    /// Advance by one camera frame.
+    pub fn next_frame(&mut self) -> Option<HdrStep> {
+        match self.frames.next_frame()? {
+            FrameStep::Capture => Some(HdrStep::Capture(self.index)),
+            FrameStep::Change => {
+                self.index = (self.index + 1) % self.exposures.len();
+                Some(HdrStep::Expose(self.exposures[self.index]))
+            }
+        }
+    }
+}
+
+/// Collects the raw spectra of the brackets and merges them once all are available.
+#[derive(Debug)]
+pub struct HdrMerger {
+    relative_exposures: Vec<f32>,
+    bracket: Option<usize>,
+    spectra: Vec<Option<SpectrumRgb>>,
+}
+
+impl HdrMerger {
+    pub fn new(relative_exposures: Vec<f32>) -> Self {
+        let spectra = vec![None; relative_exposures.len()];
+        Self {
+            relative_exposures,
+            bracket: None,
+            spectra,
+        }
+    }
+
+    /// Assign the following spectra to `bracket`, or discard them with `None`.
+    pub fn set_bracket(&mut self, bracket: Option<usize>) {
+        self.bracket = bracket.filter(|&b| b < self.spectra.len());
+    }
+
+    /// Store `spectrum` for the current bracket and return the merged spectrum, if every
+    /// bracket has one of the same width.
+    pub fn add(&mut self, spectrum: SpectrumRgb) -> Option<SpectrumRgb> {
+        let bracket = self.bracket?;
+        let ncols = spectrum.ncols();
+        self.spectra[bracket] = Some(spectrum);
+        let brackets = self
+            .spectra
+            .iter()
+            .zip(&self.relative_exposures)
+            .map(|(spectrum, &exposure)| {
+                spectrum
+                    .as_ref()
+                    .filter(|s| s.ncols() == ncols)
+                    .map(|s| (exposure, s))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(merge(&brackets))
+    }
+}
+
+/// Merge raw spectra taken with the given relative exposures, scaled to an exposure of 1.
+///
+/// Every value is the sum of the unsaturated brackets divided by the sum of their exposures,
+/// which weights longer exposures by their better signal to noise ratio. Values saturated in
+/// every bracket are taken from the first one. A linear sensor response is assumed.
+pub fn merge(brackets: &[(f32, &SpectrumRgb)]) -> SpectrumRgb {
+    let (first_exposure, first) = brackets[0];
+    SpectrumRgb::from_fn(first.ncols(), |row, column| {
+        let (sum, exposure) = brackets
+            .iter()
+            .filter(|(_, s)| s[(row, column)] / CHANNEL_FULL_SCALE < SATURATION_LEVEL)
+            .fold((0., 0.), |(sum, exposure), (e, s)| {
+                (sum + s[(row, column)], exposure + e)
+            });
+        if exposure > 0. {
+            sum / exposure
+        } else {
+            first[(row, column)] / first_exposure
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame_settling::SETTLE_FRAMES;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn hdr_bracketing() {
+        let config = HdrConfig {
+            brackets: 3,
+            step: 4.,
+        };
+        let mut bracketing = HdrBracketing::new(100, &config, 1..=1000);
+        assert_eq!(bracketing.exposures(), &[100, 400, 1000]);
+        assert_eq!(bracketing.relative_exposures(), vec![1., 4., 10.]);
+        assert_eq!(
+            HdrBracketing::new(500, &config, 1..=1000).exposures(),
+            &[500, 1000]
+        );
+
+        let steps: Vec<_> = (0..2 * (SETTLE_FRAMES + CAPTURE_FRAMES))
+            .filter_map(|_| bracketing.next_frame())
+            .collect();
+        assert_eq!(
+            steps,
+            vec![
+                HdrStep::Capture(0),
+                HdrStep::Expose(400),
+                HdrStep::Capture(1),
+                HdrStep::Expose(1000),
+            ]
+        );
+        assert_eq!(bracketing.bracket(), 2);
+    }
+
+    #[test]
+    fn hdr_merge() {
+        // A line saturating the long exposure on a weak continuum
+        let short = SpectrumRgb::from_row_slice(&[0.01, 0.3, 0.01, 0.01, 0.3, 0.01, 0., 0., 0.]);
+        let long = short.map(|v| (v * 4.).min(CHANNEL_FULL_SCALE));
+
+        let mut merger = HdrMerger::new(vec![1., 4.]);
+        assert_eq!(merger.add(short.clone()), None);
+        merger.set_bracket(Some(0));
+        assert_eq!(merger.add(short.clone()), None);
+        merger.set_bracket(Some(1));
+        let merged = merger.add(long).unwrap();
+
+        assert_relative_eq!(merged[(0, 0)], 0.01, max_relative = 1e-5);
+        // Only the short exposure is used for the line
+        assert_relative_eq!(merged[(0, 1)], 0.3, max_relative = 1e-5);
+        assert_eq!(merged[(2, 2)], 0.);
+
+        merger.set_bracket(Some(0));
+        assert_eq!(merger.add(SpectrumRgb::zeros(2)), None);
+    }
+}
@@ -0,0 +1,123 @@
+use std::process::Command;
+
+/// Split a user supplied command line at whitespace outside of double or single quotes, which
+/// group arguments like paths with spaces and are removed. Backslashes are kept as they are, as
+/// they separate Windows paths.
+pub fn split_command_line(command: &str) -> Result<Vec<String>, String> {
+    let mut parts = vec![];
+    let mut part: Option<String> = None;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => part.get_or_insert_with(String::new).push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                part.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => parts.extend(part.take()),
+            (None, c) => part.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("Unbalanced quote in \"{}\"", command));
+    }
+    parts.extend(part);
+    Ok(parts)
+}
+
+/// Command of a user supplied command line with `args` appended.
+///
+/// Returns `None` if the command is empty, i.e. no command is configured.
+fn command_line(command: &str, args: &[&str]) -> Result<Option<Command>, String> {
+    let parts = split_command_line(command)?;
+    Ok(parts.split_first().map(|(program, parts)| {
+        let mut cmd = Command::new(program);
+        cmd.args(parts).args(args);
+        cmd
+    }))
+}
+
+/// Split a user supplied command line into program and arguments and append the exported path.
+///
+/// Returns `None` if the command is empty, i.e. no hook is configured.
+pub fn build_command(command: &str, path: &str) -> Result<Option<Command>, String> {
+    command_line(command, &[path])
+}
+
+/// Run the post-export command without blocking the caller.
+///
+/// Only failing to start the command is reported, its exit status is logged from a
+/// separate thread.
+pub fn run_post_export_hook(command: &str, path: &str) -> Result<(), String> {
+    match build_command(command, path)? {
+        None => Ok(()),
+        Some(cmd) => spawn(cmd, "Post-export"),
+    }
+}
+
+/// Run a user supplied command line without arguments added, like the commands switching
+/// the source of the lock-in detection, the same way as [run_post_export_hook].
+pub fn run_command(command: &str, name: &str) -> Result<(), String> {
+    match command_line(command, &[])? {
+        None => Ok(()),
+        Some(cmd) => spawn(cmd, name),
+    }
+}
+
+fn spawn(mut cmd: Command, name: &str) -> Result<(), String> {
+    let mut child = cmd
+        .spawn()
+        .map_err(|e| format!("Could not run {} command: {}", name.to_lowercase(), e))?;
+    let name = name.to_string();
+    std::thread::spawn(move || match child.wait() {
+        Ok(status) if status.success() => {}
+        Ok(status) => log::warn!("{} command exited with {}", name, status),
+        Err(e) => log::error!("{:?}", e),
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn post_export_command() {
+        assert!(build_command("", "spectrum.csv").unwrap().is_none());
+        assert!(build_command("   ", "spectrum.csv").unwrap().is_none());
+
+        let cmd = build_command("upload.sh --host lab", "spectrum.csv")
+            .unwrap()
+            .unwrap();
+        assert_eq!(cmd.get_program(), OsStr::new("upload.sh"));
+        assert_eq!(
+            cmd.get_args().collect::<Vec<_>>(),
+            vec!["--host", "lab", "spectrum.csv"]
+        );
+
+        // Paths with spaces and backslashes, and the exported path as a single argument
+        let cmd = build_command(
+            r#""C:\Program Files\Upload\upload.exe" --title 'lamp test' --empty """#,
+            "my data/spectrum 1.csv",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            cmd.get_program(),
+            OsStr::new(r"C:\Program Files\Upload\upload.exe")
+        );
+        assert_eq!(
+            cmd.get_args().collect::<Vec<_>>(),
+            vec![
+                "--title",
+                "lamp test",
+                "--empty",
+                "",
+                "my data/spectrum 1.csv"
+            ]
+        );
+        assert!(build_command("upload.sh \"lab", "spectrum.csv").is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use crate::config::HotPixelMap;
+use image::{DynamicImage, ImageBuffer, Pixel, Primitive, Rgb, RgbImage};
+
+/// Dark frames averaged to find the hot pixels.
+pub const HOT_PIXEL_FRAMES: usize = 10;
+
+/// Averages dark frames and finds the pixels standing out from their neighbors, which cheap
+/// sensors have enough of to show up as fake emission lines.
+#[derive(Debug)]
+pub struct HotPixelCapture {
+    /// Threshold above the median of the neighbors, relative to full scale.
+    threshold: f32,
+    /// Whether the frames are flipped horizontally, while the map is for the unflipped ones.
+    flipped: bool,
+    width: u32,
+    height: u32,
+    /// Sum of the brightest channel of each pixel.
+    sum: Vec<f32>,
+    frames: usize,
+}
+
+impl HotPixelCapture {
+    pub fn new(threshold: f32, flipped: bool) -> Self {
+        Self {
+            threshold,
+            flipped,
+            width: 0,
+            height: 0,
+            sum: vec![],
+            frames: 0,
+        }
+    }
+
+    /// Number of frames added so far.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Add a frame taken with the lens covered and return the hot pixels once
+    /// [HOT_PIXEL_FRAMES] were added. A change of the frame size starts over.
+    pub fn add(&mut self, frame: &RgbImage) -> Option<HotPixelMap> {
+        if frame.dimensions() != (self.width, self.height) {
+            (self.width, self.height) = frame.dimensions();
+            self.sum = vec![0.; (self.width * self.height) as usize];
+            self.frames = 0;
+        }
+        for (sum, pixel) in self.sum.iter_mut().zip(frame.pixels()) {
+            *sum += pixel.0.into_iter().max().unwrap() as f32 / 255.;
+        }
+        self.frames += 1;
+        if self.frames < HOT_PIXEL_FRAMES {
+            return None;
+        }
+        let mean: Vec<f32> = self.sum.iter().map(|s| s / self.frames as f32).collect();
+        let mut pixels = find_hot_pixels(&mean, self.width, self.height, self.threshold);
+        if self.flipped {
+            for [x, _] in &mut pixels {
+                *x = self.width - 1 - *x;
+            }
+            pixels.sort_by_key(|[x, y]| (*y, *x));
+        }
+        Some(HotPixelMap {
+            width: self.width,
+            height: self.height,
+            pixels,
+        })
+    }
+}
+
+/// Pixels of the `width` x `height` brightness image exceeding the median of their neighbors
+/// by more than `threshold`, in row order.
+fn find_hot_pixels(brightness: &[f32], width: u32, height: u32, threshold: f32) -> Vec<[u32; 2]> {
+    let mut pixels = vec![];
+    for y in 0..height {
+        for x in 0..width {
+            let mut neighbors: Vec<f32> = neighbors(x, y, width, height, 1)
+                .map(|[x, y]| brightness[(y * width + x) as usize])
+                .collect();
+            if neighbors.is_empty() {
+                continue;
+            }
+            neighbors.sort_by(f32::total_cmp);
+            let n = neighbors.len();
+            let median = (neighbors[(n - 1) / 2] + neighbors[n / 2]) / 2.;
+            if brightness[(y * width + x) as usize] - median > threshold {
+                pixels.push([x, y]);
+            }
+        }
+    }
+    pixels
+}
+
+/// Pixels within `distance` of (x, y) inside the image, without the pixel itself.
+fn neighbors(
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    distance: u32,
+) -> impl Iterator<Item = [u32; 2]> {
+    let xs = x.saturating_sub(distance)..(x + distance + 1).min(width);
+    let ys = y.saturating_sub(distance)..(y + distance + 1).min(height);
+    ys.flat_map(move |ny| xs.clone().map(move |nx| [nx, ny]))
+        .filter(move |&n| n != [x, y])
+}
+
+impl HotPixelMap {
+    /// Add the pixels of `other`, replacing the map if it is for another frame size.
+    pub fn merge(&mut self, other: HotPixelMap) {
+        if (self.width, self.height) != (other.width, other.height) {
+            *self = other;
+            return;
+        }
+        self.pixels.extend(other.pixels);
+        self.pixels.sort_by_key(|[x, y]| (*y, *x));
+        self.pixels.dedup();
+    }
+
+    fn is_hot(&self, x: u32, y: u32) -> bool {
+        self.pixels
+            .binary_search_by_key(&(y, x), |[x, y]| (*y, *x))
+            .is_ok()
+    }
+
+    /// Replace the hot pixels by the mean of their neighbors which are not hot. Frames of
+    /// another size are left as they are.
+    pub fn correct<P: Primitive + 'static>(&self, frame: &mut ImageBuffer<Rgb<P>, Vec<P>>)
+    where
+        Rgb<P>: Pixel<Subpixel = P>,
+    {
+        if frame.dimensions() != (self.width, self.height) {
+            return;
+        }
+        for &[x, y] in &self.pixels {
+            let mut sum = [0f32; 3];
+            let mut count = 0;
+            for [nx, ny] in neighbors(x, y, self.width, self.height, 1) {
+                if !self.is_hot(nx, ny) {
+                    let pixel = frame.get_pixel(nx, ny).0;
+                    for (s, v) in sum.iter_mut().zip(pixel) {
+                        *s += v.to_f32().unwrap();
+                    }
+                    count += 1;
+                }
+            }
+            if count > 0 {
+                let pixel = sum.map(|s| P::from((s / count as f32).round()).unwrap());
+                frame.put_pixel(x, y, Rgb(pixel));
+            }
+        }
+    }
+
+    /// Correct a frame of any depth, converting it to 16 bits per channel unless it has 8 or
+    /// 16 bits per RGB channel already.
+    pub fn correct_deep(&self, frame: &mut DynamicImage) {
+        match frame {
+            DynamicImage::ImageRgb8(frame) => self.correct(frame),
+            DynamicImage::ImageRgb16(frame) => self.correct(frame),
+            other => {
+                let mut converted = other.to_rgb16();
+                self.correct(&mut converted);
+                *other = DynamicImage::ImageRgb16(converted);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hot_pixels() {
+        let mut dark = RgbImage::from_pixel(8, 6, Rgb([10, 10, 10]));
+        dark.put_pixel(3, 2, Rgb([200, 20, 20]));
+        dark.put_pixel(4, 2, Rgb([180, 180, 180]));
+        dark.put_pixel(0, 5, Rgb([60, 60, 60]));
+
+        let mut capture = HotPixelCapture::new(0.1, false);
+        for _ in 1..HOT_PIXEL_FRAMES {
+            assert_eq!(capture.add(&dark), None);
+        }
+        let mut map = capture.add(&dark).unwrap();
+        assert_eq!(map.pixels, vec![[3, 2], [4, 2], [0, 5]]);
+
+        // Another capture adds to the map, one of another size replaces it
+        let mut dark = RgbImage::from_pixel(8, 6, Rgb([10, 10, 10]));
+        dark.put_pixel(0, 0, Rgb([255, 255, 255]));
+        let mut capture = HotPixelCapture::new(0.1, true);
+        let found = (0..HOT_PIXEL_FRAMES)
+            .find_map(|_| capture.add(&dark))
+            .unwrap();
+        map.merge(found);
+        assert_eq!(map.pixels, vec![[7, 0], [3, 2], [4, 2], [0, 5]]);
+
+        // Adjacent hot pixels are interpolated from the others only
+        let mut frame = RgbImage::from_fn(8, 6, |x, _| Rgb([x as u8 * 10; 3]));
+        frame.put_pixel(3, 2, Rgb([255, 0, 0]));
+        frame.put_pixel(4, 2, Rgb([255, 255, 255]));
+        map.correct(&mut frame);
+        assert_eq!(frame.get_pixel(3, 2).0, [29; 3]);
+        assert_eq!(frame.get_pixel(4, 2).0, [41; 3]);
+
+        let mut deep = DynamicImage::ImageRgba16(image::ImageBuffer::new(8, 6));
+        map.correct_deep(&mut deep);
+        assert!(matches!(deep, DynamicImage::ImageRgb16(_)));
+
+        let mut other = RgbImage::new(4, 4);
+        map.correct(&mut other);
+        map.merge(HotPixelMap {
+            width: 4,
+            height: 4,
+            pixels: vec![],
+        });
+        assert!(map.pixels.is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+use crate::config::{AxisConfig, AxisUnit, LinearizationPoint, SpectrumPoint};
+use crate::export::{
+    SpectralWorkbenchSpectrum, SPC_FLAG_16_BIT, SPC_FLAG_XVALUES, SPC_FLAG_XYXY,
+    SPC_FLOAT_EXPONENT, SPC_HEADER_SIZE, SPC_INTENSITY_PER_LABEL, SPC_LASER_LABEL, SPC_LOG_OFFSET,
+    SPC_SUBHEADER_SIZE, SPC_VERSION,
+};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Line after which SpectraSuite/OceanView stop listing data.
+const SPECTRA_SUITE_END: &str = ">>>>>End";
+
+/// Read a spectrum exported by third-party software, sorted by wavelength.
+///
+/// `.spc` files are read as Galactic SPC, `.json` files as Spectral Workbench spectra,
+/// everything else, e.g. Theremino Spectrometer files, as delimited text.
+pub fn read_spectrum_file(path: &Path) -> Result<Vec<SpectrumPoint>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let has_extension = |extension: &str| {
+        path.extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case(extension))
+    };
+    if has_extension("spc") {
+        read_spc(BufReader::new(file))
+    } else if has_extension("json") {
+        read_spectral_workbench(BufReader::new(file))
+    } else {
+        read_delimited(BufReader::new(file))
+    }
+}
+
+/// Read the `average` of a calibrated spectrum in the JSON format of the PublicLab Spectral
+/// Workbench.
+pub fn read_spectral_workbench<R: Read>(reader: R) -> Result<Vec<SpectrumPoint>, String> {
+    let spectrum: SpectralWorkbenchSpectrum =
+        serde_json::from_reader(reader).map_err(|e| e.to_string())?;
+    let mut points = spectrum
+        .data
+        .lines
+        .iter()
+        .map(|line| {
+            line.wavelength.map(|wavelength| SpectrumPoint {
+                wavelength,
+                value: line.average,
+            })
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or("Spectral Workbench spectrum is not calibrated")?;
+    if points.is_empty() {
+        return Err("No spectral data found".to_string());
+    }
+    points.sort_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+    Ok(points)
+}
+
+/// Read two columns of wavelength and value separated by tabs, semicolons, commas or spaces.
+///
+/// Header lines like those of SpectraSuite or Spectragryph are skipped. With tabs or
+/// semicolons as separator a decimal comma is accepted as well.
+pub fn read_delimited<R: BufRead>(reader: R) -> Result<Vec<SpectrumPoint>, String> {
+    let mut spectrum = vec![];
+    for line in reader.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.starts_with(SPECTRA_SUITE_END) {
+            break;
+        }
+        let line = if line.contains('\t') || line.contains(';') {
+            line.replace(',', ".")
+        } else {
+            line
+        };
+        let mut fields = line
+            .split(|c: char| c == '\t' || c == ';' || c == ',' || c.is_whitespace())
+            .filter(|f| !f.is_empty())
+            .map(|f| f.parse::<f32>());
+        if let (Some(Ok(wavelength)), Some(Ok(value))) = (fields.next(), fields.next()) {
+            spectrum.push(SpectrumPoint { wavelength, value });
+        }
+    }
+    if spectrum.is_empty() {
+        return Err("No spectral data found".to_string());
+    }
+    spectrum.sort_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+    Ok(spectrum)
+}
+
+/// Read a custom linearization curve of two columns, the value and the linearized value, like
+/// [read_delimited].
+///
+/// Values are relative to full scale, or 8 bit codes if any is above 1.
+pub fn read_linearization<R: BufRead>(reader: R) -> Result<Vec<LinearizationPoint>, String> {
+    let points = read_delimited(reader).map_err(|_| "No linearization data found".to_string())?;
+    let max = points
+        .iter()
+        .flat_map(|p| [p.wavelength, p.value])
+        .fold(0., f32::max);
+    let scale = if max > 1. { u8::MAX as f32 } else { 1. };
+    let lut: Vec<LinearizationPoint> = points
+        .iter()
+        .map(|p| LinearizationPoint {
+            value: p.wavelength / scale,
+            linear: p.value / scale,
+        })
+        .collect();
+    if lut.len() < 2 {
+        return Err("A linearization needs at least two points".to_string());
+    }
+    if lut
+        .iter()
+        .any(|p| !(0. ..=1.).contains(&p.value) || p.linear < 0.)
+    {
+        return Err("Linearization values out of range".to_string());
+    }
+    if lut.windows(2).any(|p| p[1].linear < p[0].linear) {
+        return Err("Linearized values must not decrease".to_string());
+    }
+    Ok(lut)
+}
+
+/// Read the first subfile of a new format Galactic SPC file.
+///
+/// X values in wavenumbers, eV, GHz or µm are converted to nm, for a Raman shift the laser
+/// wavelength is taken from the log block. Intensities per axis unit, as noted in the log block
+/// by [crate::export::write_spc], are converted back to intensities per nm.
+pub fn read_spc<R: Read>(mut reader: R) -> Result<Vec<SpectrumPoint>, String> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data).map_err(|e| e.to_string())?;
+    if data.len() < SPC_HEADER_SIZE {
+        return Err("File too short for SPC".to_string());
+    }
+    if data[1] != SPC_VERSION {
+        return Err("Only little endian new format SPC files are supported".to_string());
+    }
+    let flags = data[0];
+    if flags & SPC_FLAG_XYXY != 0 {
+        return Err("SPC files with separate X values per subfile are not supported".to_string());
+    }
+
+    let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+    let f64_at = |i: usize| f64::from_le_bytes(data[i..i + 8].try_into().unwrap());
+    let n = u32_at(4) as usize;
+    let (first, last) = (f64_at(8), f64_at(16));
+    let float = data[3] == SPC_FLOAT_EXPONENT;
+    let exponent = data[3] as i8;
+    let (axis, x_factor) = match data[28] {
+        0 | 3 => (AxisUnit::Nanometer, 1.),
+        1 => (AxisUnit::Wavenumber, 1.),
+        2 => (AxisUnit::Nanometer, 1000.),
+        13 => (AxisUnit::RamanShift, 1.),
+        14 => (AxisUnit::ElectronVolt, 1.),
+        26 => (AxisUnit::Terahertz, 0.001),
+        t => return Err(format!("Unsupported SPC X unit {}", t)),
+    };
+    let axis = AxisConfig {
+        unit: axis,
+        laser_wavelength: match axis {
+            AxisUnit::RamanShift => spc_log_value(&data, SPC_LASER_LABEL)
+                .and_then(|w| w.parse().ok())
+                .ok_or("Raman shift SPC file without laser wavelength")?,
+            _ => 0.,
+        },
+        jacobian: spc_log_value(&data, SPC_INTENSITY_PER_LABEL).as_deref() == Some(axis.symbol()),
+        ..Default::default()
+    };
+
+    let x_size = if flags & SPC_FLAG_XVALUES != 0 {
+        4 * n
+    } else {
+        0
+    };
+    let y_offset = SPC_HEADER_SIZE + x_size + SPC_SUBHEADER_SIZE;
+    let y_size = if flags & SPC_FLAG_16_BIT != 0 { 2 } else { 4 } * n;
+    if n == 0 || data.len() < y_offset + y_size {
+        return Err("SPC file is truncated".to_string());
+    }
+
+    let x = |i: usize| {
+        if flags & SPC_FLAG_XVALUES != 0 {
+            let o = SPC_HEADER_SIZE + 4 * i;
+            f32::from_le_bytes(data[o..o + 4].try_into().unwrap())
+        } else {
+            (first + (last - first) * i as f64 / (n - 1).max(1) as f64) as f32
+        }
+    };
+    let y = |i: usize| {
+        if flags & SPC_FLAG_16_BIT != 0 {
+            let o = y_offset + 2 * i;
+            let v = i16::from_le_bytes(data[o..o + 2].try_into().unwrap());
+            v as f32 * 2f32.powi(exponent as i32 - 16)
+        } else {
+            let o = y_offset + 4 * i;
+            let bytes = data[o..o + 4].try_into().unwrap();
+            if float {
+                f32::from_le_bytes(bytes)
+            } else {
+                i32::from_le_bytes(bytes) as f32 * 2f32.powi(exponent as i32 - 32)
+            }
+        }
+    };
+
+    let mut spectrum: Vec<_> = (0..n)
+        .map(|i| {
+            let wavelength = axis.to_wavelength(x(i) * x_factor);
+            SpectrumPoint {
+                wavelength,
+                value: y(i) / axis.jacobian(wavelength),
+            }
+        })
+        .collect();
+    spectrum.sort_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+    Ok(spectrum)
+}
+
+/// Value after `label` as written by [crate::export::write_spc] to the log block, up to the
+/// next whitespace.
+fn spc_log_value(data: &[u8], label: &str) -> Option<String> {
+    let offset =
+        u32::from_le_bytes(data[SPC_LOG_OFFSET..SPC_LOG_OFFSET + 4].try_into().unwrap()) as usize;
+    let text = String::from_utf8_lossy(data.get(offset..)?);
+    let start = text.find(label)? + label.len();
+    text[start..].split_whitespace().next().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SpectrumCalibration;
+    use crate::export::{write_spc, write_spectral_workbench, write_theremino};
+    use crate::spectrum::SpectrumExportPoint;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn delimited() {
+        let spectra_suite = "SpectraSuite Data File\n\
+            Integration Time (usec): 100000\n\
+            >>>>>Begin Processed Spectral Data<<<<<\n\
+            400,5\t12,25\n\
+            399,9\t10\n\
+            >>>>>End Processed Spectral Data<<<<<\n\
+            1000\t1\n";
+        assert_eq!(
+            read_delimited(spectra_suite.as_bytes()).unwrap(),
+            vec![
+                SpectrumPoint {
+                    wavelength: 399.9,
+                    value: 10.
+                },
+                SpectrumPoint {
+                    wavelength: 400.5,
+                    value: 12.25
+                }
+            ]
+        );
+
+        let spectragryph = "Wavelength [nm],Intensity\n500.0,1.5\n501.0,2.5\n";
+        let spectrum = read_delimited(spectragryph.as_bytes()).unwrap();
+        assert_eq!(spectrum.len(), 2);
+        assert_eq!(spectrum[1].value, 2.5);
+
+        assert!(read_delimited("no data".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn linearization() {
+        let lut = read_linearization("value,linear\n1,1\n0,0\n0.5,0.2\n".as_bytes()).unwrap();
+        assert_eq!(
+            lut[1],
+            LinearizationPoint {
+                value: 0.5,
+                linear: 0.2
+            }
+        );
+        let codes = read_linearization("0\t0\n255\t255\n".as_bytes()).unwrap();
+        assert_eq!(codes[1].value, 1.);
+
+        assert!(read_linearization("0,0\n".as_bytes()).is_err());
+        assert!(read_linearization("0,0\n0.5,0.6\n1,0.5\n".as_bytes()).is_err());
+        assert!(read_linearization("0,0\n300,1\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn community_formats() {
+        let points: Vec<_> = (0..5)
+            .map(|i| SpectrumExportPoint {
+                wavelength: 400.5 + i as f32,
+                r: 1.,
+                sum: i as f32 * 2.,
+                ..Default::default()
+            })
+            .collect();
+
+        let mut buffer = Vec::new();
+        write_spectral_workbench(&mut buffer, "test", &points).unwrap();
+        let spectrum = read_spectral_workbench(buffer.as_slice()).unwrap();
+        assert_eq!(spectrum.len(), 5);
+        assert_eq!(
+            spectrum[4],
+            SpectrumPoint {
+                wavelength: 404.5,
+                value: 8.
+            }
+        );
+        let uncalibrated = r#"{"title":"x","notes":null,"data":{"name":"x","lines":
+            [{"r":1,"g":2,"b":3,"average":2,"wavelength":null}]}}"#;
+        assert!(read_spectral_workbench(uncalibrated.as_bytes()).is_err());
+
+        let mut buffer = Vec::new();
+        write_theremino(&mut buffer, &points).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert!(text.starts_with("Wavelength (nm)\t"));
+        let spectrum = read_delimited(text.as_bytes()).unwrap();
+        // Resampled to whole nm
+        assert_eq!(spectrum.len(), 4);
+        assert_eq!(
+            spectrum[0],
+            SpectrumPoint {
+                wavelength: 401.,
+                value: 1.
+            }
+        );
+        assert!(write_theremino(&mut Vec::new(), &points[..1]).is_err());
+    }
+
+    #[test]
+    fn spc() {
+        let points: Vec<_> = (0..10)
+            .map(|i| SpectrumExportPoint {
+                wavelength: 400. + i as f32 * 0.5,
+                sum: i as f32,
+                ..Default::default()
+            })
+            .collect();
+        let write = |unit| {
+            let mut buffer = Vec::new();
+            let axis = AxisConfig {
+                unit,
+                jacobian: false,
+                ..Default::default()
+            };
+            write_spc(
+                &mut buffer,
+                "test",
+                &points,
+                &SpectrumCalibration::default(),
+                &axis,
+            )
+            .unwrap();
+            buffer
+        };
+
+        let buffer = write(AxisUnit::Nanometer);
+        let spectrum = read_spc(buffer.as_slice()).unwrap();
+        assert_eq!(spectrum.len(), 10);
+        assert_eq!(spectrum[9].wavelength, 404.5);
+        assert_eq!(spectrum[9].value, 9.);
+        for unit in [AxisUnit::Terahertz, AxisUnit::RamanShift] {
+            let spectrum = read_spc(write(unit).as_slice()).unwrap();
+            assert_relative_eq!(spectrum[9].wavelength, 404.5, max_relative = 1e-5);
+            assert_eq!(spectrum[9].value, 9.);
+        }
+
+        // Intensities per eV are read back per nm
+        let axis = AxisConfig {
+            unit: AxisUnit::ElectronVolt,
+            ..Default::default()
+        };
+        let mut buffer = Vec::new();
+        write_spc(
+            &mut buffer,
+            "test",
+            &points,
+            &SpectrumCalibration::default(),
+            &axis,
+        )
+        .unwrap();
+        let spectrum = read_spc(buffer.as_slice()).unwrap();
+        assert_relative_eq!(
+            spectrum[9].value,
+            9. / axis.jacobian(404.5),
+            max_relative = 1e-4
+        );
+
+        // Evenly spaced X with scaled integer Y
+        let mut header = vec![0u8; SPC_HEADER_SIZE + SPC_SUBHEADER_SIZE];
+        header[1] = SPC_VERSION;
+        header[3] = 32;
+        header[4..8].copy_from_slice(&3u32.to_le_bytes());
+        header[8..16].copy_from_slice(&500f64.to_le_bytes());
+        header[16..24].copy_from_slice(&510f64.to_le_bytes());
+        for v in [1i32, 2, 3] {
+            header.extend_from_slice(&v.to_le_bytes());
+        }
+        let spectrum = read_spc(header.as_slice()).unwrap();
+        assert_eq!(spectrum[1].wavelength, 505.);
+        assert_eq!(spectrum[2].value, 3.);
+
+        assert!(read_spc(&buffer[..100]).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::config::{LampCheckConfig, SpectrumCalibration, TimeOfDay};
+use chrono::{DateTime, Local, TimeZone};
+use std::fs::OpenOptions;
+use std::path::Path;
+
+/// Result of a measurement of the calibration lamp.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LampCheck {
+    pub time: DateTime<Local>,
+    /// Measured minus true wavelength of each line, `None` if it was not found.
+    pub deviations: Vec<Option<f32>>,
+    /// Mean deviation of the lines found.
+    pub drift: Option<f32>,
+    /// Whether the drift was corrected.
+    pub corrected: bool,
+}
+
+/// Measured minus true wavelength of each of the `lines`, from the nearest of the `peaks`
+/// within `search_window` nm.
+pub fn line_deviations(lines: &[f32], peaks: &[f32], search_window: f32) -> Vec<Option<f32>> {
+    lines
+        .iter()
+        .map(|&line| {
+            peaks
+                .iter()
+                .map(|&peak| peak - line)
+                .filter(|deviation| deviation.abs() <= search_window)
+                .min_by(|a, b| a.abs().total_cmp(&b.abs()))
+        })
+        .collect()
+}
+
+/// Times of day like "08:00, 20:30", `None` if one is not valid.
+pub fn parse_times(text: &str) -> Option<Vec<TimeOfDay>> {
+    let mut times = text
+        .split(',')
+        .filter(|t| !t.trim().is_empty())
+        .map(|t| {
+            let (hour, minute) = t.trim().split_once(':')?;
+            let time = TimeOfDay {
+                hour: hour.parse().ok()?,
+                minute: minute.parse().ok()?,
+            };
+            (time.hour < 24 && time.minute < 60).then_some(time)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    times.sort();
+    Some(times)
+}
+
+pub fn format_times(times: &[TimeOfDay]) -> String {
+    times
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl LampCheckConfig {
+    /// Whether one of the scheduled times passed after `since` and up to `now`.
+    pub fn is_due(&self, since: DateTime<Local>, now: DateTime<Local>) -> bool {
+        let since = since.naive_local();
+        let now = now.naive_local();
+        self.times.iter().any(|time| {
+            let latest = now
+                .date()
+                .and_hms_opt(time.hour, time.minute, 0)
+                .map(|today| {
+                    if today > now {
+                        today - chrono::Duration::days(1)
+                    } else {
+                        today
+                    }
+                });
+            latest.is_some_and(|latest| latest > since)
+        })
+    }
+
+    /// Time of the last check, `None` if there was none.
+    pub fn last_check(&self) -> Option<DateTime<Local>> {
+        Local.timestamp_opt(self.last_check?, 0).single()
+    }
+
+    /// Measure the drift of the lines from the `peaks`, wavelengths in nm under the current
+    /// `calibration`, and correct it if that is configured and the drift within bounds.
+    pub fn check(&self, peaks: &[f32], calibration: &mut SpectrumCalibration) -> LampCheck {
+        let deviations = line_deviations(&self.lines, peaks, self.search_window);
+        let found: Vec<f32> = deviations.iter().flatten().copied().collect();
+        let drift = (!found.is_empty()).then(|| found.iter().sum::<f32>() / found.len() as f32);
+        let corrected = self.auto_correct && drift.is_some_and(|d| d.abs() <= self.max_correction);
+        if corrected {
+            calibration.offset -= drift.unwrap();
+        }
+        LampCheck {
+            time: Local::now(),
+            deviations,
+            drift,
+            corrected,
+        }
+    }
+}
+
+/// Append the check to the CSV log at `path`, with a header if the file is new.
+pub fn append_to_log(path: &Path, check: &LampCheck, lines: &[f32]) -> Result<(), String> {
+    let new = !path.exists();
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(file);
+    if new {
+        let mut header = vec![
+            "time".to_string(),
+            "drift".to_string(),
+            "corrected".to_string(),
+        ];
+        header.extend(lines.iter().map(|line| format!("{} nm", line)));
+        writer.write_record(&header).map_err(|e| e.to_string())?;
+    }
+    let optional = |value: Option<f32>| value.map_or(String::new(), |v| v.to_string());
+    let mut record = vec![
+        check.time.to_rfc3339(),
+        optional(check.drift),
+        check.corrected.to_string(),
+    ];
+    record.extend(check.deviations.iter().map(|&d| optional(d)));
+    writer.write_record(&record).map_err(|e| e.to_string())?;
+    writer.flush().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn lamp_check() {
+        let times = parse_times("20:30, 8:00").unwrap();
+        assert_eq!(format_times(&times), "08:00, 20:30");
+        assert_eq!(parse_times("8:00, 24:00"), None);
+        assert_eq!(parse_times("noon"), None);
+
+        let config = LampCheckConfig {
+            times,
+            lines: vec![435.83, 546.07, 611.6],
+            search_window: 3.,
+            max_correction: 1.,
+            ..Default::default()
+        };
+        let at = |day, hour, minute| {
+            Local
+                .with_ymd_and_hms(2024, 3, day, hour, minute, 0)
+                .unwrap()
+        };
+        assert!(config.is_due(at(1, 7, 0), at(1, 8, 0)));
+        assert!(!config.is_due(at(1, 8, 0), at(1, 20, 0)));
+        assert!(config.is_due(at(1, 21, 0), at(2, 8, 30)));
+        assert!(config.is_due(at(1, 19, 0), at(2, 1, 0)));
+
+        let mut calibration = SpectrumCalibration::default();
+        let check = config.check(&[436.33, 546.67, 700.], &mut calibration);
+        assert_eq!(check.deviations[2], None);
+        assert_relative_eq!(check.drift.unwrap(), 0.55, epsilon = 1e-4);
+        assert!(check.corrected);
+        assert_relative_eq!(calibration.offset, -0.55, epsilon = 1e-4);
+
+        // Too large drifts are only logged
+        let check = config.check(&[437.83], &mut calibration);
+        assert!(!check.corrected);
+        assert_relative_eq!(calibration.offset, -0.55, epsilon = 1e-4);
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("lamp-check.csv");
+        append_to_log(&path, &check, &config.lines).unwrap();
+        append_to_log(&path, &check, &config.lines).unwrap();
+        let log = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = log.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "time,drift,corrected,435.83 nm,546.07 nm,611.6 nm"
+        );
+        assert!(lines[2].ends_with(",2,false,2,,"));
+    }
+}
@@ -1,12 +1,73 @@
+pub mod acquisition;
+pub mod auto_exposure;
+pub mod band_monitor;
+pub mod baseline;
 pub mod camera;
+pub mod camera_stream;
+pub mod color_rendering;
+pub mod colorimetry;
 pub mod config;
+pub mod dark_library;
+pub mod database;
+pub mod deconvolution;
+pub mod device_control;
+pub mod engine;
+pub mod export;
+pub mod expression;
+pub mod filename_template;
+pub mod filter_pair;
+pub mod filter_wheel;
+pub mod fingerprint;
+pub mod flicker;
+pub mod frame_integrity;
+pub mod frame_settling;
+#[cfg(target_os = "linux")]
+pub mod gpio;
 pub mod gui;
+pub mod hdf5;
+pub mod hdr;
+pub mod hook;
+pub mod hot_pixels;
+pub mod import;
+pub mod lamp_check;
+pub mod line_identification;
+pub mod linearity;
+pub mod lock_in;
+pub mod network_camera;
+pub mod notifications;
+pub mod peak_diff;
+pub mod peak_fit;
+pub mod photobiology;
+pub mod pixel_format;
+pub mod power;
+pub mod qe_curves;
+pub mod quantitation;
+pub mod recording;
+pub mod reference_library;
+pub mod report;
+pub mod resampling;
+pub mod rest_api;
+pub mod roi;
+pub mod script;
 pub mod serde;
+pub mod session;
+pub mod smoothing;
 pub mod spectrum;
+pub mod spectrum_log;
+pub mod spectrum_stream;
+pub mod synthetic_camera;
+pub mod test_pattern;
 pub mod tungsten_halogen;
+pub mod websocket;
+pub mod widgets;
+pub mod window_detection;
 
 use log::{set_max_level, LevelFilter};
 use simple_logger::SimpleLogger;
+use std::sync::Arc;
+
+/// Wakes the event loop of an idle GUI, for results of background threads.
+pub type Waker = Arc<dyn Fn() + Send + Sync>;
 
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum ThreadId {
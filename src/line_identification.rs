@@ -0,0 +1,197 @@
+//! Identification of discharge lamps and flames from the wavelengths of their peaks.
+
+use std::ops::RangeInclusive;
+
+/// Prominent emission lines in air in nm with their approximate relative strength, from the
+/// NIST Atomic Spectra Database.
+const EMISSION_LINES: [(&str, &[(f32, f32)]); 8] = [
+    (
+        "Hydrogen",
+        &[(656.28, 1.), (486.13, 0.6), (434.05, 0.3), (410.17, 0.15)],
+    ),
+    (
+        "Helium",
+        &[
+            (587.56, 1.),
+            (667.82, 0.5),
+            (706.52, 0.5),
+            (501.57, 0.4),
+            (492.19, 0.2),
+            (471.31, 0.2),
+            (447.15, 0.5),
+            (388.87, 0.4),
+        ],
+    ),
+    (
+        "Neon",
+        &[
+            (585.25, 1.),
+            (640.22, 1.),
+            (614.31, 0.7),
+            (703.24, 0.7),
+            (692.95, 0.6),
+            (667.83, 0.5),
+            (659.90, 0.4),
+            (650.65, 0.6),
+            (638.30, 0.6),
+            (633.44, 0.5),
+            (626.65, 0.4),
+            (621.73, 0.3),
+            (616.36, 0.4),
+            (609.62, 0.5),
+            (607.43, 0.4),
+            (603.00, 0.3),
+            (594.48, 0.5),
+            (588.19, 0.5),
+            (540.06, 0.3),
+        ],
+    ),
+    (
+        "Argon",
+        &[
+            (696.54, 0.6),
+            (706.72, 0.6),
+            (738.40, 0.6),
+            (750.39, 0.9),
+            (763.51, 1.),
+            (772.38, 0.6),
+            (794.82, 0.6),
+            (800.62, 0.6),
+            (811.53, 1.),
+            (826.45, 0.6),
+            (842.46, 0.7),
+            (852.14, 0.5),
+            (415.86, 0.2),
+            (419.83, 0.2),
+            (420.07, 0.2),
+            (425.94, 0.2),
+            (430.01, 0.2),
+            (433.36, 0.2),
+        ],
+    ),
+    (
+        "Mercury",
+        &[
+            (404.66, 0.6),
+            (407.78, 0.2),
+            (435.83, 1.),
+            (546.07, 1.),
+            (576.96, 0.5),
+            (579.07, 0.5),
+        ],
+    ),
+    (
+        "Sodium",
+        &[
+            (589.00, 1.),
+            (589.59, 1.),
+            (568.82, 0.1),
+            (615.42, 0.1),
+            (818.33, 0.3),
+            (819.48, 0.3),
+        ],
+    ),
+    (
+        "Krypton",
+        &[
+            (557.03, 0.8),
+            (587.09, 1.),
+            (431.96, 0.4),
+            (437.61, 0.4),
+            (760.15, 0.8),
+            (811.29, 0.8),
+        ],
+    ),
+    (
+        "Xenon",
+        &[
+            (467.12, 1.),
+            (462.43, 0.7),
+            (823.16, 1.),
+            (828.01, 0.8),
+            (881.94, 0.8),
+        ],
+    ),
+];
+
+/// Line of a source matched by a detected peak.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LineMatch {
+    pub line: f32,
+    pub peak: f32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Candidate {
+    pub source: &'static str,
+    /// Strength weighted share of the lines within the spectrum that were found, from 0 to 1,
+    /// lowered for peaks further from the lines.
+    pub score: f32,
+    pub matches: Vec<LineMatch>,
+    /// Lines of the source within the spectrum.
+    pub lines_in_range: usize,
+}
+
+/// Rank the sources whose lines within `range` match the `peaks`, all in nm, by how well.
+///
+/// A line is matched by the closest peak at most `tolerance` away. Peaks of other sources do
+/// not lower the score, so mixtures like a fluorescent lamp's mercury and argon are found as
+/// well. Sources without a matched line are left out.
+pub fn identify(peaks: &[f32], range: RangeInclusive<f32>, tolerance: f32) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = EMISSION_LINES
+        .iter()
+        .filter_map(|(source, lines)| {
+            let lines: Vec<_> = lines.iter().filter(|(w, _)| range.contains(w)).collect();
+            let total: f32 = lines.iter().map(|(_, strength)| strength).sum();
+            let mut score = 0.;
+            let mut matches = vec![];
+            for &&(line, strength) in &lines {
+                let closest = peaks
+                    .iter()
+                    .copied()
+                    .min_by(|a, b| (a - line).abs().total_cmp(&(b - line).abs()))
+                    .filter(|peak| (peak - line).abs() <= tolerance);
+                if let Some(peak) = closest {
+                    score += strength * (1. - (peak - line).abs() / tolerance / 2.);
+                    matches.push(LineMatch { line, peak });
+                }
+            }
+            (!matches.is_empty()).then(|| Candidate {
+                source,
+                score: score / total,
+                matches,
+                lines_in_range: lines.len(),
+            })
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identification() {
+        // Compact fluorescent lamp: mercury lines, a europium phosphor peak and argon
+        let peaks = [404.8, 436.0, 546.2, 578.1, 611.6, 763.4, 811.7];
+        let candidates = identify(&peaks, 380.0..=850.0, 1.5);
+        assert_eq!(candidates[0].source, "Mercury");
+        assert!(candidates[0].score > 0.8);
+        assert_eq!(candidates[0].lines_in_range, 6);
+        assert!(candidates[0].matches.contains(&LineMatch {
+            line: 546.07,
+            peak: 546.2
+        }));
+        assert!(candidates.iter().any(|c| c.source == "Argon"));
+
+        // Only lines within the spectrum count
+        let candidates = identify(&[656.3, 486.1], 450.0..=700.0, 1.);
+        assert_eq!(candidates[0].source, "Hydrogen");
+        assert_eq!(candidates[0].lines_in_range, 2);
+        assert!(candidates[0].score > 0.9);
+
+        assert!(identify(&[], 380.0..=780.0, 1.).is_empty());
+    }
+}
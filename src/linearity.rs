@@ -0,0 +1,261 @@
+//! Characterization of the sensor response by sweeping the exposure, for a custom
+//! linearization instead of the gamma curves of the video standards.
+
+use crate::config::LinearizationPoint;
+use crate::frame_settling::{FrameSettling, FrameStep};
+use image::{ImageBuffer, Rgb};
+use std::ops::RangeInclusive;
+
+/// Frames averaged per exposure.
+const CAPTURE_FRAMES: usize = 3;
+/// Fraction of full scale from which the region counts as saturated and the sweep ends.
+const SATURATION_LEVEL: f32 = 0.95;
+/// Fraction of full scale below which values are dominated by the black level and noise.
+const BLACK_LEVEL: f32 = 0.02;
+
+/// Mean value of the region at one exposure.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ResponsePoint {
+    pub exposure: i32,
+    /// Relative to full scale.
+    pub value: f32,
+}
+
+/// What the GUI has to do for the current camera frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SweepStep {
+    /// Set the exposure control to the value.
+    Expose(i32),
+    /// Every exposure is measured, go back to the initial one.
+    Done,
+}
+
+/// Steps the camera exposure from the shortest to the longest one, recording the response of
+/// the brightest column of the window until it saturates.
+#[derive(Debug)]
+pub struct LinearitySweep {
+    initial_exposure: i32,
+    exposures: Vec<i32>,
+    /// Column and channel of the region, chosen on the first frame.
+    region: Option<(u32, usize)>,
+    index: usize,
+    frames: FrameSettling,
+    captured: Vec<f32>,
+    points: Vec<ResponsePoint>,
+}
+
+impl LinearitySweep {
+    /// Sweep over `steps` logarithmically spaced exposures of `range`, starting from the
+    /// frames at `initial_exposure`.
+    pub fn new(initial_exposure: i32, range: RangeInclusive<i32>, steps: usize) -> Self {
+        let (first, last) = ((*range.start()).max(1) as f32, (*range.end()).max(1) as f32);
+        let steps = steps.max(2);
+        let mut exposures: Vec<i32> = (0..steps)
+            .map(|i| (first * (last / first).powf(i as f32 / (steps - 1) as f32)).round() as i32)
+            .collect();
+        exposures.dedup();
+        Self {
+            initial_exposure,
+            exposures,
+            region: None,
+            index: 0,
+            frames: FrameSettling::new(CAPTURE_FRAMES),
+            captured: vec![],
+            points: vec![],
+        }
+    }
+
+    pub fn initial_exposure(&self) -> i32 {
+        self.initial_exposure
+    }
+
+    /// Index of the exposure currently measured and the number of exposures.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.index, self.exposures.len())
+    }
+
+    pub fn points(&self) -> &[ResponsePoint] {
+        &self.points
+    }
+
+    /// Advance by one camera frame with the spectrum `window` cut from it.
+    pub fn next_frame(&mut self, window: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Option<SweepStep> {
+        let (column, channel) = match self.region {
+            Some(region) => region,
+            None => {
+                // The brightest column at the initial exposure saturates first
+                self.region = brightest_column(window);
+                return Some(SweepStep::Expose(self.exposures[0]));
+            }
+        };
+        if self.frames.is_settling() {
+            self.frames.next_frame();
+            return None;
+        }
+        if column >= window.width() || window.height() == 0 {
+            return Some(SweepStep::Done);
+        }
+        let value = (0..window.height())
+            .map(|y| window.get_pixel(column, y)[channel] as f32)
+            .sum::<f32>()
+            / (window.height() as f32 * u8::MAX as f32);
+        self.captured.push(value);
+        if self.frames.next_frame() != Some(FrameStep::Change) {
+            return None;
+        }
+
+        let value = self.captured.drain(..).sum::<f32>() / CAPTURE_FRAMES as f32;
+        self.points.push(ResponsePoint {
+            exposure: self.exposures[self.index],
+            value,
+        });
+        if value >= SATURATION_LEVEL || self.index + 1 == self.exposures.len() {
+            return Some(SweepStep::Done);
+        }
+        self.index += 1;
+        Some(SweepStep::Expose(self.exposures[self.index]))
+    }
+}
+
+/// Column and channel with the highest mean of the window.
+fn brightest_column(window: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Option<(u32, usize)> {
+    (0..window.width())
+        .flat_map(|x| (0..3).map(move |c| (x, c)))
+        .max_by_key(|&(x, c)| {
+            (0..window.height())
+                .map(|y| window.get_pixel(x, y)[c] as u32)
+                .sum::<u32>()
+        })
+}
+
+/// Sensor response fitted to the points of a sweep.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResponseFit {
+    /// Exponent of the power law fitted to the response, roughly 2 for sRGB like cameras.
+    pub gamma: f32,
+    /// Linearization curve from the measured points, with the linear values proportional to
+    /// the exposure and normalized to the exposure reaching full scale.
+    pub lut: Vec<LinearizationPoint>,
+}
+
+/// Fit a power law to the unsaturated points above the black level and derive the
+/// linearization curve from them.
+///
+/// The exposure reaching full scale is extrapolated from the two brightest points, as camera
+/// curves often deviate from a power law towards black.
+pub fn fit_response(points: &[ResponsePoint]) -> Result<ResponseFit, String> {
+    let usable: Vec<(f32, f32)> = points
+        .iter()
+        .filter(|p| p.value > BLACK_LEVEL && p.value < SATURATION_LEVEL && p.exposure > 0)
+        .map(|p| (p.exposure as f32, p.value))
+        .collect();
+    if usable.len() < 3 {
+        return Err(
+            "Too few points between black level and saturation, use a brighter source".to_string(),
+        );
+    }
+
+    // Least squares line of ln(value) over ln(exposure)
+    let n = usable.len() as f32;
+    let (sx, sy, sxx, sxy) = usable
+        .iter()
+        .fold((0., 0., 0., 0.), |(sx, sy, sxx, sxy), (e, v)| {
+            let (x, y) = (e.ln(), v.ln());
+            (sx + x, sy + y, sxx + x * x, sxy + x * y)
+        });
+    let slope = (n * sxy - sx * sy) / (n * sxx - sx * sx);
+    let intercept = (sy - slope * sx) / n;
+    if !slope.is_finite() || slope <= 0. {
+        return Err("The response does not increase with the exposure".to_string());
+    }
+
+    let mut measured = usable;
+    measured.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let (e1, v1) = measured[measured.len() - 2];
+    let (e2, v2) = measured[measured.len() - 1];
+    let local_slope = (v2 / v1).ln() / (e2 / e1).ln();
+    let full_scale_exposure = if local_slope.is_finite() && local_slope > 0. {
+        e2 * v2.powf(-1. / local_slope)
+    } else {
+        (-intercept / slope).exp()
+    };
+
+    let mut lut = vec![LinearizationPoint {
+        value: 0.,
+        linear: 0.,
+    }];
+    for (exposure, value) in measured {
+        // Keep the curve monotonic despite noise
+        let linear = (exposure / full_scale_exposure).max(lut[lut.len() - 1].linear);
+        lut.push(LinearizationPoint { value, linear });
+    }
+    let last = lut[lut.len() - 1].linear;
+    lut.push(LinearizationPoint {
+        value: 1.,
+        linear: last.max(1.),
+    });
+    Ok(ResponseFit {
+        gamma: 1. / slope,
+        lut,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Linearize;
+
+    /// sRGB encoding of a linear value, the inverse of [Linearize::SRgb].
+    fn encode(linear: f32) -> f32 {
+        if linear < 0.0031308 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1. / 2.4) - 0.055
+        }
+    }
+
+    #[test]
+    fn linearity_sweep() {
+        let mut sweep = LinearitySweep::new(100, 1..=2000, 12);
+        assert_eq!(sweep.progress(), (0, 12));
+        // Column 2 is the brightest, it reaches full scale at an exposure of 1000
+        let frame = |exposure: i32| {
+            ImageBuffer::from_fn(4, 2, |x, _| {
+                let linear = exposure as f32 / 1000. * if x == 2 { 1. } else { 0.3 };
+                Rgb([(encode(linear.min(1.)) * 255.).round() as u8; 3])
+            })
+        };
+
+        let mut exposure = 100;
+        let mut steps = 0;
+        loop {
+            match sweep.next_frame(&frame(exposure)) {
+                Some(SweepStep::Expose(e)) => exposure = e,
+                Some(SweepStep::Done) => break,
+                None => {}
+            }
+            steps += 1;
+            assert!(steps < 1000);
+        }
+        assert_eq!(sweep.initial_exposure(), 100);
+        let points = sweep.points();
+        assert_eq!(points[0].exposure, 1);
+        assert!(points.windows(2).all(|p| p[0].exposure < p[1].exposure));
+        // The sweep ends at the first saturated exposure
+        assert!(points[points.len() - 1].value >= SATURATION_LEVEL);
+        assert!(points[points.len() - 1].exposure < 2000);
+
+        let fit = fit_response(points).unwrap();
+        assert!(fit.gamma > 1.5 && fit.gamma < 2.5);
+        let linearize = Linearize::Custom(fit.lut);
+        // Proportional to the light, up to the scale of the extrapolated full scale exposure
+        let scale = linearize.linearize(encode(0.3)) / 0.3;
+        assert!((scale - 1.).abs() < 0.1);
+        for linear in [0.05, 0.1, 0.6, 0.8] {
+            let ratio = linearize.linearize(encode(linear)) / (scale * linear);
+            assert!((ratio - 1.).abs() < 0.05, "{} at {}", ratio, linear);
+        }
+
+        assert!(fit_response(&points[..1]).is_err());
+    }
+}
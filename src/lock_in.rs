@@ -0,0 +1,278 @@
+//! Lock-in detection, so that only the light of a modulated source contributes to the
+//! spectrum and constant ambient light cancels out, e.g. for fluorescence and absorption.
+
+use crate::config::{LockInConfig, LockInReference};
+use crate::frame_settling::{FrameSettling, FrameStep};
+use crate::spectrum::SpectrumRgb;
+use std::f32::consts::{FRAC_PI_2, TAU};
+use std::time::Instant;
+
+/// Frames needed to tell the modulation apart from a constant signal.
+const MIN_MODULATION_FRAMES: usize = 3;
+
+/// What the GUI has to do for the current camera frame.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ToggleStep {
+    /// Switch the source on or off and discard spectra until the change shows.
+    Switch(bool),
+    /// The following spectra are taken with the source on or off.
+    Capture(bool),
+}
+
+/// Switches the source on and off, for the given number of camera frames each.
+#[derive(Debug)]
+pub struct SourceToggling {
+    on: bool,
+    frames: FrameSettling,
+}
+
+impl SourceToggling {
+    /// Starts with the source switched on, which the caller does.
+    pub fn new(frames_per_state: usize) -> Self {
+        Self {
+            on: true,
+            frames: FrameSettling::new(frames_per_state),
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Advance by one camera frame.
+    pub fn next_frame(&mut self) -> Option<ToggleStep> {
+        match self.frames.next_frame()? {
+            FrameStep::Capture => Some(ToggleStep::Capture(self.on)),
+            FrameStep::Change => {
+                self.on = !self.on;
+                Some(ToggleStep::Switch(self.on))
+            }
+        }
+    }
+}
+
+/// Sum of raw spectra and their number.
+#[derive(Debug, Clone, Default)]
+struct Sum {
+    sum: Option<SpectrumRgb>,
+    frames: usize,
+}
+
+impl Sum {
+    fn add(&mut self, spectrum: &SpectrumRgb) {
+        match &mut self.sum {
+            Some(sum) => *sum += spectrum,
+            None => self.sum = Some(spectrum.clone()),
+        }
+        self.frames += 1;
+    }
+
+    fn mean(&self) -> Option<SpectrumRgb> {
+        self.sum.as_ref().map(|sum| sum / self.frames as f32)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Demodulation {
+    /// Difference of the mean spectra with the source on and off, once per on and off cycle,
+    /// see [SourceToggling].
+    Toggled {
+        state: Option<bool>,
+        on: Sum,
+        off: Sum,
+    },
+    /// In-phase component of the spectra over whole periods of a known modulation.
+    Modulation {
+        frequency: f32,
+        periods: usize,
+        start: Option<Instant>,
+        /// Sums of the spectra times the cosine and times the sine of the phase, and of the
+        /// spectra.
+        sums: Option<(SpectrumRgb, SpectrumRgb, SpectrumRgb)>,
+        /// Sums of the cosine, sine and the number of frames.
+        reference: (f32, f32, usize),
+    },
+}
+
+/// Demodulates raw spectra into the spectrum of the modulated source alone.
+#[derive(Debug, Clone)]
+pub struct LockInDemodulator {
+    demodulation: Demodulation,
+}
+
+impl LockInDemodulator {
+    pub fn new(config: &LockInConfig) -> Self {
+        let demodulation = match config.reference {
+            LockInReference::Toggled => Demodulation::Toggled {
+                state: None,
+                on: Sum::default(),
+                off: Sum::default(),
+            },
+            LockInReference::Modulation => Demodulation::Modulation {
+                frequency: config.frequency,
+                periods: config.periods.max(1),
+                start: None,
+                sums: None,
+                reference: (0., 0., 0),
+            },
+        };
+        Self { demodulation }
+    }
+
+    /// State of the toggled source for the following spectra, which are discarded with
+    /// `None` while it switches.
+    pub fn set_state(&mut self, source_on: Option<bool>) {
+        if let Demodulation::Toggled { state, .. } = &mut self.demodulation {
+            *state = source_on;
+        }
+    }
+
+    /// Add a raw spectrum acquired at `time` and return the demodulated spectrum, once a
+    /// cycle of the source is complete. A change of the window width starts over.
+    pub fn add(&mut self, spectrum: SpectrumRgb, time: Instant) -> Option<SpectrumRgb> {
+        match &mut self.demodulation {
+            Demodulation::Toggled { state, on, off } => {
+                let source_on = (*state)?;
+                let ncols = spectrum.ncols();
+                if [&on.sum, &off.sum]
+                    .iter()
+                    .any(|s| s.as_ref().is_some_and(|s| s.ncols() != ncols))
+                {
+                    *on = Sum::default();
+                    *off = Sum::default();
+                }
+                // A cycle ends with the first spectrum of the next on state
+                let demodulated = match (source_on, on.mean(), off.mean()) {
+                    (true, Some(on_mean), Some(off_mean)) => {
+                        *on = Sum::default();
+                        *off = Sum::default();
+                        Some(on_mean - off_mean)
+                    }
+                    _ => None,
+                };
+                if source_on {
+                    on.add(&spectrum);
+                } else {
+                    off.add(&spectrum);
+                }
+                demodulated
+            }
+            Demodulation::Modulation {
+                frequency,
+                periods,
+                start,
+                sums,
+                reference,
+            } => {
+                if sums
+                    .as_ref()
+                    .is_some_and(|s| s.2.ncols() != spectrum.ncols())
+                {
+                    *sums = None;
+                }
+                if sums.is_none() {
+                    *start = Some(time);
+                    *reference = (0., 0., 0);
+                }
+                let elapsed = time
+                    .saturating_duration_since(start.unwrap_or(time))
+                    .as_secs_f32();
+                let (sin, cos) = (TAU * *frequency * elapsed).sin_cos();
+                match sums {
+                    Some((sum_cos, sum_sin, sum)) => {
+                        *sum_cos += &spectrum * cos;
+                        *sum_sin += &spectrum * sin;
+                        *sum += spectrum;
+                    }
+                    None => *sums = Some((&spectrum * cos, &spectrum * sin, spectrum)),
+                }
+                *reference = (reference.0 + cos, reference.1 + sin, reference.2 + 1);
+                let (cos_sum, sin_sum, frames) = *reference;
+                if elapsed * *frequency < *periods as f32 || frames < MIN_MODULATION_FRAMES {
+                    return None;
+                }
+
+                let (sum_cos, sum_sin, sum) = sums.take()?;
+                let n = frames as f32;
+                // Without the constant part, which whole periods do not always cancel when
+                // sampled by frames
+                let in_phase = sum_cos - &sum * (cos_sum / n);
+                let quadrature = sum_sin - &sum * (sin_sum / n);
+                // The phase of the source is unknown, take the one of the total signal so
+                // that the result keeps its sign
+                let phase = quadrature.sum().atan2(in_phase.sum());
+                let (sin, cos) = phase.sin_cos();
+                // Amplitude of the fundamental, scaled to the on-off difference of a square
+                // wave modulation like that of a chopper or a switched LED
+                Some((in_phase * cos + quadrature * sin) * (2. / n * FRAC_PI_2))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use std::time::Duration;
+
+    #[test]
+    fn lock_in() {
+        let mut toggling = SourceToggling::new(2);
+        let steps: Vec<_> = (0..6).map(|_| toggling.next_frame()).collect();
+        assert_eq!(
+            steps,
+            vec![
+                None,
+                None,
+                Some(ToggleStep::Capture(true)),
+                None,
+                Some(ToggleStep::Switch(false)),
+                None
+            ]
+        );
+        assert!(!toggling.is_on());
+
+        // Constant ambient light of 0.2 plus 0.1 of the source in the second column
+        let config = LockInConfig::default();
+        let spectrum =
+            |on: bool| SpectrumRgb::from_fn(2, |_, c| 0.2 + if on { 0.1 } else { 0. } * c as f32);
+        let mut toggled = LockInDemodulator::new(&LockInConfig {
+            reference: LockInReference::Toggled,
+            ..config.clone()
+        });
+        let now = Instant::now();
+        assert_eq!(toggled.add(spectrum(true), now), None);
+        for on in [true, false, false] {
+            toggled.set_state(Some(on));
+            assert_eq!(toggled.add(spectrum(on), now), None);
+        }
+        toggled.set_state(None);
+        assert_eq!(toggled.add(spectrum(true), now), None);
+        toggled.set_state(Some(true));
+        let demodulated = toggled.add(spectrum(true), now).unwrap();
+        assert_relative_eq!(demodulated[(0, 0)], 0., epsilon = 1e-6);
+        assert_relative_eq!(demodulated[(1, 1)], 0.1, epsilon = 1e-6);
+
+        // Source switched at 5 Hz, sampled at 30 fps
+        let mut modulation = LockInDemodulator::new(&LockInConfig {
+            reference: LockInReference::Modulation,
+            frequency: 5.,
+            periods: 2,
+            ..config
+        });
+        let mut demodulated = None;
+        for i in 0..100 {
+            let time = now + Duration::from_secs_f32(i as f32 / 30. + 0.01);
+            let on = (time - now).as_secs_f32() * 5. % 1. < 0.5;
+            if let Some(spectrum) = modulation.add(spectrum(on), time) {
+                demodulated = Some(spectrum);
+                break;
+            }
+        }
+        let demodulated = demodulated.unwrap();
+        assert_relative_eq!(demodulated[(0, 0)], 0., epsilon = 1e-3);
+        // The frames sample the square wave coarsely
+        assert_relative_eq!(demodulated[(2, 1)], 0.1, max_relative = 0.1);
+    }
+}
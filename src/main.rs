@@ -4,12 +4,14 @@ use glium::texture::RawImage2d;
 use glium::texture::SrgbTexture2d;
 use glium::Surface as _;
 use glium::{glutin, Display};
-use spectro_cam_rs::camera::CameraThread;
+use spectro_cam_rs::camera_stream::CameraStream;
 use spectro_cam_rs::config::SpectrometerConfig;
+use spectro_cam_rs::engine::SpectroEngine;
 use spectro_cam_rs::gui::SpectrometerGui;
 use spectro_cam_rs::init_logging;
-use spectro_cam_rs::spectrum::SpectrumCalculator;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 fn create_display(
     event_loop: &glutin::event_loop::EventLoop<()>,
@@ -61,25 +63,31 @@ fn main() {
 
     let texture_id = register_webcam_texture(&display, &mut egui_glium);
 
-    let (frame_tx, frame_rx) = flume::unbounded();
-    let (window_tx, window_rx) = flume::unbounded();
-    let (spectrum_tx, spectrum_rx) = flume::unbounded();
-    let (config_tx, config_rx) = flume::unbounded();
-    let (result_tx, result_rx) = flume::unbounded();
+    let engine = SpectroEngine::default();
+    let stream = CameraStream::spawn(engine.clone());
+    let frame_rx = stream.frame_rx.clone();
 
-    std::thread::spawn(move || CameraThread::new(frame_tx, window_tx, config_rx, result_tx).run());
-    std::thread::spawn(move || SpectrumCalculator::new(window_rx, spectrum_tx).run());
+    // Background threads wake the idle event loop with a user event
+    let proxy = Mutex::new(event_loop.create_proxy());
+    let waker = Arc::new(move || {
+        proxy.lock().unwrap().send_event(()).ok();
+    });
 
-    let mut gui = SpectrometerGui::new(texture_id, config_tx, spectrum_rx, config, result_rx);
+    let mut gui = SpectrometerGui::new(texture_id, stream, config, engine, waker);
 
     event_loop.run(move |event, _, control_flow| {
-        if let Ok(frame) = frame_rx.try_recv() {
-            let dim = frame.dimensions();
-            let image = RawImage2d::from_raw_rgb(frame.into_raw(), dim);
-            let tex = SrgbTexture2d::new(&display, image).unwrap();
-            egui_glium
-                .painter
-                .replace_native_texture(texture_id, Rc::new(tex));
+        // Only the newest frame is shown if the camera outruns the GUI
+        if let Some(frame) = frame_rx.drain().last() {
+            gui.inspect_frame(&frame);
+            // No preview texture upload in low power mode
+            if !gui.low_power_active() {
+                let dim = frame.dimensions();
+                let image = RawImage2d::from_raw_rgb(frame.into_raw(), dim);
+                let tex = SrgbTexture2d::new(&display, image).unwrap();
+                egui_glium
+                    .painter
+                    .replace_native_texture(texture_id, Rc::new(tex));
+            }
         };
 
         let mut redraw = || {
@@ -88,10 +96,22 @@ fn main() {
             });
 
             *control_flow = if needs_repaint {
-                display.gl_window().window().request_redraw();
-                glutin::event_loop::ControlFlow::Poll
+                match gui.repaint_interval() {
+                    Some(interval) => {
+                        glutin::event_loop::ControlFlow::WaitUntil(Instant::now() + interval)
+                    }
+                    None => {
+                        display.gl_window().window().request_redraw();
+                        glutin::event_loop::ControlFlow::Poll
+                    }
+                }
             } else {
-                glutin::event_loop::ControlFlow::Wait
+                match gui.idle_wakeup_interval() {
+                    Some(interval) => {
+                        glutin::event_loop::ControlFlow::WaitUntil(Instant::now() + interval)
+                    }
+                    None => glutin::event_loop::ControlFlow::Wait,
+                }
             };
 
             {
@@ -108,6 +128,20 @@ fn main() {
 
                 target.finish().unwrap();
             }
+
+            if gui.plot_image_requested() {
+                match display.read_front_buffer::<RawImage2d<u8>>() {
+                    Ok(screenshot) => gui.save_plot_image(
+                        image::ImageBuffer::from_raw(
+                            screenshot.width,
+                            screenshot.height,
+                            screenshot.data.into_owned(),
+                        )
+                        .unwrap(),
+                    ),
+                    Err(e) => gui.plot_image_failed(format!("{:?}", e)),
+                }
+            }
         };
 
         match event {
@@ -117,6 +151,12 @@ fn main() {
             glutin::event::Event::RedrawEventsCleared if cfg!(windows) => redraw(),
             glutin::event::Event::RedrawRequested(_) if !cfg!(windows) => redraw(),
 
+            glutin::event::Event::NewEvents(glutin::event::StartCause::ResumeTimeReached {
+                ..
+            }) => display.gl_window().window().request_redraw(),
+
+            glutin::event::Event::UserEvent(()) => display.gl_window().window().request_redraw(),
+
             glutin::event::Event::WindowEvent { event, .. } => {
                 use glutin::event::WindowEvent;
                 if matches!(event, WindowEvent::CloseRequested | WindowEvent::Destroyed) {
@@ -0,0 +1,122 @@
+use chrono::{DateTime, Local};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Time a notification is shown as a toast.
+pub const TOAST_DURATION: Duration = Duration::from_secs(5);
+/// Toasts shown at the same time, the oldest ones are only in the history.
+const MAX_TOASTS: usize = 4;
+/// Notifications kept in the history.
+const MAX_HISTORY: usize = 100;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum NotificationLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub message: String,
+    pub time: DateTime<Local>,
+    posted: Instant,
+    dismissed: bool,
+}
+
+/// Events shown as toasts for a short time and kept in a history, so that they are neither
+/// missed nor need to be confirmed.
+#[derive(Debug, Default)]
+pub struct Notifications {
+    history: VecDeque<Notification>,
+}
+
+impl Notifications {
+    /// Post a notification, which is also logged.
+    pub fn push(&mut self, level: NotificationLevel, message: impl Into<String>) {
+        let message = message.into();
+        match level {
+            NotificationLevel::Info => log::info!("{}", message),
+            NotificationLevel::Warning => log::warn!("{}", message),
+            NotificationLevel::Error => log::error!("{}", message),
+        }
+        if self.history.len() == MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(Notification {
+            level,
+            message,
+            time: Local::now(),
+            posted: Instant::now(),
+            dismissed: false,
+        });
+    }
+
+    pub fn info(&mut self, message: impl Into<String>) {
+        self.push(NotificationLevel::Info, message);
+    }
+
+    /// Indices and notifications to show as toasts at `now`, the newest last.
+    pub fn toasts(&self, now: Instant) -> Vec<(usize, &Notification)> {
+        let mut toasts: Vec<_> = self
+            .history
+            .iter()
+            .enumerate()
+            .rev()
+            .take_while(|(_, n)| now.saturating_duration_since(n.posted) < TOAST_DURATION)
+            .filter(|(_, n)| !n.dismissed)
+            .take(MAX_TOASTS)
+            .collect();
+        toasts.reverse();
+        toasts
+    }
+
+    /// Stop showing the notification with the index as a toast.
+    pub fn dismiss(&mut self, index: usize) {
+        if let Some(notification) = self.history.get_mut(index) {
+            notification.dismissed = true;
+        }
+    }
+
+    /// All notifications, the oldest first.
+    pub fn history(&self) -> impl DoubleEndedIterator<Item = &Notification> {
+        self.history.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notifications() {
+        let mut notifications = Notifications::default();
+        for i in 0..6 {
+            notifications.info(format!("Exported {}", i));
+        }
+        notifications.push(NotificationLevel::Error, "Camera lost");
+
+        let now = Instant::now();
+        let toasts = notifications.toasts(now);
+        assert_eq!(toasts.len(), MAX_TOASTS);
+        assert_eq!(toasts.last().unwrap().0, 6);
+        assert_eq!(toasts.last().unwrap().1.level, NotificationLevel::Error);
+
+        notifications.dismiss(6);
+        assert_eq!(notifications.toasts(now)[0].1.message, "Exported 2");
+        assert!(notifications.toasts(now + TOAST_DURATION).is_empty());
+        assert_eq!(notifications.history().count(), 7);
+
+        for _ in 0..MAX_HISTORY {
+            notifications.info("Dark captured");
+        }
+        assert_eq!(notifications.history().count(), MAX_HISTORY);
+        notifications.clear();
+        assert!(notifications.toasts(now).is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+use crate::config::SpectrumPoint;
+use serde::{Deserialize, Serialize};
+
+/// One line of a peak comparison, either a matched pair or a peak of only one spectrum.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+pub struct PeakDiffRow {
+    pub wavelength_a: Option<f32>,
+    pub wavelength_b: Option<f32>,
+    pub value_a: Option<f32>,
+    pub value_b: Option<f32>,
+    /// Wavelength of B minus wavelength of A.
+    pub shift: Option<f32>,
+    /// Value of B divided by value of A.
+    pub ratio: Option<f32>,
+}
+
+impl PeakDiffRow {
+    pub fn is_matched(&self) -> bool {
+        self.shift.is_some()
+    }
+
+    fn wavelength(&self) -> f32 {
+        self.wavelength_a.or(self.wavelength_b).unwrap_or_default()
+    }
+}
+
+/// Match the peaks of `a` and `b` which are at most `tolerance` nm apart.
+///
+/// Pairs are formed greedily, closest first, so every peak is used at most once. The rows are
+/// sorted by wavelength.
+pub fn diff_peaks(a: &[SpectrumPoint], b: &[SpectrumPoint], tolerance: f32) -> Vec<PeakDiffRow> {
+    let mut pairs: Vec<(usize, usize, f32)> = a
+        .iter()
+        .enumerate()
+        .flat_map(|(i, pa)| {
+            b.iter()
+                .enumerate()
+                .map(move |(j, pb)| (i, j, (pb.wavelength - pa.wavelength).abs()))
+        })
+        .filter(|(_, _, distance)| *distance <= tolerance)
+        .collect();
+    pairs.sort_by(|x, y| x.2.total_cmp(&y.2));
+
+    let mut matched_a = vec![false; a.len()];
+    let mut matched_b = vec![false; b.len()];
+    let mut rows = vec![];
+    for (i, j, _) in pairs {
+        if matched_a[i] || matched_b[j] {
+            continue;
+        }
+        matched_a[i] = true;
+        matched_b[j] = true;
+        rows.push(PeakDiffRow {
+            wavelength_a: Some(a[i].wavelength),
+            wavelength_b: Some(b[j].wavelength),
+            value_a: Some(a[i].value),
+            value_b: Some(b[j].value),
+            shift: Some(b[j].wavelength - a[i].wavelength),
+            ratio: Some(b[j].value / a[i].value),
+        });
+    }
+    rows.extend(
+        a.iter()
+            .zip(matched_a)
+            .filter(|(_, m)| !m)
+            .map(|(p, _)| PeakDiffRow {
+                wavelength_a: Some(p.wavelength),
+                value_a: Some(p.value),
+                ..Default::default()
+            }),
+    );
+    rows.extend(
+        b.iter()
+            .zip(matched_b)
+            .filter(|(_, m)| !m)
+            .map(|(p, _)| PeakDiffRow {
+                wavelength_b: Some(p.wavelength),
+                value_b: Some(p.value),
+                ..Default::default()
+            }),
+    );
+    rows.sort_by(|x, y| x.wavelength().total_cmp(&y.wavelength()));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff() {
+        let point = |wavelength, value| SpectrumPoint { wavelength, value };
+        let a = [point(450., 1.), point(500., 2.), point(600., 1.)];
+        let b = [point(452., 2.), point(455., 1.), point(650., 1.)];
+
+        let rows = diff_peaks(&a, &b, 5.);
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[0].shift, Some(2.));
+        assert_eq!(rows[0].ratio, Some(2.));
+        assert_eq!(rows[1].wavelength_b, Some(455.));
+        assert!(!rows[1].is_matched());
+        assert_eq!(rows[2].wavelength_a, Some(500.));
+        assert_eq!(rows[2].wavelength_b, None);
+        assert_eq!(rows.iter().filter(|r| r.is_matched()).count(), 1);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::config::{PeakShape, SpectrumPoint};
+use nalgebra::{Matrix3, Vector3};
+
+/// Fraction of the peak height above which points are used for the fit.
+const FIT_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct PeakFit {
+    pub center: f32,
+    pub fwhm: f32,
+    /// Height above the baseline.
+    pub amplitude: f32,
+    pub baseline: f32,
+}
+
+/// Fit a peak shape to the points around the maximum of `points`.
+///
+/// The baseline is the minimum of `points`, only the points above half of the peak height are
+/// used. Gaussians are fitted as parabola to the logarithm, Lorentzians as parabola to the
+/// reciprocal. Returns `None` if there are too few points or the fit is not a peak.
+pub fn fit_peak(points: &[SpectrumPoint], shape: PeakShape) -> Option<PeakFit> {
+    let (peak, max) = points
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.value.total_cmp(&b.1.value))?;
+    let baseline = points.iter().map(|p| p.value).fold(f32::INFINITY, f32::min);
+    let height = max.value - baseline;
+    if height <= 0. {
+        return None;
+    }
+
+    let limit = baseline + FIT_THRESHOLD * height;
+    let start = points[..peak]
+        .iter()
+        .rposition(|p| p.value < limit)
+        .map_or(0, |i| i + 1);
+    let end = points[peak..]
+        .iter()
+        .position(|p| p.value < limit)
+        .map_or(points.len(), |i| peak + i);
+    let core = &points[start..end];
+    if core.len() < 3 {
+        return None;
+    }
+
+    // Least squares fit of a + b * x + c * x² relative to the maximum for better conditioning
+    let mut normal = Matrix3::<f64>::zeros();
+    let mut rhs = Vector3::<f64>::zeros();
+    for p in core {
+        let x = (p.wavelength - max.wavelength) as f64;
+        let v = (p.value - baseline) as f64;
+        let y = match shape {
+            PeakShape::Gaussian => v.ln(),
+            PeakShape::Lorentzian => 1. / v,
+        };
+        let basis = Vector3::new(1., x, x * x);
+        normal += basis * basis.transpose();
+        rhs += basis * y;
+    }
+    let coefficients = normal.lu().solve(&rhs)?;
+    let (a, b, c) = (coefficients[0], coefficients[1], coefficients[2]);
+
+    let offset = -b / (2. * c);
+    let vertex = a - b * b / (4. * c);
+    let (amplitude, fwhm) = match shape {
+        PeakShape::Gaussian if c < 0. => {
+            let sigma = (-1. / (2. * c)).sqrt();
+            (vertex.exp(), 2. * (2. * 2f64.ln()).sqrt() * sigma)
+        }
+        PeakShape::Lorentzian if c > 0. && vertex > 0. => (1. / vertex, 2. * (vertex / c).sqrt()),
+        _ => return None,
+    };
+
+    let center = max.wavelength + offset as f32;
+    (center >= core[0].wavelength && center <= core[core.len() - 1].wavelength).then_some(PeakFit {
+        center,
+        fwhm: fwhm as f32,
+        amplitude: amplitude as f32,
+        baseline,
+    })
+}
+
+/// Fit with the center closest to `wavelength`, at most `tolerance` away.
+pub fn closest_fit(fits: &[PeakFit], wavelength: f32, tolerance: f32) -> Option<PeakFit> {
+    fits.iter()
+        .copied()
+        .min_by(|a, b| {
+            (a.center - wavelength)
+                .abs()
+                .total_cmp(&(b.center - wavelength).abs())
+        })
+        .filter(|fit| (fit.center - wavelength).abs() <= tolerance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use rstest::*;
+
+    #[rstest]
+    #[case(PeakShape::Gaussian)]
+    #[case(PeakShape::Lorentzian)]
+    fn fit(#[case] shape: PeakShape) {
+        let (center, fwhm, amplitude, baseline) = (500.3, 6., 100., 10.);
+        let points: Vec<_> = (480..=520)
+            .map(|w| {
+                let x = (w as f32 - center) / fwhm;
+                let value = match shape {
+                    PeakShape::Gaussian => (-4. * 2f32.ln() * x * x).exp(),
+                    PeakShape::Lorentzian => 1. / (1. + 4. * x * x),
+                };
+                SpectrumPoint {
+                    wavelength: w as f32,
+                    value: baseline + amplitude * value,
+                }
+            })
+            .collect();
+
+        let fit = fit_peak(&points, shape).unwrap();
+        assert_relative_eq!(fit.center, center, max_relative = 1e-4);
+        assert_relative_eq!(fit.fwhm, fwhm, max_relative = 0.02);
+        assert_relative_eq!(
+            fit.amplitude + fit.baseline,
+            amplitude + baseline,
+            max_relative = 0.02
+        );
+
+        assert_eq!(fit_peak(&points[..2], shape), None);
+
+        let other = PeakFit {
+            center: 507.,
+            ..fit
+        };
+        assert_eq!(closest_fit(&[other, fit], 501., 5.), Some(fit));
+        assert_eq!(closest_fit(&[other, fit], 490., 5.), None);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::config::{SpectrumPoint, WavelengthBand};
+use std::fmt::{Display, Formatter};
+
+const C: f64 = physical_constants::SPEED_OF_LIGHT_IN_VACUUM;
+const H: f64 = physical_constants::PLANCK_CONSTANT;
+const N_A: f64 = physical_constants::AVOGADRO_CONSTANT;
+
+/// Photosynthetically active radiation.
+pub const PAR: WavelengthBand = WavelengthBand {
+    start: 400.,
+    end: 700.,
+};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PhotonBand {
+    Blue,
+    Green,
+    Red,
+    FarRed,
+}
+
+impl PhotonBand {
+    pub const ALL: [PhotonBand; 4] = [
+        PhotonBand::Blue,
+        PhotonBand::Green,
+        PhotonBand::Red,
+        PhotonBand::FarRed,
+    ];
+
+    pub fn band(&self) -> WavelengthBand {
+        let (start, end) = match self {
+            PhotonBand::Blue => (400., 500.),
+            PhotonBand::Green => (500., 600.),
+            PhotonBand::Red => (600., 700.),
+            PhotonBand::FarRed => (700., 750.),
+        };
+        WavelengthBand { start, end }
+    }
+}
+
+impl Display for PhotonBand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhotonBand::Blue => write!(f, "Blue"),
+            PhotonBand::Green => write!(f, "Green"),
+            PhotonBand::Red => write!(f, "Red"),
+            PhotonBand::FarRed => write!(f, "Far-Red"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Photobiology {
+    /// Photosynthetic photon flux density in µmol·m⁻²·s⁻¹ for a spectrum in W·m⁻²·nm⁻¹.
+    pub ppfd: f32,
+    /// Photon flux of each [PhotonBand] relative to the total from 400 to 750 nm.
+    pub fractions: [f32; 4],
+}
+
+/// Photon flux in µmol·m⁻²·s⁻¹ per W·m⁻²·nm⁻¹ at `wavelength` in nm.
+fn photons_per_energy(wavelength: f32) -> f32 {
+    (wavelength as f64 * 1e-9 / (H * C * N_A) * 1e6) as f32
+}
+
+/// Trapezoidal integral of the photon flux over the points of the spectrum inside `band`.
+pub fn photon_flux(spectrum: &[SpectrumPoint], band: WavelengthBand) -> f32 {
+    spectrum
+        .iter()
+        .filter(|sp| band.contains(sp.wavelength))
+        .map(|sp| (sp.wavelength, sp.value * photons_per_energy(sp.wavelength)))
+        .collect::<Vec<_>>()
+        .windows(2)
+        .map(|p| (p[1].0 - p[0].0) * (p[0].1 + p[1].1) / 2.)
+        .sum()
+}
+
+/// PPFD and band fractions of a spectrum sorted by wavelength.
+///
+/// Returns `None` if there is no photon flux from 400 to 750 nm.
+pub fn photobiology(spectrum: &[SpectrumPoint]) -> Option<Photobiology> {
+    let fluxes = PhotonBand::ALL.map(|b| photon_flux(spectrum, b.band()));
+    let total: f32 = fluxes.iter().sum();
+    if total.is_nan() || total <= 0. {
+        return None;
+    }
+    Some(Photobiology {
+        ppfd: photon_flux(spectrum, PAR),
+        fractions: fluxes.map(|f| f / total),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn ppfd() {
+        // 1 W·m⁻²·nm⁻¹ around 550 nm, 11 nm wide with the trapezoidal edges
+        let spectrum: Vec<_> = (400..=800)
+            .map(|w| SpectrumPoint {
+                wavelength: w as f32,
+                value: if (545..=555).contains(&w) { 1. } else { 0. },
+            })
+            .collect();
+        let p = photobiology(&spectrum).unwrap();
+        assert_relative_eq!(p.ppfd, 11. * 4.598, max_relative = 1e-3);
+        assert_eq!(p.fractions, [0., 1., 0., 0.]);
+
+        let flat: Vec<_> = (300..=800)
+            .map(|w| SpectrumPoint {
+                wavelength: w as f32,
+                value: 1.,
+            })
+            .collect();
+        let p = photobiology(&flat).unwrap();
+        assert_relative_eq!(p.fractions.iter().sum::<f32>(), 1., max_relative = 1e-5);
+        assert!(p.fractions[0] < p.fractions[1] && p.fractions[1] < p.fractions[2]);
+
+        assert_eq!(photobiology(&[]), None);
+    }
+}
@@ -0,0 +1,224 @@
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// Order of the color filters of a Bayer sensor, named after the first two pixels of the
+/// first two rows.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BayerPattern {
+    Rggb,
+    Grbg,
+    Gbrg,
+    Bggr,
+}
+
+impl BayerPattern {
+    /// Channel (red 0, green 1, blue 2) of the filter over the pixel at `x`, `y`.
+    fn channel(&self, x: u32, y: u32) -> usize {
+        let channels = match self {
+            BayerPattern::Rggb => [0, 1, 1, 2],
+            BayerPattern::Grbg => [1, 0, 2, 1],
+            BayerPattern::Gbrg => [1, 2, 0, 1],
+            BayerPattern::Bggr => [2, 1, 1, 0],
+        };
+        channels[(y % 2 * 2 + x % 2) as usize]
+    }
+}
+
+impl Display for BayerPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BayerPattern::Rggb => write!(f, "RGGB"),
+            BayerPattern::Grbg => write!(f, "GRBG"),
+            BayerPattern::Gbrg => write!(f, "GBRG"),
+            BayerPattern::Bggr => write!(f, "BGGR"),
+        }
+    }
+}
+
+/// Pixel formats read from the camera without the conversion to 8-bit RGB of the camera API.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PixelFormat {
+    /// Grayscale with the given number of significant bits.
+    Gray { bits: u8 },
+    /// Raw sensor data behind a Bayer color filter with the given number of significant bits.
+    Bayer { pattern: BayerPattern, bits: u8 },
+}
+
+/// V4L2 four character codes of the supported formats. Samples with more than 8 bits are
+/// stored little endian in 16 bits.
+const FOURCCS: [(&[u8; 4], PixelFormat); 16] = [
+    (b"GREY", PixelFormat::Gray { bits: 8 }),
+    (b"Y10 ", PixelFormat::Gray { bits: 10 }),
+    (b"Y12 ", PixelFormat::Gray { bits: 12 }),
+    (b"Y16 ", PixelFormat::Gray { bits: 16 }),
+    (b"RGGB", bayer(BayerPattern::Rggb, 8)),
+    (b"GRBG", bayer(BayerPattern::Grbg, 8)),
+    (b"GBRG", bayer(BayerPattern::Gbrg, 8)),
+    (b"BA81", bayer(BayerPattern::Bggr, 8)),
+    (b"RG10", bayer(BayerPattern::Rggb, 10)),
+    (b"BA10", bayer(BayerPattern::Grbg, 10)),
+    (b"GB10", bayer(BayerPattern::Gbrg, 10)),
+    (b"BG10", bayer(BayerPattern::Bggr, 10)),
+    (b"RG12", bayer(BayerPattern::Rggb, 12)),
+    (b"BA12", bayer(BayerPattern::Grbg, 12)),
+    (b"GB12", bayer(BayerPattern::Gbrg, 12)),
+    (b"BG12", bayer(BayerPattern::Bggr, 12)),
+];
+
+const fn bayer(pattern: BayerPattern, bits: u8) -> PixelFormat {
+    PixelFormat::Bayer { pattern, bits }
+}
+
+impl PixelFormat {
+    pub fn from_fourcc(fourcc: &[u8; 4]) -> Option<Self> {
+        FOURCCS
+            .iter()
+            .find(|(code, _)| *code == fourcc)
+            .map(|(_, format)| *format)
+    }
+
+    pub fn fourcc(&self) -> &'static [u8; 4] {
+        FOURCCS.iter().find(|(_, f)| f == self).unwrap().0
+    }
+
+    pub fn bits(&self) -> u8 {
+        match self {
+            PixelFormat::Gray { bits } | PixelFormat::Bayer { bits, .. } => *bits,
+        }
+    }
+
+    /// Bytes per pixel in a frame.
+    pub fn sample_bytes(&self) -> usize {
+        if self.bits() > 8 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Convert a frame with `stride` bytes per line to RGB with 16 bits per channel, scaling
+    /// the significant bits to full scale.
+    ///
+    /// Bayer data is interpolated bilinearly: the channels missing at a pixel are the mean of
+    /// the neighbouring pixels with that filter. Lines are converted in parallel.
+    pub fn decode(
+        &self,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+    ) -> Result<ImageBuffer<Rgb<u16>, Vec<u16>>, String> {
+        let bytes = self.sample_bytes();
+        let line = width as usize * bytes;
+        if height > 0 && data.len() < stride * (height as usize - 1) + line || stride < line {
+            return Err(format!(
+                "Frame of {} bytes is too short for {}x{} {}",
+                data.len(),
+                width,
+                height,
+                self
+            ));
+        }
+        if width == 0 || height == 0 {
+            return Ok(ImageBuffer::new(width, height));
+        }
+        let (columns, rows) = (width as usize, height as usize);
+        let max = (1u32 << self.bits()) - 1;
+        // Samples scaled to full scale, line by line
+        let mut samples = vec![0u16; columns * rows];
+        samples
+            .par_chunks_mut(columns)
+            .zip(data.par_chunks(stride))
+            .for_each(|(samples, line)| {
+                for (sample, value) in samples.iter_mut().zip(line.chunks_exact(bytes)) {
+                    let value = if bytes == 2 {
+                        u16::from_le_bytes([value[0], value[1]]) as u32
+                    } else {
+                        value[0] as u32
+                    };
+                    *sample = (value.min(max) * u16::MAX as u32 / max) as u16;
+                }
+            });
+        let mut rgb = vec![0u16; 3 * samples.len()];
+        match self {
+            PixelFormat::Gray { .. } => rgb
+                .par_chunks_mut(3 * columns)
+                .zip(samples.par_chunks(columns))
+                .for_each(|(rgb, samples)| {
+                    for (pixel, &sample) in rgb.chunks_exact_mut(3).zip(samples) {
+                        pixel.fill(sample);
+                    }
+                }),
+            PixelFormat::Bayer { pattern, .. } => rgb
+                .par_chunks_mut(3 * columns)
+                .enumerate()
+                .for_each(|(y, rgb)| {
+                    let neighbours = y.saturating_sub(1)..=(y + 1).min(rows - 1);
+                    for (x, pixel) in rgb.chunks_exact_mut(3).enumerate() {
+                        let mut sums = [0u32; 3];
+                        let mut counts = [0u32; 3];
+                        for ny in neighbours.clone() {
+                            for nx in x.saturating_sub(1)..=(x + 1).min(columns - 1) {
+                                let channel = pattern.channel(nx as u32, ny as u32);
+                                sums[channel] += samples[ny * columns + nx] as u32;
+                                counts[channel] += 1;
+                            }
+                        }
+                        let own = pattern.channel(x as u32, y as u32);
+                        for (channel, value) in pixel.iter_mut().enumerate() {
+                            *value = if channel == own {
+                                samples[y * columns + x]
+                            } else {
+                                (sums[channel] / counts[channel].max(1)) as u16
+                            };
+                        }
+                    }
+                }),
+        }
+        Ok(ImageBuffer::from_raw(width, height, rgb).unwrap())
+    }
+}
+
+impl Display for PixelFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PixelFormat::Gray { bits } => write!(f, "{}-bit Gray", bits),
+            PixelFormat::Bayer { pattern, bits } => write!(f, "{}-bit Bayer {}", bits, pattern),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_format_decode() {
+        let y10 = PixelFormat::from_fourcc(b"Y10 ").unwrap();
+        assert_eq!(y10, PixelFormat::Gray { bits: 10 });
+        assert_eq!(y10.fourcc(), b"Y10 ");
+        assert_eq!(PixelFormat::from_fourcc(b"MJPG"), None);
+
+        // Two pixels per line and a padding byte
+        let data = [0xff, 0x03, 0x00, 0x02, 0, 0x00, 0x00, 0x00, 0x00, 0];
+        let frame = y10.decode(&data, 2, 2, 5).unwrap();
+        assert_eq!(frame.get_pixel(0, 0).0, [u16::MAX; 3]);
+        assert_eq!(frame.get_pixel(1, 0).0, [32799; 3]);
+        assert_eq!(frame.get_pixel(1, 1).0, [0; 3]);
+        assert!(y10.decode(&data[..8], 2, 2, 5).is_err());
+
+        // Uniformly lit sensor with a different value behind each filter
+        let rggb = PixelFormat::from_fourcc(b"RGGB").unwrap();
+        let data: Vec<u8> = (0..4 * 4)
+            .map(|i| match BayerPattern::Rggb.channel(i % 4, i / 4) {
+                0 => 200,
+                1 => 100,
+                _ => 50,
+            })
+            .collect();
+        let frame = rggb.decode(&data, 4, 4, 4).unwrap();
+        assert!(frame.pixels().all(|p| p.0 == [51400, 25700, 12850]));
+    }
+}
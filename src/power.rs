@@ -0,0 +1,36 @@
+#[cfg(target_os = "linux")]
+use std::fs;
+
+/// Check if the system is running on battery, i.e. there is a battery and no external supply.
+#[cfg(target_os = "linux")]
+pub fn on_battery() -> bool {
+    let entries = match fs::read_dir("/sys/class/power_supply") {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    let mut has_battery = false;
+    let mut external_online = false;
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let typ = fs::read_to_string(path.join("type")).unwrap_or_default();
+        match typ.trim() {
+            "Battery" => has_battery = true,
+            "Mains" | "USB" => {
+                external_online |= fs::read_to_string(path.join("online"))
+                    .map(|online| online.trim() == "1")
+                    .unwrap_or(false)
+            }
+            _ => {}
+        }
+    }
+
+    has_battery && !external_online
+}
+
+/// Without a way to query the power supply, the system is assumed to run on external supply.
+#[cfg(not(target_os = "linux"))]
+pub fn on_battery() -> bool {
+    false
+}
@@ -0,0 +1,107 @@
+use crate::config::{QeCurve, QePoint};
+
+const WAVELENGTHS: [f32; 13] = [
+    400., 425., 450., 475., 500., 525., 550., 575., 600., 625., 650., 675., 700.,
+];
+
+/// Approximate (r, g, b) quantum efficiency of common sensors, read from published charts.
+///
+/// Only meant as a starting point if the sensitivity was not measured.
+const SENSORS: [(&str, [[f32; 13]; 3]); 4] = [
+    (
+        "Sony IMX219",
+        [
+            [
+                0.03, 0.03, 0.03, 0.03, 0.04, 0.05, 0.08, 0.20, 0.50, 0.55, 0.48, 0.38, 0.28,
+            ],
+            [
+                0.05, 0.08, 0.15, 0.28, 0.50, 0.62, 0.60, 0.45, 0.18, 0.08, 0.05, 0.04, 0.04,
+            ],
+            [
+                0.30, 0.45, 0.55, 0.52, 0.38, 0.20, 0.08, 0.04, 0.03, 0.03, 0.03, 0.03, 0.03,
+            ],
+        ],
+    ),
+    (
+        "Sony IMX290",
+        [
+            [
+                0.04, 0.04, 0.04, 0.04, 0.06, 0.08, 0.12, 0.30, 0.70, 0.80, 0.78, 0.70, 0.60,
+            ],
+            [
+                0.08, 0.12, 0.22, 0.40, 0.66, 0.78, 0.75, 0.55, 0.24, 0.12, 0.09, 0.10, 0.13,
+            ],
+            [
+                0.40, 0.58, 0.68, 0.65, 0.48, 0.26, 0.10, 0.05, 0.04, 0.04, 0.05, 0.07, 0.10,
+            ],
+        ],
+    ),
+    (
+        "OmniVision OV2640",
+        [
+            [
+                0.02, 0.02, 0.02, 0.02, 0.03, 0.04, 0.06, 0.15, 0.38, 0.42, 0.36, 0.28, 0.20,
+            ],
+            [
+                0.04, 0.06, 0.10, 0.20, 0.38, 0.46, 0.44, 0.32, 0.12, 0.05, 0.03, 0.03, 0.04,
+            ],
+            [
+                0.22, 0.35, 0.42, 0.40, 0.28, 0.14, 0.06, 0.03, 0.02, 0.02, 0.02, 0.03, 0.04,
+            ],
+        ],
+    ),
+    (
+        "OmniVision OV5647",
+        [
+            [
+                0.02, 0.02, 0.03, 0.03, 0.04, 0.05, 0.07, 0.18, 0.45, 0.50, 0.43, 0.34, 0.25,
+            ],
+            [
+                0.05, 0.07, 0.12, 0.24, 0.44, 0.54, 0.52, 0.38, 0.15, 0.06, 0.04, 0.04, 0.05,
+            ],
+            [
+                0.25, 0.38, 0.46, 0.44, 0.32, 0.16, 0.07, 0.03, 0.03, 0.03, 0.03, 0.03, 0.04,
+            ],
+        ],
+    ),
+];
+
+pub fn bundled_qe_curves() -> Vec<QeCurve> {
+    SENSORS
+        .iter()
+        .map(|(name, [r, g, b])| QeCurve {
+            name: name.to_string(),
+            points: WAVELENGTHS
+                .iter()
+                .enumerate()
+                .map(|(i, &wavelength)| QePoint {
+                    wavelength,
+                    r: r[i],
+                    g: g[i],
+                    b: b[i],
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_curves() {
+        let curves = bundled_qe_curves();
+        assert_eq!(curves.len(), SENSORS.len());
+        for curve in curves {
+            assert!(curve
+                .points
+                .windows(2)
+                .all(|p| p[0].wavelength < p[1].wavelength));
+            assert!(curve
+                .points
+                .iter()
+                .all(|p| [p.r, p.g, p.b].iter().all(|qe| *qe > 0. && *qe <= 1.)));
+        }
+    }
+}
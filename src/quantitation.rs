@@ -0,0 +1,151 @@
+use crate::config::{QuantitationConfig, ReferenceBeam, SpectrumPoint, WavelengthBand};
+
+/// Decadic absorbance of `sample` relative to `blank`, averaged over `bandwidth` nm around
+/// `wavelength`. `None` if either has no points there or the blank no light.
+pub fn absorbance(
+    sample: &[SpectrumPoint],
+    blank: &[SpectrumPoint],
+    wavelength: f32,
+    bandwidth: f32,
+) -> Option<f32> {
+    let band = WavelengthBand {
+        start: wavelength - bandwidth / 2.,
+        end: wavelength + bandwidth / 2.,
+    };
+    let sample = band.mean_value(sample)?;
+    let blank = band.mean_value(blank)?;
+    (blank > 0.).then(|| ReferenceBeam::Absorbance.apply(sample, blank))
+}
+
+/// Straight line of absorbance over concentration, which Beer–Lambert predicts for dilute
+/// solutions.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CalibrationCurve {
+    pub slope: f32,
+    pub intercept: f32,
+    /// Coefficient of determination, 1 for standards exactly on the line.
+    pub r_squared: f32,
+}
+
+impl CalibrationCurve {
+    /// Least squares fit to concentration and absorbance pairs. `None` with less than two
+    /// different concentrations.
+    pub fn fit(points: &[[f32; 2]]) -> Option<Self> {
+        let n = points.len() as f32;
+        let mean_c = points.iter().map(|p| p[0]).sum::<f32>() / n;
+        let mean_a = points.iter().map(|p| p[1]).sum::<f32>() / n;
+        let variance = points.iter().map(|p| (p[0] - mean_c).powi(2)).sum::<f32>();
+        if points.len() < 2 || variance <= 0. {
+            return None;
+        }
+        let slope = points
+            .iter()
+            .map(|p| (p[0] - mean_c) * (p[1] - mean_a))
+            .sum::<f32>()
+            / variance;
+        let intercept = mean_a - slope * mean_c;
+        let total = points.iter().map(|p| (p[1] - mean_a).powi(2)).sum::<f32>();
+        let residual = points
+            .iter()
+            .map(|p| (p[1] - slope * p[0] - intercept).powi(2))
+            .sum::<f32>();
+        Some(Self {
+            slope,
+            intercept,
+            r_squared: if total > 0. {
+                1. - residual / total
+            } else {
+                1.
+            },
+        })
+    }
+
+    pub fn absorbance(&self, concentration: f32) -> f32 {
+        self.slope * concentration + self.intercept
+    }
+
+    /// Concentration giving the absorbance, `None` for a flat curve.
+    pub fn concentration(&self, absorbance: f32) -> Option<f32> {
+        (self.slope != 0.).then(|| (absorbance - self.intercept) / self.slope)
+    }
+}
+
+impl QuantitationConfig {
+    /// Absorbance of the spectrum at the configured wavelength, `None` without a blank.
+    pub fn absorbance(&self, spectrum: &[SpectrumPoint]) -> Option<f32> {
+        absorbance(
+            spectrum,
+            self.blank.as_ref()?,
+            self.wavelength,
+            self.bandwidth,
+        )
+    }
+
+    /// Concentration and absorbance of the standards, leaving out those without one.
+    pub fn standard_points(&self) -> Vec<[f32; 2]> {
+        self.standards
+            .iter()
+            .filter_map(|s| Some([s.concentration, self.absorbance(&s.spectrum)?]))
+            .collect()
+    }
+
+    pub fn curve(&self) -> Option<CalibrationCurve> {
+        CalibrationCurve::fit(&self.standard_points())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QuantitationStandard;
+    use approx::assert_relative_eq;
+
+    fn spectrum(transmission: f32) -> Vec<SpectrumPoint> {
+        (0..=100)
+            .map(|i| SpectrumPoint {
+                wavelength: 500. + i as f32,
+                value: if (545..=555).contains(&(500 + i)) {
+                    0.8 * transmission
+                } else {
+                    0.8
+                },
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quantitation() {
+        let mut config = QuantitationConfig {
+            wavelength: 550.,
+            bandwidth: 4.,
+            ..Default::default()
+        };
+        assert_eq!(config.absorbance(&spectrum(0.1)), None);
+        config.blank = Some(spectrum(1.));
+        assert_relative_eq!(config.absorbance(&spectrum(0.1)).unwrap(), 1.);
+
+        // Absorbance of 0.2 per unit of concentration
+        for concentration in [0., 1., 2., 4.] {
+            config.standards.push(QuantitationStandard {
+                concentration,
+                spectrum: spectrum(10f32.powf(-0.2 * concentration)),
+            });
+        }
+        let curve = config.curve().unwrap();
+        assert_relative_eq!(curve.slope, 0.2, max_relative = 1e-4);
+        assert_relative_eq!(curve.intercept, 0., epsilon = 1e-5);
+        assert_relative_eq!(curve.r_squared, 1., max_relative = 1e-4);
+        let unknown = config.absorbance(&spectrum(10f32.powf(-0.5))).unwrap();
+        assert_relative_eq!(
+            curve.concentration(unknown).unwrap(),
+            2.5,
+            max_relative = 1e-4
+        );
+
+        let scattered = CalibrationCurve::fit(&[[0., 0.], [1., 0.3], [2., 0.4]]).unwrap();
+        assert_relative_eq!(scattered.slope, 0.2, max_relative = 1e-4);
+        assert!(scattered.r_squared < 1.);
+        assert_eq!(CalibrationCurve::fit(&[[1., 0.2], [1., 0.3]]), None);
+        assert_eq!(CalibrationCurve::fit(&[]), None);
+    }
+}
@@ -0,0 +1,289 @@
+//! Recording of the raw spectra of a session into a spectrum stream, see
+//! [crate::spectrum_stream], and their replay into the processing.
+//!
+//! A record holds the r, g and b channels of the sample window, followed by those of the
+//! reference window if it was recorded in reference beam mode.
+
+use crate::spectrum::{RawSpectra, SpectrumRgb};
+use crate::spectrum_stream::{
+    decompressing_reader, SpectrumRecord, SpectrumStreamHeader, SpectrumStreamReader,
+    SpectrumStreamWriter,
+};
+use flume::Sender;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Pauses of the recording, e.g. while the acquisition was stopped, are shortened to this.
+const MAX_GAP: Duration = Duration::from_secs(1);
+/// Longest sleep before the stop flag is checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Writes raw spectra with the layout of the first ones, compressed with zstd in frames of
+/// their own, see [crate::spectrum_stream::FrameEncoder], so that a crash only loses the last
+/// frame.
+pub struct Recorder {
+    writer: SpectrumStreamWriter<Box<dyn Write>>,
+    reference: bool,
+    frames: u64,
+}
+
+impl Recorder {
+    /// Replace the file at `path` with a recording of spectra laid out like `spectra`,
+    /// `wavelengths` holds the calibrated wavelength of every column.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        spectra: &RawSpectra,
+        wavelengths: Vec<f32>,
+    ) -> std::io::Result<Self> {
+        File::create(&path)?;
+        let reference = spectra
+            .reference
+            .as_ref()
+            .is_some_and(|r| r.ncols() == spectra.sample.ncols());
+        let header = SpectrumStreamHeader {
+            channels: if reference { 6 } else { 3 },
+            wavelengths,
+        };
+        Ok(Self {
+            writer: SpectrumStreamWriter::append_to_file(path, header, true)?,
+            reference,
+            frames: 0,
+        })
+    }
+
+    /// Append the spectra, which are skipped with `false` if their layout changed.
+    pub fn record(&mut self, spectra: &RawSpectra) -> std::io::Result<bool> {
+        let header = self.writer.header();
+        let points = header.wavelengths.len();
+        let reference = spectra.reference.as_ref().filter(|r| r.ncols() == points);
+        if spectra.sample.ncols() != points || reference.is_some() != self.reference {
+            return Ok(false);
+        }
+        let mut values = Vec::with_capacity(header.record_len());
+        for spectrum in std::iter::once(&spectra.sample).chain(reference) {
+            for row in spectrum.row_iter() {
+                values.extend(row.iter());
+            }
+        }
+        self.writer.write_record(&SpectrumRecord::now(values))?;
+        self.frames += 1;
+        Ok(true)
+    }
+
+    /// Number of spectra recorded so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+}
+
+/// Raw spectra of a record with `points` columns.
+fn record_to_spectra(record: &SpectrumRecord, points: usize) -> RawSpectra {
+    let channels = |offset: usize| {
+        SpectrumRgb::from_fn(points, |channel, column| {
+            record.values[offset + channel * points + column]
+        })
+    };
+    RawSpectra {
+        sample: channels(0),
+        reference: (record.values.len() == 6 * points).then(|| channels(3 * points)),
+    }
+}
+
+/// Thread sending the spectra of a recording with their original timing, stopped when
+/// dropped.
+pub struct Replay {
+    stop: Arc<AtomicBool>,
+    frames: Arc<AtomicU64>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Replay {
+    /// Send the spectra of the recording at `path` to `spectrum_tx`, `speed` times faster than
+    /// they were recorded.
+    ///
+    /// Fails for a `speed` which is not positive and finite.
+    pub fn spawn<P: AsRef<Path>>(
+        path: P,
+        speed: f32,
+        spectrum_tx: Sender<RawSpectra>,
+    ) -> Result<Self, String> {
+        if !(speed > 0. && speed.is_finite()) {
+            return Err(format!("Invalid replay speed {}", speed));
+        }
+        let path = PathBuf::from(path.as_ref());
+        let stop = Arc::new(AtomicBool::new(false));
+        let frames = Arc::new(AtomicU64::new(0));
+        let thread_stop = Arc::clone(&stop);
+        let thread_frames = Arc::clone(&frames);
+        let (ready_tx, ready_rx) = flume::bounded(1);
+        let handle = std::thread::spawn(move || {
+            let reader = File::open(&path)
+                .and_then(decompressing_reader)
+                .and_then(SpectrumStreamReader::new)
+                .map_err(|e| e.to_string())
+                .and_then(|reader| match reader.header().channels {
+                    3 | 6 => Ok(reader),
+                    channels => Err(format!(
+                        "Not a recording of raw spectra: {} channels",
+                        channels
+                    )),
+                });
+            let mut reader = match reader {
+                Ok(reader) => {
+                    let _ = ready_tx.send(Ok(()));
+                    reader
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            let points = reader.header().wavelengths.len();
+            let started = Instant::now();
+            let mut due = Duration::ZERO;
+            let mut previous: Option<i64> = None;
+            loop {
+                let record = match reader.read_record() {
+                    Ok(Some(record)) => record,
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::warn!("Replay of {}: {}", path.display(), e);
+                        break;
+                    }
+                };
+                if let Some(previous) = previous {
+                    let gap = Duration::from_micros((record.timestamp_us - previous).max(0) as u64);
+                    due += gap.min(MAX_GAP).div_f32(speed);
+                }
+                previous = Some(record.timestamp_us);
+                while let Some(wait) = due
+                    .checked_sub(started.elapsed())
+                    .filter(|wait| !wait.is_zero())
+                {
+                    if thread_stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    std::thread::sleep(wait.min(POLL_INTERVAL));
+                }
+                if thread_stop.load(Ordering::Relaxed)
+                    || spectrum_tx
+                        .send(record_to_spectra(&record, points))
+                        .is_err()
+                {
+                    return;
+                }
+                thread_frames.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        ready_rx
+            .recv()
+            .unwrap_or_else(|_| Err("Replay thread failed".to_string()))?;
+        Ok(Self {
+            stop,
+            frames,
+            handle: Some(handle),
+        })
+    }
+
+    /// Number of spectra sent so far.
+    pub fn frames(&self) -> u64 {
+        self.frames.load(Ordering::Relaxed)
+    }
+
+    /// Whether every spectrum of the recording was sent.
+    pub fn is_finished(&self) -> bool {
+        self.handle.as_ref().is_some_and(JoinHandle::is_finished)
+    }
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum_stream::FRAME_BYTES;
+    use std::fs::OpenOptions;
+
+    fn spectra(value: f32, reference: bool) -> RawSpectra {
+        RawSpectra {
+            sample: SpectrumRgb::from_fn(4, |channel, column| {
+                value + channel as f32 + column as f32 / 10.
+            }),
+            reference: reference.then(|| SpectrumRgb::from_element(4, -value)),
+        }
+    }
+
+    #[test]
+    fn recording() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("recording.spcs");
+        let recorded = [spectra(1., true), spectra(2., true)];
+        {
+            let mut recorder =
+                Recorder::create(&path, &recorded[0], vec![400., 500., 600., 700.]).unwrap();
+            assert!(recorder.record(&recorded[0]).unwrap());
+            // Without the reference the layout differs
+            assert!(!recorder.record(&spectra(3., false)).unwrap());
+            assert!(recorder.record(&recorded[1]).unwrap());
+            assert_eq!(recorder.frames(), 2);
+        }
+
+        let (spectrum_tx, spectrum_rx) = flume::unbounded();
+        let replay = Replay::spawn(&path, 100., spectrum_tx).unwrap();
+        let replayed: Vec<RawSpectra> = spectrum_rx.iter().collect();
+        assert_eq!(replay.frames(), 2);
+        assert_eq!(replayed.len(), 2);
+        for (replayed, recorded) in replayed.iter().zip(&recorded) {
+            assert_eq!(replayed.sample, recorded.sample);
+            assert_eq!(replayed.reference, recorded.reference);
+        }
+
+        assert!(Replay::spawn(path.with_extension("missing"), 1., flume::unbounded().0).is_err());
+        for speed in [0., -1., f32::NAN, f32::INFINITY] {
+            assert!(Replay::spawn(&path, speed, flume::unbounded().0).is_err());
+        }
+    }
+
+    #[test]
+    fn truncated_recording() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("recording.spcs");
+        let recorded = spectra(1., true);
+        let frame_records = FRAME_BYTES / (8 + 4 * 6 * 4);
+        let written = 2 * frame_records + 100;
+        {
+            let mut recorder =
+                Recorder::create(&path, &recorded, vec![400., 500., 600., 700.]).unwrap();
+            for _ in 0..written {
+                recorder.record(&recorded).unwrap();
+            }
+        }
+
+        // Killed while writing the last frame
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 5)
+            .unwrap();
+
+        let (spectrum_tx, spectrum_rx) = flume::unbounded();
+        let _replay = Replay::spawn(&path, 1000., spectrum_tx).unwrap();
+        let replayed = spectrum_rx.iter().count();
+        assert!(replayed >= 2 * frame_records - 1);
+        assert!(replayed < written);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::colorimetry::{planck, C2};
+use crate::config::{IntensityUnit, SpectrumPoint};
+
+/// Second radiation constant of the CIE definition of illuminant A in nm·K, which predates
+/// the current value of [`C2`].
+const ILLUMINANT_A_C2: f64 = 1.435e7;
+const ILLUMINANT_A_TEMP: f64 = 2848.;
+
+/// CIE standard illuminant D65 from 300 to 830 nm in 10 nm steps, 100 at 560 nm.
+const D65: [f32; 54] = [
+    0.0341, 3.2945, 20.236, 37.0535, 39.9488, 44.9117, 46.6383, 52.0891, 49.9755, 54.6482, 82.7549,
+    91.486, 93.4318, 86.6823, 104.865, 117.008, 117.812, 114.861, 115.923, 108.811, 109.354,
+    107.802, 104.79, 107.689, 104.405, 104.046, 100., 96.3342, 95.788, 88.6856, 90.0062, 89.5991,
+    87.6987, 83.2886, 83.6992, 80.0268, 80.2146, 82.2778, 78.2842, 69.7213, 71.6091, 74.349,
+    61.604, 69.8856, 75.087, 63.5927, 46.4182, 66.8054, 63.3828, 64.304, 59.4519, 51.959, 57.4406,
+    60.3125,
+];
+
+/// Approximate ASTM G173 AM1.5 global tilt solar spectral irradiance in W·m⁻²·nm⁻¹ from 300
+/// to 1100 nm in 10 nm steps, read from the published chart.
+///
+/// Only meant as a starting point, the absorption bands are smoothed out.
+const AM15G: [f32; 81] = [
+    0.001, 0.05, 0.2, 0.4, 0.45, 0.48, 0.52, 0.63, 0.67, 0.68, 1.08, 1.18, 1.23, 1.13, 1.4, 1.57,
+    1.6, 1.58, 1.61, 1.54, 1.55, 1.56, 1.49, 1.56, 1.52, 1.53, 1.48, 1.5, 1.5, 1.4, 1.47, 1.48,
+    1.48, 1.42, 1.44, 1.39, 1.46, 1.43, 1.4, 1.15, 1.29, 1.3, 1.05, 1.15, 1.25, 1.24, 0.35, 1.15,
+    1.19, 1.12, 1.09, 1.07, 1.05, 1.02, 0.99, 0.96, 0.94, 0.91, 0.88, 0.82, 0.79, 0.77, 0.69, 0.55,
+    0.26, 0.33, 0.45, 0.6, 0.68, 0.72, 0.74, 0.73, 0.71, 0.69, 0.67, 0.66, 0.63, 0.6, 0.56, 0.5,
+    0.44,
+];
+
+/// Phosphor converted white LEDs as (name, CCT, weight of the blue peak, phosphor peak and
+/// FWHM in nm), fitted to the CCT on the Planckian locus.
+///
+/// Models of typical LEDs, not measured ones.
+const WHITE_LEDS: [(&str, f32, f32, f32, f32); 3] = [
+    ("White LED 2700 K (model)", 2700., 0.34, 595., 110.),
+    ("White LED 4000 K (model)", 4000., 0.54, 580., 130.),
+    ("White LED 6500 K (model)", 6500., 0.65, 560., 170.),
+];
+const LED_BLUE_PEAK: f32 = 450.;
+const LED_BLUE_FWHM: f32 = 20.;
+
+/// Reference spectrum to use with [crate::config::ReferenceConfig].
+#[derive(Debug, PartialEq, Clone)]
+pub struct LibraryReference {
+    pub name: String,
+    pub unit: IntensityUnit,
+    pub spectrum: Vec<SpectrumPoint>,
+}
+
+fn table(first: f32, step: f32, values: &[f32], scale: f32) -> Vec<SpectrumPoint> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| SpectrumPoint {
+            wavelength: first + step * i as f32,
+            value: value * scale,
+        })
+        .collect()
+}
+
+fn gaussian(wavelength: f32, peak: f32, fwhm: f32) -> f32 {
+    let sigma = fwhm / (2. * (2. * std::f32::consts::LN_2).sqrt());
+    (-(wavelength - peak).powi(2) / (2. * sigma.powi(2))).exp()
+}
+
+/// CIE standard illuminant A from its definition, normalized to 1 at 560 nm.
+///
+/// The relative radiance only depends on `c2 / T`, so the definition is a black body at a
+/// temperature scaled to the current second radiation constant.
+fn illuminant_a(wavelength: f64) -> f64 {
+    let temperature = ILLUMINANT_A_TEMP * C2 / ILLUMINANT_A_C2;
+    planck(wavelength, temperature) / planck(560., temperature)
+}
+
+/// Standard illuminants, solar and LED references.
+pub fn library_references() -> Vec<LibraryReference> {
+    let mut references = vec![
+        LibraryReference {
+            name: "CIE D65".to_string(),
+            unit: IntensityUnit::Relative,
+            spectrum: table(300., 10., &D65, 0.01),
+        },
+        LibraryReference {
+            name: "CIE Illuminant A".to_string(),
+            unit: IntensityUnit::Relative,
+            spectrum: (300..=830)
+                .step_by(5)
+                .map(|wavelength| SpectrumPoint {
+                    wavelength: wavelength as f32,
+                    value: illuminant_a(wavelength as f64) as f32,
+                })
+                .collect(),
+        },
+        LibraryReference {
+            name: "AM1.5G Solar (approximate)".to_string(),
+            unit: IntensityUnit::SpectralIrradiance,
+            spectrum: table(300., 10., &AM15G, 1.),
+        },
+    ];
+    references.extend(
+        WHITE_LEDS
+            .iter()
+            .map(|&(name, _, blue, phosphor_peak, phosphor_fwhm)| {
+                let spectrum: Vec<_> = (380..=780)
+                    .step_by(2)
+                    .map(|wavelength| {
+                        let wavelength = wavelength as f32;
+                        SpectrumPoint {
+                            wavelength,
+                            value: blue * gaussian(wavelength, LED_BLUE_PEAK, LED_BLUE_FWHM)
+                                + (1. - blue) * gaussian(wavelength, phosphor_peak, phosphor_fwhm),
+                        }
+                    })
+                    .collect();
+                let max = spectrum.iter().map(|p| p.value).fold(0., f32::max);
+                LibraryReference {
+                    name: name.to_string(),
+                    unit: IntensityUnit::Relative,
+                    spectrum: spectrum
+                        .into_iter()
+                        .map(|p| SpectrumPoint {
+                            wavelength: p.wavelength,
+                            value: p.value / max,
+                        })
+                        .collect(),
+                }
+            }),
+    );
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colorimetry::colorimetry;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn library() {
+        let references = library_references();
+        assert_eq!(references.len(), 3 + WHITE_LEDS.len());
+        for reference in &references {
+            assert!(reference
+                .spectrum
+                .windows(2)
+                .all(|p| p[0].wavelength < p[1].wavelength));
+            assert!(reference.spectrum.iter().all(|p| p.value >= 0.));
+        }
+        assert_eq!(references[0].spectrum.last().unwrap().wavelength, 830.);
+        assert_abs_diff_eq!(illuminant_a(560.), 1., epsilon = 1e-9);
+
+        // Chromaticities of the standard illuminants
+        let d65 = colorimetry(&references[0].spectrum).unwrap();
+        assert_abs_diff_eq!(d65.x, 0.3127, epsilon = 0.002);
+        assert_abs_diff_eq!(d65.y, 0.3290, epsilon = 0.002);
+        let a = colorimetry(&references[1].spectrum).unwrap();
+        assert_abs_diff_eq!(a.x, 0.4476, epsilon = 0.002);
+        assert_abs_diff_eq!(a.y, 0.4074, epsilon = 0.002);
+        for (reference, (_, cct, ..)) in references[3..].iter().zip(WHITE_LEDS) {
+            let colorimetry = colorimetry(&reference.spectrum).unwrap();
+            assert_abs_diff_eq!(colorimetry.cct.unwrap(), cct, epsilon = cct * 0.02);
+            assert!(colorimetry.duv.unwrap().abs() < 0.001);
+        }
+    }
+}
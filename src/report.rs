@@ -0,0 +1,326 @@
+use crate::config::{CameraControl, SpectrumCalibration, SpectrumPoint, SpectrumWindow};
+use crate::fingerprint::spectrum_fingerprint;
+use crate::peak_fit::PeakFit;
+use chrono::{DateTime, Local, TimeZone};
+use std::fmt::Write as _;
+use std::io::Write;
+
+const PLOT_WIDTH: f32 = 800.;
+const PLOT_HEIGHT: f32 = 400.;
+/// Space for the axis labels around the plot area.
+const PLOT_MARGIN: f32 = 50.;
+const PLOT_TICKS: usize = 5;
+
+/// Everything shown in a measurement report.
+pub struct Report<'a> {
+    pub title: &'a str,
+    pub time: DateTime<Local>,
+    /// Sum channel in the calibrated unit.
+    pub spectrum: &'a [SpectrumPoint],
+    pub peaks: &'a [PeakFit],
+    /// Name and value of the dashboard values.
+    pub derived_values: &'a [(String, f32)],
+    pub calibration: &'a SpectrumCalibration,
+    pub camera: &'a str,
+    /// Camera format, `None` for sources without one.
+    pub camera_format: Option<String>,
+    pub window: SpectrumWindow,
+    pub camera_controls: &'a [CameraControl],
+    /// Frames averaged into the spectrum.
+    pub averaged_frames: usize,
+}
+
+/// Escape the characters with a meaning in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Line plot of the spectrum as SVG, empty without points.
+pub fn svg_plot(spectrum: &[SpectrumPoint], y_label: &str) -> String {
+    let (first, last) = match (spectrum.first(), spectrum.last()) {
+        (Some(first), Some(last)) if last.wavelength > first.wavelength => (first, last),
+        _ => return String::new(),
+    };
+    let max = spectrum
+        .iter()
+        .map(|p| p.value)
+        .fold(f32::NEG_INFINITY, f32::max);
+    let min = spectrum.iter().map(|p| p.value).fold(0., f32::min);
+    let range = if max > min { max - min } else { 1. };
+    let x = |wavelength: f32| {
+        PLOT_MARGIN
+            + (wavelength - first.wavelength) / (last.wavelength - first.wavelength) * PLOT_WIDTH
+    };
+    let y = |value: f32| PLOT_MARGIN + (1. - (value - min) / range) * PLOT_HEIGHT;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" \
+         font-family=\"sans-serif\" font-size=\"12\">\n\
+         <rect x=\"{m}\" y=\"{m}\" width=\"{pw}\" height=\"{ph}\" fill=\"none\" stroke=\"#888\"/>\n",
+        w = PLOT_WIDTH + 2. * PLOT_MARGIN,
+        h = PLOT_HEIGHT + 2. * PLOT_MARGIN,
+        m = PLOT_MARGIN,
+        pw = PLOT_WIDTH,
+        ph = PLOT_HEIGHT,
+    );
+    for i in 0..=PLOT_TICKS {
+        let fraction = i as f32 / PLOT_TICKS as f32;
+        let wavelength = first.wavelength + fraction * (last.wavelength - first.wavelength);
+        let value = min + fraction * range;
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{:.0}</text>",
+            x(wavelength),
+            PLOT_MARGIN + PLOT_HEIGHT + 18.,
+            wavelength
+        );
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{:.3}</text>",
+            PLOT_MARGIN - 6.,
+            y(value) + 4.,
+            value
+        );
+    }
+    let _ = writeln!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">Wavelength [nm]</text>",
+        PLOT_MARGIN + PLOT_WIDTH / 2.,
+        PLOT_MARGIN + PLOT_HEIGHT + 40.
+    );
+    let _ = writeln!(
+        svg,
+        "<text x=\"{m:.1}\" y=\"{:.1}\">{}</text>",
+        PLOT_MARGIN - 20.,
+        escape(y_label),
+        m = PLOT_MARGIN,
+    );
+    let points: Vec<String> = spectrum
+        .iter()
+        .map(|p| format!("{:.1},{:.1}", x(p.wavelength), y(p.value)))
+        .collect();
+    let _ = writeln!(
+        svg,
+        "<polyline fill=\"none\" stroke=\"#1f77b4\" stroke-width=\"1.5\" points=\"{}\"/>",
+        points.join(" ")
+    );
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Write the report as a single HTML file with the plot embedded as SVG.
+pub fn write_html<W: Write>(writer: &mut W, report: &Report) -> std::io::Result<()> {
+    let calibration = report.calibration;
+    let unit = calibration.intensity_unit();
+    let title = escape(report.title);
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(writer, "<title>{}</title>", title)?;
+    writeln!(
+        writer,
+        "<style>body {{ font-family: sans-serif; margin: 2em; }} \
+         table {{ border-collapse: collapse; margin-bottom: 1em; }} \
+         th, td {{ border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; }}</style>"
+    )?;
+    writeln!(writer, "</head>\n<body>")?;
+    writeln!(writer, "<h1>{}</h1>", title)?;
+    writeln!(
+        writer,
+        "<p>{}, spectro-cam-rs {}</p>",
+        report.time.format("%Y-%m-%d %H:%M:%S"),
+        env!("CARGO_PKG_VERSION")
+    )?;
+    // Same as in the snapshot list and the measurement database
+    writeln!(
+        writer,
+        "<p>Measurement ID {}</p>",
+        spectrum_fingerprint(report.spectrum, unit)
+    )?;
+    writeln!(writer, "{}", svg_plot(report.spectrum, &unit.axis_label()))?;
+
+    writeln!(writer, "<h2>Peaks</h2>")?;
+    if report.peaks.is_empty() {
+        writeln!(writer, "<p>No peaks found.</p>")?;
+    } else {
+        writeln!(
+            writer,
+            "<table>\n<tr><th>Center [nm]</th><th>FWHM [nm]</th><th>Amplitude [{}]</th></tr>",
+            escape(unit.symbol())
+        )?;
+        for peak in report.peaks {
+            writeln!(
+                writer,
+                "<tr><td>{:.2}</td><td>{:.2}</td><td>{:.4}</td></tr>",
+                peak.center, peak.fwhm, peak.amplitude
+            )?;
+        }
+        writeln!(writer, "</table>")?;
+    }
+
+    let row = |writer: &mut W, name: &str, value: &str| {
+        writeln!(
+            writer,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            name,
+            escape(value)
+        )
+    };
+    if !report.derived_values.is_empty() {
+        writeln!(writer, "<h2>Derived Values</h2>\n<table>")?;
+        for (name, value) in report.derived_values {
+            row(writer, &escape(name), &format!("{:.4}", value))?;
+        }
+        writeln!(writer, "</table>")?;
+    }
+
+    writeln!(writer, "<h2>Calibration</h2>\n<table>")?;
+    row(
+        writer,
+        "Low",
+        &format!(
+            "{} nm at index {}",
+            calibration.low.wavelength, calibration.low.index
+        ),
+    )?;
+    row(
+        writer,
+        "High",
+        &format!(
+            "{} nm at index {}",
+            calibration.high.wavelength, calibration.high.index
+        ),
+    )?;
+    row(writer, "Offset", &format!("{} nm", calibration.offset))?;
+    row(writer, "Linearize", &calibration.linearize.to_string())?;
+    row(
+        writer,
+        "Gain RGB",
+        &format!(
+            "{}, {}, {}",
+            calibration.gain_r, calibration.gain_g, calibration.gain_b
+        ),
+    )?;
+    row(writer, "Intensity Unit", unit.symbol())?;
+    row(
+        writer,
+        "QE Curve",
+        calibration.qe_curve.as_ref().map_or("None", |c| &c.name),
+    )?;
+    let modified = calibration
+        .modified
+        .and_then(|m| Local.timestamp_opt(m, 0).single())
+        .map_or("Unknown".to_string(), |m| {
+            m.format("%Y-%m-%d %H:%M").to_string()
+        });
+    row(writer, "Modified", &modified)?;
+    let resolution = calibration
+        .resolution
+        .map_or("Not measured".to_string(), |r| {
+            format!(
+                "{:.2} nm FWHM at {:.1} nm (λ/Δλ {:.0})",
+                r.fwhm,
+                r.wavelength,
+                r.resolving_power()
+            )
+        });
+    row(writer, "Resolution", &resolution)?;
+    writeln!(writer, "</table>")?;
+
+    writeln!(writer, "<h2>Camera</h2>\n<table>")?;
+    row(writer, "Camera", report.camera)?;
+    row(
+        writer,
+        "Format",
+        report.camera_format.as_deref().unwrap_or("None"),
+    )?;
+    row(
+        writer,
+        "Window",
+        &format!(
+            "{}×{} at {}, {}",
+            report.window.size.x,
+            report.window.size.y,
+            report.window.offset.x,
+            report.window.offset.y
+        ),
+    )?;
+    row(
+        writer,
+        "Averaged Frames",
+        &report.averaged_frames.to_string(),
+    )?;
+    for control in report.camera_controls {
+        row(writer, &escape(&control.name), &control.value.to_string())?;
+    }
+    writeln!(writer, "</table>\n</body>\n</html>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IntensityUnit;
+    use egui::Vec2;
+
+    #[test]
+    fn report() {
+        let spectrum: Vec<_> = (0..=100)
+            .map(|i| SpectrumPoint {
+                wavelength: 400. + 3. * i as f32,
+                value: i as f32 / 100.,
+            })
+            .collect();
+        let peaks = [PeakFit {
+            center: 546.07,
+            fwhm: 2.5,
+            amplitude: 0.8,
+            baseline: 0.,
+        }];
+        let controls = [CameraControl {
+            id: 1,
+            name: "Exposure <abs>".to_string(),
+            value: 156,
+        }];
+        let report = Report {
+            title: "Lamp & filter",
+            time: Local::now(),
+            spectrum: &spectrum,
+            peaks: &peaks,
+            derived_values: &[("Ratio".to_string(), 1.5)],
+            calibration: &SpectrumCalibration::default(),
+            camera: "webcam",
+            camera_format: Some("640x480@30 MJPEG".to_string()),
+            window: SpectrumWindow {
+                offset: Vec2::new(0., 200.),
+                size: Vec2::new(640., 20.),
+            },
+            camera_controls: &controls,
+            averaged_frames: 10,
+        };
+        let mut buffer = Vec::new();
+        write_html(&mut buffer, &report).unwrap();
+        let html = String::from_utf8(buffer).unwrap();
+
+        assert!(html.contains("<title>Lamp &amp; filter</title>"));
+        assert!(html.contains("<polyline"));
+        assert!(html.contains(&format!(
+            "<p>Measurement ID {}</p>",
+            spectrum_fingerprint(&spectrum, IntensityUnit::Counts)
+        )));
+        assert!(html.contains("<td>546.07</td><td>2.50</td><td>0.8000</td>"));
+        assert!(html.contains("<tr><th>Low</th><td>436 nm at index 261</td></tr>"));
+        assert!(html.contains("<tr><th>Resolution</th><td>Not measured</td></tr>"));
+        assert!(html.contains("<tr><th>Exposure &lt;abs&gt;</th><td>156</td></tr>"));
+        assert!(html.contains("<tr><th>Averaged Frames</th><td>10</td></tr>"));
+        assert!(html.contains("<tr><th>Ratio</th><td>1.5000</td></tr>"));
+        assert!(html.ends_with("</html>\n"));
+
+        let svg = svg_plot(&spectrum, "Intensity");
+        // First point at the bottom left, last at the top right of the plot area
+        assert!(svg.contains("points=\"50.0,450.0 "));
+        assert!(svg.contains(" 850.0,50.0\""));
+        assert_eq!(svg_plot(&spectrum[..1], "Intensity"), "");
+    }
+}
@@ -0,0 +1,101 @@
+use crate::config::Resampling;
+
+/// Values of the samples `y` at `x`, sorted ascending, at the `grid` positions within `x`.
+pub fn resample(x: &[f32], y: &[f32], grid: &[f32], resampling: Resampling) -> Vec<f32> {
+    match resampling {
+        Resampling::Off => y.to_vec(),
+        Resampling::Linear => grid
+            .iter()
+            .map(|&position| {
+                let (i, t) = segment(x, position);
+                y[i] + t * (y[i + 1] - y[i])
+            })
+            .collect(),
+        Resampling::Spline => {
+            let second_derivatives = spline_second_derivatives(x, y);
+            grid.iter()
+                .map(|&position| {
+                    let (i, t) = segment(x, position);
+                    let h = x[i + 1] - x[i];
+                    let (a, b) = (1. - t, t);
+                    a * y[i]
+                        + b * y[i + 1]
+                        + ((a.powi(3) - a) * second_derivatives[i]
+                            + (b.powi(3) - b) * second_derivatives[i + 1])
+                            * h.powi(2)
+                            / 6.
+                })
+                .collect()
+        }
+    }
+}
+
+/// Index of the interval of `x` containing `position` and the fraction of it up to there.
+fn segment(x: &[f32], position: f32) -> (usize, f32) {
+    let i = x
+        .partition_point(|&v| v <= position)
+        .saturating_sub(1)
+        .min(x.len().saturating_sub(2));
+    let h = x[i + 1] - x[i];
+    let t = if h > 0. { (position - x[i]) / h } else { 0. };
+    (i, t)
+}
+
+/// Second derivatives of the natural cubic spline through the samples, from the tridiagonal
+/// system of the continuity conditions.
+fn spline_second_derivatives(x: &[f32], y: &[f32]) -> Vec<f32> {
+    let n = x.len();
+    let mut second_derivatives = vec![0f32; n];
+    if n < 3 {
+        return second_derivatives;
+    }
+    // Forward elimination of the Thomas algorithm
+    let mut upper = vec![0f32; n];
+    let mut rhs = vec![0f32; n];
+    for i in 1..n - 1 {
+        let (h0, h1) = (x[i] - x[i - 1], x[i + 1] - x[i]);
+        if h0 <= 0. || h1 <= 0. {
+            continue;
+        }
+        let slope_change = (y[i + 1] - y[i]) / h1 - (y[i] - y[i - 1]) / h0;
+        let diagonal = 2. * (h0 + h1) - h0 * upper[i - 1];
+        upper[i] = h1 / diagonal;
+        rhs[i] = (6. * slope_change - h0 * rhs[i - 1]) / diagonal;
+    }
+    for i in (1..n - 1).rev() {
+        second_derivatives[i] = rhs[i] - upper[i] * second_derivatives[i + 1];
+    }
+    second_derivatives
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn resampling() {
+        let x: Vec<f32> = (0..20).map(|i| 400. + 2.5 * i as f32).collect();
+        let y: Vec<f32> = x.iter().map(|x| ((x - 400.) / 8.).sin()).collect();
+        let grid: Vec<f32> = (401..=447).map(|w| w as f32).collect();
+
+        let linear = resample(&x, &y, &grid, Resampling::Linear);
+        let spline = resample(&x, &y, &grid, Resampling::Spline);
+        assert_eq!(linear.len(), grid.len());
+        let max_error = |values: &[f32]| {
+            grid.iter()
+                .zip(values)
+                .map(|(w, v)| (((w - 400.) / 8.).sin() - v).abs())
+                .fold(0., f32::max)
+        };
+        assert!(max_error(&linear) < 0.02);
+        assert!(max_error(&spline) < 0.2 * max_error(&linear));
+
+        // Both pass through the samples
+        let at_samples = resample(&x, &y, &x, Resampling::Spline);
+        for (a, b) in at_samples.iter().zip(&y) {
+            assert_abs_diff_eq!(a, b, epsilon = 1e-5);
+        }
+        assert_eq!(resample(&x, &y, &[402.5], Resampling::Linear), vec![y[1]]);
+    }
+}
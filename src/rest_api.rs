@@ -0,0 +1,436 @@
+//! Small HTTP API for lab automation:
+//!
+//! | Request         | Effect                                                           |
+//! |-----------------|------------------------------------------------------------------|
+//! | `GET /spectrum` | Latest remote spectrum as JSON, see [ProcessedSpectrum::to_json] |
+//! | `POST /zero`    | Set the current spectrum as zero reference                       |
+//! | `POST /start`   | Start the camera stream                                          |
+//! | `POST /stop`    | Stop the acquisition                                             |
+//!
+//! Commands need the header `Authorization: Bearer <token>`, they are carried out by the GUI
+//! and answered with `200 OK` once done or `409 Conflict` with the reason they failed.
+
+use crate::engine::{ProcessedSpectrum, SpectroEngine};
+use crate::Waker;
+use flume::{Receiver, RecvTimeoutError, Sender};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Time for a client to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+/// Time for the GUI to carry out a command.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest wait for a spectrum before new connections and the stop flag are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Request bodies are not used, larger ones are not read.
+const MAX_BODY: u64 = 4096;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RemoteCommand {
+    SetZeroReference,
+    Start,
+    Stop,
+}
+
+/// Command of a client waiting for its outcome.
+pub struct RemoteRequest {
+    pub command: RemoteCommand,
+    reply_tx: Sender<Result<(), String>>,
+}
+
+impl RemoteRequest {
+    /// Answer the client with the outcome of the command.
+    pub fn reply(self, result: Result<(), String>) {
+        // The client may have given up waiting
+        let _ = self.reply_tx.send(result);
+    }
+}
+
+/// Random token for the clients to authenticate with.
+pub fn generate_token() -> String {
+    let state = RandomState::new();
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    (0..2u8)
+        .map(|i| {
+            let mut hasher = state.build_hasher();
+            hasher.write_u8(i);
+            hasher.write_u128(nanos);
+            format!("{:016x}", hasher.finish())
+        })
+        .collect()
+}
+
+/// Compare without returning early, so the time taken does not reveal the token.
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+/// What the server shares with the connections.
+struct Shared {
+    token: String,
+    latest: Mutex<Option<ProcessedSpectrum>>,
+    request_tx: Sender<RemoteRequest>,
+    waker: Option<Waker>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Shared {
+    /// Hand `command` to the GUI and wait for its outcome.
+    fn run(&self, command: RemoteCommand) -> (&'static str, String) {
+        let (reply_tx, reply_rx) = flume::bounded(1);
+        if self
+            .request_tx
+            .send(RemoteRequest { command, reply_tx })
+            .is_err()
+        {
+            return ("503 Service Unavailable", error_body("Server stopped"));
+        }
+        if let Some(waker) = &self.waker {
+            waker();
+        }
+        let deadline = Instant::now() + COMMAND_TIMEOUT;
+        while !self.stop.load(Ordering::Relaxed) && Instant::now() < deadline {
+            match reply_rx.recv_timeout(POLL_INTERVAL) {
+                Ok(Ok(())) => return ("200 OK", "{\"status\":\"done\"}".to_string()),
+                Ok(Err(e)) => return ("409 Conflict", error_body(&e)),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        (
+            "503 Service Unavailable",
+            error_body("The command was not carried out"),
+        )
+    }
+}
+
+/// Status line and JSON body of the response to a request with the bearer token
+/// `authorization`, if any.
+fn respond(
+    method: &str,
+    path: &str,
+    authorization: Option<&str>,
+    shared: &Shared,
+) -> (&'static str, String) {
+    let command = match path {
+        "/spectrum" => None,
+        "/zero" => Some(RemoteCommand::SetZeroReference),
+        "/start" => Some(RemoteCommand::Start),
+        "/stop" => Some(RemoteCommand::Stop),
+        _ => return ("404 Not Found", error_body("Not found")),
+    };
+    match (method, command) {
+        ("GET", None) => match shared.latest.lock().unwrap().as_ref() {
+            Some(spectrum) => ("200 OK", spectrum.to_json()),
+            None => ("503 Service Unavailable", error_body("No spectrum yet")),
+        },
+        ("POST", Some(command)) => match authorization {
+            None => ("401 Unauthorized", error_body("Bearer token required")),
+            Some(token) if !token_matches(token, &shared.token) => {
+                ("403 Forbidden", error_body("Invalid token"))
+            }
+            Some(_) => shared.run(command),
+        },
+        _ => ("405 Method Not Allowed", error_body("Method not allowed")),
+    }
+}
+
+/// Read a request from the client and answer it.
+fn handle_connection(mut stream: TcpStream, shared: &Shared) -> Result<(), String> {
+    stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(REQUEST_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    stream
+        .set_write_timeout(Some(REQUEST_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let mut request_line = String::new();
+    reader
+        .read_line(&mut request_line)
+        .map_err(|e| e.to_string())?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return Err(format!("Invalid request: {}", request_line.trim())),
+    };
+    let mut content_length = 0;
+    let mut authorization = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = value
+                    .trim()
+                    .strip_prefix("Bearer ")
+                    .map(|token| token.trim().to_string());
+            }
+        }
+    }
+    std::io::copy(
+        &mut reader.take(content_length.min(MAX_BODY)),
+        &mut std::io::sink(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let path = target
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('/');
+    let (status, body) = respond(method, path, authorization.as_deref(), shared);
+    let challenge = if status.starts_with("401") {
+        "WWW-Authenticate: Bearer\r\n"
+    } else {
+        ""
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         {}\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        challenge,
+        body
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Server thread answering the requests, stopped when dropped.
+pub struct RestServer {
+    port: u16,
+    stop: Arc<AtomicBool>,
+    request_rx: Receiver<RemoteRequest>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl RestServer {
+    /// Listen on `address` at `port`, 0 for any free port, accepting commands with `token`.
+    /// `waker` is called when a command waits for the GUI.
+    pub fn start(
+        address: IpAddr,
+        port: u16,
+        token: String,
+        engine: &SpectroEngine,
+        waker: Option<Waker>,
+    ) -> Result<Self, String> {
+        let listener = TcpListener::bind((address, port)).map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (request_tx, request_rx) = flume::unbounded();
+        let shared = Arc::new(Shared {
+            token,
+            latest: Mutex::new(None),
+            request_tx,
+            waker,
+            stop: Arc::clone(&stop),
+        });
+        let spectrum_rx = engine.subscribe_remote();
+        let thread_stop = Arc::clone(&stop);
+        let handle = std::thread::spawn(move || {
+            let mut connections: Vec<JoinHandle<()>> = vec![];
+            while !thread_stop.load(Ordering::Relaxed) {
+                while let Ok((stream, address)) = listener.accept() {
+                    let shared = Arc::clone(&shared);
+                    connections.push(std::thread::spawn(move || {
+                        handle_connection(stream, &shared)
+                            .map_err(|e| log::warn!("REST client {}: {}", address, e))
+                            .ok();
+                    }));
+                }
+                if let Ok(spectrum) = spectrum_rx.recv_timeout(POLL_INTERVAL) {
+                    let latest = spectrum_rx.drain().last().unwrap_or(spectrum);
+                    *shared.latest.lock().unwrap() = Some(latest);
+                }
+                connections.retain(|connection| !connection.is_finished());
+            }
+            for connection in connections {
+                connection.join().ok();
+            }
+        });
+        Ok(Self {
+            port,
+            stop,
+            request_rx,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Commands received since the last call, each to be replied to.
+    pub fn requests(&self) -> Vec<RemoteRequest> {
+        self.request_rx.drain().collect()
+    }
+}
+
+impl Drop for RestServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::SpectrumFlags;
+    use crate::spectrum::Spectrum;
+    use std::net::Ipv4Addr;
+
+    const TOKEN: &str = "secret";
+
+    fn request(port: u16, method: &str, path: &str, token: Option<&str>) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        let authorization = token
+            .map(|token| format!("Authorization: Bearer {}\r\n", token))
+            .unwrap_or_default();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: 2\r\n\r\n{{}}",
+            method, path, authorization
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    /// Send a command and answer it like the GUI, returning the response.
+    fn command(server: &RestServer, path: &'static str, result: Result<(), String>) -> String {
+        let port = server.port();
+        let client = std::thread::spawn(move || request(port, "POST", path, Some(TOKEN)));
+        let started = Instant::now();
+        let request = loop {
+            if let Some(request) = server.requests().pop() {
+                break request;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        let command = request.command;
+        request.reply(result);
+        let response = client.join().unwrap();
+        format!("{:?} {}", command, response)
+    }
+
+    #[test]
+    fn rest_api() {
+        let engine = SpectroEngine::default();
+        let server = RestServer::start(
+            Ipv4Addr::LOCALHOST.into(),
+            0,
+            TOKEN.to_string(),
+            &engine,
+            None,
+        )
+        .unwrap();
+        let port = server.port();
+
+        assert!(request(port, "GET", "/spectrum", None).starts_with("HTTP/1.1 503"));
+        assert!(request(port, "GET", "/other", None).starts_with("HTTP/1.1 404"));
+        assert!(request(port, "GET", "/zero", Some(TOKEN)).starts_with("HTTP/1.1 405"));
+        let unauthorized = request(port, "POST", "/zero", None);
+        assert!(unauthorized.starts_with("HTTP/1.1 401"));
+        assert!(unauthorized.contains("WWW-Authenticate: Bearer\r\n"));
+        assert!(request(port, "POST", "/zero", Some("secreT")).starts_with("HTTP/1.1 403"));
+        assert!(server.requests().is_empty());
+
+        assert!(command(&server, "/start/", Ok(())).starts_with("Start HTTP/1.1 200"));
+        assert!(command(&server, "/stop", Ok(())).starts_with("Stop HTTP/1.1 200"));
+        let failed = command(&server, "/zero", Err("No \"camera\"".to_string()));
+        assert!(failed.starts_with("SetZeroReference HTTP/1.1 409"));
+        assert!(failed.ends_with("{\"error\":\"No \\\"camera\\\"\"}"));
+
+        engine.publish(ProcessedSpectrum {
+            timestamp: SystemTime::UNIX_EPOCH,
+            wavelengths: vec![400., 500.],
+            channels: Spectrum::from_element(2, 0.5),
+            averaged_frames: 1,
+            flags: SpectrumFlags::default(),
+        });
+        let started = Instant::now();
+        let response = loop {
+            let response = request(port, "GET", "/spectrum?channels=all", None);
+            if !response.starts_with("HTTP/1.1 503") {
+                break response;
+            }
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: application/json\r\n"));
+        assert!(response.contains("\r\n\r\n{\"timestamp_ms\":0,\"wavelengths\":[400.0,500.0],"));
+        assert!(response.ends_with("\"transformed\":false}}"));
+    }
+
+    #[test]
+    fn unanswered_command() {
+        let engine = SpectroEngine::default();
+        let server = RestServer::start(
+            Ipv4Addr::LOCALHOST.into(),
+            0,
+            TOKEN.to_string(),
+            &engine,
+            None,
+        )
+        .unwrap();
+        let port = server.port();
+        let client = std::thread::spawn(move || request(port, "POST", "/stop", Some(TOKEN)));
+        // Dropped requests are not carried out
+        let started = Instant::now();
+        while server.requests().is_empty() {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(client.join().unwrap().starts_with("HTTP/1.1 503"));
+    }
+
+    #[test]
+    fn tokens() {
+        let token = generate_token();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, generate_token());
+        assert!(token_matches(&token, &token.clone()));
+        assert!(!token_matches(&token, &token[1..]));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::config::{Binning, SpectrumWindow};
+use egui::Vec2;
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel, Primitive, Rgb};
+
+/// Window the spectrum is calculated from, with 16 bits per channel to keep the precision of
+/// deep pixel formats.
+pub type WindowImage = ImageBuffer<Rgb<u16>, Vec<u16>>;
+
+/// Windows cut from one frame.
+#[derive(Debug, Clone)]
+pub struct BeamWindows {
+    pub sample: WindowImage,
+    /// Reference strip of the reference beam mode, see [crate::config::ImageConfig].
+    pub reference: Option<WindowImage>,
+    pub binning: Binning,
+}
+
+/// Rotate `v` by `rotation` degrees, clockwise in image coordinates with y pointing down.
+fn rotate(v: Vec2, rotation: f32) -> Vec2 {
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+/// Corners of the window rotated around its center, clockwise starting top left.
+pub fn window_corners(window: &SpectrumWindow, rotation: f32) -> [Vec2; 4] {
+    let center = window.offset + window.size / 2.;
+    let half = window.size / 2.;
+    [
+        Vec2::new(-half.x, -half.y),
+        Vec2::new(half.x, -half.y),
+        Vec2::new(half.x, half.y),
+        Vec2::new(-half.x, half.y),
+    ]
+    .map(|corner| center + rotate(corner, rotation))
+}
+
+/// Point in the middle of the right edge of the rotated window, where it can be turned.
+pub fn rotation_handle(window: &SpectrumWindow, rotation: f32) -> Vec2 {
+    window.offset + window.size / 2. + rotate(Vec2::new(window.size.x / 2., 0.), rotation)
+}
+
+/// Rotation in degrees which puts the rotation handle at `position`.
+pub fn rotation_towards(window: &SpectrumWindow, position: Vec2) -> f32 {
+    let v = position - (window.offset + window.size / 2.);
+    v.y.atan2(v.x).to_degrees()
+}
+
+/// Cut the window out of the frame, rotated by `rotation` degrees around its center.
+///
+/// Rotated windows are resampled bilinearly, so that a tilted spectrum ends up horizontal.
+/// Pixels outside of the frame are black.
+pub fn extract_window<P: Primitive + 'static>(
+    frame: &ImageBuffer<Rgb<P>, Vec<P>>,
+    window: &SpectrumWindow,
+    rotation: f32,
+) -> ImageBuffer<Rgb<P>, Vec<P>>
+where
+    Rgb<P>: Pixel<Subpixel = P>,
+{
+    let (width, height) = (window.size.x as u32, window.size.y as u32);
+    if rotation == 0. {
+        return frame
+            .view(
+                window.offset.x as u32,
+                window.offset.y as u32,
+                width,
+                height,
+            )
+            .to_image();
+    }
+
+    let center = window.offset + window.size / 2.;
+    let pixel = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= frame.width() as i64 || y >= frame.height() as i64 {
+            [0.; 3]
+        } else {
+            frame
+                .get_pixel(x as u32, y as u32)
+                .0
+                .map(|v| v.to_f32().unwrap())
+        }
+    };
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let offset = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - window.size / 2.;
+        // Pixel centers are at half integer coordinates
+        let source = center + rotate(offset, rotation) - Vec2::splat(0.5);
+        let (x0, y0) = (source.x.floor(), source.y.floor());
+        let (tx, ty) = (source.x - x0, source.y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let (p00, p10) = (pixel(x0, y0), pixel(x0 + 1, y0));
+        let (p01, p11) = (pixel(x0, y0 + 1), pixel(x0 + 1, y0 + 1));
+        Rgb([0, 1, 2].map(|c| {
+            let top = p00[c] + tx * (p10[c] - p00[c]);
+            let bottom = p01[c] + tx * (p11[c] - p01[c]);
+            P::from((top + ty * (bottom - top)).round()).unwrap()
+        }))
+    })
+}
+
+/// Whether the unrotated window lies inside a frame of the given size.
+pub fn window_inside(window: &SpectrumWindow, width: u32, height: u32) -> bool {
+    let end = window.offset + window.size;
+    window.offset.x >= 0.
+        && window.offset.y >= 0.
+        && end.x <= width as f32
+        && end.y <= height as f32
+}
+
+/// Mean of each row of the window relative to full scale, showing where the spectrum falls
+/// across the window.
+pub fn row_profile<P: Primitive + 'static>(window: &ImageBuffer<Rgb<P>, Vec<P>>) -> Vec<f32>
+where
+    Rgb<P>: Pixel<Subpixel = P>,
+{
+    let max_value = window.width() as f32 * P::DEFAULT_MAX_VALUE.to_f32().unwrap() * 3.;
+    window
+        .rows()
+        .map(|row| {
+            row.flat_map(|p| p.0)
+                .map(|v| v.to_f32().unwrap())
+                .sum::<f32>()
+                / max_value
+        })
+        .collect()
+}
+
+/// Cut the window out of a frame of any depth, see [extract_window].
+pub fn extract_deep_window(
+    frame: &DynamicImage,
+    window: &SpectrumWindow,
+    rotation: f32,
+) -> WindowImage {
+    match frame {
+        DynamicImage::ImageRgb8(frame) => {
+            DynamicImage::ImageRgb8(extract_window(frame, window, rotation)).into_rgb16()
+        }
+        DynamicImage::ImageRgb16(frame) => extract_window(frame, window, rotation),
+        frame => extract_window(&frame.to_rgb16(), window, rotation),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotated_window() {
+        let rotation = 5f32;
+        // Line through (50, 30) tilted by the rotation
+        let frame = ImageBuffer::from_fn(100, 60, |x, y| {
+            let line_y = 30. + (x as f32 + 0.5 - 50.) * rotation.to_radians().tan();
+            if (y as f32 + 0.5 - line_y).abs() < 0.5 {
+                Rgb([200; 3])
+            } else {
+                Rgb([0; 3])
+            }
+        });
+        let window = SpectrumWindow {
+            offset: Vec2::new(10., 25.),
+            size: Vec2::new(80., 10.),
+        };
+        let lit_rows = |image: &ImageBuffer<Rgb<u8>, Vec<u8>>| {
+            image
+                .rows()
+                .map(|row| row.map(|p| p[0] as u32).sum::<u32>())
+                .filter(|sum| *sum > 80 * 20)
+                .count()
+        };
+
+        assert!(lit_rows(&extract_window(&frame, &window, 0.)) >= 5);
+        assert!(lit_rows(&extract_window(&frame, &window, rotation)) <= 2);
+
+        let handle = rotation_handle(&window, rotation);
+        assert!((rotation_towards(&window, handle) - rotation).abs() < 1e-3);
+        assert_eq!(window_corners(&window, 0.)[2], Vec2::new(90., 35.));
+    }
+}
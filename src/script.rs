@@ -0,0 +1,127 @@
+//! User scripts in [Rhai](https://rhai.rs) computing named values from the spectrum.
+//!
+//! The script gets the arrays `wavelength` in nm and `intensity` and returns a map of the
+//! values, which are shown next to the derived values and exported with them:
+//!
+//! ```
+//! use spectro_cam_rs::config::SpectrumPoint;
+//! use spectro_cam_rs::script::CompiledScript;
+//!
+//! let spectrum: Vec<_> = (400..=700)
+//!     .map(|w| SpectrumPoint { wavelength: w as f32, value: 2. })
+//!     .collect();
+//! let script = r#"
+//!     let total = 0.0;
+//!     for i in intensity { total += i; }
+//!     #{ mean: total / intensity.len(), points: intensity.len() }
+//! "#;
+//! let values = CompiledScript::default().run(script, &spectrum).unwrap();
+//! assert_eq!(values, vec![("mean".to_string(), 2.), ("points".to_string(), 301.)]);
+//! ```
+
+use crate::config::SpectrumPoint;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use std::sync::{Mutex, PoisonError};
+
+/// Operations a script may take per run, so an endless loop does not hang the GUI.
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+/// A script compiled once and kept until its text changes, as it is run every frame.
+#[derive(Debug)]
+pub struct CompiledScript {
+    engine: Engine,
+    compiled: Mutex<Option<(String, Result<AST, String>)>>,
+}
+
+impl Default for CompiledScript {
+    fn default() -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        Self {
+            engine,
+            compiled: Mutex::new(None),
+        }
+    }
+}
+
+impl CompiledScript {
+    /// Values computed by `script` over `spectrum`, sorted by name.
+    pub fn run(
+        &self,
+        script: &str,
+        spectrum: &[SpectrumPoint],
+    ) -> Result<Vec<(String, f32)>, String> {
+        let mut compiled = self.compiled.lock().unwrap_or_else(PoisonError::into_inner);
+        if !matches!(&*compiled, Some((compiled_script, _)) if compiled_script == script) {
+            let ast = self.engine.compile(script).map_err(|e| e.to_string());
+            *compiled = Some((script.to_string(), ast));
+        }
+        let ast = match &compiled.as_ref().expect("compiled above").1 {
+            Ok(ast) => ast,
+            Err(e) => return Err(e.clone()),
+        };
+
+        let array = |value: fn(&SpectrumPoint) -> f32| -> Array {
+            spectrum
+                .iter()
+                .map(|sp| Dynamic::from_float(value(sp).into()))
+                .collect()
+        };
+        let mut scope = Scope::new();
+        scope.push("wavelength", array(|sp| sp.wavelength));
+        scope.push("intensity", array(|sp| sp.value));
+        let values = self
+            .engine
+            .eval_ast_with_scope::<Map>(&mut scope, ast)
+            .map_err(|e| e.to_string())?;
+        values
+            .into_iter()
+            .map(|(name, value)| {
+                let number = value
+                    .as_float()
+                    .or_else(|_| value.as_int().map(|i| i as f64))
+                    .map_err(|type_name| format!("'{}' is a {}, not a number", name, type_name))?;
+                Ok((name.to_string(), number as f32))
+            })
+            .collect()
+    }
+}
+
+/// The copy compiles the script again when it is first run.
+impl Clone for CompiledScript {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+/// Only a cache, so it never makes a difference.
+impl PartialEq for CompiledScript {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_errors() {
+        let spectrum = [SpectrumPoint {
+            wavelength: 500.,
+            value: 1.,
+        }];
+        let script = CompiledScript::default();
+        assert!(script.run("#{ a: ", &spectrum).is_err());
+        assert!(script
+            .run("#{ a: \"text\" }", &spectrum)
+            .unwrap_err()
+            .contains("'a'"));
+        assert!(script.run("loop {}", &spectrum).is_err());
+        assert!(script.run("42", &spectrum).is_err());
+        assert_eq!(
+            script.run("#{ peak: wavelength[0] }", &spectrum),
+            Ok(vec![("peak".to_string(), 500.)])
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use crate::config::{
+    DarkLibrary, ImageConfig, LoggingConfig, ReferenceConfig, Snapshot, SpectrometerConfig,
+    SpectrumCalibration, ZeroReference,
+};
+use crate::serde::CameraFormatDef;
+use nokhwa::CameraFormat;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::thread::JoinHandle;
+
+const APP_NAME: &str = "spectro-cam-rs";
+const SESSION_NAME: &str = "session";
+
+/// State of a measurement saved regularly while the application runs, removed on a clean
+/// exit. Settings are left to the configuration stored on exit.
+#[serde_as]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Session {
+    /// Unix time of the autosave.
+    pub saved: i64,
+    pub camera_id: usize,
+    #[serde_as(as = "Option<CameraFormatDef>")]
+    pub camera_format: Option<CameraFormat>,
+    pub image_config: ImageConfig,
+    pub spectrum_calibration: SpectrumCalibration,
+    pub reference_config: ReferenceConfig,
+    pub zero_reference: Option<ZeroReference>,
+    pub dark_library: Option<DarkLibrary>,
+    pub snapshots: Vec<Snapshot>,
+    pub last_spectrum: Option<Snapshot>,
+    /// Log which was being written.
+    pub logging: Option<LoggingConfig>,
+}
+
+impl Session {
+    /// Measurement state of `config`, with the log being written if any.
+    pub fn new(saved: i64, config: &SpectrometerConfig, logging: Option<LoggingConfig>) -> Self {
+        Self {
+            saved,
+            camera_id: config.camera_id,
+            camera_format: config.camera_format,
+            image_config: config.image_config.clone(),
+            spectrum_calibration: config.spectrum_calibration.clone(),
+            reference_config: config.reference_config.clone(),
+            zero_reference: config.zero_reference.clone(),
+            dark_library: config.dark_library.clone(),
+            snapshots: config.snapshots.clone(),
+            last_spectrum: config.last_spectrum.clone(),
+            logging,
+        }
+    }
+
+    /// Take over the measurement state into `config`, returning the log to resume.
+    pub fn restore(self, config: &mut SpectrometerConfig) -> Option<LoggingConfig> {
+        config.camera_id = self.camera_id;
+        config.camera_format = self.camera_format;
+        config.image_config = self.image_config;
+        config.spectrum_calibration = self.spectrum_calibration;
+        config.reference_config = self.reference_config;
+        config.zero_reference = self.zero_reference;
+        config.dark_library = self.dark_library;
+        config.snapshots = self.snapshots;
+        config.last_spectrum = self.last_spectrum;
+        self.logging
+    }
+
+    /// Session of a run which did not exit cleanly, if any.
+    pub fn load_unfinished() -> Option<Self> {
+        let path = confy::get_configuration_file_path(APP_NAME, SESSION_NAME).ok()?;
+        if !path.exists() {
+            return None;
+        }
+        confy::load(APP_NAME, SESSION_NAME)
+            .map_err(|e| log::warn!("Could not load the autosaved session: {:?}", e))
+            .ok()
+    }
+
+    /// Store the session on another thread, as the snapshots can be large.
+    pub fn autosave(self) -> JoinHandle<()> {
+        std::thread::spawn(move || {
+            if let Err(e) = confy::store(APP_NAME, SESSION_NAME, self) {
+                log::warn!("Could not autosave the session: {:?}", e);
+            }
+        })
+    }
+
+    /// Mark the session as ended cleanly.
+    pub fn finish() {
+        if let Ok(path) = confy::get_configuration_file_path(APP_NAME, SESSION_NAME) {
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore() {
+        let mut config = SpectrometerConfig::default();
+        config.spectrum_calibration.high.index = 100;
+        config.camera_id = 2;
+        config.view_config.touch_mode = true;
+        let session = Session::new(0, &config, Some(config.logging_config.clone()));
+
+        let mut restored = SpectrometerConfig::default();
+        assert_eq!(
+            session.restore(&mut restored),
+            Some(config.logging_config.clone())
+        );
+        assert_eq!(restored.spectrum_calibration, config.spectrum_calibration);
+        assert_eq!(restored.camera_id, 2);
+        // Settings are not part of the session
+        assert!(!restored.view_config.touch_mode);
+    }
+}
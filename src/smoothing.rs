@@ -0,0 +1,78 @@
+use crate::deconvolution::convolve;
+use nalgebra::DMatrix;
+
+/// Savitzky-Golay smoothing with a polynomial of `order` over `2 * half_width + 1` points.
+///
+/// Preserves the height and width of peaks better than a low-pass filter.
+pub fn savitzky_golay(signal: &[f32], half_width: usize, order: usize) -> Vec<f32> {
+    let order = order.min(2 * half_width);
+    let half_width_i = half_width as isize;
+    let vandermonde = DMatrix::<f64>::from_fn(2 * half_width + 1, order + 1, |i, k| {
+        ((i as isize - half_width_i) as f64).powi(k as i32)
+    });
+    let inverse = match (vandermonde.transpose() * &vandermonde).try_inverse() {
+        Some(inverse) => inverse,
+        None => return signal.to_vec(),
+    };
+    // The first row of the pseudo inverse gives the value of the fit at the center
+    let coefficients: Vec<f32> = (inverse * vandermonde.transpose())
+        .row(0)
+        .iter()
+        .map(|c| *c as f32)
+        .collect();
+    convolve(signal, &coefficients)
+}
+
+/// Moving median over `2 * half_width + 1` points, removes spikes without blurring edges.
+pub fn median(signal: &[f32], half_width: usize) -> Vec<f32> {
+    let last = signal.len() as isize - 1;
+    let half_width = half_width as isize;
+    (0..signal.len() as isize)
+        .map(|i| {
+            let mut window: Vec<f32> = (i - half_width..=i + half_width)
+                .map(|j| signal[j.clamp(0, last) as usize])
+                .collect();
+            window.sort_by(f32::total_cmp);
+            window[window.len() / 2]
+        })
+        .collect()
+}
+
+/// Convolution with a normalized Gaussian, truncated at three standard deviations.
+pub fn gaussian(signal: &[f32], sigma: f32) -> Vec<f32> {
+    if sigma <= 0. {
+        return signal.to_vec();
+    }
+    let half_width = (3. * sigma).ceil() as isize;
+    let kernel: Vec<f32> = (-half_width..=half_width)
+        .map(|x| (-(x * x) as f32 / (2. * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    let kernel: Vec<f32> = kernel.into_iter().map(|w| w / sum).collect();
+    convolve(signal, &kernel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn smoothing() {
+        let parabola: Vec<f32> = (0..20).map(|x| (x * x) as f32).collect();
+        let smoothed = savitzky_golay(&parabola, 3, 2);
+        for i in 3..17 {
+            assert_relative_eq!(smoothed[i], parabola[i], max_relative = 1e-4);
+        }
+
+        let spiked = [1., 1., 9., 1., 1., 2., 2., 2.];
+        assert_eq!(median(&spiked, 1), vec![1., 1., 1., 1., 1., 2., 2., 2.]);
+
+        let mut impulse = vec![0.; 21];
+        impulse[10] = 1.;
+        let smoothed = gaussian(&impulse, 2.);
+        assert_relative_eq!(smoothed.iter().sum::<f32>(), 1., max_relative = 1e-5);
+        assert!(smoothed[10] < 1. && smoothed[10] > smoothed[11]);
+        assert_eq!(gaussian(&impulse, 0.), impulse);
+    }
+}
@@ -1,18 +1,47 @@
+use crate::camera_stream::interpolate;
 use crate::config::{
-    Linearize, ReferenceConfig, SpectrometerConfig, SpectrumCalibration, SpectrumPoint,
+    AxisConfig, AxisUnit, BaselineMethod, Binning, BinningMode, DarkFrame, ExportFormat,
+    IntensityUnit, Linearize, PeakWindow, PostprocessingConfig, ReferenceBeam, ReferenceConfig,
+    Resampling, SmoothingMethod, SpectrometerConfig, SpectrumCalibration, SpectrumPoint,
 };
+use crate::dark_library::{DarkCapture, DarkModel};
+use crate::deconvolution::{extract_line_shape, richardson_lucy};
+use crate::engine::{ProcessedSpectrum, SpectroEngine, SpectrumFlags};
+use crate::export::{write_jcamp_dx, write_spc, write_spectral_workbench, write_theremino};
+use crate::flicker::frame_rate;
+use crate::hdf5::{Hdf5Data, Hdf5Dataset, Hdf5File, Hdf5Value};
+use crate::hdr::HdrMerger;
+use crate::lock_in::LockInDemodulator;
+use crate::peak_fit::{fit_peak, PeakFit};
+use crate::recording::Recorder;
+use crate::resampling::resample;
+use crate::roi::BeamWindows;
+use crate::smoothing::{gaussian, median, savitzky_golay};
 use biquad::{
     Biquad, Coefficients, DirectForm2Transposed, Hertz, ToHertz, Type, Q_BUTTERWORTH_F32,
 };
+use chrono::Local;
 use flume::{Receiver, Sender};
-use image::{ImageBuffer, Pixel, Rgb};
-use nalgebra::{Dynamic, OMatrix, U3, U4};
+use image::{ImageBuffer, Pixel, Primitive, Rgb};
+use nalgebra::{Dynamic, OMatrix, RowDVector, VectorSlice, U3, U4, U9};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
 
 pub type SpectrumRgb = OMatrix<f32, U3, Dynamic>;
 pub type Spectrum = OMatrix<f32, U4, Dynamic>;
+/// Sums of the r, g and b values of each column and of their products rr, gg, bb, rg, rb and
+/// gb, in double precision as the variances are small differences of them.
+type ChannelMoments = OMatrix<f64, U9, Dynamic>;
+
+/// Full scale of each channel of a [SpectrumRgb], so that the sum of the channels is 1 at
+/// full scale.
+pub const CHANNEL_FULL_SCALE: f32 = 1. / 3.;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
 pub struct SpectrumExportPoint {
@@ -21,18 +50,174 @@ pub struct SpectrumExportPoint {
     pub g: f32,
     pub b: f32,
     pub sum: f32,
+    pub detection_limit: Option<f32>,
+    pub unit: IntensityUnit,
+    /// Position in the axis unit, only if that is not nm.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub axis: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub axis_unit: Option<AxisUnit>,
+    /// Whether the intensities are per axis unit instead of per nm, only if that is not nm.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jacobian: Option<bool>,
+}
+
+/// Keep every n-th point so that at most `max_points` points remain.
+pub fn decimate<T>(points: Vec<T>, max_points: usize) -> Vec<T> {
+    if max_points == 0 || points.len() <= max_points {
+        return points;
+    }
+    let step = points.len().div_ceil(max_points);
+    points.into_iter().step_by(step).collect()
+}
+
+/// Three times the standard error of the mean of each column, averaged over bands of
+/// `band_width` columns.
+///
+/// Constant offsets like a dark frame do not contribute, so this is the detection limit of
+/// the dark-corrected average. Returns `None` for less than two frames.
+pub fn detection_limit(frames: &[Vec<f32>], band_width: usize) -> Option<Vec<f32>> {
+    let n = frames.len();
+    let ncols = frames.first()?.len();
+    if n < 2 || frames.iter().any(|f| f.len() != ncols) {
+        return None;
+    }
+
+    let variances: Vec<f32> = (0..ncols)
+        .map(|i| {
+            let mean = frames.iter().map(|f| f[i]).sum::<f32>() / n as f32;
+            frames.iter().map(|f| (f[i] - mean).powi(2)).sum::<f32>() / (n - 1) as f32
+        })
+        .collect();
+    Some(band_limits(&variances, n, band_width))
+}
+
+/// Detection limit of the mean of `n` frames from the `variances` of the columns, see
+/// [detection_limit].
+fn band_limits(variances: &[f32], n: usize, band_width: usize) -> Vec<f32> {
+    variances
+        .chunks(band_width.max(1))
+        .flat_map(|band| {
+            let variance = band.iter().sum::<f32>() / band.len() as f32;
+            let limit = 3. * (variance / n as f32).sqrt();
+            std::iter::repeat_n(limit, band.len())
+        })
+        .collect()
+}
+
+fn channel_moments(spectrum: &SpectrumRgb) -> ChannelMoments {
+    ChannelMoments::from_fn(spectrum.ncols(), |row, i| {
+        let [r, g, b] = [0, 1, 2].map(|c| spectrum[(c, i)] as f64);
+        [r, g, b, r * r, g * g, b * b, r * g, r * b, g * b][row]
+    })
+}
+
+/// Sample variance of `weights` times the r, g and b values of `n` frames, from the
+/// [ChannelMoments] of a column.
+fn weighted_variance(moments: VectorSlice<f64, U9>, n: usize, weights: [f64; 3]) -> f32 {
+    let n = n as f64;
+    let covariance = |c: usize, d: usize| {
+        let product = match (c.min(d), c.max(d)) {
+            (c, d) if c == d => 3 + c,
+            (0, 1) => 6,
+            (0, 2) => 7,
+            _ => 8,
+        };
+        (moments[product] - moments[c] * moments[d] / n) / (n - 1.)
+    };
+    let mut variance = 0.;
+    for c in 0..3 {
+        for d in 0..3 {
+            variance += weights[c] * weights[d] * covariance(c, d);
+        }
+    }
+    // Rounding may leave a constant column slightly negative
+    variance.max(0.) as f32
+}
+
+/// Indices of the local maxima (or minima) within `find_window` which are also the extreme
+/// within `unique_window` nm.
+pub fn find_peaks_dips(
+    spectrum: &[SpectrumPoint],
+    peaks: bool,
+    find_window: PeakWindow,
+    unique_window: f32,
+) -> Vec<usize> {
+    let mut peaks_dips = Vec::new();
+
+    for (i, center) in spectrum.iter().enumerate() {
+        let (left, right) = match find_window.extent(spectrum, i) {
+            None => continue,
+            Some(extent) => extent,
+        };
+        let win = &spectrum[i - left..=i + right];
+        if win[..left].iter().chain(win[left + 1..].iter()).all(|p| {
+            if peaks {
+                p.value < center.value
+            } else {
+                p.value > center.value
+            }
+        }) {
+            peaks_dips.push(i);
+        }
+    }
+
+    peaks_dips
+        .iter()
+        .copied()
+        .filter(|&peak_dip| {
+            let center = spectrum[peak_dip].wavelength;
+            spectrum[peak_dip].value
+                == peaks_dips
+                    .iter()
+                    .filter(|&&i| {
+                        spectrum[i].wavelength > center - unique_window / 2.
+                            && spectrum[i].wavelength < center + unique_window / 2.
+                    })
+                    .map(|&i| spectrum[i].value)
+                    .reduce(if peaks { f32::max } else { f32::min })
+                    .unwrap()
+        })
+        .collect()
+}
+
+/// Combined channel from the `sum` of the colors: their mean, scaled by the intensity
+/// calibration.
+fn combined_channel(
+    mut sum: RowDVector<f32>,
+    calibration: &SpectrumCalibration,
+) -> RowDVector<f32> {
+    sum.iter_mut().enumerate().for_each(|(i, v)| {
+        *v *= calibration.get_scaling_factor_from_index(i);
+    });
+    sum / 3.
+}
+
+/// Replace each channel of the spectrum with `f` applied to it.
+fn map_channels(spectrum: &mut Spectrum, f: impl Fn(&[f32]) -> Vec<f32>) {
+    for mut channel in spectrum.row_iter_mut() {
+        let values: Vec<f32> = channel.iter().copied().collect();
+        channel
+            .iter_mut()
+            .zip(f(&values))
+            .for_each(|(v, smoothed)| *v = smoothed);
+    }
+}
+
+/// Raw spectra of the windows of one frame, see [BeamWindows].
+#[derive(Debug, Clone)]
+pub struct RawSpectra {
+    pub sample: SpectrumRgb,
+    pub reference: Option<SpectrumRgb>,
 }
 
 pub struct SpectrumCalculator {
-    window_rx: Receiver<ImageBuffer<Rgb<u8>, Vec<u8>>>,
-    spectrum_tx: Sender<SpectrumRgb>,
+    window_rx: Receiver<BeamWindows>,
+    spectrum_tx: Sender<RawSpectra>,
 }
 
 impl SpectrumCalculator {
-    pub fn new(
-        window_rx: Receiver<ImageBuffer<Rgb<u8>, Vec<u8>>>,
-        spectrum_tx: Sender<SpectrumRgb>,
-    ) -> Self {
+    pub fn new(window_rx: Receiver<BeamWindows>, spectrum_tx: Sender<RawSpectra>) -> Self {
         SpectrumCalculator {
             window_rx,
             spectrum_tx,
@@ -41,91 +226,332 @@ impl SpectrumCalculator {
 
     pub fn run(&mut self) -> ! {
         loop {
-            if let Ok(window) = self.window_rx.recv() {
-                let spectrum = Self::process_window(&window);
+            if let Ok(windows) = self.window_rx.recv() {
+                let spectra = RawSpectra {
+                    sample: Self::process_window(&windows.sample, &windows.binning),
+                    reference: windows
+                        .reference
+                        .as_ref()
+                        .map(|reference| Self::process_window(reference, &windows.binning)),
+                };
 
-                self.spectrum_tx.send(spectrum).unwrap();
+                self.spectrum_tx.send(spectra).unwrap();
             }
         }
     }
 
-    pub fn process_window(window: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> SpectrumRgb {
-        let columns = window.width();
-        let rows = window.height();
-        let max_value = rows * u8::MAX as u32 * 3;
+    /// Columns of the window binned over the rows, with each channel normalized to
+    /// [CHANNEL_FULL_SCALE].
+    pub fn process_window<P: Primitive + Sync + 'static>(
+        window: &ImageBuffer<Rgb<P>, Vec<P>>,
+        binning: &Binning,
+    ) -> SpectrumRgb
+    where
+        Rgb<P>: Pixel<Subpixel = P>,
+    {
+        let columns = window.width() as usize;
+        let rows = window.height() as usize;
+        let max_value = P::DEFAULT_MAX_VALUE.to_f32().unwrap() / CHANNEL_FULL_SCALE;
 
+        if binning.mode == BinningMode::Median {
+            return SpectrumRgb::from_fn(columns, |channel, column| {
+                let mut values: Vec<f32> = (0..rows)
+                    .map(|row| {
+                        window.get_pixel(column as u32, row as u32).0[channel]
+                            .to_f32()
+                            .unwrap()
+                    })
+                    .collect();
+                values.sort_by(f32::total_cmp);
+                // Mean of the two middle values for an even number of rows
+                let median = match rows {
+                    0 => 0.,
+                    _ => (values[(rows - 1) / 2] + values[rows / 2]) / 2.,
+                };
+                median / max_value
+            });
+        }
+
+        let weights = binning.weights(rows);
         let spectrum: SpectrumRgb = window
             .rows()
+            .zip(weights)
             .par_bridge()
-            .map(|r| {
+            .map(|(r, weight)| {
                 SpectrumRgb::from_vec(
-                    r.flat_map(|p| p.channels().iter().map(|&v| v as f32))
+                    r.flat_map(|p| p.channels().iter().map(|v| v.to_f32().unwrap()))
                         .collect::<Vec<f32>>(),
-                )
+                ) * weight
             })
-            .reduce(
-                || SpectrumRgb::from_element(columns as usize, 0.),
-                |a, b| a + b,
-            )
-            / max_value as f32;
+            .reduce(|| SpectrumRgb::from_element(columns, 0.), |a, b| a + b)
+            / max_value;
         spectrum
     }
 }
 
-pub struct SpectrumContainer {
-    spectrum: Spectrum,
+/// Number of frames after which the running sum of the averaging buffer is recomputed.
+const FULL_SUM_INTERVAL: usize = 1000;
+/// Fraction of full scale from which a column of the window counts as saturated.
+const SATURATION_LEVEL: f32 = 0.98;
+/// Frames in the averaging buffer needed to tell outliers apart.
+const MIN_OUTLIER_FRAMES: usize = 3;
+/// Ratio of the standard deviation to the median absolute deviation of normal data.
+const MAD_TO_SIGMA: f32 = 1.4826;
+
+/// Which of the `frames` deviate from their `mean` by more than `sigma` standard deviations.
+///
+/// The deviation of a frame is its RMS difference to the mean. Median and median absolute
+/// deviation of those estimate their center and spread, so that a few strong outliers, like
+/// someone walking past the lamp, do not hide themselves.
+fn outlier_frames<'a>(
+    frames: impl Iterator<Item = &'a SpectrumRgb>,
+    mean: &SpectrumRgb,
+    sigma: f32,
+) -> Vec<bool> {
+    let deviations: Vec<f32> = frames
+        .map(|frame| ((frame - mean).norm_squared() / mean.len() as f32).sqrt())
+        .collect();
+    if deviations.len() < MIN_OUTLIER_FRAMES {
+        return vec![false; deviations.len()];
+    }
+    let median = |values: &mut Vec<f32>| {
+        values.sort_by(f32::total_cmp);
+        let n = values.len();
+        (values[(n - 1) / 2] + values[n / 2]) / 2.
+    };
+    let center = median(&mut deviations.clone());
+    let spread = MAD_TO_SIGMA
+        * median(
+            &mut deviations
+                .iter()
+                .map(|d| (d - center).abs())
+                .collect::<Vec<_>>(),
+        );
+    deviations
+        .iter()
+        .map(|d| d - center > sigma * spread)
+        .collect()
+}
+
+/// Summary of a raw spectrum in the averaging buffer, for inspection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BufferedFrame {
+    pub id: u64,
+    pub acquired: Instant,
+    /// Largest channel value relative to full scale.
+    pub max: f32,
+    /// Left out of the average by the outlier rejection.
+    pub rejected: bool,
+}
+
+impl BufferedFrame {
+    pub fn saturated(&self) -> bool {
+        self.max >= SATURATION_LEVEL
+    }
+}
+
+/// Turns raw spectra of the camera window into the averaged and calibrated spectrum.
+///
+/// Independent of the GUI, e.g. for programmatic use:
+///
+/// ```
+/// use spectro_cam_rs::config::SpectrometerConfig;
+/// use spectro_cam_rs::spectrum::{SpectrumProcessor, SpectrumRgb};
+///
+/// let config = SpectrometerConfig::default();
+/// let mut processor = SpectrumProcessor::new();
+/// let frame = processor.process(SpectrumRgb::from_element(1000, 0.5), &config);
+/// assert_eq!(frame.spectrum().ncols(), 1000);
+/// ```
+pub struct SpectrumProcessor {
+    frame: SpectrumFrame,
     spectrum_buffer: VecDeque<SpectrumRgb>,
+    /// Summaries of the spectra in `spectrum_buffer`, in the same order.
+    buffered_frames: VecDeque<BufferedFrame>,
+    next_frame_id: u64,
+    /// Sum of all spectra in `spectrum_buffer`.
+    buffer_sum: SpectrumRgb,
+    /// Moments of all spectra in `spectrum_buffer` for the detection limit, updated like
+    /// `buffer_sum`.
+    buffer_moments: ChannelMoments,
+    frames_since_full_sum: usize,
     zero_reference: Option<Spectrum>,
-    spectrum_rx: Receiver<SpectrumRgb>,
+    /// Averages and calibrates the reference strip in the reference beam mode.
+    reference_processor: Option<Box<SpectrumProcessor>>,
 }
 
-impl SpectrumContainer {
-    pub fn new(spectrum_rx: Receiver<SpectrumRgb>) -> Self {
-        SpectrumContainer {
-            spectrum: Spectrum::zeros(0),
+impl Default for SpectrumProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpectrumProcessor {
+    pub fn new() -> Self {
+        SpectrumProcessor {
+            frame: SpectrumFrame::default(),
             spectrum_buffer: VecDeque::with_capacity(100),
+            buffered_frames: VecDeque::with_capacity(100),
+            next_frame_id: 0,
+            buffer_sum: SpectrumRgb::zeros(0),
+            buffer_moments: ChannelMoments::zeros(0),
+            frames_since_full_sum: 0,
             zero_reference: None,
-            spectrum_rx,
+            reference_processor: None,
         }
     }
 
     pub fn clear_buffer(&mut self) {
         self.spectrum_buffer.clear();
+        self.buffered_frames.clear();
+        self.reference_processor = None;
     }
 
-    pub fn update(&mut self, config: &SpectrometerConfig) {
-        if let Ok(spectrum) = self.spectrum_rx.try_recv() {
-            self.update_spectrum(spectrum, config);
+    /// Remove the spectrum with the given id from the averaging buffer, e.g. an outlier.
+    ///
+    /// Takes effect with the next processed spectrum.
+    pub fn drop_frame(&mut self, id: u64) {
+        if let Some(i) = self.buffered_frames.iter().position(|f| f.id == id) {
+            self.buffered_frames.remove(i);
+            if let Some(dropped) = self.spectrum_buffer.remove(i) {
+                self.buffer_moments -= channel_moments(&dropped);
+                self.buffer_sum -= dropped;
+            }
         }
     }
 
-    pub fn update_spectrum(&mut self, mut spectrum: SpectrumRgb, config: &SpectrometerConfig) {
+    /// Result of the last processed spectrum.
+    pub fn frame(&self) -> &SpectrumFrame {
+        &self.frame
+    }
+
+    /// Add a raw spectrum to the averaging buffer and return the updated result.
+    pub fn process(
+        &mut self,
+        spectrum: SpectrumRgb,
+        config: &SpectrometerConfig,
+    ) -> &SpectrumFrame {
+        self.process_with_reference(spectrum, None, config)
+    }
+
+    /// Like [SpectrumProcessor::process], relating the result to the spectrum of the
+    /// reference strip of the reference beam mode, which is averaged and calibrated the same
+    /// way, see [ReferenceBeam].
+    pub fn process_with_reference(
+        &mut self,
+        mut spectrum: SpectrumRgb,
+        reference: Option<SpectrumRgb>,
+        config: &SpectrometerConfig,
+    ) -> &SpectrumFrame {
         let ncols = spectrum.ncols();
+        let reference = match reference {
+            Some(reference) => Some(
+                self.reference_processor
+                    .get_or_insert_with(Default::default)
+                    .process(reference, config)
+                    .spectrum
+                    .clone(),
+            ),
+            None => {
+                self.reference_processor = None;
+                None
+            }
+        };
 
         // Clear buffer and zero reference on dimension change
         if let Some(s) = self.spectrum_buffer.get(0) {
             if s.ncols() != ncols {
-                self.spectrum_buffer.clear();
+                self.clear_buffer();
+                self.zero_reference = None;
+            }
+        }
+        // A restored zero reference may not fit either
+        if let Some(zero_reference) = self.zero_reference.as_ref() {
+            if zero_reference.ncols() != ncols {
                 self.zero_reference = None;
             }
         }
 
-        if config.spectrum_calibration.linearize != Linearize::Off {
-            spectrum
-                .iter_mut()
-                .for_each(|v| *v = config.spectrum_calibration.linearize.linearize(*v));
+        self.buffered_frames.push_front(BufferedFrame {
+            id: self.next_frame_id,
+            acquired: Instant::now(),
+            max: spectrum.max() / CHANNEL_FULL_SCALE,
+            rejected: false,
+        });
+        self.next_frame_id += 1;
+
+        let linearize = &config.spectrum_calibration.linearize;
+        if *linearize != Linearize::Off {
+            // The curves are defined over full scale
+            spectrum.iter_mut().for_each(|v| {
+                *v = linearize.linearize(*v / CHANNEL_FULL_SCALE) * CHANNEL_FULL_SCALE
+            });
         }
 
+        // Update the running sums instead of summing up the whole buffer
+        if self.spectrum_buffer.is_empty() {
+            self.buffer_sum = spectrum.clone();
+            self.buffer_moments = channel_moments(&spectrum);
+        } else {
+            self.buffer_sum += &spectrum;
+            self.buffer_moments += channel_moments(&spectrum);
+        }
         self.spectrum_buffer.push_front(spectrum);
-        self.spectrum_buffer
-            .truncate(config.postprocessing_config.spectrum_buffer_size);
+        let buffer_size = config
+            .postprocessing_config
+            .synchronized_buffer_size(frame_rate(&self.buffered_frames));
+        while self.spectrum_buffer.len() > buffer_size {
+            if let Some(evicted) = self.spectrum_buffer.pop_back() {
+                self.buffer_moments -= channel_moments(&evicted);
+                self.buffer_sum -= evicted;
+            }
+            self.buffered_frames.pop_back();
+        }
 
-        let mut combined_buffer = self
-            .spectrum_buffer
-            .par_iter()
-            .cloned()
-            .reduce(|| SpectrumRgb::from_element(ncols, 0.), |a, b| a + b)
-            / self.spectrum_buffer.len() as f32;
+        // Recompute the sum from time to time to get rid of accumulated rounding errors
+        self.frames_since_full_sum += 1;
+        if self.frames_since_full_sum >= FULL_SUM_INTERVAL {
+            self.buffer_sum = self
+                .spectrum_buffer
+                .par_iter()
+                .cloned()
+                .reduce(|| SpectrumRgb::from_element(ncols, 0.), |a, b| a + b);
+            self.buffer_moments = self
+                .spectrum_buffer
+                .par_iter()
+                .map(channel_moments)
+                .reduce(|| ChannelMoments::zeros(ncols), |a, b| a + b);
+            self.frames_since_full_sum = 0;
+        }
+
+        let mut combined_buffer = &self.buffer_sum / self.spectrum_buffer.len() as f32;
+        let mut averaged_frames = self.spectrum_buffer.len();
+        let postprocessing = &config.postprocessing_config;
+        if postprocessing.outlier_rejection {
+            let outliers = outlier_frames(
+                self.spectrum_buffer.iter(),
+                &combined_buffer,
+                postprocessing.outlier_sigma,
+            );
+            for (frame, &outlier) in self.buffered_frames.iter_mut().zip(&outliers) {
+                frame.rejected = outlier;
+            }
+            let rejected = outliers.iter().filter(|&&o| o).count();
+            if rejected > 0 {
+                averaged_frames -= rejected;
+                combined_buffer = self
+                    .spectrum_buffer
+                    .iter()
+                    .zip(&outliers)
+                    .filter(|(_, &outlier)| !outlier)
+                    .fold(SpectrumRgb::zeros(ncols), |sum, (frame, _)| sum + frame)
+                    / averaged_frames as f32;
+            }
+        } else {
+            self.buffered_frames
+                .iter_mut()
+                .for_each(|f| f.rejected = false);
+        }
 
         combined_buffer.set_row(
             0,
@@ -140,101 +566,543 @@ impl SpectrumContainer {
             &(combined_buffer.row(2) * config.spectrum_calibration.gain_b),
         );
 
+        let qe_correction: Option<Vec<(f32, f32, f32)>> =
+            config.spectrum_calibration.qe_curve.as_ref().map(|curve| {
+                (0..ncols)
+                    .map(|i| {
+                        curve.get_correction_at_wavelength(
+                            config.spectrum_calibration.get_wavelength_from_index(i),
+                        )
+                    })
+                    .collect()
+            });
+        if let Some(qe_correction) = qe_correction.as_ref() {
+            combined_buffer
+                .column_iter_mut()
+                .zip(qe_correction)
+                .for_each(|(mut p, (r, g, b))| {
+                    p[0] *= r;
+                    p[1] *= g;
+                    p[2] *= b;
+                });
+        }
+
         let mut current_spectrum = Spectrum::from_rows(&[
             combined_buffer.row(0).clone_owned(),
             combined_buffer.row(1).clone_owned(),
             combined_buffer.row(2).clone_owned(),
-            if config.spectrum_calibration.scaling.is_some() {
-                let mut sum = combined_buffer.row_sum();
-                sum.iter_mut().enumerate().for_each(|(i, v)| {
-                    *v *= config.spectrum_calibration.get_scaling_factor_from_index(i);
-                });
-                sum / 3.
-            } else {
-                combined_buffer.row_sum() / 3.
-            },
+            combined_channel(combined_buffer.row_sum(), &config.spectrum_calibration),
         ]);
 
-        if config.postprocessing_config.spectrum_filter_active {
-            let cutoff = config
-                .postprocessing_config
-                .spectrum_filter_cutoff
-                .clamp(0.001, 1.);
-            let fs: Hertz<f32> = 2.0.hz();
-            let f0: Hertz<f32> = cutoff.hz();
+        match postprocessing.effective_smoothing_method() {
+            SmoothingMethod::None => {}
+            SmoothingMethod::LowPass => {
+                let cutoff = postprocessing.spectrum_filter_cutoff.clamp(0.001, 1.);
+                let fs: Hertz<f32> = 2.0.hz();
+                let f0: Hertz<f32> = cutoff.hz();
 
-            let coeffs =
-                Coefficients::<f32>::from_params(Type::LowPass, fs, f0, Q_BUTTERWORTH_F32).unwrap();
-            for mut channel in current_spectrum.row_iter_mut() {
-                let mut biquad = DirectForm2Transposed::<f32>::new(coeffs);
-                for sample in channel.iter_mut() {
-                    *sample = biquad.run(*sample);
-                }
-                // Apply filter in reverse to compensate phase error
-                for sample in channel.iter_mut().rev() {
-                    *sample = biquad.run(*sample);
+                let coeffs =
+                    Coefficients::<f32>::from_params(Type::LowPass, fs, f0, Q_BUTTERWORTH_F32)
+                        .unwrap();
+                for mut channel in current_spectrum.row_iter_mut() {
+                    let mut biquad = DirectForm2Transposed::<f32>::new(coeffs);
+                    for sample in channel.iter_mut() {
+                        *sample = biquad.run(*sample);
+                    }
+                    // Apply filter in reverse to compensate phase error
+                    for sample in channel.iter_mut().rev() {
+                        *sample = biquad.run(*sample);
+                    }
                 }
             }
+            SmoothingMethod::SavitzkyGolay => map_channels(&mut current_spectrum, |c| {
+                savitzky_golay(
+                    c,
+                    postprocessing.savitzky_golay_half_width,
+                    postprocessing.savitzky_golay_order,
+                )
+            }),
+            SmoothingMethod::Median => map_channels(&mut current_spectrum, |c| {
+                median(c, postprocessing.median_half_width)
+            }),
+            SmoothingMethod::Gaussian => map_channels(&mut current_spectrum, |c| {
+                gaussian(c, postprocessing.gaussian_sigma)
+            }),
+        }
+        if postprocessing.baseline.method != BaselineMethod::None {
+            map_channels(&mut current_spectrum, |c| {
+                postprocessing.baseline.subtract(c)
+            });
+        }
+
+        // Of the combined channel of the buffered frames, from the running moments
+        let frames = self.spectrum_buffer.len();
+        let detection_limit = (frames >= 2).then(|| {
+            let calibration = &config.spectrum_calibration;
+            let variances: Vec<f32> = (0..ncols)
+                .map(|i| {
+                    let (qe_r, qe_g, qe_b) = qe_correction
+                        .as_ref()
+                        .map_or((1., 1., 1.), |qe_correction| qe_correction[i]);
+                    let scale = calibration.get_scaling_factor_from_index(i) / 3.;
+                    let weights = [
+                        calibration.gain_r * qe_r * scale,
+                        calibration.gain_g * qe_g * scale,
+                        calibration.gain_b * qe_b * scale,
+                    ];
+                    weighted_variance(
+                        self.buffer_moments.column(i),
+                        frames,
+                        weights.map(f64::from),
+                    )
+                })
+                .collect();
+            band_limits(&variances, frames, postprocessing.noise_band_width)
+        });
+
+        let deconvolution = &config.postprocessing_config.deconvolution;
+        if let Some(line_shape) = config.postprocessing_config.effective_line_shape() {
+            for mut channel in current_spectrum.row_iter_mut() {
+                let observed: Vec<f32> = channel.iter().copied().collect();
+                let deconvolved = richardson_lucy(
+                    &observed,
+                    line_shape,
+                    deconvolution.iterations,
+                    deconvolution.regularization,
+                );
+                channel
+                    .iter_mut()
+                    .zip(deconvolved)
+                    .for_each(|(v, d)| *v = d);
+            }
+        }
+
+        let reference = reference.filter(|r| r.ncols() == ncols);
+        if let Some(reference) = &reference {
+            let reference_beam = config.postprocessing_config.reference_beam;
+            current_spectrum
+                .iter_mut()
+                .zip(reference.iter())
+                .for_each(|(v, r)| *v = reference_beam.apply(*v, *r));
         }
 
         if let Some(zero_reference) = self.zero_reference.as_ref() {
             current_spectrum -= zero_reference;
         }
 
-        self.spectrum = current_spectrum;
+        if postprocessing.is_transforming() {
+            let wavelengths: Vec<f32> = (0..ncols)
+                .map(|i| config.spectrum_calibration.get_wavelength_from_index(i))
+                .collect();
+            for c in 0..3 {
+                let points: Vec<SpectrumPoint> = wavelengths
+                    .iter()
+                    .zip(current_spectrum.row(c).iter())
+                    .map(|(&wavelength, &value)| SpectrumPoint { wavelength, value })
+                    .collect();
+                if let Some(values) = postprocessing.transform(&points) {
+                    current_spectrum
+                        .row_mut(c)
+                        .iter_mut()
+                        .zip(values)
+                        .for_each(|(v, transformed)| *v = transformed);
+                }
+            }
+            // Combined from the transformed colors, not transformed itself
+            let combined = combined_channel(
+                current_spectrum.rows(0, 3).row_sum(),
+                &config.spectrum_calibration,
+            );
+            current_spectrum.row_mut(3).copy_from(&combined);
+        }
+
+        let spectrum_math = &postprocessing.spectrum_math;
+        if spectrum_math.is_active() {
+            current_spectrum
+                .row_mut(3)
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, v)| {
+                    *v = spectrum_math
+                        .apply(config.spectrum_calibration.get_wavelength_from_index(i), *v)
+                });
+        }
+
+        self.frame = SpectrumFrame {
+            spectrum: current_spectrum,
+            detection_limit,
+            averaged_frames,
+            buffered_frames: self.buffered_frames.iter().copied().collect(),
+            zero_reference_applied: self.zero_reference.is_some(),
+            reference_beam: reference
+                .is_some()
+                .then_some(config.postprocessing_config.reference_beam),
+            ..SpectrumFrame::default()
+        };
+        &self.frame
     }
 
-    pub fn spectrum_to_peaks_and_dips(
-        &self,
-        peaks: bool,
-        config: &SpectrometerConfig,
-    ) -> Vec<SpectrumPoint> {
-        let mut peaks_dips = Vec::new();
+    pub fn set_zero_reference(&mut self) {
+        self.zero_reference = Some(self.frame.spectrum.clone());
+    }
 
-        let spectrum: Vec<_> = self.spectrum.row(3).iter().cloned().collect();
+    pub fn clear_zero_reference(&mut self) {
+        self.zero_reference = None;
+    }
 
-        let windows_size = config.view_config.peaks_dips_find_window * 2 + 1;
-        let mid_index = (windows_size - 1) / 2;
+    /// Use a zero reference taken earlier, e.g. in the last session.
+    pub fn load_zero_reference(&mut self, zero_reference: Spectrum) {
+        self.zero_reference = Some(zero_reference);
+    }
+}
 
-        for (i, win) in spectrum.as_slice().windows(windows_size).enumerate() {
-            let (lower, upper) = win.split_at(mid_index);
+#[derive(Debug, Clone)]
+pub enum ProcessorEvent {
+    Config {
+        spectrum_calibration: Box<SpectrumCalibration>,
+        postprocessing_config: Box<PostprocessingConfig>,
+    },
+    ClearBuffer,
+    /// Drop the spectrum with the given [BufferedFrame::id] from the averaging buffer.
+    DropFrame(u64),
+    /// Process every spectrum on its own instead of averaging, e.g. for a still image.
+    SingleShot(bool),
+    SetZeroReference,
+    ClearZeroReference,
+    LoadZeroReference(Box<Spectrum>),
+    /// Process a generated raw spectrum instead of every received one, see
+    /// [crate::test_pattern], or go back to the received ones with `None`.
+    TestPattern(Option<Box<SpectrumRgb>>),
+    /// Merge spectra taken with the given relative exposures into one, see [crate::hdr], or
+    /// go back to processing every spectrum with `None`.
+    Hdr(Option<Vec<f32>>),
+    /// The following spectra belong to the HDR bracket with the index, or are discarded
+    /// while the exposure settles with `None`.
+    HdrBracket(Option<usize>),
+    /// Subtract the dark of the model from every raw spectrum, see [crate::dark_library], or
+    /// stop with `None`.
+    DarkModel(Option<Box<DarkModel>>),
+    /// Current value of the exposure control, for the dark of the model.
+    Exposure(i32),
+    /// Average the following raw spectra into a dark taken at the exposure, which is returned
+    /// with [SpectrumFrame::captured_dark].
+    CaptureDark(i32),
+    /// Record every following raw spectrum into the file at the path, see
+    /// [crate::recording], or stop with `None`.
+    Record(Option<String>),
+    /// Process the demodulated spectra of the lock-in detection instead of every raw
+    /// spectrum, see [crate::lock_in], or go back with `None`.
+    LockIn(Option<Box<LockInDemodulator>>),
+    /// The following spectra are taken with the toggled source of the lock-in detection on
+    /// or off, or are discarded while it switches with `None`.
+    LockInState(Option<bool>),
+}
 
-            if lower.iter().chain(upper[1..].iter()).all(|&v| {
-                if peaks {
-                    v < win[mid_index]
-                } else {
-                    v > win[mid_index]
+/// Runs a [SpectrumProcessor] on its own thread, sends every processed frame and publishes it
+/// to the subscribers of the [SpectroEngine].
+pub struct SpectrumWorker {
+    processor: SpectrumProcessor,
+    config: SpectrometerConfig,
+    spectrum_rx: Receiver<RawSpectra>,
+    event_rx: Receiver<ProcessorEvent>,
+    frame_tx: Sender<SpectrumFrame>,
+    engine: SpectroEngine,
+    single_shot: bool,
+    test_pattern: Option<SpectrumRgb>,
+    hdr: Option<HdrMerger>,
+    lock_in: Option<LockInDemodulator>,
+    dark_model: Option<DarkModel>,
+    exposure: i32,
+    dark_capture: Option<DarkCapture>,
+    captured_dark: Option<DarkFrame>,
+    /// Path of the recording until its first spectrum creates the recorder.
+    recording_path: Option<String>,
+    recorder: Option<Recorder>,
+}
+
+impl SpectrumWorker {
+    pub fn new(
+        spectrum_rx: Receiver<RawSpectra>,
+        event_rx: Receiver<ProcessorEvent>,
+        frame_tx: Sender<SpectrumFrame>,
+        engine: SpectroEngine,
+    ) -> Self {
+        Self {
+            processor: SpectrumProcessor::new(),
+            config: SpectrometerConfig::default(),
+            spectrum_rx,
+            event_rx,
+            frame_tx,
+            engine,
+            single_shot: false,
+            test_pattern: None,
+            hdr: None,
+            lock_in: None,
+            dark_model: None,
+            exposure: 0,
+            dark_capture: None,
+            captured_dark: None,
+            recording_path: None,
+            recorder: None,
+        }
+    }
+
+    pub fn run(&mut self) -> ! {
+        loop {
+            while let Ok(event) = self.event_rx.try_recv() {
+                self.handle_event(event);
+            }
+            // Wake up regularly to handle events without new spectra
+            if let Ok(mut spectra) = self.spectrum_rx.recv_timeout(Duration::from_millis(50)) {
+                self.record(&spectra);
+                if self.config.postprocessing_config.low_latency {
+                    // Skip spectra which queued up, only the freshest one matters
+                    while let Ok(latest) = self.spectrum_rx.try_recv() {
+                        self.record(&latest);
+                        spectra = latest;
+                    }
                 }
-            }) {
-                peaks_dips.push(SpectrumPoint {
-                    wavelength: config
-                        .spectrum_calibration
-                        .get_wavelength_from_index(i + mid_index),
-                    value: win[mid_index],
-                })
+                self.handle_spectra(spectra);
             }
         }
+    }
 
-        let mut filtered_peaks_dips = Vec::new();
+    /// Process the raw spectra, then send and publish the frame unless they are merged into a
+    /// later one.
+    fn handle_spectra(&mut self, spectra: RawSpectra) {
+        let RawSpectra {
+            sample: mut spectrum,
+            mut reference,
+        } = spectra;
+        if let Some(dark) = self.dark_capture.as_mut().and_then(|c| c.add(&spectrum)) {
+            self.captured_dark = Some(dark);
+            self.dark_capture = None;
+        }
+        let dark = self
+            .dark_model
+            .as_ref()
+            .filter(|model| model.ncols() == spectrum.ncols());
+        if let Some(dark) = dark {
+            let dark = dark.dark(self.exposure);
+            spectrum -= &dark;
+            if let Some(reference) = reference.as_mut().filter(|r| r.ncols() == dark.ncols()) {
+                *reference -= &dark;
+            }
+        }
+        let dark_subtracted = dark.is_some() && self.test_pattern.is_none();
+        if let Some(test_pattern) = &self.test_pattern {
+            spectrum = test_pattern.clone();
+            reference = None;
+        } else if let Some(hdr) = &mut self.hdr {
+            // Only the window is merged, the reference beam needs a fixed exposure
+            reference = None;
+            spectrum = match hdr.add(spectrum) {
+                None => return,
+                Some(merged) => merged,
+            };
+        } else if let Some(lock_in) = &mut self.lock_in {
+            // The reference beam is not modulated
+            reference = None;
+            spectrum = match lock_in.add(spectrum, Instant::now()) {
+                None => return,
+                Some(demodulated) => demodulated,
+            };
+        }
+        if self.single_shot {
+            self.processor.clear_buffer();
+        }
+        let mut frame = self
+            .processor
+            .process_with_reference(spectrum, reference, &self.config)
+            .clone();
+        frame.dark_subtracted = dark_subtracted;
+        frame.dark_capture_frames = self.dark_capture.as_ref().map(DarkCapture::frames);
+        frame.recorded_frames = self
+            .recorder
+            .as_ref()
+            .map(Recorder::frames)
+            .or(self.recording_path.as_ref().map(|_| 0));
+        // Kept until a frame is sent, merging HDR brackets skips some
+        frame.captured_dark = self.captured_dark.take();
+        if self.engine.has_subscribers() {
+            self.engine
+                .publish(frame.to_processed_spectrum(&self.config));
+        }
+        self.frame_tx.send(frame).unwrap();
+    }
 
-        let window = config.view_config.peaks_dips_unique_window;
+    pub fn handle_event(&mut self, event: ProcessorEvent) {
+        match event {
+            ProcessorEvent::Config {
+                spectrum_calibration,
+                postprocessing_config,
+            } => {
+                self.config.spectrum_calibration = *spectrum_calibration;
+                self.config.postprocessing_config = *postprocessing_config;
+            }
+            ProcessorEvent::ClearBuffer => self.processor.clear_buffer(),
+            ProcessorEvent::DropFrame(id) => self.processor.drop_frame(id),
+            ProcessorEvent::SingleShot(single_shot) => self.single_shot = single_shot,
+            ProcessorEvent::SetZeroReference => self.processor.set_zero_reference(),
+            ProcessorEvent::ClearZeroReference => self.processor.clear_zero_reference(),
+            ProcessorEvent::LoadZeroReference(zero_reference) => {
+                self.processor.load_zero_reference(*zero_reference)
+            }
+            ProcessorEvent::TestPattern(spectrum) => {
+                self.test_pattern = spectrum.map(|s| *s);
+                self.processor.clear_buffer();
+            }
+            ProcessorEvent::Hdr(relative_exposures) => {
+                self.hdr = relative_exposures.map(HdrMerger::new);
+                self.processor.clear_buffer();
+            }
+            ProcessorEvent::HdrBracket(bracket) => {
+                if let Some(hdr) = &mut self.hdr {
+                    hdr.set_bracket(bracket);
+                }
+            }
+            ProcessorEvent::LockIn(lock_in) => {
+                self.lock_in = lock_in.map(|l| *l);
+                self.processor.clear_buffer();
+            }
+            ProcessorEvent::LockInState(state) => {
+                if let Some(lock_in) = &mut self.lock_in {
+                    lock_in.set_state(state);
+                }
+            }
+            ProcessorEvent::DarkModel(model) => {
+                self.dark_model = model.map(|m| *m);
+                self.processor.clear_buffer();
+            }
+            ProcessorEvent::Exposure(exposure) => self.exposure = exposure,
+            ProcessorEvent::CaptureDark(exposure) => {
+                self.dark_capture = Some(DarkCapture::new(exposure))
+            }
+            ProcessorEvent::Record(path) => {
+                self.recording_path = path;
+                self.recorder = None;
+            }
+        }
+    }
 
-        for peak_dip in &peaks_dips {
-            if peak_dip.value
-                == peaks_dips
-                    .iter()
-                    .filter(|sp| {
-                        sp.wavelength > peak_dip.wavelength - window / 2.
-                            && sp.wavelength < peak_dip.wavelength + window / 2.
-                    })
-                    .map(|sp| sp.value)
-                    .reduce(if peaks { f32::max } else { f32::min })
-                    .unwrap()
-            {
-                filtered_peaks_dips.push(*peak_dip);
+    /// Add the raw spectra to the running recording, which stops on errors.
+    fn record(&mut self, spectra: &RawSpectra) {
+        let path = match &self.recording_path {
+            Some(path) => path,
+            None => return,
+        };
+        let result = match &mut self.recorder {
+            Some(recorder) => recorder.record(spectra),
+            None => {
+                let calibration = &self.config.spectrum_calibration;
+                let wavelengths = (0..spectra.sample.ncols())
+                    .map(|i| calibration.get_wavelength_from_index(i))
+                    .collect();
+                Recorder::create(path, spectra, wavelengths).and_then(|mut recorder| {
+                    let recorded = recorder.record(spectra);
+                    self.recorder = Some(recorder);
+                    recorded
+                })
+            }
+        };
+        match result {
+            Ok(true) => {}
+            Ok(false) => log::warn!("Skipped recording a spectrum with a different layout"),
+            Err(e) => {
+                log::error!("Recording to {} failed: {}", path, e);
+                self.recording_path = None;
+                self.recorder = None;
             }
         }
-        filtered_peaks_dips
+    }
+}
+
+/// Averaged and calibrated spectrum as produced by [SpectrumProcessor].
+#[derive(Debug, Clone)]
+pub struct SpectrumFrame {
+    spectrum: Spectrum,
+    detection_limit: Option<Vec<f32>>,
+    averaged_frames: usize,
+    buffered_frames: Vec<BufferedFrame>,
+    zero_reference_applied: bool,
+    /// Relation to the reference strip, if the reference beam mode is on.
+    reference_beam: Option<ReferenceBeam>,
+    dark_subtracted: bool,
+    /// Spectra averaged so far by a running dark capture.
+    dark_capture_frames: Option<usize>,
+    /// Dark completed with this frame, see [ProcessorEvent::CaptureDark].
+    captured_dark: Option<DarkFrame>,
+    /// Raw spectra written so far by a running recording, see [ProcessorEvent::Record].
+    recorded_frames: Option<u64>,
+}
+
+impl Default for SpectrumFrame {
+    fn default() -> Self {
+        Self {
+            spectrum: Spectrum::zeros(0),
+            detection_limit: None,
+            averaged_frames: 0,
+            buffered_frames: vec![],
+            zero_reference_applied: false,
+            reference_beam: None,
+            dark_subtracted: false,
+            dark_capture_frames: None,
+            captured_dark: None,
+            recorded_frames: None,
+        }
+    }
+}
+
+impl SpectrumFrame {
+    /// Spectrum with the rows r, g, b and sum.
+    pub fn spectrum(&self) -> &Spectrum {
+        &self.spectrum
+    }
+
+    pub fn spectrum_to_peaks_and_dips(
+        &self,
+        peaks: bool,
+        config: &SpectrometerConfig,
+    ) -> Vec<SpectrumPoint> {
+        let spectrum = self.spectrum.row(3);
+        self.peak_dip_indices(peaks, config)
+            .into_iter()
+            .map(|i| SpectrumPoint {
+                wavelength: config.spectrum_calibration.get_wavelength_from_index(i),
+                value: spectrum[i],
+            })
+            .collect()
+    }
+
+    /// Fit the configured peak shape to every detected peak of the sum channel.
+    pub fn fit_peaks(&self, config: &SpectrometerConfig) -> Vec<PeakFit> {
+        let spectrum = self.get_spectrum_channel(3, config);
+        let find_window = config.view_config.peak_find_window();
+        self.peak_dip_indices(true, config)
+            .into_iter()
+            .filter_map(|i| {
+                let (left, right) = find_window.extent(&spectrum, i)?;
+                fit_peak(
+                    &spectrum[i - left..=i + right],
+                    config.view_config.peak_shape,
+                )
+            })
+            .collect()
+    }
+
+    /// Indices of the peaks or dips of the sum channel outside of the masked Rayleigh line.
+    fn peak_dip_indices(&self, peaks: bool, config: &SpectrometerConfig) -> Vec<usize> {
+        let spectrum = self.get_spectrum_channel(3, config);
+        let axis = &config.view_config.axis;
+        find_peaks_dips(
+            &spectrum,
+            peaks,
+            config.view_config.peak_find_window(),
+            config.view_config.peaks_dips_unique_window,
+        )
+        .into_iter()
+        .filter(|&i| !axis.is_masked(spectrum[i].wavelength))
+        .collect()
     }
 
     pub fn get_spectrum_channel(
@@ -274,55 +1142,439 @@ impl SpectrumContainer {
                 })
                 .collect(),
         );
+        calibration.scaling_unit = reference_config.unit;
+    }
+
+    /// Line shape of the narrow line in the current sum channel, see [extract_line_shape].
+    pub fn get_line_shape(&self, half_width: usize) -> Option<Vec<f32>> {
+        let sum: Vec<f32> = self.spectrum.row(3).iter().copied().collect();
+        extract_line_shape(&sum, half_width)
+    }
+
+    /// Detection limit of the sum channel, see [detection_limit].
+    pub fn get_detection_limit(&self, config: &SpectrometerConfig) -> Vec<SpectrumPoint> {
+        self.detection_limit
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(i, v)| SpectrumPoint {
+                wavelength: config.spectrum_calibration.get_wavelength_from_index(i),
+                value: *v,
+            })
+            .collect()
+    }
+
+    /// Frames averaged into the spectrum, without rejected outliers.
+    pub fn averaged_frames(&self) -> usize {
+        self.averaged_frames
+    }
+
+    /// Spectra in the averaging buffer, newest first.
+    pub fn buffered_frames(&self) -> &[BufferedFrame] {
+        &self.buffered_frames
     }
 
     pub fn has_zero_reference(&self) -> bool {
-        self.zero_reference.is_some()
+        self.zero_reference_applied
     }
 
-    pub fn set_zero_reference(&mut self) {
-        self.zero_reference = Some(self.spectrum.clone());
+    pub fn reference_beam(&self) -> Option<ReferenceBeam> {
+        self.reference_beam
     }
 
-    pub fn clear_zero_reference(&mut self) {
-        self.zero_reference = None;
+    pub fn dark_subtracted(&self) -> bool {
+        self.dark_subtracted
+    }
+
+    /// Number of spectra averaged so far while a dark is captured.
+    pub fn dark_capture_frames(&self) -> Option<usize> {
+        self.dark_capture_frames
+    }
+
+    /// Number of raw spectra written so far while recording.
+    pub fn recorded_frames(&self) -> Option<u64> {
+        self.recorded_frames
+    }
+
+    /// Dark whose capture completed with this frame.
+    pub fn captured_dark(&self) -> Option<&DarkFrame> {
+        self.captured_dark.as_ref()
+    }
+
+    /// Write the traces selected in the export config, with the snapshots and the reference
+    /// interpolated to the wavelengths of the spectrum and left empty outside of them.
+    pub fn write_to_csv(&self, path: &String, config: &SpectrometerConfig) -> Result<(), String> {
+        let axis = &config.view_config.axis;
+        let traces = config.import_export_config.export_traces;
+        let mut overlays = vec![];
+        if traces.snapshots {
+            overlays.extend(
+                config
+                    .snapshots
+                    .iter()
+                    .map(|s| (s.name.clone(), s.spectrum.clone())),
+            );
+        }
+        if traces.reference {
+            let reference_config = &config.reference_config;
+            if let Some(reference) = &reference_config.reference {
+                let scaled = reference
+                    .iter()
+                    .map(|p| SpectrumPoint {
+                        wavelength: p.wavelength,
+                        value: p.value * reference_config.scale,
+                    })
+                    .collect();
+                overlays.push(("reference".to_string(), scaled));
+            }
+        }
+        for (_, spectrum) in &mut overlays {
+            spectrum.sort_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+        }
+
+        let channels = [
+            ("r", traces.r),
+            ("g", traces.g),
+            ("b", traces.b),
+            ("sum", traces.sum),
+        ];
+        let converted = axis.unit != AxisUnit::Nanometer;
+        let mut header = vec!["wavelength".to_string()];
+        header.extend(
+            channels
+                .iter()
+                .filter(|(_, selected)| *selected)
+                .map(|(name, _)| name.to_string()),
+        );
+        if traces.detection_limit {
+            header.push("detection_limit".to_string());
+        }
+        header.extend(overlays.iter().map(|(name, _)| name.clone()));
+        header.push("unit".to_string());
+        if converted {
+            header.extend(["axis", "axis_unit", "jacobian"].map(String::from));
+        }
+
+        let mut writer = csv::Writer::from_path(path).map_err(|e| e.to_string())?;
+        writer.write_record(&header).map_err(|e| e.to_string())?;
+        let optional = |value: Option<f32>| value.map_or(String::new(), |v| v.to_string());
+        for p in self.export_points(config, axis) {
+            let mut record = vec![p.wavelength.to_string()];
+            for ((_, selected), value) in channels.iter().zip([p.r, p.g, p.b, p.sum]) {
+                if *selected {
+                    record.push(value.to_string());
+                }
+            }
+            if traces.detection_limit {
+                record.push(optional(p.detection_limit));
+            }
+            let jacobian = axis.jacobian(p.wavelength);
+            for (_, spectrum) in &overlays {
+                record.push(optional(
+                    interpolate(spectrum, p.wavelength).map(|v| v * jacobian),
+                ));
+            }
+            record.push(format!("{:?}", p.unit));
+            if converted {
+                record.push(optional(p.axis));
+                record.push(p.axis_unit.map_or(String::new(), |u| format!("{:?}", u)));
+                record.push(p.jacobian.map_or(String::new(), |j| j.to_string()));
+            }
+            writer.write_record(&record).map_err(|e| e.to_string())?;
+        }
+        writer.flush().map_err(|e| e.to_string())
     }
 
-    pub fn write_to_csv(
+    pub fn write_to_jcamp_dx(
         &self,
         path: &String,
-        calibration: &SpectrumCalibration,
+        config: &SpectrometerConfig,
     ) -> Result<(), String> {
-        let writer = csv::Writer::from_path(path);
-        match writer {
-            Ok(mut writer) => {
-                for p in self.spectrum_to_point_vec(calibration) {
-                    writer.serialize(p).unwrap();
-                }
-                writer.flush().unwrap();
-                Ok(())
+        let calibration = &config.spectrum_calibration;
+        let axis = &config.view_config.axis;
+        let title = Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        write_jcamp_dx(
+            &mut writer,
+            &title,
+            &self.export_points(config, axis),
+            calibration,
+            axis,
+        )
+        .and_then(|_| writer.flush())
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn write_to_spc(&self, path: &String, config: &SpectrometerConfig) -> Result<(), String> {
+        let calibration = &config.spectrum_calibration;
+        let axis = &config.view_config.axis;
+        let title = Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        write_spc(
+            &mut writer,
+            &title,
+            &self.export_points(config, axis),
+            calibration,
+            axis,
+        )
+        .and_then(|_| writer.flush())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Write the channels as HDF5 datasets with the calibration and `attributes`, e.g. about
+    /// the camera, as attributes of the file.
+    pub fn write_to_hdf5(
+        &self,
+        path: &String,
+        config: &SpectrometerConfig,
+        attributes: &[(&str, Hdf5Value)],
+    ) -> Result<(), String> {
+        let calibration = &config.spectrum_calibration;
+        let axis = &config.view_config.axis;
+        let points = self.export_points(config, axis);
+        let n = points.len();
+        let channel = |name: &str, value: fn(&SpectrumExportPoint) -> f32| {
+            Hdf5Dataset::new(
+                name,
+                &[n],
+                Hdf5Data::F32(points.iter().map(value).collect()),
+            )
+            .attribute("unit", calibration.intensity_unit().symbol())
+            .attribute("per_axis_unit", axis.converts_intensity() as i64)
+        };
+
+        let mut hdf5 = Hdf5File::default();
+        hdf5.attribute(
+            "software",
+            format!("spectro-cam-rs {}", env!("CARGO_PKG_VERSION")),
+        );
+        hdf5.attribute("created", Local::now().to_rfc3339());
+        hdf5.attribute("calibration_id", calibration.fingerprint().to_string());
+        for (name, point) in [
+            ("calibration_low", calibration.low),
+            ("calibration_high", calibration.high),
+        ] {
+            hdf5.attribute(
+                name,
+                format!("{} nm at index {}", point.wavelength, point.index),
+            );
+        }
+        hdf5.attribute("calibration_offset", calibration.offset);
+        hdf5.attribute("linearize", calibration.linearize.to_string());
+        hdf5.attribute(
+            "gain_rgb",
+            format!(
+                "{}, {}, {}",
+                calibration.gain_r, calibration.gain_g, calibration.gain_b
+            ),
+        );
+        hdf5.attribute("averaged_frames", self.averaged_frames as i64);
+        hdf5.attribute("zero_reference_applied", self.zero_reference_applied as i64);
+        for (name, value) in attributes {
+            hdf5.attribute(name, value.clone());
+        }
+        hdf5.dataset(
+            Hdf5Dataset::new(
+                "wavelength",
+                &[n],
+                Hdf5Data::F32(points.iter().map(|p| p.wavelength).collect()),
+            )
+            .attribute("unit", "nm"),
+        );
+        if axis.unit != AxisUnit::Nanometer {
+            hdf5.dataset(
+                Hdf5Dataset::new(
+                    "axis",
+                    &[n],
+                    Hdf5Data::F32(points.iter().filter_map(|p| p.axis).collect()),
+                )
+                .attribute("name", axis.unit.to_string())
+                .attribute("unit", axis.unit.symbol()),
+            );
+        }
+        hdf5.dataset(channel("r", |p| p.r));
+        hdf5.dataset(channel("g", |p| p.g));
+        hdf5.dataset(channel("b", |p| p.b));
+        hdf5.dataset(channel("sum", |p| p.sum));
+        if self.detection_limit.is_some() {
+            hdf5.dataset(channel("detection_limit", |p| {
+                p.detection_limit.unwrap_or(f32::NAN)
+            }));
+        }
+
+        let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        hdf5.write(&mut writer)
+            .and_then(|_| writer.flush())
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn write_to_file(
+        &self,
+        path: &String,
+        format: ExportFormat,
+        config: &SpectrometerConfig,
+    ) -> Result<(), String> {
+        match format {
+            ExportFormat::Csv => self.write_to_csv(path, config),
+            ExportFormat::JcampDx => self.write_to_jcamp_dx(path, config),
+            ExportFormat::Spc => self.write_to_spc(path, config),
+            ExportFormat::Hdf5 => self.write_to_hdf5(path, config, &[]),
+            ExportFormat::SpectralWorkbench | ExportFormat::Theremino => {
+                self.write_to_community_format(path, format, config)
+            }
+        }
+    }
+
+    /// Calibrated channels as tab separated text with a header line, to paste into a
+    /// spreadsheet.
+    pub fn to_tab_separated(&self, calibration: &SpectrumCalibration, axis: &AxisConfig) -> String {
+        let mut text = format!("{}\tR\tG\tB\tSum\n", axis.unit.axis_label());
+        for p in self.spectrum_to_point_vec(calibration, axis) {
+            let _ = writeln!(
+                text,
+                "{}\t{}\t{}\t{}\t{}",
+                p.axis.unwrap_or(p.wavelength),
+                p.r,
+                p.g,
+                p.b,
+                p.sum
+            );
+        }
+        text
+    }
+
+    /// Write the spectrum over nm for Spectral Workbench or Theremino Spectrometer.
+    pub fn write_to_community_format(
+        &self,
+        path: &String,
+        format: ExportFormat,
+        config: &SpectrometerConfig,
+    ) -> Result<(), String> {
+        let title = Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let points = self.export_points(config, &AxisConfig::default());
+        let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        match format {
+            ExportFormat::SpectralWorkbench => {
+                write_spectral_workbench(&mut writer, &title, &points)
             }
-            Err(e) => Err(e.to_string()),
+            _ => write_theremino(&mut writer, &points),
         }
+        .and_then(|_| writer.flush())
+        .map_err(|e| e.to_string())
     }
 
-    fn spectrum_to_point_vec(&self, calibration: &SpectrumCalibration) -> Vec<SpectrumExportPoint> {
+    /// Points of the spectrum, resampled onto the grid of the export config if enabled.
+    fn export_points(
+        &self,
+        config: &SpectrometerConfig,
+        axis: &AxisConfig,
+    ) -> Vec<SpectrumExportPoint> {
+        let mut points = self.spectrum_to_point_vec(&config.spectrum_calibration, axis);
+        let resampling = config.import_export_config.resampling;
+        if resampling.resampling == Resampling::Off || points.len() < 2 {
+            return points;
+        }
+        points.sort_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+        let (first, last) = (points[0], points[points.len() - 1]);
+        let grid = resampling.grid(first.wavelength..=last.wavelength);
+        let x: Vec<f32> = points.iter().map(|p| p.wavelength).collect();
+        let channel = |value: fn(&SpectrumExportPoint) -> f32| {
+            let y: Vec<f32> = points.iter().map(value).collect();
+            resample(&x, &y, &grid, resampling.resampling)
+        };
+        let (r, g, b, sum) = (
+            channel(|p| p.r),
+            channel(|p| p.g),
+            channel(|p| p.b),
+            channel(|p| p.sum),
+        );
+        let detection_limit = first
+            .detection_limit
+            .map(|_| channel(|p| p.detection_limit.unwrap_or(0.)));
+        grid.iter()
+            .enumerate()
+            .filter(|(_, &wavelength)| !axis.is_masked(wavelength))
+            .map(|(i, &wavelength)| SpectrumExportPoint {
+                wavelength,
+                r: r[i],
+                g: g[i],
+                b: b[i],
+                sum: sum[i],
+                detection_limit: detection_limit.as_ref().map(|d| d[i]),
+                axis: first.axis.map(|_| axis.convert(wavelength)),
+                ..first
+            })
+            .collect()
+    }
+
+    fn spectrum_to_point_vec(
+        &self,
+        calibration: &SpectrumCalibration,
+        axis: &AxisConfig,
+    ) -> Vec<SpectrumExportPoint> {
+        let converted = axis.unit != AxisUnit::Nanometer;
         self.spectrum
             .column_iter()
             .enumerate()
             .map(|(i, p)| {
                 let x = calibration.get_wavelength_from_index(i);
+                let jacobian = axis.jacobian(x);
                 SpectrumExportPoint {
                     wavelength: x,
-                    r: p[0],
-                    g: p[1],
-                    b: p[2],
-                    sum: p[3],
+                    r: p[0] * jacobian,
+                    g: p[1] * jacobian,
+                    b: p[2] * jacobian,
+                    sum: p[3] * jacobian,
+                    detection_limit: self
+                        .detection_limit
+                        .as_ref()
+                        .and_then(|limit| limit.get(i).map(|l| l * jacobian)),
+                    unit: calibration.intensity_unit(),
+                    axis: converted.then(|| axis.convert(x)),
+                    axis_unit: converted.then_some(axis.unit),
+                    jacobian: converted.then_some(axis.converts_intensity()),
                 }
             })
+            .filter(|p| !axis.is_masked(p.wavelength))
             .collect()
     }
 
+    pub fn to_processed_spectrum(&self, config: &SpectrometerConfig) -> ProcessedSpectrum {
+        let calibration = &config.spectrum_calibration;
+        ProcessedSpectrum {
+            timestamp: SystemTime::now(),
+            wavelengths: (0..self.spectrum.ncols())
+                .map(|i| calibration.get_wavelength_from_index(i))
+                .collect(),
+            channels: self.spectrum.clone(),
+            averaged_frames: self.averaged_frames,
+            flags: SpectrumFlags {
+                linearized: calibration.linearize != Linearize::Off,
+                intensity_calibrated: calibration.scaling.is_some(),
+                filtered: config.postprocessing_config.effective_smoothing_method()
+                    != SmoothingMethod::None,
+                zero_reference_applied: self.zero_reference_applied,
+                dark_subtracted: self.dark_subtracted,
+                baseline_corrected: config.postprocessing_config.baseline.method
+                    != BaselineMethod::None,
+                deconvolved: config
+                    .postprocessing_config
+                    .effective_line_shape()
+                    .is_some(),
+                transformed: config.postprocessing_config.is_transforming(),
+            },
+        }
+    }
+
     pub fn get_spectrum_max_value(&self) -> Option<f32> {
         self.spectrum.iter().cloned().reduce(f32::max)
     }
@@ -331,12 +1583,172 @@ impl SpectrumContainer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{ExportTraces, ResamplingConfig, Snapshot};
+    use approx::assert_relative_eq;
     use rstest::*;
 
+    #[rstest]
+    #[case(BinningMode::Sum, 0.28)]
+    #[case(BinningMode::Median, 0.2)]
+    #[case(BinningMode::Gaussian, 0.2 + 0.4 * 0.4238)]
+    fn binning(#[case] mode: BinningMode, #[case] expected: f32) {
+        // Spectrum on the second row of five and a hot pixel in the first column
+        let window = image::RgbImage::from_fn(3, 5, |column, row| {
+            let value = match (column, row) {
+                (0, 4) => 255,
+                (_, 1) => 153,
+                _ => 51,
+            };
+            Rgb([value; 3])
+        });
+        let binning = Binning {
+            mode,
+            center: 1.,
+            sigma: 1.,
+        };
+        let spectrum = SpectrumCalculator::process_window(&window, &binning);
+        assert_relative_eq!(spectrum[(1, 2)] * 3., expected, max_relative = 1e-3);
+        if mode == BinningMode::Median {
+            assert_eq!(spectrum.column(0), spectrum.column(2));
+        }
+    }
+
+    #[rstest]
+    fn outlier_rejection(
+        mut spectrum_processor: SpectrumProcessor,
+        mut config: SpectrometerConfig,
+    ) {
+        config.postprocessing_config.outlier_rejection = true;
+        // Slightly noisy frames and one with a passing shadow
+        for i in 0..9 {
+            let level = 0.3 + 0.001 * (i % 3) as f32;
+            spectrum_processor.process(SpectrumRgb::from_element(100, level), &config);
+        }
+        let frame = spectrum_processor.process(SpectrumRgb::from_element(100, 0.1), &config);
+        assert_eq!(frame.averaged_frames, 9);
+        assert!(frame.buffered_frames()[0].rejected);
+        assert_eq!(
+            frame
+                .buffered_frames()
+                .iter()
+                .filter(|f| f.rejected)
+                .count(),
+            1
+        );
+        assert_relative_eq!(frame.spectrum()[(3, 50)], 0.301, max_relative = 1e-3);
+
+        config.postprocessing_config.outlier_rejection = false;
+        let frame = spectrum_processor.process(SpectrumRgb::from_element(100, 0.3), &config);
+        assert_eq!(frame.averaged_frames, 10);
+        assert!(frame.buffered_frames().iter().all(|f| !f.rejected));
+    }
+
+    #[rstest]
+    fn transform(mut spectrum_processor: SpectrumProcessor, mut config: SpectrometerConfig) {
+        config.postprocessing_config.transform = "2 * y + x".to_string();
+        let frame = spectrum_processor.process(SpectrumRgb::from_element(100, 0.3), &config);
+        let wavelength = config.spectrum_calibration.get_wavelength_from_index(10);
+        assert_relative_eq!(
+            frame.spectrum()[(3, 10)],
+            0.6 + wavelength,
+            max_relative = 1e-5
+        );
+        assert!(frame.to_processed_spectrum(&config).flags.transformed);
+
+        // The combined channel stays the mean of the transformed colors
+        config.postprocessing_config.transform = "y * y".to_string();
+        let mut rgb = SpectrumRgb::from_element(100, 0.3);
+        rgb.row_mut(1).fill(0.6);
+        rgb.row_mut(2).fill(0.9);
+        let mut processor = SpectrumProcessor::new();
+        let frame = processor.process(rgb, &config);
+        assert_relative_eq!(frame.spectrum()[(1, 10)], 0.36, max_relative = 1e-5);
+        assert_relative_eq!(frame.spectrum()[(3, 10)], 0.42, max_relative = 1e-5);
+
+        // Invalid expressions are left out
+        config.postprocessing_config.transform = "2 *".to_string();
+        let frame = spectrum_processor.process(SpectrumRgb::from_element(100, 0.3), &config);
+        assert_relative_eq!(frame.spectrum()[(3, 10)], 0.3, max_relative = 1e-5);
+    }
+
+    #[rstest]
+    fn export_traces(mut config: SpectrometerConfig) {
+        let frame = SpectrumFrame {
+            spectrum: Spectrum::from_element(3, 0.5),
+            ..Default::default()
+        };
+        let wavelengths: Vec<f32> = (0..3)
+            .map(|i| config.spectrum_calibration.get_wavelength_from_index(i))
+            .collect();
+        config.import_export_config.export_traces = ExportTraces {
+            r: false,
+            g: false,
+            b: false,
+            sum: true,
+            detection_limit: false,
+            snapshots: true,
+            reference: true,
+        };
+        config.snapshots.push(Snapshot {
+            name: "Lamp".to_string(),
+            color: egui::Color32::RED,
+            visible: true,
+            unit: IntensityUnit::Counts,
+            spectrum: vec![
+                SpectrumPoint {
+                    wavelength: wavelengths[1],
+                    value: 2.,
+                },
+                SpectrumPoint {
+                    wavelength: wavelengths[0],
+                    value: 1.,
+                },
+            ],
+        });
+        config.reference_config.reference = Some(vec![
+            SpectrumPoint {
+                wavelength: 0.,
+                value: 0.25,
+            },
+            SpectrumPoint {
+                wavelength: 2000.,
+                value: 0.25,
+            },
+        ]);
+        config.reference_config.scale = 2.;
+
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("traces.csv");
+        let path = path.to_string_lossy().to_string();
+        frame
+            .write_to_file(&path, ExportFormat::Csv, &config)
+            .unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let tsv = frame.to_tab_separated(&config.spectrum_calibration, &AxisConfig::default());
+        assert_eq!(
+            tsv.lines().nth(1).unwrap(),
+            format!("{}\t0.5\t0.5\t0.5\t0.5", wavelengths[0])
+        );
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "wavelength,sum,Lamp,reference,unit");
+        assert_eq!(lines[1], format!("{},0.5,1,0.5,Counts", wavelengths[0]));
+        assert_eq!(lines[3], format!("{},0.5,,0.5,Counts", wavelengths[2]));
+
+        // Resampled onto a grid within the wavelengths of the spectrum
+        config.import_export_config.resampling = ResamplingConfig {
+            resampling: Resampling::Spline,
+            start: 0.,
+            end: 2000.,
+            step: (wavelengths[2] - wavelengths[0]) / 4.,
+        };
+        let points = frame.export_points(&config, &AxisConfig::default());
+        assert_eq!(points.len(), 4);
+        assert!(points.iter().all(|p| p.sum == 0.5));
+    }
+
     #[fixture]
-    fn spectrum_container() -> SpectrumContainer {
-        let (_tx, rx) = flume::unbounded();
-        SpectrumContainer::new(rx)
+    fn spectrum_processor() -> SpectrumProcessor {
+        SpectrumProcessor::new()
     }
 
     #[fixture]
@@ -345,33 +1757,316 @@ mod tests {
     }
 
     #[rstest]
-    fn buffer_size(mut spectrum_container: SpectrumContainer, config: SpectrometerConfig) {
-        spectrum_container.update_spectrum(SpectrumRgb::from_element(1000, 0.5), &config);
-        spectrum_container.update_spectrum(SpectrumRgb::from_element(1000, 0.75), &config);
+    fn buffer_size(mut spectrum_processor: SpectrumProcessor, config: SpectrometerConfig) {
+        spectrum_processor.process(SpectrumRgb::from_element(1000, 0.5), &config);
+        spectrum_processor.process(SpectrumRgb::from_element(1000, 0.75), &config);
 
-        assert_eq!(spectrum_container.spectrum_buffer.len(), 2);
+        assert_eq!(spectrum_processor.spectrum_buffer.len(), 2);
 
         for _ in 0..100 {
-            spectrum_container.update_spectrum(SpectrumRgb::from_element(1000, 0.5), &config);
+            spectrum_processor.process(SpectrumRgb::from_element(1000, 0.5), &config);
             assert!(
-                spectrum_container.spectrum_buffer.len()
+                spectrum_processor.spectrum_buffer.len()
                     <= config.postprocessing_config.spectrum_buffer_size
             );
         }
 
         assert_eq!(
-            spectrum_container.spectrum_buffer.len(),
+            spectrum_processor.spectrum_buffer.len(),
             config.postprocessing_config.spectrum_buffer_size
         );
+
+        let mut low_latency = config.clone();
+        low_latency.postprocessing_config.low_latency = true;
+        spectrum_processor.process(SpectrumRgb::from_element(1000, 0.25), &low_latency);
+        assert_eq!(spectrum_processor.spectrum_buffer.len(), 1);
+        assert_relative_eq!(
+            spectrum_processor.frame().spectrum()[(3, 0)],
+            0.25,
+            epsilon = 1e-6
+        );
+    }
+
+    #[rstest]
+    fn running_sum(mut spectrum_processor: SpectrumProcessor, mut config: SpectrometerConfig) {
+        config.postprocessing_config.spectrum_buffer_size = 5;
+        for i in 0..(FULL_SUM_INTERVAL + 20) {
+            if i == 500 {
+                config.postprocessing_config.spectrum_buffer_size = 2;
+            }
+            spectrum_processor
+                .process(SpectrumRgb::from_element(100, (i % 7) as f32 / 7.), &config);
+            let sum = spectrum_processor
+                .spectrum_buffer
+                .iter()
+                .fold(SpectrumRgb::zeros(100), |a, b| a + b);
+            assert_relative_eq!(spectrum_processor.buffer_sum, sum, epsilon = 1e-4);
+            let moments = spectrum_processor
+                .spectrum_buffer
+                .iter()
+                .fold(ChannelMoments::zeros(100), |a, b| a + channel_moments(b));
+            assert_relative_eq!(spectrum_processor.buffer_moments, moments, epsilon = 1e-6);
+        }
+        assert_eq!(spectrum_processor.spectrum_buffer.len(), 2);
+    }
+
+    #[rstest]
+    fn reference_beam(mut spectrum_processor: SpectrumProcessor, mut config: SpectrometerConfig) {
+        // The lamp got weaker between the two frames, which the reference strip sees as well
+        for (sample, reference) in [(0.2, 0.4), (0.1, 0.2)] {
+            spectrum_processor.process_with_reference(
+                SpectrumRgb::from_element(10, sample),
+                Some(SpectrumRgb::from_element(10, reference)),
+                &config,
+            );
+        }
+        let frame = spectrum_processor.frame();
+        assert_eq!(frame.reference_beam(), Some(ReferenceBeam::Ratio));
+        assert_relative_eq!(frame.spectrum()[(3, 4)], 0.5, epsilon = 1e-6);
+
+        config.postprocessing_config.reference_beam = ReferenceBeam::Absorbance;
+        let frame = spectrum_processor.process_with_reference(
+            SpectrumRgb::from_element(10, 0.2),
+            Some(SpectrumRgb::from_element(10, 0.4)),
+            &config,
+        );
+        assert_relative_eq!(
+            frame.spectrum()[(0, 0)],
+            0.5f32.log10().abs(),
+            epsilon = 1e-6
+        );
+        assert_eq!(ReferenceBeam::Absorbance.apply(0., 0.4), 4.);
+        assert_eq!(ReferenceBeam::Ratio.apply(0.2, 0.), 0.);
+
+        let frame = spectrum_processor.process(SpectrumRgb::from_element(10, 0.2), &config);
+        assert_eq!(frame.reference_beam(), None);
+        assert!(spectrum_processor.reference_processor.is_none());
+    }
+
+    #[rstest]
+    fn drop_frame(mut spectrum_processor: SpectrumProcessor, config: SpectrometerConfig) {
+        spectrum_processor.process(SpectrumRgb::from_element(10, 0.1), &config);
+        spectrum_processor.process(SpectrumRgb::from_element(10, 1. / 3.), &config);
+        let frames = spectrum_processor.frame().buffered_frames().to_vec();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].saturated());
+        assert!(!frames[1].saturated());
+
+        spectrum_processor.drop_frame(frames[0].id);
+        spectrum_processor.process(SpectrumRgb::from_element(10, 0.1), &config);
+        assert_eq!(spectrum_processor.frame().buffered_frames().len(), 2);
+        assert_relative_eq!(
+            spectrum_processor.frame().spectrum()[(0, 0)],
+            0.1,
+            epsilon = 1e-6
+        );
+    }
+
+    #[rstest]
+    #[case(10, 20, 10)]
+    #[case(10, 10, 10)]
+    #[case(10, 5, 5)]
+    #[case(10, 3, 3)]
+    #[case(1000, 200, 200)]
+    #[case(1001, 200, 167)]
+    #[case(10, 0, 10)]
+    fn decimate(#[case] len: usize, #[case] max_points: usize, #[case] expected: usize) {
+        let points: Vec<_> = (0..len).collect();
+        let decimated = super::decimate(points, max_points);
+
+        assert_eq!(decimated.len(), expected);
+        assert_eq!(decimated.first(), Some(&0));
+    }
+
+    #[rstest]
+    fn to_processed_spectrum(
+        mut spectrum_processor: SpectrumProcessor,
+        config: SpectrometerConfig,
+    ) {
+        spectrum_processor.process(SpectrumRgb::from_element(1000, 0.5), &config);
+        spectrum_processor.set_zero_reference();
+
+        let processed = spectrum_processor
+            .process(SpectrumRgb::from_element(1000, 0.5), &config)
+            .to_processed_spectrum(&config);
+
+        assert_eq!(processed.wavelengths.len(), 1000);
+        assert_eq!(
+            processed.wavelengths[config.spectrum_calibration.low.index],
+            config.spectrum_calibration.low.wavelength as f32
+        );
+        assert_eq!(processed.channels.ncols(), 1000);
+        assert_eq!(processed.averaged_frames, 2);
+        assert!(processed.flags.zero_reference_applied);
+        assert!(!processed.flags.intensity_calibrated);
+    }
+
+    #[rstest]
+    fn detection_limit(mut spectrum_processor: SpectrumProcessor, mut config: SpectrometerConfig) {
+        let frame = spectrum_processor.process(SpectrumRgb::from_element(100, 0.5), &config);
+        assert!(frame.get_detection_limit(&config).is_empty());
+
+        let frame = spectrum_processor.process(SpectrumRgb::from_element(100, 0.5), &config);
+        let limit = frame.get_detection_limit(&config);
+        assert_eq!(limit.len(), 100);
+        assert!(limit.iter().all(|sp| sp.value == 0.));
+
+        let frames = vec![vec![1., 1., 0., 2.], vec![3., 1., 0., 4.]];
+        let limit = super::detection_limit(&frames, 2).unwrap();
+        // Mean variance of 1 in each band of two columns, standard error of two frames
+        assert_relative_eq!(limit.as_slice(), [3. * 0.5f32.sqrt(); 4].as_slice());
+
+        // The running moments give the limit of the combined channel of the frames
+        let calibration = &mut config.spectrum_calibration;
+        calibration.linearize = Linearize::Off;
+        calibration.gain_g = 2.;
+        let frames: Vec<SpectrumRgb> = (0..4)
+            .map(|k| SpectrumRgb::from_fn(10, |c, i| 1000. + ((k * 7 + c * 3 + i) % 5) as f32))
+            .collect();
+        spectrum_processor.clear_buffer();
+        for frame in &frames {
+            spectrum_processor.process(frame.clone(), &config);
+        }
+        let calibration = &config.spectrum_calibration;
+        let combined: Vec<Vec<f32>> = frames
+            .iter()
+            .map(|frame| {
+                (0..10)
+                    .map(|i| {
+                        (frame[(0, i)] * calibration.gain_r
+                            + frame[(1, i)] * calibration.gain_g
+                            + frame[(2, i)] * calibration.gain_b)
+                            * calibration.get_scaling_factor_from_index(i)
+                            / 3.
+                    })
+                    .collect()
+            })
+            .collect();
+        let band_width = config.postprocessing_config.noise_band_width;
+        assert_relative_eq!(
+            spectrum_processor
+                .frame()
+                .detection_limit
+                .as_ref()
+                .unwrap()
+                .as_slice(),
+            super::detection_limit(&combined, band_width)
+                .unwrap()
+                .as_slice(),
+            max_relative = 1e-3
+        );
+    }
+
+    #[rstest]
+    #[case(PeakWindow::Samples(2), vec![2, 6, 9])]
+    #[case(PeakWindow::Nanometer(5.), vec![6, 9])]
+    #[case(PeakWindow::Nanometer(13.), vec![9])]
+    fn find_peaks_dips(#[case] find_window: PeakWindow, #[case] expected: Vec<usize>) {
+        // Points get denser towards short wavelengths, where a ripple is only 1 nm wide
+        let wavelengths = [
+            400., 400.5, 401., 401.5, 402., 404., 406., 409., 412., 416., 420., 425.,
+        ];
+        let values = [0., 1., 2., 1., 0., 0., 3., 0., 1., 2., 0., 0.];
+        let spectrum: Vec<_> = wavelengths
+            .iter()
+            .zip(values)
+            .map(|(&wavelength, value)| SpectrumPoint { wavelength, value })
+            .collect();
+
+        assert_eq!(
+            super::find_peaks_dips(&spectrum, true, find_window, 1.),
+            expected
+        );
     }
 
     #[rstest]
     fn get_spectrum_max_value(
-        mut spectrum_container: SpectrumContainer,
+        mut spectrum_processor: SpectrumProcessor,
         config: SpectrometerConfig,
     ) {
-        spectrum_container.update_spectrum(SpectrumRgb::from_element(1000, 0.5), &config);
+        spectrum_processor.process(SpectrumRgb::from_element(1000, 0.5), &config);
+
+        assert_eq!(
+            spectrum_processor.frame().get_spectrum_max_value(),
+            Some(0.5)
+        );
+    }
+
+    #[test]
+    fn worker_events() {
+        let (_spectrum_tx, spectrum_rx) = flume::unbounded();
+        let (_event_tx, event_rx) = flume::unbounded();
+        let (frame_tx, frame_rx) = flume::unbounded();
+        let engine = SpectroEngine::default();
+        let published = engine.subscribe();
+        let mut worker = SpectrumWorker::new(spectrum_rx, event_rx, frame_tx, engine);
+
+        worker.handle_event(ProcessorEvent::Config {
+            spectrum_calibration: Box::default(),
+            postprocessing_config: Box::new(PostprocessingConfig {
+                spectrum_buffer_size: 1,
+                ..Default::default()
+            }),
+        });
+        assert_eq!(worker.config.postprocessing_config.spectrum_buffer_size, 1);
+
+        worker
+            .processor
+            .process(SpectrumRgb::from_element(10, 0.5), &worker.config);
+        worker.handle_event(ProcessorEvent::SetZeroReference);
+        let frame = worker
+            .processor
+            .process(SpectrumRgb::from_element(10, 0.5), &worker.config);
+        assert!(frame.has_zero_reference());
+        assert_eq!(frame.get_spectrum_max_value(), Some(0.));
+
+        worker.handle_event(ProcessorEvent::ClearZeroReference);
+        worker.handle_event(ProcessorEvent::ClearBuffer);
+        assert!(worker.processor.spectrum_buffer.is_empty());
+        let frame = worker
+            .processor
+            .process(SpectrumRgb::from_element(10, 0.5), &worker.config);
+        assert!(!frame.has_zero_reference());
+
+        // Restored zero references of another window width are dropped
+        worker.handle_event(ProcessorEvent::LoadZeroReference(Box::new(
+            Spectrum::from_element(10, 0.25),
+        )));
+        let frame = worker
+            .processor
+            .process(SpectrumRgb::from_element(10, 0.5), &worker.config);
+        assert_relative_eq!(frame.get_spectrum_max_value().unwrap(), 0.25);
+        worker.handle_event(ProcessorEvent::LoadZeroReference(Box::new(
+            Spectrum::from_element(5, 0.25),
+        )));
+        let frame = worker
+            .processor
+            .process(SpectrumRgb::from_element(10, 0.5), &worker.config);
+        assert!(!frame.has_zero_reference());
+
+        worker.handle_event(ProcessorEvent::TestPattern(Some(Box::new(
+            SpectrumRgb::from_element(10, 0.25),
+        ))));
+        assert_eq!(worker.test_pattern.as_ref().map(|p| p.ncols()), Some(10));
+        assert!(worker.processor.spectrum_buffer.is_empty());
+        worker.handle_event(ProcessorEvent::TestPattern(None));
+        assert!(worker.test_pattern.is_none());
+
+        worker.handle_event(ProcessorEvent::Hdr(Some(vec![1., 4.])));
+        worker.handle_event(ProcessorEvent::HdrBracket(Some(1)));
+        let hdr = worker.hdr.as_mut().unwrap();
+        assert_eq!(hdr.add(SpectrumRgb::from_element(10, 0.2)), None);
+        worker.handle_event(ProcessorEvent::Hdr(None));
+        assert!(worker.hdr.is_none());
 
-        assert_eq!(spectrum_container.get_spectrum_max_value(), Some(0.5));
+        // Published without the GUI
+        worker.handle_spectra(RawSpectra {
+            sample: SpectrumRgb::from_element(10, 0.5),
+            reference: None,
+        });
+        assert_eq!(frame_rx.drain().count(), 1);
+        let spectrum = published.try_recv().unwrap();
+        assert_eq!(spectrum.wavelengths.len(), 10);
+        assert_eq!(spectrum.averaged_frames, 1);
     }
 }
@@ -0,0 +1,428 @@
+use crate::config::{LogFormat, LoggingConfig, LoggingMode, SpectrumPoint};
+use crate::database::{Measurement, MeasurementDatabase, MeasurementMetadata};
+use crate::filename_template::{export_path, TemplateValues};
+use crate::spectrum_stream::{
+    repair_file, SpectrumRecord, SpectrumStreamHeader, SpectrumStreamWriter,
+};
+use chrono::Local;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::iter::once;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogColumn {
+    pub name: String,
+    /// Wavelength of the point or center of the band.
+    pub wavelength: f32,
+    pub value: f32,
+}
+
+enum LogWriter {
+    /// Opened with the first row, as the header needs the columns.
+    Csv {
+        writer: Option<Box<csv::Writer<File>>>,
+        header: Vec<String>,
+    },
+    /// Opened with the first row, as the header needs the wavelength axis.
+    Binary {
+        writer: Option<SpectrumStreamWriter<Box<dyn Write>>>,
+        compressed: bool,
+    },
+    /// Stores the full spectrum independent of the logging mode.
+    Database(MeasurementDatabase),
+}
+
+/// Appends the spectrum to a log file in a fixed interval, one row per point in time.
+pub struct SpectrumLogger {
+    writer: LogWriter,
+    path: String,
+    interval: Duration,
+    last_log: Option<Instant>,
+    rows: usize,
+}
+
+impl SpectrumLogger {
+    pub fn new(config: &LoggingConfig) -> Result<Self, String> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)
+            .map_err(|e| e.to_string())?;
+
+        let writer = match config.format {
+            LogFormat::Csv => LogWriter::Csv {
+                writer: None,
+                header: vec![],
+            },
+            LogFormat::Binary => LogWriter::Binary {
+                writer: None,
+                compressed: false,
+            },
+            LogFormat::CompressedBinary => LogWriter::Binary {
+                writer: None,
+                compressed: true,
+            },
+            LogFormat::Database => LogWriter::Database(MeasurementDatabase::open(&config.path)?),
+        };
+
+        Ok(Self {
+            writer,
+            path: config.path.clone(),
+            interval: Duration::from_secs_f32(config.interval.max(0.)),
+            last_log: None,
+            rows: 0,
+        })
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.last_log
+            .is_none_or(|last_log| last_log.elapsed() >= self.interval)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// File the rows are written to, which differs from the configured one after a rotation.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn log(
+        &mut self,
+        spectrum: &[SpectrumPoint],
+        config: &LoggingConfig,
+        derived_values: &[(String, f32)],
+        metadata: &MeasurementMetadata,
+    ) -> Result<(), String> {
+        if spectrum.is_empty() || !self.is_due() {
+            return Ok(());
+        }
+        self.last_log = Some(Instant::now());
+
+        match &mut self.writer {
+            LogWriter::Csv { writer, header } => {
+                let columns = Self::columns(spectrum, config, derived_values);
+                let columns_header: Vec<String> = once("timestamp".to_string())
+                    .chain(columns.iter().map(|c| c.name.clone()))
+                    .collect();
+                if writer.is_none() || *header != columns_header {
+                    *writer = Some(Box::new(open_csv(
+                        &mut self.path,
+                        &config.path,
+                        &columns_header,
+                    )?));
+                    *header = columns_header;
+                }
+
+                let writer = writer.as_mut().unwrap();
+                writer
+                    .write_record(
+                        once(Local::now().to_rfc3339())
+                            .chain(columns.iter().map(|c| c.value.to_string())),
+                    )
+                    .map_err(|e| e.to_string())?;
+                writer.flush().map_err(|e| e.to_string())?;
+            }
+            LogWriter::Binary { writer, compressed } => {
+                let columns = Self::columns(spectrum, config, derived_values);
+                let header = SpectrumStreamHeader {
+                    channels: 1,
+                    wavelengths: columns.iter().map(|c| c.wavelength).collect(),
+                };
+                if writer.as_ref().is_some_and(|w| w.header() != &header) {
+                    return Err("Wavelength axis changed while logging".to_string());
+                }
+                if writer.is_none() {
+                    *writer = Some(
+                        SpectrumStreamWriter::append_to_file(&self.path, header, *compressed)
+                            .map_err(|e| e.to_string())?,
+                    );
+                }
+                let writer = writer.as_mut().unwrap();
+                writer
+                    .write_record(&SpectrumRecord::now(
+                        columns.iter().map(|c| c.value).collect(),
+                    ))
+                    .map_err(|e| e.to_string())?;
+                writer.flush().map_err(|e| e.to_string())?;
+            }
+            LogWriter::Database(database) => {
+                let inserted = database.insert(&Measurement {
+                    timestamp: Local::now(),
+                    metadata: metadata.clone(),
+                    spectrum: spectrum.to_vec(),
+                })?;
+                // An unchanged spectrum, e.g. of a stalled camera, is stored once
+                if inserted.is_none() {
+                    return Ok(());
+                }
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Columns of one row without the timestamp.
+    ///
+    /// Derived values, see [crate::config::SpectrometerConfig::derived_values], use their
+    /// index as wavelength.
+    pub fn columns(
+        spectrum: &[SpectrumPoint],
+        config: &LoggingConfig,
+        derived_values: &[(String, f32)],
+    ) -> Vec<LogColumn> {
+        match config.mode {
+            LoggingMode::FullSpectrum => spectrum
+                .iter()
+                .map(|sp| LogColumn {
+                    name: sp.wavelength.to_string(),
+                    wavelength: sp.wavelength,
+                    value: sp.value,
+                })
+                .collect(),
+            LoggingMode::Bands => config
+                .bands
+                .iter()
+                .map(|band| LogColumn {
+                    name: band.to_string(),
+                    wavelength: (band.start + band.end) / 2.,
+                    value: band.mean_value(spectrum).unwrap_or(0.),
+                })
+                .collect(),
+            LoggingMode::DerivedValues => derived_values
+                .iter()
+                .enumerate()
+                .map(|(i, (name, value))| LogColumn {
+                    name: name.clone(),
+                    wavelength: i as f32,
+                    value: *value,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Open the CSV log at `path` to append rows with `header`. A log with other columns, e.g.
+/// from another calibration or logging mode, is rotated to a new file named after the
+/// configured `base` path, which is stored in `path`.
+fn open_csv(path: &mut String, base: &str, header: &[String]) -> Result<csv::Writer<File>, String> {
+    let existing = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_path(&path)
+        .ok()
+        .and_then(|mut reader| reader.records().next())
+        .and_then(Result::ok);
+    if existing.is_some_and(|record| !record.iter().eq(header.iter().map(String::as_str))) {
+        let values = TemplateValues {
+            sample: "",
+            camera: "",
+            time: Local::now(),
+        };
+        let extension = Path::new(base)
+            .extension()
+            .map_or("csv".to_string(), |e| e.to_string_lossy().to_string());
+        *path = export_path(base, "", &values, &extension)
+            .to_string_lossy()
+            .to_string();
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Could not open {}: {}", path, e))?;
+    let empty = file.metadata().map(|m| m.len() == 0).unwrap_or(true);
+    let mut writer = csv::Writer::from_writer(file);
+    // Do not repeat the header when appending to an existing log
+    if empty {
+        writer.write_record(header).map_err(|e| e.to_string())?;
+    }
+    Ok(writer)
+}
+
+/// Cut off the row a crash left incomplete at the end of the log, returning whether the file
+/// had to be repaired.
+pub fn repair_log(config: &LoggingConfig) -> Result<bool, String> {
+    if !Path::new(&config.path).exists() {
+        return Ok(false);
+    }
+    match config.format {
+        LogFormat::Csv => repair_csv(&config.path),
+        LogFormat::Binary | LogFormat::CompressedBinary => repair_file(&config.path),
+        // Transactions keep the database consistent
+        LogFormat::Database => Ok(false),
+    }
+    .map_err(|e| format!("Could not repair {}: {}", config.path, e))
+}
+
+fn repair_csv(path: &str) -> std::io::Result<bool> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let len = file.metadata()?.len();
+    // Search the last line break backwards, a row of the full spectrum can be long
+    let mut buffer = vec![0; 64 * 1024];
+    let mut end = len;
+    while end > 0 {
+        let start = end.saturating_sub(buffer.len() as u64);
+        let chunk = &mut buffer[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+        if let Some(i) = chunk.iter().rposition(|&b| b == b'\n') {
+            let complete = start + i as u64 + 1;
+            if complete < len {
+                file.set_len(complete)?;
+            }
+            return Ok(complete < len);
+        }
+        end = start;
+    }
+    file.set_len(0)?;
+    Ok(len > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DerivedValue, WavelengthBand};
+
+    #[test]
+    fn columns() {
+        let spectrum: Vec<_> = (500..510)
+            .map(|w| SpectrumPoint {
+                wavelength: w as f32,
+                value: 1.,
+            })
+            .collect();
+        let mut config = LoggingConfig::default();
+
+        let columns = SpectrumLogger::columns(&spectrum, &config, &[]);
+        assert_eq!(columns.len(), 10);
+        assert_eq!(
+            columns[0],
+            LogColumn {
+                name: "500".to_string(),
+                wavelength: 500.,
+                value: 1.
+            }
+        );
+
+        config.mode = LoggingMode::Bands;
+        config.bands = vec![
+            WavelengthBand {
+                start: 500.,
+                end: 505.,
+            },
+            WavelengthBand {
+                start: 600.,
+                end: 605.,
+            },
+        ];
+        let columns = SpectrumLogger::columns(&spectrum, &config, &[]);
+        assert_eq!(
+            columns
+                .iter()
+                .map(|c| (c.name.as_str(), c.wavelength, c.value))
+                .collect::<Vec<_>>(),
+            vec![("500-505 nm", 502.5, 1.), ("600-605 nm", 602.5, 0.)]
+        );
+
+        config.mode = LoggingMode::DerivedValues;
+        let ratio = DerivedValue::new("ratio".to_string(), "I(509) / I(500)".to_string());
+        let derived_values = [(ratio.name.clone(), ratio.eval(&spectrum).unwrap())];
+        let columns = SpectrumLogger::columns(&spectrum, &config, &derived_values);
+        assert_eq!(
+            columns,
+            vec![LogColumn {
+                name: "ratio".to_string(),
+                wavelength: 0.,
+                value: 1.
+            }]
+        );
+    }
+
+    #[test]
+    fn rotate_csv_log() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("log.csv");
+        let config = LoggingConfig {
+            path: path.to_str().unwrap().to_string(),
+            interval: 0.,
+            ..Default::default()
+        };
+        let spectrum = |start: u32| -> Vec<SpectrumPoint> {
+            (start..start + 2)
+                .map(|w| SpectrumPoint {
+                    wavelength: w as f32,
+                    value: 1.,
+                })
+                .collect()
+        };
+        let metadata = MeasurementMetadata {
+            exposure: None,
+            calibration_id: String::new(),
+            unit: crate::config::IntensityUnit::Relative,
+            averaged_frames: 1,
+            notes: String::new(),
+        };
+        let log = |spectra: &[Vec<SpectrumPoint>]| {
+            let mut logger = SpectrumLogger::new(&config).unwrap();
+            for spectrum in spectra {
+                logger.log(spectrum, &config, &[], &metadata).unwrap();
+            }
+            logger.path().to_string()
+        };
+        let lines = |path: &str| {
+            std::fs::read_to_string(path)
+                .unwrap()
+                .lines()
+                .map(|l| l.split(',').skip(1).collect::<Vec<_>>().join(","))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(log(&[spectrum(500)]), config.path);
+        // Appending with the same columns keeps the header once
+        assert_eq!(log(&[spectrum(500)]), config.path);
+        assert_eq!(lines(&config.path), vec!["500,501", "1,1", "1,1"]);
+
+        // Another wavelength axis, and a change while logging
+        let rotated = log(&[spectrum(600), spectrum(700)]);
+        assert_eq!(lines(&config.path).len(), 3);
+        let second = directory.path().join("log_2.csv");
+        assert_eq!(lines(second.to_str().unwrap()), vec!["600,601", "1,1"]);
+        assert_eq!(
+            rotated,
+            directory.path().join("log_3.csv").to_str().unwrap()
+        );
+        assert_eq!(lines(&rotated), vec!["700,701", "1,1"]);
+    }
+
+    #[test]
+    fn repair_csv_log() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("repair.csv");
+        let config = LoggingConfig {
+            path: path.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        std::fs::write(
+            &path,
+            "timestamp,500\n2022-05-01T12:00:00+00:00,1\n2022-05-01T1",
+        )
+        .unwrap();
+        assert_eq!(repair_log(&config), Ok(true));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "timestamp,500\n2022-05-01T12:00:00+00:00,1\n"
+        );
+        assert_eq!(repair_log(&config), Ok(false));
+
+        std::fs::write(&path, "timest").unwrap();
+        assert_eq!(repair_log(&config), Ok(true));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(repair_log(&config), Ok(false));
+    }
+}
@@ -0,0 +1,938 @@
+//! Compact binary format for timestamped spectra.
+//!
+//! All numbers are little endian. The header is followed by any number of records:
+//!
+//! | Field       | Type                     |
+//! |-------------|--------------------------|
+//! | magic       | `b"SPCS"`                |
+//! | version     | `u16`                    |
+//! | channels    | `u16`                    |
+//! | points      | `u32`                    |
+//! | wavelengths | `points` x `f32`         |
+//!
+//! Each record consists of the timestamp in microseconds since the UNIX epoch as `i64`
+//! followed by `channels` x `points` `f32` values, one channel after the other.
+//!
+//! Streams may be compressed with zstd as a whole. Files are written in zstd frames of at most
+//! [FRAME_BYTES] of records each, so that only the last frame is lost if writing is
+//! interrupted.
+
+use crate::hdf5::{Hdf5Data, Hdf5Dataset, Hdf5File};
+use chrono::{DateTime, Local, Utc};
+use rayon::prelude::*;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::SystemTime;
+
+pub const MAGIC: &[u8; 4] = b"SPCS";
+pub const VERSION: u16 = 1;
+/// Start of every zstd frame.
+pub const ZSTD_MAGIC: &[u8; 4] = &[0x28, 0xb5, 0x2f, 0xfd];
+const ZSTD_LEVEL: i32 = 3;
+/// Uncompressed bytes after which the current zstd frame of a file is finished.
+pub const FRAME_BYTES: usize = 256 * 1024;
+/// Records read before they are converted in parallel and written in one go.
+const CHUNK_RECORDS: usize = 4096;
+/// Records converted by one thread at a time.
+const BATCH_RECORDS: usize = 256;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpectrumStreamHeader {
+    pub channels: u16,
+    pub wavelengths: Vec<f32>,
+}
+
+impl SpectrumStreamHeader {
+    pub fn record_len(&self) -> usize {
+        self.channels as usize * self.wavelengths.len()
+    }
+
+    /// Size of the written header in bytes.
+    pub fn encoded_len(&self) -> usize {
+        MAGIC.len() + 2 + 2 + 4 + 4 * self.wavelengths.len()
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.channels.to_le_bytes())?;
+        writer.write_all(&(self.wavelengths.len() as u32).to_le_bytes())?;
+        write_f32s(writer, &self.wavelengths)
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> std::io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("Not a spectrum stream"));
+        }
+        let version = read_u16(reader)?;
+        if version != VERSION {
+            return Err(invalid_data(&format!(
+                "Unsupported spectrum stream version {}",
+                version
+            )));
+        }
+        let channels = read_u16(reader)?;
+        let mut points = [0; 4];
+        reader.read_exact(&mut points)?;
+        let wavelengths = read_f32s(reader, u32::from_le_bytes(points) as usize)?;
+        Ok(Self {
+            channels,
+            wavelengths,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpectrumRecord {
+    pub timestamp_us: i64,
+    pub values: Vec<f32>,
+}
+
+impl SpectrumRecord {
+    pub fn now(values: Vec<f32>) -> Self {
+        let timestamp_us = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_micros() as i64)
+            .unwrap_or_default();
+        Self {
+            timestamp_us,
+            values,
+        }
+    }
+
+    pub fn local_time(&self) -> Option<DateTime<Local>> {
+        DateTime::<Utc>::from_timestamp_micros(self.timestamp_us).map(|t| t.with_timezone(&Local))
+    }
+}
+
+pub struct SpectrumStreamWriter<W: Write> {
+    writer: W,
+    header: SpectrumStreamHeader,
+}
+
+impl<W: Write> SpectrumStreamWriter<W> {
+    pub fn new(mut writer: W, header: SpectrumStreamHeader) -> std::io::Result<Self> {
+        header.write(&mut writer)?;
+        Ok(Self { writer, header })
+    }
+
+    /// Continue a stream whose header has already been written.
+    pub fn without_header(writer: W, header: SpectrumStreamHeader) -> Self {
+        Self { writer, header }
+    }
+
+    pub fn header(&self) -> &SpectrumStreamHeader {
+        &self.header
+    }
+
+    pub fn write_record(&mut self, record: &SpectrumRecord) -> std::io::Result<()> {
+        if record.values.len() != self.header.record_len() {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                "Record length does not match the header",
+            ));
+        }
+        // In one piece, so that compressed frames end between records
+        let mut bytes = Vec::with_capacity(8 + 4 * record.values.len());
+        bytes.extend_from_slice(&record.timestamp_us.to_le_bytes());
+        bytes.extend(record.values.iter().flat_map(|v| v.to_le_bytes()));
+        self.writer.write_all(&bytes)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl SpectrumStreamWriter<Box<dyn Write>> {
+    /// Append to the stream file at `path` or create it, optionally compressed with zstd.
+    ///
+    /// Fails if the file already contains a stream with a different header or compression.
+    pub fn append_to_file<P: AsRef<Path>>(
+        path: P,
+        header: SpectrumStreamHeader,
+        compressed: bool,
+    ) -> std::io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        if file.metadata()?.len() == 0 {
+            return Self::new(file_writer(file, compressed)?, header);
+        }
+
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+        if (&magic == ZSTD_MAGIC) != compressed {
+            return Err(invalid_data("Existing stream has a different compression"));
+        }
+        file.seek(SeekFrom::Start(0))?;
+        if SpectrumStreamHeader::read(&mut decompressing_reader(&mut file)?)? == header {
+            Ok(Self::without_header(file_writer(file, compressed)?, header))
+        } else {
+            Err(invalid_data(
+                "Existing stream has a different wavelength axis",
+            ))
+        }
+    }
+}
+
+fn file_writer(file: File, compressed: bool) -> std::io::Result<Box<dyn Write>> {
+    let writer = BufWriter::new(file);
+    Ok(if compressed {
+        Box::new(FrameEncoder::new(writer)?)
+    } else {
+        Box::new(writer)
+    })
+}
+
+/// zstd encoder which finishes a frame whenever [FRAME_BYTES] have been written, and the last
+/// one when it is dropped.
+///
+/// Frames only end after a whole `write`, so they contain whole records.
+pub struct FrameEncoder<W: Write> {
+    encoder: Option<zstd::stream::write::Encoder<'static, W>>,
+    written: usize,
+}
+
+impl<W: Write> FrameEncoder<W> {
+    pub fn new(writer: W) -> std::io::Result<Self> {
+        Ok(Self {
+            encoder: Some(zstd::stream::write::Encoder::new(writer, ZSTD_LEVEL)?),
+            written: 0,
+        })
+    }
+
+    /// Finish the last frame and return the inner writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.encoder.take().ok_or_else(frame_failed)?.finish()
+    }
+}
+
+/// The encoder is gone after finishing a frame failed.
+fn frame_failed() -> std::io::Error {
+    std::io::Error::other("Failed to finish a zstd frame")
+}
+
+impl<W: Write> Write for FrameEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let encoder = self.encoder.as_mut().ok_or_else(frame_failed)?;
+        encoder.write_all(buf)?;
+        self.written += buf.len();
+        if self.written >= FRAME_BYTES {
+            let writer = self.encoder.take().ok_or_else(frame_failed)?.finish()?;
+            self.encoder = Some(zstd::stream::write::Encoder::new(writer, ZSTD_LEVEL)?);
+            self.written = 0;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.encoder.as_mut().ok_or_else(frame_failed)?.flush()
+    }
+}
+
+impl<W: Write> Drop for FrameEncoder<W> {
+    fn drop(&mut self) {
+        if let Some(encoder) = self.encoder.take() {
+            let _ = encoder.finish();
+        }
+    }
+}
+
+/// Read plain or zstd compressed data alike.
+pub fn decompressing_reader<'a, R: Read + 'a>(reader: R) -> std::io::Result<Box<dyn Read + 'a>> {
+    let mut reader = BufReader::new(reader);
+    Ok(if reader.fill_buf()?.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::stream::read::Decoder::with_buffer(reader)?)
+    } else {
+        Box::new(reader)
+    })
+}
+
+pub struct SpectrumStreamReader<R: Read> {
+    reader: R,
+    header: SpectrumStreamHeader,
+}
+
+impl<R: Read> SpectrumStreamReader<R> {
+    pub fn new(mut reader: R) -> std::io::Result<Self> {
+        let header = SpectrumStreamHeader::read(&mut reader)?;
+        Ok(Self { reader, header })
+    }
+
+    pub fn header(&self) -> &SpectrumStreamHeader {
+        &self.header
+    }
+
+    /// Read the next record or `None` at the end of the stream.
+    pub fn read_record(&mut self) -> std::io::Result<Option<SpectrumRecord>> {
+        let mut timestamp = [0; 8];
+        match self.reader.read_exact(&mut timestamp) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let values = read_f32s(&mut self.reader, self.header.record_len())?;
+        Ok(Some(SpectrumRecord {
+            timestamp_us: i64::from_le_bytes(timestamp),
+            values,
+        }))
+    }
+}
+
+impl<R: Read> Iterator for SpectrumStreamReader<R> {
+    type Item = std::io::Result<SpectrumRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Convert a stream into CSV with the same layout as the CSV logger.
+///
+/// Compressed streams are decompressed transparently.
+pub fn stream_to_csv<R: Read, W: Write>(reader: R, writer: W) -> Result<(), String> {
+    let reader = decompressing_reader(reader)
+        .and_then(SpectrumStreamReader::new)
+        .map_err(|e| e.to_string())?;
+    let header = reader.header().clone();
+    let mut writer = csv::Writer::from_writer(writer);
+
+    let mut columns = vec!["timestamp".to_string()];
+    for channel in 0..header.channels {
+        for wavelength in &header.wavelengths {
+            columns.push(if header.channels == 1 {
+                wavelength.to_string()
+            } else {
+                format!("{}:{}", channel, wavelength)
+            });
+        }
+    }
+    writer.write_record(&columns).map_err(|e| e.to_string())?;
+
+    writer.flush().map_err(|e| e.to_string())?;
+    let mut writer = writer.into_inner().map_err(|e| e.to_string())?;
+
+    let mut records = reader.peekable();
+    while records.peek().is_some() {
+        let chunk = records
+            .by_ref()
+            .take(CHUNK_RECORDS)
+            .collect::<std::io::Result<Vec<_>>>()
+            .map_err(|e| e.to_string())?;
+        let batches = chunk
+            .par_chunks(BATCH_RECORDS)
+            .map(records_to_csv)
+            .collect::<Result<Vec<_>, _>>()?;
+        for batch in batches {
+            writer.write_all(&batch).map_err(|e| e.to_string())?;
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+fn records_to_csv(records: &[SpectrumRecord]) -> Result<Vec<u8>, String> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    for record in records {
+        let timestamp = record
+            .local_time()
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default();
+        writer
+            .write_record(
+                std::iter::once(timestamp).chain(record.values.iter().map(|v| v.to_string())),
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    writer.into_inner().map_err(|e| e.to_string())
+}
+
+/// Convert a stream into HDF5 with the datasets `time` in µs since the UNIX epoch,
+/// `wavelength` and `spectra` with one row per record, or one row per channel if there are
+/// several.
+///
+/// Compressed streams are decompressed transparently.
+pub fn stream_to_hdf5<R: Read, W: Write>(reader: R, writer: &mut W) -> Result<(), String> {
+    let reader = decompressing_reader(reader)
+        .and_then(SpectrumStreamReader::new)
+        .map_err(|e| e.to_string())?;
+    let header = reader.header().clone();
+    let mut times = vec![];
+    let mut values = vec![];
+    for record in reader {
+        let record = record.map_err(|e| e.to_string())?;
+        times.push(record.timestamp_us);
+        values.extend(record.values);
+    }
+    let points = header.wavelengths.len();
+    let shape = if header.channels == 1 {
+        vec![times.len(), points]
+    } else {
+        vec![times.len(), header.channels as usize, points]
+    };
+
+    let mut hdf5 = Hdf5File::default();
+    hdf5.attribute(
+        "software",
+        format!("spectro-cam-rs {}", env!("CARGO_PKG_VERSION")),
+    );
+    hdf5.attribute("channels", header.channels as i64);
+    hdf5.dataset(
+        Hdf5Dataset::new("time", &[times.len()], Hdf5Data::I64(times))
+            .attribute("unit", "us since 1970-01-01T00:00:00Z"),
+    );
+    hdf5.dataset(
+        Hdf5Dataset::new("wavelength", &[points], Hdf5Data::F32(header.wavelengths))
+            .attribute("unit", "nm"),
+    );
+    hdf5.dataset(Hdf5Dataset::new("spectra", &shape, Hdf5Data::F32(values)));
+    hdf5.write(writer).map_err(|e| e.to_string())
+}
+
+/// Convert the stream or CSV log at `path` into an HDF5 file next to it, see
+/// [stream_to_hdf5], returning its path.
+///
+/// The spectra are held in memory until the file is written, [ConversionJob::spawn_hdf5]
+/// converts on a background thread.
+pub fn convert_file_to_hdf5(path: &str) -> Result<String, String> {
+    convert_file_to_hdf5_with_progress(path, &ConversionProgress::default())
+}
+
+fn convert_file_to_hdf5_with_progress(
+    path: &str,
+    progress: &ConversionProgress,
+) -> Result<String, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    progress
+        .total
+        .store(file.metadata().map_or(0, |m| m.len()), Ordering::Relaxed);
+    let mut input = BufReader::new(ProgressReader {
+        reader: file,
+        progress,
+    });
+    let is_stream = input
+        .fill_buf()
+        .map(|buf| buf.starts_with(MAGIC) || buf.starts_with(ZSTD_MAGIC))
+        .map_err(|e| e.to_string())?;
+    let output_path = output_path(path, "h5");
+    let mut output = BufWriter::new(File::create(&output_path).map_err(|e| e.to_string())?);
+    if is_stream {
+        stream_to_hdf5(input, &mut output)?;
+    } else {
+        let mut stream = vec![];
+        csv_to_stream(input, &mut stream)?;
+        stream_to_hdf5(stream.as_slice(), &mut output)?;
+    }
+    output.flush().map_err(|e| e.to_string())?;
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+/// Convert a single channel CSV log into a stream.
+///
+/// Column headers are either wavelengths or bands like `500-510 nm`, which are stored as
+/// their center wavelength.
+pub fn csv_to_stream<R: Read, W: Write>(reader: R, writer: W) -> Result<(), String> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let wavelengths = reader
+        .headers()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .skip(1)
+        .map(parse_column_wavelength)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut writer = SpectrumStreamWriter::new(
+        writer,
+        SpectrumStreamHeader {
+            channels: 1,
+            wavelengths,
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut rows = reader.records().peekable();
+    while rows.peek().is_some() {
+        let chunk = rows
+            .by_ref()
+            .take(CHUNK_RECORDS)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let records = chunk
+            .par_iter()
+            .map(|row| {
+                let timestamp_us = DateTime::parse_from_rfc3339(row.get(0).unwrap_or_default())
+                    .map_err(|e| e.to_string())?
+                    .timestamp_micros();
+                let values = row
+                    .iter()
+                    .skip(1)
+                    .map(|v| v.parse::<f32>().map_err(|e| e.to_string()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(SpectrumRecord {
+                    timestamp_us,
+                    values,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        for record in &records {
+            writer.write_record(record).map_err(|e| e.to_string())?;
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Convert the file at `path` between stream and CSV, depending on its content.
+///
+/// Returns the path of the written file.
+pub fn convert_file(path: &str) -> Result<String, String> {
+    convert_file_with_progress(path, &ConversionProgress::default())
+}
+
+fn convert_file_with_progress(path: &str, progress: &ConversionProgress) -> Result<String, String> {
+    let mut magic = [0; 4];
+    let is_stream = File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok()
+        && (&magic == MAGIC || &magic == ZSTD_MAGIC);
+
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    progress
+        .total
+        .store(file.metadata().map_or(0, |m| m.len()), Ordering::Relaxed);
+    let input = BufReader::new(ProgressReader {
+        reader: file,
+        progress,
+    });
+    let output_path = output_path(path, if is_stream { "csv" } else { "bin" });
+    let mut output = BufWriter::new(File::create(&output_path).map_err(|e| e.to_string())?);
+    if is_stream {
+        stream_to_csv(input, &mut output)?;
+    } else {
+        csv_to_stream(input, &mut output)?;
+    }
+    output
+        .into_inner()
+        .map_err(|e| e.to_string())?
+        .sync_all()
+        .map_err(|e| e.to_string())?;
+    Ok(output_path.to_string_lossy().to_string())
+}
+
+/// The file next to `path` with the given extension, or `<stem>.converted.<extension>` if
+/// `path` already has it, so that a conversion never overwrites its input.
+fn output_path(path: &str, extension: &str) -> PathBuf {
+    let path = Path::new(path);
+    let output = path.with_extension(extension);
+    if output == path {
+        path.with_extension(format!("converted.{}", extension))
+    } else {
+        output
+    }
+}
+
+/// Cut off the record a crash left incomplete at the end of the stream file at `path`,
+/// returning whether the file had to be repaired.
+///
+/// Compressed streams are rewritten with the records of all frames that were finished and
+/// of the blocks of the last one that can still be decoded.
+pub fn repair_file<P: AsRef<Path>>(path: P) -> std::io::Result<bool> {
+    let path = path.as_ref();
+    let mut reader = decompressing_reader(File::open(path)?)?;
+    let header = SpectrumStreamHeader::read(&mut reader)?;
+    let mut record = vec![0; 8 + 4 * header.record_len()];
+    let mut records = 0;
+    // An unfinished zstd frame fails to decode
+    let intact = loop {
+        match read_up_to(&mut reader, &mut record) {
+            Ok(len) if len == record.len() => records += 1,
+            Ok(0) => break true,
+            _ => break false,
+        }
+    };
+    drop(reader);
+    if intact {
+        return Ok(false);
+    }
+
+    let mut magic = [0; 4];
+    File::open(path)?.read_exact(&mut magic)?;
+    if &magic == ZSTD_MAGIC {
+        let repaired = path.with_extension("repaired");
+        {
+            let mut reader = SpectrumStreamReader::new(decompressing_reader(File::open(path)?)?)?;
+            let output = FrameEncoder::new(BufWriter::new(File::create(&repaired)?))?;
+            let mut writer = SpectrumStreamWriter::new(output, header)?;
+            for _ in 0..records {
+                let record = reader
+                    .read_record()?
+                    .ok_or_else(|| invalid_data("Stream changed while repairing"))?;
+                writer.write_record(&record)?;
+            }
+            writer.into_inner().finish()?.flush()?;
+        }
+        std::fs::rename(repaired, path)?;
+    } else {
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len((header.encoded_len() + records * record.len()) as u64)?;
+    }
+    Ok(true)
+}
+
+/// Shared state of a conversion running on another thread.
+#[derive(Debug, Default)]
+struct ConversionProgress {
+    read: AtomicU64,
+    total: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+/// Counts the bytes read and aborts reading once the conversion is cancelled.
+struct ProgressReader<'a, R: Read> {
+    reader: R,
+    progress: &'a ConversionProgress,
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.progress.cancelled.load(Ordering::Relaxed) {
+            return Err(std::io::Error::other("Conversion cancelled"));
+        }
+        let n = self.reader.read(buf)?;
+        self.progress.read.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// [convert_file] or [convert_file_to_hdf5] on a background thread, so that large logs
+/// neither block the GUI nor the acquisition.
+pub struct ConversionJob {
+    progress: Arc<ConversionProgress>,
+    handle: JoinHandle<Result<String, String>>,
+}
+
+impl ConversionJob {
+    /// Convert between stream and CSV, see [convert_file].
+    pub fn spawn(path: &str) -> Self {
+        Self::spawn_with(path, convert_file_with_progress)
+    }
+
+    /// Export as HDF5, see [convert_file_to_hdf5].
+    pub fn spawn_hdf5(path: &str) -> Self {
+        Self::spawn_with(path, convert_file_to_hdf5_with_progress)
+    }
+
+    fn spawn_with(
+        path: &str,
+        convert: fn(&str, &ConversionProgress) -> Result<String, String>,
+    ) -> Self {
+        let progress = Arc::new(ConversionProgress::default());
+        let thread_progress = Arc::clone(&progress);
+        let path = path.to_string();
+        Self {
+            progress,
+            handle: std::thread::spawn(move || convert(&path, &thread_progress)),
+        }
+    }
+
+    /// Fraction of the input file read so far.
+    pub fn progress(&self) -> f32 {
+        let total = self.progress.total.load(Ordering::Relaxed);
+        if total == 0 {
+            0.
+        } else {
+            self.progress.read.load(Ordering::Relaxed) as f32 / total as f32
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Wait for the conversion and return the path of the written file.
+    pub fn join(self) -> Result<String, String> {
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err("Conversion thread panicked".to_string()))
+    }
+}
+
+fn parse_column_wavelength(column: &str) -> Result<f32, String> {
+    if let Ok(wavelength) = column.trim().parse::<f32>() {
+        return Ok(wavelength);
+    }
+    column
+        .trim()
+        .trim_end_matches("nm")
+        .trim()
+        .split_once('-')
+        .and_then(|(start, end)| {
+            Some((start.trim().parse::<f32>().ok()? + end.trim().parse::<f32>().ok()?) / 2.)
+        })
+        .ok_or(format!("Invalid wavelength column: {}", column))
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Fill `buf` as far as the data goes, returning the number of bytes read.
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> std::io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_f32s<R: Read>(reader: &mut R, len: usize) -> std::io::Result<Vec<f32>> {
+    let mut bytes = vec![0; len * 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
+}
+
+fn write_f32s<W: Write>(writer: &mut W, values: &[f32]) -> std::io::Result<()> {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    writer.write_all(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header() -> SpectrumStreamHeader {
+        SpectrumStreamHeader {
+            channels: 1,
+            wavelengths: vec![400., 500., 600.],
+        }
+    }
+
+    #[test]
+    fn stream_roundtrip() {
+        let records = vec![
+            SpectrumRecord {
+                timestamp_us: 1_000_000,
+                values: vec![0.1, 0.2, 0.3],
+            },
+            SpectrumRecord {
+                timestamp_us: 2_000_000,
+                values: vec![0.4, 0.5, 0.6],
+            },
+        ];
+        let mut writer = SpectrumStreamWriter::new(Vec::new(), header()).unwrap();
+        for record in &records {
+            writer.write_record(record).unwrap();
+        }
+        assert!(writer
+            .write_record(&SpectrumRecord {
+                timestamp_us: 0,
+                values: vec![0.],
+            })
+            .is_err());
+        let bytes = writer.into_inner();
+        assert_eq!(
+            bytes.len(),
+            header().encoded_len() + records.len() * (8 + 4 * header().record_len())
+        );
+
+        let reader = SpectrumStreamReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.header(), &header());
+        assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), records);
+
+        assert!(SpectrumStreamReader::new(&b"SPCX"[..]).is_err());
+    }
+
+    #[test]
+    fn csv_roundtrip() {
+        let csv = "timestamp,400,500-510 nm\n\
+                   2022-05-01T12:00:00+00:00,1,2\n\
+                   2022-05-01T12:00:01+00:00,3,4\n";
+        let mut stream = Vec::new();
+        csv_to_stream(csv.as_bytes(), &mut stream).unwrap();
+
+        let mut reader = SpectrumStreamReader::new(stream.as_slice()).unwrap();
+        assert_eq!(reader.header().wavelengths, vec![400., 505.]);
+        let first = reader.read_record().unwrap().unwrap();
+        assert_eq!(first.values, vec![1., 2.]);
+        assert_eq!(first.timestamp_us, 1_651_406_400_000_000);
+
+        let mut csv = Vec::new();
+        stream_to_csv(stream.as_slice(), &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "timestamp,400,505");
+        assert!(lines[2].ends_with(",3,4"));
+    }
+
+    #[test]
+    fn compressed_append() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("compressed-append.bin");
+        for i in 0..2 {
+            let mut writer = SpectrumStreamWriter::append_to_file(&path, header(), true).unwrap();
+            writer
+                .write_record(&SpectrumRecord {
+                    timestamp_us: i,
+                    values: vec![0.1, 0.2, 0.3],
+                })
+                .unwrap();
+        }
+        assert!(SpectrumStreamWriter::append_to_file(&path, header(), false).is_err());
+
+        let mut csv = Vec::new();
+        stream_to_csv(File::open(&path).unwrap(), &mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 3);
+    }
+
+    /// Write `records` records to a new file at `path`.
+    fn write_file(path: &Path, compressed: bool, records: usize) {
+        let mut writer = SpectrumStreamWriter::append_to_file(path, header(), compressed).unwrap();
+        for i in 0..records {
+            writer
+                .write_record(&SpectrumRecord {
+                    timestamp_us: i as i64,
+                    values: vec![0.1, 0.2, 0.3],
+                })
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn repair_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("log.bin");
+        write_file(&path, false, 3);
+        assert!(!super::repair_file(&path).unwrap());
+
+        // Crash in the middle of the last record
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 5)
+            .unwrap();
+        assert!(super::repair_file(&path).unwrap());
+        assert!(!super::repair_file(&path).unwrap());
+
+        let reader = SpectrumStreamReader::new(File::open(&path).unwrap()).unwrap();
+        let records = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].timestamp_us, 1);
+    }
+
+    #[test]
+    fn truncated_compressed_log() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("log.bin");
+        let frame_records = FRAME_BYTES / (8 + 4 * header().record_len());
+        let written = 2 * frame_records + 100;
+        write_file(&path, true, written);
+        assert!(!super::repair_file(&path).unwrap());
+
+        // Killed while writing the last frame
+        let len = std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 5)
+            .unwrap();
+        assert!(super::repair_file(&path).unwrap());
+        assert!(!super::repair_file(&path).unwrap());
+
+        let reader =
+            SpectrumStreamReader::new(decompressing_reader(File::open(&path).unwrap()).unwrap())
+                .unwrap();
+        let records = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert!(records.len() >= 2 * frame_records - 1);
+        assert!(records.len() < written);
+        assert!(records
+            .iter()
+            .enumerate()
+            .all(|(i, r)| r.timestamp_us == i as i64));
+    }
+
+    #[test]
+    fn conversion_job() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("conversion-job.bin");
+        let mut writer = SpectrumStreamWriter::new(File::create(&path).unwrap(), header()).unwrap();
+        for i in 0..(CHUNK_RECORDS + 10) {
+            writer
+                .write_record(&SpectrumRecord {
+                    timestamp_us: i as i64 * 1_000_000,
+                    values: vec![i as f32, 0.5, 1.],
+                })
+                .unwrap();
+        }
+        writer.flush().unwrap();
+
+        let job = ConversionJob::spawn(path.to_str().unwrap());
+        let csv_path = job.join().unwrap();
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), CHUNK_RECORDS + 11);
+        assert!(lines[CHUNK_RECORDS + 10].ends_with(&format!(",{},0.5,1", CHUNK_RECORDS + 9)));
+    }
+
+    #[test]
+    fn convert_without_overwriting_input() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("log.csv");
+        let mut writer = SpectrumStreamWriter::new(File::create(&path).unwrap(), header()).unwrap();
+        writer
+            .write_record(&SpectrumRecord {
+                timestamp_us: 0,
+                values: vec![0., 0.5, 1.],
+            })
+            .unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let stream = std::fs::read(&path).unwrap();
+
+        let csv_path = convert_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            Path::new(&csv_path),
+            directory.path().join("log.converted.csv")
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), stream);
+        assert_eq!(
+            std::fs::read_to_string(csv_path).unwrap().lines().count(),
+            2
+        );
+    }
+}
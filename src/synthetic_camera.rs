@@ -0,0 +1,160 @@
+use crate::colorimetry::{color_matching_functions, planck};
+use crate::config::SyntheticCameraConfig;
+use image::{ImageBuffer, Rgb};
+use std::time::{Duration, Instant};
+
+/// CIE XYZ to linear sRGB.
+const XYZ_TO_RGB: [[f32; 3]; 3] = [
+    [3.2406, -1.5372, -0.4986],
+    [-0.9689, 1.8758, 0.0415],
+    [0.0557, -0.2040, 1.0570],
+];
+
+/// Virtual camera drawing a configurable spectrum into its frames, for demos and tests.
+pub struct SyntheticCamera {
+    config: SyntheticCameraConfig,
+    /// Pixel values of the band for every column, before noise.
+    columns: Vec<[f32; 3]>,
+    rng: u64,
+    /// Second sample of the last Box-Muller transform.
+    spare_noise: Option<f32>,
+    last_frame: Option<Instant>,
+}
+
+impl SyntheticCamera {
+    pub fn new(config: SyntheticCameraConfig) -> Self {
+        let columns = (0..config.width)
+            .map(|column| {
+                // Wavelengths decrease to the right, as the default flip expects
+                let wavelength = config.wavelength_start
+                    + (config.width - 1 - column) as f32 * config.dispersion;
+                let value = Self::spectral_power(&config, wavelength);
+                Self::sensor_response(wavelength).map(|c| c * value * u8::MAX as f32)
+            })
+            .collect();
+        Self {
+            config,
+            columns,
+            rng: 0x2545_f491_4f6c_dd1d,
+            spare_noise: None,
+            last_frame: None,
+        }
+    }
+
+    /// Relative spectral power of continuum and lines at `wavelength`.
+    fn spectral_power(config: &SyntheticCameraConfig, wavelength: f32) -> f32 {
+        let temperature = config.temperature as f64;
+        // Wien's displacement law
+        let peak = 2.897_772e6 / temperature;
+        let continuum = config.continuum
+            * (planck(wavelength as f64, temperature) / planck(peak, temperature)) as f32;
+        config.lines.iter().fold(continuum, |sum, line| {
+            let x = (wavelength - line.wavelength) / line.fwhm;
+            sum + line.intensity * (-4. * std::f32::consts::LN_2 * x * x).exp()
+        })
+    }
+
+    /// Color of a pure spectral line with its brightest channel at 1, black outside of the
+    /// visible range.
+    fn sensor_response(wavelength: f32) -> [f32; 3] {
+        let xyz = color_matching_functions(wavelength);
+        let rgb =
+            XYZ_TO_RGB.map(|row| row.iter().zip(xyz).map(|(m, v)| m * v).sum::<f32>().max(0.));
+        let max = rgb.iter().cloned().fold(0., f32::max);
+        if max > 0. {
+            rgb.map(|c| c / max)
+        } else {
+            rgb
+        }
+    }
+
+    /// Uniformly distributed in (0, 1].
+    fn uniform(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        ((self.rng >> 40) + 1) as f32 / (1 << 24) as f32
+    }
+
+    /// Normal distributed noise with unit standard deviation, two samples per Box-Muller
+    /// transform.
+    fn noise(&mut self) -> f32 {
+        if let Some(spare) = self.spare_noise.take() {
+            return spare;
+        }
+        let radius = (-2. * self.uniform().ln()).sqrt();
+        let (sin, cos) = (std::f32::consts::TAU * self.uniform()).sin_cos();
+        self.spare_noise = Some(radius * sin);
+        radius * cos
+    }
+
+    /// Next frame, waiting for the configured frame rate.
+    pub fn poll_frame(&mut self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let interval = Duration::from_secs_f32(1. / self.config.fps.max(1) as f32);
+        if let Some(elapsed) = self.last_frame.map(|t| t.elapsed()) {
+            if elapsed < interval {
+                std::thread::sleep(interval - elapsed);
+            }
+        }
+        self.last_frame = Some(Instant::now());
+        self.frame()
+    }
+
+    /// Generate a frame without waiting.
+    pub fn frame(&mut self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let half_band = self.config.band_height as f32 / 2.;
+        let band_row = self.config.band_row as f32;
+        let noise = self.config.noise * u8::MAX as f32;
+        let mut frame = ImageBuffer::new(self.config.width, self.config.height);
+        for (column, row, pixel) in frame.enumerate_pixels_mut() {
+            let in_band = (row as f32 - band_row).abs() <= half_band;
+            let signal = if in_band {
+                self.columns[column as usize]
+            } else {
+                [0.; 3]
+            };
+            let value = if noise > 0. {
+                signal.map(|c| c + noise * self.noise())
+            } else {
+                signal
+            };
+            *pixel = Rgb(value.map(|c| c.round().clamp(0., u8::MAX as f32) as u8));
+        }
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ImageConfig, SpectrometerConfig};
+    use crate::roi::extract_window;
+    use crate::spectrum::{SpectrumCalculator, SpectrumProcessor};
+    use image::DynamicImage;
+
+    #[test]
+    fn synthetic_spectrum() {
+        let config = SpectrometerConfig::default();
+        let image_config = ImageConfig::default();
+        let mut camera = SyntheticCamera::new(SyntheticCameraConfig {
+            noise: 0.,
+            ..Default::default()
+        });
+
+        let frame = DynamicImage::ImageRgb8(camera.frame()).fliph().into_rgb8();
+        let window = extract_window(&frame, &image_config.window, image_config.rotation);
+        let mut processor = SpectrumProcessor::new();
+        let spectrum = processor
+            .process(
+                SpectrumCalculator::process_window(&window, &image_config.binning),
+                &config,
+            )
+            .to_processed_spectrum(&config);
+
+        let sum = spectrum.channels.row(3);
+        let peak = (0..sum.len())
+            .max_by(|&a, &b| sum[a].total_cmp(&sum[b]))
+            .unwrap();
+        assert!((spectrum.wavelengths[peak] - 546.1).abs() < 1.);
+    }
+}
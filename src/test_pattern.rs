@@ -0,0 +1,99 @@
+use crate::config::{SpectrumCalibration, SpectrumPoint, TestPatternConfig};
+use crate::spectrum::{SpectrumRgb, CHANNEL_FULL_SCALE};
+
+/// Peak height of the lines relative to full scale.
+const LINE_INTENSITY: f32 = 0.8;
+
+/// Raw spectrum of a window `width` columns wide with Gaussian lines exactly at the configured
+/// wavelengths under `calibration`, to check the wavelength axis, markers and exports.
+pub fn test_pattern(
+    config: &TestPatternConfig,
+    calibration: &SpectrumCalibration,
+    width: usize,
+) -> SpectrumRgb {
+    let centers = config
+        .wavelengths
+        .iter()
+        .map(|&w| calibration.get_index_from_wavelength(w))
+        .collect::<Vec<_>>();
+    // The calibration is linear, so the width in columns is the same for all lines
+    let fwhm = (calibration.get_index_from_wavelength(config.fwhm)
+        - calibration.get_index_from_wavelength(0.))
+    .abs();
+    SpectrumRgb::from_fn(width, |_, column| {
+        let value = centers.iter().fold(0., |sum, center| {
+            let x = (column as f32 - center) / fwhm;
+            sum + LINE_INTENSITY * (-4. * std::f32::consts::LN_2 * x * x).exp()
+        });
+        value.min(1.) * CHANNEL_FULL_SCALE
+    })
+}
+
+/// Largest distance in nm between a line of the pattern and the nearest of `peaks`, `None`
+/// if no peak was found.
+pub fn max_deviation(config: &TestPatternConfig, peaks: &[SpectrumPoint]) -> Option<f32> {
+    config
+        .wavelengths
+        .iter()
+        .map(|&w| {
+            peaks
+                .iter()
+                .map(|p| (p.wavelength - w).abs())
+                .reduce(f32::min)
+        })
+        .try_fold(0., |max: f32, deviation| Some(max.max(deviation?)))
+}
+
+/// Wavelengths separated by commas, `None` if one is not a number.
+pub fn parse_wavelengths(text: &str) -> Option<Vec<f32>> {
+    text.split(',')
+        .filter(|w| !w.trim().is_empty())
+        .map(|w| w.trim().parse().ok())
+        .collect()
+}
+
+pub fn format_wavelengths(wavelengths: &[f32]) -> String {
+    wavelengths
+        .iter()
+        .map(|w| w.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SpectrometerConfig;
+    use crate::spectrum::SpectrumProcessor;
+
+    #[test]
+    fn test_pattern_peaks() {
+        let mut config = SpectrometerConfig::default();
+        config.view_config.fit_peaks = true;
+        let width = config.image_config.window.size.x as usize;
+        let spectrum = test_pattern(&config.test_pattern, &config.spectrum_calibration, width);
+
+        let mut processor = SpectrumProcessor::new();
+        let frame = processor.process(spectrum, &config);
+        let peaks = frame
+            .fit_peaks(&config)
+            .into_iter()
+            .map(|fit| SpectrumPoint {
+                wavelength: fit.center,
+                value: fit.amplitude,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(peaks.len(), config.test_pattern.wavelengths.len());
+        assert!(max_deviation(&config.test_pattern, &peaks).unwrap() < 0.05);
+        assert_eq!(max_deviation(&config.test_pattern, &[]), None);
+
+        let text = format_wavelengths(&config.test_pattern.wavelengths);
+        assert_eq!(text, "450, 500, 550, 600, 650");
+        assert_eq!(
+            parse_wavelengths(&text),
+            Some(config.test_pattern.wavelengths)
+        );
+        assert_eq!(parse_wavelengths("435.8,546.1, "), Some(vec![435.8, 546.1]));
+        assert_eq!(parse_wavelengths("435.8, Hg"), None);
+    }
+}
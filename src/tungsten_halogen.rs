@@ -1,16 +1,64 @@
+use crate::colorimetry::planck;
 use crate::config::SpectrumPoint;
+use std::fmt::{Display, Formatter};
 
 const T0: f64 = 2.200;
-const C: f64 = physical_constants::SPEED_OF_LIGHT_IN_VACUUM;
-const H: f64 = physical_constants::PLANCK_CONSTANT;
-const K: f64 = physical_constants::BOLTZMANN_CONSTANT;
 
-pub fn reference_from_filament_temp(filament_temp: u16) -> Vec<SpectrumPoint> {
+/// Bulb material, whose transmission is applied to the filament radiation.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GlassEnvelope {
+    None,
+    /// Fused quartz of halogen lamps.
+    Quartz,
+    Borosilicate,
+    /// Soda-lime glass of household incandescent bulbs.
+    SodaLime,
+}
+
+impl Display for GlassEnvelope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GlassEnvelope::None => write!(f, "None"),
+            GlassEnvelope::Quartz => write!(f, "Quartz"),
+            GlassEnvelope::Borosilicate => write!(f, "Borosilicate"),
+            GlassEnvelope::SodaLime => write!(f, "Soda-Lime"),
+        }
+    }
+}
+
+impl GlassEnvelope {
+    /// Transmission of a 1 mm wall as UV absorption edge with Fresnel losses at both surfaces.
+    pub fn transmission(&self, wavelength: f64) -> f64 {
+        let (max, edge, width) = match self {
+            GlassEnvelope::None => return 1.,
+            GlassEnvelope::Quartz => (0.93, 200., 8.),
+            GlassEnvelope::Borosilicate => (0.92, 295., 12.),
+            GlassEnvelope::SodaLime => (0.91, 325., 10.),
+        };
+        max / (1. + (-(wavelength - edge) / width).exp())
+    }
+}
+
+/// Normalized spectrum of a tungsten filament at `filament_temp` in K, with the emissivity of
+/// tungsten or as black body, seen through `envelope`.
+pub fn reference_from_filament_temp(
+    filament_temp: u16,
+    emissivity: bool,
+    envelope: GlassEnvelope,
+) -> Vec<SpectrumPoint> {
     let mut ref_points = (340..2000)
         .into_iter()
-        .map(|wavelength| SpectrumPoint {
-            wavelength: wavelength as f32,
-            value: spectral_irradiance(wavelength as f64, filament_temp as f64).unwrap() as f32,
+        .map(|wavelength| {
+            let wavelength = wavelength as f64;
+            let radiance = if emissivity {
+                spectral_irradiance(wavelength, filament_temp as f64).unwrap()
+            } else {
+                planck(wavelength, filament_temp as f64)
+            };
+            SpectrumPoint {
+                wavelength: wavelength as f32,
+                value: (radiance * envelope.transmission(wavelength)) as f32,
+            }
         })
         .collect::<Vec<_>>();
     let max = ref_points
@@ -25,11 +73,7 @@ pub fn reference_from_filament_temp(filament_temp: u16) -> Vec<SpectrumPoint> {
 /// From: <https://doi.org/10.1364/AO.49.000880>
 ///
 fn spectral_irradiance(wavelength: f64, filament_temp: f64) -> Option<f64> {
-    let wavelength_m = wavelength * 10.0f64.powi(-9);
-    emissivity(wavelength, filament_temp).map(|e| {
-        e * 2. * H * C.powi(2)
-            / (wavelength_m.powi(5) * (H * C / (wavelength_m * K * filament_temp)).exp_m1())
-    })
+    emissivity(wavelength, filament_temp).map(|e| e * planck(wavelength, filament_temp))
 }
 
 /// From: <https://doi.org/10.1364/AO.23.000975>
@@ -67,11 +111,19 @@ mod tests {
 
     #[test]
     fn tungsten() {
-        let r = reference_from_filament_temp(2500);
+        let r = reference_from_filament_temp(2500, true, GlassEnvelope::None);
 
         assert_eq!(r.iter().map(|rp| rp.value).reduce(f32::max), Some(1.));
         assert_eq!(r.len(), 2000 - 340);
         assert_eq!(r.first().unwrap().wavelength, 340.);
         assert_eq!(r.last().unwrap().wavelength, 2000. - 1.);
+
+        // The emissivity of tungsten falls with the wavelength, which shifts the spectrum blue
+        let black_body = reference_from_filament_temp(2500, false, GlassEnvelope::None);
+        assert!(r[100].value > black_body[100].value);
+        // Soda-lime glass absorbs the near UV
+        let bulb = reference_from_filament_temp(2500, true, GlassEnvelope::SodaLime);
+        assert!(bulb[0].value < 0.9 * r[0].value);
+        assert!((bulb[500].value / r[500].value - 1.).abs() < 0.01);
     }
 }
@@ -0,0 +1,280 @@
+//! Minimal WebSocket server (RFC 6455) pushing every remote spectrum of the
+//! [SpectroEngine] as JSON text message to all connected clients.
+//!
+//! Every client has its own thread and queue of spectra, so that a slow client only drops
+//! its own spectra. Messages from clients are not read, a client is dropped as soon as
+//! sending fails.
+
+use crate::engine::SpectroEngine;
+use sha1::{Digest, Sha1};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Appended to the key of the client to compute the accept header.
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Time for a client to send the upgrade request, and to take a message.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Longest wait for a spectrum or a connection before the stop flag is checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+const OPCODE_TEXT: u8 = 0x1;
+const FIN: u8 = 0x80;
+
+/// Value of the `Sec-WebSocket-Accept` header for the key of the client.
+fn accept_key(key: &str) -> String {
+    base64::encode(Sha1::digest(
+        format!("{}{}", key.trim(), HANDSHAKE_GUID).as_bytes(),
+    ))
+}
+
+/// Read the upgrade request from the client and answer it, rejecting other requests.
+fn handshake(stream: &mut TcpStream) -> Result<(), String> {
+    stream
+        .set_read_timeout(Some(CLIENT_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    stream
+        .set_write_timeout(Some(CLIENT_TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let (mut key, mut upgrade, mut version) = (None, false, None);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+            return Err("Connection closed during the handshake".to_string());
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            match name.trim().to_lowercase().as_str() {
+                "sec-websocket-key" => key = Some(value.to_string()),
+                "sec-websocket-version" => version = Some(value.to_string()),
+                "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+                _ => {}
+            }
+        }
+    }
+    let key = match key {
+        Some(key) if upgrade => key,
+        _ => {
+            stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n").ok();
+            return Err("Not a WebSocket upgrade request".to_string());
+        }
+    };
+    if version.as_deref() != Some("13") {
+        stream
+            .write_all(b"HTTP/1.1 426 Upgrade Required\r\nSec-WebSocket-Version: 13\r\n\r\n")
+            .ok();
+        return Err(format!("Unsupported WebSocket version {:?}", version));
+    }
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Unmasked text frame with the message, as sent by servers.
+fn text_frame(message: &str) -> Vec<u8> {
+    let payload = message.as_bytes();
+    let mut frame = vec![FIN | OPCODE_TEXT];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Server thread accepting clients, stopped with the client threads when dropped.
+pub struct WebSocketServer {
+    port: u16,
+    stop: Arc<AtomicBool>,
+    clients: Arc<AtomicUsize>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WebSocketServer {
+    /// Listen on `address` at `port`, 0 for any free port.
+    pub fn start(address: IpAddr, port: u16, engine: &SpectroEngine) -> Result<Self, String> {
+        let listener = TcpListener::bind((address, port)).map_err(|e| e.to_string())?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let stop = Arc::new(AtomicBool::new(false));
+        let clients = Arc::new(AtomicUsize::new(0));
+        let engine = engine.clone();
+        let thread_stop = Arc::clone(&stop);
+        let thread_clients = Arc::clone(&clients);
+        let handle = std::thread::spawn(move || {
+            let mut client_threads: Vec<JoinHandle<()>> = vec![];
+            while !thread_stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, address)) => {
+                        let stop = Arc::clone(&thread_stop);
+                        let clients = Arc::clone(&thread_clients);
+                        let engine = engine.clone();
+                        client_threads.push(std::thread::spawn(move || {
+                            serve_client(stream, &engine, &stop, &clients)
+                                .map_err(|e| log::warn!("WebSocket client {}: {}", address, e))
+                                .ok();
+                        }));
+                    }
+                    Err(_) => std::thread::sleep(POLL_INTERVAL),
+                }
+                client_threads.retain(|thread| !thread.is_finished());
+            }
+            for thread in client_threads {
+                thread.join().ok();
+            }
+        });
+        Ok(Self {
+            port,
+            stop,
+            clients,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Number of connected clients.
+    pub fn clients(&self) -> usize {
+        self.clients.load(Ordering::Relaxed)
+    }
+}
+
+/// Send the spectra to one client until it disconnects or the server stops.
+fn serve_client(
+    mut stream: TcpStream,
+    engine: &SpectroEngine,
+    stop: &AtomicBool,
+    clients: &AtomicUsize,
+) -> Result<(), String> {
+    stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+    stream.set_nodelay(true).map_err(|e| e.to_string())?;
+    handshake(&mut stream)?;
+    log::info!("WebSocket client {:?} connected", stream.peer_addr());
+    let spectrum_rx = engine.subscribe_remote();
+    clients.fetch_add(1, Ordering::Relaxed);
+    let mut result = Ok(());
+    while !stop.load(Ordering::Relaxed) {
+        if let Ok(spectrum) = spectrum_rx.recv_timeout(POLL_INTERVAL) {
+            if let Err(e) = stream.write_all(&text_frame(&spectrum.to_json())) {
+                result = Err(e.to_string());
+                break;
+            }
+        }
+    }
+    clients.fetch_sub(1, Ordering::Relaxed);
+    result
+}
+
+impl Drop for WebSocketServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{ProcessedSpectrum, SpectrumFlags};
+    use crate::spectrum::Spectrum;
+    use std::io::Read;
+    use std::net::Ipv4Addr;
+    use std::time::{Instant, SystemTime};
+
+    #[test]
+    fn websocket() {
+        // Example of RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        assert_eq!(text_frame("Hi"), vec![0x81, 2, b'H', b'i']);
+        assert_eq!(&text_frame(&"x".repeat(300))[..4], &[0x81, 126, 1, 44]);
+
+        let engine = SpectroEngine::default();
+        let server = WebSocketServer::start(Ipv4Addr::LOCALHOST.into(), 0, &engine).unwrap();
+
+        // Plain HTTP requests and other versions are rejected
+        for (request, status) in [
+            ("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n", "HTTP/1.1 400"),
+            (
+                "GET / HTTP/1.1\r\nUpgrade: websocket\r\nSec-WebSocket-Key: a2V5\r\n\
+                 Sec-WebSocket-Version: 8\r\n\r\n",
+                "HTTP/1.1 426",
+            ),
+        ] {
+            let mut client = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+            client.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            BufReader::new(client).read_line(&mut response).unwrap();
+            assert!(response.starts_with(status));
+        }
+
+        let mut client = TcpStream::connect(("127.0.0.1", server.port())).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        write!(
+            client,
+            "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n"
+        )
+        .unwrap();
+        let mut reader = BufReader::new(client);
+        let mut status = String::new();
+        reader.read_line(&mut status).unwrap();
+        assert!(status.starts_with("HTTP/1.1 101"));
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+
+        let started = Instant::now();
+        while server.clients() == 0 {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        engine.publish(ProcessedSpectrum {
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(1500),
+            wavelengths: vec![400., 500.],
+            channels: Spectrum::from_element(2, 0.5),
+            averaged_frames: 3,
+            flags: SpectrumFlags::default(),
+        });
+        let mut header = [0; 4];
+        reader.read_exact(&mut header).unwrap();
+        assert_eq!(header[..2], [0x81, 126]);
+        let mut message = vec![0; u16::from_be_bytes([header[2], header[3]]) as usize];
+        reader.read_exact(&mut message).unwrap();
+        let message = String::from_utf8(message).unwrap();
+        assert!(message.starts_with("{\"timestamp_ms\":1500,\"wavelengths\":[400.0,500.0],"));
+        assert!(message.contains("\"sum\":[0.5,0.5],\"averaged_frames\":3,"));
+    }
+}
@@ -0,0 +1,249 @@
+use egui::emath::Numeric;
+use egui::{Button, Response, RichText, Sense, Slider, Style, TextStyle, Ui, Vec2, Widget};
+use std::ops::RangeInclusive;
+
+const REPEAT_DELAY: f64 = 0.5;
+const REPEAT_INTERVAL: f64 = 0.08;
+/// Stepper steps across the whole range of a logarithmic slider.
+const LOG_STEPS: f64 = 100.;
+/// Lower end of the logarithmic stepping for ranges starting at zero, like the slider.
+const LOG_SMALLEST: f64 = 1e-6;
+
+/// Enlarge fonts and hit targets for touch screens.
+pub fn touch_style(style: &Style) -> Style {
+    let mut style = style.clone();
+    style.spacing.item_spacing *= 1.5;
+    style.spacing.button_padding *= 2.;
+    style.spacing.interact_size = Vec2::new(48., 40.);
+    style.spacing.slider_width *= 1.5;
+    style.spacing.icon_width *= 2.;
+    style.spacing.icon_width_inner *= 2.;
+    style.spacing.scroll_bar_width *= 2.;
+    for (text_style, font_id) in style.text_styles.iter_mut() {
+        if *text_style != TextStyle::Small {
+            font_id.size *= 1.4;
+        }
+    }
+    style
+}
+
+/// Slider which is replaced by +/- stepper buttons with long-press repeat in touch mode.
+pub struct TouchSlider<'a, Num: Numeric> {
+    value: &'a mut Num,
+    range: RangeInclusive<Num>,
+    touch: bool,
+    step: Option<f64>,
+    logarithmic: bool,
+    text: String,
+}
+
+impl<'a, Num: Numeric> TouchSlider<'a, Num> {
+    pub fn new(value: &'a mut Num, range: RangeInclusive<Num>, touch: bool) -> Self {
+        Self {
+            value,
+            range,
+            touch,
+            step: None,
+            logarithmic: false,
+            text: String::new(),
+        }
+    }
+
+    pub fn step_by(mut self, step: f64) -> Self {
+        self.step = Some(step);
+        self
+    }
+
+    pub fn logarithmic(mut self, logarithmic: bool) -> Self {
+        self.logarithmic = logarithmic;
+        self
+    }
+
+    pub fn text(mut self, text: impl ToString) -> Self {
+        self.text = text.to_string();
+        self
+    }
+
+    fn stepper_step(&self) -> f64 {
+        self.step.unwrap_or_else(|| {
+            if Num::INTEGRAL {
+                1.
+            } else {
+                (self.range.end().to_f64() - self.range.start().to_f64()) / 100.
+            }
+        })
+    }
+
+    fn add_steps(&mut self, steps: f64) {
+        let (start, end) = (self.range.start().to_f64(), self.range.end().to_f64());
+        let value = self.value.to_f64();
+        let mut stepped = if self.logarithmic && end > 0. {
+            // Steps of the same ratio, so that the stepper covers the range like the slider
+            let smallest = if start > 0. {
+                start
+            } else if Num::INTEGRAL {
+                1.
+            } else {
+                LOG_SMALLEST.min(end)
+            };
+            value.max(smallest) * (end / smallest).powf(steps / LOG_STEPS)
+        } else {
+            value + steps * self.stepper_step()
+        };
+        if Num::INTEGRAL {
+            stepped = stepped.round();
+            // Small ratios do not change small integers
+            if stepped == value {
+                stepped += steps.signum();
+            }
+        }
+        *self.value = Num::from_f64(stepped.clamp(start, end));
+    }
+}
+
+impl<'a, Num: Numeric> Widget for TouchSlider<'a, Num> {
+    fn ui(mut self, ui: &mut Ui) -> Response {
+        if !self.touch {
+            let mut slider = Slider::new(self.value, self.range)
+                .logarithmic(self.logarithmic)
+                .text(self.text);
+            if let Some(step) = self.step {
+                slider = slider.step_by(step);
+            }
+            return ui.add(slider);
+        }
+
+        let mut steps = 0.;
+        let mut response = ui
+            .horizontal(|ui| {
+                if repeat_button(ui, "−") {
+                    steps -= 1.;
+                }
+                ui.add_sized(
+                    ui.spacing().interact_size * Vec2::new(2., 1.),
+                    egui::Label::new(RichText::new(format_value(*self.value)).monospace()),
+                );
+                if repeat_button(ui, "+") {
+                    steps += 1.;
+                }
+                ui.label(&self.text);
+            })
+            .response;
+
+        if steps != 0. {
+            let previous = *self.value;
+            self.add_steps(steps);
+            if *self.value != previous {
+                response.mark_changed();
+            }
+        }
+        response
+    }
+}
+
+fn format_value<Num: Numeric>(value: Num) -> String {
+    if Num::INTEGRAL {
+        format!("{}", value.to_f64())
+    } else {
+        format!("{:.3}", value.to_f64())
+    }
+}
+
+/// Button which fires once on press and repeatedly while being held down.
+fn repeat_button(ui: &mut Ui, text: &str) -> bool {
+    let button = Button::new(RichText::new(text).strong()).sense(Sense::click_and_drag());
+    let response = ui.add(button);
+    let id = response.id;
+    let time = ui.input().time;
+
+    if !response.is_pointer_button_down_on() {
+        ui.data().remove::<(f64, f64)>(id);
+        return false;
+    }
+
+    ui.ctx().request_repaint();
+    let mut data = ui.data();
+    match data.get_temp::<(f64, f64)>(id) {
+        None => {
+            data.insert_temp(id, (time, time));
+            true
+        }
+        Some((pressed, last)) => {
+            let repeat = time - pressed > REPEAT_DELAY && time - last > REPEAT_INTERVAL;
+            if repeat {
+                data.insert_temp(id, (pressed, time));
+            }
+            repeat
+        }
+    }
+}
+
+/// On-screen numeric keypad editing `input`.
+///
+/// Returns `true` if enter was pressed.
+pub fn numpad(ui: &mut Ui, input: &mut String) -> bool {
+    let mut enter = false;
+    ui.label(
+        RichText::new(format!("{:>10}", input))
+            .monospace()
+            .heading(),
+    );
+    egui::Grid::new("numpad").show(ui, |ui| {
+        for row in [
+            ["7", "8", "9"],
+            ["4", "5", "6"],
+            ["1", "2", "3"],
+            [".", "0", "⌫"],
+        ] {
+            for key in row {
+                if ui.add_sized([48., 40.], Button::new(key)).clicked() {
+                    match key {
+                        "⌫" => {
+                            input.pop();
+                        }
+                        "." if input.contains('.') => {}
+                        _ => input.push_str(key),
+                    }
+                }
+            }
+            ui.end_row();
+        }
+        if ui.add_sized([48., 40.], Button::new("C")).clicked() {
+            input.clear();
+        }
+        ui.label("");
+        enter = ui.add_sized([48., 40.], Button::new("⏎")).clicked();
+        ui.end_row();
+    });
+    enter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn touch_slider_steps() {
+        let mut value = 10.;
+        let mut slider = TouchSlider::new(&mut value, 0.0..=100., true);
+        slider.add_steps(1.);
+        assert_eq!(value, 11.);
+
+        let mut exposure = 100;
+        let mut slider = TouchSlider::new(&mut exposure, 1..=10000, true).logarithmic(true);
+        slider.add_steps(1.);
+        // 10000^(1/100) is about 1.096
+        assert_eq!(exposure, 110);
+        let mut slider = TouchSlider::new(&mut exposure, 1..=10000, true).logarithmic(true);
+        slider.add_steps(-1000.);
+        assert_eq!(exposure, 1);
+        let mut slider = TouchSlider::new(&mut exposure, 1..=10000, true).logarithmic(true);
+        slider.add_steps(1.);
+        assert_eq!(exposure, 2);
+
+        let mut gain = 0.;
+        let mut slider = TouchSlider::new(&mut gain, 0.0..=1., true).logarithmic(true);
+        slider.add_steps(1.);
+        assert!(gain > 0. && gain < 1e-5);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::config::SpectrumWindow;
+use egui::Vec2;
+use image::{ImageBuffer, Rgb};
+
+/// Minimal mean brightness difference between the spectrum line and the background.
+const MIN_CONTRAST: f32 = 5.;
+/// Fraction between background and peak brightness which is still part of the line.
+const ROW_THRESHOLD: f32 = 0.5;
+const COLUMN_THRESHOLD: f32 = 0.1;
+
+/// Propose a spectrum window around the brightest horizontal band of the frame.
+///
+/// Returns `None` if no band stands out from the background.
+pub fn detect_spectrum_window(frame: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Option<SpectrumWindow> {
+    let (width, height) = frame.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let brightness = |x: u32, y: u32| {
+        let p = frame.get_pixel(x, y);
+        (p[0] as f32 + p[1] as f32 + p[2] as f32) / 3.
+    };
+
+    let rows: Vec<f32> = (0..height)
+        .map(|y| (0..width).map(|x| brightness(x, y)).sum::<f32>() / width as f32)
+        .collect();
+    let (top, bottom) = bright_range(&rows, ROW_THRESHOLD)?;
+
+    let columns: Vec<f32> = (0..width)
+        .map(|x| {
+            (top..=bottom).map(|y| brightness(x, y as u32)).sum::<f32>() / (bottom - top + 1) as f32
+        })
+        .collect();
+    let (left, right) = bright_range(&columns, COLUMN_THRESHOLD)?;
+
+    Some(SpectrumWindow {
+        offset: Vec2::new(left as f32, top as f32),
+        size: Vec2::new((right - left + 1) as f32, (bottom - top + 1) as f32),
+    })
+}
+
+/// Contiguous range around the maximum of `profile` which exceeds the threshold.
+fn bright_range(profile: &[f32], threshold: f32) -> Option<(usize, usize)> {
+    let (peak, max) = profile
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    let mut sorted = profile.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    // Low percentile, as the spectrum may cover most of the columns
+    let background = sorted[sorted.len() / 10];
+    if max - background < MIN_CONTRAST {
+        return None;
+    }
+
+    let limit = background + threshold * (max - background);
+    let start = profile[..peak]
+        .iter()
+        .rposition(|v| *v < limit)
+        .map_or(0, |i| i + 1);
+    let end = profile[peak..]
+        .iter()
+        .position(|v| *v < limit)
+        .map_or(profile.len() - 1, |i| peak + i - 1);
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_window() {
+        let frame = ImageBuffer::from_fn(200, 100, |x, y| {
+            if (40..=43).contains(&y) && (20..180).contains(&x) {
+                Rgb([200, 150, 100])
+            } else {
+                Rgb([10, 10, 10])
+            }
+        });
+        assert_eq!(
+            detect_spectrum_window(&frame),
+            Some(SpectrumWindow {
+                offset: Vec2::new(20., 40.),
+                size: Vec2::new(160., 4.),
+            })
+        );
+
+        let dark = ImageBuffer::from_pixel(200, 100, Rgb([10, 10, 10]));
+        assert_eq!(detect_spectrum_window(&dark), None);
+    }
+}